@@ -45,6 +45,29 @@ fn criterion_benchmark(c: &mut Criterion) {
                 audio_codec_algorithms::encode_adpcm_ima(black_box(i), &mut state));
         }
     }));
+
+    // adpcm ima blocks
+    let mut ima4_block = [0u8; 34];
+    for (i, b) in ima4_block.iter_mut().enumerate() {
+        *b = (i * 37) as u8;
+    }
+    c.bench_function("decode_adpcm_ima_ima4", |b| b.iter(|| {
+        let mut state = audio_codec_algorithms::AdpcmImaState::new();
+        let mut samples = [0i16; 64];
+        audio_codec_algorithms::decode_adpcm_ima_ima4(black_box(&ima4_block), &mut state,
+            &mut samples);
+        black_box(samples);
+    }));
+    let mut ms_block = [0u8; 2048];
+    for (i, b) in ms_block.iter_mut().enumerate() {
+        *b = (i * 37) as u8;
+    }
+    c.bench_function("decode_adpcm_ima_ms", |b| b.iter(|| {
+        let mut samples = [0i16; 4082];
+        let _ = audio_codec_algorithms::decode_adpcm_ima_ms(black_box(&ms_block), true,
+            &mut samples);
+        black_box(samples);
+    }));
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,111 @@
+//! Deterministic instruction count benchmarks, which run the codecs under Valgrind Callgrind.
+//!
+//! Each benchmark processes a fixed number of samples (see the benchmark names), so
+//! the instructions per sample is the instruction count divided by the number of samples.
+//!
+//! Requires Valgrind and `cargo install iai-callgrind-runner --version 0.16.1`. Run with:
+//! `cargo bench --bench instruction_count` in the `benches/instruction-count` directory
+
+use std::hint::black_box;
+use iai_callgrind::{library_benchmark, library_benchmark_group, main};
+use audio_codec_algorithms::AdpcmImaState;
+
+// deterministic pseudo-random test input
+fn test_samples<const N: usize>() -> [i16; N] {
+    let mut samples = [0i16; N];
+    let mut value: u32 = 1;
+    for s in samples.iter_mut() {
+        value = value.wrapping_mul(1664525).wrapping_add(1013904223);
+        *s = (value >> 16) as i16 / 4;
+    }
+    samples
+}
+
+fn test_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    for (b, s) in bytes.iter_mut().zip(test_samples::<N>()) {
+        *b = s as u8;
+    }
+    bytes
+}
+
+fn ms_stereo_block() -> [u8; 2048] {
+    let mut buf = test_bytes::<2048>();
+    // valid step indexes
+    buf[2] = 40;
+    buf[6] = 40;
+    buf
+}
+
+#[library_benchmark]
+#[bench::samples_1024(test_bytes::<1024>())]
+fn decode_alaw(buf: [u8; 1024]) -> [i16; 1024] {
+    black_box(buf.map(audio_codec_algorithms::decode_alaw))
+}
+
+#[library_benchmark]
+#[bench::samples_1024(test_samples::<1024>())]
+fn encode_alaw(samples: [i16; 1024]) -> [u8; 1024] {
+    black_box(samples.map(audio_codec_algorithms::encode_alaw))
+}
+
+#[library_benchmark]
+#[bench::samples_1024(test_bytes::<1024>())]
+fn decode_ulaw(buf: [u8; 1024]) -> [i16; 1024] {
+    black_box(buf.map(audio_codec_algorithms::decode_ulaw))
+}
+
+#[library_benchmark]
+#[bench::samples_1024(test_samples::<1024>())]
+fn encode_ulaw(samples: [i16; 1024]) -> [u8; 1024] {
+    black_box(samples.map(audio_codec_algorithms::encode_ulaw))
+}
+
+#[library_benchmark]
+#[bench::samples_64(test_bytes::<34>())]
+fn decode_adpcm_ima_ima4(buf: [u8; 34]) -> [i16; 64] {
+    let mut state = AdpcmImaState::new();
+    let mut samples = [0i16; 64];
+    audio_codec_algorithms::decode_adpcm_ima_ima4(black_box(&buf), &mut state, &mut samples);
+    black_box(samples)
+}
+
+#[library_benchmark]
+#[bench::samples_64(test_samples::<64>())]
+fn encode_adpcm_ima_ima4(samples: [i16; 64]) -> [u8; 34] {
+    let mut state = AdpcmImaState::new();
+    let mut buf = [0u8; 34];
+    audio_codec_algorithms::encode_adpcm_ima_ima4(black_box(&samples), &mut state, &mut buf);
+    black_box(buf)
+}
+
+#[library_benchmark]
+#[bench::samples_4082(ms_stereo_block())]
+fn decode_adpcm_ima_ms(buf: [u8; 2048]) -> [i16; 4082] {
+    let mut samples = [0i16; 4082];
+    let _ = audio_codec_algorithms::decode_adpcm_ima_ms(black_box(&buf), true, &mut samples);
+    black_box(samples)
+}
+
+#[library_benchmark]
+#[bench::samples_4082(test_samples::<4082>())]
+fn encode_adpcm_ima_ms(samples: [i16; 4082]) -> [u8; 2048] {
+    let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+    let mut buf = [0u8; 2048];
+    let _ = audio_codec_algorithms::encode_adpcm_ima_ms(black_box(&samples), &mut states,
+        &mut buf);
+    black_box(buf)
+}
+
+library_benchmark_group!(
+    name = g711;
+    benchmarks = decode_alaw, encode_alaw, decode_ulaw, encode_ulaw
+);
+
+library_benchmark_group!(
+    name = adpcm_ima;
+    benchmarks = decode_adpcm_ima_ima4, encode_adpcm_ima_ima4, decode_adpcm_ima_ms,
+        encode_adpcm_ima_ms
+);
+
+main!(library_benchmark_groups = g711, adpcm_ima);
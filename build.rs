@@ -0,0 +1,12 @@
+fn main() {
+    // the C reference implementations are only built for differential testing
+    #[cfg(feature = "c-reference")]
+    {
+        println!("cargo:rerun-if-changed=test-files/c-reference");
+        cc::Build::new()
+            .file("test-files/c-reference/g711.c")
+            .file("test-files/c-reference/g72x.c")
+            .file("test-files/c-reference/ima_adpcm.c")
+            .compile("c_reference");
+    }
+}
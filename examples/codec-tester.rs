@@ -2,17 +2,35 @@
 
 Example to encode or decode single values given as command line arguments.
 
+It can also analyze IMA ADPCM streams: print block headers, step index trajectories and
+anomalies, such as out-of-range step indexes and nonzero reserved bytes.
+
+It can also decode a file and print a fingerprint of the decoded samples for regression
+checking.
+
 */
 
 use std::env;
 use std::process::ExitCode;
+use audio_codec_algorithms::{AdpcmImaState, AudioFingerprint, Ima4Block, MsImaBlock,
+    decode_adpcm_ima};
 
 fn main() -> ExitCode {
     let args: Vec<String> = env::args().collect();
     if args.len() < 3 {
         println!("Usage: codec-tester {{decode|encode}} {{ulaw|alaw|adpcm_ima}} values...");
+        println!("       codec-tester analyze ima4 file");
+        println!("       codec-tester analyze ms_ima file block_size {{1|2}}");
+        println!("       codec-tester fingerprint {{ulaw|alaw|ima4}} file");
+        println!("       codec-tester fingerprint ms_ima file block_size {{1|2}}");
         return ExitCode::FAILURE;
     }
+    if args[1] == "analyze" {
+        return analyze(&args[2..]);
+    }
+    if args[1] == "fingerprint" {
+        return fingerprint(&args[2..]);
+    }
 
     let mut adpcm_state = audio_codec_algorithms::AdpcmImaState::new();
     let command = &args[1];
@@ -51,3 +69,229 @@ fn main() -> ExitCode {
     }
     ExitCode::SUCCESS
 }
+
+/// Step index statistics for one channel of a block.
+struct StepTrajectory {
+    min: u8,
+    max: u8,
+    end: u8,
+    clipped_samples: usize,
+}
+
+/// Decodes nibbles starting from `state` and returns the step index trajectory.
+fn trajectory(nibbles: impl Iterator<Item = u8>, state: &mut AdpcmImaState) -> StepTrajectory {
+    let mut t = StepTrajectory {
+        min: state.step_index,
+        max: state.step_index,
+        end: state.step_index,
+        clipped_samples: 0,
+    };
+    for nibble in nibbles {
+        let sample = decode_adpcm_ima(nibble, state);
+        if sample == i16::MIN || sample == i16::MAX {
+            t.clipped_samples += 1;
+        }
+        t.min = t.min.min(state.step_index);
+        t.max = t.max.max(state.step_index);
+    }
+    t.end = state.step_index;
+    t
+}
+
+fn print_channel(block_index: usize, offset: usize, ch: usize, predictor: i16, step_index: u8,
+    t: &StepTrajectory, anomalies: &[String]) {
+    let anomalies = if anomalies.is_empty() {
+        String::new()
+    } else {
+        format!("  !! {}", anomalies.join(", "))
+    };
+    println!("block {:5} offset 0x{:08x} ch {}: predictor {:6} step {:3} -> steps {:2}..={:2} \
+        end {:2}{}", block_index, offset, ch, predictor, step_index, t.min, t.max, t.end,
+        anomalies);
+}
+
+/// Analyzes a raw IMA ADPCM stream (blocks without a container) and prints block details.
+fn analyze(args: &[String]) -> ExitCode {
+    if args.len() < 2 {
+        eprintln!("ERROR: missing format or file");
+        return ExitCode::FAILURE;
+    }
+    let data = match std::fs::read(&args[1]) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("ERROR: can't read {}: {}", args[1], e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut anomaly_count = 0;
+    match args[0].as_ref() {
+        "ima4" => {
+            let mut previous_end: Option<AdpcmImaState> = None;
+            for (block_index, buf) in data.chunks(34).enumerate() {
+                let Ok(block) = Ima4Block::from_slice(buf) else {
+                    println!("block {:5}: !! truncated block, {} bytes", block_index, buf.len());
+                    anomaly_count += 1;
+                    break;
+                };
+                let mut anomalies = Vec::new();
+                if block.step_index() > 88 {
+                    anomalies.push(format!("step index {} > 88", block.step_index()));
+                }
+                // the decoder keeps its predictor if it is close to the header predictor
+                if let Some(prev) = &previous_end {
+                    if prev.step_index != block.step_index().min(88) ||
+                        (i32::from(prev.predictor) - i32::from(block.predictor())).abs() > 127 {
+                        anomalies.push(format!("discontinuity: previous block ended at {} step {}",
+                            prev.predictor, prev.step_index));
+                    }
+                }
+                let mut state = AdpcmImaState {
+                    predictor: block.predictor(),
+                    step_index: block.step_index().min(88),
+                };
+                let t = trajectory(block.nibbles(), &mut state);
+                if t.clipped_samples > 0 {
+                    anomalies.push(format!("{} clipped samples", t.clipped_samples));
+                }
+                anomaly_count += anomalies.len();
+                print_channel(block_index, block_index * 34, 0, block.predictor(),
+                    block.step_index(), &t, &anomalies);
+                previous_end = Some(state);
+            }
+        },
+        "ms_ima" => {
+            if args.len() < 4 {
+                eprintln!("ERROR: missing block size or channel count");
+                return ExitCode::FAILURE;
+            }
+            let block_size = args[2].parse::<usize>().expect("bad block size");
+            let channels = args[3].parse::<usize>().expect("bad channel count");
+            if channels != 1 && channels != 2 {
+                eprintln!("ERROR: invalid channel count {}, must be 1 or 2", channels);
+                return ExitCode::FAILURE;
+            }
+            if block_size == 0 {
+                eprintln!("ERROR: invalid block size");
+                return ExitCode::FAILURE;
+            }
+            for (block_index, buf) in data.chunks(block_size).enumerate() {
+                let Ok(block) = MsImaBlock::new(buf, channels == 2) else {
+                    println!("block {:5}: !! invalid block, {} bytes", block_index, buf.len());
+                    anomaly_count += 1;
+                    continue;
+                };
+                for ch in 0..block.channels() {
+                    let predictor = block.predictor(ch).expect("valid channel");
+                    let step_index = block.step_index(ch).expect("valid channel");
+                    let mut anomalies = Vec::new();
+                    if step_index > 88 {
+                        anomalies.push(format!("step index {} > 88", step_index));
+                    }
+                    let reserved = block.as_bytes()[ch*4+3];
+                    if reserved != 0 {
+                        anomalies.push(format!("reserved byte 0x{:02x}", reserved));
+                    }
+                    let mut state = AdpcmImaState { predictor, step_index: step_index.min(88) };
+                    let t = trajectory(block.nibbles(ch).expect("valid channel"), &mut state);
+                    if t.clipped_samples > 0 {
+                        anomalies.push(format!("{} clipped samples", t.clipped_samples));
+                    }
+                    anomaly_count += anomalies.len();
+                    print_channel(block_index, block_index * block_size, ch, predictor, step_index,
+                        &t, &anomalies);
+                }
+                if buf.len() < block_size {
+                    println!("block {:5}: !! short last block, {} bytes", block_index, buf.len());
+                    anomaly_count += 1;
+                }
+            }
+        },
+        _ => {
+            eprintln!("ERROR: invalid analyze format: {}", args[0]);
+            return ExitCode::FAILURE;
+        }
+    }
+    println!("{} anomalies", anomaly_count);
+    ExitCode::SUCCESS
+}
+
+/// Decodes a file and prints the fingerprint of the decoded samples.
+fn fingerprint(args: &[String]) -> ExitCode {
+    if args.len() < 2 {
+        eprintln!("ERROR: missing format or file");
+        return ExitCode::FAILURE;
+    }
+    let data = match std::fs::read(&args[1]) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("ERROR: can't read {}: {}", args[1], e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut fingerprint = AudioFingerprint::new();
+    match args[0].as_ref() {
+        "ulaw" => {
+            let samples: Vec<i16> = data.iter()
+                .map(|b| audio_codec_algorithms::decode_ulaw(*b)).collect();
+            fingerprint.update(&samples);
+        },
+        "alaw" => {
+            let samples: Vec<i16> = data.iter()
+                .map(|b| audio_codec_algorithms::decode_alaw(*b)).collect();
+            fingerprint.update(&samples);
+        },
+        "ima4" => {
+            // a truncated file must not give the same fingerprint as the complete blocks
+            let leftover = data.len() % 34;
+            if leftover != 0 {
+                eprintln!("ERROR: {} leftover bytes after the last complete 34 byte block",
+                    leftover);
+                return ExitCode::FAILURE;
+            }
+            let mut state = AdpcmImaState::new();
+            let mut samples = [0i16; 64];
+            for buf in data.chunks_exact(34) {
+                let buf = buf.try_into().expect("34 byte block");
+                audio_codec_algorithms::decode_adpcm_ima_ima4(buf, &mut state, &mut samples);
+                fingerprint.update(&samples);
+            }
+        },
+        "ms_ima" => {
+            if args.len() < 4 {
+                eprintln!("ERROR: missing block size or channel count");
+                return ExitCode::FAILURE;
+            }
+            let block_size = args[2].parse::<usize>().expect("bad block size");
+            let channels = args[3].parse::<usize>().expect("bad channel count");
+            if channels != 1 && channels != 2 {
+                eprintln!("ERROR: invalid channel count {}, must be 1 or 2", channels);
+                return ExitCode::FAILURE;
+            }
+            if block_size < 4 * channels {
+                eprintln!("ERROR: invalid block size");
+                return ExitCode::FAILURE;
+            }
+            let leftover = data.len() % block_size;
+            if leftover != 0 {
+                eprintln!("ERROR: {} leftover bytes after the last complete {} byte block",
+                    leftover, block_size);
+                return ExitCode::FAILURE;
+            }
+            let mut samples = vec![0i16; ((block_size / channels - 4) * 2 + 1) * channels];
+            for (block_index, buf) in data.chunks_exact(block_size).enumerate() {
+                if audio_codec_algorithms::decode_adpcm_ima_ms(buf, channels == 2, &mut samples)
+                    .is_err() {
+                    eprintln!("ERROR: can't decode block {}", block_index);
+                    return ExitCode::FAILURE;
+                }
+                fingerprint.update(&samples);
+            }
+        },
+        _ => {
+            eprintln!("ERROR: invalid fingerprint format: {}", args[0]);
+            return ExitCode::FAILURE;
+        }
+    }
+    println!("{}", fingerprint);
+    ExitCode::SUCCESS
+}
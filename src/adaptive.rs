@@ -0,0 +1,184 @@
+
+use crate::Error;
+use crate::AdpcmImaState;
+use crate::{AdpcmImaBits, decode_adpcm_ima_bits, encode_adpcm_ima_bits};
+use crate::bitreader::{BitReader, BitWriter};
+
+/// IMA ADPCM encoder, which selects the code size for each block to reach a target
+/// average bitrate.
+///
+/// Each block is encoded with 2, 3, 4 or 5 bits per sample. The encoder keeps count of
+/// the used bits and selects the code size with the smallest error, which fits in
+/// the remaining bit budget. The selected code size must be stored in a side channel
+/// (it fits in 2 bits), because the decoder needs it to decode the block with
+/// [`decode_adpcm_ima_adaptive_block()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptiveAdpcmImaEncoder {
+    state: AdpcmImaState,
+    target_bits_per_sample: f64,
+    used_bits: u64,
+    sample_count: u64,
+}
+
+impl AdaptiveAdpcmImaEncoder {
+    /// Creates a new encoder with the given target average bits per sample.
+    /// The target is clamped to 2.0..=5.0.
+    ///
+    /// For instance, a target bitrate of 24000 bits/s for 8000 Hz audio is 3 bits per sample.
+    pub fn new(target_bits_per_sample: f64) -> AdaptiveAdpcmImaEncoder {
+        AdaptiveAdpcmImaEncoder {
+            state: AdpcmImaState::new(),
+            target_bits_per_sample: target_bits_per_sample.clamp(2.0, 5.0),
+            used_bits: 0,
+            sample_count: 0,
+        }
+    }
+
+    /// Returns the current encoder state.
+    pub fn state(&self) -> &AdpcmImaState {
+        &self.state
+    }
+
+    /// Returns the average bits per sample of the blocks encoded so far,
+    /// or 0 if nothing has been encoded.
+    pub fn average_bits_per_sample(&self) -> f64 {
+        if self.sample_count == 0 {
+            return 0.0;
+        }
+        self.used_bits as f64 / self.sample_count as f64
+    }
+
+    /// Encodes a block of 16-bit signed integer samples.
+    ///
+    /// The codes are written to `out_buf` most significant bit first. The `out_buf` length
+    /// must be at least `(5 * samples.len() + 7) / 8`, so that the block fits with any
+    /// code size.
+    ///
+    /// The selected code size and the number of bytes written to `out_buf` are returned.
+    ///
+    /// An error is returned if `samples` is empty or if `out_buf` is too small.
+    /// If an error is returned, the encoder and `out_buf` are left unmodified.
+    pub fn encode_block(&mut self, samples: &[i16], out_buf: &mut [u8])
+        -> Result<(AdpcmImaBits, usize), Error> {
+
+        if samples.is_empty() || out_buf.len() < (5 * samples.len()).div_ceil(8) {
+            return Err(Error::InvalidBufferSize);
+        }
+        let sample_len = samples.len() as u64;
+        let budget = self.target_bits_per_sample * (self.sample_count + sample_len) as f64 -
+            self.used_bits as f64;
+        // select the code size with the smallest squared error, which fits in the budget
+        let mut selected = AdpcmImaBits::Bits2;
+        let mut selected_error = u64::MAX;
+        for bits in AdpcmImaBits::ALL {
+            let block_bits = u64::from(bits.bits()) * sample_len;
+            if bits != AdpcmImaBits::Bits2 && block_bits as f64 > budget {
+                continue;
+            }
+            let mut state = self.state.clone();
+            let mut decoder_state = self.state.clone();
+            let mut error = 0u64;
+            for s in samples {
+                let code = encode_adpcm_ima_bits(*s, bits, &mut state);
+                let decoded = decode_adpcm_ima_bits(code, bits, &mut decoder_state);
+                let diff = u64::from((i32::from(*s) - i32::from(decoded)).unsigned_abs());
+                error = error.saturating_add(diff * diff);
+            }
+            if error < selected_error {
+                selected = bits;
+                selected_error = error;
+            }
+        }
+        let mut writer = BitWriter::new(out_buf);
+        for s in samples {
+            let code = encode_adpcm_ima_bits(*s, selected, &mut self.state);
+            writer.write(u32::from(code), u32::from(selected.bits()))
+                .ok_or(Error::InvalidBufferSize)?;
+        }
+        self.used_bits += u64::from(selected.bits()) * sample_len;
+        self.sample_count += sample_len;
+        Ok((selected, writer.byte_len()))
+    }
+}
+
+/// Decodes a block encoded by [`AdaptiveAdpcmImaEncoder`] to 16-bit signed integer samples.
+///
+/// `buf` should contain the codes of the block and `bits` is the code size returned by
+/// the encoder. The `state` parameter should be initialized to zero for the first call
+/// and subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs `out_samples.len()` samples, so the `out_samples` length must be
+/// the number of samples in the block.
+///
+/// An error is returned if `buf` is too short for `out_samples`.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_adpcm_ima_adaptive_block(buf: &[u8], bits: AdpcmImaBits,
+    state: &mut AdpcmImaState, out_samples: &mut [i16]) -> Result<(), Error> {
+
+    if buf.len() < (usize::from(bits.bits()) * out_samples.len()).div_ceil(8) {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut reader = BitReader::new(buf);
+    for out in out_samples {
+        let code = reader.read(u32::from(bits.bits())).unwrap_or(0);
+        #[allow(clippy::cast_possible_truncation)] // code has at most 5 bits
+        {
+        *out = decode_adpcm_ima_bits(code as u8, bits, state);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_block(index: usize, out: &mut [i16; 64]) {
+        // loud and quiet blocks alternate
+        let amplitude = if index % 3 == 0 { 12000 } else { 500 };
+        for (i, s) in out.iter_mut().enumerate() {
+            let level: i32 = [ 0, 7, 10, 7, 0, -7, -10, -7 ][(i + index) % 8];
+            *s = i16::try_from(level * amplitude / 10).unwrap_or(0);
+        }
+    }
+
+    #[test]
+    fn test_adaptive_adpcm_ima_encoder() {
+        for target in [ 2.0, 2.5, 3.0, 4.0, 5.0 ] {
+            let mut encoder = AdaptiveAdpcmImaEncoder::new(target);
+            let mut decoder_state = AdpcmImaState::new();
+            let mut samples = [0i16; 64];
+            let mut buf = [0u8; 40];
+            let mut decoded = [0i16; 64];
+            for index in 0..60 {
+                test_block(index, &mut samples);
+                let result = encoder.encode_block(&samples, &mut buf);
+                assert!(result.is_ok());
+                let Ok((bits, len)) = result else { return };
+                assert_eq!(len, usize::from(bits.bits()) * 8);
+                assert!(decode_adpcm_ima_adaptive_block(&buf[..len], bits, &mut decoder_state,
+                    &mut decoded).is_ok());
+                assert_eq!(&decoder_state, encoder.state());
+            }
+            let average = encoder.average_bits_per_sample();
+            assert!(average <= target && average > target - 0.1);
+        }
+    }
+
+    #[test]
+    fn test_adaptive_adpcm_ima_errors() {
+        let mut encoder = AdaptiveAdpcmImaEncoder::new(3.0);
+        let samples = [0i16; 16];
+        let mut buf = [0u8; 10];
+        assert!(matches!(encoder.encode_block(&samples, &mut buf[..9]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encoder.encode_block(&[], &mut buf), Err(Error::InvalidBufferSize)));
+        assert_eq!(encoder, AdaptiveAdpcmImaEncoder::new(3.0));
+        let mut state = AdpcmImaState::new();
+        let mut out = [0i16; 16];
+        assert!(matches!(decode_adpcm_ima_adaptive_block(&buf[..5], AdpcmImaBits::Bits3,
+            &mut state, &mut out), Err(Error::InvalidBufferSize)));
+        assert!(decode_adpcm_ima_adaptive_block(&buf[..6], AdpcmImaBits::Bits3,
+            &mut state, &mut out).is_ok());
+    }
+}
@@ -29,12 +29,12 @@ impl Default for AdpcmImaState {
     }
 }
 
-const IMA_INDEX_TABLE: &[i8; 16] = &[
+pub(crate) const IMA_INDEX_TABLE: &[i8; 16] = &[
     -1, -1, -1, -1, 2, 4, 6, 8,
     -1, -1, -1, -1, 2, 4, 6, 8
 ];
 
-const IMA_STEP_TABLE: &[i16; 89] = &[
+pub(crate) const IMA_STEP_TABLE: &[i16; 89] = &[
     7, 8, 9, 10, 11, 12, 13, 14, 16, 17,
     19, 21, 23, 25, 28, 31, 34, 37, 41, 45,
     50, 55, 60, 66, 73, 80, 88, 97, 107, 118,
@@ -56,51 +56,192 @@ const IMA_STEP_TABLE: &[i16; 89] = &[
 #[cfg_attr(feature = "internal-no-panic", no_panic)]
 #[inline(always)]
 pub fn decode_adpcm_ima(encoded_nibble: u8, state: &mut AdpcmImaState) -> i16 {
-    let nibble = encoded_nibble & 0x0f; // ensure nibble is in the range 0..=15
-    state.step_index = state.step_index.min(88); // validate step_index
+    // ValidatedImaState::load() validates step_index
+    let mut local = ValidatedImaState::load(state);
+    // ensure nibble is in the range 0..=15
+    let sample = local.decode(encoded_nibble & 0x0f);
+    local.store(state);
+    sample
+}
 
-    // calculate the sample value (predictor) from the previous predictor, step and nibble
-    let step_size = i32::from(IMA_STEP_TABLE[usize::from(state.step_index)]);
-    let mut diff = step_size >> 3;
-    if (nibble & 4) != 0 { diff += step_size; }
-    if (nibble & 2) != 0 { diff += step_size >> 1; }
-    if (nibble & 1) != 0 { diff += step_size >> 2; }
-    let mut predictor = i32::from(state.predictor);
-    if (nibble & 8) != 0 {
-        predictor -= diff;
-    } else {
-        predictor += diff;
+// IMA ADPCM state for the block encoders and decoders. The step index is validated once
+// when the state is loaded, so the per-nibble functions don't need to validate it again.
+// The predictor and step index are kept in local variables (registers) during the block.
+pub(crate) struct ValidatedImaState {
+    predictor: i32,
+    // always 0..=88
+    step_index: usize,
+}
+
+// the step table padded to 128 items, so that `step_index & 0x7f` can be used to index it
+// without bounds checks
+static IMA_STEP_TABLE_128: [i16; 128] = {
+    let mut table = [0; 128];
+    let mut i = 0;
+    while i < 89 {
+        table[i] = IMA_STEP_TABLE[i];
+        i += 1;
     }
-    // store predictor for the next round, clamped to i16
-    #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
-    {
-    state.predictor = predictor.clamp(-32768, 32767) as i16;
+    table
+};
+
+// Precalculated result of decoding a nibble (without the sign bit) at a step index: the
+// magnitude of the difference to the predictor and the next step index.
+#[derive(Clone, Copy)]
+struct ImaDecodeEntry {
+    diff: u16,
+    next_step_index: u8,
+}
+
+// decoding table for each step index and nibble (without the sign bit), so that
+// decode_byte() only needs one table load per nibble instead of the step table load,
+// the conditional additions and the step index table load. The table is padded to 128 rows,
+// so that `step_index & 0x7f` can be used to index it without bounds checks.
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+// all values are small positive numbers, so casts never truncate, wrap or lose the sign
+static IMA_DECODE_TABLE: [[ImaDecodeEntry; 8]; 128] = {
+    let mut table = [[ImaDecodeEntry { diff: 0, next_step_index: 0 }; 8]; 128];
+    let mut step_index = 0;
+    while step_index < 89 {
+        let step_size = IMA_STEP_TABLE[step_index] as u16;
+        let mut nibble = 0;
+        while nibble < 8 {
+            let mut diff = step_size >> 3;
+            if (nibble & 4) != 0 { diff += step_size; }
+            if (nibble & 2) != 0 { diff += step_size >> 1; }
+            if (nibble & 1) != 0 { diff += step_size >> 2; }
+            let next_step_index = step_index as i32 + IMA_INDEX_TABLE[nibble] as i32;
+            table[step_index][nibble] = ImaDecodeEntry {
+                diff,
+                next_step_index: if next_step_index < 0 {
+                    0
+                } else if next_step_index > 88 {
+                    88
+                } else {
+                    next_step_index as u8
+                },
+            };
+            nibble += 1;
+        }
+        step_index += 1;
+    }
+    table
+};
+
+impl ValidatedImaState {
+    #[cfg_attr(feature = "internal-no-panic", no_panic)]
+    #[inline(always)]
+    pub(crate) fn load(state: &AdpcmImaState) -> ValidatedImaState {
+        ValidatedImaState {
+            predictor: i32::from(state.predictor),
+            step_index: usize::from(state.step_index.min(88)),
+        }
+    }
+
+    #[cfg_attr(feature = "internal-no-panic", no_panic)]
+    #[inline(always)]
+    pub(crate) fn store(&self, state: &mut AdpcmImaState) {
+        #[allow(clippy::cast_possible_truncation)] // values are clamped so truncation never happens
+        {
+        state.predictor = self.predictor as i16;
+        state.step_index = self.step_index as u8;
+        }
+    }
+
+    #[cfg_attr(feature = "internal-no-panic", no_panic)]
+    #[inline(always)]
+    fn step_size(&self) -> i32 {
+        i32::from(IMA_STEP_TABLE_128[self.step_index & 0x7f])
     }
+
     // adjust step index, clamped to 0..=88
-    state.step_index = state.step_index
-        .saturating_add_signed(IMA_INDEX_TABLE[usize::from(nibble)])
-        .min(88);
-    // predictor is the decoded sample value
-    state.predictor
+    #[cfg_attr(feature = "internal-no-panic", no_panic)]
+    #[inline(always)]
+    fn adjust_step_index(&mut self, nibble: u8) {
+        self.step_index = self.step_index
+            .saturating_add_signed(isize::from(IMA_INDEX_TABLE[usize::from(nibble & 0x0f)]))
+            .min(88);
+    }
+
+    // nibble must be 0..=15
+    #[cfg_attr(feature = "internal-no-panic", no_panic)]
+    #[inline(always)]
+    pub(crate) fn decode(&mut self, nibble: u8) -> i16 {
+        // calculate the sample value (predictor) from the previous predictor, step and nibble
+        let step_size = self.step_size();
+        let mut diff = step_size >> 3;
+        if (nibble & 4) != 0 { diff += step_size; }
+        if (nibble & 2) != 0 { diff += step_size >> 1; }
+        if (nibble & 1) != 0 { diff += step_size >> 2; }
+        // the predictor was in the i16 range, so only one side needs to be clamped
+        if (nibble & 8) != 0 {
+            self.predictor = (self.predictor - diff).max(-32768);
+        } else {
+            self.predictor = (self.predictor + diff).min(32767);
+        }
+        self.adjust_step_index(nibble);
+        // predictor is the decoded sample value
+        #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+        {
+        self.predictor as i16
+        }
+    }
+
+    // decodes both nibbles of a byte, the low nibble first. This is the same as calling
+    // decode() for both nibbles, but the predictor and step index stay in local variables and
+    // each nibble is decoded with a single IMA_DECODE_TABLE load.
+    #[cfg_attr(feature = "internal-no-panic", no_panic)]
+    #[inline(always)]
+    pub(crate) fn decode_byte(&mut self, b: u8) -> [i16; 2] {
+        let mut predictor = self.predictor;
+        let mut step_index = self.step_index;
+        let mut out = [0i16; 2];
+        for (o, nibble) in out.iter_mut().zip([ b & 0x0f, b >> 4 ]) {
+            let entry = IMA_DECODE_TABLE[step_index & 0x7f][usize::from(nibble & 7)];
+            // the predictor was in the i16 range, so only one side needs to be clamped
+            predictor = if (nibble & 8) != 0 {
+                (predictor - i32::from(entry.diff)).max(-32768)
+            } else {
+                (predictor + i32::from(entry.diff)).min(32767)
+            };
+            step_index = usize::from(entry.next_step_index);
+            #[allow(clippy::cast_possible_truncation)] // value is clamped, so no truncation
+            {
+            *o = predictor as i16;
+            }
+        }
+        self.predictor = predictor;
+        self.step_index = step_index;
+        out
+    }
+
+    #[cfg_attr(feature = "internal-no-panic", no_panic)]
+    #[inline(always)]
+    pub(crate) fn encode(&mut self, sample_value: i16) -> u8 {
+        let (nibble, predictor) = encode_nibble(sample_value, self.predictor, self.step_size());
+        self.predictor = predictor;
+        self.adjust_step_index(nibble);
+        nibble
+    }
+
+    // encodes two samples to a byte, the first sample to the low nibble
+    #[cfg_attr(feature = "internal-no-panic", no_panic)]
+    #[inline(always)]
+    pub(crate) fn encode_byte(&mut self, sample0: i16, sample1: i16) -> u8 {
+        let nibble0 = self.encode(sample0);
+        let nibble1 = self.encode(sample1);
+        nibble1 << 4 | nibble0
+    }
 }
 
-/// Decodes an AIFF-C / QT "ima4" compressed block to 16-bit signed integer samples.
-///
-/// `buf` should contain 2 header bytes (predictor and step index) and 32 bytes of 4-bit samples.
-///
-/// The `state` parameter should be initialized to zero for the first call and subsequent calls
-/// should pass in the state values from the previous call.
-///
-/// This function outputs 64 decoded samples to `out_samples`.
+/// Updates `state` from the two header bytes of an AIFF-C / QT "ima4" block.
 #[cfg_attr(feature = "internal-no-panic", no_panic)]
 #[inline(always)]
-pub fn decode_adpcm_ima_ima4(buf: &[u8; 34], state: &mut AdpcmImaState,
-    out_samples: &mut [i16; 64]) {
-
+pub(crate) fn apply_ima4_header(header0: u8, header1: u8, state: &mut AdpcmImaState) {
     // the first two bytes are the initial state: pppppppp piiiiiii
-    let predictor = i16::from_be_bytes([ buf[0], buf[1] & 0b1000_0000 ]);
+    let predictor = i16::from_be_bytes([ header0, header1 & 0b1000_0000 ]);
     // clamp to the same range as macOS AudioToolbox Framework (0..=88)
-    let step_index = (buf[1] & 0b0111_1111).min(88);
+    let step_index = (header1 & 0b0111_1111).min(88);
     // use the previous block's last sample value as the predictor instead of
     // block header's predictor, if the last sample value is close enough the block header's
     // predictor - this increases the decoding accuracy.
@@ -112,16 +253,94 @@ pub fn decode_adpcm_ima_ima4(buf: &[u8; 34], state: &mut AdpcmImaState,
         state.predictor = predictor;
         state.step_index = step_index;
     }
+}
+
+// Checks the length of a MS IMA ADPCM block with `channels` channels and returns the number of
+// samples in the block, counting all channels. All the MS IMA ADPCM block functions use this so
+// that they accept exactly the same block lengths.
+//
+// 1 channel blocks must be at least 4 bytes. Blocks with more channels must be at least
+// `4 * channels` bytes and divisible by `4 * channels`. Blocks must always be less than
+// 65536 bytes.
+pub(crate) fn ms_block_sample_len(buf_len: usize, channels: usize) -> Result<usize, Error> {
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    let header_len = channels.checked_mul(4).ok_or(Error::InvalidBufferSize)?;
+    if buf_len < header_len || (channels > 1 && buf_len % header_len != 0) ||
+        buf_len > 0xffff {
+        return Err(Error::InvalidBufferSize);
+    }
+    // buf_len is less than 65536, so this doesn't overflow
+    Ok((buf_len - header_len) * 2 + channels)
+}
+
+// Checks the number of channels and the samples length for the MS IMA ADPCM block encoders
+// and returns the length of the encoded block.
+//
+// Only 1 and 2 channels are supported. 1 channel blocks must have an odd number of samples and
+// 2 channel blocks must have 2 + 16 * n samples. The encoded block must be less than
+// 65536 bytes.
+pub(crate) fn ms_encoded_block_len(samples_len: usize, channels: usize)
+    -> Result<usize, Error> {
+
+    if channels < 1 || channels > 2 {
+        return Err(Error::InvalidChannels);
+    }
+    if (channels == 1 && samples_len & 1 == 0) ||
+        (channels == 2 && (samples_len < 2 || (samples_len - 2) % 16 != 0)) {
+        return Err(Error::InvalidBufferSize);
+    }
+    let buf_len = (samples_len - channels) / 2 + channels * 4;
+    if buf_len > 0xffff {
+        return Err(Error::InvalidBufferSize);
+    }
+    Ok(buf_len)
+}
+
+/// Decodes an AIFF-C / QT "ima4" compressed block to 16-bit signed integer samples.
+///
+/// `buf` should contain 2 header bytes (predictor and step index) and 32 bytes of 4-bit samples.
+///
+/// The `state` parameter should be initialized to zero for the first call and subsequent calls
+/// should pass in the state values from the previous call.
+///
+/// This function outputs 64 decoded samples to `out_samples`.
+#[cfg_attr(feature = "internal-no-panic", no_panic)]
+#[inline(always)]
+pub fn decode_adpcm_ima_ima4(buf: &[u8; 34], state: &mut AdpcmImaState,
+    out_samples: &mut [i16; 64]) {
+
+    apply_ima4_header(buf[0], buf[1], state);
     // decode the rest of the block as nibbles
-    let mut sample_index = 0;
-    for b in &buf[2..] {
-        let s0 = decode_adpcm_ima(*b & 0x0f, state);
-        out_samples[sample_index] = s0;
-        sample_index += 1;
-        let s1 = decode_adpcm_ima(*b >> 4, state);
-        out_samples[sample_index] = s1;
-        sample_index += 1;
+    let mut local = ValidatedImaState::load(state);
+    // constant bounds keep the loop free of panics even if it isn't inlined
+    for i in 0..32 {
+        let [ s0, s1 ] = local.decode_byte(buf[2 + i]);
+        out_samples[2 * i] = s0;
+        out_samples[2 * i + 1] = s1;
     }
+    local.store(state);
+}
+
+/// Decodes an AIFF-C / QT "ima4" compressed block to 16-bit signed integer samples.
+///
+/// This works like [`decode_adpcm_ima_ima4()`], but accepts slices. The `buf` length must be 34
+/// and the `out_samples` length must be 64.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_adpcm_ima_ima4_slice(buf: &[u8], state: &mut AdpcmImaState,
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    let Ok(buf) = buf.try_into() else {
+        return Err(Error::InvalidBufferSize);
+    };
+    let Ok(out_samples) = out_samples.try_into() else {
+        return Err(Error::InvalidBufferSize);
+    };
+    decode_adpcm_ima_ima4(buf, state, out_samples);
+    Ok(())
 }
 
 /// Decodes WAV / MS IMA ADPCM (wav format 0x0011) compressed block to
@@ -149,20 +368,8 @@ pub fn decode_adpcm_ima_ms(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
     } else {
         1
     };
-    // check buf length
-    if (channels == 1 && buf.len() < 4) ||
-        (channels == 2 && (buf.len() < 8 || buf.len() % 8 != 0)) {
-        return Err(Error::InvalidBufferSize);
-    }
-    if buf.len() > 0xffff {
-        return Err(crate::Error::InvalidBufferSize);
-    }
     // check that the length of the input buffer and output buffer match
-    let expected_sample_len = (buf.len() - 4 * channels)
-        .checked_mul(2)
-        .and_then(|v| v.checked_add(channels))
-        .ok_or(Error::InvalidBufferSize)?;
-    if expected_sample_len != out_samples.len() {
+    if ms_block_sample_len(buf.len(), channels)? != out_samples.len() {
         return Err(Error::InvalidBufferSize);
     }
     let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
@@ -176,14 +383,16 @@ pub fn decode_adpcm_ima_ms(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
         states[ch].step_index = buf[ch*4+2].min(88);
         out_samples[ch] = states[ch].predictor;
     }
+    let mut states = [ ValidatedImaState::load(&states[0]), ValidatedImaState::load(&states[1]) ];
     // decode the rest of the block from nibbles to interleaved samples
     let mut out_index = 0;
     let mut ch = 0;
     let mut out_subindex = 0;
     for b in &buf[4*channels..] {
         let pos = channels + out_index*4*channels*channels + out_subindex*channels + ch;
-        out_samples[pos] = decode_adpcm_ima(*b & 0x0f, &mut states[ch]);
-        out_samples[pos + channels] = decode_adpcm_ima(*b >> 4, &mut states[ch]);
+        let [ s0, s1 ] = states[ch].decode_byte(*b);
+        out_samples[pos] = s0;
+        out_samples[pos + channels] = s1;
         out_subindex += 2;
         if out_subindex == 4*channels {
             out_subindex = 0;
@@ -197,6 +406,169 @@ pub fn decode_adpcm_ima_ms(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
     Ok(())
 }
 
+/// Decodes WAV / MS IMA ADPCM (wav format 0x0011) compressed block with any number of
+/// channels to 16-bit signed integer samples.
+///
+/// This works like [`decode_adpcm_ima_ms()`], but takes the number of channels instead of
+/// `is_stereo`. `buf` should contain 4 header bytes (predictor and step index) for each
+/// channel followed by bytes of 4-bit encoded samples, which are interleaved in groups of
+/// 4 bytes (8 samples) per channel. For 1 channel audio, the `buf` length must be at least 4.
+/// For more channels, the `buf` length must be at least `4 * channels` and it must be
+/// divisible by `4 * channels`. The `buf` length must always be less than 65536.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * (buf.len() - 4 * channels) + channels`. Samples are interleaved for multichannel audio.
+///
+/// An error is returned if `channels` is zero or if the `buf` or `out_samples` length isn't
+/// correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ima_ms_multichannel(buf: &[u8], channels: usize, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    // check that the length of the input buffer and output buffer match
+    if ms_block_sample_len(buf.len(), channels)? != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    if channels == 1 {
+        return decode_adpcm_ima_ms(buf, false, out_samples);
+    }
+    let group_len = channels * 4;
+    let (header, data) = buf.split_at(group_len);
+    // decode one channel at a time so that only one state is needed
+    for (ch, h) in header.chunks_exact(4).enumerate() {
+        let state = AdpcmImaState {
+            predictor: i16::from_le_bytes([ h[0], h[1] ]),
+            // clamp step index to 0..=88 like decode_adpcm_ima_ms() so that something is decoded
+            step_index: h[2].min(88),
+        };
+        out_samples[ch] = state.predictor;
+        let mut state = ValidatedImaState::load(&state);
+        for (g, group) in data.chunks_exact(group_len).enumerate() {
+            for (i, b) in group[ch*4..ch*4+4].iter().enumerate() {
+                let pos = channels + g*8*channels + 2*i*channels + ch;
+                let [ s0, s1 ] = state.decode_byte(*b);
+                out_samples[pos] = s0;
+                out_samples[pos + channels] = s1;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decodes WAV / MS IMA ADPCM (wav format 0x0011) compressed block to planar
+/// 16-bit signed integer samples.
+///
+/// This works like [`decode_adpcm_ima_ms_multichannel()`], but instead of interleaving
+/// the samples, each channel is written to its own slice in `out_channels`. The number of
+/// channels is the `out_channels` length.
+///
+/// The length of each slice in `out_channels` must be `2 * (buf.len() / channels - 4) + 1`.
+///
+/// An error is returned if `out_channels` is empty or if the `buf` or `out_channels` slice
+/// lengths aren't correct.
+/// If an error is returned, `out_channels` are left unmodified.
+pub fn decode_adpcm_ima_ms_planar(buf: &[u8], out_channels: &mut [&mut [i16]])
+    -> Result<(), Error> {
+
+    let channels = out_channels.len();
+    // check that the length of the input buffer and output buffers match
+    let expected_sample_len = ms_block_sample_len(buf.len(), channels)? / channels;
+    if out_channels.iter().any(|out| out.len() != expected_sample_len) {
+        return Err(Error::InvalidBufferSize);
+    }
+    let group_len = channels * 4;
+    let (header, data) = buf.split_at(group_len);
+    for (ch, (h, out)) in header.chunks_exact(4).zip(out_channels.iter_mut()).enumerate() {
+        let state = AdpcmImaState {
+            predictor: i16::from_le_bytes([ h[0], h[1] ]),
+            // clamp step index to 0..=88 like decode_adpcm_ima_ms() so that something is decoded
+            step_index: h[2].min(88),
+        };
+        out[0] = state.predictor;
+        let mut state = ValidatedImaState::load(&state);
+        // each channel has 4 bytes in each group, except for 1 channel audio, where
+        // the last group may be shorter
+        let bytes = data.chunks(group_len).flat_map(|group| group.iter().skip(ch*4).take(4));
+        for (b, out) in bytes.zip(out[1..].chunks_exact_mut(2)) {
+            let [ s0, s1 ] = state.decode_byte(*b);
+            out[0] = s0;
+            out[1] = s1;
+        }
+    }
+    Ok(())
+}
+
+/// Decodes an AIFF-C / QT "ima4" compressed block and passes the samples to a callback.
+///
+/// This works like [`decode_adpcm_ima_ima4()`], but instead of writing to an output buffer,
+/// `sample_sink` is called for each of the 64 decoded samples in order.
+///
+/// The `state` parameter should be initialized to zero for the first call and subsequent calls
+/// should pass in the state values from the previous call.
+#[cfg_attr(feature = "internal-no-panic", no_panic)]
+#[inline(always)]
+pub fn decode_adpcm_ima_ima4_with(buf: &[u8; 34], state: &mut AdpcmImaState,
+    sample_sink: &mut impl FnMut(i16)) {
+
+    apply_ima4_header(buf[0], buf[1], state);
+    let mut local = ValidatedImaState::load(state);
+    // constant bounds keep the loop free of panics even if it isn't inlined
+    for i in 0..32 {
+        let [ s0, s1 ] = local.decode_byte(buf[2 + i]);
+        sample_sink(s0);
+        sample_sink(s1);
+    }
+    local.store(state);
+}
+
+/// Decodes WAV / MS IMA ADPCM (wav format 0x0011) compressed block and passes the samples
+/// to a callback.
+///
+/// This works like [`decode_adpcm_ima_ms()`], but instead of writing to an output buffer,
+/// `sample_sink` is called for each decoded sample. Samples are passed in interleaved order
+/// for 2 channel audio.
+///
+/// An error is returned if the `buf` length isn't correct.
+/// If an error is returned, `sample_sink` is not called.
+pub fn decode_adpcm_ima_ms_with(buf: &[u8], is_stereo: bool, sample_sink: &mut impl FnMut(i16))
+    -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    // check buf length
+    ms_block_sample_len(buf.len(), channels)?;
+    let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+    // the first channels*4 bytes are the initial state (every fourth byte is ignored)
+    for ch in 0..channels {
+        states[ch].predictor = i16::from_le_bytes([ buf[ch*4], buf[ch*4+1] ]);
+        states[ch].step_index = buf[ch*4+2].min(88);
+        sample_sink(states[ch].predictor);
+    }
+    let mut states = [ ValidatedImaState::load(&states[0]), ValidatedImaState::load(&states[1]) ];
+    // each channel has 4 bytes (8 samples) in turn, so decode them in parallel
+    // to output interleaved samples
+    for group in buf[4*channels..].chunks_exact(4*channels) {
+        for i in 0..8 {
+            for ch in 0..channels {
+                let b = group[ch*4 + i/2];
+                let nibble = if i % 2 == 0 { b & 0x0f } else { b >> 4 };
+                sample_sink(states[ch].decode(nibble));
+            }
+        }
+    }
+    // 1 channel blocks may end with a partial group
+    if channels == 1 {
+        for b in buf[4..].chunks_exact(4).remainder() {
+            sample_sink(states[0].decode(*b & 0x0f));
+            sample_sink(states[0].decode(*b >> 4));
+        }
+    }
+    Ok(())
+}
+
 /// Encodes a linear 16-bit signed integer sample value to a 4-bit encoded IMA ADPCM value.
 ///
 /// The `state` parameter should be initialized to zero or to values from the audio stream
@@ -207,9 +579,27 @@ pub fn decode_adpcm_ima_ms(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
 pub fn encode_adpcm_ima(sample_value: i16, state: &mut AdpcmImaState) -> u8 {
     state.step_index = state.step_index.min(88); // validate step_index
 
-    // calculate the output nibble using the sample value, previous predictor value and step
+    let step_size = i32::from(IMA_STEP_TABLE[usize::from(state.step_index)]);
+    let (nibble, predictor) = encode_nibble(sample_value, i32::from(state.predictor), step_size);
+    // store predictor for the next round, the value is already clamped to i16
+    #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+    {
+    state.predictor = predictor as i16;
+    }
+    // adjust step index, clamped to 0..=88
+    state.step_index = state.step_index
+        .saturating_add_signed(IMA_INDEX_TABLE[usize::from(nibble)])
+        .min(88);
+    // nibble is the encoded value
+    nibble
+}
 
-    let mut diff = i32::from(sample_value) - i32::from(state.predictor);
+// Calculates the output nibble and the new predictor using the sample value,
+// previous predictor value and step size. The returned predictor is clamped to i16.
+#[cfg_attr(feature = "internal-no-panic", no_panic)]
+#[inline(always)]
+fn encode_nibble(sample_value: i16, predictor: i32, step_size: i32) -> (u8, i32) {
+    let mut diff = i32::from(sample_value) - predictor;
     let mut nibble: u8;
     if diff >= 0 {
         nibble = 0;
@@ -219,7 +609,6 @@ pub fn encode_adpcm_ima(sample_value: i16, state: &mut AdpcmImaState) -> u8 {
     }
     // calculate nibble and predictor_diff
     // nibble bit 4, predictor_diff step_size
-    let step_size = i32::from(IMA_STEP_TABLE[usize::from(state.step_index)]);
     let mut predictor_diff: i32 = step_size >> 3;
     let mut temp_step_size = step_size;
     if diff >= temp_step_size {
@@ -240,25 +629,13 @@ pub fn encode_adpcm_ima(sample_value: i16, state: &mut AdpcmImaState) -> u8 {
         nibble |= 1;
         predictor_diff += step_size >> 2;
     }
-
     // update the predicted sample (predictor)
-    let mut predictor = i32::from(state.predictor);
-    if (nibble & 8) == 8 {
-        predictor -= predictor_diff;
+    let predictor = if (nibble & 8) == 8 {
+        predictor - predictor_diff
     } else {
-        predictor += predictor_diff;
-    }
-    // store predictor for the next round, clamped to i16
-    #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
-    {
-    state.predictor = predictor.clamp(-32768, 32767) as i16;
-    }
-    // adjust step index, clamped to 0..=88
-    state.step_index = state.step_index
-        .saturating_add_signed(IMA_INDEX_TABLE[usize::from(nibble)])
-        .min(88);
-    // nibble is the encoded value
-    nibble
+        predictor + predictor_diff
+    };
+    (nibble, predictor.clamp(-32768, 32767))
 }
 
 /// Encodes 16-bit signed integer samples to an AIFF-C / QT "ima4" compressed block.
@@ -281,14 +658,32 @@ pub fn encode_adpcm_ima_ima4(samples: &[i16; 64], state: &mut AdpcmImaState,
     out_buf[1] = (state.predictor & 0x80) as u8 | state.step_index;
     }
     // encode 64 samples to 64 nibbles (32 bytes)
-    let mut sample_index = 0;
-    for out_b in &mut out_buf[2..] {
-        let nibble0 = encode_adpcm_ima(samples[sample_index], state);
-        sample_index += 1;
-        let nibble1 = encode_adpcm_ima(samples[sample_index], state);
-        sample_index += 1;
-        *out_b = nibble1 << 4 | nibble0;
+    let mut local = ValidatedImaState::load(state);
+    // constant bounds keep the loop free of panics even if it isn't inlined
+    for i in 0..32 {
+        out_buf[2 + i] = local.encode_byte(samples[2 * i], samples[2 * i + 1]);
     }
+    local.store(state);
+}
+
+/// Encodes 16-bit signed integer samples to an AIFF-C / QT "ima4" compressed block.
+///
+/// This works like [`encode_adpcm_ima_ima4()`], but accepts slices. The `samples` length must
+/// be 64 and the `out_buf` length must be 34.
+///
+/// An error is returned if the `samples` or `out_buf` length isn't correct.
+/// If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_adpcm_ima_ima4_slice(samples: &[i16], state: &mut AdpcmImaState,
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    let Ok(samples) = samples.try_into() else {
+        return Err(Error::InvalidBufferSize);
+    };
+    let Ok(out_buf) = out_buf.try_into() else {
+        return Err(Error::InvalidBufferSize);
+    };
+    encode_adpcm_ima_ima4(samples, state, out_buf);
+    Ok(())
 }
 
 /// Encodes 16-bit signed integer samples to a MS / WAV IMA ADPCM (wav format 0x0011)
@@ -316,20 +711,8 @@ pub fn encode_adpcm_ima_ima4(samples: &[i16; 64], state: &mut AdpcmImaState,
 pub fn encode_adpcm_ima_ms(samples: &[i16], states: &mut [AdpcmImaState], out_buf: &mut [u8])
     -> Result<(), Error> {
     let channels = states.len();
-    if channels < 1 || channels > 2 {
-        return Err(crate::Error::InvalidChannels);
-    }
-    // check samples length
-    if (channels == 1 && samples.len() & 1 == 0) ||
-        (channels == 2 && (samples.len() < 2 || (samples.len()-2) % 16 != 0)) {
-        return Err(crate::Error::InvalidBufferSize);
-    }
-    // check buf length
-    if out_buf.len() > 0xffff {
-        return Err(crate::Error::InvalidBufferSize);
-    }
     // check that the length of the input buffer and output buffer match
-    if ((samples.len() - states.len()) / 2) + states.len()*4 != out_buf.len() {
+    if ms_encoded_block_len(samples.len(), channels)? != out_buf.len() {
         return Err(crate::Error::InvalidBufferSize);
     }
     // the first channels*4 bytes are the initial state (every fourth byte is ignored)
@@ -341,15 +724,18 @@ pub fn encode_adpcm_ima_ms(samples: &[i16], states: &mut [AdpcmImaState], out_bu
         out_buf[ch*4+2] = states[ch].step_index;
         out_buf[ch*4+3] = 0;
     }
+    // for 1 channel audio, the second item isn't used
+    let mut locals = [
+        ValidatedImaState::load(&states[0]),
+        ValidatedImaState::load(&states[channels - 1]),
+    ];
     // encode interleaved samples to nibbles
     let mut index = 0;
     let mut ch = 0;
     let mut subindex = 0;
     for b in &mut out_buf[channels*4..] {
         let pos = channels + index*4*channels*channels + subindex*channels + ch;
-        let s0 = encode_adpcm_ima(samples[pos], &mut states[ch]);
-        let s1 = encode_adpcm_ima(samples[pos+channels], &mut states[ch]);
-        *b = s0 | (s1 << 4);
+        *b = locals[ch].encode_byte(samples[pos], samples[pos+channels]);
         subindex += 2;
         if subindex == 4*channels {
             subindex = 0;
@@ -360,6 +746,9 @@ pub fn encode_adpcm_ima_ms(samples: &[i16], states: &mut [AdpcmImaState], out_bu
             }
         }
     }
+    for (local, state) in locals.iter().zip(states.iter_mut()) {
+        local.store(state);
+    }
     Ok(())
 }
 
@@ -405,6 +794,36 @@ mod tests {
         assert_eq!(state, AdpcmImaState { predictor: -20478, step_index: 87 });
     }
 
+    #[test]
+    fn test_validated_ima_state() {
+        // the block kernels must match decode_adpcm_ima() and encode_adpcm_ima()
+        for step_index in 0..=90 {
+            for predictor in (-32768..=32767).step_by(257) {
+                for b in 0..=255 {
+                    let mut state = AdpcmImaState { predictor, step_index };
+                    let mut expected_state = state.clone();
+                    let s0 = decode_adpcm_ima(b & 0x0f, &mut expected_state);
+                    let s1 = decode_adpcm_ima(b >> 4, &mut expected_state);
+                    let mut local = ValidatedImaState::load(&state);
+                    assert_eq!(local.decode_byte(b), [ s0, s1 ]);
+                    local.store(&mut state);
+                    assert_eq!(state, expected_state);
+
+                    let sample0 = i16::from(b) * 128 - 16384;
+                    let sample1 = predictor.wrapping_add(i16::from(b) * 3);
+                    let mut state = AdpcmImaState { predictor, step_index };
+                    let mut expected_state = state.clone();
+                    let n0 = encode_adpcm_ima(sample0, &mut expected_state);
+                    let n1 = encode_adpcm_ima(sample1, &mut expected_state);
+                    let mut local = ValidatedImaState::load(&state);
+                    assert_eq!(local.encode_byte(sample0, sample1), n1 << 4 | n0);
+                    local.store(&mut state);
+                    assert_eq!(state, expected_state);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_decode_adpcm_ima4() {
         // macOS 14 afconvert has been tested to return the same values
@@ -587,6 +1006,35 @@ mod tests {
         assert_eq!(state, AdpcmImaState { predictor: 31999, step_index: 57 });
     }
 
+    #[test]
+    fn test_decode_adpcm_ima4_slice() {
+        let block = [ 0x00, 0x00,
+            0x06, 0x08, 0x08, 0x08, 0xFF, 0xFF, 0xFF, 0xFF,
+            0xFF, 0x08, 0x80, 0x00, 0x80, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x01, 0x11, 0x11, 0x11, 0x22, 0x22,
+            0x32, 0x43, 0x33, 0x43, 0x43, 0x42, 0x32, 0x43
+        ];
+        let mut expected = [0i16; 64];
+        let mut expected_state = AdpcmImaState::new();
+        decode_adpcm_ima_ima4(&block, &mut expected_state, &mut expected);
+
+        let mut decoded_buf = [0i16; 64];
+        let mut state = AdpcmImaState::new();
+        assert!(decode_adpcm_ima_ima4_slice(&block, &mut state, &mut decoded_buf).is_ok());
+        assert_eq!(decoded_buf, expected);
+        assert_eq!(state, expected_state);
+
+        // invalid buf length
+        let mut state = AdpcmImaState::new();
+        assert!(matches!(decode_adpcm_ima_ima4_slice(&block[..33], &mut state, &mut decoded_buf),
+            Err(Error::InvalidBufferSize)));
+        assert_eq!(state, AdpcmImaState::new());
+
+        // invalid out_samples length
+        assert!(matches!(decode_adpcm_ima_ima4_slice(&block, &mut state, &mut decoded_buf[..63]),
+            Err(Error::InvalidBufferSize)));
+    }
+
     #[test]
     fn test_decode_adpcm_ms() {
         // Windows 10 acmStreamConvert() has been tested to return the same values
@@ -651,6 +1099,130 @@ mod tests {
         assert!(decode_adpcm_ima_ms(&[0u8; 2048], true, &mut samples).is_ok());
     }
 
+    #[test]
+    fn test_decode_adpcm_ms_multichannel() {
+        let stereo_buf = [ 0x38, 0xB1, 0x47, 0x00,
+            0x1A, 0x9B, 0x50, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x08, 0x00, 0x08
+        ];
+        let mut stereo_samples = [0i16; 18];
+        assert!(decode_adpcm_ima_ms(&stereo_buf, true, &mut stereo_samples).is_ok());
+
+        // one and two channels give the same results as decode_adpcm_ima_ms()
+        let mut samples = [0i16; 18];
+        assert!(decode_adpcm_ima_ms_multichannel(&stereo_buf, 2, &mut samples).is_ok());
+        assert_eq!(samples, stereo_samples);
+        let mut mono_samples = [0i16; 25];
+        assert!(decode_adpcm_ima_ms(&stereo_buf, false, &mut mono_samples).is_ok());
+        let mut samples = [0i16; 25];
+        assert!(decode_adpcm_ima_ms_multichannel(&stereo_buf, 1, &mut samples).is_ok());
+        assert_eq!(samples, mono_samples);
+
+        // four channels: channels 2 and 3 are copies of channels 0 and 1
+        let mut buf = [0u8; 32];
+        for (i, b) in buf.iter_mut().enumerate() {
+            // headers and data groups are both 4 bytes per channel
+            *b = stereo_buf[(i / 16) * 8 + i % 8];
+        }
+        let mut samples = [0i16; 36];
+        assert!(decode_adpcm_ima_ms_multichannel(&buf, 4, &mut samples).is_ok());
+        for (frame, stereo_frame) in samples.chunks_exact(4).zip(stereo_samples.chunks_exact(2)) {
+            assert_eq!(frame[0..2], *stereo_frame);
+            assert_eq!(frame[2..4], *stereo_frame);
+        }
+
+        // six channels and header only
+        let mut samples = [0i16; 6];
+        assert!(decode_adpcm_ima_ms_multichannel(&[0u8; 24], 6, &mut samples).is_ok());
+
+        // zero channels
+        assert!(matches!(decode_adpcm_ima_ms_multichannel(&buf, 0, &mut samples),
+            Err(Error::InvalidChannels)));
+        // invalid buf length
+        let mut samples = [0i16; 36];
+        assert!(matches!(decode_adpcm_ima_ms_multichannel(&buf[..28], 4, &mut samples),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_adpcm_ima_ms_multichannel(&buf[..8], 4, &mut samples),
+            Err(Error::InvalidBufferSize)));
+        // invalid out_samples length
+        assert!(matches!(decode_adpcm_ima_ms_multichannel(&buf, 4, &mut samples[..35]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_adpcm_ms_planar() {
+        let buf = [ 0x38, 0xB1, 0x47, 0x00,
+            0x1A, 0x9B, 0x50, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x08, 0x00, 0x08
+        ];
+        // two channels give the same samples as interleaved decoding
+        let mut interleaved = [0i16; 18];
+        assert!(decode_adpcm_ima_ms(&buf, true, &mut interleaved).is_ok());
+        let mut left = [0i16; 9];
+        let mut right = [0i16; 9];
+        assert!(decode_adpcm_ima_ms_planar(&buf, &mut [ &mut left, &mut right ]).is_ok());
+        for (i, frame) in interleaved.chunks_exact(2).enumerate() {
+            assert_eq!(frame, [ left[i], right[i] ]);
+        }
+
+        // one channel with a buf length which isn't divisible by 4
+        let mut mono = [0i16; 23];
+        assert!(decode_adpcm_ima_ms(&buf[..15], false, &mut mono).is_ok());
+        let mut out = [0i16; 23];
+        assert!(decode_adpcm_ima_ms_planar(&buf[..15], &mut [ &mut out ]).is_ok());
+        assert_eq!(out, mono);
+
+        // four channels
+        let buf = [0x11u8; 48];
+        let mut interleaved = [0i16; 68];
+        assert!(decode_adpcm_ima_ms_multichannel(&buf, 4, &mut interleaved).is_ok());
+        let mut planar = [[0i16; 17]; 4];
+        let [ a, b, c, d ] = &mut planar;
+        assert!(decode_adpcm_ima_ms_planar(&buf, &mut [ a, b, c, d ]).is_ok());
+        for (i, frame) in interleaved.chunks_exact(4).enumerate() {
+            assert_eq!(frame, [ planar[0][i], planar[1][i], planar[2][i], planar[3][i] ]);
+        }
+
+        // no channels
+        assert!(matches!(decode_adpcm_ima_ms_planar(&buf, &mut []),
+            Err(Error::InvalidChannels)));
+        // invalid buf length
+        let [ a, b, c, d ] = &mut planar;
+        assert!(matches!(decode_adpcm_ima_ms_planar(&buf[..44], &mut [ a, b, c, d ]),
+            Err(Error::InvalidBufferSize)));
+        // invalid output lengths
+        let [ a, b, c, d ] = &mut planar;
+        assert!(matches!(decode_adpcm_ima_ms_planar(&buf, &mut [ a, b, c, &mut d[..16] ]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_ms_block_len_validation() {
+        assert!(matches!(ms_block_sample_len(4, 1), Ok(1)));
+        assert!(matches!(ms_block_sample_len(7, 1), Ok(7)));
+        assert!(matches!(ms_block_sample_len(8, 2), Ok(2)));
+        assert!(matches!(ms_block_sample_len(2048, 2), Ok(4082)));
+        assert!(matches!(ms_block_sample_len(24, 3), Ok(27)));
+        assert!(matches!(ms_block_sample_len(0xffff, 1), Ok(131_063)));
+        assert!(matches!(ms_block_sample_len(3, 1), Err(Error::InvalidBufferSize)));
+        assert!(matches!(ms_block_sample_len(12, 2), Err(Error::InvalidBufferSize)));
+        assert!(matches!(ms_block_sample_len(0x10000, 1), Err(Error::InvalidBufferSize)));
+        assert!(matches!(ms_block_sample_len(8, 0), Err(Error::InvalidChannels)));
+
+        assert!(matches!(ms_encoded_block_len(1, 1), Ok(4)));
+        assert!(matches!(ms_encoded_block_len(25, 1), Ok(16)));
+        assert!(matches!(ms_encoded_block_len(2, 2), Ok(8)));
+        assert!(matches!(ms_encoded_block_len(18, 2), Ok(16)));
+        assert!(matches!(ms_encoded_block_len(24, 1), Err(Error::InvalidBufferSize)));
+        assert!(matches!(ms_encoded_block_len(17, 2), Err(Error::InvalidBufferSize)));
+        assert!(matches!(ms_encoded_block_len(131_065, 1), Err(Error::InvalidBufferSize)));
+        assert!(matches!(ms_encoded_block_len(3, 3), Err(Error::InvalidChannels)));
+        // the encoded blocks are accepted by the decoders
+        for (samples_len, channels) in [ (1, 1), (25, 1), (2, 2), (18, 2) ] {
+            let buf_len = ms_encoded_block_len(samples_len, channels).ok();
+            assert_eq!(buf_len.and_then(|len| ms_block_sample_len(len, channels).ok()),
+                Some(samples_len));
+        }
+    }
+
     #[test]
     fn test_decode_adpcm_ms_with_different_buf_sizes() {
         let buf_area = [0u8; 4096];
@@ -658,12 +1230,12 @@ mod tests {
         // one channel
         for buf_len in 0..=1025 {
             let buf = &buf_area[0..buf_len];
-            let sample_len = 2 * buf.len().max(4) - 7 * 1;
-            let mut samples = &mut sample_area[0..sample_len];
+            let sample_len = 2 * buf.len().max(4) - 7;
+            let samples = &mut sample_area[0..sample_len];
             if buf_len >= 4 {
-                assert!(decode_adpcm_ima_ms(&buf, false, &mut samples).is_ok());
+                assert!(decode_adpcm_ima_ms(buf, false, samples).is_ok());
             } else {
-                assert!(matches!(decode_adpcm_ima_ms(&buf, false, &mut samples),
+                assert!(matches!(decode_adpcm_ima_ms(buf, false, samples),
                     Err(Error::InvalidBufferSize)));
             }
         }
@@ -671,16 +1243,96 @@ mod tests {
         for buf_len in 0..=2049 {
             let buf = &buf_area[0..buf_len];
             let sample_len = 2 * buf.len().max(7) - 7 * 2;
-            let mut samples = &mut sample_area[0..sample_len];
+            let samples = &mut sample_area[0..sample_len];
             if buf_len >= 8 && buf_len % 8 == 0 {
-                assert!(decode_adpcm_ima_ms(&buf, true, &mut samples).is_ok());
+                assert!(decode_adpcm_ima_ms(buf, true, samples).is_ok());
             } else {
-                assert!(matches!(decode_adpcm_ima_ms(&buf, true, &mut samples),
+                assert!(matches!(decode_adpcm_ima_ms(buf, true, samples),
                     Err(Error::InvalidBufferSize)));
             }
         }
     }
 
+    #[test]
+    fn test_decode_adpcm_ima4_with() {
+        let block = [ 0x0C, 0xB1,
+            0x42, 0x32, 0x43, 0x42, 0x32, 0x43, 0x42, 0x32,
+            0x43, 0x42, 0x32, 0x43, 0x42, 0x32, 0x33, 0x34,
+            0x34, 0x33, 0x34, 0x34, 0x33, 0x34, 0xF5, 0xFF,
+            0xEF, 0x80, 0x00, 0x08, 0x80, 0x00, 0x08, 0x80
+        ];
+        let mut expected = [0i16; 64];
+        let mut expected_state = AdpcmImaState { predictor: 3260, step_index: 49 };
+        decode_adpcm_ima_ima4(&block, &mut expected_state, &mut expected);
+
+        let mut decoded_buf = [0i16; 64];
+        let mut out = decoded_buf.iter_mut();
+        let mut index = 0;
+        let mut state = AdpcmImaState { predictor: 3260, step_index: 49 };
+        // the sink must not panic, because decode_adpcm_ima_ima4_with() is checked by no-panic
+        decode_adpcm_ima_ima4_with(&block, &mut state, &mut |s| {
+            if let Some(o) = out.next() {
+                *o = s;
+            }
+            index += 1;
+        });
+        assert_eq!(index, 64);
+        assert_eq!(decoded_buf, expected);
+        assert_eq!(state, expected_state);
+    }
+
+    #[test]
+    fn test_decode_adpcm_ms_with() {
+        // one channel
+        let block = [ 0xAE, 0xC8, 0x40, 0x00,
+            0x10, 0x10, 0x10, 0x11, 0x21, 0x21, 0x22, 0x32, 0x43, 0x33, 0x43, 0x43
+        ];
+        let mut expected = [0i16; 25];
+        assert!(decode_adpcm_ima_ms(&block, false, &mut expected).is_ok());
+        let mut samples = [0i16; 25];
+        let mut index = 0;
+        assert!(decode_adpcm_ima_ms_with(&block, false, &mut |s| {
+            samples[index] = s;
+            index += 1;
+        }).is_ok());
+        assert_eq!(index, 25);
+        assert_eq!(samples, expected);
+
+        // one channel block, which doesn't end at a 4 byte group
+        let mut expected = [0i16; 23];
+        assert!(decode_adpcm_ima_ms(&block[..15], false, &mut expected).is_ok());
+        let mut index = 0;
+        assert!(decode_adpcm_ima_ms_with(&block[..15], false, &mut |s| {
+            samples[index] = s;
+            index += 1;
+        }).is_ok());
+        assert_eq!(index, 23);
+        assert_eq!(samples[..23], expected);
+
+        // two channels
+        let block = [ 0x38, 0xB1, 0x47, 0x00, 0x1A, 0x9B, 0x50, 0x00,
+            0x00, 0x00, 0x01, 0x00, 0x00, 0x08, 0x00, 0x08,
+            0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF0
+        ];
+        let mut expected = [0i16; 34];
+        assert!(decode_adpcm_ima_ms(&block, true, &mut expected).is_ok());
+        let mut samples = [0i16; 34];
+        let mut index = 0;
+        assert!(decode_adpcm_ima_ms_with(&block, true, &mut |s| {
+            samples[index] = s;
+            index += 1;
+        }).is_ok());
+        assert_eq!(index, 34);
+        assert_eq!(samples, expected);
+
+        // invalid buf length, sink isn't called
+        let mut called = false;
+        assert!(matches!(decode_adpcm_ima_ms_with(&[ 0x38, 0xB1, 0x47, 0x00,
+            0x38, 0xB1, 0x47, 0x38, 0xB1
+        ], true, &mut |_| called = true), Err(Error::InvalidBufferSize)));
+        assert!(!called);
+    }
+
     #[test]
     fn test_encode_adpcm_ima() {
         // normal encoding
@@ -779,6 +1431,30 @@ mod tests {
         assert_eq!(state, AdpcmImaState { predictor: -197, step_index: 56 });
     }
 
+    #[test]
+    fn test_encode_adpcm_ima4_slice() {
+        let samples = [1000i16; 64];
+        let mut expected = [0u8; 34];
+        let mut expected_state = AdpcmImaState::new();
+        encode_adpcm_ima_ima4(&samples, &mut expected_state, &mut expected);
+
+        let mut encoded_buf = [0u8; 34];
+        let mut state = AdpcmImaState::new();
+        assert!(encode_adpcm_ima_ima4_slice(&samples, &mut state, &mut encoded_buf).is_ok());
+        assert_eq!(encoded_buf, expected);
+        assert_eq!(state, expected_state);
+
+        // invalid samples length
+        let mut state = AdpcmImaState::new();
+        assert!(matches!(encode_adpcm_ima_ima4_slice(&samples[..63], &mut state, &mut encoded_buf),
+            Err(Error::InvalidBufferSize)));
+        assert_eq!(state, AdpcmImaState::new());
+
+        // invalid out_buf length
+        assert!(matches!(encode_adpcm_ima_ima4_slice(&samples, &mut state, &mut [0u8; 35]),
+            Err(Error::InvalidBufferSize)));
+    }
+
     #[test]
     fn test_encode_adpcm_ms() {
         // Windows 10 acmStreamConvert() has been tested to return the same values
@@ -858,27 +1534,27 @@ mod tests {
         let mut buf_area = [0u8; 4096];
         // one channel
         for buf_len in 0..=1025 {
-            let mut buf = &mut buf_area[0..buf_len];
-            let sample_len = 2 * buf.len().max(4) - 7 * 1;
+            let buf = &mut buf_area[0..buf_len];
+            let sample_len = 2 * buf.len().max(4) - 7;
             let samples = &sample_area[0..sample_len];
             let mut states = [ AdpcmImaState::new() ];
             if buf_len >= 4 {
-                assert!(encode_adpcm_ima_ms(&samples, &mut states, &mut buf).is_ok());
+                assert!(encode_adpcm_ima_ms(samples, &mut states, buf).is_ok());
             } else {
-                assert!(matches!(encode_adpcm_ima_ms(&samples, &mut states, &mut buf),
+                assert!(matches!(encode_adpcm_ima_ms(samples, &mut states, buf),
                     Err(Error::InvalidBufferSize)));
             }
         }
         // two channels
         for buf_len in 0..=2049 {
-            let mut buf = &mut buf_area[0..buf_len];
+            let buf = &mut buf_area[0..buf_len];
             let sample_len = 2 * buf.len().max(7) - 7 * 2;
             let samples = &sample_area[0..sample_len];
             let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
             if buf_len >= 8 && buf_len % 8 == 0 {
-                assert!(encode_adpcm_ima_ms(&samples, &mut states, &mut buf).is_ok());
+                assert!(encode_adpcm_ima_ms(samples, &mut states, buf).is_ok());
             } else {
-                assert!(matches!(encode_adpcm_ima_ms(&samples, &mut states, &mut buf),
+                assert!(matches!(encode_adpcm_ima_ms(samples, &mut states, buf),
                     Err(Error::InvalidBufferSize)));
             }
         }
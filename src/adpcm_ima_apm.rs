@@ -0,0 +1,127 @@
+
+use crate::Error;
+use crate::{AdpcmImaState, decode_adpcm_ima};
+
+/// Reads the initial IMA ADPCM states from an Ubisoft APM file "vs12" chunk.
+///
+/// `buf` should start at the "vs12" chunk identifier, which follows the 20 byte
+/// WAVEFORMATEX header in APM files. The chunk is at least 48 bytes long.
+///
+/// The returned array contains the states for the left (first) and right (second) channel.
+/// The predictors and step indexes are clamped to valid ranges.
+///
+/// An error is returned if `buf` is too short or if it doesn't start with "vs12".
+pub fn parse_adpcm_ima_apm_vs12(buf: &[u8]) -> Result<[AdpcmImaState; 2], Error> {
+    if buf.len() < 48 {
+        return Err(Error::InvalidBufferSize);
+    }
+    if &buf[0..4] != b"vs12" {
+        return Err(Error::InvalidData);
+    }
+    let read_i32 = |pos: usize| {
+        i32::from_le_bytes([ buf[pos], buf[pos+1], buf[pos+2], buf[pos+3] ])
+    };
+    let to_state = |predictor: i32, step_index: i32| AdpcmImaState {
+        predictor: i16::try_from(predictor.clamp(-32768, 32767)).unwrap_or(0),
+        step_index: u8::try_from(step_index.clamp(0, 88)).unwrap_or(0),
+    };
+    // the state is stored as: has_saved, right predictor, right step index, right saved,
+    // left predictor, left step index, left saved
+    Ok([
+        to_state(read_i32(36), read_i32(40)),
+        to_state(read_i32(24), read_i32(28)),
+    ])
+}
+
+/// Decodes Ubisoft APM (Rayman 2) IMA ADPCM data to 16-bit signed integer samples.
+///
+/// `buf` should contain 4-bit encoded samples. For 2 channel audio, each byte contains
+/// two samples for one channel and the channels alternate byte by byte.
+/// The high nibble of each byte is decoded first. The `buf` length must be divisible by
+/// the number of channels.
+///
+/// `states` must contain channel number of `AdpcmImaState` items (1 or 2). The state objects
+/// should be initialized with [`parse_adpcm_ima_apm_vs12()`] for the first call and subsequent
+/// calls should pass in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * buf.len()`. Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if `states` has an invalid number of state objects or
+/// if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ima_apm(buf: &[u8], states: &mut [AdpcmImaState], out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels < 1 || channels > 2 {
+        return Err(Error::InvalidChannels);
+    }
+    if buf.len() % channels != 0 {
+        return Err(Error::InvalidBufferSize);
+    }
+    if buf.len().checked_mul(2) != Some(out_samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (bytes, out) in buf.chunks_exact(channels).zip(out_samples.chunks_exact_mut(2 * channels)) {
+        for (ch, b) in bytes.iter().enumerate() {
+            out[ch] = decode_adpcm_ima(*b >> 4, &mut states[ch]);
+            out[channels + ch] = decode_adpcm_ima(*b & 0x0f, &mut states[ch]);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_adpcm_ima_apm_vs12() {
+        let mut chunk = [0u8; 80];
+        chunk[0..4].copy_from_slice(b"vs12");
+        chunk[24..28].copy_from_slice(&(-200i32).to_le_bytes());
+        chunk[28..32].copy_from_slice(&10i32.to_le_bytes());
+        chunk[36..40].copy_from_slice(&100000i32.to_le_bytes());
+        chunk[40..44].copy_from_slice(&100i32.to_le_bytes());
+        assert!(matches!(parse_adpcm_ima_apm_vs12(&chunk), Ok(states) if states == [
+            AdpcmImaState { predictor: 32767, step_index: 88 },
+            AdpcmImaState { predictor: -200, step_index: 10 },
+        ]));
+
+        assert!(matches!(parse_adpcm_ima_apm_vs12(&chunk[..47]), Err(Error::InvalidBufferSize)));
+        chunk[0] = b'x';
+        assert!(matches!(parse_adpcm_ima_apm_vs12(&chunk), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_apm() {
+        // one channel, high nibble first
+        let mut states = [ AdpcmImaState { predictor: 100, step_index: 0 } ];
+        let mut samples = [0i16; 4];
+        assert!(decode_adpcm_ima_apm(&[ 0x60, 0x80 ], &mut states, &mut samples).is_ok());
+        assert_eq!(samples, [ 110, 111, 110, 111 ]);
+        assert_eq!(states[0], AdpcmImaState { predictor: 111, step_index: 3 });
+
+        // two channels, the channels alternate byte by byte
+        let mut states = [
+            AdpcmImaState { predictor: 100, step_index: 0 },
+            AdpcmImaState { predictor: -100, step_index: 0 },
+        ];
+        let mut samples = [0i16; 4];
+        assert!(decode_adpcm_ima_apm(&[ 0x60, 0x06 ], &mut states, &mut samples).is_ok());
+        assert_eq!(samples, [ 110, -100, 111, -90 ]);
+
+        // invalid lengths
+        let mut samples = [0i16; 2];
+        assert!(matches!(decode_adpcm_ima_apm(&[ 0x60 ], &mut states, &mut samples),
+            Err(Error::InvalidBufferSize)));
+        let mut states = [ AdpcmImaState::new() ];
+        let mut samples = [0i16; 3];
+        assert!(matches!(decode_adpcm_ima_apm(&[ 0x60 ], &mut states, &mut samples),
+            Err(Error::InvalidBufferSize)));
+        let mut states = [];
+        assert!(matches!(decode_adpcm_ima_apm(&[ 0x60 ], &mut states, &mut samples),
+            Err(Error::InvalidChannels)));
+    }
+}
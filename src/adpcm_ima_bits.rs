@@ -0,0 +1,431 @@
+
+use crate::{AdpcmImaState, Error};
+use crate::{decode_adpcm_ima, decode_adpcm_ima_ms, encode_adpcm_ima, encode_adpcm_ima_ms};
+use crate::adpcm_ima::IMA_STEP_TABLE;
+use crate::bitreader::LsbBitReader;
+
+// step index adjustments for 2, 3 and 5 bit codes, indexed by the code without the sign bit
+const IMA_INDEX_TABLE_2: &[i8; 2] = &[ -1, 2 ];
+const IMA_INDEX_TABLE_3: &[i8; 4] = &[ -1, -1, 1, 2 ];
+const IMA_INDEX_TABLE_5: &[i8; 16] = &[
+    -1, -1, -1, -1, -1, -1, -1, -1, 1, 2, 4, 6, 8, 10, 13, 16
+];
+
+/// Code size of IMA ADPCM variants with 2, 3, 4 or 5 bits per sample.
+///
+/// The 4-bit codes are standard IMA ADPCM. The other code sizes use the same step table with
+/// their own step index tables, as in WAV files with 2, 3 or 5 bits per sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AdpcmImaBits {
+    /// 2 bits per sample.
+    Bits2,
+    /// 3 bits per sample.
+    Bits3,
+    /// 4 bits per sample (standard IMA ADPCM).
+    #[default]
+    Bits4,
+    /// 5 bits per sample.
+    Bits5,
+}
+
+impl AdpcmImaBits {
+    /// All code sizes from the smallest to the largest.
+    pub const ALL: [AdpcmImaBits; 4] = [
+        AdpcmImaBits::Bits2,
+        AdpcmImaBits::Bits3,
+        AdpcmImaBits::Bits4,
+        AdpcmImaBits::Bits5,
+    ];
+
+    /// Returns the number of bits per sample (2..=5).
+    pub fn bits(self) -> u8 {
+        match self {
+            AdpcmImaBits::Bits2 => 2,
+            AdpcmImaBits::Bits3 => 3,
+            AdpcmImaBits::Bits4 => 4,
+            AdpcmImaBits::Bits5 => 5,
+        }
+    }
+
+    /// Returns the code size for the number of bits per sample (2..=5),
+    /// or `None` if it isn't supported.
+    pub fn from_bits(bits: u8) -> Option<AdpcmImaBits> {
+        match bits {
+            2 => Some(AdpcmImaBits::Bits2),
+            3 => Some(AdpcmImaBits::Bits3),
+            4 => Some(AdpcmImaBits::Bits4),
+            5 => Some(AdpcmImaBits::Bits5),
+            _ => None,
+        }
+    }
+
+    // bytes and samples of a channel's group of codes in a WAV / MS IMA ADPCM block,
+    // groups are a multiple of 4 bytes and codes are packed least significant bit first
+    fn ms_group_size(self) -> (usize, usize) {
+        match self {
+            AdpcmImaBits::Bits2 => (4, 16),
+            AdpcmImaBits::Bits3 => (12, 32),
+            AdpcmImaBits::Bits4 => (4, 8),
+            AdpcmImaBits::Bits5 => (20, 32),
+        }
+    }
+
+    fn index_table(self) -> &'static [i8] {
+        match self {
+            AdpcmImaBits::Bits2 => IMA_INDEX_TABLE_2,
+            AdpcmImaBits::Bits3 => IMA_INDEX_TABLE_3,
+            AdpcmImaBits::Bits4 => &[],
+            AdpcmImaBits::Bits5 => IMA_INDEX_TABLE_5,
+        }
+    }
+}
+
+/// Decodes a 2, 3, 4 or 5-bit encoded IMA ADPCM value to a linear 16-bit signed integer
+/// sample value.
+///
+/// Only the lowest `bits` bits of `code` are used and the top-most bits are ignored.
+/// The highest used bit is the sign bit. For 4-bit codes, this is the same as
+/// [`decode_adpcm_ima()`](crate::decode_adpcm_ima). Other code sizes calculate the difference
+/// as `((2 * magnitude + 1) * step_size) >> (bits - 1)`, like FFmpeg does.
+///
+/// The `state` parameter should be initialized to zero or to values from the audio stream
+/// (depending on how the format has specified it). This method updates `state`
+/// with new values. Subsequent calls should pass in the state values from the previous call.
+pub fn decode_adpcm_ima_bits(code: u8, bits: AdpcmImaBits, state: &mut AdpcmImaState) -> i16 {
+    if bits == AdpcmImaBits::Bits4 {
+        return decode_adpcm_ima(code, state);
+    }
+    let shift = bits.bits() - 1;
+    let magnitude = code & ((1 << shift) - 1);
+    let step_index = state.step_index.min(88);
+    let step_size = i32::from(IMA_STEP_TABLE[usize::from(step_index)]);
+    let diff = ((2 * i32::from(magnitude) + 1) * step_size) >> shift;
+    let predictor = if (code >> shift) & 1 != 0 {
+        i32::from(state.predictor) - diff
+    } else {
+        i32::from(state.predictor) + diff
+    };
+    #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+    {
+    state.predictor = predictor.clamp(-32768, 32767) as i16;
+    }
+    let adjustment = bits.index_table().get(usize::from(magnitude)).copied().unwrap_or(0);
+    state.step_index = step_index.saturating_add_signed(adjustment).min(88);
+    state.predictor
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a 2, 3, 4 or 5-bit encoded
+/// IMA ADPCM value.
+///
+/// For 4-bit codes, this is the same as [`encode_adpcm_ima()`](crate::encode_adpcm_ima).
+/// For other code sizes, the code with the smallest error is selected.
+///
+/// The `state` parameter should be initialized to zero or to values from the audio stream
+/// (depending on how the format has specified it). This method updates `state`
+/// with new values. Subsequent calls should pass in the state values from the previous call.
+pub fn encode_adpcm_ima_bits(sample_value: i16, bits: AdpcmImaBits, state: &mut AdpcmImaState)
+    -> u8 {
+    if bits == AdpcmImaBits::Bits4 {
+        return encode_adpcm_ima(sample_value, state);
+    }
+    let mut best_code = 0;
+    let mut best_error = u32::MAX;
+    let mut best_state = state.clone();
+    for code in 0..(1u8 << bits.bits()) {
+        let mut candidate = state.clone();
+        let decoded = decode_adpcm_ima_bits(code, bits, &mut candidate);
+        let error = (i32::from(sample_value) - i32::from(decoded)).unsigned_abs();
+        if error < best_error {
+            best_code = code;
+            best_error = error;
+            best_state = candidate;
+        }
+    }
+    *state = best_state;
+    best_code
+}
+
+/// Returns the number of samples per channel in a WAV / MS IMA ADPCM (wav format 0x0011)
+/// block of `block_size` bytes with `bits` bits per sample.
+///
+/// For 4-bit codes, this is the same as the `out_samples` length of
+/// [`decode_adpcm_ima_ms()`](crate::decode_adpcm_ima_ms) divided by the number of channels.
+/// Other code sizes only use complete groups of codes (see [`decode_adpcm_ima_ms_bits()`]),
+/// which is `(block_size - 4 * channels) * 8 / (bits * channels) + 1` when the block data
+/// fills the groups, as in the `wSamplesPerBlock` field of the WAV format header.
+/// Zero is returned if `block_size` is too small for the block header.
+pub fn adpcm_ima_ms_bits_samples_per_block(block_size: usize, is_stereo: bool,
+    bits: AdpcmImaBits) -> usize {
+
+    let channels = if is_stereo { 2 } else { 1 };
+    if block_size < 4 * channels {
+        return 0;
+    }
+    if bits == AdpcmImaBits::Bits4 {
+        return (block_size - 4 * channels) * 2 / channels + 1;
+    }
+    let (group_bytes, group_samples) = bits.ms_group_size();
+    (block_size - 4 * channels) / (group_bytes * channels) * group_samples + 1
+}
+
+// returns the number of complete groups of codes per channel
+// or `None` if the `buf` length is invalid
+fn ms_bits_groups(buf_len: usize, channels: usize, bits: AdpcmImaBits) -> Option<usize> {
+    let (group_bytes, _) = bits.ms_group_size();
+    if buf_len < 4 * channels || buf_len > 0xffff {
+        return None;
+    }
+    Some((buf_len - 4 * channels) / (group_bytes * channels))
+}
+
+/// Decodes a WAV / MS IMA ADPCM (wav format 0x0011) compressed block with 2, 3, 4 or 5 bits
+/// per sample to 16-bit signed integer samples.
+///
+/// This works like [`decode_adpcm_ima_ms()`](crate::decode_adpcm_ima_ms), which is used for
+/// 4-bit codes. For the other code sizes, each channel has groups of 4 bytes (2 bits),
+/// 12 bytes (3 bits) or 20 bytes (5 bits) in turn after the block header, and the codes are
+/// packed least significant bit first. Bytes at the end of the block which don't fill a group
+/// for all channels are ignored. The `buf` length must be at least 4 times the number of
+/// channels and less than 65536.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// the number of channels times [`adpcm_ima_ms_bits_samples_per_block()`].
+/// Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ima_ms_bits(buf: &[u8], is_stereo: bool, bits: AdpcmImaBits,
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    if bits == AdpcmImaBits::Bits4 {
+        return decode_adpcm_ima_ms(buf, is_stereo, out_samples);
+    }
+    let channels = if is_stereo { 2 } else { 1 };
+    let groups = ms_bits_groups(buf.len(), channels, bits).ok_or(Error::InvalidBufferSize)?;
+    let (group_bytes, group_samples) = bits.ms_group_size();
+    if out_samples.len() != channels * (groups * group_samples + 1) {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+    for ch in 0..channels {
+        states[ch].predictor = i16::from_le_bytes([ buf[ch*4], buf[ch*4+1] ]);
+        states[ch].step_index = buf[ch*4+2].min(88);
+        out_samples[ch] = states[ch].predictor;
+    }
+    for (g, group) in buf[4*channels..].chunks_exact(group_bytes * channels).enumerate() {
+        for (ch, channel_group) in group.chunks_exact(group_bytes).enumerate() {
+            let mut reader = LsbBitReader::new(channel_group);
+            for i in 0..group_samples {
+                let code = u8::try_from(reader.read(u32::from(bits.bits())).unwrap_or(0))
+                    .unwrap_or(0);
+                let pos = (1 + g * group_samples + i) * channels + ch;
+                out_samples[pos] = decode_adpcm_ima_bits(code, bits, &mut states[ch]);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to a WAV / MS IMA ADPCM (wav format 0x0011)
+/// compressed block with 2, 3, 4 or 5 bits per sample.
+///
+/// This works like [`encode_adpcm_ima_ms()`](crate::encode_adpcm_ima_ms), which is used for
+/// 4-bit codes. For the other code sizes, the `samples` length must be the number of channels
+/// times [`adpcm_ima_ms_bits_samples_per_block()`] for the `out_buf` length. The block layout
+/// is described in [`decode_adpcm_ima_ms_bits()`] and unused bytes at the end of the block
+/// are set to zero.
+///
+/// `states` must contain channel number of `AdpcmImaState` items (1 or 2). The state objects
+/// should be initialized to zero for the first call and subsequent calls
+/// should pass in the state values from the previous call.
+///
+/// An error is returned if `states` has an invalid number of state objects or
+/// if the `samples` or `out_buf` length isn't correct.
+/// If an error is returned, `states` and `out_buf` are left unmodified.
+pub fn encode_adpcm_ima_ms_bits(samples: &[i16], states: &mut [AdpcmImaState],
+    bits: AdpcmImaBits, out_buf: &mut [u8]) -> Result<(), Error> {
+
+    if bits == AdpcmImaBits::Bits4 {
+        return encode_adpcm_ima_ms(samples, states, out_buf);
+    }
+    let channels = states.len();
+    if channels < 1 || channels > 2 {
+        return Err(Error::InvalidChannels);
+    }
+    let groups = ms_bits_groups(out_buf.len(), channels, bits).ok_or(Error::InvalidBufferSize)?;
+    let (group_bytes, group_samples) = bits.ms_group_size();
+    if samples.len() != channels * (groups * group_samples + 1) {
+        return Err(Error::InvalidBufferSize);
+    }
+    // the first channels*4 bytes are the initial state (every fourth byte is ignored)
+    for ch in 0..channels {
+        states[ch].predictor = samples[ch];
+        states[ch].step_index = states[ch].step_index.min(88);
+        out_buf[ch*4..ch*4+2].copy_from_slice(&samples[ch].to_le_bytes());
+        out_buf[ch*4+2] = states[ch].step_index;
+        out_buf[ch*4+3] = 0;
+    }
+    let data = &mut out_buf[4*channels..];
+    data.fill(0);
+    for (g, group) in data.chunks_exact_mut(group_bytes * channels).enumerate() {
+        for (ch, channel_group) in group.chunks_exact_mut(group_bytes).enumerate() {
+            // pack codes least significant bit first
+            let mut acc = 0u32;
+            let mut acc_bits = 0;
+            let mut out = channel_group.iter_mut();
+            for i in 0..group_samples {
+                let sample = samples[(1 + g * group_samples + i) * channels + ch];
+                acc |= u32::from(encode_adpcm_ima_bits(sample, bits, &mut states[ch])) << acc_bits;
+                acc_bits += bits.bits();
+                while acc_bits >= 8 {
+                    if let Some(b) = out.next() {
+                        *b = acc.to_le_bytes()[0];
+                    }
+                    acc >>= 8;
+                    acc_bits -= 8;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signal::tone;
+
+    #[test]
+    fn test_adpcm_ima_bits() {
+        assert!(AdpcmImaBits::ALL.iter().all(|b| AdpcmImaBits::from_bits(b.bits()) == Some(*b)));
+        assert_eq!(AdpcmImaBits::from_bits(6), None);
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_bits() {
+        // 3-bit: magnitude 3, step 7: (7 * 7) >> 2 = 12
+        let mut state = AdpcmImaState::new();
+        assert_eq!(decode_adpcm_ima_bits(3, AdpcmImaBits::Bits3, &mut state), 12);
+        assert_eq!(state, AdpcmImaState { predictor: 12, step_index: 2 });
+        assert_eq!(decode_adpcm_ima_bits(4, AdpcmImaBits::Bits3, &mut state), 10);
+        assert_eq!(state, AdpcmImaState { predictor: 10, step_index: 1 });
+
+        // 2-bit and 5-bit
+        let mut state = AdpcmImaState { predictor: 100, step_index: 88 };
+        assert_eq!(decode_adpcm_ima_bits(3, AdpcmImaBits::Bits2, &mut state), -32768);
+        assert_eq!(state.step_index, 88);
+        let mut state = AdpcmImaState { predictor: 0, step_index: 10 };
+        assert_eq!(decode_adpcm_ima_bits(0x0f, AdpcmImaBits::Bits5, &mut state), 36);
+        assert_eq!(state.step_index, 26);
+
+        // 4-bit is standard IMA ADPCM
+        let mut state = AdpcmImaState::new();
+        let mut expected_state = AdpcmImaState::new();
+        for code in [ 7, 7, 2, 2, 2, 7, 5, 0x1f ] {
+            assert_eq!(decode_adpcm_ima_bits(code, AdpcmImaBits::Bits4, &mut state),
+                decode_adpcm_ima(code, &mut expected_state));
+        }
+    }
+
+    #[test]
+    fn test_encode_adpcm_ima_bits() {
+        // more bits give smaller errors for a tone
+        let mut previous_error = u64::MAX;
+        for bits in AdpcmImaBits::ALL {
+            let mut encoder_state = AdpcmImaState::new();
+            let mut decoder_state = AdpcmImaState::new();
+            let mut error = 0u64;
+            for i in 0..1000 {
+                let sample = tone(i);
+                let code = encode_adpcm_ima_bits(sample, bits, &mut encoder_state);
+                assert!(code < (1 << bits.bits()));
+                let decoded = decode_adpcm_ima_bits(code, bits, &mut decoder_state);
+                assert_eq!(encoder_state, decoder_state);
+                error += u64::from((i32::from(sample) - i32::from(decoded)).unsigned_abs());
+            }
+            assert!(error < previous_error);
+            previous_error = error;
+        }
+    }
+
+    #[test]
+    fn test_adpcm_ima_ms_bits_samples_per_block() {
+        assert_eq!(adpcm_ima_ms_bits_samples_per_block(256, false, AdpcmImaBits::Bits3), 673);
+        assert_eq!(adpcm_ima_ms_bits_samples_per_block(1024, false, AdpcmImaBits::Bits3), 2721);
+        // 1016 bytes of stereo data has 42 groups of 24 bytes and 8 unused bytes
+        assert_eq!(adpcm_ima_ms_bits_samples_per_block(1024, true, AdpcmImaBits::Bits3), 1345);
+        assert_eq!(adpcm_ima_ms_bits_samples_per_block(256, false, AdpcmImaBits::Bits2), 1009);
+        assert_eq!(adpcm_ima_ms_bits_samples_per_block(256, false, AdpcmImaBits::Bits4), 505);
+        assert_eq!(adpcm_ima_ms_bits_samples_per_block(1024, true, AdpcmImaBits::Bits4), 1017);
+        assert_eq!(adpcm_ima_ms_bits_samples_per_block(256, false, AdpcmImaBits::Bits5), 385);
+        assert_eq!(adpcm_ima_ms_bits_samples_per_block(7, true, AdpcmImaBits::Bits3), 0);
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_ms_bits() {
+        // 3-bit codes 3 and 4 followed by zeros, packed least significant bit first
+        let mut buf = [0u8; 16];
+        buf[4] = 0x23;
+        let mut out = [0i16; 33];
+        assert!(decode_adpcm_ima_ms_bits(&buf, false, AdpcmImaBits::Bits3, &mut out).is_ok());
+        let mut state = AdpcmImaState::new();
+        let mut expected = [0i16; 33];
+        for (i, value) in expected[1..].iter_mut().enumerate() {
+            let code = [ 3, 4 ].get(i).copied().unwrap_or(0);
+            *value = decode_adpcm_ima_bits(code, AdpcmImaBits::Bits3, &mut state);
+        }
+        assert_eq!(out, expected);
+        assert_eq!(&out[..3], &[ 0, 12, 10 ]);
+
+        // a partial group at the end is ignored
+        let mut long_buf = [0u8; 20];
+        long_buf[..16].copy_from_slice(&buf);
+        let mut long_out = [0i16; 33];
+        assert!(decode_adpcm_ima_ms_bits(&long_buf, false, AdpcmImaBits::Bits3, &mut long_out)
+            .is_ok());
+        assert_eq!(long_out, out);
+
+        assert!(matches!(decode_adpcm_ima_ms_bits(&buf, false, AdpcmImaBits::Bits3, &mut out[..32]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_adpcm_ima_ms_bits(&buf[..3], false, AdpcmImaBits::Bits3, &mut []),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_encode_adpcm_ima_ms_bits() {
+        for bits in AdpcmImaBits::ALL {
+            for channels in 1..=2 {
+                let is_stereo = channels == 2;
+                let block_size = 256 * channels;
+                let len = channels
+                    * adpcm_ima_ms_bits_samples_per_block(block_size, is_stereo, bits);
+                let mut samples = [0i16; 2 * 1009];
+                for (i, value) in samples[..len].iter_mut().enumerate() {
+                    *value = tone(i / channels);
+                }
+                let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+                let mut buf = [0xffu8; 512];
+                assert!(encode_adpcm_ima_ms_bits(&samples[..len], &mut states[..channels], bits,
+                    &mut buf[..block_size]).is_ok());
+                let mut out = [0i16; 2 * 1009];
+                assert!(decode_adpcm_ima_ms_bits(&buf[..block_size], is_stereo, bits,
+                    &mut out[..len]).is_ok());
+                // the decoder ends with the encoder state
+                for ch in 0..channels {
+                    assert_eq!(out[len - channels + ch], states[ch].predictor);
+                }
+                let error: u64 = samples[..len].iter().zip(&out[..len])
+                    .map(|(a, b)| u64::from((i32::from(*a) - i32::from(*b)).unsigned_abs()))
+                    .sum();
+                assert!(error / u64::try_from(len).unwrap_or(1) < 4000);
+            }
+        }
+
+        let mut states = [ AdpcmImaState::new() ];
+        let mut buf = [0u8; 16];
+        assert!(matches!(encode_adpcm_ima_ms_bits(&[0; 32], &mut states, AdpcmImaBits::Bits3,
+            &mut buf), Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_adpcm_ima_ms_bits(&[0; 33], &mut [], AdpcmImaBits::Bits3,
+            &mut buf), Err(Error::InvalidChannels)));
+    }
+}
@@ -0,0 +1,191 @@
+
+use crate::Error;
+use crate::AdpcmImaState;
+use crate::{decode_adpcm_ima_ima4, decode_adpcm_ima_ms};
+use crate::adpcm_ima::ms_block_sample_len;
+
+/// Checked AIFF-C / QT "ima4" compressed block.
+///
+/// The block contains 2 header bytes (predictor and step index) and 32 bytes of 4-bit samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ima4Block<'a>(&'a [u8; 34]);
+
+impl<'a> Ima4Block<'a> {
+    /// Creates a new block from 34 bytes.
+    pub fn new(buf: &'a [u8; 34]) -> Ima4Block<'a> {
+        Ima4Block(buf)
+    }
+
+    /// Creates a new block from a slice.
+    ///
+    /// An error is returned if the `buf` length isn't 34.
+    pub fn from_slice(buf: &'a [u8]) -> Result<Ima4Block<'a>, Error> {
+        match buf.try_into() {
+            Ok(b) => Ok(Ima4Block(b)),
+            Err(_) => Err(Error::InvalidBufferSize),
+        }
+    }
+
+    /// Returns the raw bytes of the block.
+    pub fn as_bytes(&self) -> &'a [u8; 34] {
+        self.0
+    }
+
+    /// Returns the predictor stored in the block header.
+    pub fn predictor(&self) -> i16 {
+        i16::from_be_bytes([ self.0[0], self.0[1] & 0b1000_0000 ])
+    }
+
+    /// Returns the step index stored in the block header.
+    ///
+    /// The returned value is not clamped, so it can be greater than 88 for invalid blocks.
+    pub fn step_index(&self) -> u8 {
+        self.0[1] & 0b0111_1111
+    }
+
+    /// Returns an iterator over the 64 encoded 4-bit values in sample order.
+    pub fn nibbles(&self) -> impl Iterator<Item = u8> + 'a {
+        self.0[2..].iter().flat_map(|b| [ *b & 0x0f, *b >> 4 ])
+    }
+
+    /// Decodes the block to 64 samples. See [`decode_adpcm_ima_ima4()`] for details.
+    pub fn decode(&self, state: &mut AdpcmImaState, out_samples: &mut [i16; 64]) {
+        decode_adpcm_ima_ima4(self.0, state, out_samples);
+    }
+}
+
+/// Checked WAV / MS IMA ADPCM (wav format 0x0011) compressed block.
+///
+/// The block length is validated once when the block is created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MsImaBlock<'a> {
+    buf: &'a [u8],
+    channels: usize,
+}
+
+impl<'a> MsImaBlock<'a> {
+    /// Creates a new block.
+    ///
+    /// For 1 channel audio, the `buf` length must be at least 4. For 2 channel audio,
+    /// the `buf` length must be at least 8 and it must be divisible by 8.
+    /// The `buf` length must always be less than 65536.
+    ///
+    /// An error is returned if the `buf` length isn't correct.
+    pub fn new(buf: &'a [u8], is_stereo: bool) -> Result<MsImaBlock<'a>, Error> {
+        let channels = if is_stereo {
+            2
+        } else {
+            1
+        };
+        ms_block_sample_len(buf.len(), channels)?;
+        Ok(MsImaBlock { buf, channels })
+    }
+
+    /// Returns the raw bytes of the block.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    /// Returns the number of channels (1 or 2).
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Returns the number of samples in the block, counting all channels.
+    pub fn sample_len(&self) -> usize {
+        (self.buf.len() - 4 * self.channels) * 2 + self.channels
+    }
+
+    /// Returns the predictor stored in the block header for channel `ch`.
+    ///
+    /// An error is returned if `ch` is not a valid channel index.
+    pub fn predictor(&self, ch: usize) -> Result<i16, Error> {
+        if ch >= self.channels {
+            return Err(Error::InvalidChannels);
+        }
+        Ok(i16::from_le_bytes([ self.buf[ch*4], self.buf[ch*4+1] ]))
+    }
+
+    /// Returns the step index stored in the block header for channel `ch`.
+    ///
+    /// The returned value is not clamped, so it can be greater than 88 for invalid blocks.
+    ///
+    /// An error is returned if `ch` is not a valid channel index.
+    pub fn step_index(&self, ch: usize) -> Result<u8, Error> {
+        if ch >= self.channels {
+            return Err(Error::InvalidChannels);
+        }
+        Ok(self.buf[ch*4+2])
+    }
+
+    /// Returns an iterator over the encoded 4-bit values of channel `ch` in sample order.
+    /// The header sample isn't included.
+    ///
+    /// An error is returned if `ch` is not a valid channel index.
+    pub fn nibbles(&self, ch: usize) -> Result<impl Iterator<Item = u8> + 'a, Error> {
+        if ch >= self.channels {
+            return Err(Error::InvalidChannels);
+        }
+        Ok(self.buf[4*self.channels..]
+            .chunks(4*self.channels)
+            .flat_map(move |group| group.iter().skip(ch*4).take(4))
+            .flat_map(|b| [ *b & 0x0f, *b >> 4 ]))
+    }
+
+    /// Decodes the block to interleaved samples. See [`decode_adpcm_ima_ms()`] for details.
+    ///
+    /// An error is returned if the `out_samples` length isn't [`sample_len()`](Self::sample_len).
+    pub fn decode(&self, out_samples: &mut [i16]) -> Result<(), Error> {
+        decode_adpcm_ima_ms(self.buf, self.channels == 2, out_samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ima4_block() {
+        let buf = [ 0x0C, 0xB1,
+            0x42, 0x32, 0x43, 0x42, 0x32, 0x43, 0x42, 0x32,
+            0x43, 0x42, 0x32, 0x43, 0x42, 0x32, 0x33, 0x34,
+            0x34, 0x33, 0x34, 0x34, 0x33, 0x34, 0xF5, 0xFF,
+            0xEF, 0x80, 0x00, 0x08, 0x80, 0x00, 0x08, 0x80
+        ];
+        let block = Ima4Block::new(&buf);
+        assert_eq!(block.predictor(), 0x0c80);
+        assert_eq!(block.step_index(), 49);
+        assert_eq!(block.nibbles().count(), 64);
+        assert!(block.nibbles().take(4).eq([ 2, 4, 2, 3 ]));
+        assert!(Ima4Block::from_slice(&buf).is_ok());
+        assert!(matches!(Ima4Block::from_slice(&buf[1..]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_ms_ima_block() {
+        let buf = [ 0x38, 0xB1, 0x47, 0x00, 0x1A, 0x9B, 0x50, 0x00,
+            0x00, 0x00, 0x01, 0x00, 0x00, 0x08, 0x00, 0x08
+        ];
+        let block = MsImaBlock::new(&buf, true);
+        assert!(block.is_ok());
+        let Ok(block) = block else { return };
+        assert_eq!(block.channels(), 2);
+        assert_eq!(block.sample_len(), 18);
+        assert!(matches!(block.predictor(0), Ok(-20168)));
+        assert!(matches!(block.predictor(1), Ok(-25830)));
+        assert!(matches!(block.step_index(1), Ok(0x50)));
+        assert!(matches!(block.predictor(2), Err(Error::InvalidChannels)));
+        assert!(matches!(block.nibbles(0).map(|n| n.eq([ 0, 0, 0, 0, 1, 0, 0, 0 ])), Ok(true)));
+        assert!(matches!(block.nibbles(1).map(|n| n.eq([ 0, 0, 8, 0, 0, 0, 8, 0 ])), Ok(true)));
+        let mut samples = [0i16; 18];
+        assert!(block.decode(&mut samples).is_ok());
+
+        // invalid lengths
+        // 1 channel blocks don't need to be divisible by 4
+        let block = MsImaBlock::new(&buf[..7], false);
+        assert!(matches!(block.map(|b| b.nibbles(0).map(|n| n.count())), Ok(Ok(6))));
+
+        assert!(matches!(MsImaBlock::new(&buf[..3], false), Err(Error::InvalidBufferSize)));
+        assert!(matches!(MsImaBlock::new(&buf[..9], true), Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::Error;
+use crate::{AdpcmImaState, decode_adpcm_ima};
+
+/// Decodes a Eurocom DAT4 IMA ADPCM compressed block to 16-bit signed integer samples.
+///
+/// For 2 channel audio, the channels aren't interleaved: the first half of `buf` contains
+/// the first channel and the second half contains the second channel. Each channel starts with
+/// 4 header bytes (16-bit little-endian predictor, 8-bit step index and an unused byte)
+/// followed by bytes of 4-bit encoded samples. For 1 channel audio, the `buf` length must be
+/// at least 4. For 2 channel audio, the `buf` length must be at least 8 and it must be
+/// divisible by 2.
+///
+/// The header predictor isn't output as a sample. The high nibble of each byte is decoded
+/// first.
+///
+/// `is_stereo` should be `false` for 1 channel (mono) audio and `true` for
+/// 2 channel (stereo) audio.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * (buf.len() - 4)` for 1 channel audio and `2 * (buf.len() - 8)` for 2 channel audio.
+/// Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ima_dat4(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    if buf.len() < 4 * channels || buf.len() % channels != 0 {
+        return Err(Error::InvalidBufferSize);
+    }
+    let expected_sample_len = (buf.len() - 4 * channels)
+        .checked_mul(2)
+        .ok_or(Error::InvalidBufferSize)?;
+    if expected_sample_len != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (ch, channel_buf) in buf.chunks_exact(buf.len() / channels).enumerate() {
+        let mut state = AdpcmImaState {
+            predictor: i16::from_le_bytes([ channel_buf[0], channel_buf[1] ]),
+            // clamp step index to 0..=88 so that something is decoded
+            step_index: channel_buf[2].min(88),
+        };
+        let nibbles = channel_buf[4..].iter().flat_map(|b| [ *b >> 4, *b & 0x0f ]);
+        for (i, nibble) in nibbles.enumerate() {
+            out_samples[i * channels + ch] = decode_adpcm_ima(nibble, &mut state);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_adpcm_ima_dat4() {
+        // one channel, high nibble first
+        let mut samples = [0i16; 4];
+        assert!(decode_adpcm_ima_dat4(&[ 0x64, 0x00, 0x00, 0x00, 0x60, 0x80 ], false,
+            &mut samples).is_ok());
+        assert_eq!(samples, [ 110, 111, 110, 111 ]);
+
+        // two channels, not interleaved
+        let mut samples = [0i16; 4];
+        assert!(decode_adpcm_ima_dat4(&[ 0x64, 0x00, 0x00, 0x00, 0x60,
+            0x9c, 0xff, 0x00, 0x00, 0x06 ], true, &mut samples).is_ok());
+        assert_eq!(samples, [ 110, -100, 111, -90 ]);
+
+        // step index is clamped
+        let mut samples = [0i16; 2];
+        assert!(decode_adpcm_ima_dat4(&[ 0x00, 0x00, 0xff, 0x00, 0x00 ], false,
+            &mut samples).is_ok());
+        assert_eq!(samples, [ 4095, 7819 ]);
+
+        // invalid buf length
+        let mut samples = [0i16; 2];
+        assert!(matches!(decode_adpcm_ima_dat4(&[ 0, 0, 0, 0, 0, 0, 0, 0, 0 ], true,
+            &mut samples), Err(Error::InvalidBufferSize)));
+        let mut samples = [0i16; 0];
+        assert!(matches!(decode_adpcm_ima_dat4(&[ 0, 0, 0 ], false, &mut samples),
+            Err(Error::InvalidBufferSize)));
+
+        // invalid out_samples length
+        let mut samples = [0i16; 3];
+        assert!(matches!(decode_adpcm_ima_dat4(&[ 0x64, 0x00, 0x00, 0x00, 0x60, 0x80 ], false,
+            &mut samples), Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,135 @@
+use crate::Error;
+use crate::{AdpcmImaState, decode_adpcm_ima};
+
+/// Size of the Duck DK3 IMA ADPCM block header in bytes.
+pub const DK3_ADPCM_HEADER_SIZE: usize = 16;
+
+/// Returns the number of samples in a Duck DK3 IMA ADPCM block of `block_size` bytes.
+///
+/// The returned value is the total number of interleaved samples for both channels.
+/// Zero is returned if `block_size` is smaller than the header size.
+pub fn dk3_adpcm_samples_per_block(block_size: usize) -> usize {
+    let nibble_count = block_size.saturating_sub(DK3_ADPCM_HEADER_SIZE) * 2;
+    // every three nibbles produce two stereo sample pairs
+    nibble_count / 3 * 4
+}
+
+/// Decodes a Duck DK3 IMA ADPCM compressed block to 16-bit signed integer samples.
+///
+/// DK3 is always 2 channel (stereo) audio, which is coded as a sum channel and a difference
+/// channel. `buf` should contain a 16 byte header followed by bytes of 4-bit encoded samples.
+/// The header contains the 16-bit little-endian initial predictors of the sum and difference
+/// channels at offsets 10 and 12 and their step indexes at offsets 14 and 15.
+///
+/// The low nibble of each byte is decoded first. The nibbles are in groups of three: a sum
+/// channel nibble, a difference channel nibble and another sum channel nibble. Each group
+/// produces two stereo sample pairs. Nibbles which don't form a complete group are ignored.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// the value returned by [`dk3_adpcm_samples_per_block()`] for the `buf` length. Samples are
+/// interleaved.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct or if a header
+/// step index is greater than 88. If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ima_dk3(buf: &[u8], out_samples: &mut [i16]) -> Result<(), Error> {
+    if buf.len() < DK3_ADPCM_HEADER_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    if dk3_adpcm_samples_per_block(buf.len()) != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    if buf[14] > 88 || buf[15] > 88 {
+        return Err(Error::InvalidData);
+    }
+    let mut sum_state = AdpcmImaState {
+        predictor: i16::from_le_bytes([ buf[10], buf[11] ]),
+        step_index: buf[14],
+    };
+    let mut diff_state = AdpcmImaState {
+        predictor: i16::from_le_bytes([ buf[12], buf[13] ]),
+        step_index: buf[15],
+    };
+    let mut diff = i32::from(diff_state.predictor);
+    let mut nibbles = buf[DK3_ADPCM_HEADER_SIZE..].iter()
+        .flat_map(|b| [ *b & 0x0f, *b >> 4 ]);
+    for out in out_samples.chunks_exact_mut(4) {
+        let (n0, n1, n2) = match (nibbles.next(), nibbles.next(), nibbles.next()) {
+            (Some(n0), Some(n1), Some(n2)) => (n0, n1, n2),
+            _ => break,
+        };
+        let sum = decode_adpcm_ima(n0, &mut sum_state);
+        let diff_predictor = decode_adpcm_ima(n1, &mut diff_state);
+        diff = (diff + i32::from(diff_predictor)) / 2;
+        store_pair(sum, diff, &mut out[0..2]);
+        let sum = decode_adpcm_ima(n2, &mut sum_state);
+        diff = (diff + i32::from(diff_predictor)) / 2;
+        store_pair(sum, diff, &mut out[2..4]);
+    }
+    Ok(())
+}
+
+// converts a sum and difference to left and right samples
+fn store_pair(sum: i16, diff: i32, out: &mut [i16]) {
+    let sum = i32::from(sum);
+    out[0] = i16::try_from((sum + diff).clamp(-32768, 32767)).unwrap_or(0);
+    out[1] = i16::try_from((sum - diff).clamp(-32768, 32767)).unwrap_or(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(sum: i16, diff: i16, sum_index: u8, diff_index: u8) -> [u8; 16] {
+        let mut buf = [0u8; 16];
+        buf[10..12].copy_from_slice(&sum.to_le_bytes());
+        buf[12..14].copy_from_slice(&diff.to_le_bytes());
+        buf[14] = sum_index;
+        buf[15] = diff_index;
+        buf
+    }
+
+    #[test]
+    fn test_dk3_adpcm_samples_per_block() {
+        assert_eq!(dk3_adpcm_samples_per_block(0), 0);
+        assert_eq!(dk3_adpcm_samples_per_block(16), 0);
+        assert_eq!(dk3_adpcm_samples_per_block(17), 0);
+        assert_eq!(dk3_adpcm_samples_per_block(19), 8);
+        assert_eq!(dk3_adpcm_samples_per_block(20), 8);
+        assert_eq!(dk3_adpcm_samples_per_block(1024), 2688);
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_dk3() {
+        let mut buf = [0u8; 19];
+        buf[..16].copy_from_slice(&header(100, 10, 0, 0));
+        // nibbles: sum 4, diff 7, sum 1, then sum 0, diff 8, sum 3
+        buf[16..].copy_from_slice(&[ 0x74, 0x01, 0x38 ]);
+        let mut samples = [0i16; 8];
+        assert!(decode_adpcm_ima_dk3(&buf, &mut samples).is_ok());
+        assert_eq!(samples, [ 122, 92, 128, 92, 129, 93, 133, 97 ]);
+
+        // output is clamped
+        let mut buf = [0u8; 18];
+        buf[..16].copy_from_slice(&header(32767, 32767, 88, 0));
+        let mut samples = [0i16; 4];
+        assert!(decode_adpcm_ima_dk3(&buf, &mut samples).is_ok());
+        assert_eq!(samples, [ 32767, 0, 32767, 0 ]);
+
+        // invalid step index
+        let mut buf = [0u8; 19];
+        buf[..16].copy_from_slice(&header(0, 0, 0, 89));
+        let mut samples = [0i16; 8];
+        assert!(matches!(decode_adpcm_ima_dk3(&buf, &mut samples), Err(Error::InvalidData)));
+        assert_eq!(samples, [0; 8]);
+
+        // invalid buf length
+        let mut samples = [0i16; 0];
+        assert!(matches!(decode_adpcm_ima_dk3(&[0; 15], &mut samples),
+            Err(Error::InvalidBufferSize)));
+
+        // invalid out_samples length
+        let mut samples = [0i16; 4];
+        assert!(matches!(decode_adpcm_ima_dk3(&buf, &mut samples),
+            Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::Error;
+use crate::{AdpcmImaState, decode_adpcm_ima, encode_adpcm_ima};
+
+// DVI4 is the IMA ADPCM RTP payload format specified in RFC 3551, section 4.5.1.
+
+/// Size of the DVI4 payload header in bytes.
+pub const DVI4_HEADER_SIZE: usize = 4;
+
+/// Decodes a DVI4 (RFC 3551) RTP payload to 16-bit signed integer samples.
+///
+/// `buf` should contain 4 header bytes (16-bit big-endian predictor, 8-bit step index and
+/// a reserved byte) followed by bytes of 4-bit encoded samples. The `buf` length must be
+/// at least 4. Only 1 channel audio is supported.
+///
+/// The header contains the state before the first sample, so the header predictor isn't output
+/// as a sample. The high nibble of each byte is decoded first.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * (buf.len() - 4)`.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ima_dvi4(buf: &[u8], out_samples: &mut [i16]) -> Result<(), Error> {
+    if buf.len() < DVI4_HEADER_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    let expected_sample_len = (buf.len() - DVI4_HEADER_SIZE)
+        .checked_mul(2)
+        .ok_or(Error::InvalidBufferSize)?;
+    if expected_sample_len != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut state = AdpcmImaState {
+        predictor: i16::from_be_bytes([ buf[0], buf[1] ]),
+        // clamp step index to 0..=88 so that something is decoded
+        step_index: buf[2].min(88),
+    };
+    for (b, out) in buf[DVI4_HEADER_SIZE..].iter().zip(out_samples.chunks_exact_mut(2)) {
+        out[0] = decode_adpcm_ima(*b >> 4, &mut state);
+        out[1] = decode_adpcm_ima(*b & 0x0f, &mut state);
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to a DVI4 (RFC 3551) RTP payload.
+///
+/// The header is written from `state` before encoding the samples, so that each payload can
+/// be decoded independently. The high nibble of each byte is encoded first. The reserved
+/// header byte is set to zero.
+///
+/// The `state` parameter should be initialized to zero for the first payload and subsequent
+/// calls should pass in the state values from the previous call.
+///
+/// This function outputs encoded bytes to `out_buf`. The `samples` length must be even and
+/// the `out_buf` length must be `4 + samples.len() / 2`.
+///
+/// An error is returned if the `samples` or `out_buf` length isn't correct.
+/// If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_adpcm_ima_dvi4(samples: &[i16], state: &mut AdpcmImaState, out_buf: &mut [u8])
+    -> Result<(), Error> {
+
+    if samples.len() % 2 != 0 || out_buf.len() != DVI4_HEADER_SIZE + samples.len() / 2 {
+        return Err(Error::InvalidBufferSize);
+    }
+    state.step_index = state.step_index.min(88);
+    out_buf[0..2].copy_from_slice(&state.predictor.to_be_bytes());
+    out_buf[2] = state.step_index;
+    out_buf[3] = 0;
+    for (out, pair) in out_buf[DVI4_HEADER_SIZE..].iter_mut().zip(samples.chunks_exact(2)) {
+        let high = encode_adpcm_ima(pair[0], state);
+        let low = encode_adpcm_ima(pair[1], state);
+        *out = (high << 4) | low;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_adpcm_ima_dvi4() {
+        // big-endian predictor, high nibble first
+        let mut samples = [0i16; 4];
+        assert!(decode_adpcm_ima_dvi4(&[ 0x00, 0x64, 0x00, 0x00, 0x60, 0x80 ],
+            &mut samples).is_ok());
+        assert_eq!(samples, [ 110, 111, 110, 111 ]);
+
+        // step index is clamped
+        let mut samples = [0i16; 2];
+        assert!(decode_adpcm_ima_dvi4(&[ 0x00, 0x00, 0xff, 0x00, 0x70 ], &mut samples).is_ok());
+        assert_eq!(samples, [ 32767, 32767 ]);
+
+        // header only
+        assert!(decode_adpcm_ima_dvi4(&[ 0x00, 0x00, 0x00, 0x00 ], &mut []).is_ok());
+
+        // invalid buf length
+        assert!(matches!(decode_adpcm_ima_dvi4(&[ 0x00, 0x00, 0x00 ], &mut []),
+            Err(Error::InvalidBufferSize)));
+
+        // invalid out_samples length
+        let mut samples = [0i16; 3];
+        assert!(matches!(decode_adpcm_ima_dvi4(&[ 0x00, 0x64, 0x00, 0x00, 0x60, 0x80 ],
+            &mut samples), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_encode_adpcm_ima_dvi4() {
+        let samples = [ 110, 111, 110, 111 ];
+        let mut state = AdpcmImaState { predictor: 100, step_index: 0 };
+        let mut buf = [0u8; 6];
+        assert!(encode_adpcm_ima_dvi4(&samples, &mut state, &mut buf).is_ok());
+        assert_eq!(buf, [ 0x00, 0x64, 0x00, 0x00, 0x60, 0x80 ]);
+        assert_eq!(state, AdpcmImaState { predictor: 111, step_index: 3 });
+
+        // the next payload starts from the state after the previous payload
+        let samples = [ -1000, -2000 ];
+        let mut buf2 = [0u8; 5];
+        assert!(encode_adpcm_ima_dvi4(&samples, &mut state, &mut buf2).is_ok());
+        assert_eq!(buf2[..4], [ 0x00, 0x6f, 0x03, 0x00 ]);
+        let mut decoded = [0i16; 2];
+        assert!(decode_adpcm_ima_dvi4(&buf2, &mut decoded).is_ok());
+        assert_eq!(decoded[1], state.predictor);
+
+        // invalid lengths
+        assert!(matches!(encode_adpcm_ima_dvi4(&[ 0, 0, 0 ], &mut state, &mut buf[..5]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_adpcm_ima_dvi4(&[ 0, 0 ], &mut state, &mut buf),
+            Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,109 @@
+
+use crate::Error;
+use crate::{AdpcmImaState, decode_adpcm_ima};
+
+/// Decodes a Funcom ISS IMA ADPCM compressed block to 16-bit signed integer samples.
+///
+/// `buf` should contain 4 header bytes for each channel (16-bit little-endian predictor and
+/// 16-bit little-endian step index) followed by bytes of 4-bit encoded samples. For 1 channel
+/// audio, the `buf` length must be at least 4. For 2 channel audio, the `buf` length must be
+/// at least 8.
+///
+/// Unlike MS IMA ADPCM, the header predictor isn't output as a sample. For 1 channel audio,
+/// the low nibble of each byte is decoded first. For 2 channel audio, the high nibble of each
+/// byte belongs to the first channel and the low nibble to the second channel.
+///
+/// `is_stereo` should be `false` for 1 channel (mono) audio and `true` for
+/// 2 channel (stereo) audio.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * (buf.len() - 4)` for 1 channel audio and `2 * (buf.len() - 8)` for 2 channel audio.
+/// Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct or if a header
+/// step index is greater than 88. If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ima_iss(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    if buf.len() < 4 * channels {
+        return Err(Error::InvalidBufferSize);
+    }
+    let expected_sample_len = (buf.len() - 4 * channels)
+        .checked_mul(2)
+        .ok_or(Error::InvalidBufferSize)?;
+    if expected_sample_len != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+    for ch in 0..channels {
+        // FFmpeg rejects blocks which have step index > 88
+        let step_index = match u8::try_from(u16::from_le_bytes([ buf[ch*4+2], buf[ch*4+3] ])) {
+            Ok(step_index) if step_index <= 88 => step_index,
+            _ => return Err(Error::InvalidData),
+        };
+        states[ch].predictor = i16::from_le_bytes([ buf[ch*4], buf[ch*4+1] ]);
+        states[ch].step_index = step_index;
+    }
+    let (first_state, second_state) = states.split_at_mut(1);
+    for (b, out) in buf[4*channels..].iter().zip(out_samples.chunks_exact_mut(2)) {
+        if is_stereo {
+            out[0] = decode_adpcm_ima(*b >> 4, &mut first_state[0]);
+            out[1] = decode_adpcm_ima(*b & 0x0f, &mut second_state[0]);
+        } else {
+            out[0] = decode_adpcm_ima(*b & 0x0f, &mut first_state[0]);
+            out[1] = decode_adpcm_ima(*b >> 4, &mut first_state[0]);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_adpcm_ima_iss() {
+        // one channel, low nibble first
+        let mut samples = [0i16; 4];
+        assert!(decode_adpcm_ima_iss(&[ 0x64, 0x00, 0x00, 0x00, 0x06, 0x08 ], false,
+            &mut samples).is_ok());
+        assert_eq!(samples, [ 110, 111, 110, 111 ]);
+
+        // two channels, high nibble is the first channel
+        let mut samples = [0i16; 4];
+        assert!(decode_adpcm_ima_iss(&[ 0x64, 0x00, 0x00, 0x00, 0x9c, 0xff, 0x00, 0x00,
+            0x60, 0x86 ], true, &mut samples).is_ok());
+        assert_eq!(samples, [ 110, -100, 109, -90 ]);
+
+        // max step index
+        let mut samples = [0i16; 2];
+        assert!(decode_adpcm_ima_iss(&[ 0x00, 0x00, 0x58, 0x00, 0x00 ], false,
+            &mut samples).is_ok());
+        assert_eq!(samples, [ 4095, 7819 ]);
+
+        // invalid step index, out_samples is left unmodified
+        let mut samples = [0i16; 2];
+        assert!(matches!(decode_adpcm_ima_iss(&[ 0x00, 0x00, 0x59, 0x00, 0x00 ], false,
+            &mut samples), Err(Error::InvalidData)));
+        assert!(matches!(decode_adpcm_ima_iss(&[ 0x00, 0x00, 0x00, 0x01, 0x00 ], false,
+            &mut samples), Err(Error::InvalidData)));
+        assert!(matches!(decode_adpcm_ima_iss(&[ 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff,
+            0x00 ], true, &mut samples), Err(Error::InvalidData)));
+        assert_eq!(samples, [ 0, 0 ]);
+
+        // invalid buf length
+        let mut samples = [0i16; 0];
+        assert!(matches!(decode_adpcm_ima_iss(&[ 0x00, 0x00, 0x00, 0x00 ], true, &mut samples),
+            Err(Error::InvalidBufferSize)));
+
+        // invalid out_samples length
+        let mut samples = [0i16; 3];
+        assert!(matches!(decode_adpcm_ima_iss(&[ 0x64, 0x00, 0x00, 0x00, 0x06, 0x08 ], false,
+            &mut samples), Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::Error;
+use crate::{AdpcmImaState, decode_adpcm_ima, encode_adpcm_ima};
+use crate::bitreader::{BitReader, BitWriter};
+
+// Natural MicroSystems VBXADPCM is IMA ADPCM where the lowest 0 to 3 bits of each 4-bit code
+// are truncated. The decoder fills the truncated bits with zeros, so the step index and
+// the predictor are updated exactly like IMA ADPCM.
+
+/// Returns the number of bits of a VBXADPCM code with `truncation` truncated bits (0..=3).
+/// Truncation values larger than 3 are limited to 3.
+pub fn adpcm_ima_vbx_bits(truncation: u8) -> u8 {
+    4 - truncation.min(3)
+}
+
+/// Decodes a NMS VBXADPCM code to a linear 16-bit signed integer sample value.
+///
+/// The code has `4 - truncation` bits: the IMA ADPCM sign bit followed by the highest
+/// magnitude bits. The truncated lowest bits are decoded as zeros. Truncation values larger
+/// than 3 are limited to 3. A zero truncation is the same as
+/// [`decode_adpcm_ima()`](crate::decode_adpcm_ima).
+///
+/// Only the lowest `4 - truncation` bits of `code` are used and the top-most bits are ignored.
+///
+/// The `state` parameter should be initialized to zero at the start of the stream.
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn decode_adpcm_ima_vbx(code: u8, truncation: u8, state: &mut AdpcmImaState) -> i16 {
+    let truncation = truncation.min(3);
+    decode_adpcm_ima((code << truncation) & 0x0f, state)
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a NMS VBXADPCM code with
+/// `truncation` truncated bits.
+///
+/// The returned code has `4 - truncation` bits. Truncation values larger than 3 are limited
+/// to 3. A zero truncation is the same as [`encode_adpcm_ima()`](crate::encode_adpcm_ima).
+/// Otherwise, the code with the smallest error is selected.
+///
+/// The `state` parameter should be initialized to zero at the start of the stream.
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn encode_adpcm_ima_vbx(sample_value: i16, truncation: u8, state: &mut AdpcmImaState)
+    -> u8 {
+    let truncation = truncation.min(3);
+    if truncation == 0 {
+        return encode_adpcm_ima(sample_value, state);
+    }
+    let mut best_code = 0;
+    let mut best_error = u32::MAX;
+    let mut best_state = state.clone();
+    for code in 0..(1u8 << (4 - truncation)) {
+        let mut candidate = state.clone();
+        let decoded = decode_adpcm_ima_vbx(code, truncation, &mut candidate);
+        let error = (i32::from(sample_value) - i32::from(decoded)).unsigned_abs();
+        if error < best_error {
+            best_code = code;
+            best_error = error;
+            best_state = candidate;
+        }
+    }
+    *state = best_state;
+    best_code
+}
+
+/// Decodes NMS VBXADPCM codes with `truncation` truncated bits to 16-bit signed integer
+/// samples.
+///
+/// The `4 - truncation` bit codes are packed most significant bit first. Truncation values
+/// larger than 3 are limited to 3. Bits at the end of `buf` which don't fill a code
+/// are ignored.
+///
+/// The `state` parameter should be initialized to zero at the start of the stream and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `buf.len() * 8 / (4 - truncation)`.
+///
+/// An error is returned if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_adpcm_ima_vbx_slice(buf: &[u8], truncation: u8, state: &mut AdpcmImaState,
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    let bits = adpcm_ima_vbx_bits(truncation);
+    let expected_sample_len = buf.len()
+        .checked_mul(8)
+        .ok_or(Error::InvalidBufferSize)? / usize::from(bits);
+    if expected_sample_len != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut reader = BitReader::new(buf);
+    for out in out_samples.iter_mut() {
+        let code = u8::try_from(reader.read(u32::from(bits)).unwrap_or(0)).unwrap_or(0);
+        *out = decode_adpcm_ima_vbx(code, truncation, state);
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to NMS VBXADPCM codes with `truncation` truncated
+/// bits.
+///
+/// The `4 - truncation` bit codes are packed most significant bit first. Truncation values
+/// larger than 3 are limited to 3. The unused bits of the last byte are set to zero.
+///
+/// The `state` parameter should be initialized to zero at the start of the stream and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs encoded codes to `out_buf`. The `out_buf` length must be
+/// `(samples.len() * (4 - truncation)).div_ceil(8)`.
+///
+/// An error is returned if the `out_buf` length isn't correct.
+/// If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_adpcm_ima_vbx_slice(samples: &[i16], truncation: u8, state: &mut AdpcmImaState,
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    let bits = adpcm_ima_vbx_bits(truncation);
+    let expected_buf_len = samples.len()
+        .checked_mul(usize::from(bits))
+        .ok_or(Error::InvalidBufferSize)?
+        .div_ceil(8);
+    if expected_buf_len != out_buf.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    out_buf.fill(0);
+    let mut writer = BitWriter::new(out_buf);
+    for sample in samples {
+        let code = encode_adpcm_ima_vbx(*sample, truncation, state);
+        writer.write(u32::from(code), u32::from(bits));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signal::tone;
+
+    #[test]
+    fn test_adpcm_ima_vbx_bits() {
+        assert_eq!(adpcm_ima_vbx_bits(0), 4);
+        assert_eq!(adpcm_ima_vbx_bits(1), 3);
+        assert_eq!(adpcm_ima_vbx_bits(3), 1);
+        assert_eq!(adpcm_ima_vbx_bits(200), 1);
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_vbx() {
+        // zero truncation is IMA ADPCM
+        let mut state = AdpcmImaState::new();
+        let mut ima_state = AdpcmImaState::new();
+        for code in 0..16 {
+            assert_eq!(decode_adpcm_ima_vbx(code, 0, &mut state),
+                decode_adpcm_ima(code, &mut ima_state));
+        }
+        // the truncated bits are zeros: 3-bit code 3 is IMA code 6 and 2-bit code 3 is 12
+        let mut state = AdpcmImaState::new();
+        let mut ima_state = AdpcmImaState::new();
+        assert_eq!(decode_adpcm_ima_vbx(3, 1, &mut state), decode_adpcm_ima(6, &mut ima_state));
+        assert_eq!(decode_adpcm_ima_vbx(0x0b, 2, &mut state),
+            decode_adpcm_ima(12, &mut ima_state));
+        assert_eq!(decode_adpcm_ima_vbx(1, 3, &mut state), decode_adpcm_ima(8, &mut ima_state));
+        assert_eq!(decode_adpcm_ima_vbx(1, 4, &mut state), decode_adpcm_ima(8, &mut ima_state));
+        assert_eq!(state, ima_state);
+    }
+
+    #[test]
+    fn test_encode_adpcm_ima_vbx() {
+        for truncation in 0..4 {
+            let mut encoder_state = AdpcmImaState::new();
+            let mut decoder_state = AdpcmImaState::new();
+            let mut error = 0;
+            for i in 0..1000 {
+                let sample = tone(i);
+                let code = encode_adpcm_ima_vbx(sample, truncation, &mut encoder_state);
+                assert!(code < 1 << adpcm_ima_vbx_bits(truncation));
+                let decoded = decode_adpcm_ima_vbx(code, truncation, &mut decoder_state);
+                assert_eq!(encoder_state, decoder_state);
+                if i >= 100 {
+                    error = error.max((i32::from(sample) - i32::from(decoded)).abs());
+                }
+            }
+            // more truncated bits give larger errors
+            match truncation {
+                0 => assert!(error < 1000),
+                1 => assert!(error < 2000),
+                2 => assert!(error < 8000),
+                _ => (),
+            }
+        }
+        // zero truncation is IMA ADPCM
+        let mut state = AdpcmImaState::new();
+        let mut ima_state = AdpcmImaState::new();
+        for i in 0..100 {
+            assert_eq!(encode_adpcm_ima_vbx(tone(i), 0, &mut state),
+                encode_adpcm_ima(tone(i), &mut ima_state));
+        }
+    }
+
+    #[test]
+    fn test_adpcm_ima_vbx_slice() {
+        let samples = [ 100, 2000, 3000, -500, -4000, 0 ];
+        let mut state = AdpcmImaState::new();
+        let mut buf = [0u8; 3];
+        assert!(encode_adpcm_ima_vbx_slice(&samples, 1, &mut state, &mut buf).is_ok());
+        // 3-bit codes packed most significant bit first
+        let mut expected_state = AdpcmImaState::new();
+        let codes = samples.map(|s| encode_adpcm_ima_vbx(s, 1, &mut expected_state));
+        let packed = codes.iter().fold(0u32, |acc, c| (acc << 3) | u32::from(*c)) << 6;
+        assert_eq!(buf, packed.to_be_bytes()[1..]);
+        assert_eq!(state, expected_state);
+
+        // 24 bits contain 8 codes, the last two are zero padding
+        let mut state = AdpcmImaState::new();
+        let mut out = [0i16; 8];
+        assert!(decode_adpcm_ima_vbx_slice(&buf, 1, &mut state, &mut out[..6]).is_err());
+        assert!(decode_adpcm_ima_vbx_slice(&buf, 1, &mut state, &mut out).is_ok());
+        let mut expected_state = AdpcmImaState::new();
+        for (code, out) in codes.iter().zip(out) {
+            assert_eq!(decode_adpcm_ima_vbx(*code, 1, &mut expected_state), out);
+        }
+
+        let mut state = AdpcmImaState::new();
+        assert!(matches!(encode_adpcm_ima_vbx_slice(&samples, 1, &mut state, &mut buf[..2]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_adpcm_ima_vbx_slice(&buf, 0, &mut state, &mut out),
+            Err(Error::InvalidBufferSize)));
+        assert_eq!(state, AdpcmImaState::new());
+        let mut buf = [0u8; 1];
+        assert!(encode_adpcm_ima_vbx_slice(&samples, 3, &mut state, &mut buf).is_ok());
+        assert_eq!(buf[0] & 0x03, 0);
+    }
+}
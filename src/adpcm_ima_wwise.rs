@@ -0,0 +1,96 @@
+use crate::Error;
+use crate::{AdpcmImaState, decode_adpcm_ima};
+
+/// Decodes an Audiokinetic Wwise IMA ADPCM compressed block to 16-bit signed integer samples.
+///
+/// `buf` should contain 4 header bytes for each channel (16-bit little-endian predictor,
+/// 8-bit step index and an unused byte) followed by bytes of 4-bit encoded samples.
+/// Unlike in MS IMA ADPCM, the encoded samples aren't interleaved in 4 byte groups: all
+/// the bytes of the first channel are followed by all the bytes of the second channel.
+/// For 1 channel audio, the `buf` length must be at least 4. For 2 channel audio, the `buf`
+/// length must be at least 8 and it must be divisible by 2.
+///
+/// The header predictor of each channel is output as the first sample. The low nibble of each
+/// byte is decoded first. The last nibble of each channel isn't decoded, so that the number of
+/// samples is the same as the number of nibbles.
+///
+/// `is_stereo` should be `false` for 1 channel (mono) audio and `true` for
+/// 2 channel (stereo) audio.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * (buf.len() - 4)` for 1 channel audio and `2 * (buf.len() - 8)` for 2 channel audio.
+/// Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ima_wwise(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    if buf.len() < 4 * channels || buf.len() % channels != 0 {
+        return Err(Error::InvalidBufferSize);
+    }
+    let expected_sample_len = (buf.len() - 4 * channels)
+        .checked_mul(2)
+        .ok_or(Error::InvalidBufferSize)?;
+    if expected_sample_len != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    let (headers, data) = buf.split_at(4 * channels);
+    let channel_size = data.len() / channels;
+    for ch in 0..channels {
+        let mut state = AdpcmImaState {
+            predictor: i16::from_le_bytes([ headers[ch*4], headers[ch*4+1] ]),
+            // clamp step index to 0..=88 so that something is decoded
+            step_index: headers[ch*4+2].min(88),
+        };
+        let channel_data = &data[ch * channel_size..(ch + 1) * channel_size];
+        let samples = core::iter::once(state.predictor).chain(channel_data.iter()
+            .flat_map(|b| [ *b & 0x0f, *b >> 4 ])
+            .map(|nibble| decode_adpcm_ima(nibble, &mut state)));
+        for (out, sample) in out_samples.iter_mut().skip(ch).step_by(channels).zip(samples) {
+            *out = sample;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_adpcm_ima_wwise() {
+        // one channel, header predictor first, low nibble first, last nibble isn't decoded
+        let mut samples = [0i16; 4];
+        assert!(decode_adpcm_ima_wwise(&[ 0x64, 0x00, 0x00, 0x00, 0x06, 0x78 ], false,
+            &mut samples).is_ok());
+        assert_eq!(samples, [ 100, 110, 111, 110 ]);
+
+        // two channels, channel data isn't interleaved
+        let mut samples = [0i16; 4];
+        assert!(decode_adpcm_ima_wwise(&[ 0x64, 0x00, 0x00, 0x00, 0x9c, 0xff, 0x00, 0x00,
+            0x06, 0x60 ], true, &mut samples).is_ok());
+        assert_eq!(samples, [ 100, -100, 110, -100 ]);
+
+        // step index is clamped
+        let mut samples = [0i16; 2];
+        assert!(decode_adpcm_ima_wwise(&[ 0x00, 0x00, 0xff, 0x00, 0x00 ], false,
+            &mut samples).is_ok());
+        assert_eq!(samples, [ 0, 4095 ]);
+
+        // invalid buf length
+        let mut samples = [0i16; 2];
+        assert!(matches!(decode_adpcm_ima_wwise(&[ 0, 0, 0, 0, 0, 0, 0, 0, 0 ], true,
+            &mut samples), Err(Error::InvalidBufferSize)));
+
+        // invalid out_samples length
+        let mut samples = [0i16; 5];
+        assert!(matches!(decode_adpcm_ima_wwise(&[ 0x64, 0x00, 0x00, 0x00, 0x06, 0x08 ], false,
+            &mut samples), Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,88 @@
+
+use crate::Error;
+use crate::decode_adpcm_ima_ms_with;
+
+/// Xbox IMA ADPCM (wav format 0x0069) block size in bytes for each channel.
+pub const XBOX_ADPCM_BLOCK_SIZE: usize = 36;
+
+/// Number of samples for each channel in a Xbox IMA ADPCM block.
+pub const XBOX_ADPCM_SAMPLES_PER_BLOCK: usize = 64;
+
+/// Decodes a Xbox IMA ADPCM (wav format 0x0069) compressed block to 16-bit signed integer
+/// samples. This format is used in Xbox wave banks (XWB) and wav files.
+///
+/// Xbox IMA ADPCM is MS IMA ADPCM with a fixed block size of 36 bytes for each channel: 4 header
+/// bytes (predictor and step index) for each channel followed by 4 byte groups of 4-bit encoded
+/// samples for each channel in turn. Unlike MS IMA ADPCM, the header predictor isn't output as
+/// a sample, so each block contains 64 samples for each channel.
+///
+/// `is_stereo` should be `false` for 1 channel (mono) audio and `true` for
+/// 2 channel (stereo) audio. The `buf` length must be 36 for 1 channel audio and 72 for
+/// 2 channel audio.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// 64 for 1 channel audio and 128 for 2 channel audio. Samples are interleaved for
+/// 2 channel audio.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ima_xbox(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    if buf.len() != XBOX_ADPCM_BLOCK_SIZE * channels ||
+        out_samples.len() != XBOX_ADPCM_SAMPLES_PER_BLOCK * channels {
+        return Err(Error::InvalidBufferSize);
+    }
+    // skip the header predictors, which are output first
+    let mut index = 0;
+    let mut out = out_samples.iter_mut();
+    decode_adpcm_ima_ms_with(buf, is_stereo, &mut |s| {
+        if index >= channels {
+            if let Some(o) = out.next() {
+                *o = s;
+            }
+        }
+        index += 1;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_adpcm_ima_ms;
+
+    #[test]
+    fn test_decode_adpcm_ima_xbox() {
+        let mut buf = [0u8; 72];
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = u8::try_from(i * 13 % 256).unwrap_or(0);
+        }
+        // valid step indexes
+        buf[2] = 10;
+        buf[6] = 20;
+
+        // same as MS IMA ADPCM without the header samples
+        let mut expected = [0i16; 65];
+        assert!(decode_adpcm_ima_ms(&buf[..36], false, &mut expected).is_ok());
+        let mut samples = [0i16; 64];
+        assert!(decode_adpcm_ima_xbox(&buf[..36], false, &mut samples).is_ok());
+        assert_eq!(samples[..], expected[1..]);
+
+        let mut expected = [0i16; 130];
+        assert!(decode_adpcm_ima_ms(&buf, true, &mut expected).is_ok());
+        let mut samples = [0i16; 128];
+        assert!(decode_adpcm_ima_xbox(&buf, true, &mut samples).is_ok());
+        assert_eq!(samples[..], expected[2..]);
+
+        // invalid lengths
+        assert!(matches!(decode_adpcm_ima_xbox(&buf[..36], true, &mut samples),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_adpcm_ima_xbox(&buf, true, &mut samples[..64]),
+            Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,489 @@
+
+use crate::Error;
+
+/// The seven standard MS ADPCM predictor coefficient pairs (in 1/256 units).
+///
+/// WAV files store the coefficient table in the format chunk, and the standard table is
+/// always at the beginning of it. See [`parse_adpcm_ms_coefficients()`] for
+/// reading custom tables.
+pub const ADPCM_MS_COEFFICIENTS: &[[i16; 2]; 7] = &[
+    [ 256, 0 ],
+    [ 512, -256 ],
+    [ 0, 0 ],
+    [ 192, 64 ],
+    [ 240, 0 ],
+    [ 460, -208 ],
+    [ 392, -232 ],
+];
+
+const ADPCM_MS_ADAPTATION_TABLE: &[i32; 16] = &[
+    230, 230, 230, 230, 307, 409, 512, 614,
+    768, 614, 512, 409, 307, 230, 230, 230
+];
+
+// limit delta so that the adaptation never overflows
+const ADPCM_MS_MAX_DELTA: i32 = i32::MAX / 768;
+
+/// Decoder and encoder state of one MS ADPCM channel.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AdpcmMsChannel {
+    pub(crate) coefficient1: i32,
+    pub(crate) coefficient2: i32,
+    pub(crate) delta: i32,
+    pub(crate) sample1: i16,
+    pub(crate) sample2: i16,
+}
+
+impl AdpcmMsChannel {
+    pub(crate) fn new(coefficients: [i16; 2], delta: i32, sample1: i16, sample2: i16)
+        -> AdpcmMsChannel {
+        AdpcmMsChannel {
+            coefficient1: i32::from(coefficients[0]),
+            coefficient2: i32::from(coefficients[1]),
+            delta,
+            sample1,
+            sample2,
+        }
+    }
+
+    pub(crate) fn predict(&self) -> i32 {
+        // custom coefficients can be any 16-bit values, so the sum may not fit in 32 bits
+        let prediction = (i64::from(self.sample1) * i64::from(self.coefficient1) +
+            i64::from(self.sample2) * i64::from(self.coefficient2)) >> 8;
+        // the prediction is always in the range of -2^23..=2^23, so clamping doesn't change it
+        i32::try_from(prediction.clamp(-0x80_0000, 0x80_0000)).unwrap_or(0)
+    }
+
+    pub(crate) fn decode(&mut self, nibble: u8) -> i16 {
+        let nibble = nibble & 0x0f;
+        let signed_nibble = (i32::from(nibble) ^ 8) - 8;
+        let predictor = self.predict() + signed_nibble * self.delta;
+        self.sample2 = self.sample1;
+        #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+        {
+        self.sample1 = predictor.clamp(-32768, 32767) as i16;
+        }
+        self.delta = ((ADPCM_MS_ADAPTATION_TABLE[usize::from(nibble)] * self.delta) >> 8)
+            .clamp(16, ADPCM_MS_MAX_DELTA);
+        self.sample1
+    }
+
+    pub(crate) fn encode(&mut self, sample_value: i16) -> u8 {
+        let diff = i32::from(sample_value) - self.predict();
+        // round to the nearest code
+        let bias = if diff >= 0 { self.delta / 2 } else { -(self.delta / 2) };
+        let code = ((diff + bias) / self.delta).clamp(-8, 7);
+        let nibble = code.to_le_bytes()[0] & 0x0f;
+        self.decode(nibble);
+        nibble
+    }
+}
+
+/// Reads a custom MS ADPCM coefficient table from the WAV format chunk extension.
+///
+/// `buf` should contain the extension bytes after the `cbSize` field: samples per block
+/// (2 bytes), the number of coefficient pairs (2 bytes) and the coefficient pairs
+/// (4 bytes each). The coefficient pairs are written to `out_coefficients` and their number
+/// is returned. A block can refer to 256 pairs at most, so `out_coefficients` doesn't need
+/// to be longer than that.
+///
+/// An error is returned if `buf` is too short, if there are no coefficient pairs or
+/// if `out_coefficients` is too short.
+/// If an error is returned, `out_coefficients` is left unmodified.
+pub fn parse_adpcm_ms_coefficients(buf: &[u8], out_coefficients: &mut [[i16; 2]])
+    -> Result<usize, Error> {
+
+    if buf.len() < 4 {
+        return Err(Error::InvalidBufferSize);
+    }
+    let count = usize::from(u16::from_le_bytes([ buf[2], buf[3] ]));
+    if count == 0 {
+        return Err(Error::InvalidData);
+    }
+    if buf.len() < 4 + count * 4 || out_coefficients.len() < count {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (pair, out) in buf[4..4 + count * 4].chunks_exact(4).zip(out_coefficients.iter_mut()) {
+        *out = [
+            i16::from_le_bytes([ pair[0], pair[1] ]),
+            i16::from_le_bytes([ pair[2], pair[3] ]),
+        ];
+    }
+    Ok(count)
+}
+
+/// Decodes a WAV / MS ADPCM (wav format 0x0002) compressed block to 16-bit signed integer
+/// samples.
+///
+/// This uses the seven standard coefficient pairs. See
+/// [`decode_adpcm_ms_with_coefficients()`] for files with custom coefficient tables.
+///
+/// `buf` should contain one block. The block starts with a header for each channel:
+/// the coefficient index (1 byte), the initial delta (2 bytes) and two history samples
+/// (2 bytes each). For 2 channel audio, header fields alternate between channels.
+/// The rest of the block contains 4-bit encoded samples, high nibble first.
+/// For 2 channel audio, the high nibble is the left channel and the low nibble is
+/// the right channel.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * buf.len() - 12` for 1 channel audio and `2 * buf.len() - 24` for 2 channel audio.
+/// Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if the `buf` length is shorter than the header,
+/// if the `out_samples` length isn't correct or if a coefficient index is invalid.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ms(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
+    -> Result<(), Error> {
+    decode_adpcm_ms_with_coefficients(buf, is_stereo, ADPCM_MS_COEFFICIENTS, out_samples)
+}
+
+/// Decodes a WAV / MS ADPCM (wav format 0x0002) compressed block to 16-bit signed integer
+/// samples using a custom coefficient table.
+///
+/// This works like [`decode_adpcm_ms()`], but the block coefficient indexes refer to
+/// `coefficients`, which can be read from the WAV format chunk with
+/// [`parse_adpcm_ms_coefficients()`].
+///
+/// An error is returned if the `buf` length is shorter than the header,
+/// if the `out_samples` length isn't correct or if a coefficient index is invalid.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ms_with_coefficients(buf: &[u8], is_stereo: bool,
+    coefficients: &[[i16; 2]], out_samples: &mut [i16]) -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    if buf.len() < 7 * channels {
+        return Err(Error::InvalidBufferSize);
+    }
+    if (buf.len() - 7 * channels) * 2 + 2 * channels != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    let read_i16 = |pos: usize| i16::from_le_bytes([ buf[pos], buf[pos+1] ]);
+    let mut states = [
+        AdpcmMsChannel::new([ 0, 0 ], 16, 0, 0),
+        AdpcmMsChannel::new([ 0, 0 ], 16, 0, 0),
+    ];
+    for (ch, state) in states.iter_mut().enumerate().take(channels) {
+        let Some(pair) = coefficients.get(usize::from(buf[ch])) else {
+            return Err(Error::InvalidData);
+        };
+        *state = AdpcmMsChannel::new(*pair,
+            i32::from(read_i16(channels + ch * 2)),
+            read_i16(channels * 3 + ch * 2),
+            read_i16(channels * 5 + ch * 2));
+    }
+    // the second history sample is the first output sample
+    for ch in 0..channels {
+        out_samples[ch] = states[ch].sample2;
+        out_samples[channels + ch] = states[ch].sample1;
+    }
+    // for 2 channel audio, the high nibble is for the left channel and the low nibble is
+    // for the right channel
+    let out_pairs = out_samples[2 * channels..].chunks_exact_mut(2);
+    for (b, out) in buf[7 * channels..].iter().zip(out_pairs) {
+        out[0] = states[0].decode(b >> 4);
+        out[1] = states[channels - 1].decode(b & 0x0f);
+    }
+    Ok(())
+}
+
+// initial delta for a block, the average prediction error of the first samples is
+// roughly 2 deltas
+fn initial_delta(coefficients: [i16; 2], samples: &[i16], channels: usize, ch: usize) -> i32 {
+    let mut state = AdpcmMsChannel::new(coefficients, 16, samples[channels + ch], samples[ch]);
+    let mut total = 0;
+    let mut count = 0;
+    for s in samples.iter().skip(2 * channels + ch).step_by(channels).take(16) {
+        total += (i32::from(*s) - state.predict()).abs();
+        count += 1;
+        state.sample2 = state.sample1;
+        state.sample1 = *s;
+    }
+    if count == 0 {
+        return 16;
+    }
+    (total / count / 2).clamp(16, 32767)
+}
+
+// encodes one channel and returns the squared error
+fn encode_channel(state: &mut AdpcmMsChannel, samples: &[i16], channels: usize, ch: usize,
+    out_nibbles: &mut impl FnMut(usize, u8)) -> u64 {
+
+    let mut error = 0u64;
+    let channel_samples = samples.iter().skip(2 * channels + ch).step_by(channels);
+    for (i, s) in channel_samples.enumerate() {
+        let nibble = state.encode(*s);
+        out_nibbles(i, nibble);
+        let diff = u64::from((i32::from(*s) - i32::from(state.sample1)).unsigned_abs());
+        error = error.saturating_add(diff * diff);
+    }
+    error
+}
+
+/// Encodes 16-bit signed integer samples to a WAV / MS ADPCM (wav format 0x0002)
+/// compressed block.
+///
+/// The coefficient pair is selected separately for each channel by encoding the block with
+/// all seven standard coefficient pairs and selecting the one with the smallest error.
+/// The initial delta is calculated from the first samples of the block.
+/// See [`encode_adpcm_ms_with_coefficients()`] for custom coefficient tables.
+///
+/// Only 1 or 2 channel audio data is supported. Samples must be interleaved for 2 channel
+/// audio. The first two samples of each channel are stored in the block header.
+///
+/// This function outputs encoded bytes to `out_buf`. The `samples` length must be
+/// `2 * out_buf.len() - 12` for 1 channel audio and `2 * out_buf.len() - 24` for 2 channel
+/// audio.
+///
+/// Usually, for 1 channel (mono) audio, the `out_buf` length is 256 and
+/// the `samples` length is 500.
+///
+/// An error is returned if the `samples` or `out_buf` length isn't correct.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn encode_adpcm_ms(samples: &[i16], is_stereo: bool, out_buf: &mut [u8])
+    -> Result<(), Error> {
+    encode_adpcm_ms_with_coefficients(samples, is_stereo, ADPCM_MS_COEFFICIENTS, out_buf)
+}
+
+/// Encodes 16-bit signed integer samples to a WAV / MS ADPCM (wav format 0x0002)
+/// compressed block using a custom coefficient table.
+///
+/// This works like [`encode_adpcm_ms()`], but the coefficient pair is selected from
+/// `coefficients`, which must be the same table stored in the WAV format chunk.
+/// `coefficients` may contain 256 pairs at most.
+///
+/// An error is returned if `coefficients` is empty or longer than 256 pairs or
+/// if the `samples` or `out_buf` length isn't correct.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn encode_adpcm_ms_with_coefficients(samples: &[i16], is_stereo: bool,
+    coefficients: &[[i16; 2]], out_buf: &mut [u8]) -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    if coefficients.is_empty() || coefficients.len() > 256 {
+        return Err(Error::InvalidData);
+    }
+    if out_buf.len() < 7 * channels ||
+        (out_buf.len() - 7 * channels) * 2 + 2 * channels != samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for ch in 0..channels {
+        // select the coefficient pair with the smallest error
+        let mut best_index = 0;
+        let mut best_error = u64::MAX;
+        for (index, pair) in coefficients.iter().enumerate() {
+            let delta = initial_delta(*pair, samples, channels, ch);
+            let mut state = AdpcmMsChannel::new(*pair, delta, samples[channels + ch], samples[ch]);
+            let error = encode_channel(&mut state, samples, channels, ch, &mut |_, _| {});
+            if error < best_error {
+                best_index = index;
+                best_error = error;
+            }
+        }
+        let pair = coefficients[best_index];
+        let delta = initial_delta(pair, samples, channels, ch);
+        out_buf[ch] = u8::try_from(best_index).unwrap_or(0);
+        out_buf[channels + ch * 2..channels + ch * 2 + 2]
+            .copy_from_slice(&i16::try_from(delta).unwrap_or(16).to_le_bytes());
+        out_buf[channels * 3 + ch * 2..channels * 3 + ch * 2 + 2]
+            .copy_from_slice(&samples[channels + ch].to_le_bytes());
+        out_buf[channels * 5 + ch * 2..channels * 5 + ch * 2 + 2]
+            .copy_from_slice(&samples[ch].to_le_bytes());
+        // for 2 channel audio, the high nibble is for the left channel and the low nibble is
+        // for the right channel
+        let data = &mut out_buf[7 * channels..];
+        let mut state = AdpcmMsChannel::new(pair, delta, samples[channels + ch], samples[ch]);
+        encode_channel(&mut state, samples, channels, ch, &mut |i, nibble| {
+            let (pos, is_high) = if channels == 1 {
+                (i / 2, i % 2 == 0)
+            } else {
+                (i, ch == 0)
+            };
+            if is_high {
+                data[pos] = (data[pos] & 0x0f) | (nibble << 4);
+            } else {
+                data[pos] = (data[pos] & 0xf0) | nibble;
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_adpcm_ms_mono() {
+        // coefficient index 0, delta 16, sample1 100, sample2 50
+        let buf = [ 0, 16, 0, 100, 0, 50, 0, 0x1f, 0x80 ];
+        let mut out = [0i16; 6];
+        assert!(decode_adpcm_ms(&buf, false, &mut out).is_ok());
+        // prediction is sample1, delta adapts: 16 -> 16 (min), 16 -> 16 (min), 16 -> 48
+        assert_eq!(out, [ 50, 100, 116, 100, -28, -28 ]);
+    }
+
+    #[test]
+    fn test_decode_adpcm_ms_stereo() {
+        // left: coefficient index 1, delta 20, sample1 10, sample2 0
+        // right: coefficient index 2, delta 100, sample1 -5, sample2 -6
+        let buf = [
+            1, 2,
+            20, 0, 100, 0,
+            10, 0, 0xfb, 0xff,
+            0, 0, 0xfa, 0xff,
+            0x27,
+        ];
+        let mut out = [0i16; 6];
+        assert!(decode_adpcm_ms(&buf, true, &mut out).is_ok());
+        // left prediction is 2 * 10 - 0 = 20, right prediction is 0
+        assert_eq!(out, [ 0, -6, 10, -5, 60, 700 ]);
+    }
+
+    #[test]
+    fn test_decode_adpcm_ms_errors() {
+        let buf = [0u8; 16];
+        let mut out = [0i16; 20];
+        assert!(matches!(decode_adpcm_ms(&buf[..6], false, &mut out[..0]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_adpcm_ms(&buf, false, &mut out[..19]),
+            Err(Error::InvalidBufferSize)));
+        assert!(decode_adpcm_ms(&buf, false, &mut out[..20]).is_ok());
+        assert!(decode_adpcm_ms(&buf[..14], true, &mut out[..4]).is_ok());
+        assert!(matches!(decode_adpcm_ms(&buf[..13], true, &mut out[..2]),
+            Err(Error::InvalidBufferSize)));
+
+        let mut buf = [0u8; 8];
+        buf[0] = 7;
+        let mut out = [1i16; 4];
+        assert!(matches!(decode_adpcm_ms(&buf, false, &mut out), Err(Error::InvalidData)));
+        assert_eq!(out, [1; 4]);
+    }
+
+    #[test]
+    fn test_adpcm_ms_custom_coefficients() {
+        // 7 standard pairs and a custom pair
+        let mut ext = [0u8; 4 + 8 * 4];
+        ext[0..2].copy_from_slice(&500u16.to_le_bytes());
+        ext[2..4].copy_from_slice(&8u16.to_le_bytes());
+        for (i, pair) in ADPCM_MS_COEFFICIENTS.iter().chain(&[ [ 128, 0 ] ]).enumerate() {
+            ext[4+i*4..6+i*4].copy_from_slice(&pair[0].to_le_bytes());
+            ext[6+i*4..8+i*4].copy_from_slice(&pair[1].to_le_bytes());
+        }
+        let mut coefficients = [[0i16; 2]; 256];
+        let result = parse_adpcm_ms_coefficients(&ext, &mut coefficients);
+        assert!(matches!(result, Ok(8)));
+        assert_eq!(coefficients[..7], ADPCM_MS_COEFFICIENTS[..]);
+        assert_eq!(coefficients[7], [ 128, 0 ]);
+
+        // the custom pair halves the previous sample
+        let buf = [ 7, 16, 0, 100, 0, 0, 0, 0x00 ];
+        let mut out = [0i16; 4];
+        assert!(matches!(decode_adpcm_ms(&buf, false, &mut out), Err(Error::InvalidData)));
+        assert!(decode_adpcm_ms_with_coefficients(&buf, false, &coefficients[..8], &mut out)
+            .is_ok());
+        assert_eq!(out, [ 0, 100, 50, 25 ]);
+
+        // extreme coefficients and history samples don't overflow
+        let buf = [ 0, 16, 0, 0x00, 0x80, 0x00, 0x80, 0x00 ];
+        assert!(decode_adpcm_ms_with_coefficients(&buf, false, &[ [ -32768, -32768 ] ], &mut out)
+            .is_ok());
+        assert_eq!(out, [ -32768, -32768, 32767, 128 ]);
+        let buf = [ 0, 16, 0, 0xff, 0x7f, 0xff, 0x7f, 0x77 ];
+        assert!(decode_adpcm_ms_with_coefficients(&buf, false, &[ [ 32767, 32767 ] ], &mut out)
+            .is_ok());
+        assert_eq!(out, [ 32767, 32767, 32767, 32767 ]);
+
+        assert!(matches!(parse_adpcm_ms_coefficients(&ext[..35], &mut coefficients),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(parse_adpcm_ms_coefficients(&ext, &mut coefficients[..7]),
+            Err(Error::InvalidBufferSize)));
+        ext[2] = 0;
+        assert!(matches!(parse_adpcm_ms_coefficients(&ext, &mut coefficients),
+            Err(Error::InvalidData)));
+    }
+
+    fn test_signal(out: &mut [i16], channels: usize) {
+        for (i, s) in out.iter_mut().enumerate() {
+            let ch = i % channels;
+            let t = i32::try_from(i / channels).unwrap_or(0);
+            let level = [ 0, 7, 10, 7, 0, -7, -10, -7 ][usize::try_from(t).unwrap_or(0) % 8];
+            let value = if ch == 0 { level * 2000 + t } else { level * 300 - 500 };
+            *s = i16::try_from(value).unwrap_or(0);
+        }
+    }
+
+    #[test]
+    fn test_encode_adpcm_ms() {
+        for (is_stereo, channels, block_size) in [ (false, 1, 256), (true, 2, 512) ] {
+            let mut samples = [0i16; 1000];
+            let sample_len = 2 * block_size - 12 * channels;
+            test_signal(&mut samples[..sample_len], channels);
+            let mut buf = [0u8; 512];
+            assert!(encode_adpcm_ms(&samples[..sample_len], is_stereo, &mut buf[..block_size])
+                .is_ok());
+            // the first two samples are in the header
+            for ch in 0..channels {
+                let pos = channels * 3 + ch * 2;
+                assert_eq!(i16::from_le_bytes([ buf[pos], buf[pos+1] ]), samples[channels + ch]);
+            }
+            let mut decoded = [0i16; 1000];
+            assert!(decode_adpcm_ms(&buf[..block_size], is_stereo, &mut decoded[..sample_len])
+                .is_ok());
+            assert_eq!(decoded[..2 * channels], samples[..2 * channels]);
+            for (s, d) in samples[..sample_len].iter().zip(&decoded[..sample_len]) {
+                assert!((i32::from(*s) - i32::from(*d)).abs() < 1500);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_adpcm_ms_coefficient_selection() {
+        // a ramp is predicted exactly by 2 * sample1 - sample2
+        let mut samples = [0i16; 20];
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = i16::try_from(i * 1000).unwrap_or(0);
+        }
+        let mut buf = [0u8; 16];
+        assert!(encode_adpcm_ms(&samples, false, &mut buf).is_ok());
+        assert_eq!(buf[0], 1);
+        let mut decoded = [0i16; 20];
+        assert!(decode_adpcm_ms(&buf, false, &mut decoded).is_ok());
+        assert_eq!(decoded, samples);
+
+        // custom coefficients
+        let coefficients = [ [ 0, 0 ], [ 128, 0 ] ];
+        let samples = [ 0, 1000, 500, 250, 125, 62 ];
+        let mut buf = [0u8; 9];
+        assert!(encode_adpcm_ms_with_coefficients(&samples, false, &coefficients, &mut buf)
+            .is_ok());
+        assert_eq!(buf[0], 1);
+        let mut decoded = [0i16; 6];
+        assert!(decode_adpcm_ms_with_coefficients(&buf, false, &coefficients, &mut decoded)
+            .is_ok());
+        assert_eq!(decoded, samples);
+    }
+
+    #[test]
+    fn test_encode_adpcm_ms_errors() {
+        let samples = [0i16; 24];
+        let mut buf = [0xffu8; 16];
+        assert!(matches!(encode_adpcm_ms(&samples[..19], false, &mut buf),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_adpcm_ms(&samples[..2], false, &mut buf[..6]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_adpcm_ms(&samples[..20], true, &mut buf),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_adpcm_ms_with_coefficients(&samples[..20], false, &[],
+            &mut buf), Err(Error::InvalidData)));
+        assert_eq!(buf, [0xff; 16]);
+        assert!(encode_adpcm_ms(&samples[..20], false, &mut buf).is_ok());
+        assert!(encode_adpcm_ms(&samples[..8], true, &mut buf).is_ok());
+    }
+}
@@ -0,0 +1,327 @@
+use crate::{AdpcmImaBits, AdpcmImaState, Error};
+use crate::adpcm_ima::IMA_STEP_TABLE;
+use crate::bitreader::{BitReader, BitWriter};
+
+// Adobe Flash (SWF) ADPCM. The stream starts with a 2-bit code size (code bits - 2) and
+// is followed by blocks. Each block has a 16-bit initial sample and a 6-bit step index for
+// each channel, and up to 4095 codes for each channel. All values are packed most
+// significant bit first and codes of the channels are interleaved.
+
+/// Number of samples per channel in a full SWF ADPCM block, including the initial sample.
+pub const ADPCM_SWF_SAMPLES_PER_BLOCK: usize = 4096;
+
+// bits of the initial sample and step index of a channel in a block header
+const BLOCK_HEADER_BITS: usize = 22;
+
+// step index adjustments for 2, 3, 4 and 5 bit codes, indexed by the code without the sign bit
+const SWF_INDEX_TABLE_2: &[i8; 2] = &[ -1, 2 ];
+const SWF_INDEX_TABLE_3: &[i8; 4] = &[ -1, -1, 2, 4 ];
+const SWF_INDEX_TABLE_4: &[i8; 8] = &[ -1, -1, -1, -1, 2, 4, 6, 8 ];
+const SWF_INDEX_TABLE_5: &[i8; 16] = &[
+    -1, -1, -1, -1, -1, -1, -1, -1, 1, 2, 4, 6, 8, 10, 13, 16
+];
+
+fn index_table(bits: AdpcmImaBits) -> &'static [i8] {
+    match bits {
+        AdpcmImaBits::Bits2 => SWF_INDEX_TABLE_2,
+        AdpcmImaBits::Bits3 => SWF_INDEX_TABLE_3,
+        AdpcmImaBits::Bits4 => SWF_INDEX_TABLE_4,
+        AdpcmImaBits::Bits5 => SWF_INDEX_TABLE_5,
+    }
+}
+
+fn decode_code(code: u8, bits: AdpcmImaBits, state: &mut AdpcmImaState) -> i16 {
+    let sign_bit = 1 << (bits.bits() - 1);
+    let step_index = state.step_index.min(88);
+    // like IMA ADPCM, the difference is (magnitude + 0.5) * step / 2^(bits - 2)
+    let mut step = i32::from(IMA_STEP_TABLE[usize::from(step_index)]);
+    let mut diff = 0;
+    let mut mask = sign_bit >> 1;
+    while mask != 0 {
+        if code & mask != 0 {
+            diff += step;
+        }
+        step >>= 1;
+        mask >>= 1;
+    }
+    diff += step;
+    let predictor = if code & sign_bit != 0 {
+        i32::from(state.predictor) - diff
+    } else {
+        i32::from(state.predictor) + diff
+    };
+    #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+    {
+    state.predictor = predictor.clamp(-32768, 32767) as i16;
+    }
+    let magnitude = usize::from(code & (sign_bit - 1));
+    let adjustment = index_table(bits).get(magnitude).copied().unwrap_or(0);
+    state.step_index = step_index.saturating_add_signed(adjustment).min(88);
+    state.predictor
+}
+
+// selects the code with the smallest error
+fn encode_code(sample_value: i16, bits: AdpcmImaBits, state: &mut AdpcmImaState) -> u8 {
+    let mut best_code = 0;
+    let mut best_error = u32::MAX;
+    let mut best_state = state.clone();
+    for code in 0..(1u8 << bits.bits()) {
+        let mut candidate = state.clone();
+        let decoded = decode_code(code, bits, &mut candidate);
+        let error = (i32::from(sample_value) - i32::from(decoded)).unsigned_abs();
+        if error < best_error {
+            best_code = code;
+            best_error = error;
+            best_state = candidate;
+        }
+    }
+    *state = best_state;
+    best_code
+}
+
+/// Returns the code size and the number of samples (counting all channels) in
+/// SWF ADPCM data.
+///
+/// The sample count is the maximum number of samples the data can hold. The last byte may
+/// have enough padding bits for extra codes, so the sample count given by the SWF sound tag
+/// should be preferred when it's available.
+///
+/// `is_stereo` should be `false` for 1 channel (mono) audio and `true` for
+/// 2 channel (stereo) audio.
+///
+/// An error is returned if `buf` is empty.
+pub fn adpcm_swf_info(buf: &[u8], is_stereo: bool) -> Result<(AdpcmImaBits, usize), Error> {
+    let channels = if is_stereo { 2 } else { 1 };
+    let Some(&first) = buf.first() else {
+        return Err(Error::InvalidBufferSize);
+    };
+    let bits = AdpcmImaBits::from_bits((first >> 6) + 2).ok_or(Error::InvalidData)?;
+    let total_bits = buf.len() * 8;
+    let code_bits = usize::from(bits.bits()) * channels;
+    let mut pos = 2;
+    let mut count = 0;
+    while pos + BLOCK_HEADER_BITS * channels <= total_bits {
+        pos += BLOCK_HEADER_BITS * channels;
+        let codes = ((total_bits - pos) / code_bits).min(ADPCM_SWF_SAMPLES_PER_BLOCK - 1);
+        pos += codes * code_bits;
+        count += (codes + 1) * channels;
+    }
+    Ok((bits, count))
+}
+
+/// Returns the number of bytes needed to encode `sample_count` samples (counting all channels)
+/// to SWF ADPCM data with `bits` bits per sample.
+pub fn adpcm_swf_encoded_len(sample_count: usize, is_stereo: bool, bits: AdpcmImaBits) -> usize {
+    let channels = if is_stereo { 2 } else { 1 };
+    let frames = sample_count / channels;
+    let blocks = frames.div_ceil(ADPCM_SWF_SAMPLES_PER_BLOCK);
+    let total_bits = 2 + blocks * BLOCK_HEADER_BITS * channels
+        + (frames - blocks) * usize::from(bits.bits()) * channels;
+    total_bits.div_ceil(8)
+}
+
+/// Decodes SWF ADPCM data to 16-bit signed integer samples.
+///
+/// `buf` should contain the sound data of a SWF DefineSound or SoundStreamBlock tag,
+/// starting with the code size.
+///
+/// `is_stereo` should be `false` for 1 channel (mono) audio and `true` for
+/// 2 channel (stereo) audio.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length is
+/// the number of samples to decode, which must be divisible by the number of channels
+/// and at most the sample count returned by [`adpcm_swf_info()`]. Samples are interleaved
+/// for 2 channel audio.
+///
+/// An error is returned if the code size is invalid or if the `buf` or `out_samples`
+/// length isn't correct. If an error is returned, `out_samples` may have been
+/// partially written.
+pub fn decode_adpcm_swf(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = if is_stereo { 2 } else { 1 };
+    let (bits, _) = adpcm_swf_info(buf, is_stereo)?;
+    if out_samples.len() % channels != 0 {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut reader = BitReader::new(buf);
+    reader.read(2).ok_or(Error::InvalidBufferSize)?;
+    let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+    let block_len = ADPCM_SWF_SAMPLES_PER_BLOCK * channels;
+    for block in out_samples.chunks_mut(block_len) {
+        for (ch, state) in states[..channels].iter_mut().enumerate() {
+            let predictor = reader.read_signed(16).ok_or(Error::InvalidBufferSize)?;
+            state.predictor = i16::try_from(predictor).unwrap_or(0);
+            state.step_index = u8::try_from(reader.read(6).ok_or(Error::InvalidBufferSize)?)
+                .unwrap_or(0);
+            block[ch] = state.predictor;
+        }
+        for frame in block[channels..].chunks_exact_mut(channels) {
+            for (sample, state) in frame.iter_mut().zip(states.iter_mut()) {
+                let code = reader.read(u32::from(bits.bits())).ok_or(Error::InvalidBufferSize)?;
+                *sample = decode_code(u8::try_from(code).unwrap_or(0), bits, state);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to SWF ADPCM data.
+///
+/// `samples` are split to blocks of [`ADPCM_SWF_SAMPLES_PER_BLOCK`] samples per channel.
+/// The `samples` length must be divisible by the number of channels. Samples must be
+/// interleaved for 2 channel audio. The code with the smallest error is selected for
+/// each sample. Unused bits of the last byte are set to zero.
+///
+/// `states` must contain channel number of `AdpcmImaState` items (1 or 2). The step indexes
+/// of the state objects are written to the first block, limited to 63 as in the following
+/// blocks, and updated states are stored back when the function returns. They should be
+/// initialized to zero for the first call and subsequent calls should pass in the state values
+/// from the previous call.
+///
+/// This function outputs encoded bytes to `out_buf`. The `out_buf` length must be
+/// [`adpcm_swf_encoded_len()`] for the `samples` length.
+///
+/// An error is returned if `states` has an invalid number of state objects or
+/// if the `samples` or `out_buf` length isn't correct.
+/// If an error is returned, `states` and `out_buf` are left unmodified.
+pub fn encode_adpcm_swf(samples: &[i16], states: &mut [AdpcmImaState], bits: AdpcmImaBits,
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels < 1 || channels > 2 {
+        return Err(Error::InvalidChannels);
+    }
+    if samples.len() % channels != 0
+        || out_buf.len() != adpcm_swf_encoded_len(samples.len(), channels == 2, bits) {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut local_states = [ states[0].clone(), states[channels - 1].clone() ];
+    let mut writer = BitWriter::new(out_buf);
+    writer.write(u32::from(bits.bits() - 2), 2).ok_or(Error::InvalidBufferSize)?;
+    for block in samples.chunks(ADPCM_SWF_SAMPLES_PER_BLOCK * channels) {
+        for (ch, state) in local_states[..channels].iter_mut().enumerate() {
+            state.predictor = block[ch];
+            // the block header can only store step indexes up to 63
+            state.step_index = state.step_index.min(63);
+            let sample = u16::from_le_bytes(block[ch].to_le_bytes());
+            writer.write(u32::from(sample), 16).ok_or(Error::InvalidBufferSize)?;
+            writer.write(u32::from(state.step_index), 6).ok_or(Error::InvalidBufferSize)?;
+        }
+        for frame in block[channels..].chunks_exact(channels) {
+            for (&sample, state) in frame.iter().zip(local_states.iter_mut()) {
+                let code = encode_code(sample, bits, state);
+                writer.write(u32::from(code), u32::from(bits.bits()))
+                    .ok_or(Error::InvalidBufferSize)?;
+            }
+        }
+    }
+    for (state, local) in states.iter_mut().zip(local_states) {
+        *state = local;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_adpcm_ima;
+    use crate::test_signal::tone;
+
+    #[test]
+    fn test_decode_code() {
+        // 4-bit codes are the same as IMA ADPCM
+        let mut state = AdpcmImaState { predictor: 100, step_index: 30 };
+        let mut expected_state = state.clone();
+        for code in [ 7, 0, 15, 3, 12, 5, 8, 1 ] {
+            assert_eq!(decode_code(code, AdpcmImaBits::Bits4, &mut state),
+                decode_adpcm_ima(code, &mut expected_state));
+            assert_eq!(state, expected_state);
+        }
+        // 3-bit: magnitude 3, step 7: 7 + 3 + 1 = 11
+        let mut state = AdpcmImaState::new();
+        assert_eq!(decode_code(3, AdpcmImaBits::Bits3, &mut state), 11);
+        assert_eq!(state, AdpcmImaState { predictor: 11, step_index: 4 });
+        assert_eq!(decode_code(5, AdpcmImaBits::Bits3, &mut state), 11 - 5 - 2);
+        assert_eq!(state.step_index, 3);
+        // 2-bit
+        let mut state = AdpcmImaState { predictor: 0, step_index: 88 };
+        assert_eq!(decode_code(1, AdpcmImaBits::Bits2, &mut state), 32767);
+        assert_eq!(state.step_index, 88);
+    }
+
+    #[test]
+    fn test_decode_adpcm_swf() {
+        // 4-bit mono: initial sample 0x1234, step index 0, codes 7 and 8
+        // 10 0001 0010 0011 0100 000000 0111 1000
+        let buf = [ 0x84, 0x8d, 0x00, 0x78 ];
+        assert!(matches!(adpcm_swf_info(&buf, false), Ok((AdpcmImaBits::Bits4, 3))));
+        let mut out = [0i16; 3];
+        assert!(decode_adpcm_swf(&buf, false, &mut out).is_ok());
+        let mut state = AdpcmImaState { predictor: 0x1234, step_index: 0 };
+        let s1 = decode_adpcm_ima(7, &mut state);
+        let s2 = decode_adpcm_ima(8, &mut state);
+        assert_eq!(out, [ 0x1234, s1, s2 ]);
+
+        assert!(matches!(decode_adpcm_swf(&buf, false, &mut [0; 4]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_adpcm_swf(&buf, true, &mut [0; 3]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(adpcm_swf_info(&[], false), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_adpcm_swf_info() {
+        // a header needs 22 bits for each channel
+        assert!(matches!(adpcm_swf_info(&[ 0; 3 ], false), Ok((AdpcmImaBits::Bits2, 1))));
+        assert!(matches!(adpcm_swf_info(&[ 0; 5 ], true), Ok((AdpcmImaBits::Bits2, 0))));
+        assert!(matches!(adpcm_swf_info(&[ 0xc0; 6 ], true), Ok((AdpcmImaBits::Bits5, 2))));
+        for bits in AdpcmImaBits::ALL {
+            for len in [ 1, 2, 4095, 4096, 4097, 10000 ] {
+                let encoded_len = adpcm_swf_encoded_len(2 * len, true, bits);
+                let mut buf = [0u8; 26000];
+                buf[0] = (bits.bits() - 2) << 6;
+                // padding bits may hold one more code for each channel
+                assert!(matches!(adpcm_swf_info(&buf[..encoded_len], true),
+                    Ok((_, count)) if count == 2 * len || count == 2 * len + 2));
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_adpcm_swf() {
+        let mut samples = [0i16; 2 * 5000];
+        for (i, value) in samples.iter_mut().enumerate() {
+            *value = tone(i / 2);
+            if i % 2 == 1 {
+                *value /= 2;
+            }
+        }
+        let mut previous_error = u64::MAX;
+        for bits in AdpcmImaBits::ALL {
+            let len = adpcm_swf_encoded_len(samples.len(), true, bits);
+            let mut buf = [0u8; 6300];
+            let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+            assert!(encode_adpcm_swf(&samples, &mut states, bits, &mut buf[..len]).is_ok());
+            let mut out = [0i16; 2 * 5000];
+            assert!(decode_adpcm_swf(&buf[..len], true, &mut out).is_ok());
+            // the second block starts with the input sample
+            assert_eq!(out[2 * 4096], samples[2 * 4096]);
+            assert_eq!(out[out.len() - 2], states[0].predictor);
+            assert_eq!(out[out.len() - 1], states[1].predictor);
+            let error = samples.iter().zip(out)
+                .map(|(a, b)| u64::from((i32::from(*a) - i32::from(b)).unsigned_abs()))
+                .sum::<u64>();
+            assert!(error < previous_error);
+            previous_error = error;
+        }
+
+        let mut buf = [0u8; 5];
+        assert!(matches!(encode_adpcm_swf(&[0; 3], &mut [ AdpcmImaState::new() ],
+            AdpcmImaBits::Bits4, &mut buf), Err(Error::InvalidBufferSize)));
+        assert!(encode_adpcm_swf(&[0; 3], &mut [ AdpcmImaState::new() ],
+            AdpcmImaBits::Bits4, &mut buf[..4]).is_ok());
+        assert!(matches!(encode_adpcm_swf(&[0; 3], &mut [], AdpcmImaBits::Bits4, &mut buf),
+            Err(Error::InvalidChannels)));
+    }
+}
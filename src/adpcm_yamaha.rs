@@ -0,0 +1,238 @@
+
+use crate::{CodecState, Error};
+use crate::codec_state::{StateReader, StateWriter};
+
+const YAMAHA_DIFF_TABLE: &[i32; 16] = &[
+    1, 3, 5, 7, 9, 11, 13, 15, -1, -3, -5, -7, -9, -11, -13, -15
+];
+
+const YAMAHA_SCALE_TABLE: &[i32; 8] = &[ 230, 230, 230, 230, 307, 409, 512, 614 ];
+
+/// State values for the Yamaha ADPCM encoder and decoder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdpcmYamahaState {
+    /// The predicted sample value.
+    pub predictor: i16,
+    /// The step size (127..=24576). A zero step size resets the state to initial values.
+    pub step: i16,
+}
+
+impl AdpcmYamahaState {
+    /// Creates a new AdpcmYamahaState with initial values: zero predictor and step size 127.
+    pub fn new() -> AdpcmYamahaState {
+        AdpcmYamahaState { predictor: 0, step: 127 }
+    }
+
+    fn update(&mut self, nibble: u8) {
+        if self.step == 0 {
+            *self = AdpcmYamahaState::new();
+        }
+        let step = i32::from(self.step);
+        let diff = step * YAMAHA_DIFF_TABLE[usize::from(nibble)] / 8;
+        let predictor = i32::from(self.predictor) + diff;
+        #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+        {
+        self.predictor = predictor.clamp(-32768, 32767) as i16;
+        self.step = ((step * YAMAHA_SCALE_TABLE[usize::from(nibble & 7)]) >> 8)
+            .clamp(127, 24576) as i16;
+        }
+    }
+}
+
+impl Default for AdpcmYamahaState {
+    fn default() -> Self {
+        AdpcmYamahaState::new()
+    }
+}
+
+impl CodecState for AdpcmYamahaState {
+    /// 16-bit little-endian predictor and step size.
+    const SERIALIZED_LEN: usize = 4;
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.i16(self.predictor);
+        w.i16(self.step);
+        Ok(())
+    }
+
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        Ok(AdpcmYamahaState {
+            predictor: r.i16(),
+            step: r.i16(),
+        })
+    }
+}
+
+/// Decodes a 4-bit encoded Yamaha ADPCM value to a linear 16-bit signed integer sample value.
+///
+/// Yamaha ADPCM is used in the AICA (Dreamcast), YMZ280B and other Yamaha sound chips.
+/// Unlike IMA ADPCM, the step size is adapted by multiplying it.
+///
+/// Only the lowest 4 bits of `nibble` are used and the top-most bits are ignored.
+///
+/// The `state` parameter should be initialized with [`AdpcmYamahaState::new()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn decode_adpcm_yamaha(nibble: u8, state: &mut AdpcmYamahaState) -> i16 {
+    state.update(nibble & 0x0f);
+    state.predictor
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a 4-bit encoded Yamaha ADPCM value.
+///
+/// The `state` parameter should be initialized with [`AdpcmYamahaState::new()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn encode_adpcm_yamaha(sample_value: i16, state: &mut AdpcmYamahaState) -> u8 {
+    if state.step == 0 {
+        *state = AdpcmYamahaState::new();
+    }
+    let delta = i32::from(sample_value) - i32::from(state.predictor);
+    let magnitude = (delta.unsigned_abs() * 4 / u32::from(state.step.unsigned_abs())).min(7);
+    let mut nibble = u8::try_from(magnitude).unwrap_or(7);
+    if delta < 0 {
+        nibble |= 8;
+    }
+    state.update(nibble);
+    nibble
+}
+
+/// Decodes Yamaha ADPCM encoded bytes to 16-bit signed integer samples.
+///
+/// Each byte contains two samples, the low nibble is decoded first.
+///
+/// The `state` parameter should be initialized with [`AdpcmYamahaState::new()`] and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * buf.len()`.
+///
+/// An error is returned if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_adpcm_yamaha_slice(buf: &[u8], state: &mut AdpcmYamahaState,
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    if buf.len().checked_mul(2) != Some(out_samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.iter().zip(out_samples.chunks_exact_mut(2)) {
+        out[0] = decode_adpcm_yamaha(b & 0x0f, state);
+        out[1] = decode_adpcm_yamaha(b >> 4, state);
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to Yamaha ADPCM encoded bytes.
+///
+/// Each byte contains two samples, the first sample is in the low nibble.
+///
+/// The `state` parameter should be initialized with [`AdpcmYamahaState::new()`] and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs encoded bytes to `out_buf`. The `samples` length must be
+/// `2 * out_buf.len()`.
+///
+/// An error is returned if the `samples` length isn't correct.
+/// If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_adpcm_yamaha_slice(samples: &[i16], state: &mut AdpcmYamahaState,
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    if out_buf.len().checked_mul(2) != Some(samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (s, out) in samples.chunks_exact(2).zip(out_buf.iter_mut()) {
+        let low = encode_adpcm_yamaha(s[0], state);
+        let high = encode_adpcm_yamaha(s[1], state);
+        *out = low | (high << 4);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signal::tone;
+
+    #[test]
+    fn test_decode_adpcm_yamaha() {
+        let mut state = AdpcmYamahaState::new();
+        // 127 * 15 / 8 = 238, step 127 * 614 >> 8 = 304
+        assert_eq!(decode_adpcm_yamaha(7, &mut state), 238);
+        assert_eq!(state, AdpcmYamahaState { predictor: 238, step: 304 });
+        // 304 * -1 / 8 = -38, step 304 * 230 >> 8 = 273
+        assert_eq!(decode_adpcm_yamaha(8, &mut state), 200);
+        assert_eq!(state, AdpcmYamahaState { predictor: 200, step: 273 });
+        // zero step resets the state
+        let mut state = AdpcmYamahaState { predictor: 1000, step: 0 };
+        assert_eq!(decode_adpcm_yamaha(0x17, &mut state), 238);
+
+        // limits
+        let mut state = AdpcmYamahaState { predictor: 32000, step: 24576 };
+        assert_eq!(decode_adpcm_yamaha(7, &mut state), 32767);
+        assert_eq!(state.step, 24576);
+        let mut state = AdpcmYamahaState { predictor: 0, step: 127 };
+        decode_adpcm_yamaha(0, &mut state);
+        assert_eq!(state.step, 127);
+    }
+
+    #[test]
+    fn test_encode_adpcm_yamaha() {
+        let mut encoder_state = AdpcmYamahaState::new();
+        let mut decoder_state = AdpcmYamahaState::new();
+        let mut error = 0;
+        for i in 0..1000 {
+            let sample = tone(i);
+            let nibble = encode_adpcm_yamaha(sample, &mut encoder_state);
+            assert!(nibble < 16);
+            let decoded = decode_adpcm_yamaha(nibble, &mut decoder_state);
+            assert_eq!(encoder_state, decoder_state);
+            if i >= 100 {
+                error = error.max((i32::from(sample) - i32::from(decoded)).abs());
+            }
+        }
+        assert!(error < 2000);
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = AdpcmYamahaState::new();
+        assert!(state.is_default());
+        decode_adpcm_yamaha(7, &mut state);
+        let mut buf = [0u8; AdpcmYamahaState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 238, 0, 0x30, 0x01 ]);
+        assert!(matches!(AdpcmYamahaState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert!(matches!(AdpcmYamahaState::from_bytes(&buf[1..]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 5]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_adpcm_yamaha_slice() {
+        let samples = [ 100, 2000, 3000, -500, -4000, 0 ];
+        let mut state = AdpcmYamahaState::new();
+        let mut buf = [0u8; 3];
+        assert!(encode_adpcm_yamaha_slice(&samples, &mut state, &mut buf).is_ok());
+        let mut expected_state = AdpcmYamahaState::new();
+        let low = encode_adpcm_yamaha(samples[0], &mut expected_state);
+        let high = encode_adpcm_yamaha(samples[1], &mut expected_state);
+        assert_eq!(buf[0], low | (high << 4));
+
+        let mut decoder_state = AdpcmYamahaState::new();
+        let mut out = [0i16; 6];
+        assert!(decode_adpcm_yamaha_slice(&buf, &mut decoder_state, &mut out).is_ok());
+        assert_eq!(decoder_state, state);
+        let mut expected_state = AdpcmYamahaState::new();
+        assert_eq!(out[0], decode_adpcm_yamaha(low, &mut expected_state));
+        assert_eq!(out[1], decode_adpcm_yamaha(high, &mut expected_state));
+
+        assert!(matches!(encode_adpcm_yamaha_slice(&samples[..5], &mut state, &mut buf),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_adpcm_yamaha_slice(&buf, &mut state, &mut out[..5]),
+            Err(Error::InvalidBufferSize)));
+    }
+}
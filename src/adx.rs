@@ -0,0 +1,504 @@
+use crate::Error;
+use crate::sample_history::{decode_code, encode_sample, impl_sample_history, squared_error};
+
+// CRI ADX is a 4-bit ADPCM format with a fixed second order predictor.
+// see https://en.wikipedia.org/wiki/ADX_(file_format)
+
+/// Minimum size of the CRI ADX file header in bytes.
+pub const ADX_HEADER_SIZE: usize = 20;
+
+/// Size of an ADX frame for one channel in bytes: a 16-bit scale and 16 bytes of 4-bit samples.
+pub const ADX_FRAME_SIZE: usize = 18;
+
+/// Number of samples in an ADX frame for one channel.
+pub const ADX_SAMPLES_PER_FRAME: usize = 32;
+
+/// CRI ADX file header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdxHeader {
+    /// Offset of the first frame from the start of the file.
+    pub data_offset: usize,
+    /// Number of channels.
+    pub channels: usize,
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Number of samples for each channel.
+    pub sample_count: u32,
+    /// Cutoff frequency of the predictor in Hz, see [`adx_coefficients()`].
+    pub cutoff_frequency: u16,
+    /// Header version, usually 3 or 4.
+    pub version: u8,
+}
+
+/// Parses the header of a CRI ADX file.
+///
+/// The encoded frames start at `data_offset`, see [`decode_adx()`]. The prediction
+/// coefficients are calculated from the cutoff frequency and the sample rate with
+/// [`adx_coefficients()`].
+///
+/// An error is returned if the `buf` length is less than [`ADX_HEADER_SIZE`], if the header
+/// doesn't start with 0x80 0x00, if it isn't standard 4-bit ADX with 18-byte frames, if the
+/// audio is encrypted or if the number of channels is zero.
+pub fn parse_adx_header(buf: &[u8]) -> Result<AdxHeader, Error> {
+    if buf.len() < ADX_HEADER_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    if buf[0] != 0x80 || buf[1] != 0x00 {
+        return Err(Error::InvalidData);
+    }
+    // encoding type 3 is standard ADX, other types are fixed coefficient,
+    // exponential scale or AHX formats
+    if buf[4] != 3 || usize::from(buf[5]) != ADX_FRAME_SIZE || buf[6] != 4 {
+        return Err(Error::InvalidData);
+    }
+    // encryption flags
+    if buf[19] != 0 {
+        return Err(Error::InvalidData);
+    }
+    let channels = usize::from(buf[7]);
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    Ok(AdxHeader {
+        // the "(c)CRI" copyright string ends just before the data
+        data_offset: usize::from(u16::from_be_bytes([ buf[2], buf[3] ])) + 4,
+        channels,
+        sample_rate: u32::from_be_bytes([ buf[8], buf[9], buf[10], buf[11] ]),
+        sample_count: u32::from_be_bytes([ buf[12], buf[13], buf[14], buf[15] ]),
+        cutoff_frequency: u16::from_be_bytes([ buf[16], buf[17] ]),
+        version: buf[18],
+    })
+}
+
+// square root with Newton's method, core doesn't have f64::sqrt
+fn sqrt(value: f64) -> f64 {
+    if value <= 0.0 {
+        return 0.0;
+    }
+    let mut x = value.max(1.0);
+    for _ in 0..64 {
+        x = (x + value / x) / 2.0;
+    }
+    x
+}
+
+// cosine of 2 * pi * `turns` with the Taylor series, core doesn't have f64::cos
+fn cos_turns(turns: f64) -> f64 {
+    let mut x = 2.0 * core::f64::consts::PI * (turns % 1.0);
+    if x > core::f64::consts::PI {
+        x -= 2.0 * core::f64::consts::PI;
+    }
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    for k in 1..32 {
+        let n = f64::from(2 * k);
+        term *= -x * x / ((n - 1.0) * n);
+        sum += term;
+    }
+    sum
+}
+
+fn round_coefficient(value: f64) -> i16 {
+    let rounded = if value > 0.0 { value + 0.5 } else { value - 0.5 };
+    #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+    let coefficient = rounded.clamp(-32768.0, 32767.0) as i16;
+    coefficient
+}
+
+/// Calculates the two ADX prediction coefficients from the cutoff frequency and
+/// the sample rate.
+///
+/// The coefficients are 4.12 fixed point values. The cutoff frequency is usually 500 Hz.
+///
+/// An error is returned if `sample_rate` is zero.
+pub fn adx_coefficients(cutoff_frequency: u16, sample_rate: u32) -> Result<[i16; 2], Error> {
+    if sample_rate == 0 {
+        return Err(Error::InvalidData);
+    }
+    let sqrt2 = core::f64::consts::SQRT_2;
+    let a = sqrt2 - cos_turns(f64::from(cutoff_frequency) / f64::from(sample_rate));
+    let b = sqrt2 - 1.0;
+    let c = (a - sqrt((a + b) * (a - b))) / b;
+    Ok([ round_coefficient(c * 8192.0), round_coefficient(-c * c * 4096.0) ])
+}
+
+/// State values for the ADX encoder and decoder.
+///
+/// The values should be initialized to zeros at the start of the stream.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AdxState {
+    /// The previous decoded sample.
+    pub previous: i16,
+    /// The sample before the previous decoded sample.
+    pub previous2: i16,
+}
+
+// the coefficients can be any 16-bit values, so the sum is calculated in 64 bits,
+// the result is always in the range of -2^19..=2^19
+fn prediction(coefficients: &[i16; 2], previous: i16, previous2: i16) -> i32 {
+    let prediction = (i64::from(coefficients[0]) * i64::from(previous) +
+        i64::from(coefficients[1]) * i64::from(previous2)) >> 12;
+    i32::try_from(prediction).unwrap_or(0)
+}
+
+impl AdxState {
+    /// Creates a new AdxState with zero values.
+    pub fn new() -> AdxState {
+        AdxState { previous: 0, previous2: 0 }
+    }
+
+    // returns the decoded sample of a 4-bit code (-8..=7) without updating the state
+    fn decoded_value(&self, code: i32, scale: i32, coefficients: &[i16; 2]) -> i16 {
+        let prediction = prediction(coefficients, self.previous, self.previous2);
+        let sample = (code * scale + prediction).clamp(-32768, 32767);
+        i16::try_from(sample).unwrap_or(0)
+    }
+}
+
+impl_sample_history!(AdxState);
+
+/// Decodes an 18-byte ADX frame of one channel to 16-bit signed integer samples.
+///
+/// The frame starts with a 16-bit big-endian scale value followed by 4-bit samples, high
+/// nibble first. The stored scale is one less than the actual scale.
+///
+/// `coefficients` should be calculated with [`adx_coefficients()`]. `state` should be
+/// initialized to zero for the first frame and subsequent calls should pass in the state values
+/// from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`.
+///
+/// An error is returned if the highest bit of the scale value is set. It marks the end of
+/// the stream and the frame doesn't contain audio.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_adx_frame(buf: &[u8; 18], coefficients: &[i16; 2], state: &mut AdxState,
+    out_samples: &mut [i16; 32]) -> Result<(), Error> {
+
+    let stored_scale = u16::from_be_bytes([ buf[0], buf[1] ]);
+    if stored_scale & 0x8000 != 0 {
+        return Err(Error::InvalidData);
+    }
+    let scale = i32::from(stored_scale) + 1;
+    for (out, code) in out_samples.iter_mut().zip(buf[2..].iter()
+        .flat_map(|b| [ *b & 0xf0, *b << 4 ])) {
+        let code = i32::from(i8::from_le_bytes([ code ])) >> 4;
+        *out = decode_code(state, code, |s, code| s.decoded_value(code, scale, coefficients));
+    }
+    Ok(())
+}
+
+/// Decodes ADX frames to 16-bit signed integer samples.
+///
+/// `buf` should contain whole frames of all channels: the frames of each channel follow each
+/// other for every 32 samples. The `buf` length must be a multiple of `18 * states.len()`.
+///
+/// `states` must contain channel number of `AdxState` items. The state objects should be
+/// initialized to zero for the first call and subsequent calls should pass in the state values
+/// from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `32 * buf.len() / 18`. Samples are interleaved for multichannel audio.
+///
+/// An error is returned if `states` is empty, if a frame is an end of stream marker or
+/// if the `buf` length or the `out_samples` length isn't correct.
+/// If an error is returned, `states` and `out_samples` are left unmodified.
+pub fn decode_adx(buf: &[u8], coefficients: &[i16; 2], states: &mut [AdxState],
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    if buf.len() % (ADX_FRAME_SIZE * channels) != 0 ||
+        out_samples.len() != buf.len() / ADX_FRAME_SIZE * ADX_SAMPLES_PER_FRAME {
+        return Err(Error::InvalidBufferSize);
+    }
+    // check end of stream markers before modifying anything
+    if buf.chunks_exact(ADX_FRAME_SIZE).any(|frame| frame[0] & 0x80 != 0) {
+        return Err(Error::InvalidData);
+    }
+    for (frames, out) in buf.chunks_exact(ADX_FRAME_SIZE * channels)
+        .zip(out_samples.chunks_exact_mut(ADX_SAMPLES_PER_FRAME * channels)) {
+        for (ch, (frame, state)) in frames.chunks_exact(ADX_FRAME_SIZE)
+            .zip(states.iter_mut()).enumerate() {
+            let Ok(frame) = frame.try_into() else {
+                return Err(Error::InvalidBufferSize);
+            };
+            let mut samples = [0i16; 32];
+            decode_adx_frame(frame, coefficients, state, &mut samples)?;
+            for (i, sample) in samples.iter().enumerate() {
+                out[i * channels + ch] = *sample;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Encodes 32 samples of one channel to an 18-byte ADX frame.
+///
+/// The scale is estimated from the prediction error and a few scales around it are tried.
+/// The one giving the smallest squared error is used.
+///
+/// `coefficients` should be calculated with [`adx_coefficients()`]. `state` should be
+/// initialized to zero for the first frame and subsequent calls should pass in the state values
+/// from the previous call.
+pub fn encode_adx_frame(samples: &[i16; 32], coefficients: &[i16; 2], state: &mut AdxState,
+    out_buf: &mut [u8; 18]) {
+
+    // estimate the scale from the prediction error of the input samples
+    let mut max_residual = 0;
+    let mut min_residual = 0;
+    let mut history = [ state.previous, state.previous2 ];
+    for sample in samples {
+        let prediction = prediction(coefficients, history[0], history[1]);
+        let residual = i32::from(*sample) - prediction;
+        max_residual = residual.max(max_residual);
+        min_residual = residual.min(min_residual);
+        history = [ *sample, history[0] ];
+    }
+    let base_scale = (max_residual / 7).max(-min_residual / 8).max(1);
+    let mut best_scale = base_scale;
+    let mut best_error = u64::MAX;
+    for k in 2..=8 {
+        let scale = (base_scale * k / 4).clamp(1, 0x8000);
+        let error = squared_error(state, samples,
+            |s, code| s.decoded_value(code, scale, coefficients));
+        if error < best_error {
+            best_scale = scale;
+            best_error = error;
+        }
+    }
+    let stored_scale = u16::try_from(best_scale - 1).unwrap_or(0x7fff);
+    out_buf[0..2].copy_from_slice(&stored_scale.to_be_bytes());
+    for (byte, pair) in out_buf[2..].iter_mut().zip(samples.chunks_exact(2)) {
+        let mut value = 0;
+        for sample in pair {
+            let code = encode_sample(state, *sample,
+                |s, code| s.decoded_value(code, best_scale, coefficients));
+            #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)] // the code is -8..=7
+            let nibble = (code & 0x0f) as u8;
+            value = (value << 4) | nibble;
+        }
+        *byte = value;
+    }
+}
+
+/// Encodes samples to ADX frames.
+///
+/// `samples` should contain interleaved samples for multichannel audio. The `samples` length
+/// must be a multiple of `32 * states.len()`. The frames of each channel follow each other
+/// for every 32 samples, see [`encode_adx_frame()`].
+///
+/// `states` must contain channel number of `AdxState` items. The state objects should be
+/// initialized to zero for the first call and subsequent calls should pass in the state values
+/// from the previous call.
+///
+/// This function outputs the encoded frames to `out_buf`. The `out_buf` length must be
+/// `18 * samples.len() / 32`.
+///
+/// An error is returned if `states` is empty or if the `samples` length or the `out_buf`
+/// length isn't correct.
+/// If an error is returned, `states` and `out_buf` are left unmodified.
+pub fn encode_adx(samples: &[i16], coefficients: &[i16; 2], states: &mut [AdxState],
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    if samples.len() % (ADX_SAMPLES_PER_FRAME * channels) != 0 ||
+        out_buf.len() != samples.len() / ADX_SAMPLES_PER_FRAME * ADX_FRAME_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (frame_samples, frames) in samples.chunks_exact(ADX_SAMPLES_PER_FRAME * channels)
+        .zip(out_buf.chunks_exact_mut(ADX_FRAME_SIZE * channels)) {
+        for (ch, (frame, state)) in frames.chunks_exact_mut(ADX_FRAME_SIZE)
+            .zip(states.iter_mut()).enumerate() {
+            let Ok(frame) = frame.try_into() else {
+                return Err(Error::InvalidBufferSize);
+            };
+            let mut channel_samples = [0i16; 32];
+            for (i, sample) in channel_samples.iter_mut().enumerate() {
+                *sample = frame_samples[i * channels + ch];
+            }
+            encode_adx_frame(&channel_samples, coefficients, state, frame);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CodecState;
+
+    #[test]
+    fn test_parse_adx_header() {
+        let mut buf = [ 0x80, 0x00, 0x00, 0x1c, 0x03, 0x12, 0x04, 0x02,
+            0x00, 0x00, 0xac, 0x44, 0x00, 0x01, 0x00, 0x00, 0x01, 0xf4, 0x04, 0x00 ];
+        assert_eq!(parse_adx_header(&buf).ok(), Some(AdxHeader {
+            data_offset: 32,
+            channels: 2,
+            sample_rate: 44100,
+            sample_count: 65536,
+            cutoff_frequency: 500,
+            version: 4,
+        }));
+        assert!(matches!(parse_adx_header(&buf[..19]), Err(Error::InvalidBufferSize)));
+        buf[19] = 0x08;
+        assert!(matches!(parse_adx_header(&buf), Err(Error::InvalidData)));
+        buf[19] = 0x00;
+        buf[7] = 0;
+        assert!(matches!(parse_adx_header(&buf), Err(Error::InvalidChannels)));
+        buf[4] = 0x04;
+        assert!(matches!(parse_adx_header(&buf), Err(Error::InvalidData)));
+        buf[0] = 0x00;
+        assert!(matches!(parse_adx_header(&buf), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_adx_coefficients() {
+        assert_eq!(adx_coefficients(500, 44100).ok(), Some([ 7334, -3283 ]));
+        assert_eq!(adx_coefficients(500, 22050).ok(), Some([ 6569, -2634 ]));
+        assert_eq!(adx_coefficients(500, 48000).ok(), Some([ 7400, -3343 ]));
+        assert_eq!(adx_coefficients(0, 48000).ok(), Some([ 8192, -4096 ]));
+        assert!(matches!(adx_coefficients(500, 0), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_decode_adx_frame() {
+        let coefficients = [ 7334, -3283 ];
+        let mut state = AdxState::new();
+        let mut out = [0i16; 32];
+        let mut buf = [0u8; 18];
+        // scale 100, codes 7, -8, 1, 0
+        buf[0..4].copy_from_slice(&[ 0x00, 0x63, 0x78, 0x10 ]);
+        assert!(decode_adx_frame(&buf, &coefficients, &mut state, &mut out).is_ok());
+        assert_eq!(out[..5], [ 700, 453, 350, 263, 190 ]);
+        assert_eq!(state, AdxState { previous: out[31], previous2: out[30] });
+
+        // output is clamped
+        let mut state = AdxState { previous: 32767, previous2: 0 };
+        buf[0..3].copy_from_slice(&[ 0x7f, 0xff, 0x77 ]);
+        assert!(decode_adx_frame(&buf, &coefficients, &mut state, &mut out).is_ok());
+        assert_eq!(out[..2], [ 32767, 32767 ]);
+
+        // end of stream marker
+        let mut state = AdxState::new();
+        buf[0] = 0x80;
+        assert!(matches!(decode_adx_frame(&buf, &coefficients, &mut state, &mut out),
+            Err(Error::InvalidData)));
+        assert_eq!(state, AdxState::new());
+
+        // extreme coefficients don't overflow
+        let coefficients = [ -32768, -32768 ];
+        let mut state = AdxState { previous: -32768, previous2: -32768 };
+        buf.fill(0);
+        assert!(decode_adx_frame(&buf, &coefficients, &mut state, &mut out).is_ok());
+        assert_eq!(out[..2], [ 32767, 8 ]);
+        let mut state = AdxState { previous: -32768, previous2: -32768 };
+        let mut encoded = [0u8; 18];
+        // the encoder uses the same prediction
+        encode_adx_frame(&[-32768; 32], &coefficients, &mut state, &mut encoded);
+    }
+
+    #[test]
+    fn test_decode_adx() {
+        let coefficients = [ 7334, -3283 ];
+        let mut buf = [0u8; 36];
+        buf[0..3].copy_from_slice(&[ 0x00, 0x00, 0x10 ]);
+        buf[18..21].copy_from_slice(&[ 0x00, 0x01, 0xf0 ]);
+        let mut states = [ AdxState::new(), AdxState::new() ];
+        let mut out = [0i16; 64];
+        assert!(decode_adx(&buf, &coefficients, &mut states, &mut out).is_ok());
+        assert_eq!(out[..4], [ 1, -2, 1, -4 ]);
+
+        assert!(matches!(decode_adx(&buf, &coefficients, &mut [], &mut out),
+            Err(Error::InvalidChannels)));
+        assert!(matches!(decode_adx(&buf[..18], &coefficients, &mut states, &mut out[..32]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_adx(&buf, &coefficients, &mut states, &mut out[..32]),
+            Err(Error::InvalidBufferSize)));
+        buf[18] = 0x80;
+        assert!(matches!(decode_adx(&buf, &coefficients, &mut states, &mut out),
+            Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_encode_adx_frame() {
+        // a sine-like wave is encoded with small error and the decoder follows the encoder
+        let coefficients = [ 7334, -3283 ];
+        let mut samples = [0i16; 128];
+        for (i, s) in samples.iter_mut().enumerate() {
+            let x = i32::try_from(i % 64).unwrap_or(0);
+            let y = if x < 32 { x * (32 - x) } else { -(x - 32) * (64 - x) };
+            *s = i16::try_from(y * 100).unwrap_or(0);
+        }
+        let mut enc_state = AdxState::new();
+        let mut dec_state = AdxState::new();
+        let mut buf = [0u8; 18];
+        let mut out = [0i16; 32];
+        for frame_samples in samples.chunks_exact(32) {
+            let Ok(frame_samples) = frame_samples.try_into() else {
+                return;
+            };
+            encode_adx_frame(frame_samples, &coefficients, &mut enc_state, &mut buf);
+            assert!(decode_adx_frame(&buf, &coefficients, &mut dec_state, &mut out).is_ok());
+            assert_eq!(enc_state, dec_state);
+            for (a, b) in out.iter().zip(frame_samples) {
+                assert!((i32::from(*a) - i32::from(*b)).abs() < 300);
+            }
+        }
+
+        // silence has the smallest scale
+        let mut state = AdxState::new();
+        encode_adx_frame(&[0; 32], &coefficients, &mut state, &mut buf);
+        assert_eq!(buf, [0; 18]);
+    }
+
+    #[test]
+    fn test_encode_adx() {
+        let coefficients = [ 7334, -3283 ];
+        let mut samples = [0i16; 128];
+        for (i, s) in samples.iter_mut().enumerate() {
+            // left channel is a triangle wave, right channel is its inverse
+            let t = i32::try_from((i / 2) % 32).unwrap_or(0);
+            let value = i16::try_from(t.min(32 - t) * 1000 - 8000).unwrap_or(0);
+            *s = if i % 2 == 0 { value } else { -value };
+        }
+        let mut states = [ AdxState::new(), AdxState::new() ];
+        let mut buf = [0u8; 72];
+        assert!(encode_adx(&samples, &coefficients, &mut states, &mut buf).is_ok());
+
+        let mut dec_states = [ AdxState::new(), AdxState::new() ];
+        let mut out = [0i16; 128];
+        assert!(decode_adx(&buf, &coefficients, &mut dec_states, &mut out).is_ok());
+        assert_eq!(states, dec_states);
+        let error: i64 = out.iter().zip(samples)
+            .map(|(a, b)| (i64::from(*a) - i64::from(b)).abs())
+            .sum();
+        assert!(error / 128 < 250);
+
+        assert!(matches!(encode_adx(&samples, &coefficients, &mut [], &mut buf),
+            Err(Error::InvalidChannels)));
+        assert!(matches!(encode_adx(&samples[..96], &coefficients, &mut states, &mut buf[..54]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_adx(&samples, &coefficients, &mut states, &mut buf[..54]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = AdxState { previous: -1000, previous2: 2000 };
+        assert!(!state.is_default());
+        let mut buf = [0u8; AdxState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x18, 0xfc, 0xd0, 0x07 ]);
+        assert!(matches!(AdxState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, AdxState::new());
+        assert!(matches!(AdxState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 5]), Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::Error;
+use crate::sample_history::{impl_sample_history, SampleHistory};
+
+// Nintendo AFC is a 4-bit ADPCM format used in GameCube and Wii first-party games.
+// It works like DSP-ADPCM, but the prediction coefficients are a fixed table.
+
+/// Size of an AFC frame for one channel in bytes: a header byte and 8 bytes of 4-bit samples.
+pub const AFC_FRAME_SIZE: usize = 9;
+
+/// Number of samples in an AFC frame for one channel.
+pub const AFC_SAMPLES_PER_FRAME: usize = 16;
+
+// prediction coefficient pairs in 5.11 fixed point
+const AFC_COEFFICIENTS: &[[i32; 2]; 16] = &[
+    [ 0, 0 ],
+    [ 2048, 0 ],
+    [ 0, 2048 ],
+    [ 1024, 1024 ],
+    [ 4096, -2048 ],
+    [ 3584, -1536 ],
+    [ 3072, -1024 ],
+    [ 4608, -2560 ],
+    [ 4200, -2248 ],
+    [ 4800, -2300 ],
+    [ 5120, -3072 ],
+    [ 2048, -2048 ],
+    [ 1024, -1024 ],
+    [ -1024, 1024 ],
+    [ -1024, 0 ],
+    [ -2048, 0 ],
+];
+
+/// State values for the AFC decoder.
+///
+/// The values should be initialized to zeros at the start of the stream.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AfcState {
+    /// The previous decoded sample.
+    pub previous: i16,
+    /// The sample before the previous decoded sample.
+    pub previous2: i16,
+}
+
+impl AfcState {
+    /// Creates a new AfcState with zero values.
+    pub fn new() -> AfcState {
+        AfcState { previous: 0, previous2: 0 }
+    }
+}
+
+impl_sample_history!(AfcState);
+
+/// Decodes a 9-byte AFC frame of one channel to 16-bit signed integer samples.
+///
+/// The header byte contains the scale exponent in the high nibble and the index to the fixed
+/// coefficient table in the low nibble. The 4-bit samples are decoded high nibble first.
+///
+/// `state` should be initialized to zero for the first frame and subsequent calls should pass
+/// in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`.
+pub fn decode_afc_frame(buf: &[u8; 9], state: &mut AfcState, out_samples: &mut [i16; 16]) {
+    let shift = buf[0] >> 4;
+    let [ coef1, coef2 ] = AFC_COEFFICIENTS[usize::from(buf[0] & 0x0f)];
+    for (out, code) in out_samples.iter_mut().zip(buf[1..].iter()
+        .flat_map(|b| [ *b & 0xf0, *b << 4 ])) {
+        let code = i32::from(i8::from_le_bytes([ code ])) >> 4;
+        let prediction = coef1 * i32::from(state.previous) + coef2 * i32::from(state.previous2);
+        let sample = (((code << shift) << 11) + prediction) >> 11;
+        state.push(i16::try_from(sample.clamp(-32768, 32767)).unwrap_or(0));
+        *out = state.previous;
+    }
+}
+
+/// Decodes AFC frames to 16-bit signed integer samples.
+///
+/// `buf` should contain whole frames of all channels: the frames of each channel follow each
+/// other for every 16 samples. The `buf` length must be a multiple of `9 * states.len()`.
+///
+/// `states` must contain channel number of `AfcState` items. The state objects should be
+/// initialized to zero for the first call and subsequent calls should pass in the state values
+/// from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `16 * buf.len() / 9`. Samples are interleaved for multichannel audio.
+///
+/// An error is returned if `states` is empty or if the `buf` length or the `out_samples` length
+/// isn't correct. If an error is returned, `states` and `out_samples` are left unmodified.
+pub fn decode_afc(buf: &[u8], states: &mut [AfcState], out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    if buf.len() % (AFC_FRAME_SIZE * channels) != 0 ||
+        out_samples.len() != buf.len() / AFC_FRAME_SIZE * AFC_SAMPLES_PER_FRAME {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (frames, out) in buf.chunks_exact(AFC_FRAME_SIZE * channels)
+        .zip(out_samples.chunks_exact_mut(AFC_SAMPLES_PER_FRAME * channels)) {
+        for (ch, (frame, state)) in frames.chunks_exact(AFC_FRAME_SIZE)
+            .zip(states.iter_mut()).enumerate() {
+            let Ok(frame) = frame.try_into() else {
+                return Err(Error::InvalidBufferSize);
+            };
+            let mut samples = [0i16; 16];
+            decode_afc_frame(frame, state, &mut samples);
+            for (i, sample) in samples.iter().enumerate() {
+                out[i * channels + ch] = *sample;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CodecState;
+
+    #[test]
+    fn test_decode_afc_frame() {
+        // scale 2^4, coefficients 0 and 0
+        let mut state = AfcState::new();
+        let mut out = [0i16; 16];
+        decode_afc_frame(&[ 0x40, 0x7f, 0x81, 0, 0, 0, 0, 0, 0 ], &mut state, &mut out);
+        assert_eq!(out[..5], [ 112, -16, -128, 16, 0 ]);
+        assert_eq!(state, AfcState { previous: 0, previous2: 0 });
+
+        // coefficients 2 and -1 follow the previous slope
+        let mut state = AfcState { previous: 200, previous2: 100 };
+        decode_afc_frame(&[ 0x04, 0x10, 0, 0, 0, 0, 0, 0, 0 ], &mut state, &mut out);
+        assert_eq!(out[..3], [ 301, 402, 503 ]);
+        assert_eq!(state, AfcState { previous: out[15], previous2: out[14] });
+
+        // negative coefficient
+        let mut state = AfcState { previous: 1000, previous2: 0 };
+        decode_afc_frame(&[ 0x0f, 0, 0, 0, 0, 0, 0, 0, 0 ], &mut state, &mut out);
+        assert_eq!(out[..3], [ -1000, 1000, -1000 ]);
+
+        // output is clamped
+        let mut state = AfcState::new();
+        decode_afc_frame(&[ 0xf0, 0x78, 0, 0, 0, 0, 0, 0, 0 ], &mut state, &mut out);
+        assert_eq!(out[..3], [ 32767, -32768, 0 ]);
+    }
+
+    #[test]
+    fn test_decode_afc() {
+        let buf = [ 0x00, 0x10, 0, 0, 0, 0, 0, 0, 0, 0x10, 0xf0, 0, 0, 0, 0, 0, 0, 0 ];
+        let mut states = [ AfcState::new(), AfcState::new() ];
+        let mut out = [0i16; 32];
+        assert!(decode_afc(&buf, &mut states, &mut out).is_ok());
+        assert_eq!(out[..4], [ 1, -2, 0, 0 ]);
+
+        let mut states = [ AfcState::new() ];
+        assert!(decode_afc(&buf, &mut states, &mut out).is_ok());
+        assert_eq!(out[..2], [ 1, 0 ]);
+        assert_eq!(out[16..18], [ -2, 0 ]);
+
+        assert!(matches!(decode_afc(&buf, &mut [], &mut out), Err(Error::InvalidChannels)));
+        assert!(matches!(decode_afc(&buf[..17], &mut states, &mut out[..16]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_afc(&buf, &mut states, &mut out[..16]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = AfcState { previous: -1000, previous2: 2000 };
+        assert!(!state.is_default());
+        let mut buf = [0u8; AfcState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x18, 0xfc, 0xd0, 0x07 ]);
+        assert!(matches!(AfcState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, AfcState::new());
+        assert!(matches!(AfcState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 5]), Err(Error::InvalidBufferSize)));
+    }
+}
@@ -100,11 +100,9 @@ mod tests {
         // test against reference values generated for all input values -32768..=32767
         // the reference values were generated with the G.191 software tools
         let buffer = include_bytes!("../test-files/alaw-reference.bin");
-        let mut bi = 0;
-        for i in -32768..=32767 {
+        for (bi, i) in (-32768..=32767).enumerate() {
             let encoded = encode_alaw(i);
             assert_eq!(buffer[bi], encoded);
-            bi += 1;
         }
     }
 }
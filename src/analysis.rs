@@ -0,0 +1,179 @@
+
+use crate::AdpcmImaState;
+use crate::{decode_alaw, encode_alaw};
+use crate::{decode_ulaw, encode_ulaw};
+use crate::{decode_adpcm_ima, encode_adpcm_ima};
+
+/// Quantization error statistics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorStats {
+    /// Number of analyzed samples.
+    pub sample_count: usize,
+    /// Largest absolute error.
+    pub max_error: u32,
+    /// Sum of squared errors.
+    pub squared_error_sum: u64,
+    /// Histogram of absolute errors. Index `n` counts errors which need `n` bits:
+    /// index 0 counts exact values, index 1 counts error 1, index 2 counts errors 2..=3,
+    /// index 3 counts errors 4..=7 and so on.
+    pub error_histogram: [usize; 17],
+}
+
+impl ErrorStats {
+    /// Creates new ErrorStats with zero values.
+    pub fn new() -> ErrorStats {
+        ErrorStats {
+            sample_count: 0,
+            max_error: 0,
+            squared_error_sum: 0,
+            error_histogram: [0; 17],
+        }
+    }
+
+    /// Adds an input sample value and its decoded value to the statistics.
+    pub fn add(&mut self, input: i16, decoded: i16) {
+        let error = (i32::from(input) - i32::from(decoded)).unsigned_abs();
+        self.sample_count += 1;
+        self.max_error = self.max_error.max(error);
+        self.squared_error_sum = self.squared_error_sum.saturating_add(u64::from(error).pow(2));
+        let bits = (u32::BITS - error.leading_zeros()).min(16);
+        self.error_histogram[bits as usize] += 1;
+    }
+
+    /// Returns the mean squared error, or 0 if no samples have been added.
+    pub fn mean_squared_error(&self) -> f64 {
+        if self.sample_count == 0 {
+            return 0.0;
+        }
+        self.squared_error_sum as f64 / self.sample_count as f64
+    }
+}
+
+impl Default for ErrorStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Quantization analysis result for G.711 A-law and μ-law.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompandingAnalysis {
+    /// Error statistics for all samples.
+    pub error: ErrorStats,
+    /// Number of samples encoded to each segment (0..=7, smallest magnitudes first).
+    pub segment_counts: [usize; 8],
+    /// Sum of squared errors for each segment.
+    pub segment_squared_errors: [u64; 8],
+}
+
+impl CompandingAnalysis {
+    fn new() -> CompandingAnalysis {
+        CompandingAnalysis {
+            error: ErrorStats::new(),
+            segment_counts: [0; 8],
+            segment_squared_errors: [0; 8],
+        }
+    }
+
+    fn add(&mut self, input: i16, decoded: i16, segment: u8) {
+        self.error.add(input, decoded);
+        let error = (i32::from(input) - i32::from(decoded)).unsigned_abs();
+        let segment = usize::from(segment & 7);
+        self.segment_counts[segment] += 1;
+        self.segment_squared_errors[segment] = self.segment_squared_errors[segment]
+            .saturating_add(u64::from(error).pow(2));
+    }
+}
+
+/// Quantization analysis result for IMA ADPCM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdpcmImaAnalysis {
+    /// Error statistics for all samples.
+    pub error: ErrorStats,
+    /// Number of samples encoded with each step index (0..=88).
+    pub step_index_counts: [usize; 89],
+}
+
+/// Encodes and decodes `samples` with G.711 A-law and returns the quantization statistics.
+pub fn analyze_alaw(samples: &[i16]) -> CompandingAnalysis {
+    let mut result = CompandingAnalysis::new();
+    for s in samples {
+        let encoded = encode_alaw(*s);
+        result.add(*s, decode_alaw(encoded), ((encoded ^ 0xd5) >> 4) & 7);
+    }
+    result
+}
+
+/// Encodes and decodes `samples` with G.711 μ-law and returns the quantization statistics.
+pub fn analyze_ulaw(samples: &[i16]) -> CompandingAnalysis {
+    let mut result = CompandingAnalysis::new();
+    for s in samples {
+        let encoded = encode_ulaw(*s);
+        result.add(*s, decode_ulaw(encoded), ((encoded ^ 0xff) >> 4) & 7);
+    }
+    result
+}
+
+/// Encodes and decodes `samples` with IMA ADPCM and returns the quantization statistics.
+///
+/// `state` is the initial encoder state. It is updated the same way as
+/// [`encode_adpcm_ima()`](crate::encode_adpcm_ima) updates it.
+pub fn analyze_adpcm_ima(samples: &[i16], state: &mut AdpcmImaState) -> AdpcmImaAnalysis {
+    let mut result = AdpcmImaAnalysis {
+        error: ErrorStats::new(),
+        step_index_counts: [0; 89],
+    };
+    let mut decoder_state = state.clone();
+    for s in samples {
+        result.step_index_counts[usize::from(state.step_index.min(88))] += 1;
+        let encoded = encode_adpcm_ima(*s, state);
+        result.error.add(*s, decode_adpcm_ima(encoded, &mut decoder_state));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_stats() {
+        let mut stats = ErrorStats::new();
+        stats.add(0, 0);
+        stats.add(10, 7);
+        stats.add(-32768, 32767);
+        assert_eq!(stats.sample_count, 3);
+        assert_eq!(stats.max_error, 65535);
+        assert_eq!(stats.squared_error_sum, 9 + 65535 * 65535);
+        assert_eq!(stats.error_histogram[0], 1);
+        assert_eq!(stats.error_histogram[2], 1);
+        assert_eq!(stats.error_histogram[16], 1);
+        assert_eq!(ErrorStats::new().mean_squared_error(), 0.0);
+    }
+
+    #[test]
+    fn test_analyze_alaw() {
+        let result = analyze_alaw(&[ 0, 1, -1, 100, 1000, 10000, -32768 ]);
+        assert_eq!(result.error.sample_count, 7);
+        assert_eq!(result.segment_counts, [ 4, 0, 1, 0, 0, 0, 1, 1 ]);
+        assert_eq!(result.segment_counts.iter().sum::<usize>(), 7);
+    }
+
+    #[test]
+    fn test_analyze_ulaw() {
+        let result = analyze_ulaw(&[ 0, 1, -1, 100, 1000, 10000, -32768 ]);
+        assert_eq!(result.error.sample_count, 7);
+        assert_eq!(result.segment_counts, [ 4, 0, 0, 1, 0, 0, 1, 1 ]);
+        assert_eq!(result.segment_squared_errors[7], 644 * 644);
+    }
+
+    #[test]
+    fn test_analyze_adpcm_ima() {
+        let mut state = AdpcmImaState::new();
+        let result = analyze_adpcm_ima(&[ 0, 1000, 2000, 3000, 4000, 5000 ], &mut state);
+        assert_eq!(result.error.sample_count, 6);
+        assert_eq!(result.step_index_counts.iter().sum::<usize>(), 6);
+        assert_eq!(result.step_index_counts[0], 2);
+        assert_eq!(state.step_index, 40);
+    }
+}
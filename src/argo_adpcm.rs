@@ -0,0 +1,170 @@
+use crate::Error;
+use crate::sample_history::{impl_sample_history, SampleHistory};
+
+// Argonaut Games ASF ADPCM (Croc, FX Fighter). Each block selects between a first order
+// and a second order predictor.
+// see https://wiki.multimedia.cx/index.php/Argonaut_Games_ASF
+
+/// Size of an Argonaut ASF ADPCM block for one channel in bytes: a control byte and
+/// 16 bytes of 4-bit samples.
+pub const ARGO_ADPCM_BLOCK_SIZE: usize = 17;
+
+/// Number of samples in an Argonaut ASF ADPCM block for one channel.
+pub const ARGO_ADPCM_SAMPLES_PER_BLOCK: usize = 32;
+
+/// State values for the Argonaut ASF ADPCM decoder.
+///
+/// The values should be initialized to zeros at the start of the stream.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ArgoAdpcmState {
+    /// The previous decoded sample.
+    pub previous: i16,
+    /// The sample before the previous decoded sample.
+    pub previous2: i16,
+}
+
+impl ArgoAdpcmState {
+    /// Creates a new ArgoAdpcmState with zero values.
+    pub fn new() -> ArgoAdpcmState {
+        ArgoAdpcmState { previous: 0, previous2: 0 }
+    }
+}
+
+impl_sample_history!(ArgoAdpcmState);
+
+/// Decodes a 17-byte Argonaut ASF ADPCM block of one channel to 16-bit signed integer samples.
+///
+/// The control byte contains the shift value minus 2 in the high nibble. If bit 2 of
+/// the control byte is set, the predictor is `2 * previous - previous2`, otherwise it is
+/// `previous`. The 4-bit samples are decoded high nibble first.
+///
+/// `state` should be initialized to zero for the first block and subsequent calls should pass
+/// in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`.
+pub fn decode_argo_adpcm_block(buf: &[u8; 17], state: &mut ArgoAdpcmState,
+    out_samples: &mut [i16; 32]) {
+
+    let shift = (buf[0] >> 4) + 2;
+    let is_second_order = buf[0] & 0x04 != 0;
+    for (out, code) in out_samples.iter_mut().zip(buf[1..].iter()
+        .flat_map(|b| [ *b & 0xf0, *b << 4 ])) {
+        let mut sample = (i32::from(i8::from_le_bytes([ code ])) >> 4) << shift;
+        if is_second_order {
+            sample += 8 * i32::from(state.previous) - 4 * i32::from(state.previous2);
+        } else {
+            sample += 4 * i32::from(state.previous);
+        }
+        state.push(i16::try_from((sample >> 2).clamp(-32768, 32767)).unwrap_or(0));
+        *out = state.previous;
+    }
+}
+
+/// Decodes Argonaut ASF ADPCM blocks to 16-bit signed integer samples.
+///
+/// `buf` should contain groups of blocks: a 17-byte block for each channel. The `buf` length
+/// must be a multiple of `17 * states.len()`.
+///
+/// `states` must contain channel number of `ArgoAdpcmState` items. The state objects should be
+/// initialized to zero for the first call and subsequent calls should pass in the state values
+/// from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `32 * buf.len() / 17`. Samples are interleaved for multichannel audio.
+///
+/// An error is returned if `states` is empty or if the `buf` length or the `out_samples` length
+/// isn't correct. If an error is returned, `states` and `out_samples` are left unmodified.
+pub fn decode_argo_adpcm(buf: &[u8], states: &mut [ArgoAdpcmState], out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    if buf.len() % (ARGO_ADPCM_BLOCK_SIZE * channels) != 0 ||
+        out_samples.len() != buf.len() / ARGO_ADPCM_BLOCK_SIZE * ARGO_ADPCM_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut block_out = [0i16; ARGO_ADPCM_SAMPLES_PER_BLOCK];
+    for (blocks, out) in buf.chunks_exact(ARGO_ADPCM_BLOCK_SIZE * channels)
+        .zip(out_samples.chunks_exact_mut(ARGO_ADPCM_SAMPLES_PER_BLOCK * channels)) {
+        for (ch, (block, state)) in blocks.chunks_exact(ARGO_ADPCM_BLOCK_SIZE)
+            .zip(states.iter_mut()).enumerate() {
+            let Ok(block) = block.try_into() else {
+                return Err(Error::InvalidBufferSize);
+            };
+            decode_argo_adpcm_block(block, state, &mut block_out);
+            for (out, sample) in out[ch..].iter_mut().step_by(channels).zip(block_out) {
+                *out = sample;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CodecState;
+
+    #[test]
+    fn test_decode_argo_adpcm_block() {
+        // shift 2, first order predictor
+        let mut buf = [0u8; 17];
+        buf[1] = 0x7f;
+        buf[2] = 0x80;
+        let mut state = ArgoAdpcmState::new();
+        let mut out = [0i16; 32];
+        decode_argo_adpcm_block(&buf, &mut state, &mut out);
+        assert_eq!(out[..5], [ 7, 6, -2, -2, -2 ]);
+        assert_eq!(state, ArgoAdpcmState { previous: -2, previous2: -2 });
+
+        // second order predictor follows the previous slope
+        let mut buf = [0u8; 17];
+        buf[0] = 0x04;
+        let mut state = ArgoAdpcmState { previous: 200, previous2: 100 };
+        decode_argo_adpcm_block(&buf, &mut state, &mut out);
+        assert_eq!(out[..3], [ 300, 400, 500 ]);
+
+        // larger shift and clamping
+        let mut buf = [0u8; 17];
+        buf[0] = 0xd0;
+        buf[1] = 0x78;
+        let mut state = ArgoAdpcmState::new();
+        decode_argo_adpcm_block(&buf, &mut state, &mut out);
+        assert_eq!(out[..2], [ 32767, -32768 ]);
+    }
+
+    #[test]
+    fn test_decode_argo_adpcm() {
+        let mut buf = [0u8; 34];
+        buf[1] = 0x10;
+        buf[18] = 0xf0;
+        let mut states = [ ArgoAdpcmState::new(), ArgoAdpcmState::new() ];
+        let mut out = [0i16; 64];
+        assert!(decode_argo_adpcm(&buf, &mut states, &mut out).is_ok());
+        assert_eq!(out[..4], [ 1, -1, 1, -1 ]);
+
+        assert!(matches!(decode_argo_adpcm(&buf, &mut [], &mut out),
+            Err(Error::InvalidChannels)));
+        assert!(matches!(decode_argo_adpcm(&buf[..17], &mut states, &mut out[..32]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_argo_adpcm(&buf, &mut states, &mut out[..32]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = ArgoAdpcmState { previous: -1000, previous2: 2000 };
+        assert!(!state.is_default());
+        let mut buf = [0u8; ArgoAdpcmState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x18, 0xfc, 0xd0, 0x07 ]);
+        assert!(matches!(ArgoAdpcmState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, ArgoAdpcmState::new());
+        assert!(matches!(ArgoAdpcmState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 5]), Err(Error::InvalidBufferSize)));
+    }
+}
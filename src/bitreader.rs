@@ -0,0 +1,160 @@
+
+/// Reads bits from a byte buffer, most significant bit first.
+pub(crate) struct BitReader<'a> {
+    buf: &'a [u8],
+    bit_pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> BitReader<'a> {
+        BitReader { buf, bit_pos: 0 }
+    }
+
+    /// Reads `count` bits (0..=32) as an unsigned value.
+    /// Returns `None` if there aren't enough bits left.
+    pub(crate) fn read(&mut self, count: u32) -> Option<u32> {
+        if count > 32 || self.bits_left() < count as usize {
+            return None;
+        }
+        let mut value: u32 = 0;
+        for _ in 0..count {
+            let byte = self.buf[self.bit_pos / 8];
+            let bit = (byte >> (7 - self.bit_pos % 8)) & 1;
+            value = (value << 1) | u32::from(bit);
+            self.bit_pos += 1;
+        }
+        Some(value)
+    }
+
+    /// Reads `count` bits (1..=32) as a two's complement signed value.
+    /// Returns `None` if there aren't enough bits left.
+    pub(crate) fn read_signed(&mut self, count: u32) -> Option<i32> {
+        if count == 0 {
+            return None;
+        }
+        let value = self.read(count)?;
+        let shift = 32 - count;
+        #[allow(clippy::cast_possible_wrap)] // wrapping is expected for sign extension
+        Some(((value << shift) as i32) >> shift)
+    }
+
+    /// Returns the number of unread bits.
+    pub(crate) fn bits_left(&self) -> usize {
+        self.buf.len() * 8 - self.bit_pos
+    }
+}
+
+/// Reads bits from a byte buffer, least significant bit first.
+pub(crate) struct LsbBitReader<'a> {
+    buf: &'a [u8],
+    bit_pos: usize,
+}
+
+impl<'a> LsbBitReader<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> LsbBitReader<'a> {
+        LsbBitReader { buf, bit_pos: 0 }
+    }
+
+    /// Reads `count` bits (0..=32) as an unsigned value. The first bit read is
+    /// the least significant bit of the value.
+    /// Returns `None` if there aren't enough bits left.
+    pub(crate) fn read(&mut self, count: u32) -> Option<u32> {
+        if count > 32 || self.bits_left() < count as usize {
+            return None;
+        }
+        let mut value: u32 = 0;
+        for i in 0..count {
+            let byte = self.buf[self.bit_pos / 8];
+            let bit = (byte >> (self.bit_pos % 8)) & 1;
+            value |= u32::from(bit) << i;
+            self.bit_pos += 1;
+        }
+        Some(value)
+    }
+
+    /// Returns the number of unread bits.
+    pub(crate) fn bits_left(&self) -> usize {
+        self.buf.len() * 8 - self.bit_pos
+    }
+}
+
+/// Writes bits to a byte buffer, most significant bit first.
+pub(crate) struct BitWriter<'a> {
+    buf: &'a mut [u8],
+    bit_pos: usize,
+}
+
+impl<'a> BitWriter<'a> {
+    pub(crate) fn new(buf: &'a mut [u8]) -> BitWriter<'a> {
+        BitWriter { buf, bit_pos: 0 }
+    }
+
+    /// Writes the lowest `count` bits (0..=32) of `value`. Bits of partially written bytes
+    /// which haven't been written yet are zero.
+    /// Returns `None` if there isn't enough space left.
+    pub(crate) fn write(&mut self, value: u32, count: u32) -> Option<()> {
+        if count > 32 || (self.buf.len() * 8 - self.bit_pos) < count as usize {
+            return None;
+        }
+        for i in (0..count).rev() {
+            let byte = &mut self.buf[self.bit_pos / 8];
+            if self.bit_pos % 8 == 0 {
+                *byte = 0;
+            }
+            if (value >> i) & 1 != 0 {
+                *byte |= 0x80 >> (self.bit_pos % 8);
+            }
+            self.bit_pos += 1;
+        }
+        Some(())
+    }
+
+    /// Returns the number of written bytes, including the last partially written byte.
+    pub(crate) fn byte_len(&self) -> usize {
+        self.bit_pos.div_ceil(8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bit_reader() {
+        let mut reader = BitReader::new(&[ 0b1010_0011, 0xff ]);
+        assert_eq!(reader.read(1), Some(1));
+        assert_eq!(reader.read(3), Some(0b010));
+        assert_eq!(reader.read_signed(4), Some(3));
+        assert_eq!(reader.read_signed(4), Some(-1));
+        assert_eq!(reader.bits_left(), 4);
+        assert_eq!(reader.read(5), None);
+        assert_eq!(reader.read(4), Some(15));
+        assert_eq!(reader.read(0), Some(0));
+    }
+
+    #[test]
+    fn test_lsb_bit_reader() {
+        let mut reader = LsbBitReader::new(&[ 0b1010_0011, 0xff ]);
+        assert_eq!(reader.read(1), Some(1));
+        assert_eq!(reader.read(3), Some(0b001));
+        assert_eq!(reader.read(6), Some(0b11_1010));
+        assert_eq!(reader.bits_left(), 6);
+        assert_eq!(reader.read(7), None);
+        assert_eq!(reader.read(6), Some(0b11_1111));
+        assert_eq!(reader.read(0), Some(0));
+    }
+
+    #[test]
+    fn test_bit_writer() {
+        let mut buf = [0xffu8; 2];
+        let mut writer = BitWriter::new(&mut buf);
+        assert_eq!(writer.write(1, 1), Some(()));
+        assert_eq!(writer.write(0b010, 3), Some(()));
+        assert_eq!(writer.write(0b0011, 4), Some(()));
+        assert_eq!(writer.write(0b101, 3), Some(()));
+        assert_eq!(writer.byte_len(), 2);
+        assert_eq!(writer.write(0, 6), None);
+        assert_eq!(buf[0], 0b1010_0011);
+        assert_eq!(buf[1], 0b1010_0000);
+    }
+}
@@ -0,0 +1,421 @@
+
+use crate::Error;
+use crate::sample_history::{decode_code, encode_sample, impl_sample_history, squared_error};
+
+// SNES BRR (bit rate reduction) is the sample format of the S-DSP sound chip.
+// see https://problemkaputt.de/fullsnes.htm#snesapudspbrrsamples
+
+/// Size of a BRR block in bytes: a header byte and 8 bytes of 4-bit samples.
+pub const BRR_BLOCK_SIZE: usize = 9;
+
+/// Number of samples in a BRR block.
+pub const BRR_SAMPLES_PER_BLOCK: usize = 16;
+
+/// State values for the BRR decoder.
+///
+/// The values should be initialized to zeros at the start of the sample.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BrrState {
+    /// The previous decoded sample.
+    pub previous: i16,
+    /// The sample before the previous decoded sample.
+    pub previous2: i16,
+}
+
+impl BrrState {
+    /// Creates a new BrrState with zero values.
+    pub fn new() -> BrrState {
+        BrrState { previous: 0, previous2: 0 }
+    }
+
+    // returns the decoded sample of a 4-bit code (-8..=7) like the S-DSP does, without
+    // updating the state
+    fn decoded_value(&self, code: i32, shift: u8, filter: u8) -> i16 {
+        // shift values 13..=15 are invalid and give -2048 or 0
+        let mut s = if shift <= 12 {
+            (code << shift) >> 1
+        } else if code < 0 {
+            -2048
+        } else {
+            0
+        };
+        let p1 = i32::from(self.previous);
+        let p2 = i32::from(self.previous2) >> 1;
+        match filter {
+            1 => {
+                s += (p1 >> 1) + ((-p1) >> 5);
+            },
+            2 => {
+                s += p1 - p2 + (p2 >> 4) + ((p1 * -3) >> 6);
+            },
+            3 => {
+                s += p1 - p2 + ((p1 * -13) >> 7) + ((p2 * 3) >> 4);
+            },
+            _ => (),
+        }
+        // the value is clamped to 16 bits and then wraps to 15 bits,
+        // the lowest bit of the output is always zero
+        let clamped = s.clamp(-32768, 32767).to_le_bytes();
+        i16::from_le_bytes([ clamped[0], clamped[1] ]).wrapping_shl(1)
+    }
+}
+
+impl_sample_history!(BrrState);
+
+/// Header byte of a BRR block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrrBlockHeader {
+    /// Shift (range) value 0..=15. Values 13..=15 are invalid, but decoded like the S-DSP does.
+    pub shift: u8,
+    /// Prediction filter 0..=3.
+    pub filter: u8,
+    /// Loop flag. If the end flag is set, `true` jumps to the loop point and
+    /// `false` stops the sample.
+    pub is_loop: bool,
+    /// End flag. `true` for the last block of the sample.
+    pub is_end: bool,
+}
+
+impl BrrBlockHeader {
+    /// Parses the header byte of a BRR block.
+    pub fn from_byte(header: u8) -> BrrBlockHeader {
+        BrrBlockHeader {
+            shift: header >> 4,
+            filter: (header >> 2) & 3,
+            is_loop: (header & 0x02) != 0,
+            is_end: (header & 0x01) != 0,
+        }
+    }
+
+    /// Returns the header byte.
+    pub fn to_byte(&self) -> u8 {
+        ((self.shift & 0x0f) << 4) | ((self.filter & 3) << 2) |
+            (u8::from(self.is_loop) << 1) | u8::from(self.is_end)
+    }
+}
+
+/// Decodes a 9-byte BRR block to 16-bit signed integer samples.
+///
+/// `state` should be initialized to zero for the first block of the sample and subsequent calls
+/// should pass in the state values from the previous call. When a looping sample jumps to its
+/// loop point, the state is kept as it is.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// [`BRR_SAMPLES_PER_BLOCK`]. The lowest bit of the decoded samples is always zero, because
+/// the S-DSP has 15-bit precision.
+///
+/// The parsed block header is returned so that the caller can handle the loop and end flags.
+///
+/// An error is returned if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_brr_block(buf: &[u8; 9], state: &mut BrrState, out_samples: &mut [i16])
+    -> Result<BrrBlockHeader, Error> {
+
+    if out_samples.len() != BRR_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    let header = BrrBlockHeader::from_byte(buf[0]);
+    for (out, code) in out_samples.iter_mut().zip(buf[1..].iter()
+        .flat_map(|b| [ *b & 0xf0, *b << 4 ])) {
+        let code = i32::from(i8::from_le_bytes([ code ])) >> 4;
+        *out = decode_code(state, code,
+            |s, code| s.decoded_value(code, header.shift, header.filter));
+    }
+    Ok(header)
+}
+
+/// Decodes consecutive BRR blocks to 16-bit signed integer samples.
+///
+/// `buf` must contain whole 9-byte blocks. All blocks are decoded, the loop and end flags
+/// aren't checked.
+///
+/// `state` should be initialized to zero for the first call and subsequent calls should pass in
+/// the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `16 * buf.len() / 9`.
+///
+/// An error is returned if the `buf` length isn't a multiple of 9 or
+/// if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_brr(buf: &[u8], state: &mut BrrState, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    if buf.len() % BRR_BLOCK_SIZE != 0 ||
+        out_samples.len() != buf.len() / BRR_BLOCK_SIZE * BRR_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (block, out) in buf.chunks_exact(BRR_BLOCK_SIZE)
+        .zip(out_samples.chunks_exact_mut(BRR_SAMPLES_PER_BLOCK)) {
+        let Ok(block) = block.try_into() else {
+            return Err(Error::InvalidBufferSize);
+        };
+        decode_brr_block(block, state, out)?;
+    }
+    Ok(())
+}
+
+/// Encodes 16 samples to a 9-byte BRR block.
+///
+/// All combinations of filter and shift values are tried and the one giving the smallest
+/// squared error is used. If `is_filter0_only` is `true`, only filter 0 is tried. Filter 0
+/// doesn't depend on the previous samples, so it should be used for the first block of
+/// a sample and for the block at the loop point, because the decoder state isn't known
+/// when playback starts or jumps to the loop point.
+///
+/// The loop and end flags of the block header are set to zero. [`encode_brr()`] sets them
+/// for a whole sample.
+///
+/// `state` should be initialized to zero for the first call and subsequent calls should pass in
+/// the state values from the previous call.
+///
+/// An error is returned if the `samples` length isn't [`BRR_SAMPLES_PER_BLOCK`].
+/// If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_brr_block(samples: &[i16], state: &mut BrrState, is_filter0_only: bool,
+    out_buf: &mut [u8; 9]) -> Result<(), Error> {
+
+    if samples.len() != BRR_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    let filter_count = if is_filter0_only { 1 } else { 4 };
+    let mut best_filter = 0;
+    let mut best_shift = 0;
+    let mut best_error = u64::MAX;
+    for filter in 0..filter_count {
+        for shift in 0..=12 {
+            let error = squared_error(state, samples,
+                |s, code| s.decoded_value(code, shift, filter));
+            if error < best_error {
+                best_filter = filter;
+                best_shift = shift;
+                best_error = error;
+            }
+        }
+    }
+    out_buf[0] = BrrBlockHeader {
+        shift: best_shift,
+        filter: best_filter,
+        is_loop: false,
+        is_end: false,
+    }.to_byte();
+    for (byte, pair) in out_buf[1..].iter_mut().zip(samples.chunks_exact(2)) {
+        let mut value = 0;
+        for sample in pair {
+            let code = encode_sample(state, *sample,
+                |s, code| s.decoded_value(code, best_shift, best_filter));
+            #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)] // the code is -8..=7
+            let nibble = (code & 0x0f) as u8;
+            value = (value << 4) | nibble;
+        }
+        *byte = value;
+    }
+    Ok(())
+}
+
+/// Encodes a whole sample to BRR blocks.
+///
+/// The `samples` length must be a multiple of [`BRR_SAMPLES_PER_BLOCK`]. The first block and
+/// the block at the loop point use filter 0, other blocks use the best filter and shift values,
+/// see [`encode_brr_block()`]. The end flag is set for the last block. If `loop_start` is set,
+/// the loop flag is set for the last block, so that playback continues from sample
+/// `loop_start`.
+///
+/// This function outputs the encoded blocks to `out_buf`. The `out_buf` length must be
+/// `9 * samples.len() / 16`.
+///
+/// An error is returned if the `samples` length or the `out_buf` length isn't correct or
+/// if `loop_start` isn't a multiple of 16 or isn't less than the `samples` length.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn encode_brr(samples: &[i16], loop_start: Option<usize>, out_buf: &mut [u8])
+    -> Result<(), Error> {
+
+    if samples.len() % BRR_SAMPLES_PER_BLOCK != 0 ||
+        out_buf.len() != samples.len() / BRR_SAMPLES_PER_BLOCK * BRR_BLOCK_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    if let Some(loop_start) = loop_start {
+        if loop_start % BRR_SAMPLES_PER_BLOCK != 0 || loop_start >= samples.len() {
+            return Err(Error::InvalidData);
+        }
+    }
+    let loop_block = loop_start.map(|pos| pos / BRR_SAMPLES_PER_BLOCK);
+    let block_count = samples.len() / BRR_SAMPLES_PER_BLOCK;
+    let mut state = BrrState::new();
+    for (i, (block_samples, block)) in samples.chunks_exact(BRR_SAMPLES_PER_BLOCK)
+        .zip(out_buf.chunks_exact_mut(BRR_BLOCK_SIZE)).enumerate() {
+        let Ok(block) = <&mut [u8; 9]>::try_from(block) else {
+            return Err(Error::InvalidBufferSize);
+        };
+        let is_filter0_only = i == 0 || loop_block == Some(i);
+        encode_brr_block(block_samples, &mut state, is_filter0_only, &mut *block)?;
+        if i + 1 == block_count {
+            block[0] |= if loop_block.is_some() { 0x03 } else { 0x01 };
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CodecState;
+
+    #[test]
+    fn test_brr_block_header() {
+        let header = BrrBlockHeader::from_byte(0xc7);
+        assert_eq!(header, BrrBlockHeader { shift: 12, filter: 1, is_loop: true, is_end: true });
+        assert_eq!(header.to_byte(), 0xc7);
+        assert_eq!(BrrBlockHeader::from_byte(0x38).filter, 2);
+    }
+
+    #[test]
+    fn test_decode_brr_block() {
+        // shift 12, filter 0
+        let mut state = BrrState::new();
+        let mut out = [0i16; 16];
+        let buf = [ 0xc0, 0x10, 0x78, 0, 0, 0, 0, 0, 0 ];
+        assert!(matches!(decode_brr_block(&buf, &mut state, &mut out),
+            Ok(BrrBlockHeader { shift: 12, filter: 0, is_loop: false, is_end: false })));
+        assert_eq!(out[..5], [ 4096, 0, 28672, -32768, 0 ]);
+
+        // filter 1 decays towards zero
+        let mut state = BrrState { previous: 4096, previous2: 0 };
+        let buf = [ 0xc5, 0, 0, 0, 0, 0, 0, 0, 0 ];
+        assert!(matches!(decode_brr_block(&buf, &mut state, &mut out),
+            Ok(BrrBlockHeader { is_end: true, .. })));
+        assert_eq!(out[..3], [ 3840, 3600, 3374 ]);
+        assert_eq!(state, BrrState { previous: out[15], previous2: out[14] });
+
+        // filter 2 and 3 follow the previous slope
+        let mut state = BrrState { previous: 2000, previous2: 1000 };
+        let buf = [ 0x08, 0, 0, 0, 0, 0, 0, 0, 0 ];
+        assert!(decode_brr_block(&buf, &mut state, &mut out).is_ok());
+        assert_eq!(out[0], 2 * (2000 - 500 + 31 - 94));
+        let mut state = BrrState { previous: 2000, previous2: 1000 };
+        let buf = [ 0x0c, 0, 0, 0, 0, 0, 0, 0, 0 ];
+        assert!(decode_brr_block(&buf, &mut state, &mut out).is_ok());
+        assert_eq!(out[0], 2 * (2000 - 500 - 204 + 93));
+
+        // invalid shift values
+        let mut state = BrrState::new();
+        let buf = [ 0xd0, 0x1f, 0, 0, 0, 0, 0, 0, 0 ];
+        assert!(decode_brr_block(&buf, &mut state, &mut out).is_ok());
+        assert_eq!(out[..2], [ 0, -4096 ]);
+
+        // values over 15 bits wrap around
+        let mut state = BrrState { previous: 28672, previous2: 0 };
+        let buf = [ 0xc4, 0x70, 0, 0, 0, 0, 0, 0, 0 ];
+        assert!(decode_brr_block(&buf, &mut state, &mut out).is_ok());
+        assert_eq!(out[0], -9984);
+
+        assert!(matches!(decode_brr_block(&buf, &mut state, &mut out[..15]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_brr() {
+        let buf = [ 0xc0, 0x10, 0, 0, 0, 0, 0, 0, 0, 0xc5, 0, 0, 0, 0, 0, 0, 0, 0 ];
+        let mut state = BrrState::new();
+        let mut out = [0i16; 32];
+        assert!(decode_brr(&buf, &mut state, &mut out).is_ok());
+        assert_eq!(out[0], 4096);
+        assert_eq!(out[16], 0);
+        assert!(matches!(decode_brr(&buf[..17], &mut state, &mut out[..16]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_brr(&buf, &mut state, &mut out[..16]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_encode_brr_block() {
+        // a sine wave is encoded with small error and the decoder follows the encoder
+        let mut samples = [0i16; 64];
+        for (i, s) in samples.iter_mut().enumerate() {
+            let x = i32::try_from(i % 32).unwrap_or(0);
+            let y = if x < 16 { x * (16 - x) } else { -(x - 16) * (32 - x) };
+            *s = i16::try_from(y * 200).unwrap_or(0);
+        }
+        let mut enc_state = BrrState::new();
+        let mut dec_state = BrrState::new();
+        let mut buf = [0u8; 9];
+        let mut out = [0i16; 16];
+        for (i, block_samples) in samples.chunks_exact(16).enumerate() {
+            assert!(encode_brr_block(block_samples, &mut enc_state, i == 0, &mut buf).is_ok());
+            let header = BrrBlockHeader::from_byte(buf[0]);
+            assert!(!header.is_loop && !header.is_end && header.shift <= 12);
+            if i == 0 {
+                assert_eq!(header.filter, 0);
+            }
+            assert!(decode_brr_block(&buf, &mut dec_state, &mut out).is_ok());
+            assert_eq!(enc_state, dec_state);
+            for (a, b) in out.iter().zip(block_samples) {
+                // filter 0 of the first block needs a larger shift
+                let max_error = if i == 0 { 1024 } else { 200 };
+                assert!((i32::from(*a) - i32::from(*b)).abs() < max_error);
+            }
+        }
+
+        // full scale values don't wrap around, but filter 0 can't reach them exactly
+        let samples = [ 32767, -32768, 32767, -32768, 32767, 32767, 32767, 32767,
+            -32768, -32768, -32768, -32768, 0, 0, 0, 0 ];
+        let mut state = BrrState::new();
+        assert!(encode_brr_block(&samples, &mut state, false, &mut buf).is_ok());
+        let mut state = BrrState::new();
+        assert!(decode_brr_block(&buf, &mut state, &mut out).is_ok());
+        for (a, b) in out.iter().zip(samples) {
+            assert!((i32::from(*a) - i32::from(b)).abs() <= 4096);
+        }
+
+        assert!(matches!(encode_brr_block(&samples[..15], &mut state, false, &mut buf),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_encode_brr() {
+        let mut samples = [0i16; 64];
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = if (i / 4) % 2 == 0 { 8000 } else { -8000 };
+        }
+        let mut buf = [0u8; 36];
+        assert!(encode_brr(&samples, Some(32), &mut buf).is_ok());
+        let headers = [ buf[0], buf[9], buf[18], buf[27] ].map(BrrBlockHeader::from_byte);
+        assert_eq!(headers[0].filter, 0);
+        assert_eq!(headers[2].filter, 0);
+        assert!(headers[..3].iter().all(|h| !h.is_loop && !h.is_end));
+        assert!(headers[3].is_loop && headers[3].is_end);
+
+        let mut state = BrrState::new();
+        let mut out = [0i16; 64];
+        assert!(decode_brr(&buf, &mut state, &mut out).is_ok());
+        for (a, b) in out.iter().zip(samples) {
+            assert!((i32::from(*a) - i32::from(b)).abs() < 600);
+        }
+
+        assert!(encode_brr(&samples, None, &mut buf).is_ok());
+        assert!(BrrBlockHeader::from_byte(buf[27]).is_end);
+        assert!(!BrrBlockHeader::from_byte(buf[27]).is_loop);
+
+        // errors
+        assert!(matches!(encode_brr(&samples, Some(8), &mut buf), Err(Error::InvalidData)));
+        assert!(matches!(encode_brr(&samples, Some(64), &mut buf), Err(Error::InvalidData)));
+        assert!(matches!(encode_brr(&samples[..60], None, &mut buf[..27]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_brr(&samples, None, &mut buf[..27]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = BrrState { previous: -1000, previous2: 2000 };
+        assert!(!state.is_default());
+        let mut buf = [0u8; BrrState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x18, 0xfc, 0xd0, 0x07 ]);
+        assert!(matches!(BrrState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, BrrState::new());
+        assert!(matches!(BrrState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 5]), Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,236 @@
+
+use crate::AdpcmImaState;
+use crate::Error;
+
+/// Common state management for codec states.
+///
+/// This allows managing many encoder and decoder states generically, for instance,
+/// keeping one decoder state for each RTP stream.
+pub trait CodecState: Default + PartialEq + Sized {
+    /// Number of bytes used by [`to_bytes()`](CodecState::to_bytes) and
+    /// [`from_bytes()`](CodecState::from_bytes).
+    const SERIALIZED_LEN: usize;
+
+    /// Resets the state to its initial (default) values.
+    ///
+    /// States which have settings, such as the bit rate of [`G726State`](crate::G726State),
+    /// keep their settings and only the other values are reset.
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Returns `true` if the state has its initial (default) values.
+    ///
+    /// States which have settings are compared to the initial values of the same settings.
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Writes the state to `out_buf`. The `out_buf` length must be `SERIALIZED_LEN`.
+    ///
+    /// An error is returned if the `out_buf` length isn't correct.
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error>;
+
+    /// Reads a state written by [`to_bytes()`](CodecState::to_bytes).
+    /// The `buf` length must be `SERIALIZED_LEN`.
+    ///
+    /// An error is returned if the `buf` length isn't correct or if `buf` contains values
+    /// which can't be in a valid state.
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error>;
+}
+
+// Writes state values to a buffer in little-endian byte order for CodecState::to_bytes().
+pub(crate) struct StateWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> StateWriter<'a> {
+    // returns an error if the `buf` length isn't `len`
+    pub(crate) fn new(buf: &'a mut [u8], len: usize) -> Result<StateWriter<'a>, Error> {
+        if buf.len() != len {
+            return Err(Error::InvalidBufferSize);
+        }
+        Ok(StateWriter { buf, pos: 0 })
+    }
+
+    fn write<const N: usize>(&mut self, bytes: [u8; N]) {
+        if let Some(out) = self.buf.get_mut(self.pos..self.pos + N) {
+            out.copy_from_slice(&bytes);
+        }
+        self.pos += N;
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.write([ value ]);
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.write([ u8::from(value) ]);
+    }
+
+    pub(crate) fn i16(&mut self, value: i16) {
+        self.write(value.to_le_bytes());
+    }
+
+    pub(crate) fn i32(&mut self, value: i32) {
+        self.write(value.to_le_bytes());
+    }
+
+    pub(crate) fn i64(&mut self, value: i64) {
+        self.write(value.to_le_bytes());
+    }
+
+    pub(crate) fn f64(&mut self, value: f64) {
+        self.write(value.to_le_bytes());
+    }
+
+    pub(crate) fn i32_slice(&mut self, values: &[i32]) {
+        for value in values {
+            self.i32(*value);
+        }
+    }
+}
+
+// Reads state values written by StateWriter for CodecState::from_bytes().
+pub(crate) struct StateReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    // returns an error if the `buf` length isn't `len`
+    pub(crate) fn new(buf: &'a [u8], len: usize) -> Result<StateReader<'a>, Error> {
+        if buf.len() != len {
+            return Err(Error::InvalidBufferSize);
+        }
+        Ok(StateReader { buf, pos: 0 })
+    }
+
+    fn read<const N: usize>(&mut self) -> [u8; N] {
+        let mut bytes = [0; N];
+        if let Some(b) = self.buf.get(self.pos..self.pos + N) {
+            bytes.copy_from_slice(b);
+        }
+        self.pos += N;
+        bytes
+    }
+
+    pub(crate) fn u8(&mut self) -> u8 {
+        self.read::<1>()[0]
+    }
+
+    // returns an error if the value isn't 0 or 1
+    pub(crate) fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8() {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::InvalidData),
+        }
+    }
+
+    pub(crate) fn i16(&mut self) -> i16 {
+        i16::from_le_bytes(self.read())
+    }
+
+    pub(crate) fn i32(&mut self) -> i32 {
+        i32::from_le_bytes(self.read())
+    }
+
+    pub(crate) fn i64(&mut self) -> i64 {
+        i64::from_le_bytes(self.read())
+    }
+
+    pub(crate) fn f64(&mut self) -> f64 {
+        f64::from_le_bytes(self.read())
+    }
+
+    pub(crate) fn i32_slice(&mut self, values: &mut [i32]) {
+        for value in values {
+            *value = self.i32();
+        }
+    }
+}
+
+impl CodecState for AdpcmImaState {
+    /// 16-bit little-endian predictor followed by the step index.
+    const SERIALIZED_LEN: usize = 3;
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.i16(self.predictor);
+        w.u8(self.step_index);
+        Ok(())
+    }
+
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        Ok(AdpcmImaState {
+            predictor: r.i16(),
+            step_index: r.u8(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adpcm_ima_state() {
+        let mut state = AdpcmImaState { predictor: -1000, step_index: 42 };
+        assert!(!state.is_default());
+        let mut buf = [0u8; 3];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x18, 0xfc, 42 ]);
+        assert!(matches!(AdpcmImaState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, AdpcmImaState::new());
+
+        let mut buf = [0u8; 4];
+        assert!(matches!(state.to_bytes(&mut buf), Err(Error::InvalidBufferSize)));
+        assert!(matches!(AdpcmImaState::from_bytes(&buf[..2]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_state_writer_and_reader() {
+        let mut buf = [0u8; 4];
+        let mut w = StateWriter { buf: &mut buf, pos: 0 };
+        w.u8(0xab);
+        w.i16(-2);
+        w.i16(0x1234);
+        assert_eq!(w.pos, 5);
+        // values beyond the buffer are ignored
+        assert_eq!(buf, [ 0xab, 0xfe, 0xff, 0x00 ]);
+
+        let mut r = StateReader { buf: &buf, pos: 0 };
+        assert_eq!(r.u8(), 0xab);
+        assert_eq!(r.i16(), -2);
+        // values beyond the buffer are zeros
+        assert_eq!(r.i16(), 0);
+
+        let mut buf = [0u8; 26];
+        let mut w = StateWriter { buf: &mut buf, pos: 0 };
+        w.bool(true);
+        w.i32(-2);
+        w.i32_slice(&[ 0x1234_5678 ]);
+        w.f64(-0.25);
+        w.i64(-3);
+        w.u8(2);
+        let mut r = StateReader { buf: &buf, pos: 0 };
+        assert!(matches!(r.bool(), Ok(true)));
+        assert_eq!(r.i32(), -2);
+        let mut values = [0i32; 1];
+        r.i32_slice(&mut values);
+        assert_eq!(values, [ 0x1234_5678 ]);
+        assert_eq!(r.f64(), -0.25);
+        assert_eq!(r.i64(), -3);
+        assert!(matches!(r.bool(), Err(Error::InvalidData)));
+
+        assert!(StateWriter::new(&mut buf, 26).is_ok());
+        assert!(StateReader::new(&buf, 26).is_ok());
+        assert!(matches!(StateWriter::new(&mut buf, 25), Err(Error::InvalidBufferSize)));
+        assert!(matches!(StateReader::new(&buf, 27), Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,438 @@
+use crate::CompandingLaw;
+use crate::Error;
+use crate::level::{FULL_SCALE_DB, db_to_gain, exp, ln, log10, round_to_i16};
+
+// Comfort noise (CN) for G.711 discontinuous transmission (DTX) as described in
+// ITU-T G.711 Appendix II. The CN parameters are sent in RFC 3389 payloads: a noise level
+// byte followed by optional quantized reflection coefficients describing the noise spectrum.
+
+/// The maximum number of reflection coefficients (the model order) in a CN payload
+/// supported by this crate.
+pub const CN_MAX_ORDER: usize = 10;
+
+/// Comfort noise parameters of an RFC 3389 CN payload.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CnParameters {
+    /// The noise level in -dBov, 0..=127. 0 is the loudest level and 127 is the quietest level.
+    pub noise_level: u8,
+    /// The number of reflection coefficients, 0..=`CN_MAX_ORDER`.
+    pub order: usize,
+    /// The quantized reflection coefficients. A value `q` is the reflection coefficient
+    /// `(q - 127) / 128`. Only the first `order` values are used.
+    pub reflection_coefficients: [u8; CN_MAX_ORDER],
+}
+
+impl CnParameters {
+    /// Returns the size of the RFC 3389 payload for these parameters in bytes.
+    pub fn encoded_len(&self) -> usize {
+        1 + self.order
+    }
+}
+
+/// Parses an RFC 3389 CN payload.
+///
+/// An error is returned if `buf` is empty, if it contains more than `CN_MAX_ORDER` reflection
+/// coefficients or if the reserved highest bit of the noise level byte is set.
+pub fn parse_cn_payload(buf: &[u8]) -> Result<CnParameters, Error> {
+    let Some((noise_level, coefficients)) = buf.split_first() else {
+        return Err(Error::InvalidBufferSize);
+    };
+    if coefficients.len() > CN_MAX_ORDER {
+        return Err(Error::InvalidBufferSize);
+    }
+    if *noise_level > 127 {
+        return Err(Error::InvalidData);
+    }
+    let mut params = CnParameters {
+        noise_level: *noise_level,
+        order: coefficients.len(),
+        reflection_coefficients: [0; CN_MAX_ORDER],
+    };
+    params.reflection_coefficients[..coefficients.len()].copy_from_slice(coefficients);
+    Ok(params)
+}
+
+/// Writes an RFC 3389 CN payload to `out_buf` and returns the number of bytes written.
+///
+/// The `out_buf` length must be at least [`CnParameters::encoded_len()`].
+///
+/// An error is returned if `out_buf` is too short, if the noise level is greater than 127 or
+/// if the order is greater than `CN_MAX_ORDER`.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn write_cn_payload(params: &CnParameters, out_buf: &mut [u8]) -> Result<usize, Error> {
+    if params.noise_level > 127 || params.order > CN_MAX_ORDER {
+        return Err(Error::InvalidData);
+    }
+    let len = params.encoded_len();
+    if out_buf.len() < len {
+        return Err(Error::InvalidBufferSize);
+    }
+    out_buf[0] = params.noise_level;
+    out_buf[1..len].copy_from_slice(&params.reflection_coefficients[..params.order]);
+    Ok(len)
+}
+
+// calculates the noise level and reflection coefficients of samples
+fn estimate(samples: impl Iterator<Item = i16>, order: usize) -> Result<CnParameters, Error> {
+    if order > CN_MAX_ORDER {
+        return Err(Error::InvalidData);
+    }
+    // autocorrelation
+    let mut r = [0.0f64; CN_MAX_ORDER + 1];
+    let mut history = [0.0f64; CN_MAX_ORDER];
+    let mut count = 0usize;
+    for sample in samples {
+        let x = f64::from(sample);
+        r[0] += x * x;
+        for (r, h) in r[1..=order].iter_mut().zip(history.iter()) {
+            *r += x * h;
+        }
+        history.copy_within(0..CN_MAX_ORDER - 1, 1);
+        history[0] = x;
+        count += 1;
+    }
+    if count == 0 {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut params = CnParameters {
+        noise_level: 127,
+        order,
+        reflection_coefficients: [127; CN_MAX_ORDER],
+    };
+    if r[0] == 0.0 {
+        return Ok(params);
+    }
+    let level_dbov = 10.0 * log10(r[0] / count as f64) - FULL_SCALE_DB;
+    params.noise_level = u8::try_from(round_to_i16(-level_dbov).clamp(0, 127)).unwrap_or(127);
+
+    // Levinson-Durbin recursion, a slight white noise correction keeps the filter stable
+    r[0] *= 1.0001;
+    let mut lpc = [0.0f64; CN_MAX_ORDER];
+    let mut error = r[0];
+    for m in 0..order {
+        let mut acc = r[m + 1];
+        for i in 0..m {
+            acc -= lpc[i] * r[m - i];
+        }
+        let k = (acc / error).clamp(-0.999, 0.999);
+        let previous = lpc;
+        lpc[m] = k;
+        for i in 0..m {
+            lpc[i] = previous[i] - k * previous[m - 1 - i];
+        }
+        error *= 1.0 - k * k;
+        params.reflection_coefficients[m] =
+            u8::try_from((round_to_i16(k * 128.0) + 127).clamp(0, 254)).unwrap_or(127);
+    }
+    Ok(params)
+}
+
+/// Estimates comfort noise parameters from 16-bit signed integer samples.
+///
+/// The noise level is the RMS level of `samples` rounded to the nearest dB. `order`
+/// reflection coefficients are calculated from the autocorrelation of `samples` using
+/// the Levinson-Durbin recursion. `order` can be 0 to only estimate the noise level.
+///
+/// An error is returned if `samples` is empty or if `order` is greater than `CN_MAX_ORDER`.
+pub fn estimate_cn_parameters(samples: &[i16], order: usize) -> Result<CnParameters, Error> {
+    estimate(samples.iter().copied(), order)
+}
+
+/// Estimates comfort noise parameters from G.711 A-law or μ-law encoded samples.
+///
+/// This works like [`estimate_cn_parameters()`] for the decoded samples.
+///
+/// An error is returned if `encoded` is empty or if `order` is greater than `CN_MAX_ORDER`.
+pub fn estimate_cn_parameters_companded<L: CompandingLaw>(encoded: &[u8], order: usize)
+    -> Result<CnParameters, Error> {
+
+    estimate(encoded.iter().map(|e| L::decode(*e)), order)
+}
+
+/// Comfort noise generator for the receive side of G.711 DTX.
+///
+/// The generator filters pseudorandom white noise with an all-pole filter derived from
+/// the reflection coefficients and scales it to the noise level of the CN parameters.
+/// Before [`set_parameters()`](ComfortNoiseGenerator::set_parameters) is called, the
+/// generator outputs silence.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ComfortNoiseGenerator {
+    seed: u32,
+    gain: f64,
+    order: usize,
+    lpc: [f64; CN_MAX_ORDER],
+    history: [f64; CN_MAX_ORDER],
+}
+
+impl ComfortNoiseGenerator {
+    /// Creates a new ComfortNoiseGenerator, which outputs silence.
+    pub fn new() -> ComfortNoiseGenerator {
+        ComfortNoiseGenerator::default()
+    }
+
+    /// Sets the CN parameters used for the following samples.
+    ///
+    /// Quantized reflection coefficients greater than 254 are decoded as 254 so that
+    /// the filter is always stable. The filter history is kept, so the noise continues
+    /// smoothly when the parameters are updated.
+    ///
+    /// An error is returned if the order is greater than `CN_MAX_ORDER`.
+    /// If an error is returned, the generator is left unmodified.
+    pub fn set_parameters(&mut self, params: &CnParameters) -> Result<(), Error> {
+        if params.order > CN_MAX_ORDER {
+            return Err(Error::InvalidData);
+        }
+        // step-up recursion from reflection coefficients to prediction coefficients
+        let mut lpc = [0.0f64; CN_MAX_ORDER];
+        let mut error = 1.0;
+        for (m, q) in params.reflection_coefficients[..params.order].iter().enumerate() {
+            let k = (f64::from((*q).min(254)) - 127.0) / 128.0;
+            let previous = lpc;
+            lpc[m] = k;
+            for i in 0..m {
+                lpc[i] = previous[i] - k * previous[m - 1 - i];
+            }
+            error *= 1.0 - k * k;
+        }
+        // the excitation is uniform noise with the variance of 32768^2 / 3, scale it
+        // so that the filtered noise has the RMS level of the noise level
+        let rms = db_to_gain(-f64::from(params.noise_level.min(127)));
+        self.gain = rms * exp(0.5 * ln(3.0 * error));
+        self.order = params.order;
+        self.lpc = lpc;
+        Ok(())
+    }
+
+    /// Returns the next comfort noise sample.
+    pub fn next_sample(&mut self) -> i16 {
+        self.seed = self.seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let [ b0, b1, _, _ ] = self.seed.to_be_bytes();
+        let mut value = f64::from(i16::from_be_bytes([ b0, b1 ])) * self.gain;
+        for (a, h) in self.lpc[..self.order].iter().zip(self.history.iter()) {
+            value += a * h;
+        }
+        self.history.copy_within(0..CN_MAX_ORDER - 1, 1);
+        self.history[0] = value;
+        round_to_i16(value)
+    }
+
+    /// Fills `out_samples` with comfort noise samples.
+    pub fn fill(&mut self, out_samples: &mut [i16]) {
+        for out in out_samples {
+            *out = self.next_sample();
+        }
+    }
+
+    /// Fills `out_buf` with G.711 A-law or μ-law encoded comfort noise samples.
+    pub fn fill_companded<L: CompandingLaw>(&mut self, out_buf: &mut [u8]) {
+        for out in out_buf {
+            *out = L::encode(self.next_sample());
+        }
+    }
+}
+
+/// The result of [`DtxEncoder`] for a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DtxFrame {
+    /// The frame contains speech and it should be transmitted as G.711 samples.
+    Speech,
+    /// The frame contains noise and the CN parameters should be transmitted
+    /// in an RFC 3389 payload.
+    ComfortNoise(CnParameters),
+    /// The frame contains noise, which is similar to the previously transmitted
+    /// CN parameters, so nothing needs to be transmitted.
+    NoTransmission,
+}
+
+/// Discontinuous transmission (DTX) decision for G.711 frames.
+///
+/// A frame is classified as noise if its noise level is quieter than the threshold.
+/// CN parameters are sent for the first noise frame after speech and whenever the noise
+/// level changes by `CN_LEVEL_UPDATE_THRESHOLD` dB or more from the transmitted level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DtxEncoder {
+    threshold_level: u8,
+    order: usize,
+    transmitted: Option<CnParameters>,
+}
+
+/// The noise level change in dB, which causes new CN parameters to be sent by [`DtxEncoder`].
+pub const CN_LEVEL_UPDATE_THRESHOLD: u8 = 3;
+
+impl DtxEncoder {
+    /// Creates a new DtxEncoder.
+    ///
+    /// Frames with a noise level greater than or equal to `threshold_level` (in -dBov) are
+    /// classified as noise. `order` is the number of reflection coefficients in the CN
+    /// parameters and it is limited to `CN_MAX_ORDER`.
+    pub fn new(threshold_level: u8, order: usize) -> DtxEncoder {
+        DtxEncoder {
+            threshold_level,
+            order: order.min(CN_MAX_ORDER),
+            transmitted: None,
+        }
+    }
+
+    fn decide(&mut self, params: CnParameters) -> DtxFrame {
+        if params.noise_level < self.threshold_level {
+            self.transmitted = None;
+            return DtxFrame::Speech;
+        }
+        match self.transmitted {
+            Some(t) if t.noise_level.abs_diff(params.noise_level) < CN_LEVEL_UPDATE_THRESHOLD => {
+                DtxFrame::NoTransmission
+            },
+            _ => {
+                self.transmitted = Some(params);
+                DtxFrame::ComfortNoise(params)
+            },
+        }
+    }
+
+    /// Classifies a frame of 16-bit signed integer samples.
+    ///
+    /// An error is returned if `samples` is empty.
+    /// If an error is returned, the encoder is left unmodified.
+    pub fn encode_frame(&mut self, samples: &[i16]) -> Result<DtxFrame, Error> {
+        let params = estimate_cn_parameters(samples, self.order)?;
+        Ok(self.decide(params))
+    }
+
+    /// Classifies a frame of G.711 A-law or μ-law encoded samples.
+    ///
+    /// An error is returned if `encoded` is empty.
+    /// If an error is returned, the encoder is left unmodified.
+    pub fn encode_frame_companded<L: CompandingLaw>(&mut self, encoded: &[u8])
+        -> Result<DtxFrame, Error> {
+
+        let params = estimate_cn_parameters_companded::<L>(encoded, self.order)?;
+        Ok(self.decide(params))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ALaw, ULaw, ToneGenerator, rms_level_dbov};
+
+    #[test]
+    fn test_cn_payload() {
+        let params = parse_cn_payload(&[ 40, 150, 100 ]);
+        assert!(params.is_ok());
+        let params = params.unwrap_or_default();
+        assert_eq!(params.noise_level, 40);
+        assert_eq!(params.order, 2);
+        assert_eq!(params.reflection_coefficients[..2], [ 150, 100 ]);
+        assert_eq!(params.encoded_len(), 3);
+        let mut buf = [0u8; 4];
+        assert_eq!(write_cn_payload(&params, &mut buf).ok(), Some(3));
+        assert_eq!(buf, [ 40, 150, 100, 0 ]);
+        assert!(matches!(write_cn_payload(&params, &mut buf[..2]), Err(Error::InvalidBufferSize)));
+
+        assert!(matches!(parse_cn_payload(&[]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(parse_cn_payload(&[ 0x80 ]), Err(Error::InvalidData)));
+        assert!(matches!(parse_cn_payload(&[ 0; 12 ]), Err(Error::InvalidBufferSize)));
+        assert_eq!(parse_cn_payload(&[ 127 ]).map(|p| p.order).ok(), Some(0));
+    }
+
+    #[test]
+    fn test_estimate_cn_parameters() {
+        // full scale square wave is 0 dBov
+        let square: [i16; 64] = core::array::from_fn(|i| if i % 2 == 0 { 32767 } else { -32767 });
+        let params = estimate_cn_parameters(&square, 1);
+        assert!(params.is_ok());
+        let params = params.unwrap_or_default();
+        assert_eq!(params.noise_level, 0);
+        // the samples alternate, so the reflection coefficient is close to -1
+        assert!(params.reflection_coefficients[0] < 4);
+
+        // a low frequency tone has a positive first reflection coefficient
+        let mut tone = [0i16; 800];
+        ToneGenerator::new(200.0, 8000, 300).fill(&mut tone);
+        let params = estimate_cn_parameters(&tone, 2);
+        assert!(params.is_ok());
+        let params = params.unwrap_or_default();
+        assert_eq!(params.noise_level, 44);
+        assert!(params.reflection_coefficients[0] > 240);
+
+        // silence is the quietest level and a flat spectrum
+        let params = estimate_cn_parameters(&[ 0; 10 ], 2);
+        assert!(params.is_ok());
+        let params = params.unwrap_or_default();
+        assert_eq!(params.noise_level, 127);
+        assert_eq!(params.reflection_coefficients[..2], [ 127, 127 ]);
+        let params = estimate_cn_parameters_companded::<ULaw>(&[ 0xff; 10 ], 0);
+        assert!(params.is_ok());
+        let params = params.unwrap_or_default();
+        assert_eq!(params.noise_level, 127);
+
+        assert!(matches!(estimate_cn_parameters(&[], 0), Err(Error::InvalidBufferSize)));
+        assert!(matches!(estimate_cn_parameters(&tone, 11), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_comfort_noise_generator() {
+        let mut generator = ComfortNoiseGenerator::new();
+        let mut out = [0i16; 8000];
+        generator.fill(&mut out);
+        assert!(out.iter().all(|s| *s == 0));
+
+        // white noise at -30 dBov
+        let mut params = CnParameters { noise_level: 30, ..CnParameters::default() };
+        assert!(generator.set_parameters(&params).is_ok());
+        generator.fill(&mut out);
+        assert!((rms_level_dbov(&out) + 30.0).abs() < 0.5);
+        assert!(estimate_cn_parameters(&out, 1)
+            .is_ok_and(|p| p.reflection_coefficients[0].abs_diff(127) <= 3));
+
+        // low pass noise keeps the level and the estimated spectrum is similar
+        params.order = 2;
+        params.reflection_coefficients[..2].copy_from_slice(&[ 220, 80 ]);
+        assert!(generator.set_parameters(&params).is_ok());
+        generator.fill(&mut out);
+        assert!((rms_level_dbov(&out[100..]) + 30.0).abs() < 1.0);
+        let estimated = estimate_cn_parameters(&out[100..], 2);
+        assert!(estimated.is_ok());
+        let estimated = estimated.unwrap_or_default();
+        assert!(estimated.reflection_coefficients[0].abs_diff(220) <= 4);
+        assert!(estimated.reflection_coefficients[1].abs_diff(80) <= 4);
+
+        let mut encoded = [0u8; 8000];
+        generator.fill_companded::<ALaw>(&mut encoded);
+        let estimated = estimate_cn_parameters_companded::<ALaw>(&encoded, 0);
+        assert!(estimated.is_ok());
+        let estimated = estimated.unwrap_or_default();
+        assert!(estimated.noise_level.abs_diff(30) <= 1);
+
+        params.order = 11;
+        assert!(matches!(generator.set_parameters(&params), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_dtx_encoder() {
+        let mut encoder = DtxEncoder::new(50, 0);
+        let mut speech = [0i16; 160];
+        ToneGenerator::new(440.0, 8000, 8000).fill(&mut speech);
+        let mut noise = [0i16; 160];
+        ToneGenerator::new(440.0, 8000, 50).fill(&mut noise);
+        let mut quieter_noise = [0i16; 160];
+        ToneGenerator::new(440.0, 8000, 20).fill(&mut quieter_noise);
+
+        assert_eq!(encoder.encode_frame(&speech).ok(), Some(DtxFrame::Speech));
+        assert!(matches!(encoder.encode_frame(&noise),
+            Ok(DtxFrame::ComfortNoise(CnParameters { noise_level: 59, .. }))));
+        assert_eq!(encoder.encode_frame(&noise).ok(), Some(DtxFrame::NoTransmission));
+        assert!(matches!(encoder.encode_frame(&quieter_noise),
+            Ok(DtxFrame::ComfortNoise(CnParameters { noise_level: 67, .. }))));
+        assert_eq!(encoder.encode_frame(&speech).ok(), Some(DtxFrame::Speech));
+        assert!(matches!(encoder.encode_frame(&quieter_noise), Ok(DtxFrame::ComfortNoise(_))));
+
+        // A-law silence decodes to 8, which is 72 -dBov
+        let encoded = [ 0xd5u8; 160 ];
+        assert!(matches!(encoder.encode_frame_companded::<ALaw>(&encoded),
+            Ok(DtxFrame::ComfortNoise(CnParameters { noise_level: 72, .. }))));
+        assert_eq!(encoder.encode_frame_companded::<ALaw>(&encoded).ok(),
+            Some(DtxFrame::NoTransmission));
+        assert!(matches!(encoder.encode_frame(&[]), Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,67 @@
+
+use crate::{decode_alaw, encode_alaw};
+use crate::{decode_ulaw, encode_ulaw};
+
+/// G.711 companding law.
+///
+/// This trait makes it possible to write code which is generic over A-law and μ-law.
+pub trait CompandingLaw {
+    /// Decodes a 8-bit encoded value to a linear 16-bit signed integer sample value.
+    fn decode(encoded: u8) -> i16;
+
+    /// Encodes a linear 16-bit signed integer sample value to a 8-bit encoded value.
+    fn encode(linear: i16) -> u8;
+}
+
+/// G.711 A-law.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ALaw;
+
+impl CompandingLaw for ALaw {
+    #[inline(always)]
+    fn decode(encoded: u8) -> i16 {
+        decode_alaw(encoded)
+    }
+
+    #[inline(always)]
+    fn encode(linear: i16) -> u8 {
+        encode_alaw(linear)
+    }
+}
+
+/// G.711 μ-law.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ULaw;
+
+impl CompandingLaw for ULaw {
+    #[inline(always)]
+    fn decode(encoded: u8) -> i16 {
+        decode_ulaw(encoded)
+    }
+
+    #[inline(always)]
+    fn encode(linear: i16) -> u8 {
+        encode_ulaw(linear)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip<L: CompandingLaw>(linear: i16) -> i16 {
+        L::decode(L::encode(linear))
+    }
+
+    #[test]
+    fn test_companding_law() {
+        for i in 0..=255 {
+            assert_eq!(ALaw::decode(i), decode_alaw(i));
+            assert_eq!(ULaw::decode(i), decode_ulaw(i));
+        }
+        assert_eq!(ALaw::encode(-5430), encode_alaw(-5430));
+        assert_eq!(ULaw::encode(-5430), encode_ulaw(-5430));
+        assert_eq!(roundtrip::<ALaw>(1000), 1008);
+        assert_eq!(roundtrip::<ULaw>(1000), 988);
+    }
+}
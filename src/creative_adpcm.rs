@@ -0,0 +1,475 @@
+use crate::{CodecState, Error};
+use crate::codec_state::{StateReader, StateWriter};
+
+// Creative Labs Sound Blaster 8-bit ADPCM, as decoded by the Sound Blaster DSP.
+// The tables are from DOSBox. The decoder keeps an 8-bit unsigned reference sample and
+// a scale, which selects a row of the table.
+
+// sample differences for each scale (rows) and 4-bit code (columns), the highest code bit
+// is the sign bit
+const SCALE_MAP_4: &[i8; 64] = &[
+    0, 1, 2, 3, 4, 5, 6, 7, 0, -1, -2, -3, -4, -5, -6, -7,
+    1, 3, 5, 7, 9, 11, 13, 15, -1, -3, -5, -7, -9, -11, -13, -15,
+    2, 6, 10, 14, 18, 22, 26, 30, -2, -6, -10, -14, -18, -22, -26, -30,
+    4, 12, 20, 28, 36, 44, 52, 60, -4, -12, -20, -28, -36, -44, -52, -60,
+];
+
+// scale adjustments: the scale increases after large codes and decreases after zero codes
+const ADJUST_MAP_4: &[i8; 64] = &[
+    0, 0, 0, 0, 0, 16, 16, 16, 0, 0, 0, 0, 0, 16, 16, 16,
+    -16, 0, 0, 0, 0, 16, 16, 16, -16, 0, 0, 0, 0, 16, 16, 16,
+    -16, 0, 0, 0, 0, 16, 16, 16, -16, 0, 0, 0, 0, 16, 16, 16,
+    -16, 0, 0, 0, 0, 0, 0, 0, -16, 0, 0, 0, 0, 0, 0, 0,
+];
+
+const SCALE_MAP_3: &[i8; 40] = &[
+    0, 1, 2, 3, 0, -1, -2, -3,
+    1, 3, 5, 7, -1, -3, -5, -7,
+    2, 6, 10, 14, -2, -6, -10, -14,
+    4, 12, 20, 28, -4, -12, -20, -28,
+    5, 15, 25, 35, -5, -15, -25, -35,
+];
+
+const ADJUST_MAP_3: &[i8; 40] = &[
+    0, 0, 0, 8, 0, 0, 0, 8,
+    -8, 0, 0, 8, -8, 0, 0, 8,
+    -8, 0, 0, 8, -8, 0, 0, 8,
+    -8, 0, 0, 8, -8, 0, 0, 8,
+    -8, 0, 0, 0, -8, 0, 0, 0,
+];
+
+const SCALE_MAP_2: &[i8; 24] = &[
+    0, 1, 0, -1,
+    1, 3, -1, -3,
+    2, 6, -2, -6,
+    4, 12, -4, -12,
+    8, 24, -8, -24,
+    16, 48, -16, -48,
+];
+
+const ADJUST_MAP_2: &[i8; 24] = &[
+    0, 4, 0, 4,
+    -4, 4, -4, 4,
+    -4, 4, -4, 4,
+    -4, 4, -4, 4,
+    -4, 4, -4, 4,
+    -4, 0, -4, 0,
+];
+
+/// State values for the Creative ADPCM decoder and encoder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreativeAdpcmState {
+    /// Previous sample as an 8-bit unsigned value.
+    pub reference: u8,
+    /// Index to the first table entry of the current scale.
+    pub scale: u8,
+}
+
+impl CreativeAdpcmState {
+    /// Creates a new state with the reference sample 0x80 (silence) and the smallest scale.
+    pub fn new() -> CreativeAdpcmState {
+        CreativeAdpcmState::with_reference(0x80)
+    }
+
+    /// Creates a new state with an 8-bit unsigned reference sample and the smallest scale.
+    ///
+    /// Creative ADPCM data usually starts with an uncompressed reference byte.
+    pub fn with_reference(reference: u8) -> CreativeAdpcmState {
+        CreativeAdpcmState {
+            reference,
+            scale: 0,
+        }
+    }
+}
+
+impl Default for CreativeAdpcmState {
+    fn default() -> Self {
+        CreativeAdpcmState::new()
+    }
+}
+
+impl CodecState for CreativeAdpcmState {
+    /// Reference sample followed by the scale.
+    const SERIALIZED_LEN: usize = 2;
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.u8(self.reference);
+        w.u8(self.scale);
+        Ok(())
+    }
+
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        Ok(CreativeAdpcmState {
+            reference: r.u8(),
+            scale: r.u8(),
+        })
+    }
+}
+
+fn reference_to_sample(reference: u8) -> i16 {
+    i16::from_le_bytes([ 0, reference ^ 0x80 ])
+}
+
+fn decode_with_tables(code: u8, state: &mut CreativeAdpcmState, scale_map: &[i8],
+    adjust_map: &[i8]) -> i16 {
+
+    let index = (usize::from(code) + usize::from(state.scale)).min(scale_map.len() - 1);
+    let reference = i16::from(state.reference) + i16::from(scale_map[index]);
+    state.reference = u8::try_from(reference.clamp(0, 255)).unwrap_or(0);
+    state.scale = state.scale.saturating_add_signed(adjust_map[index]);
+    reference_to_sample(state.reference)
+}
+
+fn encode_with_tables(sample_value: i16, state: &mut CreativeAdpcmState,
+    codes: impl Iterator<Item = u8>, decode: fn(u8, &mut CreativeAdpcmState) -> i16) -> u8 {
+
+    // select the code with the smallest error
+    let mut best_code = 0;
+    let mut best_error = u32::MAX;
+    let mut best_state = state.clone();
+    for code in codes {
+        let mut candidate = state.clone();
+        let decoded = decode(code, &mut candidate);
+        let error = (i32::from(sample_value) - i32::from(decoded)).unsigned_abs();
+        if error < best_error {
+            best_code = code;
+            best_error = error;
+            best_state = candidate;
+        }
+    }
+    *state = best_state;
+    best_code
+}
+
+/// Decodes a 4-bit encoded Creative ADPCM value to a linear 16-bit signed integer
+/// sample value.
+///
+/// Only the lowest 4 bits of `code` are used and the top-most bits are ignored.
+/// The decoded samples have 8-bit precision.
+///
+/// The `state` parameter should be initialized with [`CreativeAdpcmState::with_reference()`]
+/// using the reference byte of the audio stream. This method updates `state` with new values.
+/// Subsequent calls should pass in the state values from the previous call.
+pub fn decode_creative_adpcm4(code: u8, state: &mut CreativeAdpcmState) -> i16 {
+    decode_with_tables(code & 0x0f, state, SCALE_MAP_4, ADJUST_MAP_4)
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a 4-bit encoded Creative ADPCM
+/// value.
+///
+/// The code with the smallest error is selected.
+///
+/// The `state` parameter should be initialized with [`CreativeAdpcmState::with_reference()`]
+/// using the reference byte written to the audio stream. This method updates `state` with new
+/// values. Subsequent calls should pass in the state values from the previous call.
+pub fn encode_creative_adpcm4(sample_value: i16, state: &mut CreativeAdpcmState) -> u8 {
+    encode_with_tables(sample_value, state, 0..16, decode_creative_adpcm4)
+}
+
+/// Decodes 4-bit Creative ADPCM data to 16-bit signed integer samples.
+///
+/// Each byte contains two codes, the high nibble is decoded first. `buf` shouldn't contain
+/// the reference byte, which is used to initialize `state`.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * buf.len()`. An error is returned if the `out_samples` length isn't correct. If an error
+/// is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_creative_adpcm4_slice(buf: &[u8], state: &mut CreativeAdpcmState,
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    if out_samples.len() != 2 * buf.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.iter().zip(out_samples.chunks_exact_mut(2)) {
+        out[0] = decode_creative_adpcm4(b >> 4, state);
+        out[1] = decode_creative_adpcm4(b & 0x0f, state);
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to 4-bit Creative ADPCM data.
+///
+/// The first sample is encoded to the high nibble of the first byte. The reference byte isn't
+/// written, it should be written before the data and used to initialize `state`.
+///
+/// This function outputs encoded bytes to `out_buf`. The `samples` length must be
+/// `2 * out_buf.len()`. An error is returned if the `samples` length isn't correct. If an error
+/// is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_creative_adpcm4_slice(samples: &[i16], state: &mut CreativeAdpcmState,
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    if samples.len() != 2 * out_buf.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (s, out) in samples.chunks_exact(2).zip(out_buf.iter_mut()) {
+        *out = encode_creative_adpcm4(s[0], state) << 4 | encode_creative_adpcm4(s[1], state);
+    }
+    Ok(())
+}
+
+/// Decodes a 3-bit encoded Creative ADPCM value to a linear 16-bit signed integer
+/// sample value.
+///
+/// Only the lowest 3 bits of `code` are used and the top-most bits are ignored.
+/// The decoded samples have 8-bit precision.
+///
+/// The `state` parameter should be initialized with [`CreativeAdpcmState::with_reference()`]
+/// using the reference byte of the audio stream. This method updates `state` with new values.
+/// Subsequent calls should pass in the state values from the previous call.
+pub fn decode_creative_adpcm3(code: u8, state: &mut CreativeAdpcmState) -> i16 {
+    decode_with_tables(code & 0x07, state, SCALE_MAP_3, ADJUST_MAP_3)
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a 3-bit encoded Creative ADPCM
+/// value.
+///
+/// The code with the smallest error is selected. If `is_last_in_byte` is `true`, only codes
+/// with the lowest bit cleared are selected, because the third code of each byte has only
+/// 2 bits.
+///
+/// The `state` parameter should be initialized with [`CreativeAdpcmState::with_reference()`]
+/// using the reference byte written to the audio stream. This method updates `state` with new
+/// values. Subsequent calls should pass in the state values from the previous call.
+pub fn encode_creative_adpcm3(sample_value: i16, is_last_in_byte: bool,
+    state: &mut CreativeAdpcmState) -> u8 {
+
+    let step = if is_last_in_byte { 2 } else { 1 };
+    encode_with_tables(sample_value, state, (0..8).step_by(step), decode_creative_adpcm3)
+}
+
+/// Decodes a 2-bit encoded Creative ADPCM value to a linear 16-bit signed integer
+/// sample value.
+///
+/// Only the lowest 2 bits of `code` are used and the top-most bits are ignored.
+/// The decoded samples have 8-bit precision.
+///
+/// The `state` parameter should be initialized with [`CreativeAdpcmState::with_reference()`]
+/// using the reference byte of the audio stream. This method updates `state` with new values.
+/// Subsequent calls should pass in the state values from the previous call.
+pub fn decode_creative_adpcm2(code: u8, state: &mut CreativeAdpcmState) -> i16 {
+    decode_with_tables(code & 0x03, state, SCALE_MAP_2, ADJUST_MAP_2)
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a 2-bit encoded Creative ADPCM
+/// value.
+///
+/// The code with the smallest error is selected.
+///
+/// The `state` parameter should be initialized with [`CreativeAdpcmState::with_reference()`]
+/// using the reference byte written to the audio stream. This method updates `state` with new
+/// values. Subsequent calls should pass in the state values from the previous call.
+pub fn encode_creative_adpcm2(sample_value: i16, state: &mut CreativeAdpcmState) -> u8 {
+    encode_with_tables(sample_value, state, 0..4, decode_creative_adpcm2)
+}
+
+/// Decodes 3-bit (2.6 bits per sample) Creative ADPCM data to 16-bit signed integer samples.
+///
+/// Each byte contains three codes starting from the highest bits: two 3-bit codes and
+/// a 2-bit code, which is decoded as a 3-bit code with the lowest bit cleared. `buf` shouldn't
+/// contain the reference byte, which is used to initialize `state`.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `3 * buf.len()`. An error is returned if the `out_samples` length isn't correct. If an error
+/// is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_creative_adpcm3_slice(buf: &[u8], state: &mut CreativeAdpcmState,
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    if out_samples.len() != 3 * buf.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.iter().zip(out_samples.chunks_exact_mut(3)) {
+        out[0] = decode_creative_adpcm3(b >> 5, state);
+        out[1] = decode_creative_adpcm3(b >> 2, state);
+        out[2] = decode_creative_adpcm3(b << 1, state);
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to 3-bit (2.6 bits per sample) Creative ADPCM data.
+///
+/// The byte layout is described in [`decode_creative_adpcm3_slice()`]. The reference byte
+/// isn't written, it should be written before the data and used to initialize `state`.
+///
+/// This function outputs encoded bytes to `out_buf`. The `samples` length must be
+/// `3 * out_buf.len()`. An error is returned if the `samples` length isn't correct. If an error
+/// is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_creative_adpcm3_slice(samples: &[i16], state: &mut CreativeAdpcmState,
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    if samples.len() != 3 * out_buf.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (s, out) in samples.chunks_exact(3).zip(out_buf.iter_mut()) {
+        *out = encode_creative_adpcm3(s[0], false, state) << 5
+            | encode_creative_adpcm3(s[1], false, state) << 2
+            | encode_creative_adpcm3(s[2], true, state) >> 1;
+    }
+    Ok(())
+}
+
+/// Decodes 2-bit Creative ADPCM data to 16-bit signed integer samples.
+///
+/// Each byte contains four codes, the highest bits are decoded first. `buf` shouldn't contain
+/// the reference byte, which is used to initialize `state`.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `4 * buf.len()`. An error is returned if the `out_samples` length isn't correct. If an error
+/// is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_creative_adpcm2_slice(buf: &[u8], state: &mut CreativeAdpcmState,
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    if out_samples.len() != 4 * buf.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.iter().zip(out_samples.chunks_exact_mut(4)) {
+        for (i, sample) in out.iter_mut().enumerate() {
+            *sample = decode_creative_adpcm2(b >> (6 - 2 * i), state);
+        }
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to 2-bit Creative ADPCM data.
+///
+/// The first sample is encoded to the highest bits of the first byte. The reference byte isn't
+/// written, it should be written before the data and used to initialize `state`.
+///
+/// This function outputs encoded bytes to `out_buf`. The `samples` length must be
+/// `4 * out_buf.len()`. An error is returned if the `samples` length isn't correct. If an error
+/// is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_creative_adpcm2_slice(samples: &[i16], state: &mut CreativeAdpcmState,
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    if samples.len() != 4 * out_buf.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (s, out) in samples.chunks_exact(4).zip(out_buf.iter_mut()) {
+        *out = s.iter().fold(0, |b, sample| b << 2 | encode_creative_adpcm2(*sample, state));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signal::tone;
+
+    #[test]
+    fn test_decode_creative_adpcm4() {
+        let mut state = CreativeAdpcmState::with_reference(0x80);
+        assert_eq!(decode_creative_adpcm4(7, &mut state), 7 << 8);
+        assert_eq!(state, CreativeAdpcmState { reference: 0x87, scale: 16 });
+        assert_eq!(decode_creative_adpcm4(0x0f, &mut state), -8 << 8);
+        assert_eq!(state, CreativeAdpcmState { reference: 0x78, scale: 32 });
+        // even a zero code changes the reference with larger scales
+        assert_eq!(decode_creative_adpcm4(0, &mut state), -6 << 8);
+        assert_eq!(state.scale, 16);
+        // the scale stays at the largest row
+        let mut state = CreativeAdpcmState { reference: 0xf0, scale: 48 };
+        assert_eq!(decode_creative_adpcm4(7, &mut state), 0x7f << 8);
+        assert_eq!(state.scale, 48);
+        let mut state = CreativeAdpcmState { reference: 0x10, scale: 48 };
+        assert_eq!(decode_creative_adpcm4(0x1f, &mut state), -128 << 8);
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = CreativeAdpcmState::new();
+        assert!(state.is_default());
+        decode_creative_adpcm4(7, &mut state);
+        let mut buf = [0u8; CreativeAdpcmState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x87, 16 ]);
+        assert!(matches!(CreativeAdpcmState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state.reference, 0x80);
+        assert!(matches!(CreativeAdpcmState::from_bytes(&buf[1..]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 3]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_creative_adpcm4_slice() {
+        let mut samples = [0i16; 200];
+        for (i, value) in samples.iter_mut().enumerate() {
+            *value = tone(i);
+        }
+        let mut encoder = CreativeAdpcmState::new();
+        let mut buf = [0u8; 100];
+        assert!(encode_creative_adpcm4_slice(&samples, &mut encoder, &mut buf).is_ok());
+        let mut decoder = CreativeAdpcmState::new();
+        let mut out = [0i16; 200];
+        assert!(decode_creative_adpcm4_slice(&buf, &mut decoder, &mut out).is_ok());
+        assert_eq!(encoder, decoder);
+        for (s, d) in samples[20..].iter().zip(&out[20..]) {
+            assert!((i32::from(*s) - i32::from(*d)).abs() < 2048);
+        }
+
+        assert!(matches!(decode_creative_adpcm4_slice(&buf, &mut decoder, &mut out[..199]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_creative_adpcm4_slice(&samples[..199], &mut encoder, &mut buf),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_creative_adpcm3_and_2() {
+        let mut state = CreativeAdpcmState::with_reference(0x80);
+        assert_eq!(decode_creative_adpcm3(3, &mut state), 3 << 8);
+        assert_eq!(state, CreativeAdpcmState { reference: 0x83, scale: 8 });
+        assert_eq!(decode_creative_adpcm3(4, &mut state), 2 << 8);
+        assert_eq!(state, CreativeAdpcmState { reference: 0x82, scale: 0 });
+
+        let mut state = CreativeAdpcmState::with_reference(0x80);
+        assert_eq!(decode_creative_adpcm2(1, &mut state), 1 << 8);
+        assert_eq!(state.scale, 4);
+        assert_eq!(decode_creative_adpcm2(3, &mut state), -2 << 8);
+        assert_eq!(state.scale, 8);
+        assert_eq!(decode_creative_adpcm2(2, &mut state), -4 << 8);
+        assert_eq!(state.scale, 4);
+
+        // codes 3, 3 and 1, the third code of a byte is shifted left by one bit
+        let mut state = CreativeAdpcmState::with_reference(0x80);
+        let mut out = [0i16; 3];
+        assert!(decode_creative_adpcm3_slice(&[ 0x6d ], &mut state, &mut out).is_ok());
+        let mut expected_state = CreativeAdpcmState::with_reference(0x80);
+        assert_eq!(out, [ decode_creative_adpcm3(3, &mut expected_state),
+            decode_creative_adpcm3(3, &mut expected_state),
+            decode_creative_adpcm3(2, &mut expected_state) ]);
+    }
+
+    #[test]
+    fn test_creative_adpcm3_and_2_slice() {
+        let mut samples = [0i16; 240];
+        for (i, value) in samples.iter_mut().enumerate() {
+            *value = [ 0, 3000, 5000, 3000, 0, -3000, -5000, -3000 ][(i / 2) % 8];
+        }
+        let mut encoder = CreativeAdpcmState::new();
+        let mut buf = [0u8; 80];
+        assert!(encode_creative_adpcm3_slice(&samples, &mut encoder, &mut buf).is_ok());
+        let mut decoder = CreativeAdpcmState::new();
+        let mut out = [0i16; 240];
+        assert!(decode_creative_adpcm3_slice(&buf, &mut decoder, &mut out).is_ok());
+        assert_eq!(encoder, decoder);
+        for (s, d) in samples[24..].iter().zip(&out[24..]) {
+            assert!((i32::from(*s) - i32::from(*d)).abs() <= 2560);
+        }
+
+        let mut encoder = CreativeAdpcmState::new();
+        let mut buf = [0u8; 60];
+        assert!(encode_creative_adpcm2_slice(&samples, &mut encoder, &mut buf).is_ok());
+        let mut decoder = CreativeAdpcmState::new();
+        assert!(decode_creative_adpcm2_slice(&buf, &mut decoder, &mut out).is_ok());
+        assert_eq!(encoder, decoder);
+        for (s, d) in samples[24..].iter().zip(&out[24..]) {
+            assert!((i32::from(*s) - i32::from(*d)).abs() < 4096);
+        }
+
+        assert!(matches!(decode_creative_adpcm3_slice(&buf, &mut decoder, &mut out[..179]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_creative_adpcm2_slice(&samples[..239], &mut encoder, &mut buf),
+            Err(Error::InvalidBufferSize)));
+    }
+}
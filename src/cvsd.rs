@@ -0,0 +1,595 @@
+
+use crate::{CodecState, Error};
+use crate::codec_state::{StateReader, StateWriter};
+
+// Bluetooth CVSD parameters from the Bluetooth Core Specification, Part B, section 9.2.
+// The accumulator and the step size have 10 fractional bits.
+const CVSD_FRACTION_BITS: u32 = 10;
+// minimum and maximum step size
+const CVSD_STEP_MIN: i32 = 10 << CVSD_FRACTION_BITS;
+const CVSD_STEP_MAX: i32 = 1280 << CVSD_FRACTION_BITS;
+// the step size decays by 1/1024 (beta = 1 - 1/1024)
+const CVSD_STEP_DECAY_SHIFT: u32 = 10;
+// the accumulator decays by 1/32 (h = 1 - 1/32)
+const CVSD_ACCUMULATOR_DECAY_SHIFT: u32 = 5;
+// number of equal bits which increase the step size (J = K = 4)
+const CVSD_RUN_MASK: u8 = 0x0f;
+
+/// State values for the CVSD encoder and decoder.
+///
+/// The state should be created with [`CvsdState::new()`] at the start of the stream.
+/// The encoder and decoder each need their own state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CvsdState {
+    // accumulator (the predicted sample value) with fractional bits
+    accumulator: i32,
+    // step size with fractional bits
+    step: i32,
+    // previous bits, the latest bit is the lowest bit
+    history: u8,
+}
+
+impl CvsdState {
+    /// Creates a new CvsdState with initial values: zero accumulator and minimum step size.
+    pub fn new() -> CvsdState {
+        CvsdState {
+            accumulator: 0,
+            step: CVSD_STEP_MIN,
+            history: 0,
+        }
+    }
+
+    fn update(&mut self, bit: u8) -> i16 {
+        self.history = ((self.history << 1) | bit) & CVSD_RUN_MASK;
+        // syllabic companding: the step size grows when the last bits are equal
+        if self.history == 0 || self.history == CVSD_RUN_MASK {
+            self.step = (self.step + CVSD_STEP_MIN).min(CVSD_STEP_MAX);
+        } else {
+            self.step = (self.step - (self.step >> CVSD_STEP_DECAY_SHIFT)).max(CVSD_STEP_MIN);
+        }
+        let y = if bit != 0 {
+            self.accumulator + self.step
+        } else {
+            self.accumulator - self.step
+        }.clamp(-32768 << CVSD_FRACTION_BITS, 32767 << CVSD_FRACTION_BITS);
+        self.accumulator = y - (y >> CVSD_ACCUMULATOR_DECAY_SHIFT);
+        i16::try_from(self.accumulator >> CVSD_FRACTION_BITS).unwrap_or(0)
+    }
+}
+
+impl Default for CvsdState {
+    fn default() -> Self {
+        CvsdState::new()
+    }
+}
+
+impl CodecState for CvsdState {
+    /// 32-bit little-endian accumulator and step size followed by the bit history.
+    const SERIALIZED_LEN: usize = 9;
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.i32(self.accumulator);
+        w.i32(self.step);
+        w.u8(self.history);
+        Ok(())
+    }
+
+    /// An error is returned if the `buf` length isn't correct or if a value is out of range.
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        let state = CvsdState {
+            accumulator: r.i32(),
+            step: r.i32(),
+            history: r.u8(),
+        };
+        if !(-32768 << CVSD_FRACTION_BITS..=32767 << CVSD_FRACTION_BITS)
+                .contains(&state.accumulator)
+            || !(CVSD_STEP_MIN..=CVSD_STEP_MAX).contains(&state.step)
+            || state.history > CVSD_RUN_MASK {
+            return Err(Error::InvalidData);
+        }
+        Ok(state)
+    }
+}
+
+/// Decodes a CVSD bit to a linear 16-bit signed integer sample value.
+///
+/// CVSD (continuously variable slope delta modulation) codes each sample with one bit.
+/// The parameters are the ones specified for Bluetooth, where the sample rate is 64 kHz.
+///
+/// Only the lowest bit of `bit` is used and the top-most bits are ignored.
+///
+/// The `state` parameter should be initialized with [`CvsdState::new()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn decode_cvsd(bit: u8, state: &mut CvsdState) -> i16 {
+    state.update(bit & 1)
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a CVSD bit.
+///
+/// The `state` parameter should be initialized with [`CvsdState::new()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn encode_cvsd(sample_value: i16, state: &mut CvsdState) -> u8 {
+    let bit = u8::from(i32::from(sample_value) << CVSD_FRACTION_BITS >= state.accumulator);
+    state.update(bit);
+    bit
+}
+
+/// Decodes CVSD encoded bytes to 16-bit signed integer samples.
+///
+/// Each byte contains eight samples. The lowest bit is decoded first, which is the bit
+/// order of the Bluetooth air interface.
+///
+/// The `state` parameter should be initialized with [`CvsdState::new()`] and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `8 * buf.len()`.
+///
+/// An error is returned if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_cvsd_slice(buf: &[u8], state: &mut CvsdState, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    if buf.len().checked_mul(8) != Some(out_samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.iter().zip(out_samples.chunks_exact_mut(8)) {
+        for (i, out) in out.iter_mut().enumerate() {
+            *out = decode_cvsd(b >> i, state);
+        }
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to CVSD encoded bytes.
+///
+/// Each byte contains eight samples. The first sample is in the lowest bit, which is
+/// the bit order of the Bluetooth air interface.
+///
+/// The `state` parameter should be initialized with [`CvsdState::new()`] and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs encoded bytes to `out_buf`. The `samples` length must be
+/// `8 * out_buf.len()`.
+///
+/// An error is returned if the `samples` length isn't correct.
+/// If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_cvsd_slice(samples: &[i16], state: &mut CvsdState, out_buf: &mut [u8])
+    -> Result<(), Error> {
+
+    if out_buf.len().checked_mul(8) != Some(samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (s, out) in samples.chunks_exact(8).zip(out_buf.iter_mut()) {
+        *out = 0;
+        for (i, s) in s.iter().enumerate() {
+            *out |= encode_cvsd(*s, state) << i;
+        }
+    }
+    Ok(())
+}
+
+// HC-55516 parameters of the floating point model in MAME's hc55516.c, which assumes
+// a 16 kHz bit rate
+const HC55516_FILTER_MIN: f64 = 0.0416;
+const HC55516_FILTER_MAX: f64 = 1.0954;
+// syllabic filter charge and decay, pow(exp(-1), 1 / (0.004 * 16000))
+const HC55516_FILTER_CHARGE: f64 = 0.984_496_437_005_408_5;
+const HC55516_FILTER_DECAY: f64 = 0.984_496_437_005_408_5;
+// integrator leak, pow(exp(-1), 1 / (0.001 * 16000))
+const HC55516_INTEGRATOR_LEAK: f64 = 0.939_413_062_813_475_8;
+const HC55516_SAMPLE_GAIN: f64 = 10000.0;
+
+/// Variant of the Harris HC-55516 family of CVSD chips.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Hc55516Variant {
+    /// HC-55516 (and HC-55536, MC3417): three equal bits charge the syllabic filter.
+    #[default]
+    Hc55516,
+    /// HC-55532 (and MC3418): four equal bits charge the syllabic filter.
+    Hc55532,
+}
+
+impl Hc55516Variant {
+    fn coincidence_mask(self) -> u8 {
+        match self {
+            Hc55516Variant::Hc55516 => 0x07,
+            Hc55516Variant::Hc55532 => 0x0f,
+        }
+    }
+}
+
+/// State values for the HC-55516 CVSD decoder.
+///
+/// The state should be created with [`Hc55516State::new()`] at the start of the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hc55516State {
+    variant: Hc55516Variant,
+    // previous bits, the latest bit is the lowest bit
+    shift_register: u8,
+    // syllabic filter (the step size)
+    filter: f64,
+    // integrator
+    integrator: f64,
+}
+
+impl Hc55516State {
+    /// Creates a new Hc55516State for the given chip variant with initial values.
+    pub fn new(variant: Hc55516Variant) -> Hc55516State {
+        Hc55516State {
+            variant,
+            shift_register: 0,
+            filter: 0.0,
+            integrator: 0.0,
+        }
+    }
+}
+
+impl Default for Hc55516State {
+    fn default() -> Self {
+        Hc55516State::new(Hc55516Variant::default())
+    }
+}
+
+impl CodecState for Hc55516State {
+    /// Variant (0 = HC-55516, 1 = HC-55532) and the shift register followed by 64-bit
+    /// little-endian floating point syllabic filter and integrator values.
+    const SERIALIZED_LEN: usize = 18;
+
+    /// Resets the state to initial values. The variant is kept.
+    fn reset(&mut self) {
+        *self = Hc55516State::new(self.variant);
+    }
+
+    /// Returns `true` if the state has the initial values of its variant.
+    fn is_default(&self) -> bool {
+        *self == Hc55516State::new(self.variant)
+    }
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.u8(match self.variant {
+            Hc55516Variant::Hc55516 => 0,
+            Hc55516Variant::Hc55532 => 1,
+        });
+        w.u8(self.shift_register);
+        w.f64(self.filter);
+        w.f64(self.integrator);
+        Ok(())
+    }
+
+    /// An error is returned if the `buf` length isn't correct, if the variant is invalid or
+    /// if a value is out of range.
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        let variant = match r.u8() {
+            0 => Hc55516Variant::Hc55516,
+            1 => Hc55516Variant::Hc55532,
+            _ => return Err(Error::InvalidData),
+        };
+        let state = Hc55516State {
+            variant,
+            shift_register: r.u8(),
+            filter: r.f64(),
+            integrator: r.f64(),
+        };
+        if state.shift_register > variant.coincidence_mask()
+            || !(0.0..=HC55516_FILTER_MAX).contains(&state.filter)
+            || !state.integrator.is_finite() {
+            return Err(Error::InvalidData);
+        }
+        Ok(state)
+    }
+}
+
+/// Decodes a HC-55516 CVSD bit to a linear 16-bit signed integer sample value.
+///
+/// The Harris HC-55516 family of CVSD chips was used for speech in Williams and Midway
+/// arcade and pinball machines. The decoder is bit-exact with the floating point model of
+/// the chip's syllabic filter and leaky integrator in MAME's hc55516.c, which assumes a 16 kHz
+/// bit rate. Like MAME, it compresses the integrator range to 16 bits instead of clipping it.
+/// The output is the sample value for each bit, before MAME resamples it to its output rate.
+///
+/// Only the lowest bit of `bit` is used and the top-most bits are ignored.
+///
+/// The `state` parameter should be initialized with [`Hc55516State::new()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn decode_hc55516(bit: u8, state: &mut Hc55516State) -> i16 {
+    let bit = bit & 1;
+    let mask = state.variant.coincidence_mask();
+    state.shift_register = ((state.shift_register << 1) | bit) & mask;
+    // the integrator is updated with the previous step size
+    let integrator = if bit != 0 {
+        state.integrator + state.filter
+    } else {
+        state.integrator - state.filter
+    };
+    state.integrator = integrator * HC55516_INTEGRATOR_LEAK;
+    state.filter = if state.shift_register == 0 || state.shift_register == mask {
+        (HC55516_FILTER_MAX - (HC55516_FILTER_MAX - state.filter) * HC55516_FILTER_CHARGE)
+            .min(HC55516_FILTER_MAX)
+    } else {
+        (state.filter * HC55516_FILTER_DECAY).max(HC55516_FILTER_MIN)
+    };
+    let sample = state.integrator * HC55516_SAMPLE_GAIN;
+    let sample = if sample < 0.0 {
+        sample / (-sample * (1.0 / 32768.0) + 1.0)
+    } else {
+        sample / (sample * (1.0 / 32768.0) + 1.0)
+    };
+    #[allow(clippy::cast_possible_truncation)] // compressed value is always in the i16 range
+    let sample = sample as i16;
+    sample
+}
+
+/// Decodes HC-55516 CVSD encoded bytes to 16-bit signed integer samples.
+///
+/// Each byte contains eight samples. The highest bit is decoded first, which is
+/// the order most sound board programs shift the bits from speech ROMs to the chip.
+/// [`decode_hc55516()`] can be used for other bit orders.
+///
+/// The `state` parameter should be initialized with [`Hc55516State::new()`] and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `8 * buf.len()`.
+///
+/// An error is returned if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_hc55516_slice(buf: &[u8], state: &mut Hc55516State, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    if buf.len().checked_mul(8) != Some(out_samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.iter().zip(out_samples.chunks_exact_mut(8)) {
+        for (i, out) in out.iter_mut().enumerate() {
+            *out = decode_hc55516(b >> (7 - i), state);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToneGenerator;
+
+    #[test]
+    fn test_decode_cvsd() {
+        let mut state = CvsdState::new();
+        // step 10: (10 - 10 / 32) = 9.6875
+        assert_eq!(decode_cvsd(1, &mut state), 9);
+        assert_eq!(decode_cvsd(0x03, &mut state), 19);
+        assert_eq!(decode_cvsd(1, &mut state), 28);
+        // four equal bits increase the step size to 20
+        assert_eq!(decode_cvsd(1, &mut state), 46);
+        assert_eq!(state.step, 20 << CVSD_FRACTION_BITS);
+        // a different bit decays the step size
+        decode_cvsd(0, &mut state);
+        assert_eq!(state.step, (20 << CVSD_FRACTION_BITS) - 20);
+
+        // limits
+        let mut state = CvsdState::new();
+        for _ in 0..1000 {
+            decode_cvsd(1, &mut state);
+        }
+        assert_eq!(state.step, CVSD_STEP_MAX);
+        assert!(state.accumulator < 32767 << CVSD_FRACTION_BITS);
+        for _ in 0..1000 {
+            decode_cvsd(0, &mut state);
+        }
+        assert!(state.accumulator > -32768 << CVSD_FRACTION_BITS);
+    }
+
+    #[test]
+    fn test_encode_cvsd() {
+        // 1 kHz sine at 64 kHz sample rate
+        let mut encoder_state = CvsdState::new();
+        let mut decoder_state = CvsdState::new();
+        let mut tone = ToneGenerator::new(1000.0, 64000, 8000);
+        let mut signal_energy = 0f64;
+        let mut noise_energy = 0f64;
+        for i in 0..6400 {
+            let sample = tone.next_sample();
+            let bit = encode_cvsd(sample, &mut encoder_state);
+            assert!(bit <= 1);
+            let decoded = decode_cvsd(bit, &mut decoder_state);
+            assert_eq!(encoder_state, decoder_state);
+            if i >= 640 {
+                signal_energy += f64::from(sample) * f64::from(sample);
+                let noise = f64::from(sample) - f64::from(decoded);
+                noise_energy += noise * noise;
+            }
+        }
+        // signal to noise ratio should be over 10 dB
+        assert!(signal_energy > noise_energy * 10.0);
+    }
+
+    #[test]
+    fn test_cvsd_codec_state() {
+        let mut state = CvsdState::new();
+        assert!(state.is_default());
+        for s in [ 1000, 2000, 3000, 4000, 3000, -2000 ] {
+            encode_cvsd(s, &mut state);
+        }
+        assert!(!state.is_default());
+        let mut buf = [0u8; CvsdState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert!(matches!(CvsdState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+
+        buf[8] = CVSD_RUN_MASK + 1;
+        assert!(matches!(CvsdState::from_bytes(&buf), Err(Error::InvalidData)));
+        buf[8] = 0;
+        buf[4..8].copy_from_slice(&(CVSD_STEP_MIN - 1).to_le_bytes());
+        assert!(matches!(CvsdState::from_bytes(&buf), Err(Error::InvalidData)));
+        buf[4..8].copy_from_slice(&CVSD_STEP_MAX.to_le_bytes());
+        buf[0..4].copy_from_slice(&i32::MIN.to_le_bytes());
+        assert!(matches!(CvsdState::from_bytes(&buf), Err(Error::InvalidData)));
+        buf[0..4].copy_from_slice(&(-32768i32 << CVSD_FRACTION_BITS).to_le_bytes());
+        assert!(CvsdState::from_bytes(&buf).is_ok());
+        assert!(matches!(CvsdState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 10]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_cvsd_slice() {
+        let samples = [ 100, 200, 300, 400, 300, 200, 100, 0, -100, -200, -300, -400, -300,
+            -200, -100, 0 ];
+        let mut state = CvsdState::new();
+        let mut buf = [0u8; 2];
+        assert!(encode_cvsd_slice(&samples, &mut state, &mut buf).is_ok());
+        let mut expected_state = CvsdState::new();
+        let mut expected_buf = [0u8; 2];
+        for (i, s) in samples.iter().enumerate() {
+            expected_buf[i / 8] |= encode_cvsd(*s, &mut expected_state) << (i % 8);
+        }
+        assert_eq!(buf, expected_buf);
+        assert_eq!(state, expected_state);
+
+        let mut decoder_state = CvsdState::new();
+        let mut out = [0i16; 16];
+        assert!(decode_cvsd_slice(&buf, &mut decoder_state, &mut out).is_ok());
+        assert_eq!(decoder_state, state);
+        let mut expected_state = CvsdState::new();
+        for (i, o) in out.iter().enumerate() {
+            assert_eq!(*o, decode_cvsd(buf[i / 8] >> (i % 8), &mut expected_state));
+        }
+
+        assert!(matches!(encode_cvsd_slice(&samples[..15], &mut state, &mut buf),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_cvsd_slice(&buf, &mut state, &mut out[..15]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_hc55516() {
+        let mut state = Hc55516State::default();
+        // the filter starts at zero, so the first bit doesn't move the integrator
+        assert_eq!(decode_hc55516(1, &mut state), 0);
+        assert_eq!(state.filter, HC55516_FILTER_MIN);
+        assert_eq!(decode_hc55516(0x11, &mut state), 386);
+        assert_eq!(state.filter, HC55516_FILTER_MIN);
+        // three equal bits charge the filter
+        decode_hc55516(1, &mut state);
+        assert!(state.filter > HC55516_FILTER_MIN);
+
+        // HC-55532 needs four equal bits
+        let mut state = Hc55516State::new(Hc55516Variant::Hc55532);
+        for _ in 0..3 {
+            decode_hc55516(1, &mut state);
+        }
+        assert_eq!(state.filter, HC55516_FILTER_MIN);
+        decode_hc55516(1, &mut state);
+        assert!(state.filter > HC55516_FILTER_MIN);
+
+        // the filter is limited and the integrator range is compressed
+        let mut state = Hc55516State::default();
+        for _ in 0..2000 {
+            decode_hc55516(1, &mut state);
+        }
+        assert!(HC55516_FILTER_MAX - state.filter < 1e-9);
+        assert_eq!(decode_hc55516(1, &mut state), 27468);
+        let mut previous = 27468;
+        for _ in 0..4 {
+            let sample = decode_hc55516(0, &mut state);
+            assert!(sample < previous);
+            previous = sample;
+        }
+        let mut state = Hc55516State::default();
+        for _ in 0..2000 {
+            decode_hc55516(0, &mut state);
+        }
+        assert_eq!(decode_hc55516(0, &mut state), -27468);
+    }
+
+    #[test]
+    fn test_decode_hc55516_reference() {
+        // reference values are from a transcription of the floating point model
+        // of MAME's hc55516.c
+        let buf = [ 0xff, 0xff, 0xfe, 0x10, 0x00, 0x5a, 0x3c, 0xe7 ];
+        let hc55516_reference = [
+            0, 386, 740, 1209, 1773, 2414, 3112, 3851, 4619, 5401, 6189, 6972, 7745, 8503,
+            9240, 9956, 10646, 11311, 11949, 12560, 13145, 13704, 14237, 12607, 10813, 8835,
+            6552, 8223, 5822, 3135, 121, -2857, -5281, -7290, -8983, -10429, -11679, -12769,
+            -13729, -14581, -15342, -13458, -14388, -12406, -10141, -11535, -9194, -10700,
+            -11925, -12937, -10848, -8473, -5761, -2511, -5425, -7658, -4755, -1397, 2181,
+            -1539, -4538, -1209, 2284, 4989
+        ];
+        let hc55532_reference = [
+            0, 386, 740, 1066, 1507, 2043, 2657, 3330, 4047, 4793, 5556, 6325, 7093, 7852,
+            8597, 9324, 10030, 10711, 11369, 12000, 12606, 13185, 13739, 12098, 10298, 8321,
+            6149, 7740, 5454, 2930, 138, -2502, -4709, -6581, -8188, -9584, -10806, -11887,
+            -12848, -13709, -14484, -12598, -13543, -11571, -9342, -10730, -8439, -9928,
+            -11146, -12156, -10172, -7956, -5474, -2686, -5146, -7085, -4517, -1633, 1469,
+            -1579, -4045, -1274, 1630, 3978
+        ];
+        for (variant, reference) in [
+            (Hc55516Variant::Hc55516, hc55516_reference),
+            (Hc55516Variant::Hc55532, hc55532_reference),
+        ] {
+            let mut state = Hc55516State::new(variant);
+            let mut out = [0i16; 64];
+            assert!(decode_hc55516_slice(&buf, &mut state, &mut out).is_ok());
+            assert_eq!(out, reference);
+        }
+    }
+
+    #[test]
+    fn test_hc55516_codec_state() {
+        let mut state = Hc55516State::new(Hc55516Variant::Hc55532);
+        assert!(state.is_default());
+        for bit in [ 1, 1, 1, 1, 0, 1, 0 ] {
+            decode_hc55516(bit, &mut state);
+        }
+        assert!(!state.is_default());
+        let mut buf = [0u8; Hc55516State::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf[0], 1);
+        assert!(matches!(Hc55516State::from_bytes(&buf), Ok(s) if s == state));
+        // reset keeps the variant
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, Hc55516State::new(Hc55516Variant::Hc55532));
+
+        buf[1] = 0x0f;
+        assert!(Hc55516State::from_bytes(&buf).is_ok());
+        // HC-55516 has a 3-bit shift register
+        buf[0] = 0;
+        assert!(matches!(Hc55516State::from_bytes(&buf), Err(Error::InvalidData)));
+        buf[1] = 0;
+        assert!(Hc55516State::from_bytes(&buf).is_ok());
+        buf[0] = 2;
+        assert!(matches!(Hc55516State::from_bytes(&buf), Err(Error::InvalidData)));
+        buf[0] = 0;
+        buf[2..10].copy_from_slice(&f64::NAN.to_le_bytes());
+        assert!(matches!(Hc55516State::from_bytes(&buf), Err(Error::InvalidData)));
+        buf[2..10].copy_from_slice(&HC55516_FILTER_MAX.to_le_bytes());
+        buf[10..18].copy_from_slice(&f64::INFINITY.to_le_bytes());
+        assert!(matches!(Hc55516State::from_bytes(&buf), Err(Error::InvalidData)));
+        assert!(matches!(Hc55516State::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 19]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_hc55516_slice() {
+        let buf = [ 0b1110_0100, 0x0f ];
+        let mut state = Hc55516State::default();
+        let mut out = [0i16; 16];
+        assert!(decode_hc55516_slice(&buf, &mut state, &mut out).is_ok());
+        let mut expected_state = Hc55516State::default();
+        for (i, o) in out.iter().enumerate() {
+            assert_eq!(*o, decode_hc55516(buf[i / 8] >> (7 - i % 8), &mut expected_state));
+        }
+        assert_eq!(state, expected_state);
+        assert!(matches!(decode_hc55516_slice(&buf, &mut state, &mut out[..15]),
+            Err(Error::InvalidBufferSize)));
+    }
+}
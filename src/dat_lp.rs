@@ -0,0 +1,155 @@
+use crate::Error;
+
+#[cfg(feature = "internal-no-panic")]
+use no_panic::no_panic;
+
+// DAT long-play (LP) mode 12-bit non-linear quantization (IEC 61119). Each polarity has
+// 8 segments of 256 codes. The two smallest segments have the 16-bit resolution and each
+// of the following segments doubles the step size up to 64 for the largest values.
+// Negative values use the ones' complement of the positive curve, so the curve is symmetric.
+
+/// Decodes a 12-bit encoded DAT LP value to a linear 16-bit signed integer sample value.
+///
+/// `encoded` is a 12-bit two's complement value. Only the lowest 12 bits of `encoded` are used
+/// and the top-most bits are ignored.
+#[cfg_attr(feature = "internal-no-panic", no_panic)]
+#[inline(always)]
+pub fn decode_dat_lp(encoded: u16) -> i16 {
+    let code = encoded & 0x0fff;
+    // ones' complement for negative values
+    let (magnitude, is_negative) = if code & 0x0800 != 0 {
+        (!code & 0x07ff, true)
+    } else {
+        (code, false)
+    };
+    let segment = magnitude >> 8;
+    let value = if segment <= 1 {
+        magnitude
+    } else {
+        (0x100 | (magnitude & 0xff)) << (segment - 1)
+    };
+    let value = i16::try_from(value).unwrap_or(i16::MAX);
+    if is_negative {
+        !value
+    } else {
+        value
+    }
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a 12-bit encoded DAT LP value.
+///
+/// The low bits, which don't fit in the segment of the value, are truncated. The result is
+/// a 12-bit two's complement value, the top-most 4 bits are always zero.
+#[cfg_attr(feature = "internal-no-panic", no_panic)]
+#[inline(always)]
+pub fn encode_dat_lp(linear: i16) -> u16 {
+    // ones' complement for negative values
+    let (magnitude, sign) = if linear < 0 {
+        (u16::try_from(!linear).unwrap_or(0), 0x0fff)
+    } else {
+        (u16::try_from(linear).unwrap_or(0), 0)
+    };
+    let code = if magnitude < 0x200 {
+        magnitude
+    } else {
+        // magnitude is 0x200..=0x7fff, so the segment is 2..=7
+        let segment = 16 - magnitude.leading_zeros() - 8;
+        let mantissa = (magnitude >> (segment - 1)) & 0xff;
+        (u16::try_from(segment).unwrap_or(7) << 8) | mantissa
+    };
+    code ^ sign
+}
+
+/// Decodes 12-bit encoded DAT LP values to 16-bit signed integer samples.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// the same as the `buf` length.
+///
+/// An error is returned if the `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_dat_lp_slice(buf: &[u16], out_samples: &mut [i16]) -> Result<(), Error> {
+    if buf.len() != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (encoded, out) in buf.iter().zip(out_samples.iter_mut()) {
+        *out = decode_dat_lp(*encoded);
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to 12-bit encoded DAT LP values.
+///
+/// This function outputs encoded values to `out_buf`. The `out_buf` length must be
+/// the same as the `samples` length.
+///
+/// An error is returned if the `out_buf` length isn't correct.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn encode_dat_lp_slice(samples: &[i16], out_buf: &mut [u16]) -> Result<(), Error> {
+    if samples.len() != out_buf.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (sample, out) in samples.iter().zip(out_buf.iter_mut()) {
+        *out = encode_dat_lp(*sample);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_dat_lp() {
+        assert_eq!(decode_dat_lp(0x000), 0);
+        assert_eq!(decode_dat_lp(0x1ff), 511);
+        assert_eq!(decode_dat_lp(0x200), 512);
+        assert_eq!(decode_dat_lp(0x201), 514);
+        assert_eq!(decode_dat_lp(0x300), 1024);
+        assert_eq!(decode_dat_lp(0x700), 16384);
+        assert_eq!(decode_dat_lp(0x7ff), 32704);
+        assert_eq!(decode_dat_lp(0xfff), -1);
+        assert_eq!(decode_dat_lp(0xe00), -512);
+        assert_eq!(decode_dat_lp(0xdff), -513);
+        assert_eq!(decode_dat_lp(0x800), -32705);
+        // the top-most bits are ignored
+        assert_eq!(decode_dat_lp(0xf201), 514);
+    }
+
+    #[test]
+    fn test_encode_dat_lp() {
+        assert_eq!(encode_dat_lp(0), 0x000);
+        assert_eq!(encode_dat_lp(511), 0x1ff);
+        assert_eq!(encode_dat_lp(513), 0x200);
+        assert_eq!(encode_dat_lp(514), 0x201);
+        assert_eq!(encode_dat_lp(32767), 0x7ff);
+        assert_eq!(encode_dat_lp(-1), 0xfff);
+        assert_eq!(encode_dat_lp(-514), 0xdff);
+        assert_eq!(encode_dat_lp(-32768), 0x800);
+
+        // every code decodes to a value which encodes back to the same code
+        for code in 0..0x1000 {
+            assert_eq!(encode_dat_lp(decode_dat_lp(code)), code);
+        }
+        // encoding truncates towards the next smaller magnitude
+        for sample in i16::MIN..=i16::MAX {
+            let decoded = decode_dat_lp(encode_dat_lp(sample));
+            assert!(decoded.unsigned_abs() <= sample.unsigned_abs());
+            assert!(sample.unsigned_abs() - decoded.unsigned_abs() < 64);
+        }
+    }
+
+    #[test]
+    fn test_dat_lp_slice() {
+        let samples = [ 0, 1000, -1000, 32767 ];
+        let mut buf = [0u16; 4];
+        assert!(encode_dat_lp_slice(&samples, &mut buf).is_ok());
+        assert_eq!(buf, [ 0x000, 0x2f4, 0xd0c, 0x7ff ]);
+        let mut out = [0i16; 4];
+        assert!(decode_dat_lp_slice(&buf, &mut out).is_ok());
+        assert_eq!(out, [ 0, 1000, -999, 32704 ]);
+        assert!(matches!(encode_dat_lp_slice(&samples, &mut buf[..3]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_dat_lp_slice(&buf, &mut out[..3]),
+            Err(Error::InvalidBufferSize)));
+    }
+}
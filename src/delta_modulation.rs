@@ -0,0 +1,243 @@
+use crate::{CodecState, Error};
+use crate::codec_state::{StateReader, StateWriter};
+
+/// State values for the linear delta modulation encoder and decoder.
+///
+/// The state should be created with [`DeltaModulationState::new()`] at the start of
+/// the stream. The encoder and decoder each need their own state and they must use
+/// the same step size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeltaModulationState {
+    // the predicted sample value
+    accumulator: i16,
+    step: i16,
+}
+
+impl DeltaModulationState {
+    /// Creates a new DeltaModulationState with a zero accumulator and the given step size.
+    ///
+    /// Step sizes larger than 32767 are limited to 32767.
+    pub fn new(step: u16) -> DeltaModulationState {
+        DeltaModulationState {
+            accumulator: 0,
+            step: i16::try_from(step).unwrap_or(i16::MAX),
+        }
+    }
+
+    fn update(&mut self, bit: u8) -> i16 {
+        self.accumulator = if bit != 0 {
+            self.accumulator.saturating_add(self.step)
+        } else {
+            self.accumulator.saturating_sub(self.step)
+        };
+        self.accumulator
+    }
+}
+
+impl Default for DeltaModulationState {
+    /// Creates a new DeltaModulationState with a zero accumulator and step size 256.
+    fn default() -> Self {
+        DeltaModulationState::new(256)
+    }
+}
+
+impl CodecState for DeltaModulationState {
+    /// 16-bit little-endian accumulator and step size.
+    const SERIALIZED_LEN: usize = 4;
+
+    /// Resets the accumulator to zero. The step size is kept.
+    fn reset(&mut self) {
+        self.accumulator = 0;
+    }
+
+    /// Returns `true` if the accumulator is zero.
+    fn is_default(&self) -> bool {
+        self.accumulator == 0
+    }
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.i16(self.accumulator);
+        w.i16(self.step);
+        Ok(())
+    }
+
+    /// An error is returned if the `buf` length isn't correct or if the step size is negative.
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        let state = DeltaModulationState {
+            accumulator: r.i16(),
+            step: r.i16(),
+        };
+        if state.step < 0 {
+            return Err(Error::InvalidData);
+        }
+        Ok(state)
+    }
+}
+
+/// Decodes a linear delta modulation bit to a linear 16-bit signed integer sample value.
+///
+/// Linear delta modulation codes each sample with one bit: the output increases by the step
+/// size for 1 and decreases by the step size for 0. Unlike CVSD, the step size is constant.
+/// The output saturates to -32768..=32767.
+///
+/// Only the lowest bit of `bit` is used and the top-most bits are ignored.
+///
+/// The `state` parameter should be initialized with [`DeltaModulationState::new()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn decode_delta_modulation(bit: u8, state: &mut DeltaModulationState) -> i16 {
+    state.update(bit & 1)
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a linear delta modulation bit.
+///
+/// The bit is 1 if `sample_value` is greater than or equal to the previous decoded value.
+///
+/// The `state` parameter should be initialized with [`DeltaModulationState::new()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn encode_delta_modulation(sample_value: i16, state: &mut DeltaModulationState) -> u8 {
+    let bit = u8::from(sample_value >= state.accumulator);
+    state.update(bit);
+    bit
+}
+
+/// Decodes linear delta modulation encoded bytes to 16-bit signed integer samples.
+///
+/// Each byte contains eight samples. The lowest bit is decoded first.
+/// [`decode_delta_modulation()`] can be used for other bit orders.
+///
+/// The `state` parameter should be initialized with [`DeltaModulationState::new()`] and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `8 * buf.len()`.
+///
+/// An error is returned if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_delta_modulation_slice(buf: &[u8], state: &mut DeltaModulationState,
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    if buf.len().checked_mul(8) != Some(out_samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.iter().zip(out_samples.chunks_exact_mut(8)) {
+        for (i, out) in out.iter_mut().enumerate() {
+            *out = decode_delta_modulation(b >> i, state);
+        }
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to linear delta modulation encoded bytes.
+///
+/// Each byte contains eight samples. The first sample is in the lowest bit.
+///
+/// The `state` parameter should be initialized with [`DeltaModulationState::new()`] and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs encoded bytes to `out_buf`. The `samples` length must be
+/// `8 * out_buf.len()`.
+///
+/// An error is returned if the `samples` length isn't correct.
+/// If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_delta_modulation_slice(samples: &[i16], state: &mut DeltaModulationState,
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    if out_buf.len().checked_mul(8) != Some(samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (s, out) in samples.chunks_exact(8).zip(out_buf.iter_mut()) {
+        *out = 0;
+        for (i, s) in s.iter().enumerate() {
+            *out |= encode_delta_modulation(*s, state) << i;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_delta_modulation() {
+        let mut state = DeltaModulationState::new(100);
+        assert_eq!(decode_delta_modulation(1, &mut state), 100);
+        assert_eq!(decode_delta_modulation(0x03, &mut state), 200);
+        assert_eq!(decode_delta_modulation(0x02, &mut state), 100);
+        assert_eq!(decode_delta_modulation(0, &mut state), 0);
+        assert_eq!(decode_delta_modulation(0, &mut state), -100);
+
+        // the output saturates
+        let mut state = DeltaModulationState::new(40000);
+        assert_eq!(decode_delta_modulation(1, &mut state), 32767);
+        assert_eq!(decode_delta_modulation(1, &mut state), 32767);
+        assert_eq!(decode_delta_modulation(0, &mut state), 0);
+        assert_eq!(decode_delta_modulation(0, &mut state), -32767);
+        assert_eq!(decode_delta_modulation(0, &mut state), -32768);
+    }
+
+    #[test]
+    fn test_encode_delta_modulation() {
+        let mut encoder_state = DeltaModulationState::new(64);
+        let mut decoder_state = DeltaModulationState::new(64);
+        // the decoded values follow a slowly changing signal within the step size
+        for i in 0..2000 {
+            let sample = i16::try_from((i % 500 - 250) * 20).unwrap_or(0);
+            let bit = encode_delta_modulation(sample, &mut encoder_state);
+            assert!(bit <= 1);
+            let decoded = decode_delta_modulation(bit, &mut decoder_state);
+            assert_eq!(encoder_state, decoder_state);
+            if i % 500 > 200 {
+                assert!((i32::from(sample) - i32::from(decoded)).abs() <= 64);
+            }
+        }
+    }
+
+    #[test]
+    fn test_codec_state() {
+        assert_eq!(DeltaModulationState::default(), DeltaModulationState::new(256));
+        let mut state = DeltaModulationState::new(100);
+        assert!(state.is_default());
+        decode_delta_modulation(0, &mut state);
+        assert!(!state.is_default());
+        let mut buf = [0u8; DeltaModulationState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x9c, 0xff, 100, 0 ]);
+        assert!(matches!(DeltaModulationState::from_bytes(&buf), Ok(s) if s == state));
+        // reset keeps the step size
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, DeltaModulationState::new(100));
+
+        buf[3] = 0x80;
+        assert!(matches!(DeltaModulationState::from_bytes(&buf), Err(Error::InvalidData)));
+        assert!(matches!(DeltaModulationState::from_bytes(&buf[1..]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 5]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_delta_modulation_slice() {
+        let samples = [ 100, 200, 300, 400, 300, 200, 100, 0, -100, -200, -300, -400, -300,
+            -200, -100, 0 ];
+        let mut state = DeltaModulationState::new(100);
+        let mut buf = [0u8; 2];
+        assert!(encode_delta_modulation_slice(&samples, &mut state, &mut buf).is_ok());
+        assert_eq!(buf, [ 0b0000_1111, 0b1111_0000 ]);
+
+        let mut decoder_state = DeltaModulationState::new(100);
+        let mut out = [0i16; 16];
+        assert!(decode_delta_modulation_slice(&buf, &mut decoder_state, &mut out).is_ok());
+        assert_eq!(out, samples);
+        assert_eq!(decoder_state, state);
+
+        assert!(matches!(encode_delta_modulation_slice(&samples[..15], &mut state, &mut buf),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_delta_modulation_slice(&buf, &mut state, &mut out[..15]),
+            Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,229 @@
+use crate::Error;
+
+/// How a [`DpcmCodec`] handles predictor values outside the 16-bit range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DpcmOverflow {
+    /// The predictor is clamped to -32768..=32767.
+    #[default]
+    Clamp,
+    /// The predictor wraps around at 16 bits.
+    Wrap,
+}
+
+/// Generic DPCM codec with a caller-supplied delta table.
+///
+/// Each code is an index to the delta table and the delta is added to the predictor,
+/// which is the decoded sample value. The code width is 1 to 8 bits and the delta table must
+/// have an entry for every code, so its length must be 2, 4, 8, 16, 32, 64, 128 or 256.
+/// Many DPCM formats differ only in the delta table, for example Interplay DPCM and
+/// Sierra SOL DPCM.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DpcmCodec<'a> {
+    deltas: &'a [i16],
+    bits: u32,
+    overflow: DpcmOverflow,
+}
+
+impl<'a> DpcmCodec<'a> {
+    /// Creates a new DpcmCodec using the delta table `deltas`.
+    ///
+    /// An error is returned if the `deltas` length isn't a power of two from 2 to 256.
+    pub fn new(deltas: &'a [i16], overflow: DpcmOverflow) -> Result<DpcmCodec<'a>, Error> {
+        if deltas.len() < 2 || deltas.len() > 256 || !deltas.len().is_power_of_two() {
+            return Err(Error::InvalidBufferSize);
+        }
+        Ok(DpcmCodec {
+            deltas,
+            bits: deltas.len().trailing_zeros(),
+            overflow,
+        })
+    }
+
+    /// Returns the code width in bits.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    fn apply(&self, code: u8, predictor: i16) -> i16 {
+        let delta = self.deltas[usize::from(code) & (self.deltas.len() - 1)];
+        match self.overflow {
+            DpcmOverflow::Clamp => predictor.saturating_add(delta),
+            DpcmOverflow::Wrap => predictor.wrapping_add(delta),
+        }
+    }
+
+    /// Decodes a code to a linear 16-bit signed integer sample value.
+    ///
+    /// Only the lowest [`bits()`](DpcmCodec::bits) bits of `code` are used and
+    /// the top-most bits are ignored.
+    ///
+    /// The `predictor` parameter should be initialized to the initial value of the stream
+    /// (usually zero or a value from a header). This method updates `predictor` with
+    /// the new sample value.
+    pub fn decode(&self, code: u8, predictor: &mut i16) -> i16 {
+        *predictor = self.apply(code, *predictor);
+        *predictor
+    }
+
+    /// Encodes a linear 16-bit signed integer sample value to a code.
+    ///
+    /// All codes are tried and the code which decodes closest to `sample_value` is selected.
+    /// If several codes are equally close, the lowest code is selected.
+    ///
+    /// The `predictor` parameter should be initialized to the initial value of the stream.
+    /// This method updates `predictor` with the decoded value of the selected code.
+    pub fn encode(&self, sample_value: i16, predictor: &mut i16) -> u8 {
+        let mut best_code = 0;
+        let mut best_error = u16::MAX;
+        for code in 0..=u8::MAX {
+            if usize::from(code) >= self.deltas.len() {
+                break;
+            }
+            let error = self.apply(code, *predictor).abs_diff(sample_value);
+            if error < best_error {
+                best_code = code;
+                best_error = error;
+            }
+        }
+        *predictor = self.apply(best_code, *predictor);
+        best_code
+    }
+
+    /// Decodes codes to 16-bit signed integer samples.
+    ///
+    /// Each byte of `buf` contains one code in its lowest [`bits()`](DpcmCodec::bits) bits.
+    ///
+    /// The `predictor` parameter should be initialized to the initial value of the stream and
+    /// subsequent calls should pass in the predictor value from the previous call.
+    ///
+    /// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+    /// the same as the `buf` length.
+    ///
+    /// An error is returned if the `out_samples` length isn't correct.
+    /// If an error is returned, `predictor` and `out_samples` are left unmodified.
+    pub fn decode_slice(&self, buf: &[u8], predictor: &mut i16, out_samples: &mut [i16])
+        -> Result<(), Error> {
+
+        if buf.len() != out_samples.len() {
+            return Err(Error::InvalidBufferSize);
+        }
+        for (code, out) in buf.iter().zip(out_samples.iter_mut()) {
+            *out = self.decode(*code, predictor);
+        }
+        Ok(())
+    }
+
+    /// Encodes 16-bit signed integer samples to codes.
+    ///
+    /// Each byte of `out_buf` contains one code in its lowest [`bits()`](DpcmCodec::bits) bits.
+    ///
+    /// The `predictor` parameter should be initialized to the initial value of the stream and
+    /// subsequent calls should pass in the predictor value from the previous call.
+    ///
+    /// This function outputs encoded codes to `out_buf`. The `out_buf` length must be
+    /// the same as the `samples` length.
+    ///
+    /// An error is returned if the `out_buf` length isn't correct.
+    /// If an error is returned, `predictor` and `out_buf` are left unmodified.
+    pub fn encode_slice(&self, samples: &[i16], predictor: &mut i16, out_buf: &mut [u8])
+        -> Result<(), Error> {
+
+        if samples.len() != out_buf.len() {
+            return Err(Error::InvalidBufferSize);
+        }
+        for (sample, out) in samples.iter().zip(out_buf.iter_mut()) {
+            *out = self.encode(*sample, predictor);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELTAS: &[i16; 4] = &[ 0, 100, -100, 30000 ];
+
+    #[test]
+    fn test_dpcm_codec_new() {
+        assert_eq!(DpcmCodec::new(DELTAS, DpcmOverflow::Clamp).map(|c| c.bits()).ok(), Some(2));
+        assert_eq!(DpcmCodec::new(&[0; 256], DpcmOverflow::Wrap).map(|c| c.bits()).ok(), Some(8));
+        assert!(matches!(DpcmCodec::new(&[0], DpcmOverflow::Clamp),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(DpcmCodec::new(&[0; 6], DpcmOverflow::Clamp),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(DpcmCodec::new(&[0; 512], DpcmOverflow::Clamp),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_dpcm_decode() {
+        let codec = DpcmCodec::new(DELTAS, DpcmOverflow::Clamp);
+        assert!(codec.is_ok());
+        let Ok(codec) = codec else {
+            return;
+        };
+        let mut predictor = 0;
+        assert_eq!(codec.decode(1, &mut predictor), 100);
+        // the top-most bits are ignored
+        assert_eq!(codec.decode(0xf5, &mut predictor), 200);
+        assert_eq!(codec.decode(2, &mut predictor), 100);
+        assert_eq!(codec.decode(3, &mut predictor), 30100);
+        assert_eq!(codec.decode(3, &mut predictor), 32767);
+
+        let codec = DpcmCodec::new(DELTAS, DpcmOverflow::Wrap);
+        assert!(codec.is_ok());
+        let Ok(codec) = codec else {
+            return;
+        };
+        let mut predictor = 30000;
+        assert_eq!(codec.decode(3, &mut predictor), -5536);
+
+        let mut predictor = 0;
+        let mut out = [0i16; 3];
+        assert!(codec.decode_slice(&[ 1, 1, 2 ], &mut predictor, &mut out).is_ok());
+        assert_eq!(out, [ 100, 200, 100 ]);
+        assert_eq!(predictor, 100);
+        assert!(matches!(codec.decode_slice(&[ 1, 1 ], &mut predictor, &mut out),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_dpcm_encode() {
+        let codec = DpcmCodec::new(DELTAS, DpcmOverflow::Clamp);
+        assert!(codec.is_ok());
+        let Ok(codec) = codec else {
+            return;
+        };
+        let mut predictor = 0;
+        assert_eq!(codec.encode(120, &mut predictor), 1);
+        assert_eq!(predictor, 100);
+        // equally close values select the lowest code
+        assert_eq!(codec.encode(150, &mut predictor), 0);
+        assert_eq!(codec.encode(32000, &mut predictor), 3);
+        assert_eq!(predictor, 30100);
+
+        // the encoder knows about wrapping
+        let codec = DpcmCodec::new(DELTAS, DpcmOverflow::Wrap);
+        assert!(codec.is_ok());
+        let Ok(codec) = codec else {
+            return;
+        };
+        let mut predictor = 30000;
+        assert_eq!(codec.encode(-5000, &mut predictor), 3);
+        assert_eq!(predictor, -5536);
+
+        let samples = [ 90, 210, 290, 310, 180 ];
+        let mut predictor = 0;
+        let mut buf = [0u8; 5];
+        assert!(codec.encode_slice(&samples, &mut predictor, &mut buf).is_ok());
+        assert_eq!(buf, [ 1, 1, 1, 0, 2 ]);
+        let mut decoder_predictor = 0;
+        let mut out = [0i16; 5];
+        assert!(codec.decode_slice(&buf, &mut decoder_predictor, &mut out).is_ok());
+        assert_eq!(out, [ 100, 200, 300, 300, 200 ]);
+        assert_eq!(decoder_predictor, predictor);
+        assert!(matches!(codec.encode_slice(&samples, &mut predictor, &mut buf[..4]),
+            Err(Error::InvalidBufferSize)));
+    }
+}
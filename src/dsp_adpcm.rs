@@ -0,0 +1,646 @@
+
+use crate::Error;
+use crate::sample_history::{decode_code, encode_sample, impl_sample_history, squared_error};
+
+// Nintendo DSP-ADPCM is the ADPCM format of the GameCube and Wii audio DSP.
+// see https://github.com/dolphin-emu/dolphin/blob/master/Source/Core/Core/HW/DSPHLE/UCodes/AX.h
+
+/// Size of a DSP-ADPCM frame in bytes: a header byte and 7 bytes of 4-bit samples.
+pub const DSP_ADPCM_FRAME_SIZE: usize = 8;
+
+/// Number of samples in a DSP-ADPCM frame.
+pub const DSP_ADPCM_SAMPLES_PER_FRAME: usize = 14;
+
+/// State values for the DSP-ADPCM decoder.
+///
+/// The values should be initialized to the initial history values of the stream header, which
+/// are usually zeros.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DspAdpcmState {
+    /// The previous decoded sample.
+    pub previous: i16,
+    /// The sample before the previous decoded sample.
+    pub previous2: i16,
+}
+
+impl DspAdpcmState {
+    /// Creates a new DspAdpcmState with zero values.
+    pub fn new() -> DspAdpcmState {
+        DspAdpcmState { previous: 0, previous2: 0 }
+    }
+
+    // returns the decoded sample of a 4-bit code (-8..=7) without updating the state,
+    // `coefs` are in 1/2048 units
+    fn decoded_value(&self, code: i32, scale: u8, coefs: [i16; 2]) -> i16 {
+        // the header coefficients and scale can be anything, so calculate in 64 bits
+        let prediction = i64::from(coefs[0]) * i64::from(self.previous) +
+            i64::from(coefs[1]) * i64::from(self.previous2);
+        let value = ((i64::from(code) << scale) << 11) + 1024 + prediction;
+        #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+        let sample = (value >> 11).clamp(-32768, 32767) as i16;
+        sample
+    }
+}
+
+impl_sample_history!(DspAdpcmState);
+
+/// Decodes an 8-byte DSP-ADPCM frame to 16-bit signed integer samples.
+///
+/// `coefficients` are the 16 prediction coefficients of the stream header: 8 pairs in
+/// 1/2048 units. The header byte of the frame selects the pair.
+///
+/// `state` should be initialized to the initial history values for the first frame and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// [`DSP_ADPCM_SAMPLES_PER_FRAME`].
+///
+/// An error is returned if the frame header has an invalid coefficient index or
+/// if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_dsp_adpcm_frame(buf: &[u8; 8], coefficients: &[i16; 16], state: &mut DspAdpcmState,
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    if out_samples.len() != DSP_ADPCM_SAMPLES_PER_FRAME {
+        return Err(Error::InvalidBufferSize);
+    }
+    if buf[0] >= 0x80 {
+        return Err(Error::InvalidData);
+    }
+    decode_frame(buf, coefficients, state, out_samples);
+    Ok(())
+}
+
+// decodes the first `out_samples.len()` samples of a frame, which has a valid header
+fn decode_frame(buf: &[u8], coefficients: &[i16; 16], state: &mut DspAdpcmState,
+    out_samples: &mut [i16]) {
+
+    let index = usize::from(buf[0] >> 4) & 7;
+    let scale = buf[0] & 0x0f;
+    let coefs = [ coefficients[index * 2], coefficients[index * 2 + 1] ];
+    for (out, code) in out_samples.iter_mut().zip(buf[1..].iter()
+        .flat_map(|b| [ *b & 0xf0, *b << 4 ])) {
+        let code = i32::from(i8::from_le_bytes([ code ])) >> 4;
+        *out = decode_code(state, code, |s, code| s.decoded_value(code, scale, coefs));
+    }
+}
+
+/// Decodes DSP-ADPCM frames to 16-bit signed integer samples.
+///
+/// `coefficients` are the 16 prediction coefficients of the stream header.
+///
+/// `state` should be initialized to the initial history values for the first call and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length is
+/// the number of samples to decode. The last frame can be partially decoded, so the number
+/// of samples doesn't need to be a multiple of 14. The `buf` length must be
+/// `8 * out_samples.len().div_ceil(14)`.
+///
+/// An error is returned if a frame header has an invalid coefficient index or
+/// if the `buf` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_dsp_adpcm(buf: &[u8], coefficients: &[i16; 16], state: &mut DspAdpcmState,
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    if buf.len() != out_samples.len().div_ceil(DSP_ADPCM_SAMPLES_PER_FRAME) * DSP_ADPCM_FRAME_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    if buf.chunks_exact(DSP_ADPCM_FRAME_SIZE).any(|frame| frame[0] >= 0x80) {
+        return Err(Error::InvalidData);
+    }
+    for (frame, out) in buf.chunks_exact(DSP_ADPCM_FRAME_SIZE)
+        .zip(out_samples.chunks_mut(DSP_ADPCM_SAMPLES_PER_FRAME)) {
+        decode_frame(frame, coefficients, state, out);
+    }
+    Ok(())
+}
+
+/// Encodes up to 14 samples to an 8-byte DSP-ADPCM frame.
+///
+/// All coefficient pairs and scale values 0..=11 are tried and the combination giving
+/// the smallest squared error is used. If there are less than 14 samples, the remaining codes
+/// are set to zero.
+///
+/// `coefficients` are the 16 prediction coefficients, see [`dsp_adpcm_coefficients()`].
+///
+/// `state` should be initialized to zeros for the first call and subsequent calls should pass
+/// in the state values from the previous call.
+///
+/// An error is returned if the `samples` length isn't 1..=14.
+/// If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_dsp_adpcm_frame(samples: &[i16], coefficients: &[i16; 16],
+    state: &mut DspAdpcmState, out_buf: &mut [u8; 8]) -> Result<(), Error> {
+
+    if samples.is_empty() || samples.len() > DSP_ADPCM_SAMPLES_PER_FRAME {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut best_index = 0;
+    let mut best_scale = 0;
+    let mut best_error = u64::MAX;
+    for (index, coefs) in coefficients.chunks_exact(2).enumerate() {
+        let coefs = [ coefs[0], coefs[1] ];
+        for scale in 0..=11 {
+            let error = squared_error(state, samples,
+                |s, code| s.decoded_value(code, scale, coefs));
+            if error < best_error {
+                best_index = index;
+                best_scale = scale;
+                best_error = error;
+            }
+        }
+    }
+    let coefs = [ coefficients[best_index * 2], coefficients[best_index * 2 + 1] ];
+    let mut codes = [0u8; DSP_ADPCM_SAMPLES_PER_FRAME];
+    for (code, sample) in codes.iter_mut().zip(samples) {
+        let value = encode_sample(state, *sample,
+            |s, code| s.decoded_value(code, best_scale, coefs));
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)] // the code is -8..=7
+        {
+        *code = (value & 0x0f) as u8;
+        }
+    }
+    #[allow(clippy::cast_possible_truncation)] // the index is 0..=7
+    {
+    out_buf[0] = ((best_index as u8) << 4) | best_scale;
+    }
+    for (byte, pair) in out_buf[1..].iter_mut().zip(codes.chunks_exact(2)) {
+        *byte = (pair[0] << 4) | pair[1];
+    }
+    Ok(())
+}
+
+/// Encodes samples to DSP-ADPCM frames.
+///
+/// `coefficients` are the 16 prediction coefficients, see [`dsp_adpcm_coefficients()`].
+/// The same coefficients must be written to the stream header.
+///
+/// `state` should be initialized to zeros for the first call and subsequent calls should pass
+/// in the state values from the previous call.
+///
+/// This function outputs the encoded frames to `out_buf`. The `out_buf` length must be
+/// `8 * samples.len().div_ceil(14)`. The codes after the last sample of a partial last frame
+/// are set to zero.
+///
+/// An error is returned if the `out_buf` length isn't correct.
+/// If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_dsp_adpcm(samples: &[i16], coefficients: &[i16; 16], state: &mut DspAdpcmState,
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    if out_buf.len() != samples.len().div_ceil(DSP_ADPCM_SAMPLES_PER_FRAME) * DSP_ADPCM_FRAME_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (frame_samples, frame) in samples.chunks(DSP_ADPCM_SAMPLES_PER_FRAME)
+        .zip(out_buf.chunks_exact_mut(DSP_ADPCM_FRAME_SIZE)) {
+        let Ok(frame) = frame.try_into() else {
+            return Err(Error::InvalidBufferSize);
+        };
+        encode_dsp_adpcm_frame(frame_samples, coefficients, state, frame)?;
+    }
+    Ok(())
+}
+
+// The coefficient estimation follows Nintendo's DSPADPCM tool: a second order predictor is
+// calculated for each frame from its autocorrelation and the predictors are clustered to
+// 8 coefficient pairs. Vectors and matrices use indexes 1 and 2 for the coefficients like
+// the original implementation.
+// see https://github.com/jackoalan/gc-dspadpcm-encode
+
+type Vec3 = [f64; 3];
+
+// calls `f` for the predictor of each frame, which has enough signal
+fn for_each_record(samples: &[i16], mut f: impl FnMut(&Vec3)) {
+    // two samples of the previous frame and 14 samples of the current frame
+    let mut pcm = [0i16; 16];
+    for frame_samples in samples.chunks(DSP_ADPCM_SAMPLES_PER_FRAME) {
+        pcm[0] = pcm[14];
+        pcm[1] = pcm[15];
+        pcm[2..].fill(0);
+        pcm[2..2 + frame_samples.len()].copy_from_slice(frame_samples);
+        let mut vec = inner_product_merge(&pcm);
+        if vec[0].abs() > 10.0 {
+            let mut mtx = outer_product_merge(&pcm);
+            let mut indexes = [0usize; 3];
+            if !analyze_ranges(&mut mtx, &mut indexes) {
+                bidirectional_filter(&mtx, &indexes, &mut vec);
+                if !quadratic_merge(&mut vec) {
+                    f(&finish_record(vec));
+                }
+            }
+        }
+    }
+}
+
+fn inner_product_merge(pcm: &[i16; 16]) -> Vec3 {
+    let mut out = [0.0; 3];
+    for (i, value) in out.iter_mut().enumerate() {
+        for x in 2..16 {
+            *value -= f64::from(pcm[x - i]) * f64::from(pcm[x]);
+        }
+    }
+    out
+}
+
+fn outer_product_merge(pcm: &[i16; 16]) -> [Vec3; 3] {
+    let mut mtx = [[0.0; 3]; 3];
+    for x in 1..=2 {
+        for y in 1..=2 {
+            for z in 2..16 {
+                mtx[x][y] += f64::from(pcm[z - x]) * f64::from(pcm[z - y]);
+            }
+        }
+    }
+    mtx
+}
+
+// LU decomposition with partial pivoting, returns true if the matrix is (nearly) singular
+#[allow(clippy::needless_range_loop)]
+fn analyze_ranges(mtx: &mut [Vec3; 3], indexes: &mut [usize; 3]) -> bool {
+    let mut recips = [0.0; 3];
+    for x in 1..=2 {
+        let value = mtx[x][1].abs().max(mtx[x][2].abs());
+        if value < f64::EPSILON {
+            return true;
+        }
+        recips[x] = 1.0 / value;
+    }
+    let mut max_index = 0;
+    for i in 1..=2 {
+        for x in 1..i {
+            let mut tmp = mtx[x][i];
+            for y in 1..x {
+                tmp -= mtx[x][y] * mtx[y][i];
+            }
+            mtx[x][i] = tmp;
+        }
+        let mut value = 0.0;
+        for x in i..=2 {
+            let mut tmp = mtx[x][i];
+            for y in 1..i {
+                tmp -= mtx[x][y] * mtx[y][i];
+            }
+            mtx[x][i] = tmp;
+            let scaled = tmp.abs() * recips[x];
+            if scaled >= value {
+                value = scaled;
+                max_index = x;
+            }
+        }
+        if max_index != i {
+            for y in 1..=2 {
+                let tmp = mtx[max_index][y];
+                mtx[max_index][y] = mtx[i][y];
+                mtx[i][y] = tmp;
+            }
+            recips[max_index] = recips[i];
+        }
+        indexes[i] = max_index;
+        if mtx[i][i] == 0.0 {
+            return true;
+        }
+        if i != 2 {
+            let tmp = 1.0 / mtx[i][i];
+            for x in (i + 1)..=2 {
+                mtx[x][i] *= tmp;
+            }
+        }
+    }
+    let mut min: f64 = 1.0e10;
+    let mut max: f64 = 0.0;
+    for i in 1..=2 {
+        let tmp = mtx[i][i].abs();
+        min = min.min(tmp);
+        max = max.max(tmp);
+    }
+    min / max < 1.0e-10
+}
+
+// solves the decomposed equations
+#[allow(clippy::needless_range_loop)]
+fn bidirectional_filter(mtx: &[Vec3; 3], indexes: &[usize; 3], vec: &mut Vec3) {
+    let mut x = 0;
+    for i in 1..=2 {
+        let index = indexes[i];
+        let mut tmp = vec[index];
+        vec[index] = vec[i];
+        if x != 0 {
+            for y in x..i {
+                tmp -= vec[y] * mtx[i][y];
+            }
+        } else if tmp != 0.0 {
+            x = i;
+        }
+        vec[i] = tmp;
+    }
+    for i in (1..=2).rev() {
+        let mut tmp = vec[i];
+        for y in (i + 1)..=2 {
+            tmp -= vec[y] * mtx[i][y];
+        }
+        vec[i] = tmp / mtx[i][i];
+    }
+    vec[0] = 1.0;
+}
+
+// converts the predictor to reflection coefficients, returns true if it isn't stable
+fn quadratic_merge(vec: &mut Vec3) -> bool {
+    let v2 = vec[2];
+    let tmp = 1.0 - v2 * v2;
+    if tmp == 0.0 {
+        return true;
+    }
+    let v0 = (vec[0] - v2 * v2) / tmp;
+    let v1 = (vec[1] - vec[1] * v2) / tmp;
+    vec[0] = v0;
+    vec[1] = v1;
+    v1.abs() > 1.0
+}
+
+// converts reflection coefficients to a predictor
+fn finish_record(mut vec: Vec3) -> Vec3 {
+    for value in &mut vec[1..] {
+        if *value >= 1.0 {
+            *value = 0.999_999_999_9;
+        } else if *value <= -1.0 {
+            *value = -0.999_999_999_9;
+        }
+    }
+    [ 1.0, vec[2] * vec[1] + vec[1], vec[2] ]
+}
+
+// converts a predictor to autocorrelation values
+#[allow(clippy::needless_range_loop)]
+fn matrix_filter(src: &Vec3) -> Vec3 {
+    let mut mtx = [[0.0; 3]; 3];
+    mtx[2][0] = 1.0;
+    for i in 1..=2 {
+        mtx[2][i] = -src[i];
+    }
+    for i in (1..=2).rev() {
+        let value = 1.0 - mtx[i][i] * mtx[i][i];
+        for y in 1..=i {
+            mtx[i - 1][y] = (mtx[i][i] * mtx[i][y] + mtx[i][y]) / value;
+        }
+    }
+    let mut dst = [ 1.0, 0.0, 0.0 ];
+    for i in 1..=2 {
+        for y in 1..=i {
+            dst[i] += mtx[i][y] * dst[i - y];
+        }
+    }
+    dst
+}
+
+// converts autocorrelation values to a predictor (Levinson-Durbin recursion)
+#[allow(clippy::needless_range_loop)]
+fn merge_finish_record(src: &Vec3) -> Vec3 {
+    let mut tmp = [0.0; 3];
+    let mut value = src[0];
+    let mut dst = [ 1.0, 0.0, 0.0 ];
+    for i in 1..=2 {
+        let mut v2 = 0.0;
+        for y in 1..i {
+            v2 += dst[y] * src[i - y];
+        }
+        dst[i] = if value > 0.0 { -(v2 + src[i]) / value } else { 0.0 };
+        tmp[i] = dst[i];
+        for y in 1..i {
+            dst[y] += dst[i] * dst[i - y];
+        }
+        value *= 1.0 - dst[i] * dst[i];
+    }
+    finish_record(tmp)
+}
+
+// returns the prediction error of `source1` autocorrelation with `source2` predictor
+fn contrast_vectors(source1: &Vec3, source2: &Vec3) -> f64 {
+    let value = (source2[2] * source2[1] - source2[1]) / (1.0 - source2[2] * source2[2]);
+    let value1 = source1[0] * source1[0] + source1[1] * source1[1] + source1[2] * source1[2];
+    let value2 = source1[0] * source1[1] + source1[1] * source1[2];
+    let value3 = source1[0] * source1[2];
+    value1 + 2.0 * value * value2 + 2.0 * (-source2[1] * value - source2[2]) * value3
+}
+
+// assigns each frame predictor to the closest of the `count` best predictors and
+// recalculates the best predictors from the averages
+fn filter_records(best: &mut [Vec3; 8], count: usize, samples: &[i16]) {
+    for _ in 0..2 {
+        let mut counts = [0u32; 8];
+        let mut sums = [[0.0; 3]; 8];
+        for_each_record(samples, |record| {
+            let mut index = 0;
+            let mut min_value = 1.0e30;
+            for (i, vec) in best[..count].iter().enumerate() {
+                let value = contrast_vectors(vec, record);
+                if value < min_value {
+                    min_value = value;
+                    index = i;
+                }
+            }
+            counts[index] += 1;
+            for (sum, value) in sums[index].iter_mut().zip(matrix_filter(record)) {
+                *sum += value;
+            }
+        });
+        for ((vec, sum), count) in best[..count].iter_mut().zip(&mut sums).zip(counts) {
+            if count > 0 {
+                for value in sum.iter_mut() {
+                    *value /= f64::from(count);
+                }
+            }
+            *vec = merge_finish_record(sum);
+        }
+    }
+}
+
+fn round_coefficient(value: f64) -> i16 {
+    let rounded = if value > 0.0 { value + 0.5 } else { value - 0.5 };
+    #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+    let coefficient = rounded.clamp(-32768.0, 32767.0) as i16;
+    coefficient
+}
+
+/// Calculates the 16 DSP-ADPCM prediction coefficients (8 pairs) for `samples`.
+///
+/// The coefficients are calculated the same way as in Nintendo's DSPADPCM tool: each 14-sample
+/// frame gets a second order predictor from its autocorrelation and the predictors are
+/// clustered to 8 pairs. The coefficients are in 1/2048 units and they are written to
+/// the stream header.
+///
+/// All coefficients are zero if `samples` is silent.
+pub fn dsp_adpcm_coefficients(samples: &[i16]) -> [i16; 16] {
+    let mut vec = [ 1.0, 0.0, 0.0 ];
+    let mut record_count = 0u32;
+    for_each_record(samples, |record| {
+        let filtered = matrix_filter(record);
+        vec[1] += filtered[1];
+        vec[2] += filtered[2];
+        record_count += 1;
+    });
+    if record_count > 0 {
+        vec[1] /= f64::from(record_count);
+        vec[2] /= f64::from(record_count);
+    }
+    let mut best = [[0.0; 3]; 8];
+    best[0] = merge_finish_record(&vec);
+    // split each predictor to two and refine them
+    let mut count = 1;
+    for w in 1..=3 {
+        for i in 0..count {
+            best[count + i] = [ best[i][0], best[i][1] - 0.01, best[i][2] ];
+        }
+        count = 1 << w;
+        filter_records(&mut best, count, samples);
+    }
+    let mut coefficients = [0i16; 16];
+    for (pair, vec) in coefficients.chunks_exact_mut(2).zip(best) {
+        pair[0] = round_coefficient(-vec[1] * 2048.0);
+        pair[1] = round_coefficient(-vec[2] * 2048.0);
+    }
+    coefficients
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CodecState;
+
+    // pair 0 adds the previous sample, pair 1 continues the slope
+    const COEFS: [i16; 16] = [ 2048, 0, 4096, -2048, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0 ];
+
+    #[test]
+    fn test_decode_dsp_adpcm_frame() {
+        let mut state = DspAdpcmState::new();
+        let mut out = [0i16; 14];
+        let buf = [ 0x02, 0x1f, 0x70, 0x80, 0, 0, 0, 0 ];
+        assert!(decode_dsp_adpcm_frame(&buf, &COEFS, &mut state, &mut out).is_ok());
+        assert_eq!(out[..7], [ 4, 0, 28, 28, -4, -4, -4 ]);
+        assert_eq!(state, DspAdpcmState { previous: -4, previous2: -4 });
+
+        let mut state = DspAdpcmState { previous: 200, previous2: 100 };
+        let buf = [ 0x1b, 0x70, 0, 0, 0, 0, 0, 0 ];
+        assert!(decode_dsp_adpcm_frame(&buf, &COEFS, &mut state, &mut out).is_ok());
+        assert_eq!(out[..3], [ 14636, 29072, 32767 ]);
+
+        // extreme coefficients and the largest scale don't overflow
+        let mut coefs = [0i16; 16];
+        coefs[0] = 32767;
+        coefs[1] = -32767;
+        let mut state = DspAdpcmState { previous: 32767, previous2: -32768 };
+        let buf = [ 0x0f, 0x78, 0x00, 0, 0, 0, 0, 0 ];
+        assert!(decode_dsp_adpcm_frame(&buf, &coefs, &mut state, &mut out).is_ok());
+        assert_eq!(out[..4], [ 32767, -32768, -32768, 0 ]);
+
+        // errors
+        let buf = [ 0x80, 0, 0, 0, 0, 0, 0, 0 ];
+        assert!(matches!(decode_dsp_adpcm_frame(&buf, &COEFS, &mut state, &mut out),
+            Err(Error::InvalidData)));
+        assert!(matches!(decode_dsp_adpcm_frame(&buf, &COEFS, &mut state, &mut out[..13]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_dsp_adpcm() {
+        let buf = [ 0x00, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+            0x00, 0x12, 0x34, 0, 0, 0, 0, 0 ];
+        let mut state = DspAdpcmState::new();
+        let mut out = [0i16; 17];
+        assert!(decode_dsp_adpcm(&buf, &COEFS, &mut state, &mut out).is_ok());
+        assert_eq!(out[13..], [ 14, 15, 17, 20 ]);
+        assert_eq!(state, DspAdpcmState { previous: 20, previous2: 17 });
+        let mut state = DspAdpcmState::new();
+        assert!(decode_dsp_adpcm(&buf, &COEFS, &mut state, &mut out[..15]).is_ok());
+        assert_eq!(state, DspAdpcmState { previous: 15, previous2: 14 });
+
+        // errors
+        let mut state = DspAdpcmState::new();
+        assert!(matches!(decode_dsp_adpcm(&buf, &COEFS, &mut state, &mut out[..14]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_dsp_adpcm(&buf[..15], &COEFS, &mut state, &mut out),
+            Err(Error::InvalidBufferSize)));
+        let mut invalid = buf;
+        invalid[8] = 0x90;
+        assert!(matches!(decode_dsp_adpcm(&invalid, &COEFS, &mut state, &mut out),
+            Err(Error::InvalidData)));
+        assert_eq!(state, DspAdpcmState::new());
+    }
+
+    fn encode_decode_error(samples: &[i16], coefficients: &[i16; 16]) -> u64 {
+        let mut buf = [0u8; 8 * 74];
+        let mut out = [0i16; 1024];
+        let mut state = DspAdpcmState::new();
+        let buf_len = samples.len().div_ceil(14) * 8;
+        assert!(encode_dsp_adpcm(samples, coefficients, &mut state, &mut buf[..buf_len]).is_ok());
+        let mut dec_state = DspAdpcmState::new();
+        let out = &mut out[..samples.len()];
+        assert!(decode_dsp_adpcm(&buf[..buf_len], coefficients, &mut dec_state, out).is_ok());
+        assert_eq!(state, dec_state);
+        out.iter().zip(samples)
+            .map(|(a, b)| (i64::from(*a) - i64::from(*b)).unsigned_abs().pow(2))
+            .sum()
+    }
+
+    #[test]
+    fn test_encode_dsp_adpcm_frame() {
+        let samples = [ 64, 192, 384, 640, 960, 1344, 1792, 2304, 2880, 3520, 4224, 4992, 5824,
+            6720 ];
+        let mut state = DspAdpcmState::new();
+        let mut buf = [0u8; 8];
+        assert!(encode_dsp_adpcm_frame(&samples, &COEFS, &mut state, &mut buf).is_ok());
+        // the slope predictor gives the same residual for all samples
+        assert_eq!(buf[0] >> 4, 1);
+        let mut dec_state = DspAdpcmState::new();
+        let mut out = [0i16; 14];
+        assert!(decode_dsp_adpcm_frame(&buf, &COEFS, &mut dec_state, &mut out).is_ok());
+        assert_eq!(out, samples);
+        assert_eq!(state, dec_state);
+
+        // a partial frame sets the remaining codes to zero
+        let mut state = DspAdpcmState::new();
+        assert!(encode_dsp_adpcm_frame(&samples[..3], &COEFS, &mut state, &mut buf).is_ok());
+        assert_eq!(buf[3..], [ 0; 5 ]);
+        assert_eq!(state, DspAdpcmState { previous: 384, previous2: 192 });
+
+        assert!(matches!(encode_dsp_adpcm_frame(&[], &COEFS, &mut state, &mut buf),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_dsp_adpcm_frame(&[0; 15], &COEFS, &mut state, &mut buf),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_dsp_adpcm(&samples, &COEFS, &mut state, &mut [0; 16]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_dsp_adpcm_coefficients() {
+        assert_eq!(dsp_adpcm_coefficients(&[0; 100]), [0; 16]);
+        assert_eq!(dsp_adpcm_coefficients(&[]), [0; 16]);
+
+        let mut samples = [0i16; 1024];
+        crate::SweepGenerator::new(100.0, 8000.0, 32000, 1024, 12000).fill(&mut samples);
+        let coefficients = dsp_adpcm_coefficients(&samples);
+        // sines are predicted with coefficients 2 * cos(w) and -1 in 1/2048 units
+        assert!(coefficients.chunks_exact(2).all(|pair| pair[1] < -1800 && pair[1] >= -2048));
+        assert!(coefficients.chunks_exact(2).any(|pair| pair[0] > 3500));
+        assert!(coefficients.chunks_exact(2).any(|pair| pair[0] < 1000));
+        let error = encode_decode_error(&samples, &coefficients);
+        let fixed_error = encode_decode_error(&samples, &COEFS);
+        assert!(error < fixed_error);
+        // signal to noise ratio is over 30 dB
+        let signal: u64 = samples.iter().map(|s| i64::from(*s).unsigned_abs().pow(2)).sum();
+        assert!(error * 1000 < signal);
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = DspAdpcmState { previous: -1000, previous2: 2000 };
+        assert!(!state.is_default());
+        let mut buf = [0u8; DspAdpcmState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x18, 0xfc, 0xd0, 0x07 ]);
+        assert!(matches!(DspAdpcmState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, DspAdpcmState::new());
+        assert!(matches!(DspAdpcmState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 5]), Err(Error::InvalidBufferSize)));
+    }
+}
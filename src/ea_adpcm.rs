@@ -0,0 +1,410 @@
+
+use crate::Error;
+use crate::sample_history::{impl_sample_history, SampleHistory};
+use crate::xa::{XA_FILTER_K0, XA_FILTER_K1};
+
+// Electronic Arts ADPCM formats use the CD-XA prediction filters in 1/256 units.
+// see https://wiki.multimedia.cx/index.php/Electronic_Arts_XAS
+// and https://wiki.multimedia.cx/index.php/Maxis_XA
+
+/// Size of an EA XAS block for one channel in bytes.
+pub const EA_XAS_BLOCK_SIZE: usize = 76;
+
+/// Number of samples in an EA XAS block for one channel.
+pub const EA_XAS_SAMPLES_PER_BLOCK: usize = 128;
+
+/// Size of the Maxis XA file header in bytes.
+pub const MAXIS_XA_HEADER_SIZE: usize = 24;
+
+/// Size of a Maxis XA block for one channel in bytes.
+pub const MAXIS_XA_BLOCK_SIZE: usize = 15;
+
+/// Number of samples in a Maxis XA block for one channel.
+pub const MAXIS_XA_SAMPLES_PER_BLOCK: usize = 28;
+
+// returns the filter coefficients in 1/256 units
+fn ea_filter(filter: u8) -> Result<(i32, i32), Error> {
+    if filter > 3 {
+        return Err(Error::InvalidData);
+    }
+    let filter = usize::from(filter);
+    Ok((XA_FILTER_K0[filter] * 4, XA_FILTER_K1[filter] * 4))
+}
+
+/// Decodes an EA XAS block of one channel to 16-bit signed integer samples.
+///
+/// The block contains 4 rows of 32 samples. Each row starts with 2 uncompressed samples, which
+/// also contain the filter and shift values, and the 4-bit samples of the rows are interleaved.
+/// The rows are decoded independently, so there's no state between blocks.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// [`EA_XAS_SAMPLES_PER_BLOCK`].
+///
+/// An error is returned if a row has an invalid filter value or
+/// if the `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_ea_xas_block(buf: &[u8; 76], out_samples: &mut [i16]) -> Result<(), Error> {
+    if out_samples.len() != EA_XAS_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut rows = [ ((0, 0), 0u32); 4 ];
+    for (n, row) in rows.iter_mut().enumerate() {
+        *row = (ea_filter(buf[n * 4] & 0x0f)?, 20 - u32::from(buf[n * 4 + 2] & 0x0f));
+    }
+    for (n, (((k0, k1), shift), out)) in rows.iter().zip(out_samples.chunks_exact_mut(32))
+        .enumerate() {
+        out[0] = i16::from_le_bytes([ buf[n * 4] & 0xf0, buf[n * 4 + 1] ]);
+        out[1] = i16::from_le_bytes([ buf[n * 4 + 2] & 0xf0, buf[n * 4 + 3] ]);
+        let codes = buf[16 + n..].iter().step_by(4).flat_map(|b| [ *b & 0xf0, *b << 4 ]);
+        for (i, code) in (2..32).zip(codes) {
+            let level = (i32::from(i8::from_le_bytes([ code ])) >> 4) << shift;
+            let prediction = i32::from(out[i - 1]) * k0 + i32::from(out[i - 2]) * k1;
+            let sample = ((level + prediction + 0x80) >> 8).clamp(-32768, 32767);
+            out[i] = i16::try_from(sample).unwrap_or(0);
+        }
+    }
+    Ok(())
+}
+
+/// Decodes EA XAS blocks to 16-bit signed integer samples.
+///
+/// `buf` should contain groups of `channels` blocks: a 76-byte block for each channel.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `128 * buf.len() / 76`. Samples are interleaved for multichannel audio.
+///
+/// An error is returned if `channels` is zero, if a row has an invalid filter value or
+/// if the `buf` length or the `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_ea_xas(buf: &[u8], channels: usize, out_samples: &mut [i16]) -> Result<(), Error> {
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    let group_size = EA_XAS_BLOCK_SIZE * channels;
+    if buf.len() % group_size != 0 ||
+        out_samples.len() != buf.len() / EA_XAS_BLOCK_SIZE * EA_XAS_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    for block in buf.chunks_exact(EA_XAS_BLOCK_SIZE) {
+        for n in 0..4 {
+            ea_filter(block[n * 4] & 0x0f)?;
+        }
+    }
+    let mut block_out = [0i16; EA_XAS_SAMPLES_PER_BLOCK];
+    for (group, out) in buf.chunks_exact(group_size)
+        .zip(out_samples.chunks_exact_mut(EA_XAS_SAMPLES_PER_BLOCK * channels)) {
+        for (ch, block) in group.chunks_exact(EA_XAS_BLOCK_SIZE).enumerate() {
+            let Ok(block) = block.try_into() else {
+                return Err(Error::InvalidBufferSize);
+            };
+            decode_ea_xas_block(block, &mut block_out)?;
+            for (out, sample) in out[ch..].iter_mut().step_by(channels).zip(block_out) {
+                *out = sample;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Maxis XA file header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaxisXaHeader {
+    /// Size of the decoded 16-bit PCM audio in bytes.
+    pub output_size: u32,
+    /// Number of channels.
+    pub channels: usize,
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Block align of the decoded audio from the header. The encoded data always has
+    /// blocks of `15 * channels` bytes.
+    pub block_align: u16,
+    /// Bits per sample of the decoded audio, usually 16.
+    pub bits_per_sample: u16,
+}
+
+impl MaxisXaHeader {
+    /// Returns the number of samples for each channel.
+    pub fn samples_per_channel(&self) -> usize {
+        usize::try_from(self.output_size).unwrap_or(0) / 2 / self.channels.max(1)
+    }
+}
+
+/// Parses the 24-byte header of a Maxis XA file (SimCity 3000 and The Sims).
+///
+/// The encoded blocks follow the header, see [`decode_maxis_xa()`].
+///
+/// An error is returned if the `buf` length is less than [`MAXIS_XA_HEADER_SIZE`],
+/// if the header doesn't start with "XAI" or "XAJ" or if the number of channels is zero.
+pub fn parse_maxis_xa_header(buf: &[u8]) -> Result<MaxisXaHeader, Error> {
+    if buf.len() < MAXIS_XA_HEADER_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    if &buf[0..4] != b"XAI\0" && &buf[0..4] != b"XAJ\0" {
+        return Err(Error::InvalidData);
+    }
+    let channels = usize::from(u16::from_le_bytes([ buf[10], buf[11] ]));
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    Ok(MaxisXaHeader {
+        output_size: u32::from_le_bytes([ buf[4], buf[5], buf[6], buf[7] ]),
+        channels,
+        sample_rate: u32::from_le_bytes([ buf[12], buf[13], buf[14], buf[15] ]),
+        block_align: u16::from_le_bytes([ buf[20], buf[21] ]),
+        bits_per_sample: u16::from_le_bytes([ buf[22], buf[23] ]),
+    })
+}
+
+/// State values for the Maxis XA decoder.
+///
+/// The values should be initialized to zeros at the start of the stream.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MaxisXaState {
+    /// The previous decoded sample.
+    pub previous: i16,
+    /// The sample before the previous decoded sample.
+    pub previous2: i16,
+}
+
+impl MaxisXaState {
+    /// Creates a new MaxisXaState with zero values.
+    pub fn new() -> MaxisXaState {
+        MaxisXaState { previous: 0, previous2: 0 }
+    }
+}
+
+impl_sample_history!(MaxisXaState);
+
+/// Decodes a Maxis XA block to 16-bit signed integer samples.
+///
+/// The block has a header byte with the filter and shift values for each channel, followed by
+/// 14 bytes of 4-bit samples for each channel. For 2 channel audio, the bytes of the channels
+/// are interleaved.
+///
+/// `states` must contain channel number of `MaxisXaState` items. The state objects should be
+/// initialized to zero for the first call and subsequent calls should pass in the state values
+/// from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `28 * states.len()`. Samples are interleaved for multichannel audio.
+///
+/// An error is returned if `states` is empty, if a block header has an invalid filter value or
+/// if the `buf` length or the `out_samples` length isn't correct.
+/// If an error is returned, `states` and `out_samples` are left unmodified.
+pub fn decode_maxis_xa_block(buf: &[u8], states: &mut [MaxisXaState], out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    if buf.len() != MAXIS_XA_BLOCK_SIZE * channels ||
+        out_samples.len() != MAXIS_XA_SAMPLES_PER_BLOCK * channels {
+        return Err(Error::InvalidBufferSize);
+    }
+    for header in &buf[..channels] {
+        ea_filter(header >> 4)?;
+    }
+    for (ch, (header, state)) in buf[..channels].iter().zip(states.iter_mut()).enumerate() {
+        let (k0, k1) = ea_filter(header >> 4)?;
+        let shift = 20 - u32::from(header & 0x0f);
+        let codes = buf[channels + ch..].iter().step_by(channels)
+            .flat_map(|b| [ *b & 0xf0, *b << 4 ]);
+        for (out, code) in out_samples[ch..].iter_mut().step_by(channels).zip(codes) {
+            let level = (i32::from(i8::from_le_bytes([ code ])) >> 4) << shift;
+            let prediction = i32::from(state.previous) * k0 + i32::from(state.previous2) * k1;
+            let sample = ((level + prediction + 0x80) >> 8).clamp(-32768, 32767);
+            state.push(i16::try_from(sample).unwrap_or(0));
+            *out = state.previous;
+        }
+    }
+    Ok(())
+}
+
+/// Decodes Maxis XA blocks to 16-bit signed integer samples.
+///
+/// `buf` should contain whole blocks of `15 * states.len()` bytes, see
+/// [`decode_maxis_xa_block()`].
+///
+/// `states` must contain channel number of `MaxisXaState` items. The state objects should be
+/// initialized to zero for the first call and subsequent calls should pass in the state values
+/// from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `28 * buf.len() / 15`. Samples are interleaved for multichannel audio.
+///
+/// An error is returned if `states` is empty, if a block header has an invalid filter value or
+/// if the `buf` length or the `out_samples` length isn't correct.
+/// If an error is returned, `states` and `out_samples` are left unmodified.
+pub fn decode_maxis_xa(buf: &[u8], states: &mut [MaxisXaState], out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    let block_size = MAXIS_XA_BLOCK_SIZE * channels;
+    if buf.len() % block_size != 0 ||
+        out_samples.len() != buf.len() / MAXIS_XA_BLOCK_SIZE * MAXIS_XA_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    for block in buf.chunks_exact(block_size) {
+        for header in &block[..channels] {
+            ea_filter(header >> 4)?;
+        }
+    }
+    for (block, out) in buf.chunks_exact(block_size)
+        .zip(out_samples.chunks_exact_mut(MAXIS_XA_SAMPLES_PER_BLOCK * channels)) {
+        decode_maxis_xa_block(block, states, out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CodecState;
+
+    #[test]
+    fn test_decode_ea_xas_block() {
+        let mut buf = [0u8; 76];
+        // row 0: filter 0, shift 20
+        buf[0..4].copy_from_slice(&[ 0x00, 0x10, 0x00, 0x20 ]);
+        buf[16] = 0x1f;
+        // row 1: filter 1, shift 20
+        buf[4..8].copy_from_slice(&[ 0x01, 0x01, 0x00, 0x01 ]);
+        // row 3: shift 8
+        buf[12..16].copy_from_slice(&[ 0x00, 0x00, 0x0c, 0x00 ]);
+        buf[16 + 3] = 0x70;
+        let mut out = [0i16; 128];
+        assert!(decode_ea_xas_block(&buf, &mut out).is_ok());
+        assert_eq!(out[..5], [ 4096, 8192, 4096, -4096, 0 ]);
+        assert_eq!(out[32..36], [ 256, 256, 240, 225 ]);
+        assert_eq!(out[96..100], [ 0, 0, 7, 0 ]);
+
+        // errors
+        assert!(matches!(decode_ea_xas_block(&buf, &mut out[..127]),
+            Err(Error::InvalidBufferSize)));
+        buf[8] = 0x04;
+        assert!(matches!(decode_ea_xas_block(&buf, &mut out), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_decode_ea_xas() {
+        let mut buf = [0u8; 152];
+        buf[0..4].copy_from_slice(&[ 0x00, 0x10, 0x00, 0x20 ]);
+        buf[76..80].copy_from_slice(&[ 0x00, 0xf0, 0x00, 0xe0 ]);
+        let mut out = [0i16; 256];
+        assert!(decode_ea_xas(&buf, 2, &mut out).is_ok());
+        assert_eq!(out[..4], [ 4096, -4096, 8192, -8192 ]);
+        assert!(decode_ea_xas(&buf, 1, &mut out).is_ok());
+        assert_eq!(out[128..130], [ -4096, -8192 ]);
+
+        // errors
+        assert!(matches!(decode_ea_xas(&buf, 0, &mut out), Err(Error::InvalidChannels)));
+        assert!(matches!(decode_ea_xas(&buf, 3, &mut out), Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_ea_xas(&buf, 2, &mut out[..128]), Err(Error::InvalidBufferSize)));
+        buf[76] = 0x0f;
+        assert!(matches!(decode_ea_xas(&buf, 2, &mut out), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_parse_maxis_xa_header() {
+        let mut buf = [0u8; 24];
+        buf[0..4].copy_from_slice(b"XAI\0");
+        buf[4..8].copy_from_slice(&4000u32.to_le_bytes());
+        buf[8..10].copy_from_slice(&1u16.to_le_bytes());
+        buf[10..12].copy_from_slice(&2u16.to_le_bytes());
+        buf[12..16].copy_from_slice(&22050u32.to_le_bytes());
+        buf[16..20].copy_from_slice(&88200u32.to_le_bytes());
+        buf[20..22].copy_from_slice(&4u16.to_le_bytes());
+        buf[22..24].copy_from_slice(&16u16.to_le_bytes());
+        assert!(matches!(parse_maxis_xa_header(&buf), Ok(MaxisXaHeader { output_size: 4000,
+            channels: 2, sample_rate: 22050, block_align: 4, bits_per_sample: 16 })));
+        assert!(matches!(parse_maxis_xa_header(&buf).map(|h| h.samples_per_channel()),
+            Ok(1000)));
+
+        // errors
+        assert!(matches!(parse_maxis_xa_header(&buf[..23]), Err(Error::InvalidBufferSize)));
+        buf[10] = 0;
+        assert!(matches!(parse_maxis_xa_header(&buf), Err(Error::InvalidChannels)));
+        buf[3] = b'X';
+        assert!(matches!(parse_maxis_xa_header(&buf), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_decode_maxis_xa_block() {
+        // mono: shift 8 and shift 20
+        let mut buf = [0u8; 15];
+        buf[0] = 0x0c;
+        buf[1] = 0x17;
+        let mut states = [ MaxisXaState::new() ];
+        let mut out = [0i16; 28];
+        assert!(decode_maxis_xa_block(&buf, &mut states, &mut out).is_ok());
+        assert_eq!(out[..3], [ 1, 7, 0 ]);
+        buf[0] = 0x00;
+        assert!(decode_maxis_xa_block(&buf, &mut states, &mut out).is_ok());
+        assert_eq!(out[..2], [ 4096, 28672 ]);
+
+        // stereo: the right channel uses filter 1
+        let mut buf = [0u8; 30];
+        buf[0] = 0x00;
+        buf[1] = 0x10;
+        buf[2] = 0x1f;
+        let mut states = [ MaxisXaState::new(), MaxisXaState { previous: 4096, previous2: 0 } ];
+        let mut out = [0i16; 56];
+        assert!(decode_maxis_xa_block(&buf, &mut states, &mut out).is_ok());
+        assert_eq!(out[..4], [ 4096, 3840, -4096, 3600 ]);
+        assert_eq!(states[0], MaxisXaState { previous: 0, previous2: 0 });
+
+        // errors
+        assert!(matches!(decode_maxis_xa_block(&buf, &mut [], &mut out),
+            Err(Error::InvalidChannels)));
+        assert!(matches!(decode_maxis_xa_block(&buf[..15], &mut states, &mut out),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_maxis_xa_block(&buf, &mut states, &mut out[..28]),
+            Err(Error::InvalidBufferSize)));
+        buf[1] = 0x40;
+        assert!(matches!(decode_maxis_xa_block(&buf, &mut states, &mut out),
+            Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_decode_maxis_xa() {
+        let mut buf = [0u8; 30];
+        buf[0] = 0x00;
+        buf[1] = 0x10;
+        buf[15] = 0x10;
+        buf[16] = 0x10;
+        let mut states = [ MaxisXaState::new() ];
+        let mut out = [0i16; 56];
+        assert!(decode_maxis_xa(&buf, &mut states, &mut out).is_ok());
+        assert_eq!(out[..2], [ 4096, 0 ]);
+        assert_eq!(out[28..30], [ 4096, 3840 ]);
+
+        // errors
+        let mut states = [ MaxisXaState::new() ];
+        assert!(matches!(decode_maxis_xa(&buf[..29], &mut states, &mut out),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_maxis_xa(&buf, &mut states, &mut out[..28]),
+            Err(Error::InvalidBufferSize)));
+        buf[15] = 0x50;
+        assert!(matches!(decode_maxis_xa(&buf, &mut states, &mut out), Err(Error::InvalidData)));
+        assert_eq!(states[0], MaxisXaState::new());
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = MaxisXaState { previous: -1000, previous2: 2000 };
+        assert!(!state.is_default());
+        let mut buf = [0u8; MaxisXaState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x18, 0xfc, 0xd0, 0x07 ]);
+        assert!(matches!(MaxisXaState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, MaxisXaState::new());
+        assert!(matches!(MaxisXaState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 5]), Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,145 @@
+
+use crate::{AdpcmImaState, CompandingLaw, Error};
+use crate::{decode_adpcm_ima_ima4_with, decode_adpcm_ima_ms_with};
+
+/// 8-bit linear sample type: signed `i8` or unsigned `u8` (offset by 128, like 8-bit WAV).
+pub trait EightBitSample: Copy {
+    /// Converts a 16-bit sample value to a 8-bit sample value, rounding to the nearest value.
+    fn from_i16(sample: i16) -> Self;
+}
+
+impl EightBitSample for i8 {
+    #[inline(always)]
+    fn from_i16(sample: i16) -> i8 {
+        // round half up and clamp, because 32767 would round to 128
+        let rounded = ((i32::from(sample) + 128) >> 8).min(127);
+        #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+        {
+        rounded as i8
+        }
+    }
+}
+
+impl EightBitSample for u8 {
+    #[inline(always)]
+    fn from_i16(sample: i16) -> u8 {
+        (i8::from_i16(sample).to_le_bytes()[0]) ^ 0x80
+    }
+}
+
+/// Decodes G.711 A-law or μ-law encoded values to 8-bit linear samples.
+///
+/// The `out_samples` length must be the same as the `buf` length.
+///
+/// An error is returned if the `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_companded_8bit<L: CompandingLaw, S: EightBitSample>(buf: &[u8],
+    out_samples: &mut [S]) -> Result<(), Error> {
+
+    if buf.len() != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.iter().zip(out_samples.iter_mut()) {
+        *out = S::from_i16(L::decode(*b));
+    }
+    Ok(())
+}
+
+/// Decodes an AIFF-C / QT "ima4" compressed block to 8-bit linear samples.
+///
+/// This works like [`decode_adpcm_ima_ima4()`](crate::decode_adpcm_ima_ima4), but
+/// the samples are rounded to 8 bits. `state` is updated with the 16-bit values.
+pub fn decode_adpcm_ima_ima4_8bit<S: EightBitSample>(buf: &[u8; 34], state: &mut AdpcmImaState,
+    out_samples: &mut [S; 64]) {
+
+    let mut out = out_samples.iter_mut();
+    decode_adpcm_ima_ima4_with(buf, state, &mut |s| {
+        if let Some(o) = out.next() {
+            *o = S::from_i16(s);
+        }
+    });
+}
+
+/// Decodes WAV / MS IMA ADPCM (wav format 0x0011) compressed block to 8-bit linear samples.
+///
+/// This works like [`decode_adpcm_ima_ms()`](crate::decode_adpcm_ima_ms), but
+/// the samples are rounded to 8 bits.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ima_ms_8bit<S: EightBitSample>(buf: &[u8], is_stereo: bool,
+    out_samples: &mut [S]) -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    // decode_adpcm_ima_ms_with() checks the other buf length requirements
+    let expected_sample_len = buf.len()
+        .checked_sub(4 * channels)
+        .and_then(|v| v.checked_mul(2))
+        .and_then(|v| v.checked_add(channels))
+        .ok_or(Error::InvalidBufferSize)?;
+    if expected_sample_len != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut out = out_samples.iter_mut();
+    decode_adpcm_ima_ms_with(buf, is_stereo, &mut |s| {
+        if let Some(o) = out.next() {
+            *o = S::from_i16(s);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ULaw, decode_adpcm_ima_ima4, decode_adpcm_ima_ms, decode_ulaw};
+
+    #[test]
+    fn test_eight_bit_sample() {
+        assert_eq!(i8::from_i16(0), 0);
+        assert_eq!(i8::from_i16(127), 0);
+        assert_eq!(i8::from_i16(128), 1);
+        assert_eq!(i8::from_i16(-128), 0);
+        assert_eq!(i8::from_i16(-129), -1);
+        assert_eq!(i8::from_i16(32767), 127);
+        assert_eq!(i8::from_i16(-32768), -128);
+        assert_eq!(u8::from_i16(0), 128);
+        assert_eq!(u8::from_i16(32767), 255);
+        assert_eq!(u8::from_i16(-32768), 0);
+    }
+
+    #[test]
+    fn test_decode_companded_8bit() {
+        let buf = [ 0x00, 0x7f, 0x80, 0xff, 0x9a ];
+        let mut out = [0u8; 5];
+        assert!(decode_companded_8bit::<ULaw, u8>(&buf, &mut out).is_ok());
+        assert_eq!(out, buf.map(|b| u8::from_i16(decode_ulaw(b))));
+        assert!(matches!(decode_companded_8bit::<ULaw, i8>(&buf, &mut [0i8; 4]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_8bit() {
+        let mut buf = [0u8; 34];
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = u8::try_from(i * 29 % 256).unwrap_or(0);
+        }
+        buf[1] = 30;
+        let mut expected = [0i16; 64];
+        decode_adpcm_ima_ima4(&buf, &mut AdpcmImaState::new(), &mut expected);
+        let mut samples = [0i8; 64];
+        decode_adpcm_ima_ima4_8bit(&buf, &mut AdpcmImaState::new(), &mut samples);
+        assert_eq!(samples, expected.map(i8::from_i16));
+
+        let mut expected = [0i16; 57];
+        assert!(decode_adpcm_ima_ms(&buf[..32], false, &mut expected).is_ok());
+        let mut samples = [0u8; 57];
+        assert!(decode_adpcm_ima_ms_8bit(&buf[..32], false, &mut samples).is_ok());
+        assert_eq!(samples, expected.map(u8::from_i16));
+        assert!(matches!(decode_adpcm_ima_ms_8bit(&buf[..32], false, &mut samples[..56]),
+            Err(Error::InvalidBufferSize)));
+    }
+}
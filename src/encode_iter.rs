@@ -0,0 +1,234 @@
+
+use crate::Error;
+use crate::{AdpcmImaState, encode_adpcm_ima_ima4};
+use crate::adpcm_ima::{ValidatedImaState, ms_block_sample_len};
+
+// reads the next sample to `out` and returns true, or writes zero and returns false
+fn next_or_zero(samples: &mut impl Iterator<Item = i16>, out: &mut i16) -> bool {
+    match samples.next() {
+        Some(s) => {
+            *out = s;
+            true
+        },
+        None => {
+            *out = 0;
+            false
+        },
+    }
+}
+
+/// Encodes 16-bit signed integer samples from an iterator to AIFF-C / QT "ima4" compressed
+/// blocks and passes the blocks to a callback.
+///
+/// Samples are read from `samples` 64 samples at a time and each encoded 34 byte block is
+/// passed to `block_sink`. If the number of samples isn't divisible by 64, the last block
+/// is padded with zero samples.
+///
+/// The `state` parameter should be initialized to zero for the first call and subsequent calls
+/// should pass in the state values from the previous call.
+///
+/// The number of samples read from `samples` is returned.
+pub fn encode_adpcm_ima_ima4_iter(samples: impl IntoIterator<Item = i16>,
+    state: &mut AdpcmImaState, block_sink: &mut impl FnMut(&[u8; 34])) -> usize {
+
+    let mut samples = samples.into_iter();
+    let mut sample_count = 0;
+    let mut block_samples = [0i16; 64];
+    let mut out_buf = [0u8; 34];
+    loop {
+        let mut len = 0;
+        for s in &mut block_samples {
+            if next_or_zero(&mut samples, s) {
+                len += 1;
+            }
+        }
+        if len == 0 {
+            break;
+        }
+        sample_count += len;
+        encode_adpcm_ima_ima4(&block_samples, state, &mut out_buf);
+        block_sink(&out_buf);
+        if len < 64 {
+            break;
+        }
+    }
+    sample_count
+}
+
+/// Encodes 16-bit signed integer samples from an iterator to MS / WAV IMA ADPCM
+/// (wav format 0x0011) compressed blocks and passes the blocks to a callback.
+///
+/// Samples must be interleaved for 2 channel audio. Each block is encoded to `out_buf` and
+/// then passed to `block_sink`, so the `out_buf` length is the block size. For 1 channel audio,
+/// the `out_buf` length must be at least 4. For 2 channel audio, the `out_buf` length must be
+/// at least 8 and it must be divisible by 8. The `out_buf` length must always be less
+/// than 65536. If the samples don't fill the last block, it is padded with zero samples.
+///
+/// `states` must contain channel number of `AdpcmImaState` items (1 or 2). The state objects
+/// should be initialized to zero for the first call and subsequent calls
+/// should pass in the state values from the previous call.
+///
+/// The number of samples read from `samples` is returned.
+///
+/// An error is returned if `states` has an invalid number of state objects or
+/// if the `out_buf` length isn't correct.
+/// If an error is returned, no samples are read and `block_sink` is not called.
+pub fn encode_adpcm_ima_ms_iter(samples: impl IntoIterator<Item = i16>,
+    states: &mut [AdpcmImaState], out_buf: &mut [u8], block_sink: &mut impl FnMut(&[u8]))
+    -> Result<usize, Error> {
+
+    let channels = states.len();
+    if channels < 1 || channels > 2 {
+        return Err(Error::InvalidChannels);
+    }
+    ms_block_sample_len(out_buf.len(), channels)?;
+    let mut samples = samples.into_iter();
+    let mut sample_count = 0;
+    loop {
+        // the first channels*4 bytes are the initial state (every fourth byte is ignored)
+        let mut len = 0;
+        for ch in 0..channels {
+            let mut sample = 0;
+            if next_or_zero(&mut samples, &mut sample) {
+                len += 1;
+            }
+            states[ch].predictor = sample;
+            states[ch].step_index = states[ch].step_index.min(88);
+            out_buf[ch*4] = sample.to_le_bytes()[0];
+            out_buf[ch*4+1] = sample.to_le_bytes()[1];
+            out_buf[ch*4+2] = states[ch].step_index;
+            out_buf[ch*4+3] = 0;
+        }
+        if len == 0 {
+            break;
+        }
+        // each group has 4 bytes (8 samples) for each channel
+        for group in out_buf[channels*4..].chunks_mut(channels*4) {
+            let mut group_samples = [0i16; 16];
+            let group_sample_len = group.len() * 2;
+            for s in &mut group_samples[..group_sample_len] {
+                if next_or_zero(&mut samples, s) {
+                    len += 1;
+                }
+            }
+            for (ch, (channel_bytes, state)) in group.chunks_mut(4).zip(states.iter_mut())
+                .enumerate() {
+                let mut local = ValidatedImaState::load(state);
+                for (i, b) in channel_bytes.iter_mut().enumerate() {
+                    let pos = i * 2 * channels + ch;
+                    *b = local.encode_byte(group_samples[pos], group_samples[pos + channels]);
+                }
+                local.store(state);
+            }
+        }
+        sample_count += len;
+        block_sink(out_buf);
+        if len < channels + (out_buf.len() - channels*4) * 2 {
+            break;
+        }
+    }
+    Ok(sample_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode_adpcm_ima_ms;
+
+    #[test]
+    fn test_encode_adpcm_ima_ima4_iter() {
+        // two full blocks match encode_adpcm_ima_ima4()
+        let mut samples = [0i16; 128];
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = i16::try_from(i * 200).unwrap_or(0) - 12000;
+        }
+        let mut expected = [[0u8; 34]; 2];
+        let mut state = AdpcmImaState::new();
+        encode_adpcm_ima_ima4(&samples[..64].try_into().unwrap_or([0; 64]), &mut state,
+            &mut expected[0]);
+        encode_adpcm_ima_ima4(&samples[64..].try_into().unwrap_or([0; 64]), &mut state,
+            &mut expected[1]);
+        let mut state = AdpcmImaState::new();
+        let mut block_count = 0;
+        let count = encode_adpcm_ima_ima4_iter(samples.iter().copied(), &mut state,
+            &mut |block| {
+                assert_eq!(block, &expected[block_count]);
+                block_count += 1;
+            });
+        assert_eq!(count, 128);
+        assert_eq!(block_count, 2);
+
+        // the last block is padded with zeros
+        let mut padded = [0i16; 64];
+        padded[..10].copy_from_slice(&samples[..10]);
+        let mut state = AdpcmImaState::new();
+        encode_adpcm_ima_ima4(&padded, &mut state, &mut expected[0]);
+        let mut state = AdpcmImaState::new();
+        let mut block_count = 0;
+        let count = encode_adpcm_ima_ima4_iter(samples[..10].iter().copied(), &mut state,
+            &mut |block| {
+                assert_eq!(block, &expected[0]);
+                block_count += 1;
+            });
+        assert_eq!(count, 10);
+        assert_eq!(block_count, 1);
+
+        // no samples, no blocks
+        let count = encode_adpcm_ima_ima4_iter([], &mut state, &mut |_| {
+            block_count += 1;
+        });
+        assert_eq!(count, 0);
+        assert_eq!(block_count, 1);
+    }
+
+    #[test]
+    fn test_encode_adpcm_ima_ms_iter() {
+        let mut samples = [0i16; 68];
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = i16::try_from(i * 300).unwrap_or(0) - 10000;
+        }
+
+        // mono: two full blocks and a padded block
+        let mut expected = [[0u8; 12]; 3];
+        let mut states = [ AdpcmImaState::new() ];
+        assert!(encode_adpcm_ima_ms(&samples[..17], &mut states, &mut expected[0]).is_ok());
+        assert!(encode_adpcm_ima_ms(&samples[17..34], &mut states, &mut expected[1]).is_ok());
+        let mut padded = [0i16; 17];
+        padded[..6].copy_from_slice(&samples[34..40]);
+        assert!(encode_adpcm_ima_ms(&padded, &mut states, &mut expected[2]).is_ok());
+        let mut states = [ AdpcmImaState::new() ];
+        let mut out_buf = [0u8; 12];
+        let mut block_count = 0;
+        let res = encode_adpcm_ima_ms_iter(samples[..40].iter().copied(), &mut states,
+            &mut out_buf, &mut |block| {
+                assert_eq!(block, &expected[block_count]);
+                block_count += 1;
+            });
+        assert!(matches!(res, Ok(40)));
+        assert_eq!(block_count, 3);
+
+        // stereo: two full blocks
+        let mut expected = [[0u8; 16]; 2];
+        let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+        assert!(encode_adpcm_ima_ms(&samples[..18], &mut states, &mut expected[0]).is_ok());
+        assert!(encode_adpcm_ima_ms(&samples[18..36], &mut states, &mut expected[1]).is_ok());
+        let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+        let mut out_buf = [0u8; 16];
+        let mut block_count = 0;
+        let res = encode_adpcm_ima_ms_iter(samples[..36].iter().copied(), &mut states,
+            &mut out_buf, &mut |block| {
+                assert_eq!(block, &expected[block_count]);
+                block_count += 1;
+            });
+        assert!(matches!(res, Ok(36)));
+        assert_eq!(block_count, 2);
+
+        // invalid out_buf length
+        let mut out_buf = [0u8; 12];
+        assert!(matches!(encode_adpcm_ima_ms_iter(samples, &mut states, &mut out_buf, &mut |_| {}),
+            Err(Error::InvalidBufferSize)));
+        let mut states = [];
+        assert!(matches!(encode_adpcm_ima_ms_iter(samples, &mut states, &mut out_buf, &mut |_| {}),
+            Err(Error::InvalidChannels)));
+    }
+}
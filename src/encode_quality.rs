@@ -0,0 +1,317 @@
+
+use crate::Error;
+use crate::AdpcmImaState;
+use crate::{decode_adpcm_ima, encode_adpcm_ima};
+use crate::adpcm_ima::ms_encoded_block_len;
+
+/// Quality setting for the IMA ADPCM encoders.
+///
+/// Higher quality settings search for nibbles which minimize the squared error between
+/// the input samples and the decoded samples. They use more CPU time, but the output
+/// is still decodable by any IMA ADPCM decoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EncodeQuality {
+    /// Greedy encoding, same as [`encode_adpcm_ima()`](crate::encode_adpcm_ima).
+    #[default]
+    Fast,
+    /// Each nibble is selected by looking one sample ahead.
+    Balanced,
+    /// Beam search, which keeps the 16 paths with the lowest error, 64 samples at a time.
+    ///
+    /// This isn't a full trellis search over all the states of the whole block, so it
+    /// doesn't always find the nibbles with the lowest error.
+    BeamSearch,
+}
+
+// number of paths kept during the beam search
+const BEAM_WIDTH: usize = 16;
+
+// maximum number of samples searched at a time
+const MAX_SEARCH_LEN: usize = 64;
+
+struct BeamPath {
+    state: AdpcmImaState,
+    cost: u64,
+    nibbles: [u8; MAX_SEARCH_LEN],
+}
+
+struct BeamCandidate {
+    parent: usize,
+    nibble: u8,
+    state: AdpcmImaState,
+    cost: u64,
+}
+
+#[inline(always)]
+fn squared_error(a: i16, b: i16) -> u64 {
+    let diff = i32::from(a) - i32::from(b);
+    u64::from(diff.unsigned_abs()).pow(2)
+}
+
+/// Encodes up to 64 samples of one channel to nibbles using the given quality.
+/// `state` is updated to the state after the last encoded sample.
+fn encode_nibbles(samples: &[i16], state: &mut AdpcmImaState, quality: EncodeQuality,
+    out_nibbles: &mut [u8]) {
+
+    state.step_index = state.step_index.min(88);
+    match quality {
+        EncodeQuality::Fast => {
+            for (sample, nibble) in samples.iter().zip(out_nibbles.iter_mut()) {
+                *nibble = encode_adpcm_ima(*sample, state);
+            }
+        },
+        EncodeQuality::Balanced => {
+            for (i, nibble) in out_nibbles.iter_mut().enumerate().take(samples.len()) {
+                let mut best_nibble = 0;
+                let mut best_cost = u64::MAX;
+                for candidate in 0..16 {
+                    let mut st = state.clone();
+                    let mut cost = squared_error(samples[i], decode_adpcm_ima(candidate, &mut st));
+                    // look ahead one sample using the greedy encoder
+                    if let Some(next) = samples.get(i + 1) {
+                        encode_adpcm_ima(*next, &mut st);
+                        cost += squared_error(*next, st.predictor);
+                    }
+                    if cost < best_cost {
+                        best_cost = cost;
+                        best_nibble = candidate;
+                    }
+                }
+                decode_adpcm_ima(best_nibble, state);
+                *nibble = best_nibble;
+            }
+        },
+        EncodeQuality::BeamSearch => {
+            encode_nibbles_beam(samples, state, out_nibbles);
+        }
+    }
+}
+
+fn encode_nibbles_beam(samples: &[i16], state: &mut AdpcmImaState, out_nibbles: &mut [u8]) {
+    let new_path = || BeamPath {
+        state: state.clone(),
+        cost: 0,
+        nibbles: [0; MAX_SEARCH_LEN],
+    };
+    let mut paths: [BeamPath; BEAM_WIDTH] = core::array::from_fn(|_| new_path());
+    let mut next_paths: [BeamPath; BEAM_WIDTH] = core::array::from_fn(|_| new_path());
+    let mut path_count = 1;
+    let mut candidates: [BeamCandidate; BEAM_WIDTH] = core::array::from_fn(|_| {
+        BeamCandidate { parent: 0, nibble: 0, state: AdpcmImaState::new(), cost: 0 }
+    });
+    let len = samples.len().min(MAX_SEARCH_LEN).min(out_nibbles.len());
+    for (i, sample) in samples.iter().enumerate().take(len) {
+        // candidates are kept sorted by cost, lowest first
+        let mut candidate_count = 0;
+        for (parent, path) in paths.iter().enumerate().take(path_count) {
+            for nibble in 0..16 {
+                let mut st = path.state.clone();
+                let cost = path.cost + squared_error(*sample, decode_adpcm_ima(nibble, &mut st));
+                // paths ending in the same state continue identically, so keep only the better one
+                let duplicate = candidates[..candidate_count].iter().position(|c| c.state == st);
+                if let Some(dup) = duplicate {
+                    if candidates[dup].cost <= cost {
+                        continue;
+                    }
+                    candidates[dup..candidate_count].rotate_left(1);
+                    candidate_count -= 1;
+                }
+                let pos = candidates[..candidate_count].iter()
+                    .position(|c| c.cost > cost)
+                    .unwrap_or(candidate_count);
+                if pos >= BEAM_WIDTH {
+                    continue;
+                }
+                if candidate_count < BEAM_WIDTH {
+                    candidate_count += 1;
+                }
+                candidates[pos..candidate_count].rotate_right(1);
+                candidates[pos] = BeamCandidate { parent, nibble, state: st, cost };
+            }
+        }
+        let candidates = candidates.iter().take(candidate_count);
+        for (next, candidate) in next_paths.iter_mut().zip(candidates) {
+            next.nibbles = paths[candidate.parent].nibbles;
+            next.nibbles[i] = candidate.nibble;
+            next.state = candidate.state.clone();
+            next.cost = candidate.cost;
+        }
+        core::mem::swap(&mut paths, &mut next_paths);
+        path_count = candidate_count;
+    }
+    out_nibbles[..len].copy_from_slice(&paths[0].nibbles[..len]);
+    *state = paths[0].state.clone();
+}
+
+/// Encodes 16-bit signed integer samples to an AIFF-C / QT "ima4" compressed block
+/// using the given quality.
+///
+/// This works like [`encode_adpcm_ima_ima4()`](crate::encode_adpcm_ima_ima4), but `quality`
+/// selects how the nibbles are searched.
+pub fn encode_adpcm_ima_ima4_with_quality(samples: &[i16; 64], state: &mut AdpcmImaState,
+    out_buf: &mut [u8; 34], quality: EncodeQuality) {
+
+    state.step_index = state.step_index.min(88);
+    // the first two bytes are the initial state: pppppppp piiiiiii
+    #[allow(clippy::cast_sign_loss)] // sign loss is expected when splitting the values to bytes
+    {
+    out_buf[0] = (state.predictor >> 8) as u8;
+    out_buf[1] = (state.predictor & 0x80) as u8 | state.step_index;
+    }
+    let mut nibbles = [0u8; 64];
+    encode_nibbles(samples, state, quality, &mut nibbles);
+    for (out_b, n) in out_buf[2..].iter_mut().zip(nibbles.chunks_exact(2)) {
+        *out_b = n[1] << 4 | n[0];
+    }
+}
+
+/// Encodes 16-bit signed integer samples to a MS / WAV IMA ADPCM (wav format 0x0011)
+/// compressed block using the given quality.
+///
+/// This works like [`encode_adpcm_ima_ms()`](crate::encode_adpcm_ima_ms), but `quality`
+/// selects how the nibbles are searched.
+///
+/// An error is returned if `states` has an invalid number of state objects or
+/// if the `samples` or `out_buf` length isn't correct.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn encode_adpcm_ima_ms_with_quality(samples: &[i16], states: &mut [AdpcmImaState],
+    out_buf: &mut [u8], quality: EncodeQuality) -> Result<(), Error> {
+
+    let channels = states.len();
+    // check that the length of the input buffer and output buffer match
+    if ms_encoded_block_len(samples.len(), channels)? != out_buf.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    // the first channels*4 bytes are the initial state (every fourth byte is ignored)
+    for ch in 0..channels {
+        states[ch].predictor = samples[ch];
+        states[ch].step_index = states[ch].step_index.min(88);
+        out_buf[ch*4] = samples[ch].to_le_bytes()[0];
+        out_buf[ch*4+1] = samples[ch].to_le_bytes()[1];
+        out_buf[ch*4+2] = states[ch].step_index;
+        out_buf[ch*4+3] = 0;
+    }
+    // each channel has 8 samples (4 bytes) in turn, search up to 64 samples at a time
+    let channel_len = (out_buf.len() - channels*4) * 2 / channels;
+    for (ch, state) in states.iter_mut().enumerate() {
+        let mut start = 0;
+        while start < channel_len {
+            let run_len = (channel_len - start).min(MAX_SEARCH_LEN);
+            let mut run_samples = [0i16; MAX_SEARCH_LEN];
+            for (i, s) in run_samples.iter_mut().enumerate().take(run_len) {
+                let k = start + i;
+                *s = samples[channels + (k / 8)*8*channels + (k % 8)*channels + ch];
+            }
+            let mut nibbles = [0u8; MAX_SEARCH_LEN];
+            encode_nibbles(&run_samples[..run_len], state, quality, &mut nibbles);
+            for (i, n) in nibbles.chunks_exact(2).enumerate().take(run_len / 2) {
+                let k = start + i*2;
+                out_buf[channels*4 + (k / 8)*4*channels + ch*4 + (k % 8) / 2] = n[1] << 4 | n[0];
+            }
+            start += run_len;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_adpcm_ima_ima4, decode_adpcm_ima_ms};
+    use crate::{encode_adpcm_ima_ima4, encode_adpcm_ima_ms};
+
+    const SAMPLES: [i16; 64] = [
+        16000, 24000, 30000, 32000, 32000, 30000, 24000, 16000,
+        8000, 0, -8000, -16000, -24000, -30000, -32000, -32000,
+        32000, 32000, 32000, 32000, -32000, -32000, -32000, -32000,
+        32000, 32000, 32000, 32000, -32000, -32000, -32000, -32000,
+        -32, -16, -8, 0, 8, 16, 32, 16,
+        8, 0, -8, -16, -32, -16, -8, 0,
+        4, 8, 12, 16, 0, 4, 8, 12,
+        16, 12, 8, 4, 0, 16, 8, 4,
+    ];
+
+    fn total_error(a: &[i16], b: &[i16]) -> u64 {
+        a.iter().zip(b.iter()).map(|(x, y)| squared_error(*x, *y)).sum()
+    }
+
+    #[test]
+    fn test_encode_adpcm_ima4_with_quality() {
+        // fast quality matches the normal encoder
+        let mut expected = [0u8; 34];
+        let mut expected_state = AdpcmImaState::new();
+        encode_adpcm_ima_ima4(&SAMPLES, &mut expected_state, &mut expected);
+        let mut encoded = [0u8; 34];
+        let mut state = AdpcmImaState::new();
+        encode_adpcm_ima_ima4_with_quality(&SAMPLES, &mut state, &mut encoded,
+            EncodeQuality::Fast);
+        assert_eq!(encoded, expected);
+        assert_eq!(state, expected_state);
+
+        let mut errors = [0u64; 3];
+        let qualities = [ EncodeQuality::Fast, EncodeQuality::Balanced, EncodeQuality::BeamSearch ];
+        for (i, quality) in qualities.iter().enumerate() {
+            let mut state = AdpcmImaState::new();
+            encode_adpcm_ima_ima4_with_quality(&SAMPLES, &mut state, &mut encoded, *quality);
+            // the encoder state must match the decoder state
+            let mut decoded = [0i16; 64];
+            let mut decoder_state = AdpcmImaState::new();
+            decode_adpcm_ima_ima4(&encoded, &mut decoder_state, &mut decoded);
+            assert_eq!(state, decoder_state);
+            errors[i] = total_error(&SAMPLES, &decoded);
+        }
+        assert!(errors[1] <= errors[0]);
+        assert!(errors[2] <= errors[1]);
+        assert!(errors[2] < errors[0]);
+    }
+
+    #[test]
+    fn test_encode_adpcm_ms_with_quality() {
+        let mut samples = [0i16; 4082];
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = SAMPLES[i % 64];
+        }
+        // fast quality matches the normal encoder
+        let mut expected = [0u8; 2048];
+        let mut expected_states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+        assert!(encode_adpcm_ima_ms(&samples, &mut expected_states, &mut expected).is_ok());
+        let mut encoded = [0u8; 2048];
+        let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+        assert!(encode_adpcm_ima_ms_with_quality(&samples, &mut states, &mut encoded,
+            EncodeQuality::Fast).is_ok());
+        assert_eq!(encoded, expected);
+        assert_eq!(states, expected_states);
+
+        // beam search has less error
+        let mut decoded = [0i16; 4082];
+        assert!(decode_adpcm_ima_ms(&expected, true, &mut decoded).is_ok());
+        let fast_error = total_error(&samples, &decoded);
+        let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+        assert!(encode_adpcm_ima_ms_with_quality(&samples, &mut states, &mut encoded,
+            EncodeQuality::BeamSearch).is_ok());
+        assert!(decode_adpcm_ima_ms(&encoded, true, &mut decoded).is_ok());
+        assert!(total_error(&samples, &decoded) < fast_error);
+
+        // one channel, length not divisible by 8 samples
+        let mut expected = [0u8; 1022];
+        let mut expected_states = [ AdpcmImaState::new() ];
+        assert!(encode_adpcm_ima_ms(&samples[..2037], &mut expected_states, &mut expected).is_ok());
+        let mut states = [ AdpcmImaState::new() ];
+        let mut encoded = [0u8; 1022];
+        assert!(encode_adpcm_ima_ms_with_quality(&samples[..2037], &mut states, &mut encoded,
+            EncodeQuality::Fast).is_ok());
+        assert_eq!(encoded, expected);
+        let mut states = [ AdpcmImaState::new() ];
+        assert!(encode_adpcm_ima_ms_with_quality(&samples[..2037], &mut states, &mut encoded,
+            EncodeQuality::Balanced).is_ok());
+
+        // invalid lengths
+        let mut states = [ AdpcmImaState::new() ];
+        let mut encoded = [0u8; 15];
+        assert!(matches!(encode_adpcm_ima_ms_with_quality(&samples[..25], &mut states,
+            &mut encoded, EncodeQuality::BeamSearch), Err(Error::InvalidBufferSize)));
+        let mut states = [];
+        assert!(matches!(encode_adpcm_ima_ms_with_quality(&samples[..25], &mut states,
+            &mut encoded, EncodeQuality::BeamSearch), Err(Error::InvalidChannels)));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::Error;
+
+// FMOD FADPCM is a 4-bit ADPCM format of FMOD sound banks (FSB5). Every frame starts with
+// its own history values, so frames can be decoded independently.
+
+/// Size of a FADPCM frame for one channel in bytes.
+pub const FADPCM_FRAME_SIZE: usize = 140;
+
+/// Number of samples in a FADPCM frame for one channel.
+pub const FADPCM_SAMPLES_PER_FRAME: usize = 256;
+
+// prediction coefficient pairs in 1/64 units, the second coefficient is subtracted
+const FADPCM_COEFFICIENTS: &[[i32; 2]; 7] = &[
+    [ 0, 0 ],
+    [ 60, 0 ],
+    [ 122, 60 ],
+    [ 115, 52 ],
+    [ 98, 55 ],
+    [ 0, 0 ],
+    [ 0, 0 ],
+];
+
+/// Decodes a 140-byte FADPCM frame of one channel to 16-bit signed integer samples.
+///
+/// The frame starts with a 12 byte header: 8 coefficient indexes and 8 shift values packed in
+/// two 32-bit little-endian values (4 bits each) and two 16-bit little-endian history values.
+/// The history values aren't output as samples. The header is followed by 8 sub-blocks of
+/// 16 bytes. Each sub-block has 32 samples, which use the coefficient index and shift value of
+/// the sub-block. The samples are packed in 32-bit little-endian values, low nibble first.
+///
+/// This function outputs decoded samples to `out_samples`.
+pub fn decode_fadpcm_frame(buf: &[u8; 140], out_samples: &mut [i16; 256]) {
+    let coefficient_indexes = u32::from_le_bytes([ buf[0], buf[1], buf[2], buf[3] ]);
+    let shifts = u32::from_le_bytes([ buf[4], buf[5], buf[6], buf[7] ]);
+    let mut previous = i32::from(i16::from_le_bytes([ buf[8], buf[9] ]));
+    let mut previous2 = i32::from(i16::from_le_bytes([ buf[10], buf[11] ]));
+    for (i, (sub_block, out)) in buf[12..].chunks_exact(16)
+        .zip(out_samples.chunks_exact_mut(32)).enumerate() {
+        // coefficient indexes 7..=15 repeat the table
+        let index = usize::try_from((coefficient_indexes >> (i * 4)) & 0x0f).unwrap_or(0) % 7;
+        let [ coef1, coef2 ] = FADPCM_COEFFICIENTS[index];
+        let shift = 22 - ((shifts >> (i * 4)) & 0x0f);
+        for (out, code) in out.iter_mut().zip(sub_block.iter()
+            .flat_map(|b| [ *b << 4, *b & 0xf0 ])) {
+            let level = (i32::from(i8::from_le_bytes([ code ])) << 24) >> shift;
+            let sample = ((level - previous2 * coef2 + previous * coef1) >> 6)
+                .clamp(-32768, 32767);
+            previous2 = previous;
+            previous = sample;
+            *out = i16::try_from(sample).unwrap_or(0);
+        }
+    }
+}
+
+/// Decodes FADPCM frames to 16-bit signed integer samples.
+///
+/// `buf` should contain groups of `channels` frames: a 140-byte frame for each channel.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `256 * buf.len() / 140`. Samples are interleaved for multichannel audio.
+///
+/// An error is returned if `channels` is zero or if the `buf` length or the `out_samples`
+/// length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_fadpcm(buf: &[u8], channels: usize, out_samples: &mut [i16]) -> Result<(), Error> {
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    let group_size = FADPCM_FRAME_SIZE * channels;
+    if buf.len() % group_size != 0 ||
+        out_samples.len() != buf.len() / FADPCM_FRAME_SIZE * FADPCM_SAMPLES_PER_FRAME {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut frame_out = [0i16; FADPCM_SAMPLES_PER_FRAME];
+    for (group, out) in buf.chunks_exact(group_size)
+        .zip(out_samples.chunks_exact_mut(FADPCM_SAMPLES_PER_FRAME * channels)) {
+        for (ch, frame) in group.chunks_exact(FADPCM_FRAME_SIZE).enumerate() {
+            let Ok(frame) = frame.try_into() else {
+                return Err(Error::InvalidBufferSize);
+            };
+            decode_fadpcm_frame(frame, &mut frame_out);
+            for (out, sample) in out[ch..].iter_mut().step_by(channels).zip(frame_out) {
+                *out = sample;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_fadpcm_frame() {
+        let mut buf = [0u8; 140];
+        // sub-block 0 has index 0 and shift 12, which decodes codes as multiples of 4096
+        buf[4..8].copy_from_slice(&0x0000_000cu32.to_le_bytes());
+        buf[12] = 0x17;
+        buf[13] = 0x08;
+        let mut out = [0i16; 256];
+        decode_fadpcm_frame(&buf, &mut out);
+        assert_eq!(out[..5], [ 28672, 4096, -32768, 0, 0 ]);
+
+        // index 1 decays towards zero from the history value
+        let mut buf = [0u8; 140];
+        buf[0..4].copy_from_slice(&0x0000_0001u32.to_le_bytes());
+        buf[8..10].copy_from_slice(&6400i16.to_le_bytes());
+        decode_fadpcm_frame(&buf, &mut out);
+        assert_eq!(out[..3], [ 6000, 5625, 5273 ]);
+
+        // index 9 is the same as index 2, which uses both history values
+        let mut buf = [0u8; 140];
+        buf[0..4].copy_from_slice(&0x0000_0009u32.to_le_bytes());
+        buf[8..10].copy_from_slice(&1000i16.to_le_bytes());
+        buf[10..12].copy_from_slice(&1000i16.to_le_bytes());
+        decode_fadpcm_frame(&buf, &mut out);
+        assert_eq!(out[..2], [ 968, 907 ]);
+
+        // output is clamped
+        let mut buf = [0u8; 140];
+        buf[4..8].copy_from_slice(&0x0000_000fu32.to_le_bytes());
+        buf[12] = 0x87;
+        decode_fadpcm_frame(&buf, &mut out);
+        assert_eq!(out[..2], [ 32767, -32768 ]);
+    }
+
+    #[test]
+    fn test_decode_fadpcm() {
+        let mut buf = [0u8; 280];
+        buf[4..8].copy_from_slice(&0x0000_000cu32.to_le_bytes());
+        buf[12] = 0x01;
+        buf[144..148].copy_from_slice(&0x0000_000cu32.to_le_bytes());
+        buf[152] = 0x0f;
+        let mut out = [0i16; 512];
+        assert!(decode_fadpcm(&buf, 2, &mut out).is_ok());
+        assert_eq!(out[..4], [ 4096, -4096, 0, 0 ]);
+        assert!(decode_fadpcm(&buf, 1, &mut out).is_ok());
+        assert_eq!(out[..2], [ 4096, 0 ]);
+        assert_eq!(out[256], -4096);
+
+        assert!(matches!(decode_fadpcm(&buf, 0, &mut out), Err(Error::InvalidChannels)));
+        assert!(matches!(decode_fadpcm(&buf, 3, &mut out), Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_fadpcm(&buf, 2, &mut out[..256]),
+            Err(Error::InvalidBufferSize)));
+    }
+}
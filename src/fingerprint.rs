@@ -0,0 +1,129 @@
+
+use core::fmt;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Fingerprint of decoded audio for regression checking.
+///
+/// The fingerprint contains a 64-bit FNV-1a hash of the samples (as little-endian bytes),
+/// the sample count and the peak absolute sample value. It is stable across crate versions,
+/// so it can be stored instead of full decoded files to check that decoding is bit-exact.
+///
+/// The fingerprint can be calculated incrementally with [`AudioFingerprint::update()`]
+/// or for a single slice with [`fingerprint_samples()`]. It is displayed as
+/// `hash-count-peak`, for instance `a8c7f832281a39c5-64-2017`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AudioFingerprint {
+    hash: u64,
+    sample_count: u64,
+    peak: u16,
+}
+
+impl AudioFingerprint {
+    /// Creates a new fingerprint for zero samples.
+    pub fn new() -> AudioFingerprint {
+        AudioFingerprint {
+            hash: FNV_OFFSET_BASIS,
+            sample_count: 0,
+            peak: 0,
+        }
+    }
+
+    /// Updates the fingerprint with more samples.
+    pub fn update(&mut self, samples: &[i16]) {
+        for s in samples {
+            for byte in s.to_le_bytes() {
+                self.hash ^= u64::from(byte);
+                self.hash = self.hash.wrapping_mul(FNV_PRIME);
+            }
+            self.peak = self.peak.max(s.unsigned_abs());
+        }
+        self.sample_count += samples.len() as u64;
+    }
+
+    /// Returns the FNV-1a hash of the samples.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Returns the number of samples.
+    pub fn sample_count(&self) -> u64 {
+        self.sample_count
+    }
+
+    /// Returns the peak absolute sample value.
+    pub fn peak(&self) -> u16 {
+        self.peak
+    }
+}
+
+impl Default for AudioFingerprint {
+    fn default() -> Self {
+        AudioFingerprint::new()
+    }
+}
+
+impl fmt::Display for AudioFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}-{}-{}", self.hash, self.sample_count, self.peak)
+    }
+}
+
+/// Calculates the fingerprint of decoded 16-bit signed integer samples.
+///
+/// See [`AudioFingerprint`] for details.
+pub fn fingerprint_samples(samples: &[i16]) -> AudioFingerprint {
+    let mut fingerprint = AudioFingerprint::new();
+    fingerprint.update(samples);
+    fingerprint
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_samples() {
+        let empty = fingerprint_samples(&[]);
+        assert_eq!(empty.hash(), FNV_OFFSET_BASIS);
+        assert_eq!(empty.sample_count(), 0);
+        assert_eq!(empty.peak(), 0);
+        assert_eq!(empty, AudioFingerprint::default());
+
+        // FNV-1a of the bytes "a" and "b" (samples 0x6261 as little-endian)
+        let fingerprint = fingerprint_samples(&[ 0x6261 ]);
+        assert_eq!(fingerprint.hash(), 0x089c_4407_b545_986a);
+        let fingerprint = fingerprint_samples(&[ 0, -32768, 100, -5 ]);
+        assert_eq!(fingerprint.sample_count(), 4);
+        assert_eq!(fingerprint.peak(), 32768);
+
+        // incremental updates give the same result
+        let samples = [ 1, 2, 3, -4, 5, 6000, -7000 ];
+        let mut incremental = AudioFingerprint::new();
+        incremental.update(&samples[..3]);
+        incremental.update(&samples[3..]);
+        assert_eq!(incremental, fingerprint_samples(&samples));
+        assert_ne!(incremental, fingerprint_samples(&samples[..6]));
+        assert_ne!(fingerprint_samples(&[ 0 ]), fingerprint_samples(&[ 0, 0 ]));
+    }
+
+    #[test]
+    fn test_fingerprint_display() {
+        use core::fmt::Write;
+        struct Buf { data: [u8; 64], len: usize }
+        impl Write for Buf {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                let end = self.len + s.len();
+                self.data.get_mut(self.len..end).ok_or(fmt::Error)?.copy_from_slice(s.as_bytes());
+                self.len = end;
+                Ok(())
+            }
+        }
+        let mut buf = Buf { data: [0; 64], len: 0 };
+        assert!(write!(buf, "{}", fingerprint_samples(&[ 0x6261, -3 ])).is_ok());
+        let text = core::str::from_utf8(&buf.data[..buf.len]).unwrap_or("");
+        assert!(text.ends_with("-2-25185"));
+        assert_eq!(text.len(), 16 + 8);
+    }
+}
@@ -0,0 +1,177 @@
+
+use heapless::Vec;
+
+use crate::Error;
+use crate::AdpcmImaState;
+use crate::{decode_adpcm_ima_ima4_slice, decode_adpcm_ima_ms};
+use crate::{encode_adpcm_ima_ima4_slice, encode_adpcm_ima_ms};
+use crate::adpcm_ima::{ms_block_sample_len, ms_encoded_block_len};
+
+/// Decodes AIFF-C / QT "ima4" compressed blocks to a fixed-capacity `heapless::Vec`.
+///
+/// `buf` should contain one or more 34 byte blocks. Each block is decoded to 64 samples,
+/// so the capacity `N` must be at least `buf.len() / 34 * 64`.
+///
+/// The `state` parameter should be initialized to zero for the first call and subsequent calls
+/// should pass in the state values from the previous call.
+///
+/// An error is returned if the `buf` length isn't divisible by 34 or if the decoded samples
+/// don't fit in `N` samples.
+pub fn decode_adpcm_ima_ima4_heapless<const N: usize>(buf: &[u8], state: &mut AdpcmImaState)
+    -> Result<Vec<i16, N>, Error> {
+
+    if buf.len() % 34 != 0 {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut out_samples = Vec::new();
+    out_samples.resize(buf.len() / 34 * 64, 0).map_err(|_| Error::InvalidBufferSize)?;
+    for (block, out_block) in buf.chunks_exact(34).zip(out_samples.chunks_exact_mut(64)) {
+        decode_adpcm_ima_ima4_slice(block, state, out_block)?;
+    }
+    Ok(out_samples)
+}
+
+/// Decodes WAV / MS IMA ADPCM (wav format 0x0011) compressed block to
+/// a fixed-capacity `heapless::Vec`.
+///
+/// This works like [`decode_adpcm_ima_ms()`](crate::decode_adpcm_ima_ms), but the output
+/// vector is created by this function. The capacity `N` must be at least
+/// `2 * buf.len() - 7` for 1 channel audio and `2 * buf.len() - 14` for 2 channel audio.
+///
+/// An error is returned if the `buf` length isn't correct or if the decoded samples
+/// don't fit in `N` samples.
+pub fn decode_adpcm_ima_ms_heapless<const N: usize>(buf: &[u8], is_stereo: bool)
+    -> Result<Vec<i16, N>, Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    let sample_len = ms_block_sample_len(buf.len(), channels)?;
+    let mut out_samples = Vec::new();
+    out_samples.resize(sample_len, 0).map_err(|_| Error::InvalidBufferSize)?;
+    decode_adpcm_ima_ms(buf, is_stereo, &mut out_samples)?;
+    Ok(out_samples)
+}
+
+/// Encodes 16-bit signed integer samples to AIFF-C / QT "ima4" compressed blocks
+/// in a fixed-capacity `heapless::Vec`.
+///
+/// The `samples` length must be divisible by 64. Every 64 samples are encoded to a 34 byte block,
+/// so the capacity `N` must be at least `samples.len() / 64 * 34`.
+///
+/// The `state` parameter should be initialized to zero for the first call and subsequent calls
+/// should pass in the state values from the previous call.
+///
+/// An error is returned if the `samples` length isn't divisible by 64 or if the encoded bytes
+/// don't fit in `N` bytes.
+pub fn encode_adpcm_ima_ima4_heapless<const N: usize>(samples: &[i16],
+    state: &mut AdpcmImaState) -> Result<Vec<u8, N>, Error> {
+
+    if samples.len() % 64 != 0 {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut out_buf = Vec::new();
+    out_buf.resize(samples.len() / 64 * 34, 0).map_err(|_| Error::InvalidBufferSize)?;
+    for (block, out_block) in samples.chunks_exact(64).zip(out_buf.chunks_exact_mut(34)) {
+        encode_adpcm_ima_ima4_slice(block, state, out_block)?;
+    }
+    Ok(out_buf)
+}
+
+/// Encodes 16-bit signed integer samples to a MS / WAV IMA ADPCM (wav format 0x0011)
+/// compressed block in a fixed-capacity `heapless::Vec`.
+///
+/// This works like [`encode_adpcm_ima_ms()`](crate::encode_adpcm_ima_ms), but the output
+/// vector is created by this function. The capacity `N` must be at least
+/// `((samples.len() - states.len()) / 2) + states.len()*4`.
+///
+/// An error is returned if `states` has an invalid number of state objects,
+/// if the `samples` length isn't correct or if the encoded bytes don't fit in `N` bytes.
+pub fn encode_adpcm_ima_ms_heapless<const N: usize>(samples: &[i16],
+    states: &mut [AdpcmImaState]) -> Result<Vec<u8, N>, Error> {
+
+    let buf_len = ms_encoded_block_len(samples.len(), states.len())?;
+    let mut out_buf = Vec::new();
+    out_buf.resize(buf_len, 0).map_err(|_| Error::InvalidBufferSize)?;
+    encode_adpcm_ima_ms(samples, states, &mut out_buf)?;
+    Ok(out_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_adpcm_ima_ima4_heapless() {
+        let mut state = AdpcmImaState::new();
+        assert!(matches!(decode_adpcm_ima_ima4_heapless::<128>(&[0u8; 68], &mut state),
+            Ok(samples) if samples.len() == 128));
+
+        // capacity too small
+        let mut state = AdpcmImaState::new();
+        assert!(matches!(decode_adpcm_ima_ima4_heapless::<64>(&[0u8; 68], &mut state),
+            Err(Error::InvalidBufferSize)));
+
+        // partial block
+        let mut state = AdpcmImaState::new();
+        assert!(matches!(decode_adpcm_ima_ima4_heapless::<128>(&[0u8; 35], &mut state),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_ms_heapless() {
+        let samples = decode_adpcm_ima_ms_heapless::<32>(&[ 0xAE, 0xC8, 0x40, 0x00,
+            0x10, 0x10, 0x10, 0x11, 0x21, 0x21, 0x22, 0x32, 0x43, 0x33, 0x43, 0x43
+        ], false);
+        assert_eq!(samples.ok().as_deref(), Some(&[
+            -14162, -13747, -12613, -12270, -11334, -11050, -10276, -9573, -8934, -8352,
+            -7471, -6991, -6263, -5601, -5000, -4453, -3757, -3124, -2384, -1688,
+            -1055, -480, 192, 825, 1565
+        ][..]));
+
+        // capacity too small
+        assert!(matches!(decode_adpcm_ima_ms_heapless::<24>(&[0u8; 16], false),
+            Err(Error::InvalidBufferSize)));
+
+        // not enough input data
+        assert!(matches!(decode_adpcm_ima_ms_heapless::<32>(&[0u8; 7], true),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_encode_adpcm_ima_ima4_heapless() {
+        let mut state = AdpcmImaState::new();
+        assert!(matches!(encode_adpcm_ima_ima4_heapless::<68>(&[0i16; 128], &mut state),
+            Ok(buf) if buf.len() == 68));
+
+        // capacity too small
+        let mut state = AdpcmImaState::new();
+        assert!(matches!(encode_adpcm_ima_ima4_heapless::<34>(&[0i16; 128], &mut state),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_encode_adpcm_ima_ms_heapless() {
+        let mut states = [ AdpcmImaState::new() ];
+        let buf = encode_adpcm_ima_ms_heapless::<16>(&[
+            10, 10, 20, 50, 80, 100, 500, 1000, 1500, 2000,
+            1500, 800, 500, 300, 100, -100, -300, -500, -800, -1400,
+            -3000, -6000, -9000, -12000, -15000
+        ], &mut states);
+        assert_eq!(buf.ok().as_deref(), Some(&[
+            10, 0, 0, 0, 96, 87, 113, 119, 7, 155, 153, 169, 185, 254, 223, 187
+        ][..]));
+
+        // capacity too small
+        let mut states = [ AdpcmImaState::new() ];
+        assert!(matches!(encode_adpcm_ima_ms_heapless::<15>(&[0i16; 25], &mut states),
+            Err(Error::InvalidBufferSize)));
+
+        // invalid number of channels
+        let mut states = [];
+        assert!(matches!(encode_adpcm_ima_ms_heapless::<16>(&[0i16; 25], &mut states),
+            Err(Error::InvalidChannels)));
+    }
+}
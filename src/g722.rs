@@ -0,0 +1,677 @@
+
+use crate::{CodecState, Error};
+use crate::codec_state::{StateReader, StateWriter};
+
+// G.722 sub-band ADPCM, based on the ITU-T G.722 specification and the CMU / spandsp
+// implementation.
+
+const QMF_COEFFICIENTS: &[i32; 12] = &[
+    3, -11, 12, 32, -210, 951, 3876, -805, 362, -156, 53, -11
+];
+
+// quantizer decision levels for the lower sub-band
+const Q6: &[i32; 32] = &[
+    0, 35, 72, 110, 150, 190, 233, 276, 323, 370, 422, 473, 530, 587, 650, 714, 786, 858, 940,
+    1023, 1121, 1219, 1339, 1458, 1612, 1765, 1980, 2195, 2557, 2919, 0, 0
+];
+
+// lower sub-band codewords for negative and positive differences
+const ILN: &[u8; 32] = &[
+    0, 63, 62, 31, 30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19, 18, 17, 16, 15, 14, 13, 12,
+    11, 10, 9, 8, 7, 6, 5, 4, 0
+];
+const ILP: &[u8; 32] = &[
+    0, 61, 60, 59, 58, 57, 56, 55, 54, 53, 52, 51, 50, 49, 48, 47, 46, 45, 44, 43, 42, 41, 40,
+    39, 38, 37, 36, 35, 34, 33, 32, 0
+];
+
+// inverse quantizer outputs for the lower sub-band
+const QM4: &[i32; 16] = &[
+    0, -20456, -12896, -8968, -6288, -4240, -2584, -1200,
+    20456, 12896, 8968, 6288, 4240, 2584, 1200, 0
+];
+const QM5: &[i32; 32] = &[
+    -280, -280, -23352, -17560, -14120, -11664, -9752, -8184, -6864, -5712, -4696, -3784,
+    -2960, -2208, -1520, -880, 23352, 17560, 14120, 11664, 9752, 8184, 6864, 5712, 4696, 3784,
+    2960, 2208, 1520, 880, 280, -280
+];
+const QM6: &[i32; 64] = &[
+    -136, -136, -136, -136, -24808, -21904, -19008, -16704, -14984, -13512, -12280, -11192,
+    -10232, -9360, -8576, -7856, -7192, -6576, -6000, -5456, -4944, -4464, -4008, -3576, -3168,
+    -2776, -2400, -2032, -1688, -1360, -1040, -728, 24808, 21904, 19008, 16704, 14984, 13512,
+    12280, 11192, 10232, 9360, 8576, 7856, 7192, 6576, 6000, 5456, 4944, 4464, 4008, 3576,
+    3168, 2776, 2400, 2032, 1688, 1360, 1040, 728, 432, 136, -432, -136
+];
+
+// scale factor adaptation for the lower sub-band
+const RL42: &[usize; 16] = &[ 0, 7, 6, 5, 4, 3, 2, 1, 7, 6, 5, 4, 3, 2, 1, 0 ];
+const WL: &[i32; 8] = &[ -60, -30, 58, 172, 334, 538, 1198, 3042 ];
+
+// codewords, inverse quantizer outputs and scale factor adaptation for the higher sub-band
+const IHN: &[u8; 3] = &[ 0, 1, 0 ];
+const IHP: &[u8; 3] = &[ 0, 3, 2 ];
+const QM2: &[i32; 4] = &[ -7408, -1616, 7408, 1616 ];
+const RH2: &[usize; 4] = &[ 2, 1, 2, 1 ];
+const WH: &[i32; 3] = &[ 0, -214, 798 ];
+
+// inverse logarithmic scale factors
+const ILB: &[i32; 32] = &[
+    2048, 2093, 2139, 2186, 2233, 2282, 2332, 2383, 2435, 2489, 2543, 2599, 2656, 2714, 2774,
+    2834, 2896, 2960, 3025, 3091, 3158, 3228, 3298, 3371, 3444, 3520, 3597, 3676, 3756, 3838,
+    3922, 4008
+];
+
+// limits and shifts of the logarithmic scale factors of the lower and higher sub-bands
+const LOW_MAX_NB: i32 = 18432;
+const LOW_SHIFT: i32 = 8;
+const HIGH_MAX_NB: i32 = 22528;
+const HIGH_SHIFT: i32 = 10;
+
+fn saturate(value: i32) -> i32 {
+    value.clamp(-32768, 32767)
+}
+
+// ADPCM state of a sub-band
+#[derive(Debug, Clone, PartialEq)]
+struct G722Band {
+    // signal estimate
+    s: i32,
+    // zero predictor part of the signal estimate
+    sz: i32,
+    // reconstructed signals (index 0 is the latest)
+    r: [i32; 3],
+    // pole predictor coefficients (index 0 is unused)
+    a: [i32; 3],
+    // partially reconstructed signals
+    p: [i32; 3],
+    // quantized differences
+    d: [i32; 7],
+    // zero predictor coefficients (index 0 is unused)
+    b: [i32; 7],
+    // logarithmic scale factor
+    nb: i32,
+    // scale factor
+    det: i32,
+}
+
+impl G722Band {
+    fn new(det: i32) -> G722Band {
+        G722Band {
+            s: 0,
+            sz: 0,
+            r: [ 0; 3 ],
+            a: [ 0; 3 ],
+            p: [ 0; 3 ],
+            d: [ 0; 7 ],
+            b: [ 0; 7 ],
+            nb: 0,
+            det,
+        }
+    }
+
+    // updates the logarithmic scale factor and computes the new scale factor
+    fn adapt_scale(&mut self, w: i32, max_nb: i32, shift: i32) {
+        self.nb = (((self.nb * 127) >> 7) + w).clamp(0, max_nb);
+        self.det = scale_factor(self.nb, shift);
+    }
+
+    // returns true if the values are in the ranges that the adaptation keeps them in
+    fn is_in_range(&self, max_nb: i32, shift: i32) -> bool {
+        let i16_range = -32768..=32767;
+        (0..=max_nb).contains(&self.nb)
+            && self.det == scale_factor(self.nb, shift)
+            && self.a[0] == 0
+            && self.b[0] == 0
+            && (-12288..=12288).contains(&self.a[2])
+            && self.a[1].abs() <= 15360 - self.a[2]
+            && [ self.s, self.sz ].iter()
+                .chain(&self.r)
+                .chain(&self.p)
+                .chain(&self.d)
+                .chain(&self.b)
+                .all(|v| i16_range.contains(v))
+    }
+
+    // updates the predictor with quantized difference `d` (block 4 of G.722)
+    fn update(&mut self, d: i32) {
+        self.d[0] = d;
+        self.r[0] = saturate(self.s + d);
+        self.p[0] = saturate(self.sz + d);
+
+        // pole predictor coefficients
+        let sg0 = self.p[0] >> 15;
+        let sg1 = self.p[1] >> 15;
+        let sg2 = self.p[2] >> 15;
+        let wd1 = saturate(self.a[1] << 2);
+        let wd2 = (if sg0 == sg1 { -wd1 } else { wd1 }).min(32767);
+        let wd3 = (if sg0 == sg2 { 128 } else { -128 }) + (wd2 >> 7) +
+            ((self.a[2] * 32512) >> 15);
+        let a2 = wd3.clamp(-12288, 12288);
+        let wd1 = if sg0 == sg1 { 192 } else { -192 };
+        let wd2 = (self.a[1] * 32640) >> 15;
+        let limit = saturate(15360 - a2);
+        let a1 = saturate(wd1 + wd2).clamp(-limit, limit);
+
+        // zero predictor coefficients
+        let wd1 = if d == 0 { 0 } else { 128 };
+        let sg0 = d >> 15;
+        for i in 1..7 {
+            let wd2 = if self.d[i] >> 15 == sg0 { wd1 } else { -wd1 };
+            self.b[i] = saturate(wd2 + ((self.b[i] * 32640) >> 15));
+        }
+
+        // delay lines
+        self.d.copy_within(0..6, 1);
+        self.r.copy_within(0..2, 1);
+        self.p.copy_within(0..2, 1);
+        self.a[1] = a1;
+        self.a[2] = a2;
+
+        // signal estimate
+        let wd1 = (self.a[1] * saturate(self.r[1] + self.r[1])) >> 15;
+        let wd2 = (self.a[2] * saturate(self.r[2] + self.r[2])) >> 15;
+        let sp = saturate(wd1 + wd2);
+        self.sz = saturate((1..7)
+            .map(|i| (self.b[i] * saturate(self.d[i] + self.d[i])) >> 15)
+            .sum());
+        self.s = saturate(sp + self.sz);
+    }
+}
+
+// returns the scale factor of logarithmic scale factor `nb`
+fn scale_factor(nb: i32, shift: i32) -> i32 {
+    let ilb = ILB[usize::try_from((nb >> 6) & 31).unwrap_or(0)];
+    let wd = shift - (nb >> 11);
+    (if wd < 0 { ilb << -wd } else { ilb >> wd }) << 2
+}
+
+/// Operating mode of G.722.
+///
+/// In modes 2 and 3, the lowest bits of each codeword carry auxiliary data instead of
+/// audio. The encoder always produces the same lower sub-band codewords, so the mode only
+/// changes how many bits the decoder uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum G722Mode {
+    /// Mode 1: 64 kbit/s audio, no auxiliary data.
+    #[default]
+    Mode1,
+    /// Mode 2: 56 kbit/s audio, the lowest bit of each codeword is auxiliary data
+    /// (8 kbit/s).
+    Mode2,
+    /// Mode 3: 48 kbit/s audio, the lowest two bits of each codeword are auxiliary data
+    /// (16 kbit/s).
+    Mode3,
+}
+
+impl G722Mode {
+    /// Returns the audio bit rate of the mode in bits per second.
+    pub fn bit_rate(self) -> u32 {
+        match self {
+            G722Mode::Mode1 => 64000,
+            G722Mode::Mode2 => 56000,
+            G722Mode::Mode3 => 48000,
+        }
+    }
+
+    /// Returns the number of auxiliary data bits in the lowest bits of each codeword.
+    pub fn auxiliary_bits(self) -> u8 {
+        match self {
+            G722Mode::Mode1 => 0,
+            G722Mode::Mode2 => 1,
+            G722Mode::Mode3 => 2,
+        }
+    }
+}
+
+/// State values for the G.722 encoder and decoder.
+///
+/// The state contains the ADPCM state of the lower and higher sub-bands and
+/// the history of the quadrature mirror filter (QMF).
+///
+/// The state should be created with [`G722State::new()`] or [`G722State::with_mode()`]
+/// at the start of the stream. The encoder and decoder each need their own state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct G722State {
+    mode: G722Mode,
+    band: [G722Band; 2],
+    x: [i32; 24],
+}
+
+impl G722State {
+    /// Creates a new G722State for mode 1 (64 kbit/s) with initial values.
+    pub fn new() -> G722State {
+        G722State::with_mode(G722Mode::Mode1)
+    }
+
+    /// Creates a new G722State for the given mode with initial values.
+    pub fn with_mode(mode: G722Mode) -> G722State {
+        G722State {
+            mode,
+            band: [ G722Band::new(32), G722Band::new(8) ],
+            x: [ 0; 24 ],
+        }
+    }
+
+    /// Returns the mode of the state.
+    pub fn mode(&self) -> G722Mode {
+        self.mode
+    }
+
+    /// Sets the mode of the state.
+    ///
+    /// The mode can be changed in the middle of a stream.
+    pub fn set_mode(&mut self, mode: G722Mode) {
+        self.mode = mode;
+    }
+}
+
+impl Default for G722State {
+    fn default() -> Self {
+        G722State::new()
+    }
+}
+
+impl CodecState for G722State {
+    /// Mode (1, 2 or 3) followed by 27 32-bit little-endian values for each sub-band
+    /// (s, sz, r, a, p, d, b, nb and det) and 24 32-bit values for the QMF delay line.
+    const SERIALIZED_LEN: usize = 1 + 2 * 27 * 4 + 24 * 4;
+
+    /// Resets the state to initial values. The mode is kept.
+    fn reset(&mut self) {
+        *self = G722State::with_mode(self.mode);
+    }
+
+    /// Returns `true` if the state has the initial values of its mode.
+    fn is_default(&self) -> bool {
+        *self == G722State::with_mode(self.mode)
+    }
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.u8(match self.mode {
+            G722Mode::Mode1 => 1,
+            G722Mode::Mode2 => 2,
+            G722Mode::Mode3 => 3,
+        });
+        for band in &self.band {
+            w.i32_slice(&[ band.s, band.sz ]);
+            w.i32_slice(&band.r);
+            w.i32_slice(&band.a);
+            w.i32_slice(&band.p);
+            w.i32_slice(&band.d);
+            w.i32_slice(&band.b);
+            w.i32_slice(&[ band.nb, band.det ]);
+        }
+        w.i32_slice(&self.x);
+        Ok(())
+    }
+
+    /// An error is returned if the `buf` length isn't correct, if the mode is invalid or if
+    /// a value is out of range.
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        let mode = match r.u8() {
+            1 => G722Mode::Mode1,
+            2 => G722Mode::Mode2,
+            3 => G722Mode::Mode3,
+            _ => return Err(Error::InvalidData),
+        };
+        let mut state = G722State::with_mode(mode);
+        for band in &mut state.band {
+            band.s = r.i32();
+            band.sz = r.i32();
+            r.i32_slice(&mut band.r);
+            r.i32_slice(&mut band.a);
+            r.i32_slice(&mut band.p);
+            r.i32_slice(&mut band.d);
+            r.i32_slice(&mut band.b);
+            band.nb = r.i32();
+            band.det = r.i32();
+        }
+        r.i32_slice(&mut state.x);
+        if !state.band[0].is_in_range(LOW_MAX_NB, LOW_SHIFT)
+            || !state.band[1].is_in_range(HIGH_MAX_NB, HIGH_SHIFT)
+            || state.x.iter().any(|x| !(-32768..=32767).contains(x)) {
+            return Err(Error::InvalidData);
+        }
+        Ok(state)
+    }
+}
+
+/// Encodes two linear 16-bit signed integer sample values to a G.722 codeword.
+///
+/// G.722 codes 16 kHz audio at 64 kbit/s. The input samples are split to two sub-bands,
+/// which are coded with ADPCM. The lowest 6 bits of the codeword contain the lower sub-band
+/// and the highest 2 bits contain the higher sub-band.
+///
+/// In modes 2 and 3, the auxiliary data bits of the codeword are set to zero, so that
+/// they can be replaced with auxiliary data.
+///
+/// The `state` parameter should be initialized with [`G722State::new()`] or
+/// [`G722State::with_mode()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn encode_g722(sample_values: [i16; 2], state: &mut G722State) -> u8 {
+    // transmit QMF
+    state.x.copy_within(2.., 0);
+    state.x[22] = i32::from(sample_values[0]);
+    state.x[23] = i32::from(sample_values[1]);
+    let mut sum_odd = 0;
+    let mut sum_even = 0;
+    for (i, x) in state.x.chunks_exact(2).enumerate() {
+        sum_odd += x[0] * QMF_COEFFICIENTS[i];
+        sum_even += x[1] * QMF_COEFFICIENTS[11 - i];
+    }
+    let xlow = (sum_even + sum_odd) >> 14;
+    let xhigh = (sum_even - sum_odd) >> 14;
+
+    // lower sub-band
+    let low = &mut state.band[0];
+    let el = saturate(xlow - low.s);
+    let wd = if el >= 0 { el } else { -(el + 1) };
+    let i = (1..30)
+        .find(|i| wd < (Q6[*i] * low.det) >> 12)
+        .unwrap_or(30);
+    let ilow = if el < 0 { ILN[i] } else { ILP[i] };
+    let ril = usize::from(ilow >> 2);
+    let dlow = (low.det * QM4[ril]) >> 15;
+    low.adapt_scale(WL[RL42[ril]], LOW_MAX_NB, LOW_SHIFT);
+    low.update(dlow);
+
+    // higher sub-band
+    let high = &mut state.band[1];
+    let eh = saturate(xhigh - high.s);
+    let wd = if eh >= 0 { eh } else { -(eh + 1) };
+    let mih = if wd >= (564 * high.det) >> 12 { 2 } else { 1 };
+    let ihigh = if eh < 0 { IHN[mih] } else { IHP[mih] };
+    let dhigh = (high.det * QM2[usize::from(ihigh)]) >> 15;
+    high.adapt_scale(WH[RH2[usize::from(ihigh)]], HIGH_MAX_NB, HIGH_SHIFT);
+    high.update(dhigh);
+
+    let aux_mask = (1 << state.mode.auxiliary_bits()) - 1;
+    ((ihigh << 6) | ilow) & !aux_mask
+}
+
+/// Decodes a G.722 codeword to two linear 16-bit signed integer sample values.
+///
+/// In modes 2 and 3, the auxiliary data bits of the codeword are ignored.
+///
+/// The `state` parameter should be initialized with [`G722State::new()`] or
+/// [`G722State::with_mode()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn decode_g722(code: u8, state: &mut G722State) -> [i16; 2] {
+    let ilow = usize::from(code & 0x3f);
+    let ihigh = usize::from(code >> 6);
+
+    // lower sub-band
+    let low = &mut state.band[0];
+    let wd = match state.mode {
+        G722Mode::Mode1 => QM6[ilow],
+        G722Mode::Mode2 => QM5[ilow >> 1],
+        G722Mode::Mode3 => QM4[ilow >> 2],
+    };
+    let rlow = (low.s + ((low.det * wd) >> 15)).clamp(-16384, 16383);
+    let ril = ilow >> 2;
+    let dlow = (low.det * QM4[ril]) >> 15;
+    low.adapt_scale(WL[RL42[ril]], LOW_MAX_NB, LOW_SHIFT);
+    low.update(dlow);
+
+    // higher sub-band
+    let high = &mut state.band[1];
+    let dhigh = (high.det * QM2[ihigh]) >> 15;
+    let rhigh = (high.s + dhigh).clamp(-16384, 16383);
+    high.adapt_scale(WH[RH2[ihigh]], HIGH_MAX_NB, HIGH_SHIFT);
+    high.update(dhigh);
+
+    // receive QMF
+    state.x.copy_within(2.., 0);
+    state.x[22] = rlow + rhigh;
+    state.x[23] = rlow - rhigh;
+    let mut xout1 = 0;
+    let mut xout2 = 0;
+    for (i, x) in state.x.chunks_exact(2).enumerate() {
+        xout2 += x[0] * QMF_COEFFICIENTS[i];
+        xout1 += x[1] * QMF_COEFFICIENTS[11 - i];
+    }
+    [
+        i16::try_from(saturate(xout1 >> 11)).unwrap_or(0),
+        i16::try_from(saturate(xout2 >> 11)).unwrap_or(0),
+    ]
+}
+
+/// Encodes 16-bit signed integer samples to G.722 codewords.
+///
+/// Each byte contains a codeword for two samples.
+///
+/// The `state` parameter should be initialized with [`G722State::new()`] or
+/// [`G722State::with_mode()`] and subsequent calls should pass in the state values from
+/// the previous call.
+///
+/// This function outputs encoded bytes to `out_buf`. The `samples` length must be
+/// `2 * out_buf.len()`.
+///
+/// An error is returned if the `samples` length isn't correct.
+/// If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_g722_slice(samples: &[i16], state: &mut G722State, out_buf: &mut [u8])
+    -> Result<(), Error> {
+
+    if out_buf.len().checked_mul(2) != Some(samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (s, out) in samples.chunks_exact(2).zip(out_buf.iter_mut()) {
+        *out = encode_g722([ s[0], s[1] ], state);
+    }
+    Ok(())
+}
+
+/// Decodes G.722 codewords to 16-bit signed integer samples.
+///
+/// Each byte contains a codeword for two samples.
+///
+/// The `state` parameter should be initialized with [`G722State::new()`] or
+/// [`G722State::with_mode()`] and subsequent calls should pass in the state values from
+/// the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * buf.len()`.
+///
+/// An error is returned if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_g722_slice(buf: &[u8], state: &mut G722State, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    if buf.len().checked_mul(2) != Some(out_samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.iter().zip(out_samples.chunks_exact_mut(2)) {
+        out.copy_from_slice(&decode_g722(*b, state));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signal::tone;
+
+    #[test]
+    fn test_g722_silence() {
+        let mut encoder_state = G722State::new();
+        let mut decoder_state = G722State::new();
+        for _ in 0..100 {
+            let code = encode_g722([ 0, 0 ], &mut encoder_state);
+            let samples = decode_g722(code, &mut decoder_state);
+            assert!(samples[0].abs() < 8 && samples[1].abs() < 8);
+        }
+    }
+
+    #[test]
+    fn test_encode_g722() {
+        // 1 kHz sine at 16 kHz sample rate
+        let sine = [ 0, 3827, 7071, 9239, 10000, 9239, 7071, 3827,
+            0, -3827, -7071, -9239, -10000, -9239, -7071, -3827 ];
+        let mut encoder_state = G722State::new();
+        let mut decoder_state = G722State::new();
+        let mut decoded = [0i16; 2000];
+        for i in 0..1000 {
+            let code = encode_g722([ sine[(i * 2) % 16], sine[(i * 2 + 1) % 16] ],
+                &mut encoder_state);
+            decoded[i * 2..i * 2 + 2].copy_from_slice(&decode_g722(code, &mut decoder_state));
+            // sub-band states stay in sync
+            assert_eq!(encoder_state.band, decoder_state.band);
+        }
+        // the QMF filters delay the signal
+        let best_error = (0..32).map(|delay| {
+            (1000..2000).map(|i| (i32::from(sine[(i - delay) % 16]) -
+                i32::from(decoded[i])).abs()).max().unwrap_or(i32::MAX)
+        }).min().unwrap_or(i32::MAX);
+        assert!(best_error < 500);
+    }
+
+    #[test]
+    fn test_g722_modes() {
+        assert_eq!(G722Mode::default(), G722Mode::Mode1);
+        assert_eq!(G722Mode::Mode2.bit_rate(), 56000);
+        assert_eq!(G722Mode::Mode3.auxiliary_bits(), 2);
+        let mut state = G722State::new();
+        state.set_mode(G722Mode::Mode2);
+        assert_eq!(state.mode(), G722Mode::Mode2);
+
+        let samples = [ 0, 3000, 6000, 9000, 6000, 3000, 0, -3000, -6000, -9000, -6000, -3000 ];
+        for mode in [ G722Mode::Mode1, G722Mode::Mode2, G722Mode::Mode3 ] {
+            let mut encoder_state = G722State::with_mode(mode);
+            let mut decoder_state = G722State::with_mode(mode);
+            let mut aux_decoder_state = G722State::with_mode(mode);
+            let aux_mask = (1 << mode.auxiliary_bits()) - 1;
+            for i in 0..200 {
+                let code = encode_g722([ samples[(i * 2) % 12], samples[(i * 2 + 1) % 12] ],
+                    &mut encoder_state);
+                assert_eq!(code & aux_mask, 0);
+                // auxiliary data doesn't change the decoded samples
+                assert_eq!(decode_g722(code, &mut decoder_state),
+                    decode_g722(code | aux_mask, &mut aux_decoder_state));
+            }
+            assert_eq!(encoder_state.band, decoder_state.band);
+        }
+
+        // decoders in all modes stay in sync with a mode 1 stream, mode 3 is less accurate
+        let mut encoder_state = G722State::new();
+        let mut decoder_state = G722State::new();
+        let mut mode3_decoder_state = G722State::with_mode(G722Mode::Mode3);
+        let mut differences = 0;
+        for i in 0..200 {
+            let code = encode_g722([ samples[(i * 2) % 12], samples[(i * 2 + 1) % 12] ],
+                &mut encoder_state);
+            let decoded = decode_g722(code, &mut decoder_state);
+            let mode3_decoded = decode_g722(code, &mut mode3_decoder_state);
+            assert_eq!(decoder_state.band, mode3_decoder_state.band);
+            assert!((i32::from(decoded[0]) - i32::from(mode3_decoded[0])).abs() < 2000);
+            if decoded != mode3_decoded {
+                differences += 1;
+            }
+        }
+        assert!(differences > 100);
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = G722State::with_mode(G722Mode::Mode2);
+        assert!(state.is_default());
+        for i in 0..50 {
+            encode_g722([ i * 300, -i * 200 ], &mut state);
+        }
+        assert!(!state.is_default());
+        let mut buf = [0u8; G722State::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf[0], 2);
+        assert!(matches!(G722State::from_bytes(&buf), Ok(s) if s == state));
+        // reset keeps the mode
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, G722State::with_mode(G722Mode::Mode2));
+
+        buf[0] = 0;
+        assert!(matches!(G722State::from_bytes(&buf), Err(Error::InvalidData)));
+        buf[0] = 4;
+        assert!(matches!(G722State::from_bytes(&buf), Err(Error::InvalidData)));
+        assert!(matches!(G722State::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 312]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_codec_state_ranges() {
+        // states reached by decoding pseudorandom codewords and encoding loud signals are
+        // accepted
+        let mut seed = 1u32;
+        let mut decoder_state = G722State::new();
+        let mut encoder_state = G722State::new();
+        let mut buf = [0u8; G722State::SERIALIZED_LEN];
+        for i in 0..20000 {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            decode_g722(u8::try_from(seed >> 24).unwrap_or(0), &mut decoder_state);
+            encode_g722([ tone(i).saturating_mul(3), -tone(i + 1).saturating_mul(3) ],
+                &mut encoder_state);
+            for state in [ &decoder_state, &encoder_state ] {
+                assert!(state.to_bytes(&mut buf).is_ok());
+                assert!(matches!(G722State::from_bytes(&buf), Ok(s) if s == *state));
+            }
+        }
+
+        // values which the adaptation can't reach are rejected
+        assert!(G722State::new().to_bytes(&mut buf).is_ok());
+        fn with_value(mut buf: [u8; G722State::SERIALIZED_LEN], offset: usize, value: i32)
+            -> Result<G722State, Error> {
+            buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            G722State::from_bytes(&buf)
+        }
+        // nb and det of the lower and higher sub-bands
+        assert!(matches!(with_value(buf, 101, -1), Err(Error::InvalidData)));
+        assert!(matches!(with_value(buf, 101, 18433), Err(Error::InvalidData)));
+        assert!(matches!(with_value(buf, 105, 33), Err(Error::InvalidData)));
+        assert!(matches!(with_value(buf, 209, 22529), Err(Error::InvalidData)));
+        assert!(matches!(with_value(buf, 213, 32), Err(Error::InvalidData)));
+        let mut nb_buf = buf;
+        nb_buf[101..105].copy_from_slice(&18432i32.to_le_bytes());
+        nb_buf[105..109].copy_from_slice(&16384i32.to_le_bytes());
+        assert!(G722State::from_bytes(&nb_buf).is_ok());
+        // pole predictor coefficients, a[1] is limited by a[2]
+        assert!(matches!(with_value(buf, 21, 1), Err(Error::InvalidData)));
+        assert!(matches!(with_value(buf, 29, 12289), Err(Error::InvalidData)));
+        assert!(with_value(buf, 25, -15360).is_ok());
+        assert!(matches!(with_value(buf, 25, 15361), Err(Error::InvalidData)));
+        // zero predictor coefficients, signal history and QMF delay line
+        assert!(matches!(with_value(buf, 73, 1), Err(Error::InvalidData)));
+        assert!(matches!(with_value(buf, 77, 32768), Err(Error::InvalidData)));
+        assert!(matches!(with_value(buf, 1, -32769), Err(Error::InvalidData)));
+        assert!(matches!(with_value(buf, 153, 40000), Err(Error::InvalidData)));
+        assert!(with_value(buf, 309, -32768).is_ok());
+        assert!(matches!(with_value(buf, 309, 32768), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_g722_slice() {
+        let samples = [ 100, 2000, 3000, -500, -4000, 0 ];
+        let mut state = G722State::new();
+        let mut buf = [0u8; 3];
+        assert!(encode_g722_slice(&samples, &mut state, &mut buf).is_ok());
+        let mut expected_state = G722State::new();
+        for (s, b) in samples.chunks_exact(2).zip(buf) {
+            assert_eq!(encode_g722([ s[0], s[1] ], &mut expected_state), b);
+        }
+        assert_eq!(state, expected_state);
+
+        let mut state = G722State::new();
+        let mut out = [0i16; 6];
+        assert!(decode_g722_slice(&buf, &mut state, &mut out).is_ok());
+        let mut expected_state = G722State::new();
+        for (b, s) in buf.iter().zip(out.chunks_exact(2)) {
+            assert_eq!(decode_g722(*b, &mut expected_state), [ s[0], s[1] ]);
+        }
+
+        assert!(matches!(encode_g722_slice(&samples[..5], &mut state, &mut buf),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_g722_slice(&buf, &mut state, &mut out[..5]),
+            Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,1215 @@
+
+use core::cmp::Ordering;
+
+use crate::{CodecState, Error, decode_alaw, decode_ulaw, encode_alaw, encode_ulaw};
+use crate::codec_state::{StateReader, StateWriter};
+
+// G.726 ADPCM, based on the Sun Microsystems reference implementation of G.721 and G.723,
+// which is in the public domain.
+
+const POWER2: &[i32; 15] = &[
+    1, 2, 4, 8, 0x10, 0x20, 0x40, 0x80, 0x100, 0x200, 0x400, 0x800, 0x1000, 0x2000, 0x4000
+];
+
+// quantizer tables for a rate
+struct G726Tables {
+    // number of bits per codeword
+    bits: u8,
+    // number of quantizer states
+    states: i32,
+    // quantizer decision levels
+    quantizer: &'static [i32],
+    // log of the reconstructed difference for each codeword
+    dqln: &'static [i32],
+    // scale factor multipliers for each codeword
+    wi: &'static [i32],
+    // transitions of the speed control for each codeword
+    fi: &'static [i32],
+}
+
+static G726_16_TABLES: G726Tables = G726Tables {
+    bits: 2,
+    states: 4,
+    quantizer: &[ 261 ],
+    dqln: &[ 116, 365, 365, 116 ],
+    wi: &[ -704, 14048, 14048, -704 ],
+    fi: &[ 0, 0xe00, 0xe00, 0 ],
+};
+
+static G726_24_TABLES: G726Tables = G726Tables {
+    bits: 3,
+    states: 7,
+    quantizer: &[ 8, 218, 331 ],
+    dqln: &[ -2048, 135, 273, 373, 373, 273, 135, -2048 ],
+    wi: &[ -128, 960, 4384, 18624, 18624, 4384, 960, -128 ],
+    fi: &[ 0, 0x200, 0x400, 0xe00, 0xe00, 0x400, 0x200, 0 ],
+};
+
+static G726_32_TABLES: G726Tables = G726Tables {
+    bits: 4,
+    states: 15,
+    quantizer: &[ -124, 80, 178, 246, 300, 349, 400 ],
+    dqln: &[
+        -2048, 4, 135, 213, 273, 323, 373, 425, 425, 373, 323, 273, 213, 135, 4, -2048
+    ],
+    wi: &[
+        -384, 576, 1312, 2048, 3584, 6336, 11360, 35904,
+        35904, 11360, 6336, 3584, 2048, 1312, 576, -384
+    ],
+    fi: &[
+        0, 0, 0, 0x200, 0x200, 0x200, 0x600, 0xe00, 0xe00, 0x600, 0x200, 0x200, 0x200, 0, 0, 0
+    ],
+};
+
+static G726_40_TABLES: G726Tables = G726Tables {
+    bits: 5,
+    states: 31,
+    quantizer: &[ -122, -16, 68, 139, 198, 250, 298, 339, 378, 413, 445, 475, 502, 528, 553 ],
+    dqln: &[
+        -2048, -66, 28, 104, 169, 224, 274, 318, 358, 395, 429, 459, 488, 514, 539, 566,
+        566, 539, 514, 488, 459, 429, 395, 358, 318, 274, 224, 169, 104, 28, -66, -2048
+    ],
+    wi: &[
+        448, 448, 768, 1248, 1280, 1312, 1856, 3200, 4512, 5728, 7008, 8960, 11456, 14080,
+        16928, 22272, 22272, 16928, 14080, 11456, 8960, 7008, 5728, 4512, 3200, 1856, 1312,
+        1280, 1248, 768, 448, 448
+    ],
+    fi: &[
+        0, 0, 0, 0, 0, 0x200, 0x200, 0x200, 0x200, 0x200, 0x400, 0x600, 0x800, 0xa00, 0xc00,
+        0xc00, 0xc00, 0xc00, 0xa00, 0x800, 0x600, 0x400, 0x200, 0x200, 0x200, 0x200, 0x200,
+        0, 0, 0, 0, 0
+    ],
+};
+
+/// Bit rate of G.726.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum G726Rate {
+    /// 16 kbit/s with 2-bit codewords.
+    Rate16,
+    /// 24 kbit/s with 3-bit codewords.
+    Rate24,
+    /// 32 kbit/s with 4-bit codewords (G.721).
+    #[default]
+    Rate32,
+    /// 40 kbit/s with 5-bit codewords.
+    Rate40,
+}
+
+impl G726Rate {
+    /// Returns the number of bits in a codeword.
+    pub fn bits_per_sample(self) -> u8 {
+        self.tables().bits
+    }
+
+    fn tables(self) -> &'static G726Tables {
+        match self {
+            G726Rate::Rate16 => &G726_16_TABLES,
+            G726Rate::Rate24 => &G726_24_TABLES,
+            G726Rate::Rate32 => &G726_32_TABLES,
+            G726Rate::Rate40 => &G726_40_TABLES,
+        }
+    }
+}
+
+/// State values for the G.726 encoder and decoder.
+///
+/// The state should be created with [`G726State::new()`] or [`G726State::with_rate()`]
+/// at the start of the stream. The encoder and decoder each need their own state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct G726State {
+    rate: G726Rate,
+    // locked (steady state) quantizer scale factor
+    yl: i32,
+    // unlocked (non-steady state) quantizer scale factor
+    yu: i32,
+    // short and long term averages of the speed control transitions
+    dms: i32,
+    dml: i32,
+    // speed control parameter
+    ap: i32,
+    // pole predictor coefficients
+    a: [i32; 2],
+    // zero predictor coefficients
+    b: [i32; 6],
+    // signs of the previous partial signal estimates
+    pk: [i32; 2],
+    // previous quantized differences in floating point format
+    dq: [i32; 6],
+    // previous reconstructed signals in floating point format
+    sr: [i32; 2],
+    // tone detector
+    td: bool,
+    // 1984 G.721 without the tone and transition detector
+    legacy: bool,
+}
+
+impl G726State {
+    /// Creates a new G726State for 32 kbit/s with initial values.
+    pub fn new() -> G726State {
+        G726State::with_rate(G726Rate::Rate32)
+    }
+
+    /// Creates a new G726State for the given bit rate with initial values.
+    pub fn with_rate(rate: G726Rate) -> G726State {
+        G726State {
+            rate,
+            yl: 34816,
+            yu: 544,
+            dms: 0,
+            dml: 0,
+            ap: 0,
+            a: [ 0; 2 ],
+            b: [ 0; 6 ],
+            pk: [ 0; 2 ],
+            dq: [ 32; 6 ],
+            sr: [ 32; 2 ],
+            td: false,
+            legacy: false,
+        }
+    }
+
+    /// Creates a new G726State for the original 1984 version of G.721 with initial values.
+    ///
+    /// The 1984 version is 32 kbit/s ADPCM without the tone and transition detector, which
+    /// was added in the 1988 revision of G.721 and is part of G.726. The detector only affects
+    /// signals with strong tones, such as modem signals, so most speech decodes identically
+    /// in both modes.
+    pub fn with_legacy_g721() -> G726State {
+        G726State {
+            legacy: true,
+            ..G726State::with_rate(G726Rate::Rate32)
+        }
+    }
+
+    /// Returns the bit rate of the state.
+    pub fn rate(&self) -> G726Rate {
+        self.rate
+    }
+
+    /// Changes the bit rate of the state, keeping the adapted values.
+    ///
+    /// G.726 allows switching the bit rate in the middle of a stream. The encoder and
+    /// the decoder must switch at the same codeword.
+    ///
+    /// An error is returned if the state was created with [`G726State::with_legacy_g721()`]
+    /// and `rate` isn't 32 kbit/s, because the 1984 G.721 only has the 32 kbit/s rate and
+    /// the other rates are only defined with the tone and transition detector.
+    /// If an error is returned, the state is left unmodified.
+    pub fn set_rate(&mut self, rate: G726Rate) -> Result<(), Error> {
+        if self.legacy && rate != G726Rate::Rate32 {
+            return Err(Error::InvalidData);
+        }
+        self.rate = rate;
+        Ok(())
+    }
+
+    fn tables(&self) -> &'static G726Tables {
+        self.rate.tables()
+    }
+
+    // returns true if the values are in the ranges that the adaptation keeps them in
+    fn is_in_range(&self) -> bool {
+        // the floating point format has a 4-bit exponent, a 6-bit mantissa and a sign
+        let float_range = -0x400..=0x3ff;
+        let b_limit = if self.tables().bits == 5 { 0x10000 } else { 0x8000 };
+        (544 << 6..=5120 << 6).contains(&self.yl)
+            && (544..=5120).contains(&self.yu)
+            && (0..=0xe00).contains(&self.dms)
+            && (0..=0x3800).contains(&self.dml)
+            && (0..=1023).contains(&self.ap)
+            && (-12288..=12288).contains(&self.a[1])
+            && self.a[0].abs() <= 15360 - self.a[1]
+            && self.b.iter().all(|b| (-b_limit..=b_limit).contains(b))
+            && self.pk.iter().all(|pk| (0..=1).contains(pk))
+            && self.dq.iter().chain(&self.sr).all(|v| float_range.contains(v))
+    }
+
+    // returns the signal estimate and the partial signal estimate of the zero predictor
+    fn predict(&self) -> (i32, i32) {
+        let sezi = wrap_i16(self.b.iter().zip(&self.dq)
+            .map(|(b, dq)| fmult(b >> 2, *dq))
+            .sum());
+        let sei = wrap_i16(sezi + fmult(self.a[1] >> 2, self.sr[1]) +
+            fmult(self.a[0] >> 2, self.sr[0]));
+        (sei >> 1, sezi >> 1)
+    }
+
+    fn step_size(&self) -> i32 {
+        if self.ap >= 256 {
+            return self.yu;
+        }
+        let y = self.yl >> 6;
+        let dif = self.yu - y;
+        let al = self.ap >> 2;
+        if dif > 0 {
+            y + ((dif * al) >> 6)
+        } else if dif < 0 {
+            y + ((dif * al + 0x3f) >> 6)
+        } else {
+            y
+        }
+    }
+
+    // reconstructs the signal from codeword `code`, updates the state and
+    // returns the 14-bit reconstructed signal
+    fn reconstruct_and_update(&mut self, code: usize, se: i32, sez: i32, y: i32) -> i32 {
+        let tables = self.tables();
+        let sign = code & (1 << (tables.bits - 1)) != 0;
+        let dq = reconstruct(sign, tables.dqln[code], y);
+        let sr = wrap_i16(if dq < 0 { se - (dq & 0x3fff) } else { se + dq });
+        let dqsez = wrap_i16(sr + sez - se);
+        self.update(y, tables.wi[code], tables.fi[code], dq, sr, dqsez);
+        sr
+    }
+
+    fn update(&mut self, y: i32, wi: i32, fi: i32, dq: i32, sr: i32, dqsez: i32) {
+        let pk0 = i32::from(dqsez < 0);
+        let mag = dq & 0x7fff;
+
+        // tone and transition detector
+        let ylint = self.yl >> 15;
+        let ylfrac = (self.yl >> 10) & 0x1f;
+        let thr1 = (32 + ylfrac) << ylint;
+        let thr2 = if ylint > 9 { 31 << 10 } else { thr1 };
+        let dqthr = (thr2 + (thr2 >> 1)) >> 1;
+        let tr = !self.legacy && self.td && mag > dqthr;
+
+        // quantizer scale factor adaptation
+        self.yu = (y + ((wi - y) >> 5)).clamp(544, 5120);
+        self.yl += self.yu + ((-self.yl) >> 6);
+
+        // adaptive predictor coefficients
+        let mut a2p = 0;
+        if tr {
+            // reset for modem signals
+            self.a = [ 0; 2 ];
+            self.b = [ 0; 6 ];
+        } else {
+            let pks1 = pk0 ^ self.pk[0];
+            a2p = self.a[1] - (self.a[1] >> 7);
+            if dqsez != 0 {
+                let fa1 = if pks1 != 0 { self.a[0] } else { -self.a[0] };
+                if fa1 < -8191 {
+                    a2p -= 0x100;
+                } else if fa1 > 8191 {
+                    a2p += 0xff;
+                } else {
+                    a2p += fa1 >> 5;
+                }
+                if pk0 ^ self.pk[1] != 0 {
+                    if a2p <= -12160 {
+                        a2p = -12288;
+                    } else if a2p >= 12416 {
+                        a2p = 12288;
+                    } else {
+                        a2p -= 0x80;
+                    }
+                } else if a2p <= -12416 {
+                    a2p = -12288;
+                } else if a2p >= 12160 {
+                    a2p = 12288;
+                } else {
+                    a2p += 0x80;
+                }
+            }
+            self.a[1] = a2p;
+
+            self.a[0] -= self.a[0] >> 8;
+            if dqsez != 0 {
+                if pks1 == 0 {
+                    self.a[0] += 192;
+                } else {
+                    self.a[0] -= 192;
+                }
+            }
+            let a1ul = 15360 - a2p;
+            self.a[0] = self.a[0].clamp(-a1ul, a1ul);
+
+            let leak = if self.tables().bits == 5 { 9 } else { 8 };
+            for (b, prev_dq) in self.b.iter_mut().zip(&self.dq) {
+                *b -= *b >> leak;
+                if mag != 0 {
+                    if (dq ^ prev_dq) >= 0 {
+                        *b += 128;
+                    } else {
+                        *b -= 128;
+                    }
+                }
+            }
+        }
+
+        // store the quantized difference and the reconstructed signal in floating point format
+        self.dq.copy_within(0..5, 1);
+        self.dq[0] = if mag == 0 {
+            if dq >= 0 { 0x20 } else { -0x3e0 }
+        } else {
+            let exp = quan(mag);
+            let value = (exp << 6) + ((mag << 6) >> exp);
+            if dq >= 0 { value } else { value - 0x400 }
+        };
+        self.sr[1] = self.sr[0];
+        self.sr[0] = if sr == 0 {
+            0x20
+        } else if sr > 0 {
+            let exp = quan(sr);
+            (exp << 6) + ((sr << 6) >> exp)
+        } else if sr > -32768 {
+            let mag = -sr;
+            let exp = quan(mag);
+            (exp << 6) + ((mag << 6) >> exp) - 0x400
+        } else {
+            -0x3e0
+        };
+        self.pk[1] = self.pk[0];
+        self.pk[0] = pk0;
+
+        // tone detector
+        self.td = !self.legacy && !tr && a2p < -11776;
+
+        // adaptation speed control
+        self.dms += (fi - self.dms) >> 5;
+        self.dml += ((fi << 2) - self.dml) >> 7;
+        if tr {
+            self.ap = 256;
+        } else if y < 1536 || self.td || ((self.dms << 2) - self.dml).abs() >= (self.dml >> 3) {
+            self.ap += (0x200 - self.ap) >> 4;
+        } else {
+            self.ap += (-self.ap) >> 4;
+        }
+    }
+}
+
+impl Default for G726State {
+    fn default() -> Self {
+        G726State::new()
+    }
+}
+
+impl CodecState for G726State {
+    /// Rate (0 = 16, 1 = 24, 2 = 32 and 3 = 40 kbit/s) followed by 23 32-bit little-endian
+    /// values (yl, yu, dms, dml, ap, a, b, pk, dq and sr), the tone detector and the legacy
+    /// G.721 flag.
+    const SERIALIZED_LEN: usize = 1 + 23 * 4 + 2;
+
+    /// Resets the state to initial values. The rate and the legacy G.721 mode are kept.
+    fn reset(&mut self) {
+        *self = G726State {
+            legacy: self.legacy,
+            ..G726State::with_rate(self.rate)
+        };
+    }
+
+    /// Returns `true` if the state has the initial values of its rate and legacy G.721 mode.
+    fn is_default(&self) -> bool {
+        *self == G726State {
+            legacy: self.legacy,
+            ..G726State::with_rate(self.rate)
+        }
+    }
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.u8(match self.rate {
+            G726Rate::Rate16 => 0,
+            G726Rate::Rate24 => 1,
+            G726Rate::Rate32 => 2,
+            G726Rate::Rate40 => 3,
+        });
+        w.i32_slice(&[ self.yl, self.yu, self.dms, self.dml, self.ap ]);
+        w.i32_slice(&self.a);
+        w.i32_slice(&self.b);
+        w.i32_slice(&self.pk);
+        w.i32_slice(&self.dq);
+        w.i32_slice(&self.sr);
+        w.bool(self.td);
+        w.bool(self.legacy);
+        Ok(())
+    }
+
+    /// An error is returned if the `buf` length isn't correct, if the rate or a flag is
+    /// invalid, if a value is out of range or if the legacy G.721 mode has another rate than
+    /// 32 kbit/s.
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        let rate = match r.u8() {
+            0 => G726Rate::Rate16,
+            1 => G726Rate::Rate24,
+            2 => G726Rate::Rate32,
+            3 => G726Rate::Rate40,
+            _ => return Err(Error::InvalidData),
+        };
+        let mut state = G726State::with_rate(rate);
+        state.yl = r.i32();
+        state.yu = r.i32();
+        state.dms = r.i32();
+        state.dml = r.i32();
+        state.ap = r.i32();
+        r.i32_slice(&mut state.a);
+        r.i32_slice(&mut state.b);
+        r.i32_slice(&mut state.pk);
+        r.i32_slice(&mut state.dq);
+        r.i32_slice(&mut state.sr);
+        state.td = r.bool()?;
+        state.legacy = r.bool()?;
+        if (state.legacy && rate != G726Rate::Rate32) || !state.is_in_range() {
+            return Err(Error::InvalidData);
+        }
+        Ok(state)
+    }
+}
+
+// wraps the value to 16 bits like the reference implementation does
+fn wrap_i16(value: i32) -> i32 {
+    ((value + 0x8000) & 0xffff) - 0x8000
+}
+
+// returns the number of POWER2 values less than or equal to `value`
+fn quan(value: i32) -> i32 {
+    let count = POWER2.iter().take_while(|p| value >= **p).count();
+    i32::try_from(count).unwrap_or(15)
+}
+
+// multiplies a predictor coefficient with a floating point value
+fn fmult(an: i32, srn: i32) -> i32 {
+    let anmag = if an > 0 { an } else { (-an) & 0x1fff };
+    let anexp = quan(anmag) - 6;
+    let anmant = if anmag == 0 {
+        32
+    } else if anexp >= 0 {
+        anmag >> anexp
+    } else {
+        anmag << -anexp
+    };
+    let wanexp = anexp + ((srn >> 6) & 0xf) - 13;
+    let wanmant = (anmant * (srn & 0x3f) + 0x30) >> 4;
+    let retval = if wanexp >= 0 {
+        (wanmant << wanexp) & 0x7fff
+    } else {
+        wanmant >> -wanexp
+    };
+    if (an ^ srn) < 0 { -retval } else { retval }
+}
+
+// quantizes the difference `d` with step size `y` to a codeword
+fn quantize(d: i32, y: i32, tables: &G726Tables) -> usize {
+    let dqm = d.abs();
+    let exp = quan(dqm >> 1);
+    let mant = ((dqm << 7) >> exp) & 0x7f;
+    let dl = (exp << 7) + mant;
+    let dln = dl - (y >> 2);
+    let i = tables.quantizer.iter().take_while(|q| dln >= **q).count();
+    let size = tables.quantizer.len();
+    if d < 0 {
+        // one's complement of i
+        (size << 1) + 1 - i
+    } else if i == 0 && tables.states & 1 != 0 {
+        // zero is only valid for an even number of states
+        (size << 1) + 1
+    } else {
+        i
+    }
+}
+
+// returns the quantized difference in sign-magnitude format
+fn reconstruct(sign: bool, dqln: i32, y: i32) -> i32 {
+    let dql = dqln + (y >> 2);
+    if dql < 0 {
+        return if sign { -0x8000 } else { 0 };
+    }
+    let dex = (dql >> 7) & 15;
+    let dqt = 128 + (dql & 127);
+    let dq = (dqt << 7) >> (14 - dex);
+    if sign { dq - 0x8000 } else { dq }
+}
+
+/// Decodes a G.726 ADPCM codeword to a linear 16-bit signed integer sample value.
+///
+/// The codeword size depends on the rate of `state`, 2 to 5 bits. Only the lowest bits of
+/// `code` are used and the top-most bits are ignored. The decoder works with 14-bit samples,
+/// which are scaled to 16 bits.
+///
+/// The `state` parameter should be initialized with [`G726State::new()`] or
+/// [`G726State::with_rate()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn decode_g726(code: u8, state: &mut G726State) -> i16 {
+    let (sr, _, _, _) = decode_g726_value(code, state);
+    to_i16(sr << 2)
+}
+
+// decodes a codeword and returns the reconstructed signal, the signal estimate,
+// the step size and the masked codeword
+fn decode_g726_value(code: u8, state: &mut G726State) -> (i32, i32, i32, usize) {
+    let tables = state.tables();
+    let code = usize::from(code) & ((1 << tables.bits) - 1);
+    let (se, sez) = state.predict();
+    let y = state.step_size();
+    let sr = state.reconstruct_and_update(code, se, sez, y);
+    (sr, se, y, code)
+}
+
+fn to_i16(value: i32) -> i16 {
+    i16::try_from(value.clamp(-32768, 32767)).unwrap_or(0)
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a G.726 ADPCM codeword.
+///
+/// The codeword size depends on the rate of `state`, 2 to 5 bits. The encoder works with
+/// 14-bit samples, so the lowest 2 bits of `sample_value` are ignored.
+///
+/// The `state` parameter should be initialized with [`G726State::new()`] or
+/// [`G726State::with_rate()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn encode_g726(sample_value: i16, state: &mut G726State) -> u8 {
+    let tables = state.tables();
+    let sl = i32::from(sample_value) >> 2;
+    let (se, sez) = state.predict();
+    let d = wrap_i16(sl - se);
+    let y = state.step_size();
+    let code = quantize(d, y, tables);
+    state.reconstruct_and_update(code, se, sez, y);
+    u8::try_from(code).unwrap_or(0)
+}
+
+/// Decodes a G.726 ADPCM codeword to a 8-bit encoded G.711 A-law value.
+///
+/// This uses the synchronous coding adjustment of G.726, which adjusts the A-law value so that
+/// encoding it again with G.726 produces the same codeword. This prevents quantization error
+/// from accumulating when a signal passes through several G.711 and G.726 links (synchronous
+/// tandem coding). The A-law value should be encoded back to G.726 with
+/// [`encode_g726_from_alaw()`]. The value is adjusted by at most one A-law step like G.726
+/// specifies, which isn't always enough at 40 kbit/s.
+///
+/// The `state` parameter should be initialized with [`G726State::new()`] or
+/// [`G726State::with_rate()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn decode_g726_to_alaw(code: u8, state: &mut G726State) -> u8 {
+    let tables = state.tables();
+    let (sr, se, y, code) = decode_g726_value(code, state);
+    // the most negative value is the reconstructed signal overflowing
+    let sr = if sr <= -32768 { -1 } else { sr };
+    let sp = encode_alaw(to_i16((sr >> 1) << 3));
+    match tandem_adjustment(sp, decode_alaw, se, y, code, tables) {
+        // A-law values have every other bit inverted
+        Ordering::Less => {
+            if sp & 0x80 != 0 {
+                if sp == 0xd5 { 0x55 } else { ((sp ^ 0x55).wrapping_sub(1)) ^ 0x55 }
+            } else if sp == 0x2a {
+                0x2a
+            } else {
+                ((sp ^ 0x55).wrapping_add(1)) ^ 0x55
+            }
+        },
+        Ordering::Greater => {
+            if sp & 0x80 != 0 {
+                if sp == 0xaa { 0xaa } else { ((sp ^ 0x55).wrapping_add(1)) ^ 0x55 }
+            } else if sp == 0x55 {
+                0xd5
+            } else {
+                ((sp ^ 0x55).wrapping_sub(1)) ^ 0x55
+            }
+        },
+        Ordering::Equal => sp,
+    }
+}
+
+/// Decodes a G.726 ADPCM codeword to a 8-bit encoded G.711 μ-law value.
+///
+/// This uses the synchronous coding adjustment of G.726, which adjusts the μ-law value so that
+/// encoding it again with G.726 produces the same codeword. This prevents quantization error
+/// from accumulating when a signal passes through several G.711 and G.726 links (synchronous
+/// tandem coding). The μ-law value should be encoded back to G.726 with
+/// [`encode_g726_from_ulaw()`]. The value is adjusted by at most one μ-law step like G.726
+/// specifies, which isn't always enough at 40 kbit/s.
+///
+/// The `state` parameter should be initialized with [`G726State::new()`] or
+/// [`G726State::with_rate()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn decode_g726_to_ulaw(code: u8, state: &mut G726State) -> u8 {
+    let tables = state.tables();
+    let (sr, se, y, code) = decode_g726_value(code, state);
+    // the most negative value is the reconstructed signal overflowing
+    let sr = if sr <= -32768 { 0 } else { sr };
+    let sp = encode_ulaw(to_i16(sr << 2));
+    match tandem_adjustment(sp, decode_ulaw, se, y, code, tables) {
+        Ordering::Less => {
+            if sp & 0x80 != 0 {
+                if sp == 0xff { 0x7e } else { sp + 1 }
+            } else {
+                sp.saturating_sub(1)
+            }
+        },
+        Ordering::Greater => {
+            if sp & 0x80 != 0 {
+                if sp == 0x80 { 0x80 } else { sp - 1 }
+            } else if sp == 0x7f {
+                0xfe
+            } else {
+                sp + 1
+            }
+        },
+        Ordering::Equal => sp,
+    }
+}
+
+// re-encodes the G.711 value `sp` and returns whether it should be adjusted to the next
+// lower or higher value to produce the codeword `code`
+fn tandem_adjustment(sp: u8, decode: fn(u8) -> i16, se: i32, y: i32, code: usize,
+    tables: &G726Tables) -> Ordering {
+
+    let dx = wrap_i16((i32::from(decode(sp)) >> 2) - se);
+    let id = quantize(dx, y, tables);
+    // codewords are ordered from the most negative to the most positive after
+    // inverting the sign bit
+    let sign = 1 << (tables.bits - 1);
+    (code ^ sign).cmp(&(id ^ sign))
+}
+
+/// Encodes a 8-bit encoded G.711 A-law value to a G.726 ADPCM codeword.
+///
+/// The A-law value is expanded to a linear value and encoded like [`encode_g726()`] does.
+/// Together with [`decode_g726_to_alaw()`], this provides synchronous tandem coding
+/// between G.711 and G.726.
+///
+/// The `state` parameter should be initialized with [`G726State::new()`] or
+/// [`G726State::with_rate()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn encode_g726_from_alaw(encoded: u8, state: &mut G726State) -> u8 {
+    encode_g726(decode_alaw(encoded), state)
+}
+
+/// Encodes a 8-bit encoded G.711 μ-law value to a G.726 ADPCM codeword.
+///
+/// The μ-law value is expanded to a linear value and encoded like [`encode_g726()`] does.
+/// Together with [`decode_g726_to_ulaw()`], this provides synchronous tandem coding
+/// between G.711 and G.726.
+///
+/// The `state` parameter should be initialized with [`G726State::new()`] or
+/// [`G726State::with_rate()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn encode_g726_from_ulaw(encoded: u8, state: &mut G726State) -> u8 {
+    encode_g726(decode_ulaw(encoded), state)
+}
+
+/// Bit order of G.726 codewords packed to bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum G726Packing {
+    /// The first codeword is in the least significant bits of the first byte
+    /// (RFC 3551, Asterisk "g726", sometimes called right-justified).
+    LsbFirst,
+    /// The first codeword is in the most significant bits of the first byte
+    /// (ITU-T I.366.2 AAL2 and X.420, Asterisk "g726aal2", sometimes called left-justified).
+    MsbFirst,
+}
+
+/// Packs G.726 codewords to bytes.
+///
+/// Each codeword in `codes` uses `rate.bits_per_sample()` bits. Only the lowest bits of
+/// each codeword are used and the top-most bits are ignored. If the codewords don't fill
+/// the last byte, it is padded with zero bits.
+///
+/// This function outputs packed bytes to `out_buf`. The `out_buf` length must be
+/// `(codes.len() * rate.bits_per_sample() + 7) / 8`.
+///
+/// An error is returned if the `out_buf` length isn't correct.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn pack_g726(codes: &[u8], rate: G726Rate, packing: G726Packing, out_buf: &mut [u8])
+    -> Result<(), Error> {
+
+    let bits = usize::from(rate.bits_per_sample());
+    if codes.len().checked_mul(bits).map(|b| b.div_ceil(8)) != Some(out_buf.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mask = (1u32 << bits) - 1;
+    let mut acc = 0u32;
+    let mut acc_bits = 0;
+    let mut out_iter = out_buf.iter_mut();
+    for code in codes {
+        let code = u32::from(*code) & mask;
+        acc = match packing {
+            G726Packing::LsbFirst => acc | (code << acc_bits),
+            G726Packing::MsbFirst => (acc << bits) | code,
+        };
+        acc_bits += bits;
+        while acc_bits >= 8 {
+            acc_bits -= 8;
+            let byte = match packing {
+                G726Packing::LsbFirst => {
+                    let byte = acc & 0xff;
+                    acc >>= 8;
+                    byte
+                },
+                G726Packing::MsbFirst => (acc >> acc_bits) & 0xff,
+            };
+            if let Some(out) = out_iter.next() {
+                *out = u8::try_from(byte).unwrap_or(0);
+            }
+        }
+    }
+    if acc_bits > 0 {
+        let byte = match packing {
+            G726Packing::LsbFirst => acc & 0xff,
+            G726Packing::MsbFirst => (acc << (8 - acc_bits)) & 0xff,
+        };
+        if let Some(out) = out_iter.next() {
+            *out = u8::try_from(byte).unwrap_or(0);
+        }
+    }
+    Ok(())
+}
+
+/// Unpacks bytes to G.726 codewords.
+///
+/// Each codeword in `buf` uses `rate.bits_per_sample()` bits. Bits at the end of `buf`,
+/// which don't form a complete codeword, are ignored.
+///
+/// This function outputs codewords to `out_codes`. The `out_codes` length must be
+/// `buf.len() * 8 / rate.bits_per_sample()`.
+///
+/// An error is returned if the `out_codes` length isn't correct.
+/// If an error is returned, `out_codes` is left unmodified.
+pub fn unpack_g726(buf: &[u8], rate: G726Rate, packing: G726Packing, out_codes: &mut [u8])
+    -> Result<(), Error> {
+
+    let bits = usize::from(rate.bits_per_sample());
+    if buf.len().checked_mul(8).map(|b| b / bits) != Some(out_codes.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mask = (1u32 << bits) - 1;
+    let mut acc = 0u32;
+    let mut acc_bits = 0;
+    let mut out_iter = out_codes.iter_mut();
+    for b in buf {
+        acc = match packing {
+            G726Packing::LsbFirst => acc | (u32::from(*b) << acc_bits),
+            G726Packing::MsbFirst => (acc << 8) | u32::from(*b),
+        };
+        acc_bits += 8;
+        while acc_bits >= bits {
+            acc_bits -= bits;
+            let code = match packing {
+                G726Packing::LsbFirst => {
+                    let code = acc & mask;
+                    acc >>= bits;
+                    code
+                },
+                G726Packing::MsbFirst => (acc >> acc_bits) & mask,
+            };
+            if let Some(out) = out_iter.next() {
+                *out = u8::try_from(code).unwrap_or(0);
+            }
+        }
+        if packing == G726Packing::MsbFirst {
+            acc &= (1 << acc_bits) - 1;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToneGenerator;
+
+    #[test]
+    fn test_decode_g726() {
+        // initial step size is 544: code 7 is reconstructed as 22 (14-bit)
+        let mut state = G726State::new();
+        assert_eq!(decode_g726(7, &mut state), 88);
+        let mut state = G726State::new();
+        assert_eq!(decode_g726(8, &mut state), -88);
+        let mut state = G726State::new();
+        assert_eq!(decode_g726(15, &mut state), 0);
+        let mut state = G726State::new();
+        assert_eq!(decode_g726(0x17, &mut state), 88);
+
+        // maximum codes increase the signal and the step size
+        let mut state = G726State::new();
+        let mut previous = 0;
+        let mut previous_step = 0;
+        for _ in 0..10 {
+            let sample = decode_g726(7, &mut state);
+            assert!(sample > previous);
+            assert!(state.yu > previous_step);
+            previous = sample;
+            previous_step = state.yu;
+        }
+    }
+
+    #[test]
+    fn test_decode_g726_rates() {
+        // initial step size is 544 for all rates
+        let mut state = G726State::with_rate(G726Rate::Rate16);
+        assert_eq!(decode_g726(1, &mut state), 15 << 2);
+        let mut state = G726State::with_rate(G726Rate::Rate16);
+        assert_eq!(decode_g726(0, &mut state), 3 << 2);
+        let mut state = G726State::with_rate(G726Rate::Rate16);
+        assert_eq!(decode_g726(6, &mut state), -15 << 2);
+        let mut state = G726State::with_rate(G726Rate::Rate24);
+        assert_eq!(decode_g726(3, &mut state), 15 << 2);
+        let mut state = G726State::with_rate(G726Rate::Rate24);
+        assert_eq!(decode_g726(4, &mut state), -15 << 2);
+        let mut state = G726State::with_rate(G726Rate::Rate40);
+        assert_eq!(decode_g726(15, &mut state), 47 << 2);
+        let mut state = G726State::with_rate(G726Rate::Rate40);
+        assert_eq!(decode_g726(0x30, &mut state), -47 << 2);
+
+        assert_eq!(G726State::default().rate(), G726Rate::Rate32);
+        assert_eq!(G726Rate::Rate16.bits_per_sample(), 2);
+        assert_eq!(G726Rate::Rate40.bits_per_sample(), 5);
+    }
+
+    #[test]
+    fn test_g726_regression() {
+        // regression values of this implementation, which agree with the C model in
+        // test-files/c-reference/g72x.c. They aren't reference or conformance vectors, and
+        // they only detect changes in the output.
+        let samples = [
+            0, 1200, 3400, 6000, 7800, 6500, 2100, -2300,
+            -6100, -8000, -5200, -1400, 600, 250, -120, 40
+        ];
+        let expected: [(G726Rate, [u8; 16], [i16; 16]); 4] = [
+            (G726Rate::Rate16,
+                [ 0, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 1, 0, 3, 0 ],
+                [ 12, 60, 68, 80, 100, 120, 144, -120,
+                    -204, -296, -480, -824, 924, 556, -272, 352 ]),
+            (G726Rate::Rate24,
+                [ 7, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 5, 2, 7, 7, 7 ],
+                [ 0, 60, 76, 92, 108, 132, 180, -196, -324, -512, -920, -1112, 752, 40, 44, 28 ]),
+            (G726Rate::Rate32,
+                [ 15, 7, 7, 7, 7, 7, 7, 8, 8, 8, 12, 15, 1, 15, 15, 15 ],
+                [ 0, 88, 120, 172, 244, 376, 584, -868,
+                    -1944, -4500, -4592, -924, 504, 64, 92, 16 ]),
+            (G726Rate::Rate40,
+                [ 31, 15, 15, 15, 15, 15, 15, 16, 16, 16, 16, 28, 5, 1, 29, 31 ],
+                [ 0, 188, 228, 276, 364, 480, 644, -712,
+                    -1268, -2136, -4204, -1476, 748, 256, -208, 52 ]),
+        ];
+        for (rate, codes, decoded) in expected {
+            let mut encoder_state = G726State::with_rate(rate);
+            let mut decoder_state = G726State::with_rate(rate);
+            for i in 0..16 {
+                assert_eq!(encode_g726(samples[i], &mut encoder_state), codes[i]);
+                assert_eq!(decode_g726(codes[i], &mut decoder_state), decoded[i]);
+            }
+        }
+    }
+
+    // encodes and decodes a sine wave and returns the signal to noise ratio
+    fn encode_decode_sine(rate: G726Rate) -> f64 {
+        let mut encoder_state = G726State::with_rate(rate);
+        let mut decoder_state = G726State::with_rate(rate);
+        let mut tone = ToneGenerator::new(440.0, 8000, 8000);
+        let mut signal_energy = 0f64;
+        let mut noise_energy = 0f64;
+        for i in 0..4000 {
+            let sample = tone.next_sample();
+            let code = encode_g726(sample, &mut encoder_state);
+            assert!(code < 1 << rate.bits_per_sample());
+            let decoded = decode_g726(code, &mut decoder_state);
+            assert_eq!(encoder_state, decoder_state);
+            if i >= 200 {
+                signal_energy += f64::from(sample) * f64::from(sample);
+                let noise = f64::from(sample) - f64::from(decoded);
+                noise_energy += noise * noise;
+            }
+        }
+        signal_energy / noise_energy
+    }
+
+    #[test]
+    fn test_encode_g726() {
+        // signal to noise ratio should be over 20 dB
+        assert!(encode_decode_sine(G726Rate::Rate32) > 100.0);
+    }
+
+    #[test]
+    fn test_encode_g726_rates() {
+        let snr16 = encode_decode_sine(G726Rate::Rate16);
+        let snr24 = encode_decode_sine(G726Rate::Rate24);
+        let snr32 = encode_decode_sine(G726Rate::Rate32);
+        let snr40 = encode_decode_sine(G726Rate::Rate40);
+        assert!(snr16 > 3.0);
+        assert!(snr24 > snr16);
+        assert!(snr32 > snr24);
+        assert!(snr40 > snr32);
+    }
+
+    #[test]
+    fn test_legacy_g721() {
+        // a pure tone followed by a louder tone at another frequency triggers the tone and
+        // transition detector of G.726, which doesn't exist in the 1984 G.721
+        let mut state = G726State::new();
+        let mut decoder_state = G726State::new();
+        let mut legacy_state = G726State::with_legacy_g721();
+        assert_eq!(legacy_state.rate(), G726Rate::Rate32);
+        let mut tone_detected = false;
+        let mut transition_detected = false;
+        let mut differences = 0;
+        let mut low_tone = ToneGenerator::new(1000.0, 8000, 4000);
+        let mut high_tone = ToneGenerator::new(2400.0, 8000, 16000);
+        for i in 0..2000 {
+            let low_sample = low_tone.next_sample();
+            let high_sample = high_tone.next_sample();
+            let sample = if i < 1000 { low_sample } else { high_sample };
+            let code = encode_g726(sample, &mut state);
+            tone_detected |= state.td;
+            transition_detected |= state.ap == 256;
+            assert!(!legacy_state.td);
+            if decode_g726(code, &mut legacy_state) != decode_g726(code, &mut decoder_state) {
+                differences += 1;
+            }
+        }
+        assert!(tone_detected);
+        assert!(transition_detected);
+        assert!(differences > 0);
+
+        // without tones, both modes produce the same output
+        let mut state = G726State::new();
+        let mut legacy_state = G726State::with_legacy_g721();
+        for code in [ 7, 7, 7, 0, 8, 15, 3, 12, 1, 9 ] {
+            assert_eq!(decode_g726(code, &mut state), decode_g726(code, &mut legacy_state));
+        }
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = G726State::with_rate(G726Rate::Rate24);
+        assert!(state.is_default());
+        for sample in [ 1000, -2000, 3000, 12000, -8000, 500 ] {
+            encode_g726(sample, &mut state);
+        }
+        assert!(!state.is_default());
+        let mut buf = [0u8; G726State::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf[0], 1);
+        assert!(matches!(G726State::from_bytes(&buf), Ok(s) if s == state));
+        // reset keeps the rate
+        state.reset();
+        assert_eq!(state, G726State::with_rate(G726Rate::Rate24));
+
+        let mut legacy_state = G726State::with_legacy_g721();
+        decode_g726(3, &mut legacy_state);
+        assert!(legacy_state.to_bytes(&mut buf).is_ok());
+        assert!(matches!(G726State::from_bytes(&buf), Ok(s) if s == legacy_state));
+        legacy_state.reset();
+        assert!(legacy_state.is_default());
+        assert_eq!(legacy_state, G726State::with_legacy_g721());
+
+        // the legacy G.721 mode only has 32 kbit/s
+        buf[0] = 0;
+        assert!(matches!(G726State::from_bytes(&buf), Err(Error::InvalidData)));
+        buf[0] = 4;
+        assert!(matches!(G726State::from_bytes(&buf), Err(Error::InvalidData)));
+        buf[0] = 2;
+        buf[G726State::SERIALIZED_LEN - 2] = 2;
+        assert!(matches!(G726State::from_bytes(&buf), Err(Error::InvalidData)));
+        assert!(matches!(G726State::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 94]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_codec_state_ranges() {
+        // states reached by decoding pseudorandom codewords are accepted
+        let mut seed = 1u32;
+        for rate in [ G726Rate::Rate16, G726Rate::Rate24, G726Rate::Rate32, G726Rate::Rate40 ] {
+            let mut state = G726State::with_rate(rate);
+            let mut buf = [0u8; G726State::SERIALIZED_LEN];
+            for _ in 0..20000 {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                decode_g726(u8::try_from(seed >> 24).unwrap_or(0), &mut state);
+                assert!(state.to_bytes(&mut buf).is_ok());
+                assert!(matches!(G726State::from_bytes(&buf), Ok(s) if s == state));
+            }
+        }
+
+        // values which the adaptation can't reach are rejected
+        let mut buf = [0u8; G726State::SERIALIZED_LEN];
+        assert!(G726State::with_rate(G726Rate::Rate40).to_bytes(&mut buf).is_ok());
+        fn with_value(mut buf: [u8; G726State::SERIALIZED_LEN], offset: usize, value: i32)
+            -> Result<G726State, Error> {
+            buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            G726State::from_bytes(&buf)
+        }
+        // yl, yu, dms, dml and ap
+        for (offset, min, max) in [ (1, 34816, 327680), (5, 544, 5120), (9, 0, 0xe00),
+                (13, 0, 0x3800), (17, 0, 1023) ] {
+            assert!(with_value(buf, offset, min).is_ok());
+            assert!(with_value(buf, offset, max).is_ok());
+            assert!(matches!(with_value(buf, offset, min - 1), Err(Error::InvalidData)));
+            assert!(matches!(with_value(buf, offset, max + 1), Err(Error::InvalidData)));
+        }
+        // a[1] and a[0], which is limited by a[1]
+        assert!(matches!(with_value(buf, 25, 12289), Err(Error::InvalidData)));
+        assert!(matches!(with_value(buf, 25, -12289), Err(Error::InvalidData)));
+        assert!(with_value(buf, 21, -15360).is_ok());
+        assert!(matches!(with_value(buf, 21, 15361), Err(Error::InvalidData)));
+        let mut a_buf = buf;
+        a_buf[25..29].copy_from_slice(&12288i32.to_le_bytes());
+        a_buf[21..25].copy_from_slice(&3072i32.to_le_bytes());
+        assert!(G726State::from_bytes(&a_buf).is_ok());
+        a_buf[21..25].copy_from_slice(&3073i32.to_le_bytes());
+        assert!(matches!(G726State::from_bytes(&a_buf), Err(Error::InvalidData)));
+        // b[5] has a wider range at 40 kbit/s
+        assert!(with_value(buf, 49, -0x10000).is_ok());
+        assert!(matches!(with_value(buf, 49, 0x10001), Err(Error::InvalidData)));
+        buf[0] = 2;
+        assert!(matches!(with_value(buf, 49, 0x8001), Err(Error::InvalidData)));
+        // pk[1], dq[5] and sr[1]
+        assert!(matches!(with_value(buf, 57, 2), Err(Error::InvalidData)));
+        assert!(with_value(buf, 81, -0x400).is_ok());
+        assert!(matches!(with_value(buf, 81, 0x400), Err(Error::InvalidData)));
+        assert!(matches!(with_value(buf, 89, -0x401), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_set_rate() {
+        // switching the rate in the middle of a stream keeps the adapted values
+        let mut state = G726State::new();
+        for code in [ 7, 7, 7, 0, 8, 15 ] {
+            decode_g726(code, &mut state);
+        }
+        let mut switched_state = state.clone();
+        assert!(switched_state.set_rate(G726Rate::Rate16).is_ok());
+        assert_eq!(switched_state.rate(), G726Rate::Rate16);
+        assert_eq!(switched_state.yu, state.yu);
+        assert_eq!(switched_state.dq, state.dq);
+        assert_eq!(decode_g726(1, &mut switched_state),
+            decode_g726(1, &mut G726State { rate: G726Rate::Rate16, ..state }));
+
+        // the legacy G.721 mode only has 32 kbit/s
+        let mut legacy_state = G726State::with_legacy_g721();
+        for rate in [ G726Rate::Rate16, G726Rate::Rate24, G726Rate::Rate40 ] {
+            assert!(matches!(legacy_state.set_rate(rate), Err(Error::InvalidData)));
+            assert_eq!(legacy_state, G726State::with_legacy_g721());
+        }
+        assert!(legacy_state.set_rate(G726Rate::Rate32).is_ok());
+        assert!(legacy_state.legacy);
+    }
+
+    // decodes codewords to G.711 and encodes them back to G.726, and returns the number of
+    // codewords which changed although some G.711 value encodes to the original codeword.
+    // At low levels, the G.711 steps can be too coarse to reach every codeword.
+    fn count_tandem_errors(codes: &[u8], rate: G726Rate, decode: fn(u8, &mut G726State) -> u8,
+        encode: fn(u8, &mut G726State) -> u8) -> usize {
+
+        let mut decoder_state = G726State::with_rate(rate);
+        let mut encoder_state = G726State::with_rate(rate);
+        let mut errors = 0;
+        for code in codes {
+            let g711 = decode(*code, &mut decoder_state);
+            let previous_state = encoder_state.clone();
+            if encode(g711, &mut encoder_state) != *code {
+                let reachable = (0..=255).any(|g711| {
+                    encode(g711, &mut previous_state.clone()) == *code
+                });
+                if reachable {
+                    errors += 1;
+                }
+                encoder_state = decoder_state.clone();
+            }
+        }
+        errors
+    }
+
+    #[test]
+    fn test_g726_tandem() {
+        fn unadjusted_alaw(code: u8, state: &mut G726State) -> u8 {
+            encode_alaw(decode_g726(code, state))
+        }
+        fn unadjusted_ulaw(code: u8, state: &mut G726State) -> u8 {
+            encode_ulaw(decode_g726(code, state))
+        }
+        for rate in [ G726Rate::Rate16, G726Rate::Rate24, G726Rate::Rate32, G726Rate::Rate40 ] {
+            let mut encoder_state = G726State::with_rate(rate);
+            let mut low_tone = ToneGenerator::new(440.0, 8000, 6000);
+            let mut high_tone = ToneGenerator::new(1900.0, 8000, 3000);
+            let mut codes = [0u8; 800];
+            for code in &mut codes {
+                let sample = low_tone.next_sample().saturating_add(high_tone.next_sample());
+                *code = encode_g726(sample, &mut encoder_state);
+            }
+            let alaw_errors = count_tandem_errors(&codes, rate, decode_g726_to_alaw,
+                encode_g726_from_alaw);
+            let ulaw_errors = count_tandem_errors(&codes, rate, decode_g726_to_ulaw,
+                encode_g726_from_ulaw);
+            let unadjusted_alaw_errors = count_tandem_errors(&codes, rate, unadjusted_alaw,
+                encode_g726_from_alaw);
+            let unadjusted_ulaw_errors = count_tandem_errors(&codes, rate, unadjusted_ulaw,
+                encode_g726_from_ulaw);
+            if rate == G726Rate::Rate40 {
+                // one step adjustment isn't always enough for the finer 40 kbit/s quantizer
+                assert!(alaw_errors < unadjusted_alaw_errors);
+                assert!(ulaw_errors < unadjusted_ulaw_errors);
+            } else {
+                // G.726 to G.711 and back to G.726 reproduces the same codewords
+                assert_eq!(alaw_errors, 0);
+                assert_eq!(ulaw_errors, 0);
+            }
+            if rate == G726Rate::Rate32 {
+                assert!(unadjusted_alaw_errors > 0);
+                assert!(unadjusted_ulaw_errors > 0);
+            }
+        }
+
+        // the first codewords need no adjustment
+        let mut state = G726State::new();
+        assert_eq!(decode_g726_to_ulaw(7, &mut state), encode_ulaw(88));
+        let mut state = G726State::new();
+        assert_eq!(decode_g726_to_alaw(8, &mut state), encode_alaw(-88));
+    }
+
+    #[test]
+    fn test_pack_g726() {
+        let codes = [ 1, 2, 3, 4, 5 ];
+        let mut buf = [0u8; 3];
+        assert!(pack_g726(&codes, G726Rate::Rate32, G726Packing::LsbFirst, &mut buf).is_ok());
+        assert_eq!(buf, [ 0x21, 0x43, 0x05 ]);
+        assert!(pack_g726(&codes, G726Rate::Rate32, G726Packing::MsbFirst, &mut buf).is_ok());
+        assert_eq!(buf, [ 0x12, 0x34, 0x50 ]);
+
+        // 3-bit codewords 001 010 011 100 101: 11010001 01011000 and 00101001 11001010
+        let mut buf = [0u8; 2];
+        assert!(pack_g726(&codes, G726Rate::Rate24, G726Packing::LsbFirst, &mut buf).is_ok());
+        assert_eq!(buf, [ 0xd1, 0x58 ]);
+        assert!(pack_g726(&codes, G726Rate::Rate24, G726Packing::MsbFirst, &mut buf).is_ok());
+        assert_eq!(buf, [ 0x29, 0xca ]);
+
+        assert!(matches!(pack_g726(&codes, G726Rate::Rate40, G726Packing::LsbFirst, &mut buf),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_unpack_g726() {
+        let mut codes = [0u8; 6];
+        assert!(unpack_g726(&[ 0x21, 0x43, 0x05 ], G726Rate::Rate32, G726Packing::LsbFirst,
+            &mut codes).is_ok());
+        assert_eq!(codes, [ 1, 2, 3, 4, 5, 0 ]);
+        assert!(unpack_g726(&[ 0x12, 0x34, 0x50 ], G726Rate::Rate32, G726Packing::MsbFirst,
+            &mut codes).is_ok());
+        assert_eq!(codes, [ 1, 2, 3, 4, 5, 0 ]);
+
+        // roundtrip for all rates and packings
+        let codes = [ 31, 0, 17, 5, 9, 22, 3, 14, 30, 1, 8, 25, 6, 11, 19, 2 ];
+        for rate in [ G726Rate::Rate16, G726Rate::Rate24, G726Rate::Rate32, G726Rate::Rate40 ] {
+            let bits = rate.bits_per_sample();
+            let codes = codes.map(|c| c & ((1 << bits) - 1));
+            for packing in [ G726Packing::LsbFirst, G726Packing::MsbFirst ] {
+                let mut buf = [0u8; 10];
+                let buf = &mut buf[..usize::from(bits) * 2];
+                assert!(pack_g726(&codes, rate, packing, buf).is_ok());
+                let mut unpacked = [0u8; 16];
+                assert!(unpack_g726(buf, rate, packing, &mut unpacked).is_ok());
+                assert_eq!(unpacked, codes);
+            }
+        }
+
+        assert!(matches!(unpack_g726(&[ 0x12 ], G726Rate::Rate24, G726Packing::LsbFirst,
+            &mut codes.clone()), Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,828 @@
+
+use crate::{CodecState, Error};
+use crate::codec_state::{StateReader, StateWriter};
+use crate::bitreader::{BitReader, BitWriter};
+
+// GSM 06.10 full rate (RPE-LTP), based on the ETSI specification and the libgsm
+// implementation by Jutta Degener and Carsten Bormann (Technische Universitaet Berlin).
+
+/// Size of an encoded GSM 06.10 frame in bytes.
+pub const GSM_FRAME_SIZE: usize = 33;
+
+/// Number of samples in a GSM 06.10 frame.
+pub const GSM_SAMPLES_PER_FRAME: usize = 160;
+
+// the top 4 bits of the first byte of a frame
+const GSM_MAGIC: i32 = 0xd;
+
+const MIN_WORD: i32 = -32768;
+const MAX_WORD: i32 = 32767;
+
+// number of bits for each coded log area ratio
+const LARC_BITS: &[u32; 8] = &[ 6, 6, 5, 5, 4, 4, 3, 3 ];
+
+// tables 4.1 to 4.6 of the specification
+const A: &[i32; 8] = &[ 20480, 20480, 20480, 20480, 13964, 15360, 8534, 9036 ];
+const B: &[i32; 8] = &[ 0, 0, 2048, -2560, 94, -1792, -341, -1144 ];
+const MIC: &[i32; 8] = &[ -32, -32, -16, -16, -8, -8, -4, -4 ];
+const MAC: &[i32; 8] = &[ 31, 31, 15, 15, 7, 7, 3, 3 ];
+const INVA: &[i32; 8] = &[ 13107, 13107, 13107, 13107, 19223, 17476, 31454, 29708 ];
+const DLB: &[i32; 4] = &[ 6554, 16384, 26214, 32767 ];
+const QLB: &[i32; 4] = &[ 3277, 11469, 21299, 32767 ];
+const H: &[i32; 11] = &[ -134, -374, 0, 2054, 5741, 8192, 5741, 2054, 0, -374, -134 ];
+const NRFAC: &[i32; 8] = &[ 29128, 26215, 23832, 21846, 20165, 18725, 17476, 16384 ];
+const FAC: &[i32; 8] = &[ 18431, 20479, 22527, 24575, 26623, 28671, 30719, 32767 ];
+
+// 16-bit fixed point arithmetic of the specification
+
+fn add(a: i32, b: i32) -> i32 {
+    (a + b).clamp(MIN_WORD, MAX_WORD)
+}
+
+fn sub(a: i32, b: i32) -> i32 {
+    (a - b).clamp(MIN_WORD, MAX_WORD)
+}
+
+fn mult(a: i32, b: i32) -> i32 {
+    if a == MIN_WORD && b == MIN_WORD { MAX_WORD } else { (a * b) >> 15 }
+}
+
+fn mult_r(a: i32, b: i32) -> i32 {
+    if a == MIN_WORD && b == MIN_WORD { MAX_WORD } else { (a * b + 16384) >> 15 }
+}
+
+fn abs(a: i32) -> i32 {
+    if a == MIN_WORD { MAX_WORD } else { a.abs() }
+}
+
+fn wrap_word(value: i32) -> i32 {
+    ((value + 0x8000) & 0xffff) - 0x8000
+}
+
+// returns the number of left shifts needed to normalize a 32-bit value
+fn norm(a: i32) -> i32 {
+    if a <= -1073741824 {
+        return 0;
+    }
+    let a = if a < 0 { !a } else { a };
+    i32::try_from(a.leading_zeros()).unwrap_or(32) - 1
+}
+
+// returns num / denum as a 15-bit fraction, 0 <= num <= denum
+fn div(num: i32, denum: i32) -> i32 {
+    if num == 0 {
+        return 0;
+    }
+    let mut l_num = num;
+    let mut result = 0;
+    for _ in 0..15 {
+        result <<= 1;
+        l_num <<= 1;
+        if l_num >= denum {
+            l_num -= denum;
+            result += 1;
+        }
+    }
+    result
+}
+
+// coded parameters of a frame
+#[derive(Debug, Clone, PartialEq, Default)]
+struct GsmParameters {
+    // coded log area ratios
+    larc: [i32; 8],
+    // LTP lags
+    nc: [i32; 4],
+    // LTP gains
+    bc: [i32; 4],
+    // RPE grid positions
+    mc: [i32; 4],
+    // block amplitudes
+    xmaxc: [i32; 4],
+    // RPE pulses
+    xmc: [[i32; 13]; 4],
+}
+
+impl GsmParameters {
+    fn read(buf: &[u8; GSM_FRAME_SIZE]) -> Result<GsmParameters, Error> {
+        let mut reader = BitReader::new(buf);
+        let mut read = |count| reader.read(count)
+            .and_then(|v| i32::try_from(v).ok())
+            .ok_or(Error::InvalidData);
+        if read(4)? != GSM_MAGIC {
+            return Err(Error::InvalidData);
+        }
+        let mut params = GsmParameters::default();
+        for (larc, bits) in params.larc.iter_mut().zip(LARC_BITS) {
+            *larc = read(*bits)?;
+        }
+        for j in 0..4 {
+            params.nc[j] = read(7)?;
+            params.bc[j] = read(2)?;
+            params.mc[j] = read(2)?;
+            params.xmaxc[j] = read(6)?;
+            for xmc in &mut params.xmc[j] {
+                *xmc = read(3)?;
+            }
+        }
+        Ok(params)
+    }
+
+    fn write(&self, buf: &mut [u8; GSM_FRAME_SIZE]) {
+        let mut writer = BitWriter::new(buf);
+        let mut write = |value: i32, count| {
+            // values are already in range, so the write never fails
+            let _ = writer.write(u32::try_from(value).unwrap_or(0), count);
+        };
+        write(GSM_MAGIC, 4);
+        for (larc, bits) in self.larc.iter().zip(LARC_BITS) {
+            write(*larc, *bits);
+        }
+        for j in 0..4 {
+            write(self.nc[j], 7);
+            write(self.bc[j], 2);
+            write(self.mc[j], 2);
+            write(self.xmaxc[j], 6);
+            for xmc in &self.xmc[j] {
+                write(*xmc, 3);
+            }
+        }
+    }
+}
+
+/// State values for the GSM 06.10 encoder and decoder.
+///
+/// The state should be created with [`GsmState::new()`] at the start of the stream.
+/// The encoder and decoder each need their own state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GsmState {
+    // previous reconstructed short term residual signal
+    dp0: [i32; 280],
+    // offset compensation filter state
+    z1: i32,
+    l_z2: i32,
+    // preemphasis filter state
+    mp: i32,
+    // short term analysis filter state
+    u: [i32; 8],
+    // decoded log area ratios of the current and the previous frame
+    larpp: [[i32; 8]; 2],
+    // index of the current frame in larpp
+    j: usize,
+    // previous LTP lag of the decoder
+    nrp: i32,
+    // short term synthesis filter state
+    v: [i32; 9],
+    // deemphasis filter state
+    msr: i32,
+}
+
+impl GsmState {
+    /// Creates a new GsmState with initial values.
+    pub fn new() -> GsmState {
+        GsmState {
+            dp0: [ 0; 280 ],
+            z1: 0,
+            l_z2: 0,
+            mp: 0,
+            u: [ 0; 8 ],
+            larpp: [ [ 0; 8 ]; 2 ],
+            j: 0,
+            nrp: 40,
+            v: [ 0; 9 ],
+            msr: 0,
+        }
+    }
+
+    // downscaling, offset compensation and preemphasis (section 4.2.1 - 4.2.3)
+    fn preprocess(&mut self, samples: &[i16; GSM_SAMPLES_PER_FRAME],
+        so: &mut [i32; GSM_SAMPLES_PER_FRAME]) {
+
+        for (s, out) in samples.iter().zip(so.iter_mut()) {
+            let s0 = (i32::from(*s) >> 3) << 2;
+            let s1 = s0 - self.z1;
+            self.z1 = s0;
+            // l_z2 can saturate if a loaded state doesn't match its z1, so both parts
+            // wrap to words like in libgsm
+            let msp = wrap_word(self.l_z2 >> 15);
+            let lsp = wrap_word(self.l_z2.wrapping_sub(msp << 15));
+            let l_s2 = (s1 << 15) + mult_r(lsp, 32735);
+            self.l_z2 = (msp * 32735).saturating_add(l_s2);
+            let l_temp = self.l_z2.saturating_add(16384);
+            let msp = mult_r(self.mp, -28180);
+            self.mp = wrap_word(l_temp >> 15);
+            *out = add(self.mp, msp);
+        }
+    }
+
+    // decodes the log area ratios and returns the log area ratios for the previous and
+    // the current frame
+    fn decode_lars(&mut self, larc: &[i32; 8]) -> ([i32; 8], [i32; 8]) {
+        let previous = self.larpp[self.j];
+        self.j ^= 1;
+        let current = &mut self.larpp[self.j];
+        for i in 0..8 {
+            let temp = add(larc[i], MIC[i]) << 10;
+            let temp = sub(temp, B[i] << 1);
+            let temp = mult_r(INVA[i], temp);
+            current[i] = add(temp, temp);
+        }
+        (previous, *current)
+    }
+
+    // short term analysis filtering of a frame (section 4.2.10)
+    fn short_term_analysis_filter(&mut self, larc: &[i32; 8],
+        s: &mut [i32; GSM_SAMPLES_PER_FRAME]) {
+
+        let (previous, current) = self.decode_lars(larc);
+        let mut start = 0;
+        for (end, rp) in interpolated_coefficients(&previous, &current) {
+            for s in &mut s[start..end] {
+                let mut di = *s;
+                let mut sav = di;
+                for (u, rpi) in self.u.iter_mut().zip(rp) {
+                    let ui = *u;
+                    *u = sav;
+                    sav = add(ui, mult_r(rpi, di));
+                    di = add(di, mult_r(rpi, ui));
+                }
+                *s = di;
+            }
+            start = end;
+        }
+    }
+
+    // short term synthesis filtering of a frame (section 4.3.4)
+    fn short_term_synthesis_filter(&mut self, larc: &[i32; 8],
+        wt: &[i32; GSM_SAMPLES_PER_FRAME], s: &mut [i32; GSM_SAMPLES_PER_FRAME]) {
+
+        let (previous, current) = self.decode_lars(larc);
+        let mut start = 0;
+        for (end, rrp) in interpolated_coefficients(&previous, &current) {
+            for (wt, s) in wt[start..end].iter().zip(&mut s[start..end]) {
+                let mut sri = *wt;
+                for i in (0..8).rev() {
+                    sri = sub(sri, mult_r(rrp[i], self.v[i]));
+                    self.v[i + 1] = add(self.v[i], mult_r(rrp[i], sri));
+                }
+                self.v[0] = sri;
+                *s = sri;
+            }
+            start = end;
+        }
+    }
+
+    // postprocessing: deemphasis, truncation and upscaling (section 4.3.5 - 4.3.7)
+    fn postprocess(&mut self, s: &[i32; GSM_SAMPLES_PER_FRAME],
+        out_samples: &mut [i16; GSM_SAMPLES_PER_FRAME]) {
+
+        for (s, out) in s.iter().zip(out_samples.iter_mut()) {
+            self.msr = add(*s, mult_r(self.msr, 28180));
+            *out = i16::try_from(add(self.msr, self.msr) & !7).unwrap_or(0);
+        }
+    }
+}
+
+impl Default for GsmState {
+    fn default() -> Self {
+        GsmState::new()
+    }
+}
+
+impl CodecState for GsmState {
+    /// 32-bit little-endian values dp0, z1, l_z2, mp, u and larpp, the index of the current
+    /// frame in larpp (0 or 1) and 32-bit values nrp, v and msr.
+    const SERIALIZED_LEN: usize = (280 + 3 + 8 + 16) * 4 + 1 + (1 + 9 + 1) * 4;
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.i32_slice(&self.dp0);
+        w.i32_slice(&[ self.z1, self.l_z2, self.mp ]);
+        w.i32_slice(&self.u);
+        w.i32_slice(&self.larpp[0]);
+        w.i32_slice(&self.larpp[1]);
+        w.bool(self.j == 1);
+        w.i32(self.nrp);
+        w.i32_slice(&self.v);
+        w.i32(self.msr);
+        Ok(())
+    }
+
+    /// An error is returned if the `buf` length isn't correct, if a value other than l_z2
+    /// doesn't fit in 16 bits, if the frame index isn't 0 or 1 or if nrp isn't
+    /// in the range 40-120.
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        let mut state = GsmState::new();
+        r.i32_slice(&mut state.dp0);
+        state.z1 = r.i32();
+        state.l_z2 = r.i32();
+        state.mp = r.i32();
+        r.i32_slice(&mut state.u);
+        r.i32_slice(&mut state.larpp[0]);
+        r.i32_slice(&mut state.larpp[1]);
+        state.j = usize::from(r.bool()?);
+        state.nrp = r.i32();
+        r.i32_slice(&mut state.v);
+        state.msr = r.i32();
+        let other_words = [ state.z1, state.mp, state.msr ];
+        let words = state.dp0.iter()
+            .chain(&state.u)
+            .chain(state.larpp.iter().flatten())
+            .chain(&state.v)
+            .chain(&other_words);
+        for word in words {
+            if !(MIN_WORD..=MAX_WORD).contains(word) {
+                return Err(Error::InvalidData);
+            }
+        }
+        // the offset compensation filter keeps l_z2 within ±2^30, so its high part fits in
+        // a word
+        if !(40..=120).contains(&state.nrp)
+            || !(MIN_WORD << 15..=(MAX_WORD << 15) | 0x7fff).contains(&state.l_z2) {
+            return Err(Error::InvalidData);
+        }
+        Ok(state)
+    }
+}
+
+// returns the end sample index and the reflection coefficients interpolated between
+// the previous and the current frame for each part of the frame (section 4.2.9)
+fn interpolated_coefficients(previous: &[i32; 8], current: &[i32; 8]) -> [(usize, [i32; 8]); 4] {
+    let mut parts = [ (13, [ 0; 8 ]), (27, [ 0; 8 ]), (40, [ 0; 8 ]), (160, *current) ];
+    for i in 0..8 {
+        parts[0].1[i] = add(add(previous[i] >> 2, current[i] >> 2), previous[i] >> 1);
+        parts[1].1[i] = add(previous[i] >> 1, current[i] >> 1);
+        parts[2].1[i] = add(add(previous[i] >> 2, current[i] >> 2), current[i] >> 1);
+    }
+    for (_, larp) in &mut parts {
+        // conversion of the log area ratios to reflection coefficients
+        for larp in larp.iter_mut() {
+            let temp = abs(*larp);
+            let temp = if temp < 11059 {
+                temp << 1
+            } else if temp < 20070 {
+                temp + 11059
+            } else {
+                add(temp >> 2, 26112)
+            };
+            *larp = if *larp < 0 { -temp } else { temp };
+        }
+    }
+    parts
+}
+
+// LPC analysis of a frame, returns the coded log area ratios (section 4.2.4 - 4.2.8)
+fn lpc_analysis(s: &mut [i32; GSM_SAMPLES_PER_FRAME]) -> [i32; 8] {
+    // autocorrelation with dynamic scaling
+    let smax = s.iter().map(|v| abs(*v)).max().unwrap_or(0);
+    let scalauto = if smax == 0 { 0 } else { 4 - norm(smax << 16) };
+    if scalauto > 0 {
+        let factor = 16384 >> (scalauto - 1);
+        for v in s.iter_mut() {
+            *v = mult_r(*v, factor);
+        }
+    }
+    let mut l_acf = [ 0; 9 ];
+    for (k, acf) in l_acf.iter_mut().enumerate() {
+        let sum: i64 = (k..GSM_SAMPLES_PER_FRAME).map(|i| i64::from(s[i]) * i64::from(s[i - k]))
+            .sum();
+        *acf = i32::try_from(sum << 1).unwrap_or(i32::MAX);
+    }
+    if scalauto > 0 {
+        for v in s.iter_mut() {
+            *v = wrap_word(*v << scalauto);
+        }
+    }
+
+    // reflection coefficients with the Schur recursion
+    let mut r = [ 0; 8 ];
+    if l_acf[0] != 0 {
+        let temp = norm(l_acf[0]);
+        let acf = l_acf.map(|v| (v << temp) >> 16);
+        let mut p = acf;
+        let mut k = acf;
+        for n in 1..=8 {
+            let temp = abs(p[1]);
+            if p[0] < temp {
+                break;
+            }
+            let mut rn = div(temp, p[0]);
+            if p[1] > 0 {
+                rn = -rn;
+            }
+            r[n - 1] = rn;
+            if n == 8 {
+                break;
+            }
+            p[0] = add(p[0], mult_r(p[1], rn));
+            for m in 1..=8 - n {
+                p[m] = add(p[m + 1], mult_r(k[m], rn));
+                k[m] = add(k[m], mult_r(p[m + 1], rn));
+            }
+        }
+    }
+
+    // transformation to log area ratios and quantization
+    let mut larc = [ 0; 8 ];
+    for i in 0..8 {
+        let temp = abs(r[i]);
+        let temp = if temp < 22118 {
+            temp >> 1
+        } else if temp < 31130 {
+            temp - 11059
+        } else {
+            (temp - 26112) << 2
+        };
+        let lar = if r[i] < 0 { -temp } else { temp };
+        let temp = add(add(mult(A[i], lar), B[i]), 256) >> 9;
+        larc[i] = if temp > MAC[i] {
+            MAC[i] - MIC[i]
+        } else if temp < MIC[i] {
+            0
+        } else {
+            temp - MIC[i]
+        };
+    }
+    larc
+}
+
+// calculates the LTP lag and gain for subframe `d` with the previous reconstructed
+// short term residual signal `dp` (section 4.2.11)
+fn ltp_parameters(d: &[i32], dp: &[i32]) -> (i32, i32) {
+    let dmax = d.iter().map(|v| abs(*v)).max().unwrap_or(0);
+    let temp = if dmax == 0 { 0 } else { norm(dmax << 16) };
+    let scal = if temp > 6 { 0 } else { 6 - temp };
+    let mut wt = [ 0; 40 ];
+    for (wt, d) in wt.iter_mut().zip(d) {
+        *wt = d >> scal;
+    }
+
+    // search for the maximum cross-correlation
+    let mut l_max = 0;
+    let mut nc = 40;
+    for lambda in 40..=120 {
+        let l_result: i64 = wt.iter().zip(&dp[120 - lambda..])
+            .map(|(wt, dp)| i64::from(*wt) * i64::from(*dp))
+            .sum();
+        if l_result > l_max {
+            nc = lambda;
+            l_max = l_result;
+        }
+    }
+    let l_max = (l_max << 1) >> (6 - scal);
+
+    // power of the reconstructed short term residual signal
+    let l_power: i64 = dp[120 - nc..160 - nc].iter()
+        .map(|dp| i64::from(dp >> 3) * i64::from(dp >> 3))
+        .sum::<i64>() << 1;
+
+    let nc = i32::try_from(nc).unwrap_or(40);
+    if l_max <= 0 {
+        return (nc, 0);
+    }
+    if l_max >= l_power {
+        return (nc, 3);
+    }
+    let l_power = i32::try_from(l_power).unwrap_or(i32::MAX);
+    let l_max = i32::try_from(l_max).unwrap_or(i32::MAX);
+    let temp = norm(l_power);
+    let r = (l_max << temp) >> 16;
+    let s = (l_power << temp) >> 16;
+    let bc = (0..3).find(|bc| r <= mult(s, DLB[*bc])).unwrap_or(3);
+    (nc, i32::try_from(bc).unwrap_or(3))
+}
+
+// returns the exponent and the mantissa of coded block amplitude `xmaxc`
+fn xmaxc_to_exp_mant(xmaxc: i32) -> (i32, i32) {
+    let mut exp = if xmaxc > 15 { (xmaxc >> 3) - 1 } else { 0 };
+    let mut mant = xmaxc - (exp << 3);
+    if mant == 0 {
+        return (-4, 7);
+    }
+    while mant <= 7 {
+        mant = (mant << 1) | 1;
+        exp -= 1;
+    }
+    (exp, mant - 8)
+}
+
+// APCM inverse quantization of the RPE pulses and RPE grid positioning
+// (section 4.2.16 - 4.2.17)
+fn rpe_decoding(xmaxc: i32, mc: i32, xmc: &[i32; 13], ep: &mut [i32]) {
+    let (exp, mant) = xmaxc_to_exp_mant(xmaxc);
+    let temp1 = FAC[usize::try_from(mant).unwrap_or(0)];
+    let temp2 = 6 - exp;
+    let temp3 = if temp2 > 0 { 1 << (temp2 - 1) } else { 0 };
+    ep.fill(0);
+    let mc = usize::try_from(mc).unwrap_or(0);
+    for (ep, xmc) in ep.iter_mut().skip(mc).step_by(3).zip(xmc) {
+        // restore the sign
+        let temp = ((xmc << 1) - 7) << 12;
+        let temp = add(mult_r(temp1, temp), temp3);
+        *ep = temp >> temp2;
+    }
+}
+
+// RPE encoding of the residual signal `e`, which has 5 zero values before and after
+// the subframe (section 4.2.13 - 4.2.15). The residual signal is replaced by
+// the reconstructed one.
+fn rpe_encoding(e: &mut [i32; 50]) -> (i32, i32, [i32; 13]) {
+    // weighting filter
+    let mut x = [ 0; 40 ];
+    for (k, x) in x.iter_mut().enumerate() {
+        let l_result = e[k..k + 11].iter().zip(H)
+            .fold(4096, |acc, (e, h)| acc + e * h);
+        *x = (l_result >> 13).clamp(MIN_WORD, MAX_WORD);
+    }
+
+    // RPE grid selection
+    let energy = |m: usize| -> i32 {
+        x.iter().skip(m).step_by(3).take(13).map(|x| (x >> 2) * (x >> 2)).sum::<i32>() << 1
+    };
+    let mut mc = 0;
+    let mut em = energy(0);
+    for m in 1..4 {
+        let e = energy(m);
+        if e > em {
+            mc = m;
+            em = e;
+        }
+    }
+    let mut xm = [ 0; 13 ];
+    for (xm, x) in xm.iter_mut().zip(x.iter().skip(mc).step_by(3)) {
+        *xm = *x;
+    }
+
+    // APCM quantization of the block amplitude
+    let xmax = xm.iter().map(|v| abs(*v)).max().unwrap_or(0);
+    let mut exp = 0;
+    let mut temp = xmax >> 9;
+    let mut itest = false;
+    for _ in 0..6 {
+        itest |= temp <= 0;
+        temp >>= 1;
+        if !itest {
+            exp += 1;
+        }
+    }
+    let xmaxc = add(xmax >> (exp + 5), exp << 3);
+
+    // APCM quantization of the RPE pulses
+    let (exp, mant) = xmaxc_to_exp_mant(xmaxc);
+    let temp1 = 6 - exp;
+    let temp2 = NRFAC[usize::try_from(mant).unwrap_or(0)];
+    let mut xmc = [ 0; 13 ];
+    for (xmc, xm) in xmc.iter_mut().zip(xm) {
+        let temp = mult(wrap_word(xm << temp1), temp2) >> 12;
+        *xmc = temp + 4;
+    }
+
+    let mc = i32::try_from(mc).unwrap_or(0);
+    rpe_decoding(xmaxc, mc, &xmc, &mut e[5..45]);
+    (xmaxc, mc, xmc)
+}
+
+/// Decodes a GSM 06.10 full rate frame to 16-bit signed integer samples.
+///
+/// GSM 06.10 (RPE-LTP) codes 160 samples at 8 kHz sample rate to a frame of 33 bytes.
+/// The top 4 bits of the first byte of the frame must be 0xD.
+///
+/// The `state` parameter should be initialized with [`GsmState::new()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+///
+/// An error is returned if the frame doesn't start with 0xD.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_gsm_frame(buf: &[u8; GSM_FRAME_SIZE], state: &mut GsmState,
+    out_samples: &mut [i16; GSM_SAMPLES_PER_FRAME]) -> Result<(), Error> {
+
+    let params = GsmParameters::read(buf)?;
+    let mut wt = [ 0; GSM_SAMPLES_PER_FRAME ];
+    for j in 0..4 {
+        let mut erp = [ 0; 40 ];
+        rpe_decoding(params.xmaxc[j], params.mc[j], &params.xmc[j], &mut erp);
+
+        // long term synthesis filtering
+        let nr = if params.nc[j] < 40 || params.nc[j] > 120 { state.nrp } else { params.nc[j] };
+        state.nrp = nr;
+        let nr = usize::try_from(nr).unwrap_or(40);
+        let brp = QLB[usize::try_from(params.bc[j]).unwrap_or(0)];
+        for (k, erp) in erp.iter().enumerate() {
+            let drpp = mult_r(brp, state.dp0[120 + k - nr]);
+            state.dp0[120 + k] = add(*erp, drpp);
+        }
+        wt[j * 40..j * 40 + 40].copy_from_slice(&state.dp0[120..160]);
+        state.dp0.copy_within(40..160, 0);
+    }
+    let mut s = [ 0; GSM_SAMPLES_PER_FRAME ];
+    state.short_term_synthesis_filter(&params.larc, &wt, &mut s);
+    state.postprocess(&s, out_samples);
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to a GSM 06.10 full rate frame.
+///
+/// GSM 06.10 (RPE-LTP) codes 160 samples at 8 kHz sample rate to a frame of 33 bytes.
+/// The encoder works with 13-bit samples, so the lowest 3 bits of the samples are ignored.
+///
+/// The `state` parameter should be initialized with [`GsmState::new()`].
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn encode_gsm_frame(samples: &[i16; GSM_SAMPLES_PER_FRAME], state: &mut GsmState,
+    out_buf: &mut [u8; GSM_FRAME_SIZE]) {
+
+    let mut so = [ 0; GSM_SAMPLES_PER_FRAME ];
+    state.preprocess(samples, &mut so);
+    let mut params = GsmParameters {
+        larc: lpc_analysis(&mut so),
+        ..Default::default()
+    };
+    state.short_term_analysis_filter(&params.larc, &mut so);
+
+    let mut e = [ 0; 50 ];
+    for j in 0..4 {
+        let d = &so[j * 40..j * 40 + 40];
+        let dp_start = 120 + j * 40;
+        let (nc, bc) = ltp_parameters(d, &state.dp0[dp_start - 120..dp_start]);
+
+        // long term analysis filtering
+        let brp = QLB[usize::try_from(bc).unwrap_or(0)];
+        let lag = usize::try_from(nc).unwrap_or(40);
+        let mut dpp = [ 0; 40 ];
+        for k in 0..40 {
+            dpp[k] = mult_r(brp, state.dp0[dp_start + k - lag]);
+            e[5 + k] = sub(d[k], dpp[k]);
+        }
+
+        let (xmaxc, mc, xmc) = rpe_encoding(&mut e);
+        for k in 0..40 {
+            state.dp0[dp_start + k] = add(e[5 + k], dpp[k]);
+        }
+        params.nc[j] = nc;
+        params.bc[j] = bc;
+        params.mc[j] = mc;
+        params.xmaxc[j] = xmaxc;
+        params.xmc[j] = xmc;
+    }
+    state.dp0.copy_within(160..280, 0);
+    params.write(out_buf);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gsm_arithmetic() {
+        assert_eq!(norm(1), 30);
+        assert_eq!(norm(0x4000_0000), 0);
+        assert_eq!(norm(-1), 31);
+        assert_eq!(norm(-0x4000_0000), 0);
+        assert_eq!(div(1, 2), 16384);
+        assert_eq!(div(0, 5), 0);
+        assert_eq!(mult_r(MIN_WORD, MIN_WORD), MAX_WORD);
+        assert_eq!(mult_r(16384, 3), 2);
+        assert_eq!(xmaxc_to_exp_mant(0), (-4, 7));
+        assert_eq!(xmaxc_to_exp_mant(1), (-3, 7));
+        assert_eq!(xmaxc_to_exp_mant(16), (1, 0));
+        assert_eq!(xmaxc_to_exp_mant(63), (6, 7));
+    }
+
+    #[test]
+    fn test_gsm_parameters() {
+        let mut params = GsmParameters {
+            larc: [ 63, 1, 31, 2, 15, 3, 7, 4 ],
+            ..Default::default()
+        };
+        for j in 0..4 {
+            params.nc[j] = 40 + 20 * i32::try_from(j).unwrap_or(0);
+            params.bc[j] = 3;
+            params.mc[j] = 2;
+            params.xmaxc[j] = 63;
+            params.xmc[j] = [ 0, 1, 2, 3, 4, 5, 6, 7, 6, 5, 4, 3, 2 ];
+        }
+        let mut buf = [0u8; GSM_FRAME_SIZE];
+        params.write(&mut buf);
+        assert_eq!(buf[0], 0xdf);
+        assert_eq!(buf[1], 0xc1);
+        assert!(matches!(GsmParameters::read(&buf), Ok(p) if p == params));
+
+        buf[0] = 0xcf;
+        assert!(matches!(GsmParameters::read(&buf), Err(Error::InvalidData)));
+        let mut state = GsmState::new();
+        let mut out = [0i16; GSM_SAMPLES_PER_FRAME];
+        assert!(matches!(decode_gsm_frame(&buf, &mut state, &mut out), Err(Error::InvalidData)));
+        assert_eq!(state, GsmState::new());
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = GsmState::new();
+        assert!(state.is_default());
+        let mut samples = [0i16; GSM_SAMPLES_PER_FRAME];
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = i16::try_from((i * 1237) % 16000).unwrap_or(0) - 8000;
+        }
+        let mut buf = [0u8; GSM_FRAME_SIZE];
+        encode_gsm_frame(&samples, &mut state, &mut buf);
+        let mut decoder_state = GsmState::new();
+        let mut out = [0i16; GSM_SAMPLES_PER_FRAME];
+        assert!(decode_gsm_frame(&buf, &mut decoder_state, &mut out).is_ok());
+        assert!(!state.is_default());
+        assert!(!decoder_state.is_default());
+
+        let mut state_buf = [0u8; GsmState::SERIALIZED_LEN];
+        for s in [ &state, &decoder_state ] {
+            assert!(s.to_bytes(&mut state_buf).is_ok());
+            assert!(matches!(GsmState::from_bytes(&state_buf), Ok(d) if d == *s));
+        }
+        state.reset();
+        assert!(state.is_default());
+
+        // nrp out of range
+        let nrp_offset = (280 + 3 + 8 + 16) * 4 + 1;
+        state_buf[nrp_offset..nrp_offset + 4].copy_from_slice(&121i32.to_le_bytes());
+        assert!(matches!(GsmState::from_bytes(&state_buf), Err(Error::InvalidData)));
+        state_buf[nrp_offset..nrp_offset + 4].copy_from_slice(&120i32.to_le_bytes());
+        assert!(GsmState::from_bytes(&state_buf).is_ok());
+        // frame index isn't 0 or 1
+        state_buf[nrp_offset - 1] = 2;
+        assert!(matches!(GsmState::from_bytes(&state_buf), Err(Error::InvalidData)));
+        state_buf[nrp_offset - 1] = 1;
+        // dp0 value doesn't fit in 16 bits
+        state_buf[0..4].copy_from_slice(&32768i32.to_le_bytes());
+        assert!(matches!(GsmState::from_bytes(&state_buf), Err(Error::InvalidData)));
+        // l_z2 is a 32-bit value, but its high part must fit in a word
+        state_buf[0..4].copy_from_slice(&(-32768i32).to_le_bytes());
+        state_buf[281 * 4..282 * 4].copy_from_slice(&0x1234_5678i32.to_le_bytes());
+        assert!(GsmState::from_bytes(&state_buf).is_ok());
+        state_buf[281 * 4..282 * 4].copy_from_slice(&(-0x4000_0000i32).to_le_bytes());
+        assert!(GsmState::from_bytes(&state_buf).is_ok());
+        state_buf[281 * 4..282 * 4].copy_from_slice(&0x4000_0000i32.to_le_bytes());
+        assert!(matches!(GsmState::from_bytes(&state_buf), Err(Error::InvalidData)));
+        state_buf[281 * 4..282 * 4].copy_from_slice(&(-0x4000_0001i32).to_le_bytes());
+        assert!(matches!(GsmState::from_bytes(&state_buf), Err(Error::InvalidData)));
+        state_buf[281 * 4..282 * 4].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(matches!(GsmState::from_bytes(&state_buf), Err(Error::InvalidData)));
+
+        // full scale steps drive l_z2 close to its limits
+        let mut state = GsmState::new();
+        for period in 1..40 {
+            for (i, s) in samples.iter_mut().enumerate() {
+                *s = if (i / period) % 2 == 0 { i16::MAX } else { i16::MIN };
+            }
+            encode_gsm_frame(&samples, &mut state, &mut buf);
+            assert!(state.to_bytes(&mut state_buf).is_ok());
+            assert!(matches!(GsmState::from_bytes(&state_buf), Ok(d) if d == state));
+        }
+
+        assert!(matches!(GsmState::from_bytes(&state_buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 1]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_gsm_silence() {
+        let mut encoder_state = GsmState::new();
+        let mut decoder_state = GsmState::new();
+        let samples = [0i16; GSM_SAMPLES_PER_FRAME];
+        let mut buf = [0u8; GSM_FRAME_SIZE];
+        let mut out = [0i16; GSM_SAMPLES_PER_FRAME];
+        for _ in 0..3 {
+            encode_gsm_frame(&samples, &mut encoder_state, &mut buf);
+            assert_eq!(buf[0] >> 4, 0xd);
+            assert!(decode_gsm_frame(&buf, &mut decoder_state, &mut out).is_ok());
+            assert!(out.iter().all(|s| s.abs() <= 16));
+        }
+    }
+
+    #[test]
+    fn test_encode_gsm_frame() {
+        // 500 Hz sine at 8 kHz sample rate
+        let sine = [ 0, 3827, 7071, 9239, 10000, 9239, 7071, 3827,
+            0, -3827, -7071, -9239, -10000, -9239, -7071, -3827 ];
+        let mut encoder_state = GsmState::new();
+        let mut decoder_state = GsmState::new();
+        let mut signal_energy = 0f64;
+        let mut noise_energy = 0f64;
+        for frame in 0..10 {
+            let mut samples = [0i16; GSM_SAMPLES_PER_FRAME];
+            for (i, s) in samples.iter_mut().enumerate() {
+                *s = sine[i % 16];
+            }
+            let mut buf = [0u8; GSM_FRAME_SIZE];
+            encode_gsm_frame(&samples, &mut encoder_state, &mut buf);
+            let mut out = [0i16; GSM_SAMPLES_PER_FRAME];
+            assert!(decode_gsm_frame(&buf, &mut decoder_state, &mut out).is_ok());
+            if frame >= 2 {
+                for (s, o) in samples.iter().zip(out) {
+                    signal_energy += f64::from(*s) * f64::from(*s);
+                    let noise = f64::from(*s) - f64::from(o);
+                    noise_energy += noise * noise;
+                }
+            }
+        }
+        // signal to noise ratio should be over 10 dB
+        assert!(signal_energy > noise_energy * 10.0);
+    }
+}
@@ -0,0 +1,95 @@
+
+use crate::AdpcmImaState;
+use crate::adpcm_ima::{ValidatedImaState, apply_ima4_header};
+
+/// Policy for the predictor, which the "ima4" encoder writes to the block header.
+///
+/// The header stores only the top 9 bits of the predictor. Different encoders fill it
+/// differently, so this can be used to produce blocks matching a specific encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ima4HeaderPolicy {
+    /// Truncated predictor of the incoming encoder state,
+    /// same as [`encode_adpcm_ima_ima4()`](crate::encode_adpcm_ima_ima4).
+    #[default]
+    IncomingState,
+    /// Truncated first sample of the block. The decoder resets its predictor to the header
+    /// predictor if the first sample is far from the incoming state.
+    FirstSample,
+    /// Predictor of the incoming encoder state rounded to the nearest value which the header
+    /// can store. This is closer to the actual predictor than truncation for decoders, which
+    /// always use the header predictor.
+    Rounded,
+}
+
+/// Encodes 16-bit signed integer samples to an AIFF-C / QT "ima4" compressed block
+/// using the given header policy.
+///
+/// This works like [`encode_adpcm_ima_ima4()`](crate::encode_adpcm_ima_ima4), but `policy`
+/// selects the predictor written to the block header. The samples are encoded starting from
+/// the state which the decoder has after reading the header, so the encoder and decoder stay
+/// in sync.
+pub fn encode_adpcm_ima_ima4_with_header_policy(samples: &[i16; 64], state: &mut AdpcmImaState,
+    out_buf: &mut [u8; 34], policy: Ima4HeaderPolicy) {
+
+    state.step_index = state.step_index.min(88);
+    let predictor = match policy {
+        Ima4HeaderPolicy::IncomingState => state.predictor,
+        Ima4HeaderPolicy::FirstSample => samples[0],
+        Ima4HeaderPolicy::Rounded => state.predictor.saturating_add(0x40),
+    };
+    // the first two bytes are the initial state: pppppppp piiiiiii
+    let [ p0, p1 ] = predictor.to_be_bytes();
+    out_buf[0] = p0;
+    out_buf[1] = (p1 & 0x80) | state.step_index;
+    // continue encoding from the state the decoder will have after the header
+    apply_ima4_header(out_buf[0], out_buf[1], state);
+    let mut local = ValidatedImaState::load(state);
+    for (out_b, pair) in out_buf[2..].iter_mut().zip(samples.chunks_exact(2)) {
+        *out_b = local.encode_byte(pair[0], pair[1]);
+    }
+    local.store(state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_adpcm_ima_ima4, encode_adpcm_ima_ima4};
+
+    #[test]
+    fn test_encode_adpcm_ima_ima4_with_header_policy() {
+        let mut samples = [0i16; 64];
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = 5000 + i16::try_from(i).unwrap_or(0) * 100;
+        }
+        // incoming state matches the default encoder
+        let mut state = AdpcmImaState { predictor: 1234, step_index: 20 };
+        let mut expected_state = state.clone();
+        let mut out_buf = [0u8; 34];
+        let mut expected = [0u8; 34];
+        encode_adpcm_ima_ima4_with_header_policy(&samples, &mut state, &mut out_buf,
+            Ima4HeaderPolicy::IncomingState);
+        encode_adpcm_ima_ima4(&samples, &mut expected_state, &mut expected);
+        assert_eq!(out_buf, expected);
+        assert_eq!(state, expected_state);
+
+        // first sample
+        let mut state = AdpcmImaState { predictor: 1234, step_index: 20 };
+        encode_adpcm_ima_ima4_with_header_policy(&samples, &mut state, &mut out_buf,
+            Ima4HeaderPolicy::FirstSample);
+        assert_eq!(out_buf[..2], [ 0x13, 0x80 | 20 ]);
+        let mut decoder_state = AdpcmImaState { predictor: 1234, step_index: 20 };
+        let mut decoded = [0i16; 64];
+        decode_adpcm_ima_ima4(&out_buf, &mut decoder_state, &mut decoded);
+        assert_eq!(state, decoder_state);
+
+        // rounded
+        let mut state = AdpcmImaState { predictor: 1234, step_index: 20 };
+        encode_adpcm_ima_ima4_with_header_policy(&samples, &mut state, &mut out_buf,
+            Ima4HeaderPolicy::Rounded);
+        assert_eq!(out_buf[..2], [ 0x05, 20 ]);
+        let mut state = AdpcmImaState { predictor: 32767, step_index: 20 };
+        encode_adpcm_ima_ima4_with_header_policy(&samples, &mut state, &mut out_buf,
+            Ima4HeaderPolicy::Rounded);
+        assert_eq!(out_buf[..2], [ 0x7f, 0x80 | 20 ]);
+    }
+}
@@ -0,0 +1,1166 @@
+
+use core::mem::size_of;
+use crate::AdpcmImaState;
+
+/// Codecs supported by this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Codec {
+    /// G.711 A-law.
+    Alaw,
+    /// G.711 μ-law.
+    Ulaw,
+    /// Legacy 7-bit μ-law of D1 channel banks.
+    Ulaw7,
+    /// IMA ADPCM without blocks.
+    AdpcmIma,
+    /// AIFF-C / QT "ima4" IMA ADPCM.
+    AdpcmImaIma4,
+    /// WAV / MS IMA ADPCM (wav format 0x0011).
+    AdpcmImaMs,
+    /// WAV / MS IMA ADPCM with 2 bits per sample.
+    AdpcmImaMs2,
+    /// WAV / MS IMA ADPCM with 3 bits per sample.
+    AdpcmImaMs3,
+    /// WAV / MS IMA ADPCM with 5 bits per sample.
+    AdpcmImaMs5,
+    /// Xbox IMA ADPCM.
+    AdpcmImaXbox,
+    /// WAV / MS ADPCM (wav format 0x0002).
+    AdpcmMs,
+    /// Funcom ISS IMA ADPCM.
+    AdpcmImaIss,
+    /// Ubisoft APM IMA ADPCM.
+    AdpcmImaApm,
+    /// Yamaha ADPCM (AICA and YMZ280B).
+    AdpcmYamaha,
+    /// OKI ADPCM (Dialogic VOX, MSM6295 and MSM6258).
+    OkiAdpcm,
+    /// ITU-T G.726 ADPCM.
+    G726,
+    /// ITU-T G.722 sub-band ADPCM.
+    G722,
+    /// GSM 06.10 full rate.
+    Gsm,
+    /// Bluetooth CVSD.
+    Cvsd,
+    /// Harris HC-55516 CVSD.
+    Hc55516,
+    /// Bluetooth SBC.
+    Sbc,
+    /// Adobe Flash (SWF) ADPCM.
+    AdpcmSwf,
+    /// Creative Labs 8-bit to 4-bit ADPCM.
+    CreativeAdpcm4,
+    /// Creative Labs 8-bit to 3-bit (2.6 bits per sample) ADPCM.
+    CreativeAdpcm3,
+    /// Creative Labs 8-bit to 2-bit ADPCM.
+    CreativeAdpcm2,
+    /// Apple MACE 3:1.
+    Mace3,
+    /// Apple MACE 6:1.
+    Mace6,
+    /// SNES BRR ADPCM.
+    Brr,
+    /// Nintendo GameCube and Wii DSP-ADPCM.
+    DspAdpcm,
+    /// Nintendo THP video audio frames (DSP-ADPCM).
+    ThpAudio,
+    /// PlayStation SPU ADPCM.
+    SpuAdpcm,
+    /// Electronic Arts XAS ADPCM.
+    EaXas,
+    /// Maxis XA ADPCM.
+    MaxisXa,
+    /// Westwood Studios AUD IMA ADPCM.
+    AdpcmImaWestwood,
+    /// Westwood Studios SND1.
+    WestwoodSnd1,
+    /// Duck DK3 IMA ADPCM.
+    AdpcmImaDk3,
+    /// Duck DK4 IMA ADPCM.
+    AdpcmImaDk4,
+    /// 3DO SDX2 squareroot-delta-exact.
+    Sdx2,
+    /// Amiga IFF 8SVX Fibonacci-delta.
+    Svx8Fibonacci,
+    /// Amiga IFF 8SVX Exponential-delta.
+    Svx8Exponential,
+    /// CRI ADX ADPCM.
+    Adx,
+    /// Nintendo AFC ADPCM.
+    Afc,
+    /// Procyon Studio ADPCM.
+    ProcyonAdpcm,
+    /// Eurocom DAT4 IMA ADPCM.
+    AdpcmImaDat4,
+    /// Audiokinetic Wwise IMA ADPCM.
+    AdpcmImaWwise,
+    /// FMOD FADPCM.
+    Fadpcm,
+    /// Argonaut Games ASF ADPCM.
+    ArgoAdpcm,
+    /// DVI4 IMA ADPCM RTP payload (RFC 3551).
+    AdpcmImaDvi4,
+    /// Linear delta modulation.
+    DeltaModulation,
+    /// NICAM near-instantaneous companding.
+    Nicam,
+    /// DAT long-play 12-bit non-linear quantization.
+    DatLp,
+    /// Natural MicroSystems VBXADPCM.
+    AdpcmImaVbx,
+    /// Interplay DPCM.
+    InterplayDpcm,
+    /// Interplay ACM.
+    InterplayAcm,
+    /// LucasArts VIMA.
+    Vima,
+    /// CD-XA ADPCM.
+    XaAdpcm,
+    /// Generic DPCM with a caller-supplied delta table.
+    Dpcm,
+}
+
+/// Codec capabilities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodecInfo {
+    /// Human readable name of the codec.
+    pub name: &'static str,
+    /// Number of bits per encoded sample. For variable rate codecs, this is the maximum value.
+    pub bits_per_sample: u8,
+    /// Compression ratio compared to 16-bit linear PCM, including block headers.
+    /// For codecs with variable block sizes, this is calculated for the usual block size.
+    /// For variable rate codecs, this is the worst case.
+    pub compression_ratio: f64,
+    /// `true` if the codec encodes audio in blocks or frames with headers.
+    pub is_block_based: bool,
+    /// Block size in bytes for each channel, if the codec has a fixed block size.
+    pub block_size: Option<usize>,
+    /// Number of samples in a block for each channel, if the codec has a fixed block size.
+    pub samples_per_block: Option<usize>,
+    /// Maximum number of channels, or `None` if channels are coded independently and
+    /// there is no limit.
+    pub max_channels: Option<usize>,
+    /// Size in bytes of the state which must be kept between calls for each channel.
+    pub state_size: usize,
+    /// `true` if this crate can encode the codec, `false` if it can only decode it.
+    pub can_encode: bool,
+}
+
+impl Codec {
+    /// All supported codecs.
+    pub const ALL: &'static [Codec] = &[
+        Codec::Alaw,
+        Codec::Ulaw,
+        Codec::Ulaw7,
+        Codec::AdpcmIma,
+        Codec::AdpcmImaIma4,
+        Codec::AdpcmImaMs,
+        Codec::AdpcmImaMs2,
+        Codec::AdpcmImaMs3,
+        Codec::AdpcmImaMs5,
+        Codec::AdpcmImaXbox,
+        Codec::AdpcmMs,
+        Codec::AdpcmImaIss,
+        Codec::AdpcmImaApm,
+        Codec::AdpcmYamaha,
+        Codec::OkiAdpcm,
+        Codec::G726,
+        Codec::G722,
+        Codec::Gsm,
+        Codec::Cvsd,
+        Codec::Hc55516,
+        Codec::Sbc,
+        Codec::AdpcmSwf,
+        Codec::CreativeAdpcm4,
+        Codec::CreativeAdpcm3,
+        Codec::CreativeAdpcm2,
+        Codec::Mace3,
+        Codec::Mace6,
+        Codec::Brr,
+        Codec::DspAdpcm,
+        Codec::ThpAudio,
+        Codec::SpuAdpcm,
+        Codec::EaXas,
+        Codec::MaxisXa,
+        Codec::AdpcmImaWestwood,
+        Codec::WestwoodSnd1,
+        Codec::AdpcmImaDk3,
+        Codec::AdpcmImaDk4,
+        Codec::Sdx2,
+        Codec::Svx8Fibonacci,
+        Codec::Svx8Exponential,
+        Codec::Adx,
+        Codec::Afc,
+        Codec::ProcyonAdpcm,
+        Codec::AdpcmImaDat4,
+        Codec::AdpcmImaWwise,
+        Codec::Fadpcm,
+        Codec::ArgoAdpcm,
+        Codec::AdpcmImaDvi4,
+        Codec::DeltaModulation,
+        Codec::Nicam,
+        Codec::DatLp,
+        Codec::AdpcmImaVbx,
+        Codec::InterplayDpcm,
+        Codec::InterplayAcm,
+        Codec::Vima,
+        Codec::XaAdpcm,
+        Codec::Dpcm,
+    ];
+
+    /// Returns the capabilities of the codec.
+    pub fn info(self) -> &'static CodecInfo {
+        match self {
+            Codec::Alaw => &ALAW_INFO,
+            Codec::Ulaw => &ULAW_INFO,
+            Codec::Ulaw7 => &ULAW7_INFO,
+            Codec::AdpcmIma => &ADPCM_IMA_INFO,
+            Codec::AdpcmImaIma4 => &ADPCM_IMA_IMA4_INFO,
+            Codec::AdpcmImaMs => &ADPCM_IMA_MS_INFO,
+            Codec::AdpcmImaMs2 => &ADPCM_IMA_MS2_INFO,
+            Codec::AdpcmImaMs3 => &ADPCM_IMA_MS3_INFO,
+            Codec::AdpcmImaMs5 => &ADPCM_IMA_MS5_INFO,
+            Codec::AdpcmImaXbox => &ADPCM_IMA_XBOX_INFO,
+            Codec::AdpcmMs => &ADPCM_MS_INFO,
+            Codec::AdpcmImaIss => &ADPCM_IMA_ISS_INFO,
+            Codec::AdpcmImaApm => &ADPCM_IMA_APM_INFO,
+            Codec::AdpcmYamaha => &ADPCM_YAMAHA_INFO,
+            Codec::OkiAdpcm => &OKI_ADPCM_INFO,
+            Codec::G726 => &G726_INFO,
+            Codec::G722 => &G722_INFO,
+            Codec::Gsm => &GSM_INFO,
+            Codec::Cvsd => &CVSD_INFO,
+            Codec::Hc55516 => &HC55516_INFO,
+            Codec::Sbc => &SBC_INFO,
+            Codec::AdpcmSwf => &ADPCM_SWF_INFO,
+            Codec::CreativeAdpcm4 => &CREATIVE_ADPCM4_INFO,
+            Codec::CreativeAdpcm3 => &CREATIVE_ADPCM3_INFO,
+            Codec::CreativeAdpcm2 => &CREATIVE_ADPCM2_INFO,
+            Codec::Mace3 => &MACE3_INFO,
+            Codec::Mace6 => &MACE6_INFO,
+            Codec::Brr => &BRR_INFO,
+            Codec::DspAdpcm => &DSP_ADPCM_INFO,
+            Codec::ThpAudio => &THP_AUDIO_INFO,
+            Codec::SpuAdpcm => &SPU_ADPCM_INFO,
+            Codec::EaXas => &EA_XAS_INFO,
+            Codec::MaxisXa => &MAXIS_XA_INFO,
+            Codec::AdpcmImaWestwood => &ADPCM_IMA_WESTWOOD_INFO,
+            Codec::WestwoodSnd1 => &WESTWOOD_SND1_INFO,
+            Codec::AdpcmImaDk3 => &ADPCM_IMA_DK3_INFO,
+            Codec::AdpcmImaDk4 => &ADPCM_IMA_DK4_INFO,
+            Codec::Sdx2 => &SDX2_INFO,
+            Codec::Svx8Fibonacci => &SVX8_FIBONACCI_INFO,
+            Codec::Svx8Exponential => &SVX8_EXPONENTIAL_INFO,
+            Codec::Adx => &ADX_INFO,
+            Codec::Afc => &AFC_INFO,
+            Codec::ProcyonAdpcm => &PROCYON_ADPCM_INFO,
+            Codec::AdpcmImaDat4 => &ADPCM_IMA_DAT4_INFO,
+            Codec::AdpcmImaWwise => &ADPCM_IMA_WWISE_INFO,
+            Codec::Fadpcm => &FADPCM_INFO,
+            Codec::ArgoAdpcm => &ARGO_ADPCM_INFO,
+            Codec::AdpcmImaDvi4 => &ADPCM_IMA_DVI4_INFO,
+            Codec::DeltaModulation => &DELTA_MODULATION_INFO,
+            Codec::Nicam => &NICAM_INFO,
+            Codec::DatLp => &DAT_LP_INFO,
+            Codec::AdpcmImaVbx => &ADPCM_IMA_VBX_INFO,
+            Codec::InterplayDpcm => &INTERPLAY_DPCM_INFO,
+            Codec::InterplayAcm => &INTERPLAY_ACM_INFO,
+            Codec::Vima => &VIMA_INFO,
+            Codec::XaAdpcm => &XA_ADPCM_INFO,
+            Codec::Dpcm => &DPCM_INFO,
+        }
+    }
+}
+
+static ALAW_INFO: CodecInfo = CodecInfo {
+    name: "G.711 A-law",
+    bits_per_sample: 8,
+    compression_ratio: 2.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: 0,
+    can_encode: true,
+};
+
+static ULAW_INFO: CodecInfo = CodecInfo {
+    name: "G.711 μ-law",
+    bits_per_sample: 8,
+    compression_ratio: 2.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: 0,
+    can_encode: true,
+};
+
+static ULAW7_INFO: CodecInfo = CodecInfo {
+    name: "D1 channel bank 7-bit μ-law",
+    bits_per_sample: 7,
+    compression_ratio: 16.0 / 7.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: 0,
+    can_encode: true,
+};
+
+static ADPCM_IMA_INFO: CodecInfo = CodecInfo {
+    name: "IMA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<AdpcmImaState>(),
+    can_encode: true,
+};
+
+static ADPCM_IMA_IMA4_INFO: CodecInfo = CodecInfo {
+    name: "AIFF-C / QT ima4 IMA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 128.0 / 34.0,
+    is_block_based: true,
+    block_size: Some(34),
+    samples_per_block: Some(64),
+    max_channels: None,
+    state_size: size_of::<AdpcmImaState>(),
+    can_encode: true,
+};
+
+// the usual block size is 1024 bytes for 1 channel audio,
+// decode_adpcm_ima_ms_multichannel() decodes any number of channels
+static ADPCM_IMA_MS_INFO: CodecInfo = CodecInfo {
+    name: "WAV / MS IMA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 2041.0 * 2.0 / 1024.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: 0,
+    can_encode: true,
+};
+
+// the usual block size is 1024 bytes for 1 channel audio
+static ADPCM_IMA_MS2_INFO: CodecInfo = CodecInfo {
+    name: "WAV / MS IMA ADPCM, 2 bits per sample",
+    bits_per_sample: 2,
+    compression_ratio: 4081.0 * 2.0 / 1024.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: true,
+};
+
+// the usual block size is 1024 bytes for 1 channel audio
+static ADPCM_IMA_MS3_INFO: CodecInfo = CodecInfo {
+    name: "WAV / MS IMA ADPCM, 3 bits per sample",
+    bits_per_sample: 3,
+    compression_ratio: 2721.0 * 2.0 / 1024.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: true,
+};
+
+// the usual block size is 1024 bytes for 1 channel audio
+static ADPCM_IMA_MS5_INFO: CodecInfo = CodecInfo {
+    name: "WAV / MS IMA ADPCM, 5 bits per sample",
+    bits_per_sample: 5,
+    compression_ratio: 1633.0 * 2.0 / 1024.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: true,
+};
+
+static ADPCM_IMA_XBOX_INFO: CodecInfo = CodecInfo {
+    name: "Xbox IMA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 128.0 / 36.0,
+    is_block_based: true,
+    block_size: Some(crate::XBOX_ADPCM_BLOCK_SIZE),
+    samples_per_block: Some(crate::XBOX_ADPCM_SAMPLES_PER_BLOCK),
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: false,
+};
+
+// the usual block size is 256 bytes for 1 channel audio at 11025 Hz
+static ADPCM_MS_INFO: CodecInfo = CodecInfo {
+    name: "WAV / MS ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 500.0 * 2.0 / 256.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: true,
+};
+
+// ISS block sizes vary, the ratio is calculated for 1024 byte blocks
+static ADPCM_IMA_ISS_INFO: CodecInfo = CodecInfo {
+    name: "Funcom ISS IMA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 2040.0 * 2.0 / 1024.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: false,
+};
+
+static ADPCM_IMA_APM_INFO: CodecInfo = CodecInfo {
+    name: "Ubisoft APM IMA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: size_of::<AdpcmImaState>(),
+    can_encode: false,
+};
+
+static ADPCM_YAMAHA_INFO: CodecInfo = CodecInfo {
+    name: "Yamaha ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<crate::AdpcmYamahaState>(),
+    can_encode: true,
+};
+
+// OKI ADPCM has 12-bit samples, but the ratio is compared to 16-bit PCM like other codecs
+static OKI_ADPCM_INFO: CodecInfo = CodecInfo {
+    name: "OKI ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<crate::OkiAdpcmState>(),
+    can_encode: false,
+};
+
+// values are for the default rate of 32 kbit/s
+static G726_INFO: CodecInfo = CodecInfo {
+    name: "G.726",
+    bits_per_sample: 4,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<crate::G726State>(),
+    can_encode: true,
+};
+
+static G722_INFO: CodecInfo = CodecInfo {
+    name: "G.722",
+    bits_per_sample: 4,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(1),
+    state_size: size_of::<crate::G722State>(),
+    can_encode: true,
+};
+
+// 160 samples are coded to 264 bits, so bits per sample is rounded up from 1.65
+static GSM_INFO: CodecInfo = CodecInfo {
+    name: "GSM 06.10",
+    bits_per_sample: 2,
+    compression_ratio: 160.0 * 2.0 / 33.0,
+    is_block_based: true,
+    block_size: Some(crate::GSM_FRAME_SIZE),
+    samples_per_block: Some(crate::GSM_SAMPLES_PER_FRAME),
+    max_channels: Some(1),
+    state_size: size_of::<crate::GsmState>(),
+    can_encode: true,
+};
+
+static CVSD_INFO: CodecInfo = CodecInfo {
+    name: "CVSD",
+    bits_per_sample: 1,
+    compression_ratio: 16.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<crate::CvsdState>(),
+    can_encode: true,
+};
+
+static HC55516_INFO: CodecInfo = CodecInfo {
+    name: "HC-55516 CVSD",
+    bits_per_sample: 1,
+    compression_ratio: 16.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<crate::Hc55516State>(),
+    can_encode: false,
+};
+
+// the frame size depends on the frame header, values are for the A2DP high quality settings:
+// joint stereo, 44.1 kHz, 8 subbands, 16 blocks and bitpool 53 (952 bits for 256 samples)
+static SBC_INFO: CodecInfo = CodecInfo {
+    name: "SBC",
+    bits_per_sample: 4,
+    compression_ratio: 256.0 * 16.0 / 952.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: size_of::<crate::SbcState>(),
+    can_encode: true,
+};
+
+// values are for 4-bit codes, blocks are bit-packed so they aren't counted as blocks here
+static ADPCM_SWF_INFO: CodecInfo = CodecInfo {
+    name: "SWF ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: size_of::<crate::AdpcmImaState>(),
+    can_encode: true,
+};
+
+// codes 8-bit samples, so the ratio is compared to 8-bit PCM
+static CREATIVE_ADPCM4_INFO: CodecInfo = CodecInfo {
+    name: "Creative ADPCM 4-bit",
+    bits_per_sample: 4,
+    compression_ratio: 2.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(1),
+    state_size: size_of::<crate::CreativeAdpcmState>(),
+    can_encode: true,
+};
+
+// 3 samples per byte, so bits per sample is rounded up from 2.67
+static CREATIVE_ADPCM3_INFO: CodecInfo = CodecInfo {
+    name: "Creative ADPCM 2.6-bit",
+    bits_per_sample: 3,
+    compression_ratio: 3.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(1),
+    state_size: size_of::<crate::CreativeAdpcmState>(),
+    can_encode: true,
+};
+
+static CREATIVE_ADPCM2_INFO: CodecInfo = CodecInfo {
+    name: "Creative ADPCM 2-bit",
+    bits_per_sample: 2,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(1),
+    state_size: size_of::<crate::CreativeAdpcmState>(),
+    can_encode: true,
+};
+
+// codes 8-bit samples like Creative ADPCM, 3 samples per byte
+static MACE3_INFO: CodecInfo = CodecInfo {
+    name: "MACE 3:1",
+    bits_per_sample: 3,
+    compression_ratio: 3.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<crate::MaceState>(),
+    can_encode: true,
+};
+
+// 6 samples per byte, so bits per sample is rounded up from 1.33
+static MACE6_INFO: CodecInfo = CodecInfo {
+    name: "MACE 6:1",
+    bits_per_sample: 2,
+    compression_ratio: 6.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<crate::MaceState>(),
+    can_encode: true,
+};
+
+static BRR_INFO: CodecInfo = CodecInfo {
+    name: "SNES BRR",
+    bits_per_sample: 4,
+    compression_ratio: 32.0 / 9.0,
+    is_block_based: true,
+    block_size: Some(9),
+    samples_per_block: Some(16),
+    max_channels: Some(1),
+    state_size: size_of::<crate::BrrState>(),
+    can_encode: true,
+};
+
+static DSP_ADPCM_INFO: CodecInfo = CodecInfo {
+    name: "Nintendo DSP-ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 28.0 / 8.0,
+    is_block_based: true,
+    block_size: Some(8),
+    samples_per_block: Some(14),
+    max_channels: None,
+    state_size: size_of::<crate::DspAdpcmState>(),
+    can_encode: true,
+};
+
+// THP audio frame sizes vary, the ratio is calculated for 1 channel frames of 1064 samples
+static THP_AUDIO_INFO: CodecInfo = CodecInfo {
+    name: "THP audio",
+    bits_per_sample: 4,
+    compression_ratio: 1064.0 * 2.0 / (80.0 + 608.0),
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: false,
+};
+
+static SPU_ADPCM_INFO: CodecInfo = CodecInfo {
+    name: "PlayStation SPU ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 56.0 / 16.0,
+    is_block_based: true,
+    block_size: Some(16),
+    samples_per_block: Some(28),
+    max_channels: Some(1),
+    state_size: size_of::<crate::SpuAdpcmState>(),
+    can_encode: true,
+};
+
+static EA_XAS_INFO: CodecInfo = CodecInfo {
+    name: "EA XAS ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 256.0 / 76.0,
+    is_block_based: true,
+    block_size: Some(76),
+    samples_per_block: Some(128),
+    max_channels: None,
+    state_size: 0,
+    can_encode: false,
+};
+
+static MAXIS_XA_INFO: CodecInfo = CodecInfo {
+    name: "Maxis XA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 56.0 / 15.0,
+    is_block_based: true,
+    block_size: Some(15),
+    samples_per_block: Some(28),
+    max_channels: None,
+    state_size: size_of::<crate::MaxisXaState>(),
+    can_encode: false,
+};
+
+static ADPCM_IMA_WESTWOOD_INFO: CodecInfo = CodecInfo {
+    name: "Westwood IMA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: size_of::<AdpcmImaState>(),
+    can_encode: false,
+};
+
+static WESTWOOD_SND1_INFO: CodecInfo = CodecInfo {
+    name: "Westwood SND1",
+    bits_per_sample: 8,
+    compression_ratio: 128.0 / 65.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(1),
+    state_size: 0,
+    can_encode: false,
+};
+
+// DK3 block sizes vary, the ratio is calculated for 1024 byte blocks
+static ADPCM_IMA_DK3_INFO: CodecInfo = CodecInfo {
+    name: "Duck DK3 IMA ADPCM",
+    bits_per_sample: 3,
+    compression_ratio: 2688.0 * 2.0 / 1024.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: false,
+};
+
+// DK4 block sizes vary, the ratio is calculated for 1024 byte blocks
+static ADPCM_IMA_DK4_INFO: CodecInfo = CodecInfo {
+    name: "Duck DK4 IMA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 2041.0 * 2.0 / 1024.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: false,
+};
+
+static SDX2_INFO: CodecInfo = CodecInfo {
+    name: "3DO SDX2",
+    bits_per_sample: 8,
+    compression_ratio: 2.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<i16>(),
+    can_encode: false,
+};
+
+static SVX8_FIBONACCI_INFO: CodecInfo = CodecInfo {
+    name: "8SVX Fibonacci-delta",
+    bits_per_sample: 4,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(1),
+    state_size: 0,
+    can_encode: false,
+};
+
+static SVX8_EXPONENTIAL_INFO: CodecInfo = CodecInfo {
+    name: "8SVX Exponential-delta",
+    bits_per_sample: 4,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(1),
+    state_size: 0,
+    can_encode: false,
+};
+
+static ADX_INFO: CodecInfo = CodecInfo {
+    name: "CRI ADX ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 64.0 / 18.0,
+    is_block_based: true,
+    block_size: Some(18),
+    samples_per_block: Some(32),
+    max_channels: None,
+    state_size: size_of::<crate::AdxState>(),
+    can_encode: true,
+};
+
+static AFC_INFO: CodecInfo = CodecInfo {
+    name: "Nintendo AFC ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 32.0 / 9.0,
+    is_block_based: true,
+    block_size: Some(9),
+    samples_per_block: Some(16),
+    max_channels: None,
+    state_size: size_of::<crate::AfcState>(),
+    can_encode: false,
+};
+
+static PROCYON_ADPCM_INFO: CodecInfo = CodecInfo {
+    name: "Procyon Studio ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 60.0 / 16.0,
+    is_block_based: true,
+    block_size: Some(16),
+    samples_per_block: Some(30),
+    max_channels: Some(1),
+    state_size: size_of::<crate::ProcyonAdpcmState>(),
+    can_encode: false,
+};
+
+// DAT4 block sizes vary, the ratio is calculated for 1024 byte blocks
+static ADPCM_IMA_DAT4_INFO: CodecInfo = CodecInfo {
+    name: "Eurocom DAT4 IMA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 2040.0 * 2.0 / 1024.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: false,
+};
+
+// Wwise block sizes vary, the ratio is calculated for 1024 byte blocks
+static ADPCM_IMA_WWISE_INFO: CodecInfo = CodecInfo {
+    name: "Wwise IMA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 2040.0 * 2.0 / 1024.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: false,
+};
+
+static FADPCM_INFO: CodecInfo = CodecInfo {
+    name: "FMOD FADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 512.0 / 140.0,
+    is_block_based: true,
+    block_size: Some(140),
+    samples_per_block: Some(256),
+    max_channels: None,
+    state_size: 0,
+    can_encode: false,
+};
+
+static ARGO_ADPCM_INFO: CodecInfo = CodecInfo {
+    name: "Argonaut ASF ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 64.0 / 17.0,
+    is_block_based: true,
+    block_size: Some(17),
+    samples_per_block: Some(32),
+    max_channels: None,
+    state_size: size_of::<crate::ArgoAdpcmState>(),
+    can_encode: false,
+};
+
+// DVI4 payload sizes vary, the ratio is calculated for 20 ms payloads at 8000 Hz
+static ADPCM_IMA_DVI4_INFO: CodecInfo = CodecInfo {
+    name: "DVI4 IMA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 160.0 * 2.0 / 84.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(1),
+    state_size: 0,
+    can_encode: true,
+};
+
+static DELTA_MODULATION_INFO: CodecInfo = CodecInfo {
+    name: "Linear delta modulation",
+    bits_per_sample: 1,
+    compression_ratio: 16.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<crate::DeltaModulationState>(),
+    can_encode: true,
+};
+
+// NICAM blocks aren't byte aligned, the ratio includes the 3-bit scale of each block
+static NICAM_INFO: CodecInfo = CodecInfo {
+    name: "NICAM companding",
+    bits_per_sample: 10,
+    compression_ratio: 16.0 * 32.0 / (10.0 * 32.0 + 3.0),
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: 0,
+    can_encode: true,
+};
+
+static DAT_LP_INFO: CodecInfo = CodecInfo {
+    name: "DAT LP 12-bit non-linear",
+    bits_per_sample: 12,
+    compression_ratio: 16.0 / 12.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: 0,
+    can_encode: true,
+};
+
+static ADPCM_IMA_VBX_INFO: CodecInfo = CodecInfo {
+    name: "NMS VBXADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 4.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<crate::AdpcmImaState>(),
+    can_encode: true,
+};
+
+static INTERPLAY_DPCM_INFO: CodecInfo = CodecInfo {
+    name: "Interplay DPCM",
+    bits_per_sample: 8,
+    compression_ratio: 2.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: false,
+};
+
+// values are packed with up to 16 bits, so the worst case is the same as 16-bit PCM
+static INTERPLAY_ACM_INFO: CodecInfo = CodecInfo {
+    name: "Interplay ACM",
+    bits_per_sample: 16,
+    compression_ratio: 1.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: 0,
+    can_encode: false,
+};
+
+static VIMA_INFO: CodecInfo = CodecInfo {
+    name: "LucasArts VIMA",
+    bits_per_sample: 7,
+    compression_ratio: 16.0 / 7.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: 0,
+    can_encode: false,
+};
+
+// the ratio is calculated for 4-bit sound groups
+static XA_ADPCM_INFO: CodecInfo = CodecInfo {
+    name: "CD-XA ADPCM",
+    bits_per_sample: 4,
+    compression_ratio: 224.0 * 2.0 / 128.0,
+    is_block_based: true,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: Some(2),
+    state_size: size_of::<crate::XaAdpcmState>(),
+    can_encode: false,
+};
+
+// the code width depends on the delta table, values are for 8-bit codes
+static DPCM_INFO: CodecInfo = CodecInfo {
+    name: "Generic DPCM",
+    bits_per_sample: 8,
+    compression_ratio: 2.0,
+    is_block_based: false,
+    block_size: None,
+    samples_per_block: None,
+    max_channels: None,
+    state_size: size_of::<i16>(),
+    can_encode: true,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec_info() {
+        for codec in Codec::ALL {
+            let info = codec.info();
+            assert!(!info.name.is_empty());
+            assert!(info.bits_per_sample > 0 && info.bits_per_sample <= 16);
+            assert!(info.compression_ratio >= 1.0);
+            assert!(info.is_block_based || info.block_size.is_none());
+            assert_eq!(info.block_size.is_some(), info.samples_per_block.is_some());
+        }
+        let info = Codec::AdpcmImaIma4.info();
+        assert_eq!(info.block_size, Some(34));
+        assert_eq!(info.samples_per_block, Some(64));
+        assert_eq!(info.state_size, 4);
+        assert!(info.can_encode);
+        assert_eq!(Codec::AdpcmImaMs.info().max_channels, None);
+        assert!(!Codec::Vima.info().can_encode);
+        assert_eq!(Codec::AdpcmImaMs3.info().compression_ratio,
+            crate::adpcm_ima_ms_bits_samples_per_block(1024, false, crate::AdpcmImaBits::Bits3)
+            as f64 * 2.0 / 1024.0);
+    }
+
+    // every decoder exported from lib.rs and its codecs
+    const DECODERS: &[(&str, &[Codec])] = &[
+        ("decode_alaw", &[ Codec::Alaw ]),
+        ("decode_ulaw", &[ Codec::Ulaw ]),
+        ("decode_companded_8bit", &[ Codec::Alaw, Codec::Ulaw ]),
+        ("decode_ulaw7", &[ Codec::Ulaw7 ]),
+        ("decode_dat_lp", &[ Codec::DatLp ]),
+        ("decode_dat_lp_slice", &[ Codec::DatLp ]),
+        ("decode_adpcm_ima_vbx", &[ Codec::AdpcmImaVbx ]),
+        ("decode_adpcm_ima_vbx_slice", &[ Codec::AdpcmImaVbx ]),
+        ("decode_adpcm_ima", &[ Codec::AdpcmIma ]),
+        ("decode_adpcm_ima_bits", &[ Codec::AdpcmIma ]),
+        ("decode_adpcm_ima_adaptive_block", &[ Codec::AdpcmIma ]),
+        ("decode_adpcm_ima_traced", &[ Codec::AdpcmIma ]),
+        ("decode_adpcm_ima_symmetric", &[ Codec::AdpcmIma ]),
+        ("decode_adpcm_ima_ima4", &[ Codec::AdpcmImaIma4 ]),
+        ("decode_adpcm_ima_ima4_slice", &[ Codec::AdpcmImaIma4 ]),
+        ("decode_adpcm_ima_ima4_with", &[ Codec::AdpcmImaIma4 ]),
+        ("decode_adpcm_ima_ima4_stereo", &[ Codec::AdpcmImaIma4 ]),
+        ("decode_adpcm_ima_ima4_symmetric", &[ Codec::AdpcmImaIma4 ]),
+        ("decode_adpcm_ima_ima4_8bit", &[ Codec::AdpcmImaIma4 ]),
+        ("decode_adpcm_ima_ima4_smoothed", &[ Codec::AdpcmImaIma4 ]),
+        ("decode_adpcm_ima_ima4_heapless", &[ Codec::AdpcmImaIma4 ]),
+        ("decode_adpcm_ima_ms", &[ Codec::AdpcmImaMs ]),
+        ("decode_adpcm_ima_ms_with", &[ Codec::AdpcmImaMs ]),
+        ("decode_adpcm_ima_ms_multichannel", &[ Codec::AdpcmImaMs ]),
+        ("decode_adpcm_ima_ms_planar", &[ Codec::AdpcmImaMs ]),
+        ("decode_adpcm_ima_ms_symmetric", &[ Codec::AdpcmImaMs ]),
+        ("decode_adpcm_ima_ms_8bit", &[ Codec::AdpcmImaMs ]),
+        ("decode_adpcm_ima_ms_lenient", &[ Codec::AdpcmImaMs ]),
+        ("decode_adpcm_ima_ms_smoothed", &[ Codec::AdpcmImaMs ]),
+        ("decode_adpcm_ima_ms_heapless", &[ Codec::AdpcmImaMs ]),
+        ("decode_adpcm_ima_ms_bits", &[ Codec::AdpcmImaMs2, Codec::AdpcmImaMs3,
+            Codec::AdpcmImaMs5 ]),
+        ("decode_adpcm_ima_xbox", &[ Codec::AdpcmImaXbox ]),
+        ("decode_adpcm_ms", &[ Codec::AdpcmMs ]),
+        ("decode_adpcm_ms_with_coefficients", &[ Codec::AdpcmMs ]),
+        ("decode_adpcm_ima_iss", &[ Codec::AdpcmImaIss ]),
+        ("decode_adpcm_ima_apm", &[ Codec::AdpcmImaApm ]),
+        ("decode_adpcm_yamaha", &[ Codec::AdpcmYamaha ]),
+        ("decode_adpcm_yamaha_slice", &[ Codec::AdpcmYamaha ]),
+        ("decode_oki_adpcm", &[ Codec::OkiAdpcm ]),
+        ("decode_oki_adpcm_slice", &[ Codec::OkiAdpcm ]),
+        ("decode_g726", &[ Codec::G726 ]),
+        ("decode_g726_to_alaw", &[ Codec::G726 ]),
+        ("decode_g726_to_ulaw", &[ Codec::G726 ]),
+        ("decode_g722", &[ Codec::G722 ]),
+        ("decode_g722_slice", &[ Codec::G722 ]),
+        ("decode_gsm_frame", &[ Codec::Gsm ]),
+        ("decode_cvsd", &[ Codec::Cvsd ]),
+        ("decode_cvsd_slice", &[ Codec::Cvsd ]),
+        ("decode_hc55516", &[ Codec::Hc55516 ]),
+        ("decode_hc55516_slice", &[ Codec::Hc55516 ]),
+        ("decode_sbc_frame", &[ Codec::Sbc ]),
+        ("decode_sbc_frame_samples", &[ Codec::Sbc ]),
+        ("decode_adpcm_swf", &[ Codec::AdpcmSwf ]),
+        ("decode_creative_adpcm4", &[ Codec::CreativeAdpcm4 ]),
+        ("decode_creative_adpcm4_slice", &[ Codec::CreativeAdpcm4 ]),
+        ("decode_creative_adpcm3", &[ Codec::CreativeAdpcm3 ]),
+        ("decode_creative_adpcm3_slice", &[ Codec::CreativeAdpcm3 ]),
+        ("decode_creative_adpcm2", &[ Codec::CreativeAdpcm2 ]),
+        ("decode_creative_adpcm2_slice", &[ Codec::CreativeAdpcm2 ]),
+        ("decode_voc_sound_data", &[ Codec::CreativeAdpcm4, Codec::CreativeAdpcm3,
+            Codec::CreativeAdpcm2 ]),
+        ("decode_mace3", &[ Codec::Mace3 ]),
+        ("decode_mace6", &[ Codec::Mace6 ]),
+        ("decode_brr", &[ Codec::Brr ]),
+        ("decode_brr_block", &[ Codec::Brr ]),
+        ("decode_dsp_adpcm", &[ Codec::DspAdpcm ]),
+        ("decode_dsp_adpcm_frame", &[ Codec::DspAdpcm ]),
+        ("decode_thp_audio_frame", &[ Codec::ThpAudio ]),
+        ("decode_spu_adpcm", &[ Codec::SpuAdpcm ]),
+        ("decode_spu_adpcm_block", &[ Codec::SpuAdpcm ]),
+        ("decode_ea_xas", &[ Codec::EaXas ]),
+        ("decode_ea_xas_block", &[ Codec::EaXas ]),
+        ("decode_maxis_xa", &[ Codec::MaxisXa ]),
+        ("decode_maxis_xa_block", &[ Codec::MaxisXa ]),
+        ("decode_westwood_ima", &[ Codec::AdpcmImaWestwood ]),
+        ("decode_westwood_snd1", &[ Codec::WestwoodSnd1 ]),
+        ("decode_adpcm_ima_dk3", &[ Codec::AdpcmImaDk3 ]),
+        ("decode_adpcm_ima_dk4", &[ Codec::AdpcmImaDk4 ]),
+        ("decode_sdx2", &[ Codec::Sdx2 ]),
+        ("decode_sdx2_slice", &[ Codec::Sdx2 ]),
+        ("decode_svx8_fibonacci", &[ Codec::Svx8Fibonacci ]),
+        ("decode_svx8_exponential", &[ Codec::Svx8Exponential ]),
+        ("decode_adx", &[ Codec::Adx ]),
+        ("decode_adx_frame", &[ Codec::Adx ]),
+        ("decode_afc", &[ Codec::Afc ]),
+        ("decode_afc_frame", &[ Codec::Afc ]),
+        ("decode_procyon_adpcm", &[ Codec::ProcyonAdpcm ]),
+        ("decode_procyon_adpcm_frame", &[ Codec::ProcyonAdpcm ]),
+        ("decode_adpcm_ima_dat4", &[ Codec::AdpcmImaDat4 ]),
+        ("decode_adpcm_ima_wwise", &[ Codec::AdpcmImaWwise ]),
+        ("decode_fadpcm", &[ Codec::Fadpcm ]),
+        ("decode_fadpcm_frame", &[ Codec::Fadpcm ]),
+        ("decode_argo_adpcm", &[ Codec::ArgoAdpcm ]),
+        ("decode_argo_adpcm_block", &[ Codec::ArgoAdpcm ]),
+        ("decode_adpcm_ima_dvi4", &[ Codec::AdpcmImaDvi4 ]),
+        ("decode_delta_modulation", &[ Codec::DeltaModulation ]),
+        ("decode_delta_modulation_slice", &[ Codec::DeltaModulation ]),
+        ("decode_nicam", &[ Codec::Nicam ]),
+        ("decode_nicam_block", &[ Codec::Nicam ]),
+        ("decode_interplay_dpcm", &[ Codec::InterplayDpcm ]),
+        ("decode_interplay_dpcm_frame", &[ Codec::InterplayDpcm ]),
+        ("decode_interplay_acm", &[ Codec::InterplayAcm ]),
+        ("decode_vima", &[ Codec::Vima ]),
+        ("decode_xa_adpcm_sound_group", &[ Codec::XaAdpcm ]),
+        ("decode_xa_sector", &[ Codec::XaAdpcm ]),
+        ("DpcmCodec", &[ Codec::Dpcm ]),
+    ];
+
+    // returns true if `name` is exported with `pub use` in lib.rs
+    fn is_exported(lib: &str, name: &str) -> bool {
+        exported_names(lib).any(|n| n == name)
+    }
+
+    // returns the names exported with `pub use` in lib.rs, including continuation lines
+    fn exported_names(lib: &str) -> impl Iterator<Item = &str> {
+        let mut in_use = false;
+        lib.lines().filter(move |line| {
+            if line.starts_with("pub use ") {
+                in_use = true;
+            } else if !line.starts_with("    ") {
+                in_use = false;
+            }
+            in_use
+        }).flat_map(|line| line.trim_start_matches("pub use ")
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '_'))
+            .filter(|n| !n.is_empty())
+    }
+
+    #[test]
+    fn test_every_decoder_has_codec() {
+        let lib = include_str!("lib.rs");
+        // every exported decoder must be listed in DECODERS
+        for name in exported_names(lib).filter(|n| n.starts_with("decode_")) {
+            assert!(DECODERS.iter().any(|(decoder, _)| *decoder == name),
+                "{} has no Codec entry", name);
+        }
+        // every listed decoder must be exported and have a registered codec
+        for (decoder, codecs) in DECODERS {
+            assert!(is_exported(lib, decoder), "{} isn't exported", decoder);
+            for codec in *codecs {
+                assert!(Codec::ALL.contains(codec), "{:?} isn't in Codec::ALL", codec);
+            }
+        }
+        // every codec must have a decoder
+        for codec in Codec::ALL {
+            assert!(DECODERS.iter().any(|(_, codecs)| codecs.contains(codec)),
+                "{:?} has no decoder", codec);
+        }
+    }
+}
@@ -0,0 +1,430 @@
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::Error;
+use crate::bitreader::LsbBitReader;
+
+// Interplay ACM is used in Fallout 1 and 2 and other Interplay games.
+// see https://wiki.multimedia.cx/index.php/Interplay_ACM
+
+const ACM_ID: &[u8; 4] = &[ 0x97, 0x28, 0x03, 0x01 ];
+
+/// Size of the Interplay ACM file header in bytes.
+pub const INTERPLAY_ACM_HEADER_SIZE: usize = 14;
+
+const MAP_1BIT: &[i32; 2] = &[ -1, 1 ];
+const MAP_2BIT_NEAR: &[i32; 4] = &[ -2, -1, 1, 2 ];
+const MAP_2BIT_FAR: &[i32; 4] = &[ -3, -2, 2, 3 ];
+const MAP_3BIT: &[i32; 8] = &[ -4, -3, -2, -1, 1, 2, 3, 4 ];
+
+/// Header of an Interplay ACM file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterplayAcmHeader {
+    /// Total number of samples for all channels, or 0 if unknown.
+    pub sample_count: u32,
+    /// Number of channels.
+    pub channels: u16,
+    /// Sample rate.
+    pub sample_rate: u16,
+    /// Number of transform levels (0..=15). A block has `2^level` columns.
+    pub level: u8,
+    /// Number of rows in a block.
+    pub rows: u16,
+}
+
+impl InterplayAcmHeader {
+    /// Returns the number of samples in a block (for all channels).
+    pub fn block_len(&self) -> usize {
+        usize::from(self.rows) << self.level
+    }
+}
+
+/// Parses the header of an Interplay ACM file.
+///
+/// The header is [`INTERPLAY_ACM_HEADER_SIZE`] bytes long. The compressed data follows it.
+///
+/// An error is returned if `buf` is too short, if it doesn't start with the ACM identifier,
+/// if the channel count is zero or if the row count is zero.
+pub fn parse_interplay_acm_header(buf: &[u8]) -> Result<InterplayAcmHeader, Error> {
+    if buf.len() < INTERPLAY_ACM_HEADER_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    if &buf[0..4] != ACM_ID {
+        return Err(Error::InvalidData);
+    }
+    let channels = u16::from_le_bytes([ buf[8], buf[9] ]);
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    // the level and rows are the first 4 and 12 bits of the bitstream
+    let level_rows = u16::from_le_bytes([ buf[12], buf[13] ]);
+    let rows = level_rows >> 4;
+    if rows == 0 {
+        return Err(Error::InvalidData);
+    }
+    Ok(InterplayAcmHeader {
+        sample_count: u32::from_le_bytes([ buf[4], buf[5], buf[6], buf[7] ]),
+        channels,
+        sample_rate: u16::from_le_bytes([ buf[10], buf[11] ]),
+        level: (level_rows & 0x0f).to_le_bytes()[0],
+        rows,
+    })
+}
+
+struct AcmDecoder {
+    level: u8,
+    rows: usize,
+    cols: usize,
+    block: Vec<i32>,
+    // overlapping values of the previous block for the inverse transform
+    wrap: Vec<i32>,
+    // amplitude table for indexes -0x8000..0x8000, values of previous blocks are kept
+    amplitudes: Vec<i32>,
+}
+
+impl AcmDecoder {
+    fn new(header: &InterplayAcmHeader) -> AcmDecoder {
+        let cols = 1usize << header.level;
+        AcmDecoder {
+            level: header.level,
+            rows: usize::from(header.rows),
+            cols,
+            block: vec![0; header.block_len()],
+            wrap: vec![0; 2 * cols - 2],
+            amplitudes: vec![0; 0x10000],
+        }
+    }
+
+    fn set(&mut self, row: usize, col: usize, index: i32) {
+        let amplitude_index = usize::try_from(index + 0x8000).unwrap_or(0x8000);
+        self.block[(row << self.level) + col] = self.amplitudes[amplitude_index];
+    }
+
+    fn decode_block(&mut self, reader: &mut LsbBitReader) -> Result<(), Error> {
+        let power = bits(reader, 4);
+        let value = i32::try_from(bits(reader, 16)).unwrap_or(0);
+        let count = 1usize << power;
+        let mut x = 0i32;
+        for a in &mut self.amplitudes[0x8000..0x8000+count] {
+            *a = x;
+            x = x.wrapping_add(value);
+        }
+        let mut x = 0i32;
+        for a in self.amplitudes[0x8000-count..0x8000].iter_mut().rev() {
+            x = x.wrapping_sub(value);
+            *a = x;
+        }
+        for col in 0..self.cols {
+            let method = bits(reader, 5);
+            self.fill_column(reader, method, col)?;
+        }
+        self.juggle_block();
+        Ok(())
+    }
+
+    // unpacks a column of values, `method` selects how the values are packed
+    fn fill_column(&mut self, reader: &mut LsbBitReader, method: u32, col: usize)
+        -> Result<(), Error> {
+
+        let rows = self.rows;
+        let mut row = 0;
+        while row < rows {
+            // the match guards read bits, so the order of the arms matters
+            match method {
+                0 => {
+                    self.set(row, col, 0);
+                },
+                3..=16 => {
+                    let middle = 1i32 << (method - 1);
+                    let b = i32::try_from(bits(reader, method)).unwrap_or(0);
+                    self.set(row, col, b - middle);
+                },
+                // methods 17, 20, 23 and 26 pack two zeros in one bit
+                17 | 20 | 23 | 26 if bits(reader, 1) == 0 => {
+                    self.set(row, col, 0);
+                    if row + 1 < rows {
+                        self.set(row + 1, col, 0);
+                    }
+                    row += 1;
+                },
+                17 | 18 if bits(reader, 1) == 0 => self.set(row, col, 0),
+                17 | 18 => self.set(row, col, MAP_1BIT[usize::from(bits(reader, 1) != 0)]),
+                20 | 21 if bits(reader, 1) == 0 => self.set(row, col, 0),
+                20 | 21 => self.set(row, col, MAP_2BIT_NEAR[bit_index(bits(reader, 2))]),
+                23 | 24 if bits(reader, 1) == 0 => self.set(row, col, 0),
+                23 | 24 if bits(reader, 1) == 0 => {
+                    self.set(row, col, MAP_1BIT[usize::from(bits(reader, 1) != 0)]);
+                },
+                23 | 24 => self.set(row, col, MAP_2BIT_FAR[bit_index(bits(reader, 2))]),
+                26 | 27 if bits(reader, 1) == 0 => self.set(row, col, 0),
+                26 | 27 => self.set(row, col, MAP_3BIT[bit_index(bits(reader, 3))]),
+                // three values in 5 bits: b = x1 + 3 * x2 + 9 * x3
+                19 => {
+                    let b = bits(reader, 5);
+                    if b > 26 {
+                        return Err(Error::InvalidData);
+                    }
+                    row = self.set_packed(row, col, b, 3, 3, 1);
+                },
+                // three values in 7 bits: b = x1 + 5 * x2 + 25 * x3
+                22 => {
+                    let b = bits(reader, 7);
+                    if b > 124 {
+                        return Err(Error::InvalidData);
+                    }
+                    row = self.set_packed(row, col, b, 5, 3, 2);
+                },
+                // two values in 7 bits: b = x1 + 11 * x2
+                29 => {
+                    let b = bits(reader, 7);
+                    if b > 120 {
+                        return Err(Error::InvalidData);
+                    }
+                    row = self.set_packed(row, col, b, 11, 2, 5);
+                },
+                _ => return Err(Error::InvalidData),
+            }
+            row += 1;
+        }
+        Ok(())
+    }
+
+    // sets `count` values packed as digits of `b` in base `base`, returns the last set row
+    fn set_packed(&mut self, row: usize, col: usize, b: u32, base: u32, count: usize,
+        offset: i32) -> usize {
+
+        let mut b = b;
+        let mut row = row;
+        for i in 0..count {
+            let digit = i32::try_from(b % base).unwrap_or(0);
+            self.set(row, col, digit - offset);
+            b /= base;
+            if i + 1 == count || row + 1 >= self.rows {
+                break;
+            }
+            row += 1;
+        }
+        row
+    }
+
+    // inverse transform of the block
+    fn juggle_block(&mut self) {
+        if self.level == 0 {
+            return;
+        }
+        let step_subcount = if self.level > 9 { 1 } else { (2048 >> self.level) - 2 };
+        let mut todo_count = self.rows;
+        let mut offset = 0;
+        loop {
+            let mut wrap_pos = 0;
+            let mut sub_len = self.cols / 2;
+            let mut sub_count = step_subcount.min(todo_count) * 2;
+            juggle(&mut self.wrap[wrap_pos..], &mut self.block[offset..], sub_len, sub_count);
+            wrap_pos += sub_len * 2;
+            for i in 0..sub_count {
+                let v = &mut self.block[offset + i * sub_len];
+                *v = v.wrapping_add(1);
+            }
+            while sub_len > 1 {
+                sub_len /= 2;
+                sub_count *= 2;
+                juggle(&mut self.wrap[wrap_pos..], &mut self.block[offset..], sub_len, sub_count);
+                wrap_pos += sub_len * 2;
+            }
+            if todo_count <= step_subcount {
+                break;
+            }
+            todo_count -= step_subcount;
+            offset += step_subcount << self.level;
+        }
+    }
+}
+
+// reads bits, missing bits at the end of the data are zeros
+fn bits(reader: &mut LsbBitReader, count: u32) -> u32 {
+    reader.read(count).unwrap_or(0)
+}
+
+fn bit_index(value: u32) -> usize {
+    usize::try_from(value).unwrap_or(0)
+}
+
+fn juggle(wrap: &mut [i32], block: &mut [i32], sub_len: usize, sub_count: usize) {
+    for i in 0..sub_len {
+        let mut r0 = wrap[2 * i];
+        let mut r1 = wrap[2 * i + 1];
+        let mut pos = i;
+        for _ in 0..sub_count / 2 {
+            let r2 = block[pos];
+            block[pos] = r1.wrapping_mul(2).wrapping_add(r0.wrapping_add(r2));
+            pos += sub_len;
+            let r3 = block[pos];
+            block[pos] = r2.wrapping_mul(2).wrapping_sub(r1.wrapping_add(r3));
+            pos += sub_len;
+            r0 = r2;
+            r1 = r3;
+        }
+        wrap[2 * i] = r0;
+        wrap[2 * i + 1] = r1;
+    }
+}
+
+/// Decodes an Interplay ACM file to 16-bit signed integer samples.
+///
+/// `buf` should contain the whole file starting with the header. The samples are
+/// interleaved for multiple channels. The returned vector contains the number of samples
+/// specified in the header or less if the data ends early. If the header has zero samples,
+/// all blocks are decoded.
+///
+/// This function is available only with the `alloc` feature.
+///
+/// An error is returned if the header is invalid or if the compressed data is invalid.
+pub fn decode_interplay_acm(buf: &[u8]) -> Result<Vec<i16>, Error> {
+    let header = parse_interplay_acm_header(buf)?;
+    let sample_count = usize::try_from(header.sample_count).unwrap_or(usize::MAX);
+    let mut decoder = AcmDecoder::new(&header);
+    let mut reader = LsbBitReader::new(&buf[INTERPLAY_ACM_HEADER_SIZE..]);
+    let mut out_samples = Vec::new();
+    while reader.bits_left() > 0 && (sample_count == 0 || out_samples.len() < sample_count) {
+        decoder.decode_block(&mut reader)?;
+        let len = if sample_count == 0 {
+            decoder.block.len()
+        } else {
+            decoder.block.len().min(sample_count - out_samples.len())
+        };
+        out_samples.extend(decoder.block[..len].iter().map(|v| {
+            let value = v >> decoder.level;
+            // value is clamped so truncation never happens
+            #[allow(clippy::cast_possible_truncation)]
+            {
+            value.clamp(-32768, 32767) as i16
+            }
+        }));
+    }
+    Ok(out_samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // writes bits least significant bit first
+    struct BitPacker {
+        buf: [u8; 32],
+        bit_pos: usize,
+    }
+
+    impl BitPacker {
+        fn new() -> BitPacker {
+            BitPacker { buf: [0; 32], bit_pos: 0 }
+        }
+
+        fn write(&mut self, value: u32, count: usize) {
+            for i in 0..count {
+                if (value >> i) & 1 != 0 {
+                    self.buf[self.bit_pos / 8] |= 1 << (self.bit_pos % 8);
+                }
+                self.bit_pos += 1;
+            }
+        }
+
+        fn bytes(&self) -> &[u8] {
+            &self.buf[..self.bit_pos.div_ceil(8)]
+        }
+    }
+
+    fn acm_file(sample_count: u32, level: u8, rows: u16, packer: &BitPacker) -> Vec<u8> {
+        let mut file = Vec::new();
+        file.extend_from_slice(ACM_ID);
+        file.extend_from_slice(&sample_count.to_le_bytes());
+        file.extend_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(&22050u16.to_le_bytes());
+        file.extend_from_slice(&(u16::from(level) | rows << 4).to_le_bytes());
+        file.extend_from_slice(packer.bytes());
+        file
+    }
+
+    #[test]
+    fn test_parse_interplay_acm_header() {
+        let file = acm_file(1000, 7, 16, &BitPacker::new());
+        let result = parse_interplay_acm_header(&file);
+        assert!(matches!(result, Ok(h) if h == InterplayAcmHeader {
+            sample_count: 1000, channels: 1, sample_rate: 22050, level: 7, rows: 16 }));
+        let Ok(header) = result else { return };
+        assert_eq!(header.block_len(), 16 * 128);
+
+        assert!(matches!(parse_interplay_acm_header(&file[..13]), Err(Error::InvalidBufferSize)));
+        let mut invalid = file.clone();
+        invalid[0] = 0;
+        assert!(matches!(parse_interplay_acm_header(&invalid), Err(Error::InvalidData)));
+        let mut invalid = file.clone();
+        invalid[8] = 0;
+        assert!(matches!(parse_interplay_acm_header(&invalid), Err(Error::InvalidChannels)));
+        let invalid = acm_file(1000, 7, 0, &BitPacker::new());
+        assert!(matches!(parse_interplay_acm_header(&invalid), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_decode_interplay_acm_level0() {
+        let mut packer = BitPacker::new();
+        // block 1: power 1, value 1000, packed 3 values in 5 bits: -1, 0, 1
+        packer.write(1, 4);
+        packer.write(1000, 16);
+        packer.write(19, 5);
+        packer.write(3 + 9 * 2, 5);
+        // block 2: two zeros in 1 bit, and 1 in 3 bits
+        packer.write(1, 4);
+        packer.write(1000, 16);
+        packer.write(17, 5);
+        packer.write(0, 1);
+        packer.write(0b111, 3);
+        // block 3: linear 4-bit values
+        packer.write(3, 4);
+        packer.write(10, 16);
+        packer.write(4, 5);
+        packer.write(0, 4);
+        packer.write(15, 4);
+        packer.write(8, 4);
+        let file = acm_file(9, 0, 3, &packer);
+        let result = decode_interplay_acm(&file);
+        assert!(matches!(result, Ok(ref s) if s[..] == [ -1000, 0, 1000, 0, 0, 1000,
+            -80, 70, 0 ]));
+
+        // the sample count limits the output
+        let file = acm_file(4, 0, 3, &packer);
+        assert!(matches!(decode_interplay_acm(&file), Ok(s) if s.len() == 4));
+
+        // invalid packing methods
+        let mut packer = BitPacker::new();
+        packer.write(1, 4);
+        packer.write(1000, 16);
+        packer.write(1, 5);
+        let file = acm_file(0, 0, 3, &packer);
+        assert!(matches!(decode_interplay_acm(&file), Err(Error::InvalidData)));
+        let mut packer = BitPacker::new();
+        packer.write(1, 4);
+        packer.write(1000, 16);
+        packer.write(19, 5);
+        packer.write(27, 5);
+        let file = acm_file(0, 0, 3, &packer);
+        assert!(matches!(decode_interplay_acm(&file), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_decode_interplay_acm_level1() {
+        // two blocks with 2 columns: the inverse transform uses values of the previous block
+        let mut packer = BitPacker::new();
+        for _ in 0..2 {
+            packer.write(2, 4);
+            packer.write(50, 16);
+            packer.write(3, 5);
+            packer.write(6, 3);
+            packer.write(3, 5);
+            packer.write(5, 3);
+        }
+        let file = acm_file(0, 1, 1, &packer);
+        let result = decode_interplay_acm(&file);
+        assert!(matches!(result, Ok(ref s) if s[..] == [ 50, 75, 150, 50 ]));
+    }
+}
@@ -0,0 +1,152 @@
+
+#[cfg(feature = "internal-no-panic")]
+use no_panic::no_panic;
+
+use crate::Error;
+
+// delta table from the Interplay MVE format,
+// see https://wiki.multimedia.cx/index.php/Interplay_DPCM
+// (the large deltas wrap around at 16 bits)
+const INTERPLAY_DELTA_TABLE: &[i16; 256] = &[
+    0, 1, 2, 3, 4, 5, 6, 7,
+    8, 9, 10, 11, 12, 13, 14, 15,
+    16, 17, 18, 19, 20, 21, 22, 23,
+    24, 25, 26, 27, 28, 29, 30, 31,
+    32, 33, 34, 35, 36, 37, 38, 39,
+    40, 41, 42, 43, 47, 51, 56, 61,
+    66, 72, 79, 86, 94, 102, 112, 122,
+    133, 145, 158, 173, 189, 206, 225, 245,
+    267, 292, 318, 348, 379, 414, 452, 493,
+    538, 587, 640, 699, 763, 832, 908, 991,
+    1081, 1180, 1288, 1405, 1534, 1673, 1826, 1993,
+    2175, 2373, 2590, 2826, 3084, 3365, 3672, 4008,
+    4373, 4772, 5208, 5683, 6202, 6767, 7385, 8059,
+    8794, 9597, 10472, 11428, 12471, 13609, 14851, 16206,
+    17685, 19298, 21060, 22981, 25078, 27367, 29864, 32589,
+    -29973, -26728, -23186, -19322, -15105, -10503, -5481, -1,
+    1, 1, 5481, 10503, 15105, 19322, 23186, 26728,
+    29973, -32589, -29864, -27367, -25078, -22981, -21060, -19298,
+    -17685, -16206, -14851, -13609, -12471, -11428, -10472, -9597,
+    -8794, -8059, -7385, -6767, -6202, -5683, -5208, -4772,
+    -4373, -4008, -3672, -3365, -3084, -2826, -2590, -2373,
+    -2175, -1993, -1826, -1673, -1534, -1405, -1288, -1180,
+    -1081, -991, -908, -832, -763, -699, -640, -587,
+    -538, -493, -452, -414, -379, -348, -318, -292,
+    -267, -245, -225, -206, -189, -173, -158, -145,
+    -133, -122, -112, -102, -94, -86, -79, -72,
+    -66, -61, -56, -51, -47, -43, -42, -41,
+    -40, -39, -38, -37, -36, -35, -34, -33,
+    -32, -31, -30, -29, -28, -27, -26, -25,
+    -24, -23, -22, -21, -20, -19, -18, -17,
+    -16, -15, -14, -13, -12, -11, -10, -9,
+    -8, -7, -6, -5, -4, -3, -2, -1,
+];
+
+/// Decodes an 8-bit encoded Interplay DPCM value to a linear 16-bit signed integer sample value.
+///
+/// The `predictor` parameter should be initialized to the value from the audio frame header.
+/// This method updates `predictor` with the new sample value.
+#[cfg_attr(feature = "internal-no-panic", no_panic)]
+#[inline(always)]
+pub fn decode_interplay_dpcm(encoded: u8, predictor: &mut i16) -> i16 {
+    let value = i32::from(*predictor) + i32::from(INTERPLAY_DELTA_TABLE[usize::from(encoded)]);
+    #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+    {
+    *predictor = value.clamp(-32768, 32767) as i16;
+    }
+    *predictor
+}
+
+/// Decodes an Interplay MVE DPCM audio frame to 16-bit signed integer samples.
+///
+/// `buf` should contain the initial 16-bit little-endian predictor for each channel followed by
+/// 8-bit encoded values. The 6 byte stream mask and length header of the MVE audio frame
+/// must not be included in `buf`. For 1 channel audio, the `buf` length must be at least 2.
+/// For 2 channel audio, the `buf` length must be at least 4 and it must be divisible by 2.
+///
+/// `is_stereo` should be `false` for 1 channel (mono) audio and `true` for
+/// 2 channel (stereo) audio.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `buf.len() - 1` for 1 channel audio and `buf.len() - 2` for 2 channel audio.
+/// Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_interplay_dpcm_frame(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    // check buf length
+    if buf.len() < 2 * channels || (channels == 2 && buf.len() % 2 != 0) {
+        return Err(Error::InvalidBufferSize);
+    }
+    if buf.len() - channels != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut predictors = [0i16; 2];
+    for ch in 0..channels {
+        predictors[ch] = i16::from_le_bytes([ buf[ch*2], buf[ch*2+1] ]);
+        out_samples[ch] = predictors[ch];
+    }
+    // the rest of the bytes are deltas for interleaved channels
+    for (i, (b, out)) in buf[2*channels..].iter()
+        .zip(out_samples[channels..].iter_mut())
+        .enumerate() {
+        *out = decode_interplay_dpcm(*b, &mut predictors[i % channels]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_interplay_dpcm() {
+        let mut predictor = 0;
+        assert_eq!(decode_interplay_dpcm(10, &mut predictor), 10);
+        assert_eq!(decode_interplay_dpcm(60, &mut predictor), 199);
+        assert_eq!(decode_interplay_dpcm(255, &mut predictor), 198);
+        assert_eq!(predictor, 198);
+
+        // large deltas are clamped
+        let mut predictor = 30000;
+        assert_eq!(decode_interplay_dpcm(119, &mut predictor), 32767);
+        assert_eq!(decode_interplay_dpcm(137, &mut predictor), 178);
+        let mut predictor = -30000;
+        assert_eq!(decode_interplay_dpcm(137, &mut predictor), -32768);
+    }
+
+    #[test]
+    fn test_decode_interplay_dpcm_frame() {
+        // one channel
+        let mut samples = [0i16; 5];
+        assert!(decode_interplay_dpcm_frame(&[ 0xe8, 0x03, 1, 43, 213, 128 ], false,
+            &mut samples).is_ok());
+        assert_eq!(samples, [ 1000, 1001, 1044, 1001, 1002 ]);
+
+        // two channels
+        let mut samples = [0i16; 6];
+        assert!(decode_interplay_dpcm_frame(&[ 0x10, 0x00, 0xf0, 0xff, 2, 254, 3, 253 ], true,
+            &mut samples).is_ok());
+        assert_eq!(samples, [ 16, -16, 18, -18, 21, -21 ]);
+
+        // invalid buf length
+        let mut samples = [0i16; 4];
+        assert!(matches!(decode_interplay_dpcm_frame(&[ 0, 0, 0, 0, 0 ], true, &mut samples),
+            Err(Error::InvalidBufferSize)));
+        let mut samples = [0i16; 0];
+        assert!(matches!(decode_interplay_dpcm_frame(&[ 0 ], false, &mut samples),
+            Err(Error::InvalidBufferSize)));
+
+        // invalid out_samples length
+        let mut samples = [0i16; 6];
+        assert!(matches!(decode_interplay_dpcm_frame(&[ 0, 0, 0, 0, 0, 0 ], false, &mut samples),
+            Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,129 @@
+
+use crate::decode_adpcm_ima_ms_with;
+
+/// Result of a lenient decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LenientDecodeReport {
+    /// Number of decoded samples, which were written to the start of the output buffer.
+    /// The rest of the output buffer is filled with zeros.
+    pub decoded_samples: usize,
+    /// Number of input bytes, which were decoded.
+    pub used_bytes: usize,
+    /// `true` if the input was valid and all of it was decoded, so that the normal decoding
+    /// function would have returned the same samples.
+    pub is_complete: bool,
+}
+
+/// Decodes a possibly damaged WAV / MS IMA ADPCM (wav format 0x0011) compressed block to
+/// 16-bit signed integer samples as well as possible.
+///
+/// This works like [`decode_adpcm_ima_ms()`](crate::decode_adpcm_ima_ms), but it never fails.
+/// Truncated blocks and blocks with invalid lengths are decoded up to the last complete
+/// 4 byte group of each channel (any byte for 1 channel audio). Decoding stops early if
+/// `out_samples` is full. The remaining `out_samples` are filled with zeros.
+/// If `buf` is shorter than the block header, no samples are decoded.
+///
+/// The returned report tells how many samples were decoded and how many bytes were used.
+pub fn decode_adpcm_ima_ms_lenient(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
+    -> LenientDecodeReport {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    // drop incomplete groups and limit to the maximum block size
+    let usable_len = if buf.len() < 4 * channels {
+        0
+    } else if is_stereo {
+        (buf.len().min(0xffff) / 8) * 8
+    } else {
+        buf.len().min(0xffff)
+    };
+    let expected_sample_len = if usable_len == 0 {
+        0
+    } else {
+        (usable_len - 4 * channels) * 2 + channels
+    };
+    let mut decoded_samples = 0;
+    if usable_len > 0 {
+        let mut out = out_samples.iter_mut();
+        // the length is always valid, so this never returns an error
+        let _ = decode_adpcm_ima_ms_with(&buf[..usable_len], is_stereo, &mut |s| {
+            if let Some(o) = out.next() {
+                *o = s;
+                decoded_samples += 1;
+            }
+        });
+    }
+    out_samples[decoded_samples..].fill(0);
+    // count bytes whose samples were all written
+    let used_bytes = if decoded_samples == expected_sample_len {
+        usable_len
+    } else if decoded_samples < channels {
+        0
+    } else if is_stereo {
+        8 + ((decoded_samples - 2) / 16) * 8
+    } else {
+        4 + (decoded_samples - 1) / 2
+    };
+    LenientDecodeReport {
+        decoded_samples,
+        used_bytes,
+        is_complete: usable_len == buf.len() && usable_len > 0 &&
+            decoded_samples == out_samples.len() && decoded_samples == expected_sample_len,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_adpcm_ima_ms;
+
+    const BLOCK: [u8; 16] = [ 0x38, 0xB1, 0x47, 0x00, 0x1A, 0x9B, 0x50, 0x00,
+        0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0 ];
+
+    #[test]
+    fn test_decode_adpcm_ima_ms_lenient_valid() {
+        let mut expected = [0i16; 18];
+        assert!(decode_adpcm_ima_ms(&BLOCK, true, &mut expected).is_ok());
+        let mut out = [1i16; 18];
+        assert_eq!(decode_adpcm_ima_ms_lenient(&BLOCK, true, &mut out), LenientDecodeReport {
+            decoded_samples: 18, used_bytes: 16, is_complete: true });
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_ms_lenient_damaged() {
+        let mut expected = [0i16; 18];
+        assert!(decode_adpcm_ima_ms(&BLOCK, true, &mut expected).is_ok());
+
+        // truncated stereo block, the incomplete group is ignored
+        let mut out = [1i16; 18];
+        assert_eq!(decode_adpcm_ima_ms_lenient(&BLOCK[..13], true, &mut out),
+            LenientDecodeReport { decoded_samples: 2, used_bytes: 8, is_complete: false });
+        assert_eq!(out[..2], expected[..2]);
+        assert!(out[2..].iter().all(|s| *s == 0));
+
+        // output buffer is too small
+        let mut out = [1i16; 10];
+        assert_eq!(decode_adpcm_ima_ms_lenient(&BLOCK, true, &mut out),
+            LenientDecodeReport { decoded_samples: 10, used_bytes: 8, is_complete: false });
+        assert_eq!(out, expected[..10]);
+
+        // truncated mono block
+        let mut expected = [0i16; 23];
+        assert!(decode_adpcm_ima_ms(&BLOCK[..15], false, &mut expected).is_ok());
+        let mut out = [1i16; 29];
+        assert_eq!(decode_adpcm_ima_ms_lenient(&BLOCK[..15], false, &mut out),
+            LenientDecodeReport { decoded_samples: 23, used_bytes: 15, is_complete: false });
+        assert_eq!(out[..23], expected);
+        assert!(out[23..].iter().all(|s| *s == 0));
+
+        // too short for the header
+        let mut out = [1i16; 4];
+        assert_eq!(decode_adpcm_ima_ms_lenient(&BLOCK[..7], true, &mut out),
+            LenientDecodeReport { decoded_samples: 0, used_bytes: 0, is_complete: false });
+        assert_eq!(out, [0; 4]);
+    }
+}
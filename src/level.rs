@@ -0,0 +1,292 @@
+
+// Level measurement and gain scaling similar to the ITU-T G.191 Software Tools Library.
+// Levels are in dBov: 0 dBov is the level of a full scale square wave (RMS 32768).
+
+// core doesn't have floating point math functions, so the functions needed here
+// are implemented below
+
+const LN_2: f64 = core::f64::consts::LN_2;
+const LN_10: f64 = core::f64::consts::LN_10;
+
+// natural logarithm, x must be positive
+pub(crate) fn ln(x: f64) -> f64 {
+    if x <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if x.is_infinite() || x.is_nan() {
+        return x;
+    }
+    // scale subnormal values to normal values
+    let (x, exp_offset) = if x < f64::MIN_POSITIVE {
+        (x * 18014398509481984.0, -54) // 2^54
+    } else {
+        (x, 0)
+    };
+    // x = m * 2^e, where m is 1..2
+    let bits = x.to_bits();
+    let e = i32::try_from((bits >> 52) & 0x7ff).unwrap_or(0) - 1023 + exp_offset;
+    let m = f64::from_bits((bits & 0x000f_ffff_ffff_ffff) | 0x3ff0_0000_0000_0000);
+    // ln(m) = 2 * atanh(s), where s = (m - 1) / (m + 1) is 0..1/3
+    let s = (m - 1.0) / (m + 1.0);
+    let s2 = s * s;
+    let mut term = s;
+    let mut sum = 0.0;
+    let mut n = 1.0;
+    while n < 40.0 {
+        sum += term / n;
+        term *= s2;
+        n += 2.0;
+    }
+    2.0 * sum + f64::from(e) * LN_2
+}
+
+// exponential function
+pub(crate) fn exp(x: f64) -> f64 {
+    if x.is_nan() {
+        return x;
+    }
+    if x > 709.0 {
+        return f64::INFINITY;
+    }
+    if x < -708.0 {
+        return 0.0;
+    }
+    // x = k * ln(2) + r, where r is -ln(2)/2..ln(2)/2
+    let kf = x / LN_2;
+    #[allow(clippy::cast_possible_truncation)] // x is limited so truncation never happens
+    let mut k = kf as i32;
+    let frac = kf - f64::from(k);
+    if frac >= 0.5 {
+        k += 1;
+    } else if frac <= -0.5 {
+        k -= 1;
+    }
+    let r = x - f64::from(k) * LN_2;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    let mut n = 1.0;
+    while n < 20.0 {
+        term *= r / n;
+        sum += term;
+        n += 1.0;
+    }
+    // k is -1022..=1023, so 2^k is a normal value
+    let Ok(biased) = u64::try_from(k + 1023) else {
+        return 0.0;
+    };
+    sum * f64::from_bits(biased << 52)
+}
+
+pub(crate) fn log10(x: f64) -> f64 {
+    ln(x) / LN_10
+}
+
+// rounds half away from zero and saturates to the i16 range
+pub(crate) fn round_to_i16(x: f64) -> i16 {
+    let x = x.clamp(-32768.0, 32767.0);
+    #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+    let mut t = x as i16;
+    let frac = x - f64::from(t);
+    if frac >= 0.5 {
+        t = t.saturating_add(1);
+    } else if frac <= -0.5 {
+        t = t.saturating_sub(1);
+    }
+    t
+}
+
+// 20 * log10(32768), the full scale level
+pub(crate) const FULL_SCALE_DB: f64 = 90.30899869919435;
+
+/// Returns the RMS level of `samples` in dBov.
+///
+/// 0 dBov is the level of a full scale square wave, so a full scale sine wave is about
+/// -3.01 dBov. `f64::NEG_INFINITY` is returned if `samples` is empty or all samples are zero.
+pub fn rms_level_dbov(samples: &[i16]) -> f64 {
+    if samples.is_empty() {
+        return f64::NEG_INFINITY;
+    }
+    let sq: f64 = samples.iter().map(|s| f64::from(*s) * f64::from(*s)).sum();
+    let len = samples.len() as f64;
+    10.0 * log10(sq / len) - FULL_SCALE_DB
+}
+
+/// Active speech level measurement result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveSpeechLevel {
+    /// Active speech level in dBov, or `f64::NEG_INFINITY` for silence.
+    pub level_dbov: f64,
+    /// Activity factor 0.0..=1.0, the proportion of active samples.
+    pub activity: f64,
+}
+
+/// Measures the active speech level of `samples` with a simplified version of the
+/// ITU-T P.56 method B (the G.191 "sv56" tool).
+///
+/// The signal envelope is tracked with a 0.03 second time constant and samples are counted
+/// as active at 16 thresholds with a 0.2 second hangover. The active level is interpolated
+/// at the threshold where the level is 15.9 dB above the threshold.
+///
+/// `sample_rate` is the sample rate of `samples` in Hz.
+pub fn active_speech_level(samples: &[i16], sample_rate: u32) -> ActiveSpeechLevel {
+    const THRESHOLD_COUNT: usize = 16;
+    const MARGIN_DB: f64 = 15.9;
+    let silence = ActiveSpeechLevel {
+        level_dbov: f64::NEG_INFINITY,
+        activity: 0.0,
+    };
+    let sample_rate = f64::from(sample_rate.max(1));
+    let g = exp(-1.0 / (sample_rate * 0.03));
+    let hangover_len = round_to_i16(0.2 * sample_rate).max(0).unsigned_abs();
+    let hangover_len = usize::from(hangover_len);
+
+    let mut sq = 0.0;
+    let mut p = 0.0;
+    let mut q = 0.0;
+    let mut active_counts = [0usize; THRESHOLD_COUNT];
+    let mut hangovers = [0usize; THRESHOLD_COUNT];
+    for s in samples {
+        let x = f64::from(*s);
+        sq += x * x;
+        p = g * p + (1.0 - g) * x.abs();
+        q = g * q + (1.0 - g) * p;
+        let mut threshold = 1.0;
+        for (count, hangover) in active_counts.iter_mut().zip(hangovers.iter_mut()) {
+            if q >= threshold {
+                *count += 1;
+                *hangover = 0;
+            } else if *hangover < hangover_len {
+                *count += 1;
+                *hangover += 1;
+            } else {
+                break;
+            }
+            threshold *= 2.0;
+        }
+    }
+    if sq == 0.0 {
+        return silence;
+    }
+
+    // find the threshold where the active level is less than the margin above the threshold
+    let mut prev: Option<(f64, f64)> = None;
+    let mut level_db = f64::NEG_INFINITY;
+    let mut threshold_db = 0.0;
+    for count in active_counts {
+        if count == 0 {
+            break;
+        }
+        let active_db = 10.0 * log10(sq / count as f64);
+        let delta = active_db - threshold_db;
+        level_db = active_db;
+        if delta <= MARGIN_DB {
+            if let Some((prev_active_db, prev_delta)) = prev {
+                // interpolate between the previous and current thresholds
+                let t = (prev_delta - MARGIN_DB) / (prev_delta - delta);
+                level_db = prev_active_db + t * (active_db - prev_active_db);
+            }
+            break;
+        }
+        prev = Some((active_db, delta));
+        threshold_db += 20.0 * log10(2.0);
+    }
+    if level_db == f64::NEG_INFINITY {
+        return silence;
+    }
+    let mean_sq = sq / samples.len() as f64;
+    ActiveSpeechLevel {
+        level_dbov: level_db - FULL_SCALE_DB,
+        activity: (mean_sq / exp(level_db / 10.0 * LN_10)).min(1.0),
+    }
+}
+
+/// Converts a level difference in dB to a linear gain factor.
+pub fn db_to_gain(db: f64) -> f64 {
+    exp(db / 20.0 * LN_10)
+}
+
+/// Converts a linear gain factor to a level difference in dB.
+pub fn gain_to_db(gain: f64) -> f64 {
+    20.0 * log10(gain)
+}
+
+/// Multiplies `samples` by `gain`, rounding the results to the nearest integer.
+///
+/// Results outside the 16-bit range are saturated to -32768..=32767.
+/// The number of saturated samples is returned.
+pub fn scale_samples(samples: &mut [i16], gain: f64) -> usize {
+    let mut saturated = 0;
+    for s in samples {
+        let value = f64::from(*s) * gain;
+        if value >= 32767.5 || value <= -32768.5 {
+            saturated += 1;
+        }
+        *s = round_to_i16(value);
+    }
+    saturated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(value: f64, expected: f64, tolerance: f64) {
+        assert!((value - expected).abs() <= tolerance, "{} != {}", value, expected);
+    }
+
+    #[test]
+    fn test_math() {
+        assert_near(ln(1.0), 0.0, 1e-12);
+        assert_near(ln(core::f64::consts::E), 1.0, 1e-12);
+        assert_near(log10(1e-300), -300.0, 1e-9);
+        assert_near(log10(5e-320), -319.30103, 1e-5);
+        assert_near(exp(1.0), core::f64::consts::E, 1e-12);
+        assert_near(exp(-10.0), 4.5399929762484854e-5, 1e-15);
+        assert_near(log10(32768.0) * 20.0, FULL_SCALE_DB, 1e-9);
+        assert_eq!(round_to_i16(2.5), 3);
+        assert_eq!(round_to_i16(-2.5), -3);
+        assert_eq!(round_to_i16(-2.4), -2);
+        assert_eq!(round_to_i16(1e9), 32767);
+    }
+
+    #[test]
+    fn test_rms_level_dbov() {
+        assert_near(rms_level_dbov(&[ 32767, -32768 ]), 0.0, 0.001);
+        assert_near(rms_level_dbov(&[ 1000, -1000 ]), -30.309, 0.001);
+        assert_eq!(rms_level_dbov(&[ 0, 0 ]), f64::NEG_INFINITY);
+        assert_eq!(rms_level_dbov(&[]), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_active_speech_level() {
+        // a constant level signal is active all the time
+        let mut samples = [0i16; 24000];
+        for (i, s) in samples[..8000].iter_mut().enumerate() {
+            *s = if i % 2 == 0 { 1000 } else { -1000 };
+        }
+        let result = active_speech_level(&samples[..8000], 8000);
+        assert_near(result.level_dbov, -30.309, 0.01);
+        assert_near(result.activity, 1.0, 0.001);
+
+        // silence after the signal doesn't change the active level much
+        let result = active_speech_level(&samples, 8000);
+        assert_near(result.level_dbov, -30.309, 2.0);
+        assert!(result.activity > 0.3 && result.activity < 0.5);
+
+        assert_eq!(active_speech_level(&samples[8000..], 8000), ActiveSpeechLevel {
+            level_dbov: f64::NEG_INFINITY,
+            activity: 0.0,
+        });
+    }
+
+    #[test]
+    fn test_scale_samples() {
+        let mut samples = [ 1000, -1000, 30000, -30000, 1 ];
+        assert_eq!(scale_samples(&mut samples, 2.0), 2);
+        assert_eq!(samples, [ 2000, -2000, 32767, -32768, 2 ]);
+        let mut samples = [ 1000, -1000, 3 ];
+        assert_eq!(scale_samples(&mut samples, db_to_gain(-6.0206)), 0);
+        assert_eq!(samples, [ 500, -500, 1 ]);
+        assert_near(gain_to_db(0.5), -6.0206, 0.0001);
+    }
+}
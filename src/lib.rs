@@ -3,6 +3,56 @@
 //!  - [G.711 A-law](https://en.wikipedia.org/wiki/G.711#A-law)
 //!  - [G.711 μ-law](https://en.wikipedia.org/wiki/G.711#μ-law)
 //!  - [IMA ADPCM](https://en.wikipedia.org/wiki/Interactive_Multimedia_Association)
+//!  - [Interplay DPCM](https://wiki.multimedia.cx/index.php/Interplay_DPCM) (decoding only)
+//!  - [LucasArts VIMA](https://wiki.multimedia.cx/index.php/VIMA) (decoding only)
+//!  - Funcom ISS IMA ADPCM (decoding only)
+//!  - Ubisoft APM IMA ADPCM (Rayman 2, decoding only)
+//!  - Xbox IMA ADPCM (decoding only)
+//!  - Legacy 7-bit μ-law (D1 channel banks)
+//!  - CD-XA ADPCM (CD-ROM XA, CD-i and PlayStation, decoding only)
+//!  - [Interplay ACM](https://wiki.multimedia.cx/index.php/Interplay_ACM)
+//!    (Fallout 1 and 2, decoding only, needs the `alloc` feature)
+//!  - [Microsoft ADPCM](https://wiki.multimedia.cx/index.php/Microsoft_ADPCM)
+//!  - [OKI ADPCM](https://wiki.multimedia.cx/index.php/Dialogic_IMA_ADPCM)
+//!    (Dialogic VOX, MSM6295 and MSM6258, decoding only)
+//!  - Yamaha ADPCM (AICA / Dreamcast and YMZ280B)
+//!  - [G.726](https://en.wikipedia.org/wiki/G.726) ADPCM (16, 24, 32 and 40 kbit/s, and 1984 G.721)
+//!  - [G.722](https://en.wikipedia.org/wiki/G.722) sub-band ADPCM (64, 56 and 48 kbit/s)
+//!  - [GSM 06.10](https://en.wikipedia.org/wiki/Full_Rate) full rate (RPE-LTP)
+//!  - [CVSD](https://en.wikipedia.org/wiki/Continuously_variable_slope_delta_modulation)
+//!    (Bluetooth, 64 kbit/s)
+//!  - Harris HC-55516 CVSD (Williams and Midway arcade speech, decoding only)
+//!  - [SBC](https://en.wikipedia.org/wiki/SBC_(codec))
+//!    (Bluetooth A2DP low-complexity subband codec)
+//!  - Adobe Flash (SWF) ADPCM (2, 3, 4 and 5 bits per sample)
+//!  - Creative Labs Sound Blaster ADPCM (8-bit to 4, 2.6 and 2 bits, VOC files)
+//!  - Apple MACE 3:1 and 6:1 (classic Mac OS and QuickTime 'MAC3' and 'MAC6')
+//!  - SNES BRR (bit rate reduction) ADPCM
+//!  - Nintendo GameCube / Wii DSP-ADPCM
+//!  - PlayStation SPU ADPCM (PS1 and PS2, VAG files)
+//!  - Electronic Arts XAS ADPCM (decoding only)
+//!  - Maxis XA ADPCM (SimCity 3000 and The Sims, decoding only)
+//!  - Westwood Studios AUD IMA ADPCM and SND1 (Command & Conquer, decoding only)
+//!  - Duck DK3 IMA ADPCM (decoding only)
+//!  - Duck DK4 IMA ADPCM (decoding only)
+//!  - 3DO SDX2 squareroot-delta-exact (decoding only)
+//!  - Amiga IFF 8SVX Fibonacci-delta and Exponential-delta (decoding only)
+//!  - CRI ADX ADPCM
+//!  - Nintendo GameCube / Wii AFC ADPCM (decoding only)
+//!  - Nintendo THP movie audio (DSP-ADPCM with per-frame headers, decoding only)
+//!  - Procyon Studio ADPCM (Nintendo DS, decoding only)
+//!  - Eurocom DAT4 IMA ADPCM (decoding only)
+//!  - Audiokinetic Wwise IMA ADPCM (decoding only)
+//!  - FMOD FADPCM (decoding only)
+//!  - Argonaut Games ASF ADPCM (Croc, decoding only)
+//!  - DVI4 IMA ADPCM RTP payloads (RFC 3551)
+//!  - [Linear delta modulation](https://en.wikipedia.org/wiki/Delta_modulation)
+//!    (1-bit, configurable step size)
+//!  - Generic DPCM with a caller-supplied delta table (1 to 8 bit codes)
+//!  - [NICAM](https://en.wikipedia.org/wiki/NICAM) near-instantaneous companding
+//!    (14-bit samples to 10 bits with a scale for each 32 samples)
+//!  - DAT long-play 12-bit non-linear quantization
+//!  - Natural MicroSystems VBXADPCM (IMA ADPCM with 0 to 3 truncated bits)
 //!
 
 #![no_std]
@@ -27,7 +77,10 @@
     clippy::cast_sign_loss,
     clippy::checked_conversions,
 )]
-#![allow(clippy::manual_range_contains)]
+#![allow(clippy::manual_range_contains, clippy::manual_is_multiple_of)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
 mod alaw;
 pub use alaw::{decode_alaw, encode_alaw};
@@ -35,17 +88,311 @@ pub use alaw::{decode_alaw, encode_alaw};
 mod ulaw;
 pub use ulaw::{decode_ulaw, encode_ulaw};
 
+mod g711_transcode;
+pub use g711_transcode::{alaw_to_ulaw, ulaw_to_alaw, alaw_to_ulaw_slice, ulaw_to_alaw_slice};
+
+mod ulaw7;
+pub use ulaw7::{decode_ulaw7, encode_ulaw7};
+
+mod dat_lp;
+pub use dat_lp::{decode_dat_lp, decode_dat_lp_slice, encode_dat_lp, encode_dat_lp_slice};
+
+mod companding;
+pub use companding::{ALaw, CompandingLaw, ULaw};
+
+mod mix;
+pub use mix::mix_companded;
+
 mod adpcm_ima;
 pub use adpcm_ima::AdpcmImaState;
 pub use adpcm_ima::{decode_adpcm_ima, decode_adpcm_ima_ima4, decode_adpcm_ima_ms};
+pub use adpcm_ima::{decode_adpcm_ima_ima4_with, decode_adpcm_ima_ms_with};
+pub use adpcm_ima::{decode_adpcm_ima_ms_multichannel, decode_adpcm_ima_ms_planar};
+pub use adpcm_ima::{decode_adpcm_ima_ima4_slice, encode_adpcm_ima_ima4_slice};
 pub use adpcm_ima::{encode_adpcm_ima, encode_adpcm_ima_ima4, encode_adpcm_ima_ms};
 
+mod adpcm_ima_bits;
+pub use adpcm_ima_bits::{AdpcmImaBits, decode_adpcm_ima_bits, encode_adpcm_ima_bits};
+pub use adpcm_ima_bits::adpcm_ima_ms_bits_samples_per_block;
+pub use adpcm_ima_bits::{decode_adpcm_ima_ms_bits, encode_adpcm_ima_ms_bits};
+
+mod adpcm_ima_vbx;
+pub use adpcm_ima_vbx::{adpcm_ima_vbx_bits, decode_adpcm_ima_vbx, encode_adpcm_ima_vbx};
+pub use adpcm_ima_vbx::{decode_adpcm_ima_vbx_slice, encode_adpcm_ima_vbx_slice};
+
+mod adaptive;
+pub use adaptive::{AdaptiveAdpcmImaEncoder, decode_adpcm_ima_adaptive_block};
+
+mod trace;
+pub use trace::{AdpcmImaTraceStep, decode_adpcm_ima_traced, trace_adpcm_ima};
+
+mod stereo;
+pub use stereo::StereoAdpcmImaState;
+pub use stereo::{decode_adpcm_ima_ima4_stereo, encode_adpcm_ima_ima4_stereo};
+
+mod adpcm_ms;
+pub use adpcm_ms::{ADPCM_MS_COEFFICIENTS, parse_adpcm_ms_coefficients};
+pub use adpcm_ms::{decode_adpcm_ms, decode_adpcm_ms_with_coefficients};
+pub use adpcm_ms::{encode_adpcm_ms, encode_adpcm_ms_with_coefficients};
+
+mod adpcm_yamaha;
+pub use adpcm_yamaha::{AdpcmYamahaState, decode_adpcm_yamaha, encode_adpcm_yamaha};
+pub use adpcm_yamaha::{decode_adpcm_yamaha_slice, encode_adpcm_yamaha_slice};
+
+mod oki;
+pub use oki::{OkiAdpcmState, OkiAdpcmVariant, decode_oki_adpcm, decode_oki_adpcm_slice};
+
+mod g726;
+pub use g726::{G726Rate, G726State, decode_g726, encode_g726};
+pub use g726::{G726Packing, pack_g726, unpack_g726};
+pub use g726::{decode_g726_to_alaw, decode_g726_to_ulaw};
+pub use g726::{encode_g726_from_alaw, encode_g726_from_ulaw};
+
+mod g722;
+pub use g722::{G722Mode, G722State, decode_g722, encode_g722};
+pub use g722::{decode_g722_slice, encode_g722_slice};
+
+mod gsm;
+pub use gsm::{GSM_FRAME_SIZE, GSM_SAMPLES_PER_FRAME, GsmState};
+pub use gsm::{decode_gsm_frame, encode_gsm_frame};
+
+mod cvsd;
+pub use cvsd::{CvsdState, decode_cvsd, decode_cvsd_slice, encode_cvsd, encode_cvsd_slice};
+pub use cvsd::{Hc55516State, Hc55516Variant, decode_hc55516, decode_hc55516_slice};
+
+mod delta_modulation;
+pub use delta_modulation::{DeltaModulationState, decode_delta_modulation, encode_delta_modulation};
+pub use delta_modulation::{decode_delta_modulation_slice, encode_delta_modulation_slice};
+
+mod sbc;
+pub use sbc::{SBC_HEADER_SIZE, SBC_SYNCWORD, SbcAllocationMethod, SbcChannelMode};
+pub use sbc::{SbcFrame, SbcFrameHeader, SbcState, parse_sbc_frame, parse_sbc_frame_header};
+pub use sbc::{decode_sbc_frame, decode_sbc_frame_samples, write_sbc_frame};
+pub use sbc::{encode_sbc_frame, encode_sbc_frame_samples};
+
+mod adpcm_swf;
+pub use adpcm_swf::{ADPCM_SWF_SAMPLES_PER_BLOCK, adpcm_swf_encoded_len, adpcm_swf_info};
+pub use adpcm_swf::{decode_adpcm_swf, encode_adpcm_swf};
+
+mod creative_adpcm;
+pub use creative_adpcm::{CreativeAdpcmState, decode_creative_adpcm4, encode_creative_adpcm4};
+pub use creative_adpcm::{decode_creative_adpcm4_slice, encode_creative_adpcm4_slice};
+pub use creative_adpcm::{decode_creative_adpcm3, decode_creative_adpcm3_slice};
+pub use creative_adpcm::{encode_creative_adpcm3, encode_creative_adpcm3_slice};
+pub use creative_adpcm::{decode_creative_adpcm2, decode_creative_adpcm2_slice};
+pub use creative_adpcm::{encode_creative_adpcm2, encode_creative_adpcm2_slice};
+
+mod mace;
+pub use mace::{MaceState, decode_mace3, decode_mace6, encode_mace3, encode_mace6};
+
+mod brr;
+pub use brr::{BRR_BLOCK_SIZE, BRR_SAMPLES_PER_BLOCK, BrrBlockHeader, BrrState};
+pub use brr::{decode_brr, decode_brr_block, encode_brr, encode_brr_block};
+
+mod dsp_adpcm;
+pub use dsp_adpcm::{DSP_ADPCM_FRAME_SIZE, DSP_ADPCM_SAMPLES_PER_FRAME, DspAdpcmState};
+pub use dsp_adpcm::{decode_dsp_adpcm, decode_dsp_adpcm_frame, dsp_adpcm_coefficients};
+pub use dsp_adpcm::{encode_dsp_adpcm, encode_dsp_adpcm_frame};
+
+mod spu_adpcm;
+pub use spu_adpcm::{SPU_ADPCM_BLOCK_SIZE, SPU_ADPCM_SAMPLES_PER_BLOCK, SpuAdpcmBlockHeader};
+pub use spu_adpcm::{SpuAdpcmState, decode_spu_adpcm, decode_spu_adpcm_block};
+pub use spu_adpcm::{encode_spu_adpcm, encode_spu_adpcm_block};
+
+mod ea_adpcm;
+pub use ea_adpcm::{EA_XAS_BLOCK_SIZE, EA_XAS_SAMPLES_PER_BLOCK, decode_ea_xas, decode_ea_xas_block};
+pub use ea_adpcm::{MAXIS_XA_BLOCK_SIZE, MAXIS_XA_HEADER_SIZE, MAXIS_XA_SAMPLES_PER_BLOCK};
+pub use ea_adpcm::{MaxisXaHeader, MaxisXaState, parse_maxis_xa_header};
+pub use ea_adpcm::{decode_maxis_xa, decode_maxis_xa_block};
+
+mod westwood;
+pub use westwood::{WESTWOOD_AUD_CHUNK_HEADER_SIZE, WESTWOOD_AUD_HEADER_SIZE};
+pub use westwood::{WestwoodAudChunkHeader, WestwoodAudCompression, WestwoodAudHeader};
+pub use westwood::{parse_westwood_aud_chunk_header, parse_westwood_aud_header};
+pub use westwood::{decode_westwood_ima, decode_westwood_snd1};
+
+mod sdx2;
+pub use sdx2::{decode_sdx2, decode_sdx2_slice};
+
+mod svx8;
+pub use svx8::{decode_svx8_exponential, decode_svx8_fibonacci};
+
+mod adx;
+pub use adx::{ADX_FRAME_SIZE, ADX_HEADER_SIZE, ADX_SAMPLES_PER_FRAME, AdxHeader, AdxState};
+pub use adx::{adx_coefficients, decode_adx, decode_adx_frame, encode_adx, encode_adx_frame,
+    parse_adx_header};
+
+mod afc;
+pub use afc::{AFC_FRAME_SIZE, AFC_SAMPLES_PER_FRAME, AfcState, decode_afc, decode_afc_frame};
+
+mod thp;
+pub use thp::{THP_AUDIO_HEADER_SIZE, ThpAudioFrameHeader, decode_thp_audio_frame,
+    parse_thp_audio_frame_header};
+
+mod procyon;
+pub use procyon::{PROCYON_ADPCM_FRAME_SIZE, PROCYON_ADPCM_SAMPLES_PER_FRAME, ProcyonAdpcmState};
+pub use procyon::{decode_procyon_adpcm, decode_procyon_adpcm_frame};
+
+mod fadpcm;
+pub use fadpcm::{FADPCM_FRAME_SIZE, FADPCM_SAMPLES_PER_FRAME, decode_fadpcm, decode_fadpcm_frame};
+
+mod argo_adpcm;
+pub use argo_adpcm::{ARGO_ADPCM_BLOCK_SIZE, ARGO_ADPCM_SAMPLES_PER_BLOCK, ArgoAdpcmState};
+pub use argo_adpcm::{decode_argo_adpcm, decode_argo_adpcm_block};
+
+mod nicam;
+pub use nicam::{NICAM_SAMPLES_PER_BLOCK, decode_nicam, decode_nicam_block, encode_nicam,
+    encode_nicam_block};
+
+mod codec_state;
+pub use codec_state::CodecState;
+
+mod bitreader;
+
+mod sample_history;
+
+mod vima;
+pub use vima::{decode_vima, vima_frame_info};
+
+mod interplay_dpcm;
+pub use interplay_dpcm::{decode_interplay_dpcm, decode_interplay_dpcm_frame};
+
+mod dpcm;
+pub use dpcm::{DpcmCodec, DpcmOverflow};
+
+#[cfg(feature = "alloc")]
+mod interplay_acm;
+#[cfg(feature = "alloc")]
+pub use interplay_acm::{INTERPLAY_ACM_HEADER_SIZE, InterplayAcmHeader};
+#[cfg(feature = "alloc")]
+pub use interplay_acm::{decode_interplay_acm, parse_interplay_acm_header};
+
+mod adpcm_ima_iss;
+pub use adpcm_ima_iss::decode_adpcm_ima_iss;
+
+mod adpcm_ima_dk3;
+pub use adpcm_ima_dk3::{DK3_ADPCM_HEADER_SIZE, decode_adpcm_ima_dk3,
+    dk3_adpcm_samples_per_block};
+
+mod adpcm_ima_dk4;
+pub use adpcm_ima_dk4::decode_adpcm_ima_dk4;
+
+mod adpcm_ima_dat4;
+pub use adpcm_ima_dat4::decode_adpcm_ima_dat4;
+
+mod adpcm_ima_wwise;
+pub use adpcm_ima_wwise::decode_adpcm_ima_wwise;
+
+mod adpcm_ima_dvi4;
+pub use adpcm_ima_dvi4::{DVI4_HEADER_SIZE, decode_adpcm_ima_dvi4, encode_adpcm_ima_dvi4};
+
+mod adpcm_ima_apm;
+pub use adpcm_ima_apm::{decode_adpcm_ima_apm, parse_adpcm_ima_apm_vs12};
+
+mod symmetric;
+pub use symmetric::{clamp_symmetric, clamp_symmetric_slice, decode_adpcm_ima_symmetric};
+pub use symmetric::{decode_adpcm_ima_ima4_symmetric, decode_adpcm_ima_ms_symmetric};
+
+mod adpcm_ima_xbox;
+pub use adpcm_ima_xbox::{XBOX_ADPCM_BLOCK_SIZE, XBOX_ADPCM_SAMPLES_PER_BLOCK};
+pub use adpcm_ima_xbox::decode_adpcm_ima_xbox;
+
+mod xa;
+pub use xa::{XA_SOUND_GROUP_SIZE, XA_SOUND_GROUPS_PER_SECTOR, XaAdpcmState, XaCodingInfo,
+    XaSubheader, decode_xa_adpcm_sound_group, decode_xa_sector, parse_xa_sector};
+
+mod voc;
+pub use voc::{VOC_HEADER_SIZE, VocBlock, VocBlocks, VocCodec, voc_blocks};
+pub use voc::{decode_voc_sound_data, encode_voc_sound_data, write_voc_block, write_voc_header};
+
+mod eight_bit;
+pub use eight_bit::{EightBitSample, decode_companded_8bit};
+pub use eight_bit::{decode_adpcm_ima_ima4_8bit, decode_adpcm_ima_ms_8bit};
+
+mod encode_iter;
+pub use encode_iter::{encode_adpcm_ima_ima4_iter, encode_adpcm_ima_ms_iter};
+
+mod encode_quality;
+pub use encode_quality::EncodeQuality;
+pub use encode_quality::{encode_adpcm_ima_ima4_with_quality, encode_adpcm_ima_ms_with_quality};
+
+mod ima4_header;
+pub use ima4_header::{Ima4HeaderPolicy, encode_adpcm_ima_ima4_with_header_policy};
+
+mod adpcm_ima_block;
+pub use adpcm_ima_block::{Ima4Block, MsImaBlock};
+
+mod lenient;
+pub use lenient::{LenientDecodeReport, decode_adpcm_ima_ms_lenient};
+
+mod resync;
+pub use resync::{adpcm_ima_states_diverged, encode_adpcm_ima_ima4_resync};
+
+mod saturation;
+pub use saturation::{AdpcmImaSaturation, encode_adpcm_ima_instrumented,
+    encode_adpcm_ima_ima4_instrumented, encode_adpcm_ima_ms_instrumented};
+
+mod smoothing;
+pub use smoothing::{BlockSmoothing, decode_adpcm_ima_ima4_smoothed, decode_adpcm_ima_ms_smoothed};
+
+mod repack;
+pub use repack::{repack_adpcm_ima_ima4_to_ms, repack_adpcm_ima_ms_to_ima4};
+
+mod level;
+pub use level::{ActiveSpeechLevel, active_speech_level, rms_level_dbov};
+pub use level::{db_to_gain, gain_to_db, scale_samples};
+
+mod rtp;
+pub use rtp::{RtpPayloadFormat, rtp_payload_format, rtp_payload_format_by_name};
+pub use rtp::{RTP_PCMU, RTP_PCMA, RTP_DVI4_8000, RTP_DVI4_16000, RTP_DVI4_11025, RTP_DVI4_22050};
+pub use rtp::RTP_CN;
+
+mod signal;
+pub use signal::{DIGITAL_MILLIWATT_ALAW, DIGITAL_MILLIWATT_ULAW};
+pub use signal::{fill_digital_milliwatt_alaw, fill_digital_milliwatt_ulaw};
+pub use signal::{SweepGenerator, ToneGenerator};
+
+mod silence;
+pub use silence::{ALAW_SILENCE, ULAW_SILENCE, fill_silence_companded};
+pub use silence::{fill_silence_adpcm_ima_ima4, fill_silence_adpcm_ima_ms};
+pub use silence::silence_block_adpcm_ima_ima4;
+
+mod comfort_noise;
+pub use comfort_noise::{CN_LEVEL_UPDATE_THRESHOLD, CN_MAX_ORDER, CnParameters};
+pub use comfort_noise::{parse_cn_payload, write_cn_payload};
+pub use comfort_noise::{estimate_cn_parameters, estimate_cn_parameters_companded};
+pub use comfort_noise::{ComfortNoiseGenerator, DtxEncoder, DtxFrame};
+
+mod info;
+pub use info::{Codec, CodecInfo};
+
+mod fingerprint;
+pub use fingerprint::{AudioFingerprint, fingerprint_samples};
+
+mod analysis;
+pub use analysis::{AdpcmImaAnalysis, CompandingAnalysis, ErrorStats};
+pub use analysis::{analyze_adpcm_ima, analyze_alaw, analyze_ulaw};
+
+#[cfg(feature = "heapless")]
+mod fixed_capacity;
+#[cfg(feature = "heapless")]
+pub use fixed_capacity::{decode_adpcm_ima_ima4_heapless, decode_adpcm_ima_ms_heapless};
+#[cfg(feature = "heapless")]
+pub use fixed_capacity::{encode_adpcm_ima_ima4_heapless, encode_adpcm_ima_ms_heapless};
+
+#[cfg(test)]
+mod test_signal;
+
 /// Error values.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Invalid number of channels.
     InvalidChannels,
 
     /// Buffer has an invalid size.
     InvalidBufferSize,
+
+    /// Data is invalid, for instance a header has an invalid identifier.
+    InvalidData,
 }
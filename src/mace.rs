@@ -0,0 +1,577 @@
+use crate::{CodecState, Error};
+use crate::codec_state::{StateReader, StateWriter};
+
+// Macintosh Audio Compression/Expansion (MACE) 3:1 and 6:1, as decoded by FFmpeg. The tables
+// are MACEtab1 to MACEtab4 of FFmpeg. Each byte contains three codes: a 3-bit code, a 2-bit
+// code and a 3-bit code. The 2-bit code uses its own tables. The decoded steps are added to
+// a leaky integrator, and a table index selects the step sizes of the next code. MACE 6:1
+// has an adaptive integrator and interpolates two samples from each code.
+
+// index changes of 3-bit codes
+const TAB1: &[i16; 8] = &[ -13, 8, 76, 222, 222, 76, 8, -13 ];
+
+// index changes of 2-bit codes
+const TAB3: &[i16; 4] = &[ -18, 140, 140, -18 ];
+
+// positive steps of 3-bit codes for each table row, negative steps mirror these
+const TAB2: &[[i16; 4]; 128] = &[
+    [ 37, 116, 206, 330 ], [ 39, 121, 216, 346 ], [ 41, 127, 225, 361 ],
+    [ 42, 132, 235, 377 ], [ 44, 137, 245, 392 ], [ 46, 144, 256, 410 ],
+    [ 48, 150, 267, 428 ], [ 51, 157, 280, 449 ], [ 53, 165, 293, 470 ],
+    [ 55, 172, 306, 490 ], [ 58, 179, 319, 511 ], [ 60, 187, 333, 534 ],
+    [ 63, 195, 348, 557 ], [ 66, 205, 364, 583 ], [ 69, 214, 380, 609 ],
+    [ 72, 223, 396, 635 ], [ 75, 233, 414, 663 ], [ 79, 244, 433, 694 ],
+    [ 82, 254, 453, 725 ], [ 86, 265, 472, 756 ], [ 90, 278, 495, 792 ],
+    [ 94, 290, 516, 826 ], [ 98, 303, 539, 863 ], [ 102, 316, 563, 901 ],
+    [ 107, 331, 590, 944 ], [ 112, 345, 614, 984 ], [ 117, 361, 642, 1028 ],
+    [ 122, 377, 670, 1073 ], [ 127, 394, 701, 1122 ], [ 133, 411, 732, 1172 ],
+    [ 139, 430, 764, 1224 ], [ 145, 449, 799, 1279 ], [ 152, 469, 835, 1336 ],
+    [ 159, 490, 872, 1396 ], [ 166, 512, 911, 1458 ], [ 173, 535, 951, 1523 ],
+    [ 181, 558, 993, 1590 ], [ 189, 584, 1038, 1661 ], [ 197, 610, 1085, 1737 ],
+    [ 206, 637, 1133, 1813 ], [ 215, 665, 1183, 1894 ], [ 225, 695, 1237, 1979 ],
+    [ 235, 726, 1291, 2067 ], [ 246, 759, 1349, 2160 ], [ 257, 792, 1409, 2256 ],
+    [ 268, 828, 1472, 2357 ], [ 280, 865, 1538, 2462 ], [ 293, 903, 1606, 2572 ],
+    [ 306, 944, 1678, 2686 ], [ 319, 986, 1753, 2806 ], [ 334, 1030, 1832, 2932 ],
+    [ 349, 1076, 1914, 3063 ], [ 364, 1124, 1999, 3200 ], [ 380, 1174, 2088, 3342 ],
+    [ 398, 1227, 2182, 3492 ], [ 415, 1281, 2278, 3647 ], [ 434, 1339, 2380, 3810 ],
+    [ 453, 1398, 2486, 3980 ], [ 473, 1461, 2598, 4157 ], [ 495, 1526, 2713, 4343 ],
+    [ 517, 1594, 2835, 4537 ], [ 540, 1665, 2961, 4739 ], [ 564, 1740, 3093, 4951 ],
+    [ 589, 1817, 3232, 5172 ], [ 615, 1898, 3375, 5402 ], [ 643, 1983, 3527, 5643 ],
+    [ 671, 2071, 3683, 5895 ], [ 701, 2164, 3848, 6158 ], [ 733, 2261, 4020, 6433 ],
+    [ 765, 2361, 4198, 6719 ], [ 800, 2467, 4386, 7019 ], [ 835, 2577, 4582, 7332 ],
+    [ 873, 2692, 4786, 7659 ], [ 912, 2812, 5000, 8001 ], [ 952, 2937, 5223, 8357 ],
+    [ 995, 3069, 5456, 8731 ], [ 1039, 3205, 5699, 9119 ], [ 1085, 3348, 5953, 9525 ],
+    [ 1134, 3497, 6218, 9950 ], [ 1184, 3653, 6495, 10394 ], [ 1237, 3816, 6785, 10857 ],
+    [ 1292, 3986, 7087, 11341 ], [ 1350, 4164, 7404, 11847 ], [ 1410, 4350, 7734, 12375 ],
+    [ 1473, 4544, 8079, 12927 ], [ 1539, 4747, 8440, 13505 ], [ 1607, 4958, 8816, 14106 ],
+    [ 1679, 5180, 9209, 14736 ], [ 1754, 5411, 9620, 15393 ], [ 1832, 5652, 10049, 16080 ],
+    [ 1914, 5904, 10497, 16797 ], [ 1999, 6167, 10965, 17545 ], [ 2088, 6442, 11454, 18327 ],
+    [ 2181, 6729, 11965, 19144 ], [ 2279, 7030, 12498, 19999 ], [ 2380, 7343, 13056, 20890 ],
+    [ 2486, 7671, 13638, 21822 ], [ 2597, 8013, 14246, 22795 ], [ 2713, 8370, 14882, 23812 ],
+    [ 2834, 8744, 15546, 24874 ], [ 2960, 9134, 16239, 25983 ], [ 3092, 9541, 16963, 27142 ],
+    [ 3230, 9966, 17719, 28353 ], [ 3374, 10410, 18509, 29617 ], [ 3525, 10875, 19334, 30937 ],
+    [ 3682, 11360, 20197, 32317 ], [ 3846, 11866, 21097, 32767 ], [ 4018, 12395, 22038, 32767 ],
+    [ 4197, 12948, 23020, 32767 ], [ 4384, 13525, 24047, 32767 ], [ 4580, 14128, 25120, 32767 ],
+    [ 4784, 14758, 26240, 32767 ], [ 4997, 15417, 27410, 32767 ], [ 5220, 16104, 28632, 32767 ],
+    [ 5453, 16822, 29910, 32767 ], [ 5696, 17572, 31243, 32767 ], [ 5950, 18356, 32637, 32767 ],
+    [ 6215, 19174, 32767, 32767 ], [ 6493, 20029, 32767, 32767 ], [ 6782, 20922, 32767, 32767 ],
+    [ 7085, 21856, 32767, 32767 ], [ 7401, 22830, 32767, 32767 ], [ 7731, 23848, 32767, 32767 ],
+    [ 8076, 24912, 32767, 32767 ], [ 8436, 26022, 32767, 32767 ], [ 8812, 27183, 32767, 32767 ],
+    [ 9205, 28395, 32767, 32767 ], [ 9615, 29661, 32767, 32767 ],
+];
+
+// positive steps of 2-bit codes for each table row, negative steps mirror these
+const TAB4: &[[i16; 2]; 128] = &[
+    [ 64, 216 ], [ 67, 226 ], [ 70, 236 ], [ 74, 248 ], [ 77, 258 ],
+    [ 80, 268 ], [ 84, 281 ], [ 88, 294 ], [ 92, 307 ], [ 96, 321 ],
+    [ 100, 334 ], [ 104, 349 ], [ 109, 364 ], [ 114, 380 ], [ 119, 397 ],
+    [ 124, 414 ], [ 130, 433 ], [ 136, 453 ], [ 142, 473 ], [ 148, 493 ],
+    [ 155, 515 ], [ 162, 538 ], [ 169, 562 ], [ 176, 587 ], [ 184, 613 ],
+    [ 193, 641 ], [ 201, 669 ], [ 210, 699 ], [ 220, 730 ], [ 229, 762 ],
+    [ 240, 796 ], [ 250, 832 ], [ 261, 869 ], [ 273, 908 ], [ 285, 948 ],
+    [ 298, 990 ], [ 311, 1034 ], [ 325, 1080 ], [ 339, 1128 ], [ 354, 1178 ],
+    [ 370, 1231 ], [ 386, 1285 ], [ 404, 1342 ], [ 422, 1402 ], [ 440, 1464 ],
+    [ 460, 1529 ], [ 480, 1597 ], [ 502, 1668 ], [ 524, 1742 ], [ 547, 1820 ],
+    [ 572, 1901 ], [ 597, 1985 ], [ 624, 2074 ], [ 651, 2166 ], [ 681, 2262 ],
+    [ 711, 2363 ], [ 742, 2468 ], [ 775, 2578 ], [ 810, 2692 ], [ 846, 2812 ],
+    [ 884, 2937 ], [ 923, 3068 ], [ 964, 3204 ], [ 1007, 3347 ], [ 1052, 3496 ],
+    [ 1098, 3651 ], [ 1147, 3813 ], [ 1198, 3983 ], [ 1251, 4160 ], [ 1307, 4345 ],
+    [ 1365, 4538 ], [ 1426, 4740 ], [ 1489, 4951 ], [ 1556, 5171 ], [ 1625, 5401 ],
+    [ 1697, 5641 ], [ 1773, 5892 ], [ 1852, 6154 ], [ 1934, 6428 ], [ 2020, 6714 ],
+    [ 2110, 7013 ], [ 2204, 7324 ], [ 2302, 7650 ], [ 2404, 7990 ], [ 2511, 8345 ],
+    [ 2623, 8716 ], [ 2740, 9104 ], [ 2862, 9508 ], [ 2989, 9931 ], [ 3122, 10372 ],
+    [ 3261, 10834 ], [ 3406, 11315 ], [ 3558, 11819 ], [ 3716, 12344 ], [ 3881, 12893 ],
+    [ 4054, 13466 ], [ 4234, 14065 ], [ 4422, 14691 ], [ 4619, 15344 ], [ 4825, 16026 ],
+    [ 5039, 16739 ], [ 5263, 17483 ], [ 5497, 18261 ], [ 5742, 19073 ], [ 5997, 19921 ],
+    [ 6264, 20807 ], [ 6543, 21733 ], [ 6834, 22700 ], [ 7138, 23709 ], [ 7455, 24766 ],
+    [ 7787, 25866 ], [ 8133, 27017 ], [ 8495, 28218 ], [ 8873, 29474 ], [ 9268, 30785 ],
+    [ 9680, 32154 ], [ 10111, 32767 ], [ 10561, 32767 ], [ 11031, 32767 ], [ 11521, 32767 ],
+    [ 12034, 32767 ], [ 12569, 32767 ], [ 13129, 32767 ], [ 13713, 32767 ], [ 14323, 32767 ],
+    [ 14961, 32767 ], [ 15626, 32767 ], [ 16321, 32767 ],
+];
+
+/// State values for the MACE decoder and encoder.
+///
+/// The state should be created with [`MaceState::new()`] at the start of the stream. Each
+/// channel needs its own state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaceState {
+    // index to the step tables, the table row is bits 4-10
+    index: i16,
+    // integrated sample value
+    level: i16,
+    // MACE 6:1 integrator factor
+    factor: i16,
+    // MACE 6:1 previous halved sample values
+    previous: i16,
+    prev2: i16,
+}
+
+impl MaceState {
+    /// Creates a new state with initial values.
+    pub fn new() -> MaceState {
+        MaceState {
+            index: 0,
+            level: 0,
+            factor: 0,
+            previous: 0,
+            prev2: 0,
+        }
+    }
+}
+
+impl Default for MaceState {
+    fn default() -> Self {
+        MaceState::new()
+    }
+}
+
+impl CodecState for MaceState {
+    /// 16-bit little-endian index, level, factor, previous and prev2 values.
+    const SERIALIZED_LEN: usize = 10;
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.i16(self.index);
+        w.i16(self.level);
+        w.i16(self.factor);
+        w.i16(self.previous);
+        w.i16(self.prev2);
+        Ok(())
+    }
+
+    /// An error is returned if the `buf` length isn't correct or if the index isn't
+    /// in the range 0-16383. Decoding keeps the index below 8192.
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        let state = MaceState {
+            index: r.i16(),
+            level: r.i16(),
+            factor: r.i16(),
+            previous: r.i16(),
+            prev2: r.i16(),
+        };
+        if !(0..=16383).contains(&state.index) {
+            return Err(Error::InvalidData);
+        }
+        Ok(state)
+    }
+}
+
+// returns the step of a code and updates the table index, the second code of each byte is
+// a 2-bit code
+fn read_table(code: u8, position: usize, state: &mut MaceState) -> i32 {
+    let row = usize::try_from((state.index & 0x7f0) >> 4).unwrap_or(0);
+    let (steps, index_changes): (&[i16], &[i16]) = if position == 1 {
+        (&TAB4[row], TAB3)
+    } else {
+        (&TAB2[row], TAB1)
+    };
+    let code = usize::from(code) % index_changes.len();
+    let step = if code < steps.len() {
+        i32::from(steps[code])
+    } else {
+        -1 - i32::from(steps[2 * steps.len() - code - 1])
+    };
+    state.index = (state.index + index_changes[code] - (state.index >> 5)).max(0);
+    step
+}
+
+// clamps to 16 bits like FFmpeg, which clamps negative values to -32767
+fn clamp(value: i32) -> i16 {
+    i16::try_from(value).unwrap_or(if value > 0 { 32767 } else { -32767 })
+}
+
+// keeps the high byte and copies it to the low byte, the output has 8-bit precision
+fn to_8bit_precision(value: i32) -> i16 {
+    let high = u8::try_from((value >> 8) & 0xff).unwrap_or(0);
+    i16::from_be_bytes([ high, high ])
+}
+
+fn decode_mace3_code(code: u8, position: usize, state: &mut MaceState) -> i16 {
+    let step = read_table(code, position, state);
+    let current = clamp(step + i32::from(state.level));
+    state.level = current - (current >> 3);
+    to_8bit_precision(i32::from(current))
+}
+
+fn decode_mace6_code(code: u8, position: usize, state: &mut MaceState) -> [i16; 2] {
+    let step = read_table(code, position, state);
+    // the integrator factor grows while the sign of the steps stays the same
+    if (i32::from(state.previous) ^ step) >= 0 {
+        state.factor = state.factor.saturating_add(506);
+    } else {
+        state.factor = state.factor.checked_sub(314).unwrap_or(-32767);
+    }
+    let current = clamp(step + i32::from(state.level));
+    state.level = i16::try_from((i32::from(current) * i32::from(state.factor)) >> 15)
+        .unwrap_or(0);
+    let current = current >> 1;
+    let (previous, prev2) = (i32::from(state.previous), i32::from(state.prev2));
+    let delta = (prev2 - i32::from(current)) >> 2;
+    state.prev2 = state.previous;
+    state.previous = current;
+    [
+        to_8bit_precision(previous + prev2 - delta),
+        to_8bit_precision(previous + i32::from(current) + delta),
+    ]
+}
+
+fn code_count(position: usize) -> u8 {
+    if position == 1 { 4 } else { 8 }
+}
+
+fn code_error<const N: usize>(samples: &[i16; N], decoded: [i16; N]) -> u32 {
+    samples.iter().zip(decoded).map(|(s, d)| (i32::from(*s) - i32::from(d)).unsigned_abs()).sum()
+}
+
+// returns the code with the smallest error and updates `state`, if `next_samples` is given,
+// the smallest error of the next code is included
+fn encode_code<const N: usize>(samples: &[i16; N], next_samples: Option<&[i16; N]>,
+    position: usize, state: &mut MaceState, decode: fn(u8, usize, &mut MaceState) -> [i16; N])
+    -> u8 {
+
+    let mut best_code = 0;
+    let mut best_error = u32::MAX;
+    let mut best_state = state.clone();
+    for code in 0..code_count(position) {
+        let mut candidate = state.clone();
+        let mut error = code_error(samples, decode(code, position, &mut candidate));
+        if let Some(next_samples) = next_samples {
+            let next_position = (position + 1) % 3;
+            error += (0..code_count(next_position)).map(|next_code| {
+                let mut next_state = candidate.clone();
+                code_error(next_samples, decode(next_code, next_position, &mut next_state))
+            }).min().unwrap_or(0);
+        }
+        if error < best_error {
+            best_code = code;
+            best_error = error;
+            best_state = candidate;
+        }
+    }
+    *state = best_state;
+    best_code
+}
+
+/// Decodes MACE 3:1 ('MAC3') data to 16-bit signed integer samples.
+///
+/// Each channel has two bytes for each six samples and each byte contains three codes,
+/// starting from the lowest bits. For 2 channel audio, the two byte pairs of the channels are
+/// interleaved. The decoded samples have 8-bit precision.
+///
+/// `states` must contain one state for each channel. The states should be initialized with
+/// [`MaceState::new()`] and subsequent calls should pass in the state values from the previous
+/// call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `3 * buf.len()` and the `buf` length must be divisible by `2 * states.len()`. Samples are
+/// interleaved for multichannel audio.
+///
+/// An error is returned if `states` is empty or if the `buf` or `out_samples` length isn't
+/// correct. If an error is returned, `states` and `out_samples` are left unmodified.
+pub fn decode_mace3(buf: &[u8], states: &mut [MaceState], out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    if buf.len() % (2 * channels) != 0 || buf.len().checked_mul(3) != Some(out_samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.chunks_exact(2 * channels).zip(out_samples.chunks_exact_mut(6 * channels)) {
+        for (ch, (bytes, state)) in b.chunks_exact(2).zip(states.iter_mut()).enumerate() {
+            for (i, byte) in bytes.iter().enumerate() {
+                let codes = [ byte & 7, (byte >> 3) & 3, byte >> 5 ];
+                for (position, code) in codes.into_iter().enumerate() {
+                    out[(3 * i + position) * channels + ch] =
+                        decode_mace3_code(code, position, state);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to MACE 3:1 ('MAC3') data.
+///
+/// The byte layout is described in [`decode_mace3()`]. The code with the smallest error is
+/// selected for each sample.
+///
+/// `states` must contain one state for each channel. The states should be initialized with
+/// [`MaceState::new()`] and subsequent calls should pass in the state values from the previous
+/// call.
+///
+/// This function outputs encoded bytes to `out_buf`. The `samples` length must be
+/// `3 * out_buf.len()` and the `out_buf` length must be divisible by `2 * states.len()`.
+/// Samples are interleaved for multichannel audio.
+///
+/// An error is returned if `states` is empty or if the `samples` or `out_buf` length isn't
+/// correct. If an error is returned, `states` and `out_buf` are left unmodified.
+pub fn encode_mace3(samples: &[i16], states: &mut [MaceState], out_buf: &mut [u8])
+    -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    if out_buf.len() % (2 * channels) != 0 || out_buf.len().checked_mul(3) != Some(samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (s, out) in samples.chunks_exact(6 * channels).zip(out_buf.chunks_exact_mut(2 * channels)) {
+        for (ch, (bytes, state)) in out.chunks_exact_mut(2).zip(states.iter_mut()).enumerate() {
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = 0;
+                for (position, shift) in [ 0, 3, 5 ].into_iter().enumerate() {
+                    let sample = s[(3 * i + position) * channels + ch];
+                    let code = encode_code(&[ sample ], None, position, state,
+                        |code, position, state| [ decode_mace3_code(code, position, state) ]);
+                    *byte |= code << shift;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decodes MACE 6:1 ('MAC6') data to 16-bit signed integer samples.
+///
+/// Each channel has one byte for each six samples and each byte contains three codes,
+/// starting from the highest bits. For 2 channel audio, the bytes of the channels are
+/// interleaved. The decoded samples have 8-bit precision.
+///
+/// `states` must contain one state for each channel. The states should be initialized with
+/// [`MaceState::new()`] and subsequent calls should pass in the state values from the previous
+/// call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `6 * buf.len()` and the `buf` length must be divisible by `states.len()`. Samples are
+/// interleaved for multichannel audio.
+///
+/// An error is returned if `states` is empty or if the `buf` or `out_samples` length isn't
+/// correct. If an error is returned, `states` and `out_samples` are left unmodified.
+pub fn decode_mace6(buf: &[u8], states: &mut [MaceState], out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    if buf.len() % channels != 0 || buf.len().checked_mul(6) != Some(out_samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.chunks_exact(channels).zip(out_samples.chunks_exact_mut(6 * channels)) {
+        for (ch, (byte, state)) in b.iter().zip(states.iter_mut()).enumerate() {
+            let codes = [ byte >> 5, (byte >> 3) & 3, byte & 7 ];
+            for (position, code) in codes.into_iter().enumerate() {
+                let samples = decode_mace6_code(code, position, state);
+                out[2 * position * channels + ch] = samples[0];
+                out[(2 * position + 1) * channels + ch] = samples[1];
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to MACE 6:1 ('MAC6') data.
+///
+/// The byte layout is described in [`decode_mace6()`]. The code with the smallest error is
+/// selected for each pair of samples, including the error of the best following code, because
+/// the output of each code depends on the next code.
+///
+/// `states` must contain one state for each channel. The states should be initialized with
+/// [`MaceState::new()`] and subsequent calls should pass in the state values from the previous
+/// call.
+///
+/// This function outputs encoded bytes to `out_buf`. The `samples` length must be
+/// `6 * out_buf.len()` and the `out_buf` length must be divisible by `states.len()`.
+/// Samples are interleaved for multichannel audio.
+///
+/// An error is returned if `states` is empty or if the `samples` or `out_buf` length isn't
+/// correct. If an error is returned, `states` and `out_buf` are left unmodified.
+pub fn encode_mace6(samples: &[i16], states: &mut [MaceState], out_buf: &mut [u8])
+    -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    if out_buf.len() % channels != 0 || out_buf.len().checked_mul(6) != Some(samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    // returns the sample pair of a code, or None after the last code
+    let pair = |code_index: usize, ch: usize| {
+        let index = 2 * code_index * channels + ch;
+        Some([ *samples.get(index)?, *samples.get(index + channels)? ])
+    };
+    for (b, out) in out_buf.chunks_exact_mut(channels).enumerate() {
+        for (ch, (byte, state)) in out.iter_mut().zip(states.iter_mut()).enumerate() {
+            *byte = 0;
+            for (position, shift) in [ 5, 3, 0 ].into_iter().enumerate() {
+                let code_index = 3 * b + position;
+                let current = pair(code_index, ch).unwrap_or([ 0, 0 ]);
+                let next = pair(code_index + 1, ch);
+                let code = encode_code(&current, next.as_ref(), position, state,
+                    decode_mace6_code);
+                *byte |= code << shift;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signal::tone;
+
+    #[test]
+    fn test_decode_mace3() {
+        // codes 3, 3 and 0: the step 330 is 257 with 8-bit precision, the 2-bit code 3 is
+        // -1 - 114 from table row 222 >> 4 = 13 and the leaky level is 330 - 330 / 8 = 289
+        let mut states = [ MaceState::new() ];
+        let mut out = [0i16; 6];
+        assert!(decode_mace3(&[ 0x1b, 0xff ], &mut states, &mut out).is_ok());
+        assert_eq!(out, [ 257, 0, 0, 0, 0, -1 ]);
+        assert_eq!((states[0].index, states[0].level), (121, -38));
+
+        // the expected samples are from a model of the FFmpeg decoder
+        let buf = [
+            0xA5, 0x4D, 0xCA, 0x18, 0x25, 0x30, 0xBB, 0x1D,
+            0x6D, 0x13, 0x2C, 0xDE, 0xD6, 0x23, 0x7B, 0x2E
+        ];
+        let mut states = [ MaceState::new(), MaceState::new() ];
+        let mut out = [0i16; 48];
+        assert!(decode_mace3(&buf, &mut states, &mut out).is_ok());
+        assert_eq!(out, [
+            -1, 0, -1, 257, -258, 0, -515, 0, -1, 0, 257, 0,
+            -258, 514, -1, 257, 257, -1, 257, -772, -1, -772, 257, -515,
+            -515, -1543, 257, -1, 2570, 514, 6168, -258, 1028, -515, 2056, -1286,
+            -772, 771, -5141, 0, -7968, 3084, 1542, 771, 4112, 3855, 7710, 5654,
+        ]);
+
+        assert!(matches!(decode_mace3(&buf, &mut [], &mut out), Err(Error::InvalidChannels)));
+        assert!(matches!(decode_mace3(&buf, &mut states, &mut out[..47]),
+            Err(Error::InvalidBufferSize)));
+        // 16 bytes can't be split to 3 channels
+        let mut states = [ MaceState::new(), MaceState::new(), MaceState::new() ];
+        assert!(matches!(decode_mace3(&buf, &mut states, &mut out),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_encode_mace3() {
+        let mut samples = [0i16; 240];
+        for (i, value) in samples.iter_mut().enumerate() {
+            *value = tone(i / 2);
+        }
+        let mut encoders = [ MaceState::new(), MaceState::new() ];
+        let mut buf = [0u8; 80];
+        assert!(encode_mace3(&samples, &mut encoders, &mut buf).is_ok());
+        let mut decoders = [ MaceState::new(), MaceState::new() ];
+        let mut out = [0i16; 240];
+        assert!(decode_mace3(&buf, &mut decoders, &mut out).is_ok());
+        assert_eq!(encoders, decoders);
+        for (s, d) in samples[60..].iter().zip(&out[60..]) {
+            assert!((i32::from(*s) - i32::from(*d)).abs() < 3072);
+        }
+
+        assert!(matches!(encode_mace3(&samples, &mut [], &mut buf), Err(Error::InvalidChannels)));
+        assert!(matches!(encode_mace3(&samples[..239], &mut encoders, &mut buf),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut states = [ MaceState::new() ];
+        assert!(states[0].is_default());
+        let mut out = [0i16; 6];
+        assert!(decode_mace6(&[ 0x60 ], &mut states, &mut out).is_ok());
+        let mut state = states[0].clone();
+        assert!(!state.is_default());
+        let mut buf = [0u8; MaceState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 179, 0, 3, 0, 0xee, 0x05, 33, 0, 59, 0 ]);
+        assert!(matches!(MaceState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+
+        buf[0..2].copy_from_slice(&16383i16.to_le_bytes());
+        assert!(MaceState::from_bytes(&buf).is_ok());
+        buf[0..2].copy_from_slice(&16384i16.to_le_bytes());
+        assert!(matches!(MaceState::from_bytes(&buf), Err(Error::InvalidData)));
+        buf[0..2].copy_from_slice(&(-1i16).to_le_bytes());
+        assert!(matches!(MaceState::from_bytes(&buf), Err(Error::InvalidData)));
+        assert!(matches!(MaceState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 11]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_mace6() {
+        // codes 3, 0 and 0: the integrator factor grows by 506 for each code with the same sign
+        let mut states = [ MaceState::new() ];
+        let mut out = [0i16; 6];
+        assert!(decode_mace6(&[ 0x60 ], &mut states, &mut out).is_ok());
+        assert_eq!(out, [ 0; 6 ]);
+        assert_eq!(states[0], MaceState { index: 179, level: 3, factor: 1518, previous: 33,
+            prev2: 59 });
+
+        // the expected samples are from a model of the FFmpeg decoder
+        let mut states = [ MaceState::new() ];
+        let mut out = [0i16; 72];
+        assert!(decode_mace6(&[ 0xFF, 0xE4, 0x60, 0x00, 0x9C, 0x7B, 0x00, 0x1B, 0x03, 0xE4,
+            0x9C, 0x7B ], &mut states, &mut out).is_ok());
+        assert_eq!(out, [
+            -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+            -1, 0, 0, 257, 257, 0, 0, 0, 0, 0, 0, 0,
+            0, -1, -258, -258, -515, -515, -258, 0, 257, 514, 771, 1028,
+            1542, 1799, 1799, 1028, 771, 771, 514, 514, 257, 0, 257, 771,
+            1285, 1542, 1542, 1028, 1285, 2056, 2313, 2056, 1542, 771, -1, -1286,
+            -3085, -5655, -6940, -6940, -7197, -8225, -5912, -258, 3341, 5911, 8224, 10537,
+        ]);
+
+        let buf = [ 0xC2, 0x34, 0x7F, 0x06, 0x6E, 0xD0, 0x8F, 0x5D, 0xC7, 0x51, 0x24, 0x47 ];
+        let mut states = [ MaceState::new(), MaceState::new() ];
+        assert!(decode_mace6(&buf, &mut states, &mut out).is_ok());
+        assert_eq!(out, [
+            -1, 0, -1, 0, -1, 0, -1, -1, 0, -1, 0, -258,
+            0, -258, 257, -1, 0, -1, 0, 0, 0, 0, -1, -1,
+            -1, -1, 0, -1, 257, -258, 514, -258, 514, -258, 0, -1,
+            -1, -1, -772, 0, -772, 257, -1, 257, 0, 0, 257, -258,
+            257, -258, -258, -1, -515, 0, -258, -1, -1, -1, 0, -1,
+            0, 0, 0, 514, 257, 771, 514, 771, 257, 514, -258, 257,
+        ]);
+
+        assert!(matches!(decode_mace6(&buf, &mut [], &mut out), Err(Error::InvalidChannels)));
+        assert!(matches!(decode_mace6(&buf, &mut states, &mut out[..71]),
+            Err(Error::InvalidBufferSize)));
+        let mut states = [ MaceState::new(), MaceState::new(), MaceState::new(),
+            MaceState::new(), MaceState::new() ];
+        assert!(matches!(decode_mace6(&buf, &mut states, &mut out),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_encode_mace6() {
+        let mut samples = [0i16; 240];
+        for (i, value) in samples.iter_mut().enumerate() {
+            *value = tone(i / 4);
+        }
+        let mut encoders = [ MaceState::new(), MaceState::new() ];
+        let mut buf = [0u8; 40];
+        assert!(encode_mace6(&samples, &mut encoders, &mut buf).is_ok());
+        let mut decoders = [ MaceState::new(), MaceState::new() ];
+        let mut out = [0i16; 240];
+        assert!(decode_mace6(&buf, &mut decoders, &mut out).is_ok());
+        assert_eq!(encoders, decoders);
+        // 1.33 bits per sample can't follow the tone closely
+        for (s, d) in samples[60..].iter().zip(&out[60..]) {
+            assert!((i32::from(*s) - i32::from(*d)).abs() < 5120);
+        }
+
+        assert!(matches!(encode_mace6(&samples, &mut [], &mut buf), Err(Error::InvalidChannels)));
+        assert!(matches!(encode_mace6(&samples[..239], &mut encoders, &mut buf),
+            Err(Error::InvalidBufferSize)));
+    }
+}
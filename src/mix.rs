@@ -0,0 +1,72 @@
+
+use crate::CompandingLaw;
+use crate::Error;
+
+/// Mixes G.711 A-law or μ-law encoded streams to one encoded stream.
+///
+/// Each sample of `inputs` is decoded, the decoded values are summed, the sum is multiplied
+/// by `gain_q15` and the result is encoded to `out_buf`. This is done in one pass.
+///
+/// `gain_q15` is a Q15 fixed-point gain, where 32768 is 1.0 (no attenuation) and 16384 is
+/// 0.5 (-6 dB). `None` means no attenuation. The result is saturated to the 16-bit range
+/// before encoding.
+///
+/// All `inputs` must have the same length as `out_buf`. If `inputs` is empty,
+/// `out_buf` is filled with encoded silence.
+///
+/// An error is returned if the lengths don't match.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn mix_companded<L: CompandingLaw>(inputs: &[&[u8]], gain_q15: Option<u16>,
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    if inputs.iter().any(|input| input.len() != out_buf.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (i, out) in out_buf.iter_mut().enumerate() {
+        // the sum fits in i64 even for a huge number of inputs
+        let mut sum: i64 = inputs.iter().map(|input| i64::from(L::decode(input[i]))).sum();
+        if let Some(gain) = gain_q15 {
+            sum = (sum * i64::from(gain)) >> 15;
+        }
+        #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+        {
+        *out = L::encode(sum.clamp(-32768, 32767) as i16);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ALaw, ULaw};
+    use crate::{decode_ulaw, encode_alaw, encode_ulaw};
+
+    #[test]
+    fn test_mix_companded() {
+        let a = [ encode_ulaw(1000), encode_ulaw(-1000), encode_ulaw(30000) ];
+        let b = [ encode_ulaw(2000), encode_ulaw(1000), encode_ulaw(30000) ];
+        let mut out = [0u8; 3];
+        assert!(mix_companded::<ULaw>(&[ &a, &b ], None, &mut out).is_ok());
+        let expected_0 = decode_ulaw(a[0]) + decode_ulaw(b[0]);
+        assert_eq!(out, [ encode_ulaw(expected_0), encode_ulaw(0), encode_ulaw(32767) ]);
+
+        // attenuation by half
+        assert!(mix_companded::<ULaw>(&[ &a, &b ], Some(16384), &mut out).is_ok());
+        assert_eq!(out[0], encode_ulaw(expected_0 / 2));
+
+        // a-law and a single input
+        let c = [ encode_alaw(500) ];
+        let mut out = [0u8; 1];
+        assert!(mix_companded::<ALaw>(&[ &c ], None, &mut out).is_ok());
+        assert_eq!(out, c);
+
+        // no inputs is silence
+        assert!(mix_companded::<ALaw>(&[], None, &mut out).is_ok());
+        assert_eq!(out, [ encode_alaw(0) ]);
+
+        // invalid length
+        assert!(matches!(mix_companded::<ULaw>(&[ &a, &b[..2] ], None, &mut [0u8; 3]),
+            Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::Error;
+
+// NICAM near-instantaneous companding as used in NICAM-728 broadcast stereo sound
+// (ETSI EN 300 163). The source samples have 14 bits and each block of 32 samples is coded
+// with 10-bit values. The block scale selects which 10 bits of the 14-bit samples are
+// transmitted: the coding range 1 is for the loudest blocks and drops the 4 lowest bits,
+// the coding range 5 is for the quietest blocks and drops the 4 highest bits, which are
+// all sign bits. Error protection, the signalling of the scale in the parity bits and
+// the frame interleaving aren't implemented here.
+
+/// Number of samples in a NICAM companding block for one channel.
+pub const NICAM_SAMPLES_PER_BLOCK: usize = 32;
+
+/// Encodes a block of 32 16-bit signed integer samples to 10-bit NICAM values.
+///
+/// The samples are reduced to 14 bits and the smallest shift (0 to 4), which keeps every
+/// value in the 10-bit range is selected. The shift is returned. It is the number of dropped
+/// low bits of the 14-bit samples, so the NICAM coding range is `5 - shift`.
+///
+/// This function outputs the 10-bit values (-512..=511) to `out_values`. The values are
+/// rounded to the nearest value and clamped to the 10-bit range.
+pub fn encode_nicam_block(samples: &[i16; 32], out_values: &mut [i16; 32]) -> u8 {
+    let max = samples.iter().map(|s| i32::from(*s).max(-i32::from(*s) - 1)).max().unwrap_or(0);
+    // 14-bit samples fit in 10 bits if they're in -512..=511
+    let mut shift = 0;
+    while shift < 4 && (max >> (2 + shift)) > 511 {
+        shift += 1;
+    }
+    let total_shift = 2 + shift;
+    for (out, sample) in out_values.iter_mut().zip(samples.iter()) {
+        let value = ((i32::from(*sample) + (1 << (total_shift - 1))) >> total_shift)
+            .clamp(-512, 511);
+        *out = i16::try_from(value).unwrap_or(0);
+    }
+    shift
+}
+
+/// Decodes a block of 32 10-bit NICAM values to 16-bit signed integer samples.
+///
+/// `shift` is the number of dropped low bits of the 14-bit samples (0 to 4), as returned by
+/// [`encode_nicam_block()`]. Values outside the 10-bit range are clamped to -512..=511.
+///
+/// This function outputs decoded samples to `out_samples`. The lowest 2 bits of the decoded
+/// samples are always zero.
+///
+/// An error is returned if `shift` is greater than 4.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_nicam_block(values: &[i16; 32], shift: u8, out_samples: &mut [i16; 32])
+    -> Result<(), Error> {
+
+    if shift > 4 {
+        return Err(Error::InvalidData);
+    }
+    for (out, value) in out_samples.iter_mut().zip(values.iter()) {
+        *out = (*value).clamp(-512, 511) << (2 + shift);
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples of one channel to 10-bit NICAM values.
+///
+/// This works like [`encode_nicam_block()`] for each block of 32 samples. The `samples`
+/// length must be divisible by 32.
+///
+/// This function outputs the 10-bit values to `out_values` and the shift of each block
+/// to `out_shifts`. The `out_values` length must be the same as the `samples` length and
+/// the `out_shifts` length must be `samples.len() / 32`.
+///
+/// An error is returned if the `samples`, `out_values` or `out_shifts` length isn't correct.
+/// If an error is returned, `out_values` and `out_shifts` are left unmodified.
+pub fn encode_nicam(samples: &[i16], out_values: &mut [i16], out_shifts: &mut [u8])
+    -> Result<(), Error> {
+
+    if samples.len() % NICAM_SAMPLES_PER_BLOCK != 0 || out_values.len() != samples.len() ||
+        out_shifts.len() != samples.len() / NICAM_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    for ((block, out), shift) in samples.chunks_exact(NICAM_SAMPLES_PER_BLOCK)
+        .zip(out_values.chunks_exact_mut(NICAM_SAMPLES_PER_BLOCK))
+        .zip(out_shifts.iter_mut()) {
+        let (Ok(block), Ok(out)) = (block.try_into(), out.try_into()) else {
+            return Err(Error::InvalidBufferSize);
+        };
+        *shift = encode_nicam_block(block, out);
+    }
+    Ok(())
+}
+
+/// Decodes 10-bit NICAM values of one channel to 16-bit signed integer samples.
+///
+/// This works like [`decode_nicam_block()`] for each block of 32 values. The `values`
+/// length must be divisible by 32 and the `shifts` length must be `values.len() / 32`.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// the same as the `values` length.
+///
+/// An error is returned if a shift is greater than 4 or if the `values`, `shifts` or
+/// `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_nicam(values: &[i16], shifts: &[u8], out_samples: &mut [i16]) -> Result<(), Error> {
+    if values.len() % NICAM_SAMPLES_PER_BLOCK != 0 || out_samples.len() != values.len() ||
+        shifts.len() != values.len() / NICAM_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    if shifts.iter().any(|s| *s > 4) {
+        return Err(Error::InvalidData);
+    }
+    for ((block, out), shift) in values.chunks_exact(NICAM_SAMPLES_PER_BLOCK)
+        .zip(out_samples.chunks_exact_mut(NICAM_SAMPLES_PER_BLOCK))
+        .zip(shifts.iter()) {
+        let (Ok(block), Ok(out)) = (block.try_into(), out.try_into()) else {
+            return Err(Error::InvalidBufferSize);
+        };
+        decode_nicam_block(block, *shift, out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_nicam_block() {
+        // quiet blocks keep all 14 bits
+        let mut samples = [0i16; 32];
+        samples[0] = 2044;
+        samples[1] = -2048;
+        samples[2] = 7;
+        let mut values = [0i16; 32];
+        assert_eq!(encode_nicam_block(&samples, &mut values), 0);
+        assert_eq!(values[..3], [ 511, -512, 2 ]);
+
+        // one larger value selects a larger shift for the whole block
+        samples[3] = 2048;
+        assert_eq!(encode_nicam_block(&samples, &mut values), 1);
+        assert_eq!(values[..4], [ 256, -256, 1, 256 ]);
+
+        // loud blocks drop 4 bits, rounding is clamped
+        samples[0] = 32767;
+        samples[1] = -32768;
+        assert_eq!(encode_nicam_block(&samples, &mut values), 4);
+        assert_eq!(values[..4], [ 511, -512, 0, 32 ]);
+    }
+
+    #[test]
+    fn test_decode_nicam_block() {
+        let mut values = [0i16; 32];
+        values[0] = 511;
+        values[1] = -512;
+        values[2] = 1000;
+        let mut out = [0i16; 32];
+        assert!(decode_nicam_block(&values, 0, &mut out).is_ok());
+        assert_eq!(out[..3], [ 2044, -2048, 2044 ]);
+        assert!(decode_nicam_block(&values, 4, &mut out).is_ok());
+        assert_eq!(out[..3], [ 32704, -32768, 32704 ]);
+        assert!(matches!(decode_nicam_block(&values, 5, &mut out), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_nicam() {
+        let samples: [i16; 64] = core::array::from_fn(|i| {
+            i16::try_from(i * i * 8).unwrap_or(0)
+        });
+        let mut values = [0i16; 64];
+        let mut shifts = [0u8; 2];
+        assert!(encode_nicam(&samples, &mut values, &mut shifts).is_ok());
+        assert_eq!(shifts, [ 2, 4 ]);
+        let mut out = [0i16; 64];
+        assert!(decode_nicam(&values, &shifts, &mut out).is_ok());
+        for (s, o) in samples.iter().zip(out.iter()).take(32) {
+            assert!((i32::from(*s) - i32::from(*o)).abs() <= 8);
+        }
+        for (s, o) in samples.iter().zip(out.iter()).skip(32) {
+            assert!((i32::from(*s) - i32::from(*o)).abs() <= 32);
+        }
+
+        assert!(matches!(encode_nicam(&samples[..63], &mut values[..63], &mut shifts),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_nicam(&samples, &mut values, &mut shifts[..1]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_nicam(&values, &shifts, &mut out[..32]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_nicam(&values, &[ 0, 5 ], &mut out), Err(Error::InvalidData)));
+    }
+}
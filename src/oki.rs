@@ -0,0 +1,204 @@
+
+use crate::{CodecState, Error};
+use crate::codec_state::{StateReader, StateWriter};
+
+// OKI ADPCM uses 12-bit samples and a 49 step table, see
+// https://wiki.multimedia.cx/index.php/Dialogic_IMA_ADPCM
+const OKI_STEP_TABLE: &[i16; 49] = &[
+    16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73, 80, 88, 97, 107, 118,
+    130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658,
+    724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552
+];
+
+const OKI_INDEX_TABLE: &[i8; 8] = &[ -1, -1, -1, -1, 2, 4, 6, 8 ];
+
+/// Variant of OKI ADPCM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OkiAdpcmVariant {
+    /// Dialogic VOX and OKI MSM6295: the high nibble of each byte is decoded first and
+    /// the 12-bit signal saturates at its limits.
+    #[default]
+    Dialogic,
+    /// OKI MSM6258 (Sharp X68000): the low nibble of each byte is decoded first and
+    /// the 12-bit signal wraps around at its limits like the chip's accumulator.
+    Msm6258,
+}
+
+/// State values for the OKI ADPCM decoder.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OkiAdpcmState {
+    /// The 12-bit signal value (-2048..=2047).
+    pub signal: i16,
+    /// The step index (0..=48).
+    pub step_index: u8,
+}
+
+impl OkiAdpcmState {
+    /// Creates a new OkiAdpcmState with zero values.
+    pub fn new() -> OkiAdpcmState {
+        OkiAdpcmState { signal: 0, step_index: 0 }
+    }
+}
+
+impl CodecState for OkiAdpcmState {
+    /// 16-bit little-endian signal value followed by the step index.
+    const SERIALIZED_LEN: usize = 3;
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.i16(self.signal);
+        w.u8(self.step_index);
+        Ok(())
+    }
+
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        Ok(OkiAdpcmState {
+            signal: r.i16(),
+            step_index: r.u8(),
+        })
+    }
+}
+
+/// Decodes a 4-bit encoded OKI ADPCM value to a linear 16-bit signed integer sample value.
+///
+/// Only the lowest 4 bits of `nibble` are used and the top-most bits are ignored.
+/// The 12-bit signal is scaled to 16 bits.
+///
+/// The `state` parameter should be initialized to zero at the start of the stream.
+/// This method updates `state` with new values. Subsequent calls should pass in the state
+/// values from the previous call.
+pub fn decode_oki_adpcm(nibble: u8, variant: OkiAdpcmVariant, state: &mut OkiAdpcmState)
+    -> i16 {
+
+    let step_index = state.step_index.min(48);
+    let step = OKI_STEP_TABLE[usize::from(step_index)];
+    let mut diff = step >> 3;
+    if nibble & 4 != 0 {
+        diff += step;
+    }
+    if nibble & 2 != 0 {
+        diff += step >> 1;
+    }
+    if nibble & 1 != 0 {
+        diff += step >> 2;
+    }
+    let signal = if nibble & 8 != 0 {
+        i32::from(state.signal) - i32::from(diff)
+    } else {
+        i32::from(state.signal) + i32::from(diff)
+    };
+    let signal = match variant {
+        OkiAdpcmVariant::Dialogic => signal.clamp(-2048, 2047),
+        OkiAdpcmVariant::Msm6258 => ((signal + 2048) & 0x0fff) - 2048,
+    };
+    state.signal = i16::try_from(signal).unwrap_or(0);
+    state.step_index = step_index.saturating_add_signed(OKI_INDEX_TABLE[usize::from(nibble & 7)])
+        .min(48);
+    state.signal << 4
+}
+
+/// Decodes OKI ADPCM encoded bytes to 16-bit signed integer samples.
+///
+/// Each byte contains two samples. The `variant` selects the nibble order and
+/// the signal limit behavior.
+///
+/// The `state` parameter should be initialized to zero at the start of the stream and
+/// subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * buf.len()`.
+///
+/// An error is returned if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_oki_adpcm_slice(buf: &[u8], variant: OkiAdpcmVariant, state: &mut OkiAdpcmState,
+    out_samples: &mut [i16]) -> Result<(), Error> {
+
+    if buf.len().checked_mul(2) != Some(out_samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.iter().zip(out_samples.chunks_exact_mut(2)) {
+        let (first, second) = match variant {
+            OkiAdpcmVariant::Dialogic => (b >> 4, b & 0x0f),
+            OkiAdpcmVariant::Msm6258 => (b & 0x0f, b >> 4),
+        };
+        out[0] = decode_oki_adpcm(first, variant, state);
+        out[1] = decode_oki_adpcm(second, variant, state);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_oki_adpcm() {
+        let mut state = OkiAdpcmState::new();
+        // step 16: 2 + 16 + 8 + 4 = 30
+        assert_eq!(decode_oki_adpcm(7, OkiAdpcmVariant::Dialogic, &mut state), 30 << 4);
+        assert_eq!(state, OkiAdpcmState { signal: 30, step_index: 8 });
+        // step 34: 4 + 34 + 17 + 8 = 63
+        assert_eq!(decode_oki_adpcm(0x0f, OkiAdpcmVariant::Dialogic, &mut state), -33 << 4);
+        assert_eq!(state, OkiAdpcmState { signal: -33, step_index: 16 });
+        assert_eq!(decode_oki_adpcm(0, OkiAdpcmVariant::Dialogic, &mut state), -24 << 4);
+        assert_eq!(state.step_index, 15);
+    }
+
+    #[test]
+    fn test_decode_oki_adpcm_limits() {
+        // step 1552: 194 + 1552 + 776 + 388 = 2910
+        let mut state = OkiAdpcmState { signal: 2000, step_index: 48 };
+        assert_eq!(decode_oki_adpcm(7, OkiAdpcmVariant::Dialogic, &mut state), 2047 << 4);
+        assert_eq!(state.step_index, 48);
+        let mut state = OkiAdpcmState { signal: 2000, step_index: 48 };
+        assert_eq!(decode_oki_adpcm(7, OkiAdpcmVariant::Msm6258, &mut state),
+            (2000 + 2910 - 4096) << 4);
+        let mut state = OkiAdpcmState { signal: -2000, step_index: 48 };
+        assert_eq!(decode_oki_adpcm(0x0f, OkiAdpcmVariant::Msm6258, &mut state),
+            (-2000 - 2910 + 4096) << 4);
+        let mut state = OkiAdpcmState { signal: -2000, step_index: 48 };
+        assert_eq!(decode_oki_adpcm(0x0f, OkiAdpcmVariant::Dialogic, &mut state), -2048 << 4);
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = OkiAdpcmState::new();
+        assert!(state.is_default());
+        decode_oki_adpcm(0x0f, OkiAdpcmVariant::Dialogic, &mut state);
+        let mut buf = [0u8; OkiAdpcmState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0xe2, 0xff, 8 ]);
+        assert!(matches!(OkiAdpcmState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert!(matches!(OkiAdpcmState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 4]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_oki_adpcm_slice() {
+        let buf = [ 0x70, 0x12 ];
+        let mut state = OkiAdpcmState::new();
+        let mut out = [0i16; 4];
+        assert!(decode_oki_adpcm_slice(&buf, OkiAdpcmVariant::Dialogic, &mut state, &mut out)
+            .is_ok());
+        let mut expected_state = OkiAdpcmState::new();
+        let expected = [ 7, 0, 1, 2 ].map(|n|
+            decode_oki_adpcm(n, OkiAdpcmVariant::Dialogic, &mut expected_state));
+        assert_eq!(out, expected);
+        assert_eq!(state, expected_state);
+
+        // MSM6258 decodes the low nibble first
+        let mut state = OkiAdpcmState::new();
+        assert!(decode_oki_adpcm_slice(&buf, OkiAdpcmVariant::Msm6258, &mut state, &mut out)
+            .is_ok());
+        let mut expected_state = OkiAdpcmState::new();
+        let expected = [ 0, 7, 2, 1 ].map(|n|
+            decode_oki_adpcm(n, OkiAdpcmVariant::Msm6258, &mut expected_state));
+        assert_eq!(out, expected);
+
+        assert!(matches!(decode_oki_adpcm_slice(&buf, OkiAdpcmVariant::Dialogic, &mut state,
+            &mut out[..3]), Err(Error::InvalidBufferSize)));
+    }
+}
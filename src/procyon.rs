@@ -0,0 +1,188 @@
+use crate::{CodecState, Error};
+use crate::codec_state::{StateReader, StateWriter};
+use crate::xa::{XA_FILTER_K0, XA_FILTER_K1};
+
+// Procyon Studio ADPCM is used in Nintendo DS games. It uses the CD-XA prediction filters,
+// but the history values have 6 extra bits of precision.
+
+/// Size of a Procyon Studio ADPCM frame in bytes: 15 bytes of 4-bit samples and a header byte.
+pub const PROCYON_ADPCM_FRAME_SIZE: usize = 16;
+
+/// Number of samples in a Procyon Studio ADPCM frame.
+pub const PROCYON_ADPCM_SAMPLES_PER_FRAME: usize = 30;
+
+/// State values for the Procyon Studio ADPCM decoder.
+///
+/// The history values are 32-bit values with 6 fractional bits. They should be initialized
+/// to zeros at the start of the stream.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProcyonAdpcmState {
+    /// The previous decoded value.
+    pub previous: i32,
+    /// The value before the previous decoded value.
+    pub previous2: i32,
+}
+
+impl ProcyonAdpcmState {
+    /// Creates a new ProcyonAdpcmState with zero values.
+    pub fn new() -> ProcyonAdpcmState {
+        ProcyonAdpcmState { previous: 0, previous2: 0 }
+    }
+}
+
+impl CodecState for ProcyonAdpcmState {
+    /// 32-bit little-endian previous and previous2 values.
+    const SERIALIZED_LEN: usize = 8;
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        w.i32(self.previous);
+        w.i32(self.previous2);
+        Ok(())
+    }
+
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        Ok(ProcyonAdpcmState {
+            previous: r.i32(),
+            previous2: r.i32(),
+        })
+    }
+}
+
+/// Decodes a 16-byte Procyon Studio ADPCM frame to 16-bit signed integer samples.
+///
+/// All bytes of the frame are stored XORed with 0x80. The last byte is the header: its high
+/// nibble is the filter index and its low nibble is the shift value. Filter indexes greater
+/// than 4 are decoded as filter 0. The 4-bit samples are decoded low nibble first.
+///
+/// `state` should be initialized to zero for the first frame and subsequent calls should pass
+/// in the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The lowest 6 bits of the decoded
+/// samples are always zero.
+pub fn decode_procyon_adpcm_frame(buf: &[u8; 16], state: &mut ProcyonAdpcmState,
+    out_samples: &mut [i16; 30]) {
+
+    let header = buf[15] ^ 0x80;
+    let shift = 12 - i32::from(header & 0x0f);
+    let filter = usize::from(header >> 4);
+    let (k0, k1) = if filter < XA_FILTER_K0.len() {
+        (i64::from(XA_FILTER_K0[filter]), i64::from(XA_FILTER_K1[filter]))
+    } else {
+        (0, 0)
+    };
+    for (out, code) in out_samples.iter_mut().zip(buf[..15].iter()
+        .flat_map(|b| [ (*b ^ 0x80) << 4, (*b ^ 0x80) & 0xf0 ])) {
+        let mut value = (i64::from(i8::from_le_bytes([ code ])) >> 4) * 64 * 64;
+        if shift < 0 {
+            value <<= -shift;
+        } else {
+            value >>= shift;
+        }
+        let prediction = (i64::from(state.previous) * k0 + i64::from(state.previous2) * k1 + 32)
+            / 64;
+        let value = (prediction + value * 64).clamp(i64::from(i32::MIN), i64::from(i32::MAX));
+        state.previous2 = state.previous;
+        state.previous = i32::try_from(value).unwrap_or(0);
+        let sample = ((value + 32) / 64).clamp(-32768, 32767) / 64 * 64;
+        *out = i16::try_from(sample).unwrap_or(0);
+    }
+}
+
+/// Decodes Procyon Studio ADPCM frames of one channel to 16-bit signed integer samples.
+///
+/// `buf` must contain whole 16-byte frames.
+///
+/// `state` should be initialized to zero for the first call and subsequent calls should pass in
+/// the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `30 * buf.len() / 16`.
+///
+/// An error is returned if the `buf` length isn't a multiple of 16 or
+/// if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_procyon_adpcm(buf: &[u8], state: &mut ProcyonAdpcmState, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    if buf.len() % PROCYON_ADPCM_FRAME_SIZE != 0 || out_samples.len() !=
+        buf.len() / PROCYON_ADPCM_FRAME_SIZE * PROCYON_ADPCM_SAMPLES_PER_FRAME {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (frame, out) in buf.chunks_exact(PROCYON_ADPCM_FRAME_SIZE)
+        .zip(out_samples.chunks_exact_mut(PROCYON_ADPCM_SAMPLES_PER_FRAME)) {
+        let (Ok(frame), Ok(out)) = (frame.try_into(), out.try_into()) else {
+            return Err(Error::InvalidBufferSize);
+        };
+        decode_procyon_adpcm_frame(frame, state, out);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_procyon_adpcm_frame() {
+        // header nibble 0 shifts the code down by 12 bits, filter 0
+        let mut buf = [0x80u8; 16];
+        buf[0] = 0x80 ^ 0x17;
+        buf[15] = 0x80;
+        let mut state = ProcyonAdpcmState::new();
+        let mut out = [0i16; 30];
+        decode_procyon_adpcm_frame(&buf, &mut state, &mut out);
+        assert_eq!(out[..3], [ 0, 0, 0 ]);
+        assert_eq!(state, ProcyonAdpcmState { previous: 0, previous2: 0 });
+
+        // header nibble 12 doesn't shift the code
+        buf[15] = 0x80 ^ 0x0c;
+        decode_procyon_adpcm_frame(&buf, &mut state, &mut out);
+        assert_eq!(out[..3], [ 28672, 4096, 0 ]);
+
+        // filter 1 decays towards zero
+        buf[15] = 0x80 ^ 0x1c;
+        let mut state = ProcyonAdpcmState::new();
+        decode_procyon_adpcm_frame(&buf, &mut state, &mut out);
+        assert_eq!(out[..4], [ 28672, 30976, 28992, 27200 ]);
+
+        // negative shift amplifies and the output is clamped
+        buf[15] = 0x80 ^ 0x0f;
+        let mut state = ProcyonAdpcmState::new();
+        decode_procyon_adpcm_frame(&buf, &mut state, &mut out);
+        assert_eq!(out[..3], [ 32704, 32704, 0 ]);
+    }
+
+    #[test]
+    fn test_decode_procyon_adpcm() {
+        let mut buf = [0x80u8; 32];
+        buf[0] = 0x80 ^ 0x01;
+        buf[15] = 0x80 ^ 0x0c;
+        buf[31] = 0x80 ^ 0x0c;
+        let mut state = ProcyonAdpcmState::new();
+        let mut out = [0i16; 60];
+        assert!(decode_procyon_adpcm(&buf, &mut state, &mut out).is_ok());
+        assert_eq!(out[0], 4096);
+        assert_eq!(out[30], 0);
+        assert!(matches!(decode_procyon_adpcm(&buf[..31], &mut state, &mut out[..30]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_procyon_adpcm(&buf, &mut state, &mut out[..30]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = ProcyonAdpcmState { previous: -1000, previous2: 2000 };
+        assert!(!state.is_default());
+        let mut buf = [0u8; ProcyonAdpcmState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x18, 0xfc, 0xff, 0xff, 0xd0, 0x07, 0, 0 ]);
+        assert!(matches!(ProcyonAdpcmState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, ProcyonAdpcmState::new());
+        assert!(matches!(ProcyonAdpcmState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 9]), Err(Error::InvalidBufferSize)));
+    }
+}
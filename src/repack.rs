@@ -0,0 +1,255 @@
+
+use crate::Error;
+use crate::AdpcmImaState;
+use crate::adpcm_ima::{ValidatedImaState, apply_ima4_header};
+
+// returns the "ima4" header bytes for a state: pppppppp piiiiiii
+fn ima4_header(state: &AdpcmImaState) -> [u8; 2] {
+    let [ p0, p1 ] = state.predictor.to_be_bytes();
+    [ p0, (p1 & 0b1000_0000) | state.step_index.min(88) ]
+}
+
+/// Repacks AIFF-C / QT "ima4" compressed blocks to a WAV / MS IMA ADPCM (wav format 0x0011)
+/// compressed block without decoding and re-encoding the samples.
+///
+/// `buf` should contain one 34 byte "ima4" block for each channel (left channel block first
+/// for 2 channel audio). `states` must contain channel number of `AdpcmImaState` items (1 or 2).
+/// They are the "ima4" decoder states, which should be initialized to zero for the first call
+/// and subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs an MS IMA ADPCM block to `out_buf`. The `out_buf` length must be
+/// `36 * states.len()`.
+///
+/// The 4-bit encoded samples are copied as they are. The MS IMA ADPCM block header contains
+/// the state the "ima4" decoder would use for the block, so decoding the output block with
+/// [`decode_adpcm_ima_ms()`](crate::decode_adpcm_ima_ms) outputs the header sample followed by
+/// the same 64 samples per channel as decoding the input blocks with
+/// [`decode_adpcm_ima_ima4()`](crate::decode_adpcm_ima_ima4).
+///
+/// An error is returned if `states` has an invalid number of state objects or
+/// if the `buf` or `out_buf` length isn't correct.
+/// If an error is returned, `states` and `out_buf` are left unmodified.
+pub fn repack_adpcm_ima_ima4_to_ms(buf: &[u8], states: &mut [AdpcmImaState],
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels < 1 || channels > 2 {
+        return Err(Error::InvalidChannels);
+    }
+    if buf.len() != 34 * channels || out_buf.len() != 36 * channels {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (ch, (block, state)) in buf.chunks_exact(34).zip(states.iter_mut()).enumerate() {
+        apply_ima4_header(block[0], block[1], state);
+        let [ p0, p1 ] = state.predictor.to_le_bytes();
+        out_buf[ch*4..ch*4+4].copy_from_slice(&[ p0, p1, state.step_index, 0 ]);
+        // MS IMA ADPCM interleaves 4 byte groups of each channel
+        let mut local = ValidatedImaState::load(state);
+        for (i, group) in block[2..].chunks_exact(4).enumerate() {
+            let pos = channels*4 + i*4*channels + ch*4;
+            out_buf[pos..pos+4].copy_from_slice(group);
+            for b in group {
+                local.decode_byte(*b);
+            }
+        }
+        local.store(state);
+    }
+    Ok(())
+}
+
+/// Repacks a WAV / MS IMA ADPCM (wav format 0x0011) compressed block to AIFF-C / QT "ima4"
+/// compressed blocks without decoding and re-encoding the samples.
+///
+/// `is_stereo` should be `false` for 1 channel (mono) audio and `true` for
+/// 2 channel (stereo) audio. The `buf` length must be `4 + 32 * n` for 1 channel audio and
+/// `8 + 64 * n` for 2 channel audio, where `n` is 1 or greater, so that each channel contains
+/// a multiple of 64 encoded samples. `states` must contain channel number of `AdpcmImaState`
+/// items (1 or 2). They are the "ima4" decoder states, which should be initialized to zero for
+/// the first call and subsequent calls should pass in the state values from the previous call.
+///
+/// This function outputs `n` blocks per channel to `out_buf`, so the `out_buf` length must be
+/// `34 * n * states.len()`. The blocks of 2 channel audio are interleaved (left channel
+/// block first).
+///
+/// The "ima4" format can't represent the header sample of an MS IMA ADPCM block, so it isn't
+/// included in the output. Decoding the output blocks with
+/// [`decode_adpcm_ima_ima4()`](crate::decode_adpcm_ima_ima4) outputs the same samples as
+/// decoding the input block with [`decode_adpcm_ima_ms()`](crate::decode_adpcm_ima_ms), except
+/// the header sample.
+///
+/// The "ima4" block header stores only the top 9 bits of the predictor, so the block can be
+/// repacked only if its header predictor matches the decoder state in `states`
+/// or if the lowest 7 bits of the header predictor are zero.
+///
+/// An error is returned if `states` has an invalid number of state objects,
+/// if the `buf` or `out_buf` length isn't correct or
+/// if the block can't be repacked without changing the decoded samples (`Error::InvalidData`).
+/// If an error is returned, `states` and `out_buf` are left unmodified.
+pub fn repack_adpcm_ima_ms_to_ima4(buf: &[u8], is_stereo: bool, states: &mut [AdpcmImaState],
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    if states.len() != channels {
+        return Err(Error::InvalidChannels);
+    }
+    if buf.len() < 4*channels || (buf.len() - 4*channels) % (32*channels) != 0 ||
+        buf.len() == 4*channels || buf.len() > 0xffff {
+        return Err(Error::InvalidBufferSize);
+    }
+    let block_count = (buf.len() - 4*channels) / (32*channels);
+    if out_buf.len() != 34 * block_count * channels {
+        return Err(Error::InvalidBufferSize);
+    }
+    // check that the "ima4" decoder will start from the MS IMA ADPCM header state
+    let mut header_states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+    for ch in 0..channels {
+        let header_state = AdpcmImaState {
+            predictor: i16::from_le_bytes([ buf[ch*4], buf[ch*4+1] ]),
+            step_index: buf[ch*4+2].min(88),
+        };
+        let [ h0, h1 ] = ima4_header(&header_state);
+        let mut decoder_state = states[ch].clone();
+        apply_ima4_header(h0, h1, &mut decoder_state);
+        if decoder_state != header_state {
+            return Err(Error::InvalidData);
+        }
+        header_states[ch] = header_state;
+    }
+    for ch in 0..channels {
+        let mut local = ValidatedImaState::load(&header_states[ch]);
+        for block_index in 0..block_count {
+            let mut block_state = AdpcmImaState::new();
+            local.store(&mut block_state);
+            let out_index = (block_index*channels + ch) * 34;
+            let out = &mut out_buf[out_index..out_index+34];
+            out[0..2].copy_from_slice(&ima4_header(&block_state));
+            // each "ima4" block contains 8 groups of 4 bytes
+            for (i, out_group) in out[2..].chunks_exact_mut(4).enumerate() {
+                let pos = channels*4 + (block_index*8 + i)*4*channels + ch*4;
+                out_group.copy_from_slice(&buf[pos..pos+4]);
+                for b in out_group.iter() {
+                    local.decode_byte(*b);
+                }
+            }
+        }
+        local.store(&mut states[ch]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_adpcm_ima_ima4, decode_adpcm_ima_ms, encode_adpcm_ima_ima4,
+        encode_adpcm_ima_ms};
+
+    fn test_samples(len: i32, seed: i32) -> impl Iterator<Item = i16> {
+        #[allow(clippy::cast_possible_truncation)] // values are in the i16 range
+        (0..len).map(move |i| ((i * seed * 37) % 20000 - 10000) as i16)
+    }
+
+    #[test]
+    fn test_repack_adpcm_ima_ima4_to_ms() {
+        for channels in 1..=2 {
+            let mut encoder_states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+            let mut ima4_states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+            let mut repack_states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+            let mut samples = test_samples(64*8, 7);
+            for _ in 0..4 {
+                let mut blocks = [0u8; 68];
+                let mut expected = [[0i16; 64]; 2];
+                for ch in 0..channels {
+                    let mut block_samples = [0i16; 64];
+                    block_samples.iter_mut().zip(&mut samples).for_each(|(s, v)| *s = v);
+                    let mut block = [0u8; 34];
+                    encode_adpcm_ima_ima4(&block_samples, &mut encoder_states[ch], &mut block);
+                    decode_adpcm_ima_ima4(&block, &mut ima4_states[ch], &mut expected[ch]);
+                    blocks[ch*34..ch*34+34].copy_from_slice(&block);
+                }
+                let mut ms_block = [0u8; 72];
+                assert!(repack_adpcm_ima_ima4_to_ms(&blocks[..34*channels],
+                    &mut repack_states[..channels], &mut ms_block[..36*channels]).is_ok());
+                assert_eq!(repack_states, ima4_states);
+                let mut decoded = [0i16; 130];
+                assert!(decode_adpcm_ima_ms(&ms_block[..36*channels], channels == 2,
+                    &mut decoded[..65*channels]).is_ok());
+                for (ch, expected) in expected.iter().enumerate().take(channels) {
+                    assert!(decoded[channels..65*channels].iter().skip(ch).step_by(channels)
+                        .eq(expected.iter()));
+                }
+            }
+        }
+        // invalid lengths and channels
+        let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+        let buf = [0u8; 68];
+        let mut out_buf = [0u8; 72];
+        assert!(matches!(repack_adpcm_ima_ima4_to_ms(&buf[..34], &mut states, &mut out_buf),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(repack_adpcm_ima_ima4_to_ms(&buf, &mut states, &mut out_buf[..71]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(repack_adpcm_ima_ima4_to_ms(&buf, &mut [], &mut out_buf),
+            Err(Error::InvalidChannels)));
+    }
+
+    #[test]
+    fn test_repack_adpcm_ima_ms_to_ima4() {
+        for channels in 1..=2 {
+            // 2 "ima4" blocks per channel
+            let block_len = 4*channels + 64*channels;
+            let sample_len = channels + 128*channels;
+            let mut samples = [0i16; 258];
+            samples.iter_mut().zip(test_samples(258, 11)).for_each(|(s, v)| *s = v);
+            // the header sample must be representable in the "ima4" header
+            samples[..channels].fill(0x1280);
+            let mut encoder_states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+            let mut ms_block = [0u8; 136];
+            assert!(encode_adpcm_ima_ms(&samples[..sample_len], &mut encoder_states[..channels],
+                &mut ms_block[..block_len]).is_ok());
+            let mut expected = [0i16; 258];
+            assert!(decode_adpcm_ima_ms(&ms_block[..block_len], channels == 2,
+                &mut expected[..sample_len]).is_ok());
+
+            let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+            let mut ima4_blocks = [0u8; 136];
+            assert!(repack_adpcm_ima_ms_to_ima4(&ms_block[..block_len], channels == 2,
+                &mut states[..channels], &mut ima4_blocks[..68*channels]).is_ok());
+            let mut ima4_states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+            for (i, block) in ima4_blocks[..68*channels].chunks_exact(34).enumerate() {
+                let (block_index, ch) = (i / channels, i % channels);
+                let mut decoded = [0i16; 64];
+                let Ok(block) = block.try_into() else { return };
+                decode_adpcm_ima_ima4(block, &mut ima4_states[ch], &mut decoded);
+                let start = channels + block_index*64*channels + ch;
+                assert!(expected[start..sample_len].iter().step_by(channels).take(64)
+                    .eq(decoded.iter()));
+            }
+            assert_eq!(states, ima4_states);
+
+            // the header predictor can't be represented
+            let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+            ms_block[0] = 0x01;
+            let mut out_buf = ima4_blocks;
+            assert!(matches!(repack_adpcm_ima_ms_to_ima4(&ms_block[..block_len], channels == 2,
+                &mut states[..channels], &mut out_buf[..68*channels]), Err(Error::InvalidData)));
+            assert_eq!(out_buf, ima4_blocks);
+            assert_eq!(states, [ AdpcmImaState::new(), AdpcmImaState::new() ]);
+        }
+        // invalid lengths and channels
+        let mut states = [ AdpcmImaState::new() ];
+        let buf = [0u8; 40];
+        let mut out_buf = [0u8; 34];
+        assert!(matches!(repack_adpcm_ima_ms_to_ima4(&buf[..36], false, &mut states,
+            &mut out_buf[..33]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(repack_adpcm_ima_ms_to_ima4(&buf[..4], false, &mut states,
+            &mut out_buf[..0]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(repack_adpcm_ima_ms_to_ima4(&buf, false, &mut states, &mut out_buf),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(repack_adpcm_ima_ms_to_ima4(&buf[..36], true, &mut states,
+            &mut out_buf), Err(Error::InvalidChannels)));
+        assert!(repack_adpcm_ima_ms_to_ima4(&buf[..36], false, &mut states, &mut out_buf).is_ok());
+    }
+}
@@ -0,0 +1,87 @@
+
+use crate::AdpcmImaState;
+use crate::adpcm_ima::{ValidatedImaState, apply_ima4_header};
+
+/// Returns `true` if the encoder state and the decoder state have diverged.
+///
+/// The states are compared after clamping the step indexes to 0..=88, because
+/// the encoder and decoder clamp them the same way.
+pub fn adpcm_ima_states_diverged(encoder_state: &AdpcmImaState, decoder_state: &AdpcmImaState)
+    -> bool {
+    encoder_state.predictor != decoder_state.predictor ||
+        encoder_state.step_index.min(88) != decoder_state.step_index.min(88)
+}
+
+/// Encodes 16-bit signed integer samples to an AIFF-C / QT "ima4" compressed block, which
+/// resynchronizes a decoder whose state has drifted from the encoder state.
+///
+/// `encoder_state` is the encoder's current state and `decoder_state` is the state reported
+/// by the decoder after it has decoded the previous block.
+///
+/// The "ima4" decoder may keep its own predictor instead of the predictor in the block header
+/// (see [`decode_adpcm_ima_ima4()`](crate::decode_adpcm_ima_ima4)), so a normal block doesn't
+/// always resynchronize a diverged decoder. This function adjusts `encoder_state` to the state
+/// the decoder will have after reading the block header, so that after decoding this block,
+/// the decoder state matches `encoder_state`.
+///
+/// MS IMA ADPCM blocks don't need this, because their headers always contain the full
+/// predictor and step index.
+pub fn encode_adpcm_ima_ima4_resync(samples: &[i16; 64], encoder_state: &mut AdpcmImaState,
+    decoder_state: &AdpcmImaState, out_buf: &mut [u8; 34]) {
+
+    encoder_state.step_index = encoder_state.step_index.min(88);
+    // the first two bytes are the initial state: pppppppp piiiiiii
+    #[allow(clippy::cast_sign_loss)] // sign loss is expected when splitting the values to bytes
+    {
+    out_buf[0] = (encoder_state.predictor >> 8) as u8;
+    out_buf[1] = (encoder_state.predictor & 0x80) as u8 | encoder_state.step_index;
+    }
+    // continue encoding from the state the decoder will have after the header
+    let mut state = decoder_state.clone();
+    apply_ima4_header(out_buf[0], out_buf[1], &mut state);
+    *encoder_state = state;
+    let mut local = ValidatedImaState::load(encoder_state);
+    for (out_b, pair) in out_buf[2..].iter_mut().zip(samples.chunks_exact(2)) {
+        *out_b = local.encode_byte(pair[0], pair[1]);
+    }
+    local.store(encoder_state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_adpcm_ima_ima4;
+
+    #[test]
+    fn test_adpcm_ima_states_diverged() {
+        let a = AdpcmImaState { predictor: 100, step_index: 10 };
+        assert!(!adpcm_ima_states_diverged(&a, &a));
+        assert!(adpcm_ima_states_diverged(&a, &AdpcmImaState { predictor: 101, step_index: 10 }));
+        assert!(adpcm_ima_states_diverged(&a, &AdpcmImaState { predictor: 100, step_index: 11 }));
+        assert!(!adpcm_ima_states_diverged(&AdpcmImaState { predictor: 0, step_index: 88 },
+            &AdpcmImaState { predictor: 0, step_index: 100 }));
+    }
+
+    #[test]
+    fn test_encode_adpcm_ima_ima4_resync() {
+        let mut samples = [0i16; 64];
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = i16::try_from(i * 40).unwrap_or(0);
+        }
+        // the decoder predictor is close to the header predictor, so it isn't reset
+        let mut encoder_state = AdpcmImaState { predictor: 1000, step_index: 20 };
+        let mut decoder_state = AdpcmImaState { predictor: 1020, step_index: 20 };
+        let mut buf = [0u8; 34];
+        let mut decoded = [0i16; 64];
+        encode_adpcm_ima_ima4_resync(&samples, &mut encoder_state, &decoder_state, &mut buf);
+        decode_adpcm_ima_ima4(&buf, &mut decoder_state, &mut decoded);
+        assert!(!adpcm_ima_states_diverged(&encoder_state, &decoder_state));
+
+        // the decoder is reset by the header
+        let mut encoder_state = AdpcmImaState { predictor: 1000, step_index: 20 };
+        let mut decoder_state = AdpcmImaState { predictor: -5000, step_index: 3 };
+        encode_adpcm_ima_ima4_resync(&samples, &mut encoder_state, &decoder_state, &mut buf);
+        decode_adpcm_ima_ima4(&buf, &mut decoder_state, &mut decoded);
+        assert!(!adpcm_ima_states_diverged(&encoder_state, &decoder_state));
+    }
+}
@@ -0,0 +1,152 @@
+
+// RTP payload formats as specified in RFC 3551 and RFC 4856
+
+/// RTP payload format and media type metadata for a codec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtpPayloadFormat {
+    /// Media subtype name, which is used in SDP "a=rtpmap" and in the "audio/" media type.
+    pub mime_subtype: &'static str,
+    /// Static RTP payload type, or `None` if a dynamic payload type must be used.
+    pub payload_type: Option<u8>,
+    /// RTP clock rate in Hz.
+    pub clock_rate: u32,
+    /// Number of channels.
+    pub channels: u8,
+    /// Number of bits per sample for sample-based codecs.
+    pub bits_per_sample: Option<u8>,
+    /// Number of samples per frame for frame-based codecs, `None` for sample-based codecs.
+    pub frame_samples: Option<u32>,
+    /// Default packetization interval in milliseconds.
+    pub default_packet_ms: u32,
+}
+
+/// G.711 μ-law (PCMU), payload type 0.
+pub const RTP_PCMU: RtpPayloadFormat = RtpPayloadFormat {
+    mime_subtype: "PCMU",
+    payload_type: Some(0),
+    clock_rate: 8000,
+    channels: 1,
+    bits_per_sample: Some(8),
+    frame_samples: None,
+    default_packet_ms: 20,
+};
+
+/// G.711 A-law (PCMA), payload type 8.
+pub const RTP_PCMA: RtpPayloadFormat = RtpPayloadFormat {
+    mime_subtype: "PCMA",
+    payload_type: Some(8),
+    clock_rate: 8000,
+    channels: 1,
+    bits_per_sample: Some(8),
+    frame_samples: None,
+    default_packet_ms: 20,
+};
+
+/// IMA ADPCM (DVI4) at 8000 Hz, payload type 5.
+pub const RTP_DVI4_8000: RtpPayloadFormat = RtpPayloadFormat {
+    mime_subtype: "DVI4",
+    payload_type: Some(5),
+    clock_rate: 8000,
+    channels: 1,
+    bits_per_sample: Some(4),
+    frame_samples: None,
+    default_packet_ms: 20,
+};
+
+/// IMA ADPCM (DVI4) at 16000 Hz, payload type 6.
+pub const RTP_DVI4_16000: RtpPayloadFormat = RtpPayloadFormat {
+    mime_subtype: "DVI4",
+    payload_type: Some(6),
+    clock_rate: 16000,
+    channels: 1,
+    bits_per_sample: Some(4),
+    frame_samples: None,
+    default_packet_ms: 20,
+};
+
+/// IMA ADPCM (DVI4) at 11025 Hz, payload type 16.
+pub const RTP_DVI4_11025: RtpPayloadFormat = RtpPayloadFormat {
+    mime_subtype: "DVI4",
+    payload_type: Some(16),
+    clock_rate: 11025,
+    channels: 1,
+    bits_per_sample: Some(4),
+    frame_samples: None,
+    default_packet_ms: 20,
+};
+
+/// IMA ADPCM (DVI4) at 22050 Hz, payload type 17.
+pub const RTP_DVI4_22050: RtpPayloadFormat = RtpPayloadFormat {
+    mime_subtype: "DVI4",
+    payload_type: Some(17),
+    clock_rate: 22050,
+    channels: 1,
+    bits_per_sample: Some(4),
+    frame_samples: None,
+    default_packet_ms: 20,
+};
+
+/// Comfort noise (CN) as specified in RFC 3389, payload type 13.
+///
+/// CN payloads are sent during silence periods instead of G.711 samples,
+/// see [`parse_cn_payload()`](crate::parse_cn_payload).
+pub const RTP_CN: RtpPayloadFormat = RtpPayloadFormat {
+    mime_subtype: "CN",
+    payload_type: Some(13),
+    clock_rate: 8000,
+    channels: 1,
+    bits_per_sample: None,
+    frame_samples: None,
+    default_packet_ms: 20,
+};
+
+const RTP_STATIC_FORMATS: &[RtpPayloadFormat] = &[
+    RTP_PCMU,
+    RTP_PCMA,
+    RTP_DVI4_8000,
+    RTP_DVI4_16000,
+    RTP_DVI4_11025,
+    RTP_DVI4_22050,
+    RTP_CN,
+];
+
+impl RtpPayloadFormat {
+    /// Returns the number of samples per channel in a packet of `packet_ms` milliseconds.
+    pub fn samples_per_packet(&self, packet_ms: u32) -> u32 {
+        self.clock_rate / 1000 * packet_ms + self.clock_rate % 1000 * packet_ms / 1000
+    }
+}
+
+/// Returns the payload format for a static RTP payload type supported by this crate,
+/// or `None` if the payload type isn't supported.
+pub fn rtp_payload_format(payload_type: u8) -> Option<&'static RtpPayloadFormat> {
+    RTP_STATIC_FORMATS.iter().find(|f| f.payload_type == Some(payload_type))
+}
+
+/// Returns the payload format for a media subtype name (case-insensitive) and clock rate,
+/// or `None` if the combination isn't supported.
+pub fn rtp_payload_format_by_name(mime_subtype: &str, clock_rate: u32)
+    -> Option<&'static RtpPayloadFormat> {
+    RTP_STATIC_FORMATS.iter().find(|f| {
+        f.mime_subtype.eq_ignore_ascii_case(mime_subtype) && f.clock_rate == clock_rate
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtp_payload_format() {
+        assert_eq!(rtp_payload_format(0), Some(&RTP_PCMU));
+        assert_eq!(rtp_payload_format(8), Some(&RTP_PCMA));
+        assert_eq!(rtp_payload_format(5), Some(&RTP_DVI4_8000));
+        assert_eq!(rtp_payload_format(17), Some(&RTP_DVI4_22050));
+        assert_eq!(rtp_payload_format(13), Some(&RTP_CN));
+        assert_eq!(rtp_payload_format(96), None);
+        assert_eq!(rtp_payload_format_by_name("dvi4", 16000), Some(&RTP_DVI4_16000));
+        assert_eq!(rtp_payload_format_by_name("PCMU", 16000), None);
+        assert_eq!(RTP_PCMU.samples_per_packet(20), 160);
+        assert_eq!(RTP_DVI4_11025.samples_per_packet(20), 220);
+    }
+}
@@ -0,0 +1,140 @@
+// Shared parts of the 4-bit ADPCM formats, which predict each sample from the two previous
+// decoded samples: BRR, DSP-ADPCM, SPU ADPCM, ADX and others. The formats differ in
+// the prediction, the shift and the clamping of the decoded value, so the functions take
+// a `decoded_value` closure, which returns the decoded sample of a code (-8..=7) for
+// a state without updating it.
+
+// a state with the previous two decoded samples
+pub(crate) trait SampleHistory: Clone {
+    // moves the history by one sample
+    fn push(&mut self, sample: i16);
+}
+
+// decodes a code, updates `state` and returns the decoded sample
+pub(crate) fn decode_code<S: SampleHistory>(state: &mut S, code: i32,
+    decoded_value: impl Fn(&S, i32) -> i16) -> i16 {
+
+    let sample = decoded_value(state, code);
+    state.push(sample);
+    sample
+}
+
+// returns the code (-8..=7), which decodes closest to `sample`
+pub(crate) fn best_code<S>(state: &S, sample: i16, decoded_value: impl Fn(&S, i32) -> i16)
+    -> i32 {
+
+    let mut best_code = 0;
+    let mut best_error = i32::MAX;
+    for code in -8..=7 {
+        let error = (i32::from(decoded_value(state, code)) - i32::from(sample)).abs();
+        if error < best_error {
+            best_code = code;
+            best_error = error;
+        }
+    }
+    best_code
+}
+
+// encodes `sample` with the best code, updates `state` and returns the code
+pub(crate) fn encode_sample<S: SampleHistory>(state: &mut S, sample: i16,
+    decoded_value: impl Fn(&S, i32) -> i16) -> i32 {
+
+    let code = best_code(state, sample, &decoded_value);
+    decode_code(state, code, decoded_value);
+    code
+}
+
+// returns the squared error of encoding `samples` from `state` without updating it
+pub(crate) fn squared_error<S: SampleHistory>(state: &S, samples: &[i16],
+    decoded_value: impl Fn(&S, i32) -> i16) -> u64 {
+
+    let mut trial_state = state.clone();
+    let mut error = 0u64;
+    for sample in samples {
+        let code = best_code(&trial_state, *sample, &decoded_value);
+        let diff = i64::from(decode_code(&mut trial_state, code, &decoded_value)) -
+            i64::from(*sample);
+        error += diff.unsigned_abs().pow(2);
+    }
+    error
+}
+
+// implements SampleHistory and CodecState for a state struct, which has the i16 fields
+// `previous` and `previous2`
+macro_rules! impl_sample_history {
+    ($state:ident) => {
+        impl $crate::sample_history::SampleHistory for $state {
+            fn push(&mut self, sample: i16) {
+                self.previous2 = self.previous;
+                self.previous = sample;
+            }
+        }
+
+        impl $crate::CodecState for $state {
+            /// 16-bit little-endian previous and previous2 samples.
+            const SERIALIZED_LEN: usize = 4;
+
+            fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), $crate::Error> {
+                let mut w = $crate::codec_state::StateWriter::new(out_buf,
+                    Self::SERIALIZED_LEN)?;
+                w.i16(self.previous);
+                w.i16(self.previous2);
+                Ok(())
+            }
+
+            fn from_bytes(buf: &[u8]) -> Result<Self, $crate::Error> {
+                let mut r = $crate::codec_state::StateReader::new(buf, Self::SERIALIZED_LEN)?;
+                Ok($state {
+                    previous: r.i16(),
+                    previous2: r.i16(),
+                })
+            }
+        }
+    };
+}
+
+pub(crate) use impl_sample_history;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Default)]
+    struct TestState {
+        previous: i16,
+        previous2: i16,
+    }
+
+    impl_sample_history!(TestState);
+
+    // first order prediction with a scale of 256
+    fn decoded_value(state: &TestState, code: i32) -> i16 {
+        let sample = (code * 256 + i32::from(state.previous)).clamp(-32768, 32767);
+        i16::try_from(sample).unwrap_or(0)
+    }
+
+    #[test]
+    fn test_sample_history() {
+        let mut state = TestState::default();
+        assert_eq!(decode_code(&mut state, 3, decoded_value), 768);
+        assert_eq!(decode_code(&mut state, -8, decoded_value), -1280);
+        assert_eq!(state, TestState { previous: -1280, previous2: 768 });
+
+        assert_eq!(best_code(&state, -1000, decoded_value), 1);
+        assert_eq!(best_code(&state, 30000, decoded_value), 7);
+        assert_eq!(best_code(&state, -30000, decoded_value), -8);
+
+        let samples = [ -1000, 0, 30000 ];
+        let error = squared_error(&state, &samples, decoded_value);
+        assert_eq!(state, TestState { previous: -1280, previous2: 768 });
+        let mut decoded_error = 0;
+        for sample in samples {
+            let code = encode_sample(&mut state, sample, decoded_value);
+            let diff = u64::from((i32::from(state.previous) - i32::from(sample)).unsigned_abs());
+            decoded_error += diff * diff;
+            assert_eq!(state.previous, decoded_value(&TestState { previous: state.previous2,
+                previous2: 0 }, code));
+        }
+        assert_eq!(error, decoded_error);
+    }
+}
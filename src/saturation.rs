@@ -0,0 +1,185 @@
+
+use crate::Error;
+use crate::AdpcmImaState;
+use crate::{MsImaBlock, decode_adpcm_ima, encode_adpcm_ima, encode_adpcm_ima_ima4,
+    encode_adpcm_ima_ms};
+use crate::adpcm_ima::{IMA_INDEX_TABLE, IMA_STEP_TABLE};
+
+/// Saturation counts for the instrumented IMA ADPCM encoders.
+///
+/// Saturation usually means that the input is overdriven or mis-scaled and that the encoded
+/// audio is distorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AdpcmImaSaturation {
+    /// Number of samples whose predictor was clamped to the 16-bit range.
+    pub predictor_clamped: usize,
+    /// Number of samples whose step index would have been greater than 88.
+    pub step_index_saturated: usize,
+}
+
+impl AdpcmImaSaturation {
+    /// Creates a new AdpcmImaSaturation with zero counts.
+    pub fn new() -> AdpcmImaSaturation {
+        AdpcmImaSaturation {
+            predictor_clamped: 0,
+            step_index_saturated: 0,
+        }
+    }
+
+    /// Returns `true` if any saturation has been counted.
+    pub fn is_saturated(&self) -> bool {
+        self.predictor_clamped > 0 || self.step_index_saturated > 0
+    }
+
+    // counts saturation for a nibble encoded from state
+    fn add(&mut self, nibble: u8, state: &AdpcmImaState) {
+        let step_index = state.step_index.min(88);
+        let step_size = i32::from(IMA_STEP_TABLE[usize::from(step_index)]);
+        let mut diff = step_size >> 3;
+        if (nibble & 4) != 0 { diff += step_size; }
+        if (nibble & 2) != 0 { diff += step_size >> 1; }
+        if (nibble & 1) != 0 { diff += step_size >> 2; }
+        let predictor = if (nibble & 8) != 0 {
+            i32::from(state.predictor) - diff
+        } else {
+            i32::from(state.predictor) + diff
+        };
+        if predictor < -32768 || predictor > 32767 {
+            self.predictor_clamped += 1;
+        }
+        if i16::from(step_index) + i16::from(IMA_INDEX_TABLE[usize::from(nibble & 0x0f)]) > 88 {
+            self.step_index_saturated += 1;
+        }
+    }
+
+    // counts saturation for nibbles decoded starting from state
+    fn add_nibbles(&mut self, nibbles: impl Iterator<Item = u8>, state: &AdpcmImaState) {
+        let mut state = state.clone();
+        for nibble in nibbles {
+            self.add(nibble, &state);
+            decode_adpcm_ima(nibble, &mut state);
+        }
+    }
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a 4-bit encoded IMA ADPCM value
+/// and counts saturation.
+///
+/// This works like [`encode_adpcm_ima()`](crate::encode_adpcm_ima), but `saturation` is
+/// updated if the predictor is clamped or the step index saturates.
+pub fn encode_adpcm_ima_instrumented(sample_value: i16, state: &mut AdpcmImaState,
+    saturation: &mut AdpcmImaSaturation) -> u8 {
+    let previous_state = state.clone();
+    let nibble = encode_adpcm_ima(sample_value, state);
+    saturation.add(nibble, &previous_state);
+    nibble
+}
+
+/// Encodes 16-bit signed integer samples to an AIFF-C / QT "ima4" compressed block
+/// and counts saturation.
+///
+/// This works like [`encode_adpcm_ima_ima4()`](crate::encode_adpcm_ima_ima4), but `saturation`
+/// is updated if the predictor is clamped or the step index saturates.
+pub fn encode_adpcm_ima_ima4_instrumented(samples: &[i16; 64], state: &mut AdpcmImaState,
+    out_buf: &mut [u8; 34], saturation: &mut AdpcmImaSaturation) {
+    let previous_state = state.clone();
+    encode_adpcm_ima_ima4(samples, state, out_buf);
+    saturation.add_nibbles(out_buf[2..].iter().flat_map(|b| [ *b & 0x0f, *b >> 4 ]),
+        &previous_state);
+}
+
+/// Encodes 16-bit signed integer samples to a MS / WAV IMA ADPCM (wav format 0x0011)
+/// compressed block and counts saturation.
+///
+/// This works like [`encode_adpcm_ima_ms()`](crate::encode_adpcm_ima_ms), but `saturation`
+/// is updated if the predictor is clamped or the step index saturates.
+///
+/// An error is returned if `states` has an invalid number of state objects or
+/// if the `samples` or `out_buf` length isn't correct.
+/// If an error is returned, `out_buf` and `saturation` are left unmodified.
+pub fn encode_adpcm_ima_ms_instrumented(samples: &[i16], states: &mut [AdpcmImaState],
+    out_buf: &mut [u8], saturation: &mut AdpcmImaSaturation) -> Result<(), Error> {
+    encode_adpcm_ima_ms(samples, states, out_buf)?;
+    let block = MsImaBlock::new(out_buf, states.len() == 2)?;
+    for ch in 0..block.channels() {
+        let header_state = AdpcmImaState {
+            predictor: block.predictor(ch)?,
+            step_index: block.step_index(ch)?,
+        };
+        saturation.add_nibbles(block.nibbles(ch)?, &header_state);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_adpcm_ima_instrumented() {
+        // quiet input doesn't saturate
+        let mut state = AdpcmImaState::new();
+        let mut saturation = AdpcmImaSaturation::new();
+        for s in [ 0, 10, 20, 10, 0, -10 ] {
+            encode_adpcm_ima_instrumented(s, &mut state, &mut saturation);
+        }
+        assert!(!saturation.is_saturated());
+
+        // full scale square wave saturates
+        let mut state = AdpcmImaState::new();
+        let mut encoder_state = AdpcmImaState::new();
+        for i in 0..200 {
+            let s = if (i / 20) % 2 == 0 { 32767 } else { -32768 };
+            let nibble = encode_adpcm_ima_instrumented(s, &mut state, &mut saturation);
+            assert_eq!(nibble, encode_adpcm_ima(s, &mut encoder_state));
+        }
+        assert!(saturation.predictor_clamped > 0);
+        assert!(saturation.step_index_saturated > 0);
+
+        // the predictor is clamped, but the step index is not saturated
+        let mut state = AdpcmImaState { predictor: 32000, step_index: 88 };
+        let mut saturation = AdpcmImaSaturation::new();
+        encode_adpcm_ima_instrumented(32767, &mut state, &mut saturation);
+        assert_eq!(saturation,
+            AdpcmImaSaturation { predictor_clamped: 1, step_index_saturated: 0 });
+    }
+
+    #[test]
+    fn test_encode_adpcm_ima_block_instrumented() {
+        let mut samples = [0i16; 64];
+        for (i, s) in samples.iter_mut().enumerate() {
+            *s = if (i / 8) % 2 == 0 { 32767 } else { -32768 };
+        }
+        let mut state = AdpcmImaState::new();
+        let mut expected_state = AdpcmImaState::new();
+        let mut saturation = AdpcmImaSaturation::new();
+        let mut expected_saturation = AdpcmImaSaturation::new();
+        let mut out_buf = [0u8; 34];
+        encode_adpcm_ima_ima4_instrumented(&samples, &mut state, &mut out_buf, &mut saturation);
+        for s in samples {
+            encode_adpcm_ima_instrumented(s, &mut expected_state, &mut expected_saturation);
+        }
+        assert_eq!(state, expected_state);
+        assert_eq!(saturation, expected_saturation);
+        assert!(saturation.is_saturated());
+
+        let mut states = [ AdpcmImaState::new() ];
+        let mut saturation = AdpcmImaSaturation::new();
+        let mut out_buf = [0u8; 36];
+        assert!(encode_adpcm_ima_ms_instrumented(&samples[..63], &mut states, &mut out_buf[..35],
+            &mut saturation).is_ok());
+        assert!(saturation.is_saturated());
+        let mut expected_state = AdpcmImaState { predictor: samples[0], step_index: 0 };
+        let mut expected_saturation = AdpcmImaSaturation::new();
+        for s in &samples[1..63] {
+            encode_adpcm_ima_instrumented(*s, &mut expected_state, &mut expected_saturation);
+        }
+        assert_eq!(saturation, expected_saturation);
+
+        // invalid lengths leave saturation unmodified
+        let mut saturation = AdpcmImaSaturation::new();
+        assert!(matches!(encode_adpcm_ima_ms_instrumented(&samples[..63], &mut states,
+            &mut out_buf, &mut saturation), Err(Error::InvalidBufferSize)));
+        assert_eq!(saturation, AdpcmImaSaturation::new());
+    }
+}
@@ -0,0 +1,1091 @@
+use crate::{CodecState, Error};
+use crate::codec_state::{StateReader, StateWriter};
+use crate::bitreader::{BitReader, BitWriter};
+
+// Low-complexity subband codec (SBC), based on the Bluetooth A2DP specification, appendix B.
+// The filterbanks are implemented with fixed point integer arithmetic.
+
+/// The first byte of every SBC frame.
+pub const SBC_SYNCWORD: u8 = 0x9c;
+
+/// Size of the SBC frame header in bytes.
+pub const SBC_HEADER_SIZE: usize = 4;
+
+const SAMPLE_RATES: &[u32; 4] = &[ 16000, 32000, 44100, 48000 ];
+
+// fraction bits of subband samples
+const SB_FRACTION_BITS: u32 = 12;
+
+// prototype filter coefficients of the specification (Proto_4_40 and Proto_8_80) in Q24
+const PROTO_4: &[i64; 40] = &[
+    0, 9002, 25030, 45864, 64378, 65298, 31303, -51340, 183103, 342901,
+    484455, 540125, 434140, 102885, -483548, -1302690, 2274878, 3271353, 4137877, 4728293,
+    4937792, 4728293, 4137877, 3271353, -2274878, -1302690, -483548, 102885, 434140, 540125,
+    484455, 342901, -183103, -51340, 31303, 65298, 64378, 45864, 25030, 9002,
+];
+
+const PROTO_8: &[i64; 80] = &[
+    0, 2627, 5759, 9305, 13823, 19125, 24770, 29926, 33753, 35295,
+    33463, 27121, 15136, -3000, -27678, -58673, 94951, 134711, 175464, 213863,
+    245829, 266834, 272141, 257000, 217050, 148605, 49058, -82473, -245625, -438051,
+    -655572, -892335, 1140833, 1392253, 1637043, 1865571, 2068036, 2235806, 2361452, 2439237,
+    2465497, 2439237, 2361452, 2235806, 2068036, 1865571, 1637043, 1392253, -1140833, -892335,
+    -655572, -438051, -245625, -82473, 49058, 148605, 217050, 257000, 272141, 266834,
+    245829, 213863, 175464, 134711, -94951, -58673, -27678, -3000, 15136, 27121,
+    33463, 35295, 33753, 29926, 24770, 19125, 13823, 9305, 5759, 2627,
+];
+
+// cos(n * pi / 32) in Q15
+const COS_64: &[i64; 64] = &[
+    32767, 32610, 32138, 31357, 30274, 28899, 27246, 25330, 23170, 20788, 18205,
+    15447, 12540, 9512, 6393, 3212, 0, -3212, -6393, -9512, -12540, -15447,
+    -18205, -20788, -23170, -25330, -27246, -28899, -30274, -31357, -32138, -32610, -32768,
+    -32610, -32138, -31357, -30274, -28899, -27246, -25330, -23170, -20788, -18205, -15447,
+    -12540, -9512, -6393, -3212, 0, 3212, 6393, 9512, 12540, 15447, 18205,
+    20788, 23170, 25330, 27246, 28899, 30274, 31357, 32138, 32610,
+];
+
+// loudness offsets for each sample rate, tables 12.9 and 12.10 of the specification
+const OFFSET_4: &[[i32; 4]; 4] = &[
+    [ -1, 0, 0, 0 ],
+    [ -2, 0, 0, 1 ],
+    [ -2, 0, 0, 1 ],
+    [ -2, 0, 0, 1 ],
+];
+
+const OFFSET_8: &[[i32; 8]; 4] = &[
+    [ -2, 0, 0, 0, 0, 0, 0, 1 ],
+    [ -3, 0, 0, 0, 0, 0, 1, 2 ],
+    [ -4, 0, 0, 0, 0, 0, 1, 2 ],
+    [ -4, 0, 0, 0, 0, 0, 1, 2 ],
+];
+
+/// SBC channel mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SbcChannelMode {
+    /// One channel.
+    Mono,
+    /// Two independent channels, each using the full bitpool.
+    DualChannel,
+    /// Two channels sharing the bitpool.
+    #[default]
+    Stereo,
+    /// Two channels sharing the bitpool, subbands may be coded as mid and side channels.
+    JointStereo,
+}
+
+/// SBC bit allocation method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SbcAllocationMethod {
+    /// Allocation based on scale factors and loudness offsets.
+    #[default]
+    Loudness,
+    /// Allocation based on scale factors only.
+    Snr,
+}
+
+/// Parameters of an SBC frame, stored in the frame header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SbcFrameHeader {
+    /// Sample rate: 16000, 32000, 44100 or 48000.
+    pub sample_rate: u32,
+    /// Number of blocks: 4, 8, 12 or 16.
+    pub blocks: usize,
+    /// Channel mode.
+    pub channel_mode: SbcChannelMode,
+    /// Bit allocation method.
+    pub allocation_method: SbcAllocationMethod,
+    /// Number of subbands: 4 or 8.
+    pub subbands: usize,
+    /// Bitpool size. It must be at least 2 and at most 16 times `subbands` for
+    /// mono and dual channel modes or 32 times `subbands` for stereo modes.
+    pub bitpool: u8,
+}
+
+impl SbcFrameHeader {
+    /// Returns the number of channels, 1 or 2.
+    pub fn channels(&self) -> usize {
+        if self.channel_mode == SbcChannelMode::Mono { 1 } else { 2 }
+    }
+
+    /// Returns the number of samples per channel in a frame.
+    pub fn samples_per_channel(&self) -> usize {
+        self.blocks * self.subbands
+    }
+
+    /// Returns the size of the frame in bytes, including the header.
+    pub fn frame_len(&self) -> usize {
+        let channels = self.channels();
+        let bitpool = usize::from(self.bitpool);
+        let mut bits = 4 * self.subbands * channels;
+        bits += match self.channel_mode {
+            SbcChannelMode::Mono | SbcChannelMode::DualChannel => self.blocks * channels * bitpool,
+            SbcChannelMode::Stereo => self.blocks * bitpool,
+            SbcChannelMode::JointStereo => self.subbands + self.blocks * bitpool,
+        };
+        SBC_HEADER_SIZE + bits.div_ceil(8)
+    }
+
+    fn sample_rate_index(&self) -> Option<usize> {
+        SAMPLE_RATES.iter().position(|&rate| rate == self.sample_rate)
+    }
+
+    fn is_valid(&self) -> bool {
+        let max_bitpool = match self.channel_mode {
+            SbcChannelMode::Mono | SbcChannelMode::DualChannel => 16 * self.subbands,
+            SbcChannelMode::Stereo | SbcChannelMode::JointStereo => 32 * self.subbands,
+        };
+        self.sample_rate_index().is_some()
+            && matches!(self.blocks, 4 | 8 | 12 | 16)
+            && matches!(self.subbands, 4 | 8)
+            && self.bitpool >= 2
+            && usize::from(self.bitpool) <= max_bitpool
+    }
+}
+
+/// Parses the header of an SBC frame.
+///
+/// An error is returned if `buf` is shorter than [`SBC_HEADER_SIZE`], if the first byte
+/// isn't [`SBC_SYNCWORD`] or if the bitpool is out of range. The CRC is checked by
+/// [`decode_sbc_frame()`].
+pub fn parse_sbc_frame_header(buf: &[u8]) -> Result<SbcFrameHeader, Error> {
+    if buf.len() < SBC_HEADER_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    if buf[0] != SBC_SYNCWORD {
+        return Err(Error::InvalidData);
+    }
+    let header = SbcFrameHeader {
+        sample_rate: SAMPLE_RATES[usize::from(buf[1] >> 6)],
+        blocks: 4 * (usize::from((buf[1] >> 4) & 3) + 1),
+        channel_mode: match (buf[1] >> 2) & 3 {
+            0 => SbcChannelMode::Mono,
+            1 => SbcChannelMode::DualChannel,
+            2 => SbcChannelMode::Stereo,
+            _ => SbcChannelMode::JointStereo,
+        },
+        allocation_method: if buf[1] & 2 == 0 {
+            SbcAllocationMethod::Loudness
+        } else {
+            SbcAllocationMethod::Snr
+        },
+        subbands: if buf[1] & 1 == 0 { 4 } else { 8 },
+        bitpool: buf[2],
+    };
+    if !header.is_valid() {
+        return Err(Error::InvalidData);
+    }
+    Ok(header)
+}
+
+fn header_byte(header: &SbcFrameHeader) -> u8 {
+    let mut value = u8::try_from(header.sample_rate_index().unwrap_or(0)).unwrap_or(0) << 6;
+    value |= u8::try_from(header.blocks / 4 - 1).unwrap_or(0) << 4;
+    value |= match header.channel_mode {
+        SbcChannelMode::Mono => 0,
+        SbcChannelMode::DualChannel => 1 << 2,
+        SbcChannelMode::Stereo => 2 << 2,
+        SbcChannelMode::JointStereo => 3 << 2,
+    };
+    if header.allocation_method == SbcAllocationMethod::Snr {
+        value |= 2;
+    }
+    if header.subbands == 8 {
+        value |= 1;
+    }
+    value
+}
+
+// CRC-8 with polynomial x^8 + x^4 + x^3 + x^2 + 1 of the top `count` bits of `byte`
+fn crc8_bits(mut crc: u8, byte: u8, count: u32) -> u8 {
+    for i in 0..count {
+        let bit = (byte >> (7 - i)) & 1;
+        let top = crc >> 7;
+        crc <<= 1;
+        if bit ^ top != 0 {
+            crc ^= 0x1d;
+        }
+    }
+    crc
+}
+
+// calculates the CRC of a frame: header bytes 1 and 2, join bits and scale factors
+fn frame_crc(buf: &[u8], header: &SbcFrameHeader) -> u8 {
+    let mut crc = crc8_bits(0x0f, buf[1], 8);
+    crc = crc8_bits(crc, buf[2], 8);
+    let mut bits = 4 * header.subbands * header.channels();
+    if header.channel_mode == SbcChannelMode::JointStereo {
+        bits += header.subbands;
+    }
+    for &byte in &buf[SBC_HEADER_SIZE..SBC_HEADER_SIZE + bits / 8] {
+        crc = crc8_bits(crc, byte, 8);
+    }
+    if bits % 8 != 0 {
+        crc = crc8_bits(crc, buf[SBC_HEADER_SIZE + bits / 8], 4);
+    }
+    crc
+}
+
+// calculates the number of bits for each subband of the channels in `channels`
+fn allocate_channels(header: &SbcFrameHeader, channels: &[usize],
+    scale_factors: &[[u8; 8]; 2], bits: &mut [[u32; 8]; 2]) {
+
+    let subbands = header.subbands;
+    let rate_index = header.sample_rate_index().unwrap_or(0);
+    let mut bitneed = [[0i32; 8]; 2];
+    let mut max_bitneed = 0;
+    for &ch in channels {
+        for sb in 0..subbands {
+            let scale_factor = i32::from(scale_factors[ch][sb]);
+            let need = match header.allocation_method {
+                SbcAllocationMethod::Snr => scale_factor,
+                SbcAllocationMethod::Loudness if scale_factor == 0 => -5,
+                SbcAllocationMethod::Loudness => {
+                    let offset = if subbands == 4 {
+                        OFFSET_4[rate_index][sb]
+                    } else {
+                        OFFSET_8[rate_index][sb]
+                    };
+                    let loudness = scale_factor - offset;
+                    if loudness > 0 { loudness / 2 } else { loudness }
+                },
+            };
+            bitneed[ch][sb] = need;
+            max_bitneed = max_bitneed.max(need);
+        }
+    }
+
+    // find the bit slice which fills the bitpool
+    let bitpool = i32::from(header.bitpool);
+    let mut bitcount = 0;
+    let mut slicecount = 0;
+    let mut bitslice = max_bitneed + 1;
+    loop {
+        bitslice -= 1;
+        bitcount += slicecount;
+        slicecount = 0;
+        for &ch in channels {
+            for &need in &bitneed[ch][..subbands] {
+                if need > bitslice + 1 && need < bitslice + 16 {
+                    slicecount += 1;
+                } else if need == bitslice + 1 {
+                    slicecount += 2;
+                }
+            }
+        }
+        // the bitpool size is validated so that this always terminates
+        if bitcount + slicecount >= bitpool {
+            break;
+        }
+    }
+    if bitcount + slicecount == bitpool {
+        bitcount += slicecount;
+        bitslice -= 1;
+    }
+
+    for &ch in channels {
+        for sb in 0..subbands {
+            let need = bitneed[ch][sb];
+            bits[ch][sb] = if need < bitslice + 2 {
+                0
+            } else {
+                (need - bitslice).min(16).unsigned_abs()
+            };
+        }
+    }
+
+    // distribute the remaining bits starting from the lowest subband, alternating between
+    // channels for stereo
+    let order = || (0..subbands).flat_map(|sb| channels.iter().map(move |&ch| (ch, sb)));
+    for (ch, sb) in order() {
+        if bitcount >= bitpool {
+            return;
+        }
+        if bits[ch][sb] >= 2 && bits[ch][sb] < 16 {
+            bits[ch][sb] += 1;
+            bitcount += 1;
+        } else if bitneed[ch][sb] == bitslice + 1 && bitpool > bitcount + 1 {
+            bits[ch][sb] = 2;
+            bitcount += 2;
+        }
+    }
+    for (ch, sb) in order() {
+        if bitcount >= bitpool {
+            return;
+        }
+        if bits[ch][sb] < 16 {
+            bits[ch][sb] += 1;
+            bitcount += 1;
+        }
+    }
+}
+
+fn allocate_bits(header: &SbcFrameHeader, scale_factors: &[[u8; 8]; 2], bits: &mut [[u32; 8]; 2]) {
+    match header.channel_mode {
+        SbcChannelMode::Mono => allocate_channels(header, &[ 0 ], scale_factors, bits),
+        SbcChannelMode::DualChannel => {
+            allocate_channels(header, &[ 0 ], scale_factors, bits);
+            allocate_channels(header, &[ 1 ], scale_factors, bits);
+        },
+        SbcChannelMode::Stereo | SbcChannelMode::JointStereo => {
+            allocate_channels(header, &[ 0, 1 ], scale_factors, bits);
+        },
+    }
+}
+
+// returns cos((2 * i + 1) * k * pi / (4 * subbands)) in Q15
+fn cos_q15(subbands: usize, i: usize, k: i64) -> i64 {
+    let step = if subbands == 4 { 2 } else { 1 };
+    let index = i64::try_from(2 * i + 1).unwrap_or(0) * k * step;
+    COS_64[usize::try_from(index.rem_euclid(64)).unwrap_or(0)]
+}
+
+fn proto(subbands: usize) -> &'static [i64] {
+    if subbands == 4 { PROTO_4 } else { PROTO_8 }
+}
+
+/// State values for the SBC encoder and decoder.
+///
+/// The state should be created with [`SbcState::new()`] at the start of the stream.
+/// It holds the filterbank history of both channels. The encoder and decoder each need
+/// their own state. The number of subbands shouldn't change during the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SbcState {
+    // analysis input history
+    x: [[i32; 80]; 2],
+    // synthesis history in Q12
+    v: [[i64; 160]; 2],
+}
+
+impl SbcState {
+    /// Creates a new state with zero filterbank history.
+    pub fn new() -> SbcState {
+        SbcState {
+            x: [[0; 80]; 2],
+            v: [[0; 160]; 2],
+        }
+    }
+
+    // analyzes `subbands` input samples to subband samples in Q12
+    fn analyze(&mut self, ch: usize, samples: &[i32], out: &mut [i32]) {
+        let m = out.len();
+        let proto = proto(m);
+        let x = &mut self.x[ch][..10 * m];
+        x.copy_within(..9 * m, m);
+        for (i, &sample) in samples.iter().enumerate() {
+            x[m - 1 - i] = sample;
+        }
+        let mut y = [0i64; 16];
+        for (k, yk) in y[..2 * m].iter_mut().enumerate() {
+            *yk = (0..5).map(|j| proto[k + j * 2 * m] * i64::from(x[k + j * 2 * m])).sum::<i64>()
+                >> (24 - SB_FRACTION_BITS);
+        }
+        let half = i64::try_from(m / 2).unwrap_or(0);
+        for (i, value) in out.iter_mut().enumerate() {
+            let mut sum = 0;
+            for (k, &yk) in (0i64..).zip(&y[..2 * m]) {
+                sum += cos_q15(m, i, 2 * k - 2 * half) * yk;
+            }
+            *value = i32::try_from((sum + (1 << 14)) >> 15).unwrap_or(0);
+        }
+    }
+
+    // synthesizes `subbands` output samples from subband samples in Q12
+    fn synthesize(&mut self, ch: usize, subband_samples: &[i32], out: &mut [i32]) {
+        let m = subband_samples.len();
+        let proto = proto(m);
+        let v = &mut self.v[ch][..20 * m];
+        v.copy_within(..18 * m, 2 * m);
+        let half = i64::try_from(m / 2).unwrap_or(0);
+        for (k, vk) in (0i64..).zip(v[..2 * m].iter_mut()) {
+            let mut sum = 0;
+            for (i, &sample) in subband_samples.iter().enumerate() {
+                sum += cos_q15(m, i, 2 * k + 2 * half) * i64::from(sample);
+            }
+            *vk = (sum + (1 << 14)) >> 15;
+        }
+        let gain = -i64::try_from(m).unwrap_or(0);
+        for (j, value) in out.iter_mut().enumerate() {
+            let mut sum = 0;
+            for i in 0..5 {
+                sum += v[i * 4 * m + j] * proto[i * 2 * m + j];
+                sum += v[i * 4 * m + 3 * m + j] * proto[i * 2 * m + m + j];
+            }
+            let shift = 24 + SB_FRACTION_BITS;
+            let sample = ((sum * gain) + (1 << (shift - 1))) >> shift;
+            *value = i32::try_from(sample.clamp(-32768, 32767)).unwrap_or(0);
+        }
+    }
+}
+
+impl Default for SbcState {
+    fn default() -> Self {
+        SbcState::new()
+    }
+}
+
+impl CodecState for SbcState {
+    /// 32-bit little-endian analysis history followed by 64-bit little-endian synthesis
+    /// history for both channels.
+    const SERIALIZED_LEN: usize = 2 * 80 * 4 + 2 * 160 * 8;
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut w = StateWriter::new(out_buf, Self::SERIALIZED_LEN)?;
+        for x in self.x.iter().flatten() {
+            w.i32(*x);
+        }
+        for v in self.v.iter().flatten() {
+            w.i64(*v);
+        }
+        Ok(())
+    }
+
+    /// An error is returned if the `buf` length isn't correct, if an analysis history value
+    /// doesn't fit in 16 bits or if a synthesis history value is outside the range
+    /// -2<sup>32</sup>..=2<sup>32</sup>, which the filterbank never exceeds.
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let mut r = StateReader::new(buf, Self::SERIALIZED_LEN)?;
+        let mut state = SbcState::new();
+        for x in state.x.iter_mut().flatten() {
+            *x = r.i32();
+            if i16::try_from(*x).is_err() {
+                return Err(Error::InvalidData);
+            }
+        }
+        for v in state.v.iter_mut().flatten() {
+            *v = r.i64();
+            if !(-1 << 32..=1 << 32).contains(v) {
+                return Err(Error::InvalidData);
+            }
+        }
+        Ok(state)
+    }
+}
+
+/// A parsed SBC frame: the header, the scale factors and the quantized subband samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SbcFrame {
+    /// Frame header.
+    pub header: SbcFrameHeader,
+    /// Subbands coded as mid and side channels. Only used in joint stereo mode.
+    pub join: [bool; 8],
+    /// Scale factor of each channel and subband, 0..=15.
+    pub scale_factors: [[u8; 8]; 2],
+    /// Quantized subband samples of each block, channel and subband. The number of bits
+    /// for each channel and subband is given by [`SbcFrame::bits()`].
+    pub samples: [[[u16; 8]; 2]; 16],
+}
+
+impl SbcFrame {
+    /// Returns the number of bits allocated to each channel and subband.
+    ///
+    /// The bit allocation is calculated from the header and the scale factors.
+    pub fn bits(&self) -> [[u32; 8]; 2] {
+        let mut bits = [[0u32; 8]; 2];
+        if self.is_valid() {
+            allocate_bits(&self.header, &self.scale_factors, &mut bits);
+        }
+        bits
+    }
+
+    fn is_valid(&self) -> bool {
+        self.header.is_valid() && self.scale_factors.iter().flatten().all(|sf| *sf <= 15)
+    }
+}
+
+/// Parses an SBC frame.
+///
+/// `buf` should start with a complete frame. Its length must be at least the frame length
+/// given by [`SbcFrameHeader::frame_len()`], extra bytes are ignored.
+///
+/// An error is returned if the frame header is invalid, if the CRC doesn't match or if
+/// `buf` is too short.
+pub fn parse_sbc_frame(buf: &[u8]) -> Result<SbcFrame, Error> {
+    let header = parse_sbc_frame_header(buf)?;
+    let frame_len = header.frame_len();
+    if buf.len() < frame_len {
+        return Err(Error::InvalidBufferSize);
+    }
+    if frame_crc(buf, &header) != buf[3] {
+        return Err(Error::InvalidData);
+    }
+    let channels = header.channels();
+    let subbands = header.subbands;
+    let blocks = header.blocks;
+    let mut frame = SbcFrame {
+        header,
+        join: [false; 8],
+        scale_factors: [[0; 8]; 2],
+        samples: [[[0; 8]; 2]; 16],
+    };
+    let mut reader = BitReader::new(&buf[SBC_HEADER_SIZE..frame_len]);
+    if frame.header.channel_mode == SbcChannelMode::JointStereo {
+        for value in &mut frame.join[..subbands] {
+            *value = reader.read(1).ok_or(Error::InvalidData)? != 0;
+        }
+    }
+    for channel_scale_factors in &mut frame.scale_factors[..channels] {
+        for value in &mut channel_scale_factors[..subbands] {
+            *value = u8::try_from(reader.read(4).ok_or(Error::InvalidData)?).unwrap_or(0);
+        }
+    }
+    let bits = frame.bits();
+    for block in &mut frame.samples[..blocks] {
+        for (channel_samples, channel_bits) in block[..channels].iter_mut().zip(&bits) {
+            for (value, &bits) in channel_samples[..subbands].iter_mut().zip(channel_bits) {
+                if bits > 0 {
+                    *value = u16::try_from(reader.read(bits).ok_or(Error::InvalidData)?)
+                        .unwrap_or(0);
+                }
+            }
+        }
+    }
+    Ok(frame)
+}
+
+/// Writes an SBC frame to `out_buf` and returns the number of bytes written.
+///
+/// The CRC is calculated and written to the header. Only the lowest bits of each quantized
+/// subband sample are used, as given by [`SbcFrame::bits()`], and the top-most bits are
+/// ignored. The `out_buf` length must be at least [`SbcFrameHeader::frame_len()`].
+///
+/// An error is returned if the header or the scale factors have invalid values or if
+/// `out_buf` is too short. If an error is returned, `out_buf` is left unmodified.
+pub fn write_sbc_frame(frame: &SbcFrame, out_buf: &mut [u8]) -> Result<usize, Error> {
+    if !frame.is_valid() {
+        return Err(Error::InvalidData);
+    }
+    let header = &frame.header;
+    let frame_len = header.frame_len();
+    if out_buf.len() < frame_len {
+        return Err(Error::InvalidBufferSize);
+    }
+    let out_buf = &mut out_buf[..frame_len];
+    // the bit allocation doesn't always fill the bitpool, so clear the padding
+    out_buf.fill(0);
+    let channels = header.channels();
+    let subbands = header.subbands;
+    let bits = frame.bits();
+    let mut writer = BitWriter::new(out_buf);
+    let header_bytes = [ SBC_SYNCWORD, header_byte(header), header.bitpool, 0 ];
+    for byte in header_bytes {
+        writer.write(u32::from(byte), 8).ok_or(Error::InvalidBufferSize)?;
+    }
+    if header.channel_mode == SbcChannelMode::JointStereo {
+        for &value in &frame.join[..subbands] {
+            writer.write(u32::from(value), 1).ok_or(Error::InvalidBufferSize)?;
+        }
+    }
+    for channel_scale_factors in &frame.scale_factors[..channels] {
+        for &value in &channel_scale_factors[..subbands] {
+            writer.write(u32::from(value), 4).ok_or(Error::InvalidBufferSize)?;
+        }
+    }
+    for block in &frame.samples[..header.blocks] {
+        for (channel_samples, channel_bits) in block[..channels].iter().zip(&bits) {
+            for (&value, &bits) in channel_samples[..subbands].iter().zip(channel_bits) {
+                if bits > 0 {
+                    let mask = (1u32 << bits) - 1;
+                    writer.write(u32::from(value) & mask, bits)
+                        .ok_or(Error::InvalidBufferSize)?;
+                }
+            }
+        }
+    }
+    out_buf[3] = frame_crc(out_buf, header);
+    Ok(frame_len)
+}
+
+/// Decodes the subband samples of a parsed SBC frame to 16-bit signed integer samples.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `channels() * samples_per_channel()` of the frame header. Samples are interleaved for
+/// 2 channel audio.
+///
+/// An error is returned if the header or the scale factors have invalid values or if
+/// the `out_samples` length isn't correct. If an error is returned, `state` and `out_samples`
+/// are left unmodified.
+pub fn decode_sbc_frame_samples(frame: &SbcFrame, state: &mut SbcState, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    if !frame.is_valid() {
+        return Err(Error::InvalidData);
+    }
+    let header = &frame.header;
+    let channels = header.channels();
+    let subbands = header.subbands;
+    if out_samples.len() != channels * header.samples_per_channel() {
+        return Err(Error::InvalidBufferSize);
+    }
+    let bits = frame.bits();
+
+    for (b, block) in frame.samples[..header.blocks].iter().enumerate() {
+        let mut sb_samples = [[0i32; 8]; 2];
+        for ch in 0..channels {
+            for sb in 0..subbands {
+                if bits[ch][sb] == 0 {
+                    continue;
+                }
+                let levels = (1i64 << bits[ch][sb]) - 1;
+                let code = i64::from(block[ch][sb]) & levels;
+                let scale = 1i64 << (u32::from(frame.scale_factors[ch][sb]) + 1 + SB_FRACTION_BITS);
+                let value = ((2 * code + 1) * scale) / levels - scale;
+                sb_samples[ch][sb] = i32::try_from(value).unwrap_or(0);
+            }
+        }
+        if header.channel_mode == SbcChannelMode::JointStereo {
+            let [left, right] = &mut sb_samples;
+            for ((left, right), &join) in left.iter_mut().zip(right.iter_mut()).zip(&frame.join) {
+                if join {
+                    (*left, *right) = (*left + *right, *left - *right);
+                }
+            }
+        }
+        for (ch, channel_samples) in sb_samples[..channels].iter().enumerate() {
+            let mut pcm = [0i32; 8];
+            state.synthesize(ch, &channel_samples[..subbands], &mut pcm[..subbands]);
+            for (i, &sample) in pcm[..subbands].iter().enumerate() {
+                let index = (b * subbands + i) * channels + ch;
+                out_samples[index] = i16::try_from(sample).unwrap_or(0);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decodes an SBC frame to 16-bit signed integer samples.
+///
+/// `buf` should start with a complete frame. Its length must be at least the frame length
+/// given by [`SbcFrameHeader::frame_len()`], extra bytes are ignored. The parsed frame header
+/// is returned so that the caller can advance to the next frame.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `channels() * samples_per_channel()` of the frame header, which can be read beforehand with
+/// [`parse_sbc_frame_header()`]. Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if the frame header is invalid, if the CRC doesn't match or if the
+/// `buf` or `out_samples` length isn't correct. If an error is returned, `state` and
+/// `out_samples` are left unmodified.
+pub fn decode_sbc_frame(buf: &[u8], state: &mut SbcState, out_samples: &mut [i16])
+    -> Result<SbcFrameHeader, Error> {
+
+    let frame = parse_sbc_frame(buf)?;
+    decode_sbc_frame_samples(&frame, state, out_samples)?;
+    Ok(frame.header)
+}
+
+// returns the smallest scale factor for which the maximum absolute value fits
+fn scale_factor(max_abs: i32) -> u8 {
+    let mut scale_factor = 0;
+    while scale_factor < 15 && i64::from(max_abs) >= 1i64 << (scale_factor + 1 + SB_FRACTION_BITS) {
+        scale_factor += 1;
+    }
+    u8::try_from(scale_factor).unwrap_or(15)
+}
+
+fn max_abs(sb_samples: &[[[i32; 8]; 2]], ch: usize, sb: usize) -> i32 {
+    sb_samples.iter().map(|block| block[ch][sb].saturating_abs()).max().unwrap_or(0)
+}
+
+/// Encodes 16-bit signed integer samples to the subband samples of an SBC frame.
+///
+/// The frame parameters are given in `header`. The `samples` length must be
+/// `channels() * samples_per_channel()` of the frame header. Samples are interleaved for
+/// 2 channel audio. The frame can be written to bytes with [`write_sbc_frame()`].
+///
+/// An error is returned if `header` has invalid values or if the `samples` length isn't
+/// correct. If an error is returned, `state` is left unmodified.
+pub fn encode_sbc_frame_samples(samples: &[i16], header: &SbcFrameHeader, state: &mut SbcState)
+    -> Result<SbcFrame, Error> {
+
+    if !header.is_valid() {
+        return Err(Error::InvalidData);
+    }
+    let channels = header.channels();
+    let subbands = header.subbands;
+    if samples.len() != channels * header.samples_per_channel() {
+        return Err(Error::InvalidBufferSize);
+    }
+
+    let mut sb_samples = [[[0i32; 8]; 2]; 16];
+    for (b, block) in sb_samples[..header.blocks].iter_mut().enumerate() {
+        for (ch, channel_samples) in block[..channels].iter_mut().enumerate() {
+            let mut pcm = [0i32; 8];
+            for (i, value) in pcm[..subbands].iter_mut().enumerate() {
+                *value = i32::from(samples[(b * subbands + i) * channels + ch]);
+            }
+            state.analyze(ch, &pcm[..subbands], &mut channel_samples[..subbands]);
+        }
+    }
+    let sb_samples = &mut sb_samples[..header.blocks];
+
+    let mut frame = SbcFrame {
+        header: header.clone(),
+        join: [false; 8],
+        scale_factors: [[0; 8]; 2],
+        samples: [[[0; 8]; 2]; 16],
+    };
+    for (ch, channel_scale_factors) in frame.scale_factors[..channels].iter_mut().enumerate() {
+        for (sb, value) in channel_scale_factors[..subbands].iter_mut().enumerate() {
+            *value = scale_factor(max_abs(sb_samples, ch, sb));
+        }
+    }
+
+    // code subbands as mid and side if it needs smaller scale factors, the last subband
+    // is never joined
+    if header.channel_mode == SbcChannelMode::JointStereo {
+        for sb in 0..subbands - 1 {
+            let mut mid_max = 0;
+            let mut side_max = 0;
+            for block in sb_samples.iter() {
+                mid_max = mid_max.max(((block[0][sb] + block[1][sb]) >> 1).saturating_abs());
+                side_max = side_max.max(((block[0][sb] - block[1][sb]) >> 1).saturating_abs());
+            }
+            let (mid_sf, side_sf) = (scale_factor(mid_max), scale_factor(side_max));
+            if mid_sf + side_sf < frame.scale_factors[0][sb] + frame.scale_factors[1][sb] {
+                frame.join[sb] = true;
+                frame.scale_factors[0][sb] = mid_sf;
+                frame.scale_factors[1][sb] = side_sf;
+                for block in sb_samples.iter_mut() {
+                    let (left, right) = (block[0][sb], block[1][sb]);
+                    block[0][sb] = (left + right) >> 1;
+                    block[1][sb] = (left - right) >> 1;
+                }
+            }
+        }
+    }
+
+    let bits = frame.bits();
+    for (block, out_block) in sb_samples.iter().zip(frame.samples.iter_mut()) {
+        for ch in 0..channels {
+            for sb in 0..subbands {
+                if bits[ch][sb] == 0 {
+                    continue;
+                }
+                let levels = (1i64 << bits[ch][sb]) - 1;
+                let shift = u32::from(frame.scale_factors[ch][sb]) + 1 + SB_FRACTION_BITS;
+                let code = (((i64::from(block[ch][sb]) + (1 << shift)) * levels) >> (shift + 1))
+                    .clamp(0, levels - 1);
+                out_block[ch][sb] = u16::try_from(code).unwrap_or(0);
+            }
+        }
+    }
+    Ok(frame)
+}
+
+/// Encodes 16-bit signed integer samples to an SBC frame.
+///
+/// The frame parameters are given in `header`. The `samples` length must be
+/// `channels() * samples_per_channel()` of the frame header. Samples are interleaved for
+/// 2 channel audio.
+///
+/// This function outputs the encoded frame to `out_buf`. The `out_buf` length must be
+/// [`SbcFrameHeader::frame_len()`].
+///
+/// An error is returned if `header` has invalid values or if the `samples` or `out_buf`
+/// length isn't correct. If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_sbc_frame(samples: &[i16], header: &SbcFrameHeader, state: &mut SbcState,
+    out_buf: &mut [u8]) -> Result<(), Error> {
+
+    if header.is_valid() && out_buf.len() != header.frame_len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    let frame = encode_sbc_frame_samples(samples, header, state)?;
+    write_sbc_frame(&frame, out_buf)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signal::tone;
+
+    fn header(channel_mode: SbcChannelMode, allocation_method: SbcAllocationMethod,
+        subbands: usize, bitpool: u8) -> SbcFrameHeader {
+        SbcFrameHeader {
+            sample_rate: 44100,
+            blocks: 16,
+            channel_mode,
+            allocation_method,
+            subbands,
+            bitpool,
+        }
+    }
+
+    #[test]
+    fn test_frame_len() {
+        // A2DP recommended high quality settings
+        assert_eq!(header(SbcChannelMode::JointStereo, SbcAllocationMethod::Loudness, 8, 53)
+            .frame_len(), 119);
+        assert_eq!(header(SbcChannelMode::Stereo, SbcAllocationMethod::Loudness, 8, 53)
+            .frame_len(), 118);
+        assert_eq!(header(SbcChannelMode::Mono, SbcAllocationMethod::Loudness, 8, 31)
+            .frame_len(), 70);
+        assert_eq!(header(SbcChannelMode::DualChannel, SbcAllocationMethod::Snr, 4, 16)
+            .frame_len(), 72);
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let expected = header(SbcChannelMode::JointStereo, SbcAllocationMethod::Loudness, 8, 53);
+        assert!(matches!(parse_sbc_frame_header(&[ 0x9c, 0xbd, 0x35, 0x00 ]),
+            Ok(parsed) if parsed == expected));
+        assert_eq!(header_byte(&expected), 0xbd);
+        assert!(matches!(parse_sbc_frame_header(&[ 0x9c, 0xbd, 0x35 ]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(parse_sbc_frame_header(&[ 0x9d, 0xbd, 0x35, 0x00 ]),
+            Err(Error::InvalidData)));
+        // bitpool too large for mono
+        assert!(matches!(parse_sbc_frame_header(&[ 0x9c, 0xb1, 0x81, 0x00 ]),
+            Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_crc() {
+        assert_eq!(crc8_bits(0x0f, 0x00, 0), 0x0f);
+        assert_eq!(crc8_bits(0x00, 0x80, 1), 0x1d);
+        assert_eq!(crc8_bits(0x80, 0x00, 1), 0x1d);
+    }
+
+    #[test]
+    fn test_bit_allocation_fills_bitpool() {
+        let scale_factors = [ [ 12, 11, 10, 9, 8, 7, 6, 5 ], [ 9; 8 ] ];
+        for method in [ SbcAllocationMethod::Loudness, SbcAllocationMethod::Snr ] {
+            let mut bits = [[0u32; 8]; 2];
+            allocate_bits(&header(SbcChannelMode::Stereo, method, 8, 53), &scale_factors,
+                &mut bits);
+            assert_eq!(bits.iter().flatten().sum::<u32>(), 53);
+            assert!(bits[0][0] > bits[0][7]);
+
+            let mut bits = [[0u32; 8]; 2];
+            allocate_bits(&header(SbcChannelMode::DualChannel, method, 8, 20), &scale_factors,
+                &mut bits);
+            assert_eq!(bits[0].iter().sum::<u32>(), 20);
+            assert_eq!(bits[1].iter().sum::<u32>(), 20);
+        }
+    }
+
+    // Frames from the encoder of this crate and the last decoded frame of each stream. The
+    // expected samples are from a floating point model of the decoder in appendix B of the
+    // specification, so the fixed point filterbank may differ by one.
+    const JOINT_STEREO_FRAMES: &[[u8; 28]; 4] = &[
+        [
+            0x9C, 0x8D, 0x1E, 0x59, 0xAC, 0x97, 0x46, 0x42, 0x43, 0x65, 0x75, 0x44, 0x54, 0x7D,
+            0xAD, 0x7A, 0xD6, 0x16, 0xB6, 0x2B, 0x5A, 0x65, 0x5B, 0xB0, 0xB6, 0xD0, 0x98, 0x18
+        ],
+        [
+            0x9C, 0x8D, 0x1E, 0x9A, 0xBE, 0xC9, 0x75, 0x55, 0x45, 0xC5, 0x9A, 0x77, 0x56, 0xB1,
+            0x28, 0x56, 0x6B, 0xB9, 0x5F, 0xA1, 0x9A, 0x2D, 0x17, 0x21, 0x5F, 0x34, 0x13, 0xC5
+        ],
+        [
+            0x9C, 0x8D, 0x1E, 0x06, 0xF0, 0xD2, 0x10, 0x00, 0x00, 0xC1, 0x7A, 0x01, 0x00, 0x9D,
+            0x0F, 0x46, 0x06, 0x76, 0xE3, 0xEE, 0xA6, 0x0B, 0x2B, 0x3C, 0x4F, 0x2B, 0xE5, 0xC0
+        ],
+        [
+            0x9C, 0x8D, 0x1E, 0xD9, 0xB0, 0xB0, 0x00, 0x00, 0x00, 0xC0, 0x7A, 0x00, 0x00, 0x10,
+            0x68, 0xF7, 0xBE, 0x91, 0xF1, 0x9A, 0xB4, 0xFC, 0x5F, 0x10, 0x47, 0x24, 0xA5, 0xB5
+        ],
+    ];
+
+    const JOINT_STEREO_EXPECTED: &[i16; 64] = &[
+        -6623, 7203, -8357, 8507, -10545, 10492, -10025, 10033, -7134, 7490,
+        -5344, 6322, -6144, 7974, -6857, 9716, -4558, 8572, -471, 5720,
+        1718, 4798, 1215, 6543, 1209, 7713, 4058, 5890, 7736, 3045,
+        8769, 2598, 7274, 4395, 6711, 4953, 8746, 2585, 10792, -124,
+        9775, -96, 6652, 1731, 5026, 1775, 5907, -939, 6278, -3352,
+        3575, -2828, -500, -1030, -2359, -1479, -1720, -4390, -1937, -6343,
+        -4992, -5294, -8488, -3587,
+    ];
+
+    const MONO_FRAMES: &[[u8; 24]; 3] = &[
+        [
+            0x9C, 0x92, 0x12, 0x79, 0xDA, 0x86, 0x7F, 0xBE, 0xE0, 0x0F, 0xB8, 0x13, 0xCE, 0x19,
+            0x0B, 0x90, 0xA4, 0x6F, 0xCD, 0x9C, 0x0F, 0x8A, 0x79, 0xC3
+        ],
+        [
+            0x9C, 0x92, 0x12, 0x98, 0xDA, 0x61, 0x69, 0x9B, 0x90, 0x70, 0x93, 0xC0, 0x81, 0x6E,
+            0x98, 0x8F, 0x3D, 0x6E, 0x8D, 0x2C, 0x51, 0x4A, 0xA7, 0x05
+        ],
+        [
+            0x9C, 0x92, 0x12, 0x85, 0xDA, 0x60, 0x77, 0x8D, 0x12, 0x7B, 0x43, 0x9F, 0xE5, 0x41,
+            0xBA, 0x81, 0x0E, 0xAC, 0x40, 0x1C, 0x61, 0x32, 0xD1, 0xC8
+        ],
+    ];
+
+    const MONO_EXPECTED: &[i16; 32] = &[
+        -7173, -5503, -6322, -6892, -4491, -420, 1681, 1119, 1157, 4106,
+        7801, 8793, 7307, 6758, 8736, 10734, 9752, 6675, 5054, 5912,
+        6258, 3537, -511, -2357, -1731, -1941, -4951, -8443, -9092, -7402,
+        -6918, -8975,
+    ];
+
+    fn max_reference_error(frames: &[&[u8]], expected: &[i16]) -> i32 {
+        let mut state = SbcState::new();
+        let mut out = [0i16; 64];
+        let out = &mut out[..expected.len()];
+        for frame in frames {
+            assert!(decode_sbc_frame(frame, &mut state, out).is_ok());
+        }
+        out.iter().zip(expected).map(|(a, b)| (i32::from(*a) - i32::from(*b)).abs()).max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_decode_reference() {
+        let frames = JOINT_STEREO_FRAMES.each_ref().map(|frame| frame.as_slice());
+        assert!(max_reference_error(&frames, JOINT_STEREO_EXPECTED) <= 1);
+        let frames = MONO_FRAMES.each_ref().map(|frame| frame.as_slice());
+        assert!(max_reference_error(&frames, MONO_EXPECTED) <= 1);
+    }
+
+    #[test]
+    fn test_parse_write_frame() {
+        for buf in JOINT_STEREO_FRAMES.iter().map(|frame| frame.as_slice())
+            .chain(MONO_FRAMES.iter().map(|frame| frame.as_slice())) {
+            let result = parse_sbc_frame(buf);
+            assert!(result.is_ok());
+            let Ok(frame) = result else { return };
+            let mut out = [0xffu8; 32];
+            assert!(matches!(write_sbc_frame(&frame, &mut out), Ok(len) if len == buf.len()));
+            assert_eq!(&out[..buf.len()], buf);
+            assert!(matches!(write_sbc_frame(&frame, &mut out[..buf.len() - 1]),
+                Err(Error::InvalidBufferSize)));
+        }
+        let result = parse_sbc_frame(&MONO_FRAMES[0]);
+        assert!(result.is_ok());
+        let Ok(mut frame) = result else { return };
+        assert!(frame.header.channel_mode == SbcChannelMode::Mono);
+        assert_eq!(frame.bits()[0].iter().sum::<u32>(), 18);
+        frame.scale_factors[0][1] = 16;
+        let mut out = [0u8; 32];
+        assert!(matches!(write_sbc_frame(&frame, &mut out), Err(Error::InvalidData)));
+        assert_eq!(out, [0; 32]);
+        let mut samples = [0i16; 32];
+        assert!(matches!(decode_sbc_frame_samples(&frame, &mut SbcState::new(), &mut samples),
+            Err(Error::InvalidData)));
+    }
+
+    fn roundtrip_snr(header: &SbcFrameHeader) -> f64 {
+        let channels = header.channels();
+        let frame_samples = channels * header.samples_per_channel();
+        let frames = 20;
+        let mut input = [0i16; 20 * 256];
+        for (i, value) in input[..frames * frame_samples].iter_mut().enumerate() {
+            let n = i32::try_from(i / channels).unwrap_or(0);
+            let ch = i32::try_from(i % channels).unwrap_or(0);
+            // two tones per channel using a triangle wave approximation
+            let tri = |period: i32, amp: i32| {
+                let phase = n % period;
+                let half = period / 2;
+                let value = if phase < half { phase } else { period - phase };
+                (value * 4 * amp) / period - amp
+            };
+            *value = i16::try_from(tri(90 + ch * 14, 6000) + tri(17, 1500)).unwrap_or(0);
+        }
+        let mut encoder = SbcState::new();
+        let mut decoder = SbcState::new();
+        let mut output = [0i16; 20 * 256];
+        let mut frame = [0u8; 512];
+        let frame_len = header.frame_len();
+        for f in 0..frames {
+            let range = f * frame_samples..(f + 1) * frame_samples;
+            assert!(encode_sbc_frame(&input[range.clone()], header, &mut encoder,
+                &mut frame[..frame_len]).is_ok());
+            assert!(matches!(decode_sbc_frame(&frame[..frame_len], &mut decoder,
+                &mut output[range]), Ok(parsed) if parsed == *header));
+        }
+        // the filterbank delay is 73 samples for 8 subbands and 37 samples for 4 subbands
+        let delay = (if header.subbands == 8 { 73 } else { 37 }) * channels;
+        let mut signal = 0.0;
+        let mut noise = 0.0;
+        for i in 1024..frames * frame_samples {
+            let expected = f64::from(input[i - delay]);
+            signal += expected * expected;
+            noise += (f64::from(output[i]) - expected).powi(2);
+        }
+        10.0 * (signal / noise).log10()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let snr = roundtrip_snr(&header(SbcChannelMode::JointStereo,
+            SbcAllocationMethod::Loudness, 8, 53));
+        assert!(snr > 30.0, "snr {snr}");
+        let snr = roundtrip_snr(&header(SbcChannelMode::Stereo, SbcAllocationMethod::Snr, 4, 32));
+        assert!(snr > 30.0, "snr {snr}");
+        let snr = roundtrip_snr(&header(SbcChannelMode::Mono,
+            SbcAllocationMethod::Loudness, 8, 31));
+        assert!(snr > 30.0, "snr {snr}");
+        let snr = roundtrip_snr(&header(SbcChannelMode::DualChannel, SbcAllocationMethod::Snr,
+            4, 16));
+        assert!(snr > 30.0, "snr {snr}");
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let header = header(SbcChannelMode::Stereo, SbcAllocationMethod::Loudness, 8, 32);
+        let mut samples = [0i16; 256];
+        for (i, value) in samples.iter_mut().enumerate() {
+            *value = tone(i);
+        }
+        let mut encoder = SbcState::new();
+        let mut decoder = SbcState::new();
+        assert!(encoder.is_default());
+        let mut frame = [0u8; 512];
+        let frame_len = header.frame_len();
+        let mut out = [0i16; 256];
+        assert!(encode_sbc_frame(&samples, &header, &mut encoder, &mut frame[..frame_len])
+            .is_ok());
+        assert!(decode_sbc_frame(&frame[..frame_len], &mut decoder, &mut out).is_ok());
+        assert!(!encoder.is_default());
+        assert!(!decoder.is_default());
+
+        let mut buf = [0u8; SbcState::SERIALIZED_LEN];
+        for state in [ &encoder, &decoder ] {
+            assert!(state.to_bytes(&mut buf).is_ok());
+            assert!(matches!(SbcState::from_bytes(&buf), Ok(s) if s == *state));
+        }
+        decoder.reset();
+        assert!(decoder.is_default());
+
+        // synthesis history
+        buf[640..648].copy_from_slice(&(1i64 << 32).to_le_bytes());
+        assert!(SbcState::from_bytes(&buf).is_ok());
+        buf[640..648].copy_from_slice(&((1i64 << 32) + 1).to_le_bytes());
+        assert!(matches!(SbcState::from_bytes(&buf), Err(Error::InvalidData)));
+        buf[640..648].copy_from_slice(&0i64.to_le_bytes());
+        // analysis history
+        buf[0..4].copy_from_slice(&32768i32.to_le_bytes());
+        assert!(matches!(SbcState::from_bytes(&buf), Err(Error::InvalidData)));
+        assert!(matches!(SbcState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(encoder.to_bytes(&mut [0u8; 1]), Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let header = header(SbcChannelMode::Mono, SbcAllocationMethod::Loudness, 8, 31);
+        let mut frame = [0u8; 70];
+        let mut state = SbcState::new();
+        assert!(encode_sbc_frame(&[100; 128], &header, &mut state, &mut frame).is_ok());
+
+        let mut state = SbcState::new();
+        let mut out = [0i16; 128];
+        assert!(matches!(decode_sbc_frame(&frame[..69], &mut state, &mut out),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_sbc_frame(&frame, &mut state, &mut out[..127]),
+            Err(Error::InvalidBufferSize)));
+        let mut corrupted = frame;
+        corrupted[5] ^= 0x10;
+        assert!(matches!(decode_sbc_frame(&corrupted, &mut state, &mut out),
+            Err(Error::InvalidData)));
+        assert_eq!(state, SbcState::new());
+        assert!(decode_sbc_frame(&frame, &mut state, &mut out).is_ok());
+
+        let mut invalid = header.clone();
+        invalid.bitpool = 1;
+        assert!(matches!(encode_sbc_frame(&[0; 128], &invalid, &mut state, &mut frame),
+            Err(Error::InvalidData)));
+        assert!(matches!(encode_sbc_frame(&[0; 127], &header, &mut state, &mut frame),
+            Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,124 @@
+
+#[cfg(feature = "internal-no-panic")]
+use no_panic::no_panic;
+
+use crate::Error;
+
+/// Decodes an 8-bit encoded 3DO SDX2 value to a linear 16-bit signed integer sample value.
+///
+/// The encoded value is a signed byte. Its square times two (with the sign of the byte) is
+/// added to the predictor. If the lowest bit of the encoded value is zero, the predictor is
+/// reset to zero before adding the delta, so even values are exact samples and odd values
+/// are deltas.
+///
+/// The `predictor` parameter should be initialized to zero. This function updates
+/// `predictor` with the new sample value.
+#[cfg_attr(feature = "internal-no-panic", no_panic)]
+#[inline(always)]
+pub fn decode_sdx2(encoded: u8, predictor: &mut i16) -> i16 {
+    let n = i32::from(i8::from_le_bytes([ encoded ]));
+    let square = n * n.abs() * 2;
+    let value = if encoded & 1 == 0 {
+        square
+    } else {
+        i32::from(*predictor) + square
+    };
+    *predictor = i16::try_from(value.clamp(-32768, 32767)).unwrap_or(0);
+    *predictor
+}
+
+/// Decodes 3DO SDX2 (squareroot-delta-exact) encoded bytes to 16-bit signed integer samples.
+///
+/// `buf` should contain 8-bit encoded values. For multichannel audio, the values are
+/// interleaved.
+///
+/// `predictors` must contain one predictor for each channel. The predictors should be
+/// initialized to zero for the first call and subsequent calls should pass in the predictor
+/// values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// the same as the `buf` length and the `buf` length must be divisible by the number of
+/// channels. Samples are interleaved for multichannel audio.
+///
+/// An error is returned if `predictors` is empty or if the `buf` or `out_samples` length isn't
+/// correct. If an error is returned, `predictors` and `out_samples` are left unmodified.
+pub fn decode_sdx2_slice(buf: &[u8], predictors: &mut [i16], out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = predictors.len();
+    if channels == 0 {
+        return Err(Error::InvalidChannels);
+    }
+    if buf.len() % channels != 0 || buf.len() != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (b, out) in buf.chunks_exact(channels).zip(out_samples.chunks_exact_mut(channels)) {
+        for ((encoded, sample), predictor) in b.iter().zip(out.iter_mut())
+            .zip(predictors.iter_mut()) {
+            *sample = decode_sdx2(*encoded, predictor);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_sdx2() {
+        let mut predictor = 0;
+        // even values are exact
+        assert_eq!(decode_sdx2(10, &mut predictor), 200);
+        // odd values are deltas
+        assert_eq!(decode_sdx2(3, &mut predictor), 218);
+        assert_eq!(decode_sdx2(0xfb, &mut predictor), 168);
+        assert_eq!(decode_sdx2(0xf6, &mut predictor), -200);
+        assert_eq!(decode_sdx2(0, &mut predictor), 0);
+        assert_eq!(predictor, 0);
+
+        // full range
+        let mut predictor = 0;
+        assert_eq!(decode_sdx2(0x80, &mut predictor), -32768);
+        assert_eq!(decode_sdx2(126, &mut predictor), 31752);
+        // values are clamped
+        assert_eq!(decode_sdx2(127, &mut predictor), 32767);
+        let mut predictor = -32768;
+        assert_eq!(decode_sdx2(0x81, &mut predictor), -32768);
+    }
+
+    #[test]
+    fn test_decode_sdx2_slice() {
+        // one channel
+        let mut predictors = [0i16];
+        let mut samples = [0i16; 4];
+        assert!(decode_sdx2_slice(&[ 10, 3, 3, 0xfd ], &mut predictors, &mut samples).is_ok());
+        assert_eq!(samples, [ 200, 218, 236, 218 ]);
+        assert_eq!(predictors, [ 218 ]);
+
+        // two channels
+        let mut predictors = [0i16, 0];
+        let mut samples = [0i16; 4];
+        assert!(decode_sdx2_slice(&[ 10, 0xf6, 1, 0xff ], &mut predictors, &mut samples).is_ok());
+        assert_eq!(samples, [ 200, -200, 202, -202 ]);
+        assert_eq!(predictors, [ 202, -202 ]);
+
+        // invalid channels
+        let mut samples = [0i16; 2];
+        assert!(matches!(decode_sdx2_slice(&[ 1, 1 ], &mut [], &mut samples),
+            Err(Error::InvalidChannels)));
+
+        // invalid buf length
+        let mut predictors = [0i16, 0];
+        let mut samples = [0i16; 3];
+        assert!(matches!(decode_sdx2_slice(&[ 1, 1, 1 ], &mut predictors, &mut samples),
+            Err(Error::InvalidBufferSize)));
+
+        // invalid out_samples length
+        let mut predictors = [0i16];
+        let mut samples = [0i16; 2];
+        assert!(matches!(decode_sdx2_slice(&[ 1, 1, 1 ], &mut predictors, &mut samples),
+            Err(Error::InvalidBufferSize)));
+        assert_eq!(predictors, [ 0 ]);
+    }
+}
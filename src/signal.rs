@@ -0,0 +1,208 @@
+
+use crate::level::round_to_i16;
+
+/// One period of the G.711 μ-law digital milliwatt: a 1 kHz (1004 Hz in practice) tone
+/// at 0 dBm0, when played at 8000 Hz.
+pub const DIGITAL_MILLIWATT_ULAW: [u8; 8] = [ 0x1e, 0x0b, 0x0b, 0x1e, 0x9e, 0x8b, 0x8b, 0x9e ];
+
+/// One period of the G.711 A-law digital milliwatt: a 1 kHz (1004 Hz in practice) tone
+/// at 0 dBm0, when played at 8000 Hz.
+pub const DIGITAL_MILLIWATT_ALAW: [u8; 8] = [ 0x34, 0x21, 0x21, 0x34, 0xb4, 0xa1, 0xa1, 0xb4 ];
+
+/// Fills `out_buf` with the μ-law digital milliwatt sequence.
+///
+/// `phase` is the position (0..=7) in the sequence for the first byte. The phase for
+/// the next call is returned, so that subsequent calls continue the sequence.
+pub fn fill_digital_milliwatt_ulaw(out_buf: &mut [u8], phase: usize) -> usize {
+    fill_sequence(&DIGITAL_MILLIWATT_ULAW, out_buf, phase)
+}
+
+/// Fills `out_buf` with the A-law digital milliwatt sequence.
+///
+/// `phase` is the position (0..=7) in the sequence for the first byte. The phase for
+/// the next call is returned, so that subsequent calls continue the sequence.
+pub fn fill_digital_milliwatt_alaw(out_buf: &mut [u8], phase: usize) -> usize {
+    fill_sequence(&DIGITAL_MILLIWATT_ALAW, out_buf, phase)
+}
+
+fn fill_sequence(sequence: &[u8; 8], out_buf: &mut [u8], phase: usize) -> usize {
+    let mut phase = phase % 8;
+    for out in out_buf {
+        *out = sequence[phase];
+        phase = (phase + 1) % 8;
+    }
+    phase
+}
+
+// sine of `turns` full turns (1.0 is 2π radians)
+fn sin_turns(turns: f64) -> f64 {
+    use core::f64::consts::PI;
+    // reduce to -0.5..0.5 turns and then to -0.25..=0.25 turns using sin(π - x) = sin(x)
+    #[allow(clippy::cast_possible_truncation)] // the integer part is only used for reduction
+    let mut t = turns - f64::from(turns as i32);
+    if t >= 0.5 {
+        t -= 1.0;
+    } else if t < -0.5 {
+        t += 1.0;
+    }
+    if t > 0.25 {
+        t = 0.5 - t;
+    } else if t < -0.25 {
+        t = -0.5 - t;
+    }
+    // Taylor series for -π/2..=π/2
+    let x = t * 2.0 * PI;
+    let x2 = x * x;
+    let mut term = x;
+    let mut sum = x;
+    let mut n = 1.0;
+    while n < 19.0 {
+        term *= -x2 / ((n + 1.0) * (n + 2.0));
+        sum += term;
+        n += 2.0;
+    }
+    sum
+}
+
+/// Sine tone generator.
+#[derive(Debug, Clone)]
+pub struct ToneGenerator {
+    phase: f64,
+    phase_step: f64,
+    amplitude: f64,
+}
+
+impl ToneGenerator {
+    /// Creates a new sine tone generator for `frequency` Hz at `sample_rate` Hz with
+    /// the given peak `amplitude`. The first sample is at phase zero.
+    pub fn new(frequency: f64, sample_rate: u32, amplitude: i16) -> ToneGenerator {
+        ToneGenerator {
+            phase: 0.0,
+            phase_step: frequency / f64::from(sample_rate.max(1)),
+            amplitude: f64::from(amplitude),
+        }
+    }
+
+    /// Returns the next sample value.
+    pub fn next_sample(&mut self) -> i16 {
+        let sample = round_to_i16(sin_turns(self.phase) * self.amplitude);
+        self.phase += self.phase_step;
+        // keep the phase small to maintain precision
+        #[allow(clippy::cast_possible_truncation)] // the integer part is only used for reduction
+        {
+        self.phase -= f64::from(self.phase as i32);
+        }
+        sample
+    }
+
+    /// Fills `out_samples` with the next sample values.
+    pub fn fill(&mut self, out_samples: &mut [i16]) {
+        for out in out_samples {
+            *out = self.next_sample();
+        }
+    }
+}
+
+/// Linear sine sweep generator.
+#[derive(Debug, Clone)]
+pub struct SweepGenerator {
+    tone: ToneGenerator,
+    phase_step_delta: f64,
+    end_phase_step: f64,
+}
+
+impl SweepGenerator {
+    /// Creates a new sine sweep generator, which sweeps linearly from `start_frequency` Hz
+    /// to `end_frequency` Hz in `sample_count` samples at `sample_rate` Hz with the given
+    /// peak `amplitude`. After `sample_count` samples, the frequency stays at `end_frequency`.
+    pub fn new(start_frequency: f64, end_frequency: f64, sample_rate: u32, sample_count: u32,
+        amplitude: i16) -> SweepGenerator {
+        let tone = ToneGenerator::new(start_frequency, sample_rate, amplitude);
+        let end_phase_step = end_frequency / f64::from(sample_rate.max(1));
+        SweepGenerator {
+            phase_step_delta: (end_phase_step - tone.phase_step) / f64::from(sample_count.max(1)),
+            end_phase_step,
+            tone,
+        }
+    }
+
+    /// Returns the next sample value.
+    pub fn next_sample(&mut self) -> i16 {
+        let sample = self.tone.next_sample();
+        self.tone.phase_step += self.phase_step_delta;
+        if (self.phase_step_delta > 0.0 && self.tone.phase_step > self.end_phase_step) ||
+            (self.phase_step_delta < 0.0 && self.tone.phase_step < self.end_phase_step) {
+            self.tone.phase_step = self.end_phase_step;
+        }
+        sample
+    }
+
+    /// Fills `out_samples` with the next sample values.
+    pub fn fill(&mut self, out_samples: &mut [i16]) {
+        for out in out_samples {
+            *out = self.next_sample();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_alaw, decode_ulaw, rms_level_dbov};
+
+    #[test]
+    fn test_digital_milliwatt() {
+        let mut buf = [0u8; 10];
+        assert_eq!(fill_digital_milliwatt_ulaw(&mut buf, 6), 0);
+        assert_eq!(buf, [ 0x8b, 0x9e, 0x1e, 0x0b, 0x0b, 0x1e, 0x9e, 0x8b, 0x8b, 0x9e ]);
+        assert_eq!(fill_digital_milliwatt_alaw(&mut buf[..3], 0), 3);
+        assert_eq!(buf[..3], [ 0x34, 0x21, 0x21 ]);
+
+        // 0 dBm0 is 3.17 dB below the μ-law and 3.14 dB below the A-law full scale sine
+        let ulaw = DIGITAL_MILLIWATT_ULAW.map(decode_ulaw);
+        let alaw = DIGITAL_MILLIWATT_ALAW.map(decode_alaw);
+        assert!((rms_level_dbov(&ulaw) + 6.18).abs() < 0.05);
+        assert!((rms_level_dbov(&alaw) + 6.15).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_sin_turns() {
+        for i in -100i32..=100 {
+            let t = f64::from(i) / 24.0;
+            let expected = [ 0.0, 0.258819045102521, 0.5,
+                core::f64::consts::FRAC_1_SQRT_2, 0.866025403784439,
+                0.965925826289068, 1.0 ];
+            // sin is symmetric around 0.25 turns and antisymmetric around 0.5 turns
+            let k = i.rem_euclid(24);
+            let (index, sign) = match k {
+                0..=6 => (k, 1.0),
+                7..=12 => (12 - k, 1.0),
+                13..=18 => (k - 12, -1.0),
+                _ => (24 - k, -1.0),
+            };
+            let Ok(index) = usize::try_from(index) else { return };
+            assert!((sin_turns(t) - sign * expected[index]).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_tone_generator() {
+        let mut tone = ToneGenerator::new(1000.0, 8000, 10000);
+        let mut samples = [0i16; 10];
+        tone.fill(&mut samples);
+        assert_eq!(samples, [ 0, 7071, 10000, 7071, 0, -7071, -10000, -7071, 0, 7071 ]);
+    }
+
+    #[test]
+    fn test_sweep_generator() {
+        // a sweep from 0 Hz to 2000 Hz over 8 samples at 8000 Hz
+        let mut sweep = SweepGenerator::new(0.0, 2000.0, 8000, 8, 10000);
+        let mut samples = [0i16; 12];
+        sweep.fill(&mut samples);
+        assert_eq!(samples[..2], [ 0, 0 ]);
+        assert_eq!(sweep.tone.phase_step, 0.25);
+        // the phase advances a quarter turn per sample at the end frequency
+        assert!((samples[9] + samples[11]).abs() <= 1);
+        assert!((samples[8] + samples[10]).abs() <= 1);
+    }
+}
@@ -0,0 +1,101 @@
+
+use crate::CompandingLaw;
+use crate::Error;
+use crate::adpcm_ima::ms_block_sample_len;
+
+/// Encoded G.711 μ-law silence (zero sample value).
+pub const ULAW_SILENCE: u8 = 0xff;
+
+/// Encoded G.711 A-law silence (the smallest positive sample value).
+pub const ALAW_SILENCE: u8 = 0xd5;
+
+/// Fills `out_buf` with encoded G.711 A-law or μ-law silence.
+///
+/// Note that zero bytes aren't silence: 0x00 is a large negative value in A-law and
+/// the most negative value in μ-law.
+pub fn fill_silence_companded<L: CompandingLaw>(out_buf: &mut [u8]) {
+    out_buf.fill(L::encode(0));
+}
+
+/// Returns an AIFF-C / QT "ima4" compressed block, which decodes to silence.
+///
+/// The block header has predictor 0 and step index 0 and all nibbles are 0, which decode to 0.
+/// Note that the "ima4" decoder may keep its own predictor instead of the header predictor
+/// (see [`decode_adpcm_ima_ima4()`](crate::decode_adpcm_ima_ima4)), so if the previous block
+/// ended close to zero with step index 0, the block decodes to that small constant value.
+pub fn silence_block_adpcm_ima_ima4() -> [u8; 34] {
+    [0; 34]
+}
+
+/// Fills `out_buf` with AIFF-C / QT "ima4" compressed blocks, which decode to silence.
+///
+/// The `out_buf` length must be divisible by 34.
+///
+/// An error is returned if the `out_buf` length isn't correct.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn fill_silence_adpcm_ima_ima4(out_buf: &mut [u8]) -> Result<(), Error> {
+    if out_buf.len() % 34 != 0 {
+        return Err(Error::InvalidBufferSize);
+    }
+    for block in out_buf.chunks_exact_mut(34) {
+        block.copy_from_slice(&silence_block_adpcm_ima_ima4());
+    }
+    Ok(())
+}
+
+/// Fills `out_buf` with a WAV / MS IMA ADPCM (wav format 0x0011) compressed block,
+/// which decodes to silence.
+///
+/// The block headers have predictor 0 and step index 0 and all nibbles are 0,
+/// which decode to 0.
+///
+/// For 1 channel audio, the `out_buf` length must be at least 4. For 2 channel audio,
+/// the `out_buf` length must be at least 8 and it must be divisible by 8.
+/// The `out_buf` length must always be less than 65536.
+///
+/// An error is returned if the `out_buf` length isn't correct.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn fill_silence_adpcm_ima_ms(out_buf: &mut [u8], is_stereo: bool) -> Result<(), Error> {
+    ms_block_sample_len(out_buf.len(), if is_stereo { 2 } else { 1 })?;
+    out_buf.fill(0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ALaw, ULaw, AdpcmImaState};
+    use crate::{decode_alaw, decode_ulaw, decode_adpcm_ima_ima4, decode_adpcm_ima_ms};
+
+    #[test]
+    fn test_silence_companded() {
+        assert_eq!(decode_ulaw(ULAW_SILENCE), 0);
+        assert_eq!(decode_alaw(ALAW_SILENCE), 8);
+        let mut buf = [0u8; 3];
+        fill_silence_companded::<ULaw>(&mut buf);
+        assert_eq!(buf, [ ULAW_SILENCE; 3 ]);
+        fill_silence_companded::<ALaw>(&mut buf);
+        assert_eq!(buf, [ ALAW_SILENCE; 3 ]);
+    }
+
+    #[test]
+    fn test_silence_adpcm_ima() {
+        let mut buf = [0xaau8; 68];
+        assert!(fill_silence_adpcm_ima_ima4(&mut buf).is_ok());
+        let mut state = AdpcmImaState { predictor: 5000, step_index: 40 };
+        let mut samples = [1i16; 64];
+        let Ok(block) = buf[34..].try_into() else { return };
+        decode_adpcm_ima_ima4(block, &mut state, &mut samples);
+        assert_eq!(samples, [0; 64]);
+        assert!(matches!(fill_silence_adpcm_ima_ima4(&mut buf[..33]),
+            Err(Error::InvalidBufferSize)));
+
+        let mut buf = [0xaau8; 16];
+        assert!(fill_silence_adpcm_ima_ms(&mut buf, true).is_ok());
+        let mut samples = [1i16; 18];
+        assert!(decode_adpcm_ima_ms(&buf, true, &mut samples).is_ok());
+        assert_eq!(samples, [0; 18]);
+        assert!(matches!(fill_silence_adpcm_ima_ms(&mut buf[..12], true),
+            Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,188 @@
+
+use crate::Error;
+use crate::AdpcmImaState;
+use crate::{decode_adpcm_ima_ima4, decode_adpcm_ima_ms};
+use crate::adpcm_ima::apply_ima4_header;
+
+/// Block boundary smoothing state for one channel of IMA ADPCM decoding.
+///
+/// Low quality encoders may produce blocks whose header predictor doesn't match the previous
+/// block's last sample, which causes an audible tick at the block boundary. Smoothing
+/// crossfades the discontinuity over the first samples of the block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockSmoothing {
+    len: usize,
+    last_samples: Option<[i16; 2]>,
+}
+
+impl BlockSmoothing {
+    /// Creates a new BlockSmoothing, which crossfades the discontinuity over `len` samples.
+    /// Smoothing is disabled if `len` is 0.
+    pub fn new(len: usize) -> BlockSmoothing {
+        BlockSmoothing {
+            len,
+            last_samples: None,
+        }
+    }
+
+    /// Returns the crossfade length.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if smoothing is disabled.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Forgets the previous block, so that the next block isn't smoothed.
+    /// This should be called after seeking.
+    pub fn reset(&mut self) {
+        self.last_samples = None;
+    }
+
+    // subtracts a fading `discontinuity` from the first samples
+    fn crossfade<'a>(&self, samples: impl Iterator<Item = &'a mut i16>, discontinuity: i32) {
+        let Ok(len) = i32::try_from(self.len) else {
+            return;
+        };
+        for (s, i) in samples.zip(0..len) {
+            let correction = discontinuity * (len - i) / (len + 1);
+            // value is clamped so truncation never happens
+            #[allow(clippy::cast_possible_truncation)]
+            {
+            *s = (i32::from(*s) - correction).clamp(-32768, 32767) as i16;
+            }
+        }
+    }
+
+    // stores the last two samples of the block
+    fn store<'a>(&mut self, mut samples: impl DoubleEndedIterator<Item = &'a i16>) {
+        let last = samples.next_back().copied().unwrap_or(0);
+        let second_last = samples.next_back().copied().unwrap_or(last);
+        self.last_samples = Some([ second_last, last ]);
+    }
+}
+
+/// Decodes an AIFF-C / QT "ima4" compressed block to 16-bit signed integer samples and
+/// smooths the block boundary.
+///
+/// This works like [`decode_adpcm_ima_ima4()`](crate::decode_adpcm_ima_ima4), but if the block
+/// header resets the decoder predictor, the jump from the previous predictor is crossfaded
+/// over the first samples of the block. `smoothing` should be created with
+/// [`BlockSmoothing::new()`] before the first call and subsequent calls should pass in
+/// the same object. For 2 channel audio, each channel needs its own `smoothing` object.
+pub fn decode_adpcm_ima_ima4_smoothed(buf: &[u8; 34], state: &mut AdpcmImaState,
+    smoothing: &mut BlockSmoothing, out_samples: &mut [i16; 64]) {
+
+    let previous_predictor = state.predictor;
+    let mut header_state = state.clone();
+    apply_ima4_header(buf[0], buf[1], &mut header_state);
+    decode_adpcm_ima_ima4(buf, state, out_samples);
+    if smoothing.last_samples.is_some() {
+        let discontinuity = i32::from(header_state.predictor) - i32::from(previous_predictor);
+        smoothing.crossfade(out_samples.iter_mut(), discontinuity);
+    }
+    smoothing.store(out_samples.iter());
+}
+
+/// Decodes WAV / MS IMA ADPCM (wav format 0x0011) compressed block to
+/// 16-bit signed integer samples and smooths the block boundary.
+///
+/// This works like [`decode_adpcm_ima_ms()`](crate::decode_adpcm_ima_ms), but the difference
+/// between the header sample and the value extrapolated from the last two samples of
+/// the previous block is crossfaded over the first samples of the block.
+/// `smoothings` must contain channel number of `BlockSmoothing` items (1 or 2). They should be
+/// created with [`BlockSmoothing::new()`] before the first call and subsequent calls should
+/// pass in the same objects.
+///
+/// An error is returned if `smoothings` has an invalid number of items or
+/// if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `smoothings` and `out_samples` are left unmodified.
+pub fn decode_adpcm_ima_ms_smoothed(buf: &[u8], is_stereo: bool,
+    smoothings: &mut [BlockSmoothing], out_samples: &mut [i16]) -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    if smoothings.len() != channels {
+        return Err(Error::InvalidChannels);
+    }
+    decode_adpcm_ima_ms(buf, is_stereo, out_samples)?;
+    for (ch, smoothing) in smoothings.iter_mut().enumerate() {
+        if let Some([ second_last, last ]) = smoothing.last_samples {
+            let predicted = (2 * i32::from(last) - i32::from(second_last)).clamp(-32768, 32767);
+            let discontinuity = i32::from(out_samples[ch]) - predicted;
+            smoothing.crossfade(out_samples.iter_mut().skip(ch).step_by(channels), discontinuity);
+        }
+        smoothing.store(out_samples.iter().skip(ch).step_by(channels));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_adpcm_ima_ima4_smoothed() {
+        // the first block sets the state, the second block resets the predictor
+        let mut block = [0u8; 34];
+        block[0] = 0x10;
+        let mut state = AdpcmImaState::new();
+        let mut smoothing = BlockSmoothing::new(4);
+        let mut out = [0i16; 64];
+        decode_adpcm_ima_ima4_smoothed(&block, &mut state, &mut smoothing, &mut out);
+        assert!(out.iter().all(|s| *s == 0x1000));
+        block[0] = 0x20;
+        decode_adpcm_ima_ima4_smoothed(&block, &mut state, &mut smoothing, &mut out);
+        assert_eq!(out[..6], [ 0x1000 + 820, 0x1000 + 1639, 0x1000 + 2458, 0x1000 + 3277,
+            0x2000, 0x2000 ]);
+        assert_eq!(state.predictor, 0x2000);
+
+        // the predictor isn't reset, so nothing is smoothed
+        block[2..].fill(0x01);
+        decode_adpcm_ima_ima4_smoothed(&block, &mut state, &mut smoothing, &mut out);
+        let mut expected = [0i16; 64];
+        let mut expected_state = AdpcmImaState { predictor: 0x2000, step_index: 0 };
+        decode_adpcm_ima_ima4(&block, &mut expected_state, &mut expected);
+        assert_eq!(out, expected);
+
+        // disabled smoothing
+        let mut smoothing = BlockSmoothing::new(0);
+        assert!(smoothing.is_empty());
+        block[2..].fill(0);
+        block[0] = 0x40;
+        decode_adpcm_ima_ima4_smoothed(&block, &mut state, &mut smoothing, &mut out);
+        block[0] = 0x10;
+        decode_adpcm_ima_ima4_smoothed(&block, &mut state, &mut smoothing, &mut out);
+        assert!(out.iter().all(|s| *s == 0x1000));
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_ms_smoothed() {
+        let mut smoothings = [ BlockSmoothing::new(3), BlockSmoothing::new(3) ];
+        let mut block = [0u8; 16];
+        block[0..2].copy_from_slice(&1000i16.to_le_bytes());
+        block[4..6].copy_from_slice(&(-1000i16).to_le_bytes());
+        let mut out = [0i16; 18];
+        assert!(decode_adpcm_ima_ms_smoothed(&block, true, &mut smoothings, &mut out).is_ok());
+        assert!(out.iter().step_by(2).all(|s| *s == 1000));
+        assert!(out.iter().skip(1).step_by(2).all(|s| *s == -1000));
+
+        // the left channel jumps by 400, the right channel continues
+        block[0..2].copy_from_slice(&1400i16.to_le_bytes());
+        assert!(decode_adpcm_ima_ms_smoothed(&block, true, &mut smoothings, &mut out).is_ok());
+        assert_eq!(out[..8], [ 1100, -1000, 1200, -1000, 1300, -1000, 1400, -1000 ]);
+
+        // invalid arguments
+        let mut smoothings_copy = smoothings.clone();
+        assert!(matches!(decode_adpcm_ima_ms_smoothed(&block, false, &mut smoothings, &mut out),
+            Err(Error::InvalidChannels)));
+        assert!(matches!(decode_adpcm_ima_ms_smoothed(&block, true, &mut smoothings_copy,
+            &mut out[..17]), Err(Error::InvalidBufferSize)));
+        assert_eq!(smoothings_copy, smoothings);
+    }
+}
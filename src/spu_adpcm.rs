@@ -0,0 +1,418 @@
+
+use crate::Error;
+use crate::sample_history::{decode_code, encode_sample, impl_sample_history, squared_error};
+use crate::xa::{XA_FILTER_K0, XA_FILTER_K1};
+
+// PlayStation SPU ADPCM is the sample format of the PS1 and PS2 sound chips (VAG files).
+// It uses the same prediction filters as CD-XA ADPCM, with an additional fifth filter.
+// see https://problemkaputt.de/psx-spx.htm#spuadpcmsamples
+
+/// Size of an SPU ADPCM block in bytes: 2 header bytes and 14 bytes of 4-bit samples.
+pub const SPU_ADPCM_BLOCK_SIZE: usize = 16;
+
+/// Number of samples in an SPU ADPCM block.
+pub const SPU_ADPCM_SAMPLES_PER_BLOCK: usize = 28;
+
+/// State values for the SPU ADPCM decoder.
+///
+/// The values should be initialized to zeros at the start of the sample.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SpuAdpcmState {
+    /// The previous decoded sample.
+    pub previous: i16,
+    /// The sample before the previous decoded sample.
+    pub previous2: i16,
+}
+
+impl SpuAdpcmState {
+    /// Creates a new SpuAdpcmState with zero values.
+    pub fn new() -> SpuAdpcmState {
+        SpuAdpcmState { previous: 0, previous2: 0 }
+    }
+
+    // returns the decoded sample of a 4-bit code (-8..=7) without updating the state
+    fn decoded_value(&self, code: i32, shift: u8, filter: u8) -> i16 {
+        let filter = usize::from(filter).min(4);
+        // shift values 13..=15 are reserved and work like 9
+        let shift = match shift {
+            s @ 0..=12 => s,
+            _ => 9,
+        };
+        let prediction = (i32::from(self.previous) * XA_FILTER_K0[filter] +
+            i32::from(self.previous2) * XA_FILTER_K1[filter] + 32) >> 6;
+        #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+        let sample = (((code << 12) >> shift) + prediction).clamp(-32768, 32767) as i16;
+        sample
+    }
+}
+
+impl_sample_history!(SpuAdpcmState);
+
+/// Header bytes of an SPU ADPCM block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SpuAdpcmBlockHeader {
+    /// Shift value 0..=12. Values 13..=15 are reserved and decoded like 9.
+    pub shift: u8,
+    /// Prediction filter 0..=4.
+    pub filter: u8,
+    /// Loop end flag. Playback jumps to the loop start address after this block.
+    pub is_loop_end: bool,
+    /// Loop repeat flag. If it is `false` in a loop end block, the voice is released after
+    /// this block.
+    pub is_loop_repeat: bool,
+    /// Loop start flag. The address of this block is set as the loop start address.
+    pub is_loop_start: bool,
+}
+
+impl SpuAdpcmBlockHeader {
+    /// Parses the 2 header bytes of an SPU ADPCM block.
+    ///
+    /// An error is returned if the filter value is larger than 4.
+    pub fn from_bytes(header: [u8; 2]) -> Result<SpuAdpcmBlockHeader, Error> {
+        let filter = header[0] >> 4;
+        if filter > 4 {
+            return Err(Error::InvalidData);
+        }
+        Ok(SpuAdpcmBlockHeader {
+            shift: header[0] & 0x0f,
+            filter,
+            is_loop_end: (header[1] & 0x01) != 0,
+            is_loop_repeat: (header[1] & 0x02) != 0,
+            is_loop_start: (header[1] & 0x04) != 0,
+        })
+    }
+
+    /// Returns the 2 header bytes.
+    pub fn to_bytes(&self) -> [u8; 2] {
+        [
+            ((self.filter & 0x0f) << 4) | (self.shift & 0x0f),
+            u8::from(self.is_loop_end) | (u8::from(self.is_loop_repeat) << 1) |
+                (u8::from(self.is_loop_start) << 2),
+        ]
+    }
+}
+
+/// Decodes a 16-byte SPU ADPCM block to 16-bit signed integer samples.
+///
+/// `state` should be initialized to zero for the first block of the sample and subsequent calls
+/// should pass in the state values from the previous call. When a looping sample jumps to its
+/// loop start, the state is kept as it is.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// [`SPU_ADPCM_SAMPLES_PER_BLOCK`].
+///
+/// The parsed block header is returned so that the caller can handle the loop flags.
+///
+/// An error is returned if the block header has an invalid filter value or
+/// if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_spu_adpcm_block(buf: &[u8; 16], state: &mut SpuAdpcmState, out_samples: &mut [i16])
+    -> Result<SpuAdpcmBlockHeader, Error> {
+
+    if out_samples.len() != SPU_ADPCM_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    let header = SpuAdpcmBlockHeader::from_bytes([ buf[0], buf[1] ])?;
+    for (out, code) in out_samples.iter_mut().zip(buf[2..].iter()
+        .flat_map(|b| [ *b << 4, *b & 0xf0 ])) {
+        let code = i32::from(i8::from_le_bytes([ code ])) >> 4;
+        *out = decode_code(state, code,
+            |s, code| s.decoded_value(code, header.shift, header.filter));
+    }
+    Ok(header)
+}
+
+/// Decodes consecutive SPU ADPCM blocks to 16-bit signed integer samples.
+///
+/// `buf` must contain whole 16-byte blocks. All blocks are decoded, the loop flags
+/// aren't checked.
+///
+/// `state` should be initialized to zero for the first call and subsequent calls should pass in
+/// the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `28 * buf.len() / 16`.
+///
+/// An error is returned if a block header has an invalid filter value,
+/// if the `buf` length isn't a multiple of 16 or if the `out_samples` length isn't correct.
+/// If an error is returned, `state` and `out_samples` are left unmodified.
+pub fn decode_spu_adpcm(buf: &[u8], state: &mut SpuAdpcmState, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    if buf.len() % SPU_ADPCM_BLOCK_SIZE != 0 ||
+        out_samples.len() != buf.len() / SPU_ADPCM_BLOCK_SIZE * SPU_ADPCM_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    for block in buf.chunks_exact(SPU_ADPCM_BLOCK_SIZE) {
+        SpuAdpcmBlockHeader::from_bytes([ block[0], block[1] ])?;
+    }
+    for (block, out) in buf.chunks_exact(SPU_ADPCM_BLOCK_SIZE)
+        .zip(out_samples.chunks_exact_mut(SPU_ADPCM_SAMPLES_PER_BLOCK)) {
+        let Ok(block) = block.try_into() else {
+            return Err(Error::InvalidBufferSize);
+        };
+        decode_spu_adpcm_block(block, state, out)?;
+    }
+    Ok(())
+}
+
+/// Encodes 28 samples to a 16-byte SPU ADPCM block.
+///
+/// All combinations of filter values 0..=4 and shift values 0..=12 are tried and the one
+/// giving the smallest squared error is used. If `is_filter0_only` is `true`, only filter 0
+/// is tried. Filter 0 doesn't depend on the previous samples, so it should be used for
+/// the first block of a sample and for the loop start block, because the decoder state
+/// isn't known when playback starts or jumps to the loop start.
+///
+/// The loop flags of the block header are set to zero. [`encode_spu_adpcm()`] sets them
+/// for a whole sample.
+///
+/// `state` should be initialized to zero for the first call and subsequent calls should pass in
+/// the state values from the previous call.
+///
+/// An error is returned if the `samples` length isn't [`SPU_ADPCM_SAMPLES_PER_BLOCK`].
+/// If an error is returned, `state` and `out_buf` are left unmodified.
+pub fn encode_spu_adpcm_block(samples: &[i16], state: &mut SpuAdpcmState, is_filter0_only: bool,
+    out_buf: &mut [u8; 16]) -> Result<(), Error> {
+
+    if samples.len() != SPU_ADPCM_SAMPLES_PER_BLOCK {
+        return Err(Error::InvalidBufferSize);
+    }
+    let filter_count = if is_filter0_only { 1 } else { 5 };
+    let mut best_filter = 0;
+    let mut best_shift = 0;
+    let mut best_error = u64::MAX;
+    for filter in 0..filter_count {
+        for shift in 0..=12 {
+            let error = squared_error(state, samples,
+                |s, code| s.decoded_value(code, shift, filter));
+            if error < best_error {
+                best_filter = filter;
+                best_shift = shift;
+                best_error = error;
+            }
+        }
+    }
+    let header = SpuAdpcmBlockHeader { shift: best_shift, filter: best_filter,
+        ..SpuAdpcmBlockHeader::default() };
+    out_buf[..2].copy_from_slice(&header.to_bytes());
+    for (byte, pair) in out_buf[2..].iter_mut().zip(samples.chunks_exact(2)) {
+        let mut nibbles = [0u8; 2];
+        for (nibble, sample) in nibbles.iter_mut().zip(pair) {
+            let code = encode_sample(state, *sample,
+                |s, code| s.decoded_value(code, best_shift, best_filter));
+            #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)] // the code is -8..=7
+            {
+            *nibble = (code & 0x0f) as u8;
+            }
+        }
+        *byte = nibbles[0] | (nibbles[1] << 4);
+    }
+    Ok(())
+}
+
+/// Encodes a whole sample to SPU ADPCM blocks.
+///
+/// The `samples` length must be a multiple of [`SPU_ADPCM_SAMPLES_PER_BLOCK`]. The first block
+/// and the loop start block use filter 0, other blocks use the best filter and shift values,
+/// see [`encode_spu_adpcm_block()`].
+///
+/// If `loop_start` is set, the loop start flag is set for the block starting at sample
+/// `loop_start` and the loop end and loop repeat flags are set for the last block.
+/// Otherwise, the loop end flag is set for the last block, so that the voice is released
+/// after it.
+///
+/// This function outputs the encoded blocks to `out_buf`. The `out_buf` length must be
+/// `16 * samples.len() / 28`.
+///
+/// An error is returned if the `samples` length or the `out_buf` length isn't correct or
+/// if `loop_start` isn't a multiple of 28 or isn't less than the `samples` length.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn encode_spu_adpcm(samples: &[i16], loop_start: Option<usize>, out_buf: &mut [u8])
+    -> Result<(), Error> {
+
+    if samples.len() % SPU_ADPCM_SAMPLES_PER_BLOCK != 0 ||
+        out_buf.len() != samples.len() / SPU_ADPCM_SAMPLES_PER_BLOCK * SPU_ADPCM_BLOCK_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    if let Some(loop_start) = loop_start {
+        if loop_start % SPU_ADPCM_SAMPLES_PER_BLOCK != 0 || loop_start >= samples.len() {
+            return Err(Error::InvalidData);
+        }
+    }
+    let loop_block = loop_start.map(|pos| pos / SPU_ADPCM_SAMPLES_PER_BLOCK);
+    let block_count = samples.len() / SPU_ADPCM_SAMPLES_PER_BLOCK;
+    let mut state = SpuAdpcmState::new();
+    for (i, (block_samples, block)) in samples.chunks_exact(SPU_ADPCM_SAMPLES_PER_BLOCK)
+        .zip(out_buf.chunks_exact_mut(SPU_ADPCM_BLOCK_SIZE)).enumerate() {
+        let Ok(block) = <&mut [u8; 16]>::try_from(block) else {
+            return Err(Error::InvalidBufferSize);
+        };
+        let is_loop_start = loop_block == Some(i);
+        encode_spu_adpcm_block(block_samples, &mut state, i == 0 || is_loop_start, &mut *block)?;
+        if is_loop_start {
+            block[1] |= 0x04;
+        }
+        if i + 1 == block_count {
+            block[1] |= if loop_block.is_some() { 0x03 } else { 0x01 };
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CodecState;
+
+    #[test]
+    fn test_spu_adpcm_block_header() {
+        assert!(matches!(SpuAdpcmBlockHeader::from_bytes([ 0x4c, 0x06 ]),
+            Ok(SpuAdpcmBlockHeader { shift: 12, filter: 4, is_loop_end: false,
+                is_loop_repeat: true, is_loop_start: true })));
+        assert!(matches!(SpuAdpcmBlockHeader::from_bytes([ 0x4c, 0x06 ]).map(|h| h.to_bytes()),
+            Ok([ 0x4c, 0x06 ])));
+        assert!(matches!(SpuAdpcmBlockHeader::from_bytes([ 0x50, 0x00 ]),
+            Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_decode_spu_adpcm_block() {
+        // filter 0: the low nibble is the first sample
+        let mut buf = [0u8; 16];
+        buf[0] = 0x0c;
+        buf[2] = 0x81;
+        buf[3] = 0x07;
+        let mut state = SpuAdpcmState::new();
+        let mut out = [0i16; 28];
+        assert!(matches!(decode_spu_adpcm_block(&buf, &mut state, &mut out),
+            Ok(SpuAdpcmBlockHeader { shift: 12, filter: 0, .. })));
+        assert_eq!(out[..4], [ 1, -8, 7, 0 ]);
+
+        // filter 1 decays towards zero
+        let mut state = SpuAdpcmState { previous: 4096, previous2: 0 };
+        let mut buf = [0u8; 16];
+        buf[0] = 0x1c;
+        buf[1] = 0x01;
+        assert!(matches!(decode_spu_adpcm_block(&buf, &mut state, &mut out),
+            Ok(SpuAdpcmBlockHeader { is_loop_end: true, .. })));
+        assert_eq!(out[..3], [ 3840, 3600, 3375 ]);
+
+        // filter 4 and a shift of 0
+        let mut state = SpuAdpcmState { previous: 1000, previous2: 500 };
+        let mut buf = [0u8; 16];
+        buf[0] = 0x40;
+        buf[2] = 0x0f;
+        assert!(decode_spu_adpcm_block(&buf, &mut state, &mut out).is_ok());
+        assert_eq!(out[0], -4096 + 1438);
+
+        // reserved shift values work like 9
+        let mut state = SpuAdpcmState::new();
+        let mut buf = [0u8; 16];
+        buf[0] = 0x0f;
+        buf[2] = 0x04;
+        assert!(decode_spu_adpcm_block(&buf, &mut state, &mut out).is_ok());
+        assert_eq!(out[0], 32);
+
+        // errors
+        assert!(matches!(decode_spu_adpcm_block(&buf, &mut state, &mut out[..27]),
+            Err(Error::InvalidBufferSize)));
+        buf[0] = 0x60;
+        assert!(matches!(decode_spu_adpcm_block(&buf, &mut state, &mut out),
+            Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_decode_spu_adpcm() {
+        let mut buf = [0u8; 32];
+        buf[0] = 0x08;
+        buf[2] = 0x01;
+        buf[16] = 0x08;
+        buf[18] = 0x20;
+        let mut state = SpuAdpcmState::new();
+        let mut out = [0i16; 56];
+        assert!(decode_spu_adpcm(&buf, &mut state, &mut out).is_ok());
+        assert_eq!(out[0], 16);
+        assert_eq!(out[28..30], [ 0, 32 ]);
+
+        // errors
+        let mut state = SpuAdpcmState::new();
+        assert!(matches!(decode_spu_adpcm(&buf[..31], &mut state, &mut out),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_spu_adpcm(&buf, &mut state, &mut out[..28]),
+            Err(Error::InvalidBufferSize)));
+        buf[16] = 0x70;
+        assert!(matches!(decode_spu_adpcm(&buf, &mut state, &mut out),
+            Err(Error::InvalidData)));
+        assert_eq!(state, SpuAdpcmState::new());
+    }
+
+    #[test]
+    fn test_encode_spu_adpcm_block() {
+        let mut samples = [0i16; 28 * 4];
+        crate::ToneGenerator::new(440.0, 22050, 16000).fill(&mut samples);
+        let mut enc_state = SpuAdpcmState::new();
+        let mut dec_state = SpuAdpcmState::new();
+        let mut buf = [0u8; 16];
+        let mut out = [0i16; 28];
+        for (i, block_samples) in samples.chunks_exact(28).enumerate() {
+            assert!(encode_spu_adpcm_block(block_samples, &mut enc_state, i == 0, &mut buf)
+                .is_ok());
+            assert_eq!(buf[1], 0);
+            assert!(decode_spu_adpcm_block(&buf, &mut dec_state, &mut out).is_ok());
+            assert_eq!(enc_state, dec_state);
+            // filter 0 of the first block needs a larger shift
+            let max_error = if i == 0 { 2049 } else { 128 };
+            for (a, b) in out.iter().zip(block_samples) {
+                assert!((i32::from(*a) - i32::from(*b)).abs() < max_error);
+            }
+        }
+
+        let mut state = SpuAdpcmState::new();
+        assert!(matches!(encode_spu_adpcm_block(&samples[..27], &mut state, false, &mut buf),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_encode_spu_adpcm() {
+        let mut samples = [0i16; 28 * 3];
+        crate::ToneGenerator::new(1000.0, 22050, 10000).fill(&mut samples);
+        let mut buf = [0u8; 48];
+        assert!(encode_spu_adpcm(&samples, Some(28), &mut buf).is_ok());
+        assert_eq!([ buf[1], buf[17], buf[33] ], [ 0x00, 0x04, 0x03 ]);
+        assert_eq!(buf[16] >> 4, 0);
+
+        let mut state = SpuAdpcmState::new();
+        let mut out = [0i16; 28 * 3];
+        assert!(decode_spu_adpcm(&buf, &mut state, &mut out).is_ok());
+        for (a, b) in out.iter().zip(samples) {
+            assert!((i32::from(*a) - i32::from(b)).abs() < 1024);
+        }
+
+        assert!(encode_spu_adpcm(&samples, None, &mut buf).is_ok());
+        assert_eq!([ buf[1], buf[17], buf[33] ], [ 0x00, 0x00, 0x01 ]);
+
+        // errors
+        assert!(matches!(encode_spu_adpcm(&samples, Some(14), &mut buf), Err(Error::InvalidData)));
+        assert!(matches!(encode_spu_adpcm(&samples, Some(84), &mut buf), Err(Error::InvalidData)));
+        assert!(matches!(encode_spu_adpcm(&samples, None, &mut buf[..32]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(encode_spu_adpcm(&samples[..80], None, &mut buf),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = SpuAdpcmState { previous: -1000, previous2: 2000 };
+        assert!(!state.is_default());
+        let mut buf = [0u8; SpuAdpcmState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x18, 0xfc, 0xd0, 0x07 ]);
+        assert!(matches!(SpuAdpcmState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, SpuAdpcmState::new());
+        assert!(matches!(SpuAdpcmState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 5]), Err(Error::InvalidBufferSize)));
+    }
+}
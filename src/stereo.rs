@@ -0,0 +1,243 @@
+
+use crate::{CodecState, Error};
+use crate::AdpcmImaState;
+use crate::{decode_adpcm_ima_ima4, decode_adpcm_ima_ms, encode_adpcm_ima_ima4,
+    encode_adpcm_ima_ms};
+
+/// IMA ADPCM encoder and decoder states for 2 channel (stereo) audio.
+///
+/// This wraps the left and right channel states and provides block-level functions,
+/// which handle the interleaving of samples.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StereoAdpcmImaState {
+    /// State of the left (first) channel.
+    pub left: AdpcmImaState,
+    /// State of the right (second) channel.
+    pub right: AdpcmImaState,
+}
+
+impl StereoAdpcmImaState {
+    /// Creates a new StereoAdpcmImaState with zero values.
+    pub fn new() -> StereoAdpcmImaState {
+        StereoAdpcmImaState {
+            left: AdpcmImaState::new(),
+            right: AdpcmImaState::new(),
+        }
+    }
+
+    /// Decodes a 2 channel AIFF-C / QT "ima4" packet to interleaved 16-bit signed integer
+    /// samples.
+    ///
+    /// `buf` should contain a 34 byte block for the left channel followed by a 34 byte block
+    /// for the right channel. See [`decode_adpcm_ima_ima4()`](crate::decode_adpcm_ima_ima4)
+    /// for details.
+    pub fn decode_ima4_packet(&mut self, buf: &[u8; 68], out_samples: &mut [i16; 128]) {
+        let mut left = [0i16; 64];
+        let mut right = [0i16; 64];
+        let left_buf: [u8; 34] = core::array::from_fn(|i| buf[i]);
+        let right_buf: [u8; 34] = core::array::from_fn(|i| buf[34 + i]);
+        decode_adpcm_ima_ima4(&left_buf, &mut self.left, &mut left);
+        decode_adpcm_ima_ima4(&right_buf, &mut self.right, &mut right);
+        for ((out, l), r) in out_samples.chunks_exact_mut(2).zip(left).zip(right) {
+            out[0] = l;
+            out[1] = r;
+        }
+    }
+
+    /// Encodes interleaved 16-bit signed integer samples to a 2 channel AIFF-C / QT "ima4"
+    /// packet.
+    ///
+    /// This function outputs a 34 byte block for the left channel followed by a 34 byte block
+    /// for the right channel. See [`encode_adpcm_ima_ima4()`](crate::encode_adpcm_ima_ima4)
+    /// for details.
+    pub fn encode_ima4_packet(&mut self, samples: &[i16; 128], out_buf: &mut [u8; 68]) {
+        let mut left = [0i16; 64];
+        let mut right = [0i16; 64];
+        for ((pair, l), r) in samples.chunks_exact(2).zip(&mut left).zip(&mut right) {
+            *l = pair[0];
+            *r = pair[1];
+        }
+        let mut left_buf = [0u8; 34];
+        let mut right_buf = [0u8; 34];
+        encode_adpcm_ima_ima4(&left, &mut self.left, &mut left_buf);
+        encode_adpcm_ima_ima4(&right, &mut self.right, &mut right_buf);
+        for (out, b) in out_buf.iter_mut().zip(left_buf.iter().chain(&right_buf)) {
+            *out = *b;
+        }
+    }
+
+    /// Decodes a 2 channel WAV / MS IMA ADPCM (wav format 0x0011) block to interleaved
+    /// 16-bit signed integer samples.
+    ///
+    /// MS IMA ADPCM blocks contain the whole decoder state in their headers, so decoding
+    /// doesn't use or change the state. See
+    /// [`decode_adpcm_ima_ms()`](crate::decode_adpcm_ima_ms) for details and errors.
+    pub fn decode_ms_block(&self, buf: &[u8], out_samples: &mut [i16]) -> Result<(), Error> {
+        decode_adpcm_ima_ms(buf, true, out_samples)
+    }
+
+    /// Encodes interleaved 16-bit signed integer samples to a 2 channel WAV / MS IMA ADPCM
+    /// (wav format 0x0011) block.
+    ///
+    /// See [`encode_adpcm_ima_ms()`](crate::encode_adpcm_ima_ms) for details and errors.
+    pub fn encode_ms_block(&mut self, samples: &[i16], out_buf: &mut [u8]) -> Result<(), Error> {
+        let mut states = [ self.left.clone(), self.right.clone() ];
+        encode_adpcm_ima_ms(samples, &mut states, out_buf)?;
+        let [ left, right ] = states;
+        self.left = left;
+        self.right = right;
+        Ok(())
+    }
+}
+
+impl CodecState for StereoAdpcmImaState {
+    /// The left channel state followed by the right channel state, both in
+    /// the [`AdpcmImaState`] format.
+    const SERIALIZED_LEN: usize = 2 * AdpcmImaState::SERIALIZED_LEN;
+
+    fn to_bytes(&self, out_buf: &mut [u8]) -> Result<(), Error> {
+        if out_buf.len() != Self::SERIALIZED_LEN {
+            return Err(Error::InvalidBufferSize);
+        }
+        let (left, right) = out_buf.split_at_mut(AdpcmImaState::SERIALIZED_LEN);
+        self.left.to_bytes(left)?;
+        self.right.to_bytes(right)
+    }
+
+    fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        if buf.len() != Self::SERIALIZED_LEN {
+            return Err(Error::InvalidBufferSize);
+        }
+        let (left, right) = buf.split_at(AdpcmImaState::SERIALIZED_LEN);
+        Ok(StereoAdpcmImaState {
+            left: AdpcmImaState::from_bytes(left)?,
+            right: AdpcmImaState::from_bytes(right)?,
+        })
+    }
+}
+
+/// Decodes a 2 channel AIFF-C / QT "ima4" packet to interleaved 16-bit signed integer samples.
+///
+/// `buf` should contain two consecutive 34-byte blocks, which QuickTime stores for each frame
+/// of 2 channel audio: first the block for the left channel and then the block for the right
+/// channel. This is the same as [`StereoAdpcmImaState::decode_ima4_packet()`].
+///
+/// The `state` parameter should be initialized to zero for the first call and subsequent calls
+/// should pass in the state values from the previous call.
+///
+/// This function outputs 128 decoded samples (64 for each channel) to `out_samples`.
+pub fn decode_adpcm_ima_ima4_stereo(buf: &[u8; 68], state: &mut StereoAdpcmImaState,
+    out_samples: &mut [i16; 128]) {
+    state.decode_ima4_packet(buf, out_samples);
+}
+
+/// Encodes interleaved 16-bit signed integer samples to a 2 channel AIFF-C / QT "ima4" packet.
+///
+/// This function outputs two consecutive 34-byte blocks to `out_buf`: first the block for
+/// the left channel and then the block for the right channel. This is the same as
+/// [`StereoAdpcmImaState::encode_ima4_packet()`].
+///
+/// The `state` parameter should be initialized to zero for the first call and subsequent calls
+/// should pass in the state values from the previous call.
+pub fn encode_adpcm_ima_ima4_stereo(samples: &[i16; 128], state: &mut StereoAdpcmImaState,
+    out_buf: &mut [u8; 68]) {
+    state.encode_ima4_packet(samples, out_buf);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_samples(out: &mut [i16]) {
+        for (i, s) in out.iter_mut().enumerate() {
+            // the left channel is a ramp, the right channel is a square wave
+            let value = if i % 2 == 0 { i * 200 } else { (i / 16 % 2) * 8000 };
+            *s = i16::try_from(value).unwrap_or(0);
+        }
+    }
+
+    #[test]
+    fn test_stereo_ima4_packet() {
+        let mut samples = [0i16; 128];
+        test_samples(&mut samples);
+        let mut encoder = StereoAdpcmImaState::new();
+        let mut packet = [0u8; 68];
+        encoder.encode_ima4_packet(&samples, &mut packet);
+
+        // compare to encoding the channels separately
+        let mut left_state = AdpcmImaState::new();
+        let mut left_samples = [0i16; 64];
+        left_samples.iter_mut().zip(samples.iter().step_by(2)).for_each(|(l, s)| *l = *s);
+        let mut left_block = [0u8; 34];
+        encode_adpcm_ima_ima4(&left_samples, &mut left_state, &mut left_block);
+        assert_eq!(packet[..34], left_block);
+        assert_eq!(encoder.left, left_state);
+
+        let mut decoder = StereoAdpcmImaState::new();
+        let mut decoded = [0i16; 128];
+        decoder.decode_ima4_packet(&packet, &mut decoded);
+        assert_eq!(decoder, encoder);
+        let mut left_decoded = [0i16; 64];
+        decode_adpcm_ima_ima4(&left_block, &mut AdpcmImaState::new(), &mut left_decoded);
+        assert!(decoded.iter().step_by(2).eq(left_decoded.iter()));
+        let mut right_decoded = [0i16; 64];
+        let right_block: [u8; 34] = core::array::from_fn(|i| packet[34 + i]);
+        decode_adpcm_ima_ima4(&right_block, &mut AdpcmImaState::new(), &mut right_decoded);
+        assert!(decoded.iter().skip(1).step_by(2).eq(right_decoded.iter()));
+
+        // the free functions work the same way
+        let mut state = StereoAdpcmImaState::new();
+        let mut buf = [0u8; 68];
+        encode_adpcm_ima_ima4_stereo(&samples, &mut state, &mut buf);
+        assert_eq!(buf, packet);
+        assert_eq!(state, encoder);
+        let mut state = StereoAdpcmImaState::new();
+        let mut out = [0i16; 128];
+        decode_adpcm_ima_ima4_stereo(&buf, &mut state, &mut out);
+        assert_eq!(out, decoded);
+        assert_eq!(state, decoder);
+    }
+
+    #[test]
+    fn test_stereo_ms_block() {
+        let mut samples = [0i16; 34];
+        test_samples(&mut samples);
+        let mut encoder = StereoAdpcmImaState::new();
+        let mut block = [0u8; 24];
+        assert!(encoder.encode_ms_block(&samples, &mut block).is_ok());
+        let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+        let mut expected = [0u8; 24];
+        assert!(encode_adpcm_ima_ms(&samples, &mut states, &mut expected).is_ok());
+        assert_eq!(block, expected);
+        assert_eq!([ encoder.left.clone(), encoder.right.clone() ], states);
+
+        let mut decoded = [0i16; 34];
+        assert!(encoder.decode_ms_block(&block, &mut decoded).is_ok());
+        assert_eq!(decoded[..2], samples[..2]);
+
+        // errors leave the state unmodified
+        let previous = encoder.clone();
+        assert!(matches!(encoder.encode_ms_block(&samples[..33], &mut block),
+            Err(Error::InvalidBufferSize)));
+        assert_eq!(encoder, previous);
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = StereoAdpcmImaState {
+            left: AdpcmImaState { predictor: -1000, step_index: 42 },
+            right: AdpcmImaState { predictor: 2000, step_index: 7 },
+        };
+        assert!(!state.is_default());
+        let mut buf = [0u8; StereoAdpcmImaState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x18, 0xfc, 42, 0xd0, 0x07, 7 ]);
+        assert!(matches!(StereoAdpcmImaState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, StereoAdpcmImaState::new());
+        assert!(matches!(StereoAdpcmImaState::from_bytes(&buf[1..]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 7]), Err(Error::InvalidBufferSize)));
+    }
+}
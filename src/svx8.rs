@@ -0,0 +1,118 @@
+use crate::Error;
+
+// delta table from the IFF 8SVX specification
+const FIBONACCI_DELTA_TABLE: &[i8; 16] = &[
+    -34, -21, -13, -8, -5, -3, -2, -1, 0, 1, 2, 3, 5, 8, 13, 21
+];
+
+// delta table for the exponential-delta variant
+const EXPONENTIAL_DELTA_TABLE: &[i8; 16] = &[
+    -128, -64, -32, -16, -8, -4, -2, -1, 0, 1, 2, 4, 8, 16, 32, 64
+];
+
+// decodes 4-bit codes to 8-bit samples, high nibble first
+fn decode_svx8_delta(buf: &[u8], table: &[i8; 16], out_samples: &mut [i8])
+    -> Result<(), Error> {
+
+    if buf.len() < 2 {
+        return Err(Error::InvalidBufferSize);
+    }
+    let expected_sample_len = (buf.len() - 2)
+        .checked_mul(2)
+        .ok_or(Error::InvalidBufferSize)?;
+    if expected_sample_len != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    // the first byte is padding and the second byte is the initial value
+    let mut value = i8::from_le_bytes([ buf[1] ]);
+    for (b, out) in buf[2..].iter().zip(out_samples.chunks_exact_mut(2)) {
+        value = value.wrapping_add(table[usize::from(*b >> 4)]);
+        out[0] = value;
+        value = value.wrapping_add(table[usize::from(*b & 0x0f)]);
+        out[1] = value;
+    }
+    Ok(())
+}
+
+/// Decodes Amiga IFF 8SVX Fibonacci-delta compressed data to 8-bit signed samples.
+///
+/// `buf` should contain the contents of a `BODY` chunk (or one channel of it): a pad byte,
+/// the initial 8-bit signed sample value and bytes of 4-bit encoded deltas. The `buf` length
+/// must be at least 2. The high nibble of each byte is decoded first. Sample values wrap
+/// around at 8 bits like in the reference implementation.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * (buf.len() - 2)`.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_svx8_fibonacci(buf: &[u8], out_samples: &mut [i8]) -> Result<(), Error> {
+    decode_svx8_delta(buf, FIBONACCI_DELTA_TABLE, out_samples)
+}
+
+/// Decodes Amiga IFF 8SVX Exponential-delta compressed data to 8-bit signed samples.
+///
+/// The data layout is the same as for [`decode_svx8_fibonacci()`], only the delta table
+/// is different.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * (buf.len() - 2)`.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_svx8_exponential(buf: &[u8], out_samples: &mut [i8]) -> Result<(), Error> {
+    decode_svx8_delta(buf, EXPONENTIAL_DELTA_TABLE, out_samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_svx8_fibonacci() {
+        let mut samples = [0i8; 4];
+        assert!(decode_svx8_fibonacci(&[ 0x00, 0x0a, 0x9f, 0x08 ], &mut samples).is_ok());
+        assert_eq!(samples, [ 11, 32, -2, -2 ]);
+
+        // values wrap around
+        let mut samples = [0i8; 2];
+        assert!(decode_svx8_fibonacci(&[ 0x00, 0x7e, 0xf0 ], &mut samples).is_ok());
+        assert_eq!(samples, [ -109, 113 ]);
+
+        // no encoded data
+        let mut samples = [0i8; 0];
+        assert!(decode_svx8_fibonacci(&[ 0x00, 0x10 ], &mut samples).is_ok());
+
+        // invalid buf length
+        let mut samples = [0i8; 0];
+        assert!(matches!(decode_svx8_fibonacci(&[ 0x00 ], &mut samples),
+            Err(Error::InvalidBufferSize)));
+
+        // invalid out_samples length
+        let mut samples = [0i8; 3];
+        assert!(matches!(decode_svx8_fibonacci(&[ 0x00, 0x00, 0x88 ], &mut samples),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_svx8_exponential() {
+        let mut samples = [0i8; 4];
+        assert!(decode_svx8_exponential(&[ 0x00, 0x0a, 0x9f, 0x08 ], &mut samples).is_ok());
+        assert_eq!(samples, [ 11, 75, -53, -53 ]);
+
+        // values wrap around
+        let mut samples = [0i8; 2];
+        assert!(decode_svx8_exponential(&[ 0x00, 0x7e, 0xf0 ], &mut samples).is_ok());
+        assert_eq!(samples, [ -66, 62 ]);
+
+        // invalid buf length
+        let mut samples = [0i8; 0];
+        assert!(matches!(decode_svx8_exponential(&[], &mut samples),
+            Err(Error::InvalidBufferSize)));
+
+        // invalid out_samples length
+        let mut samples = [0i8; 1];
+        assert!(matches!(decode_svx8_exponential(&[ 0x00, 0x00, 0x88 ], &mut samples),
+            Err(Error::InvalidBufferSize)));
+    }
+}
@@ -0,0 +1,149 @@
+
+use crate::Error;
+use crate::AdpcmImaState;
+use crate::{decode_adpcm_ima, decode_adpcm_ima_ima4_with, decode_adpcm_ima_ms_with};
+
+// G.711 A-law and μ-law decoders never output -32768 (the smallest values are -32256 and
+// -32124), so they don't need symmetric variants.
+
+/// Clamps a sample value to the symmetric range -32767..=32767.
+///
+/// This can be used for DSP chains, which require symmetric full-scale values, for instance,
+/// before fixed-point gain stages, which overflow when -32768 is negated.
+#[inline(always)]
+pub fn clamp_symmetric(sample: i16) -> i16 {
+    sample.max(-32767)
+}
+
+/// Clamps sample values in `samples` to the symmetric range -32767..=32767.
+pub fn clamp_symmetric_slice(samples: &mut [i16]) {
+    for s in samples {
+        *s = clamp_symmetric(*s);
+    }
+}
+
+/// Decodes a 4-bit encoded IMA ADPCM value to a linear 16-bit signed integer sample value
+/// in the symmetric range -32767..=32767.
+///
+/// This works like [`decode_adpcm_ima()`], but the returned sample value is clamped to
+/// -32767..=32767. `state` is updated with the unclamped value, so that the decoder stays
+/// in sync with the encoder.
+#[inline(always)]
+pub fn decode_adpcm_ima_symmetric(encoded_nibble: u8, state: &mut AdpcmImaState) -> i16 {
+    clamp_symmetric(decode_adpcm_ima(encoded_nibble, state))
+}
+
+/// Decodes an AIFF-C / QT "ima4" compressed block to 16-bit signed integer samples
+/// in the symmetric range -32767..=32767.
+///
+/// This works like [`decode_adpcm_ima_ima4()`](crate::decode_adpcm_ima_ima4), but
+/// the decoded samples are clamped to -32767..=32767. `state` is updated with the unclamped
+/// values.
+pub fn decode_adpcm_ima_ima4_symmetric(buf: &[u8; 34], state: &mut AdpcmImaState,
+    out_samples: &mut [i16; 64]) {
+
+    let mut out = out_samples.iter_mut();
+    decode_adpcm_ima_ima4_with(buf, state, &mut |s| {
+        if let Some(o) = out.next() {
+            *o = clamp_symmetric(s);
+        }
+    });
+}
+
+/// Decodes WAV / MS IMA ADPCM (wav format 0x0011) compressed block to 16-bit signed integer
+/// samples in the symmetric range -32767..=32767.
+///
+/// This works like [`decode_adpcm_ima_ms()`](crate::decode_adpcm_ima_ms), but
+/// the decoded samples are clamped to -32767..=32767.
+///
+/// An error is returned if the `buf` or `out_samples` length isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_adpcm_ima_ms_symmetric(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    // decode_adpcm_ima_ms_with() checks the other buf length requirements
+    let expected_sample_len = buf.len()
+        .checked_sub(4 * channels)
+        .and_then(|v| v.checked_mul(2))
+        .and_then(|v| v.checked_add(channels))
+        .ok_or(Error::InvalidBufferSize)?;
+    if expected_sample_len != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut out = out_samples.iter_mut();
+    decode_adpcm_ima_ms_with(buf, is_stereo, &mut |s| {
+        if let Some(o) = out.next() {
+            *o = clamp_symmetric(s);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode_adpcm_ima_ima4, decode_adpcm_ima_ms};
+
+    #[test]
+    fn test_clamp_symmetric() {
+        assert_eq!(clamp_symmetric(-32768), -32767);
+        assert_eq!(clamp_symmetric(-32767), -32767);
+        assert_eq!(clamp_symmetric(32767), 32767);
+        let mut samples = [ -32768, 0, 32767 ];
+        clamp_symmetric_slice(&mut samples);
+        assert_eq!(samples, [ -32767, 0, 32767 ]);
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_symmetric() {
+        let mut state = AdpcmImaState { predictor: -30123, step_index: 80 };
+        assert_eq!(decode_adpcm_ima_symmetric(14, &mut state), -32767);
+        // the state isn't clamped
+        assert_eq!(state, AdpcmImaState { predictor: -32768, step_index: 86 });
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_ima4_symmetric() {
+        // header predictor -32768, step index 88 and decreasing nibbles
+        let mut buf = [0xffu8; 34];
+        buf[0] = 0x80;
+        buf[1] = 88;
+        let mut state = AdpcmImaState::new();
+        let mut expected = [0i16; 64];
+        decode_adpcm_ima_ima4(&buf, &mut state, &mut expected);
+        assert!(expected.contains(&-32768));
+        let mut state = AdpcmImaState::new();
+        let mut samples = [0i16; 64];
+        decode_adpcm_ima_ima4_symmetric(&buf, &mut state, &mut samples);
+        clamp_symmetric_slice(&mut expected);
+        assert_eq!(samples, expected);
+    }
+
+    #[test]
+    fn test_decode_adpcm_ima_ms_symmetric() {
+        let mut buf = [0xffu8; 12];
+        buf[0] = 0x00;
+        buf[1] = 0x80;
+        buf[2] = 88;
+        buf[3] = 0;
+        let mut expected = [0i16; 17];
+        assert!(decode_adpcm_ima_ms(&buf, false, &mut expected).is_ok());
+        assert_eq!(expected[0], -32768);
+        let mut samples = [0i16; 17];
+        assert!(decode_adpcm_ima_ms_symmetric(&buf, false, &mut samples).is_ok());
+        clamp_symmetric_slice(&mut expected);
+        assert_eq!(samples, expected);
+
+        // invalid lengths
+        let mut samples = [0i16; 16];
+        assert!(matches!(decode_adpcm_ima_ms_symmetric(&buf, false, &mut samples),
+            Err(Error::InvalidBufferSize)));
+        let mut samples = [0i16; 0];
+        assert!(matches!(decode_adpcm_ima_ms_symmetric(&buf[..2], false, &mut samples),
+            Err(Error::InvalidBufferSize)));
+    }
+}
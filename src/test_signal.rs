@@ -0,0 +1,6 @@
+// test signals shared by the unit tests
+
+// returns sample `i` of a loud 1 kHz tone at 8000 Hz, which has large steps between samples
+pub(crate) fn tone(i: usize) -> i16 {
+    [ 0, 7000, 10000, 7000, 0, -7000, -10000, -7000 ][i % 8]
+}
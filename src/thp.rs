@@ -0,0 +1,201 @@
+use crate::Error;
+use crate::{DSP_ADPCM_FRAME_SIZE, DSP_ADPCM_SAMPLES_PER_FRAME, DspAdpcmState, decode_dsp_adpcm};
+
+// Nintendo THP movies store audio as DSP-ADPCM. Each audio frame has its own header with
+// the prediction coefficients and the initial history values of both channels.
+
+/// Size of the THP audio frame header in bytes.
+pub const THP_AUDIO_HEADER_SIZE: usize = 80;
+
+/// Header of a THP audio frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThpAudioFrameHeader {
+    /// Size of the encoded data of one channel in bytes.
+    pub channel_size: u32,
+    /// Number of samples for each channel.
+    pub sample_count: u32,
+    /// DSP-ADPCM prediction coefficients of the left and right channels.
+    pub coefficients: [[i16; 16]; 2],
+    /// Initial history values of the left and right channels.
+    pub states: [DspAdpcmState; 2],
+}
+
+/// Parses the 80-byte header of a THP audio frame.
+///
+/// The header always has coefficients and history values for two channels. The values of
+/// the right channel aren't used for 1 channel audio.
+///
+/// An error is returned if the `buf` length is less than [`THP_AUDIO_HEADER_SIZE`].
+pub fn parse_thp_audio_frame_header(buf: &[u8]) -> Result<ThpAudioFrameHeader, Error> {
+    if buf.len() < THP_AUDIO_HEADER_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    let read_i16 = |pos: usize| i16::from_be_bytes([ buf[pos], buf[pos + 1] ]);
+    let mut coefficients = [[0i16; 16]; 2];
+    for (ch, channel_coefficients) in coefficients.iter_mut().enumerate() {
+        for (i, coefficient) in channel_coefficients.iter_mut().enumerate() {
+            *coefficient = read_i16(8 + ch * 32 + i * 2);
+        }
+    }
+    Ok(ThpAudioFrameHeader {
+        channel_size: u32::from_be_bytes([ buf[0], buf[1], buf[2], buf[3] ]),
+        sample_count: u32::from_be_bytes([ buf[4], buf[5], buf[6], buf[7] ]),
+        coefficients,
+        states: [
+            DspAdpcmState { previous: read_i16(72), previous2: read_i16(74) },
+            DspAdpcmState { previous: read_i16(76), previous2: read_i16(78) },
+        ],
+    })
+}
+
+/// Decodes a THP audio frame to 16-bit signed integer samples.
+///
+/// `buf` should contain the whole audio frame: the 80-byte header followed by the DSP-ADPCM
+/// data of the left channel and then the data of the right channel. Each channel has
+/// `channel_size` bytes of data. The decoder state is initialized from the header, so every
+/// audio frame can be decoded independently.
+///
+/// `is_stereo` should be `false` for 1 channel (mono) audio and `true` for
+/// 2 channel (stereo) audio.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `sample_count` of the header for 1 channel audio and `2 * sample_count` for 2 channel audio.
+/// Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if the `buf` length is too small for the header and the channel data,
+/// if the channel data is too small for `sample_count` samples, if the `out_samples` length
+/// isn't correct or if a DSP-ADPCM frame header has an invalid coefficient index.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_thp_audio_frame(buf: &[u8], is_stereo: bool, out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let header = parse_thp_audio_frame_header(buf)?;
+    let channels = if is_stereo {
+        2
+    } else {
+        1
+    };
+    let channel_size = usize::try_from(header.channel_size)
+        .map_err(|_| Error::InvalidBufferSize)?;
+    let sample_count = usize::try_from(header.sample_count)
+        .map_err(|_| Error::InvalidBufferSize)?;
+    let data_size = sample_count.div_ceil(DSP_ADPCM_SAMPLES_PER_FRAME)
+        .checked_mul(DSP_ADPCM_FRAME_SIZE)
+        .ok_or(Error::InvalidBufferSize)?;
+    if data_size > channel_size {
+        return Err(Error::InvalidBufferSize);
+    }
+    let frame_size = channel_size.checked_mul(channels)
+        .and_then(|size| size.checked_add(THP_AUDIO_HEADER_SIZE))
+        .ok_or(Error::InvalidBufferSize)?;
+    if buf.len() < frame_size {
+        return Err(Error::InvalidBufferSize);
+    }
+    if sample_count.checked_mul(channels) != Some(out_samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    // check frame headers before modifying anything
+    for ch in 0..channels {
+        let start = THP_AUDIO_HEADER_SIZE + ch * channel_size;
+        if buf[start..start + data_size].chunks_exact(DSP_ADPCM_FRAME_SIZE)
+            .any(|frame| frame[0] >= 0x80) {
+            return Err(Error::InvalidData);
+        }
+    }
+    for ch in 0..channels {
+        let start = THP_AUDIO_HEADER_SIZE + ch * channel_size;
+        let mut state = header.states[ch].clone();
+        for (i, frame) in buf[start..start + data_size].chunks_exact(DSP_ADPCM_FRAME_SIZE)
+            .enumerate() {
+            let first = i * DSP_ADPCM_SAMPLES_PER_FRAME;
+            let count = (sample_count - first).min(DSP_ADPCM_SAMPLES_PER_FRAME);
+            let mut samples = [0i16; DSP_ADPCM_SAMPLES_PER_FRAME];
+            decode_dsp_adpcm(frame, &header.coefficients[ch], &mut state,
+                &mut samples[..count])?;
+            for (n, sample) in samples[..count].iter().enumerate() {
+                out_samples[(first + n) * channels + ch] = *sample;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_frame(channel_size: u32, sample_count: u32) -> [u8; 112] {
+        let mut buf = [0u8; 112];
+        buf[0..4].copy_from_slice(&channel_size.to_be_bytes());
+        buf[4..8].copy_from_slice(&sample_count.to_be_bytes());
+        // left channel uses coefficient pair 1 (2048, 0), right channel pair 0 (-2048, 0)
+        buf[12..14].copy_from_slice(&2048i16.to_be_bytes());
+        buf[40..42].copy_from_slice(&(-2048i16).to_be_bytes());
+        // history values
+        buf[72..74].copy_from_slice(&100i16.to_be_bytes());
+        buf[76..78].copy_from_slice(&(-50i16).to_be_bytes());
+        buf
+    }
+
+    #[test]
+    fn test_parse_thp_audio_frame_header() {
+        let buf = create_frame(16, 20);
+        let Ok(header) = parse_thp_audio_frame_header(&buf) else {
+            return;
+        };
+        assert_eq!(header.channel_size, 16);
+        assert_eq!(header.sample_count, 20);
+        assert_eq!(header.coefficients[0][2], 2048);
+        assert_eq!(header.coefficients[1][0], -2048);
+        assert_eq!(header.states[0], DspAdpcmState { previous: 100, previous2: 0 });
+        assert_eq!(header.states[1], DspAdpcmState { previous: -50, previous2: 0 });
+        assert!(matches!(parse_thp_audio_frame_header(&buf[..79]),
+            Err(Error::InvalidBufferSize)));
+    }
+
+    #[test]
+    fn test_decode_thp_audio_frame() {
+        let mut buf = create_frame(16, 16);
+        // left: pair 1 keeps the previous value and adds the code
+        buf[80] = 0x10;
+        buf[81] = 0x10;
+        buf[88] = 0x10;
+        // right: pair 0 inverts the previous value
+        buf[96] = 0x00;
+        buf[104] = 0x00;
+        let mut samples = [0i16; 32];
+        assert!(decode_thp_audio_frame(&buf, true, &mut samples).is_ok());
+        assert_eq!(samples[..6], [ 101, 50, 101, -50, 101, 50 ]);
+        assert_eq!(samples[28..32], [ 101, 50, 101, -50 ]);
+
+        // mono decodes only the left channel
+        let mut samples = [0i16; 16];
+        assert!(decode_thp_audio_frame(&buf, false, &mut samples).is_ok());
+        assert_eq!(samples[..2], [ 101, 101 ]);
+
+        // invalid out_samples length
+        let mut samples = [0i16; 30];
+        assert!(matches!(decode_thp_audio_frame(&buf, true, &mut samples),
+            Err(Error::InvalidBufferSize)));
+
+        // channel data is too small for the samples
+        let buf = create_frame(16, 29);
+        let mut samples = [0i16; 58];
+        assert!(matches!(decode_thp_audio_frame(&buf, true, &mut samples),
+            Err(Error::InvalidBufferSize)));
+
+        // buf is too small for the channel data
+        let buf = create_frame(24, 28);
+        let mut samples = [0i16; 56];
+        assert!(matches!(decode_thp_audio_frame(&buf, true, &mut samples),
+            Err(Error::InvalidBufferSize)));
+
+        // invalid frame header
+        let mut buf = create_frame(16, 16);
+        buf[104] = 0x80;
+        let mut samples = [0i16; 32];
+        assert!(matches!(decode_thp_audio_frame(&buf, true, &mut samples),
+            Err(Error::InvalidData)));
+        assert_eq!(samples, [0; 32]);
+    }
+}
@@ -0,0 +1,125 @@
+
+use crate::AdpcmImaState;
+use crate::adpcm_ima::{IMA_INDEX_TABLE, IMA_STEP_TABLE};
+
+/// Internal values of the IMA ADPCM decoder for one decoded sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdpcmImaTraceStep {
+    /// The 4-bit encoded value (0..=15).
+    pub nibble: u8,
+    /// The step index before decoding (0..=88).
+    pub step_index: u8,
+    /// The step size for `step_index`.
+    pub step_size: i16,
+    /// The signed difference added to the previous predictor.
+    pub diff: i32,
+    /// `true` if the new predictor was clamped to the 16-bit range.
+    pub clamped: bool,
+    /// The new predictor, which is the decoded sample value.
+    pub predictor: i16,
+    /// The step index adjustment for `nibble`.
+    pub index_adjustment: i8,
+    /// The new step index (0..=88).
+    pub new_step_index: u8,
+}
+
+/// Decodes a 4-bit encoded IMA ADPCM value and returns the internal values of the decoder.
+///
+/// This works like [`decode_adpcm_ima()`](crate::decode_adpcm_ima), but the decoded sample
+/// value is returned in [`AdpcmImaTraceStep::predictor`] together with the values used to
+/// calculate it. This is useful for learning and for comparing the decoder with other
+/// implementations sample by sample.
+pub fn decode_adpcm_ima_traced(encoded_nibble: u8, state: &mut AdpcmImaState)
+    -> AdpcmImaTraceStep {
+    let nibble = encoded_nibble & 0x0f;
+    let step_index = state.step_index.min(88);
+    let step_size = IMA_STEP_TABLE[usize::from(step_index)];
+    let step = i32::from(step_size);
+    let mut diff = step >> 3;
+    if (nibble & 4) != 0 { diff += step; }
+    if (nibble & 2) != 0 { diff += step >> 1; }
+    if (nibble & 1) != 0 { diff += step >> 2; }
+    if (nibble & 8) != 0 {
+        diff = -diff;
+    }
+    let unclamped = i32::from(state.predictor) + diff;
+    let clamped = unclamped < -32768 || unclamped > 32767;
+    #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+    let predictor = unclamped.clamp(-32768, 32767) as i16;
+    let index_adjustment = IMA_INDEX_TABLE[usize::from(nibble)];
+    let new_step_index = step_index.saturating_add_signed(index_adjustment).min(88);
+    state.predictor = predictor;
+    state.step_index = new_step_index;
+    AdpcmImaTraceStep {
+        nibble,
+        step_index,
+        step_size,
+        diff,
+        clamped,
+        predictor,
+        index_adjustment,
+        new_step_index,
+    }
+}
+
+/// Returns an iterator, which decodes 4-bit encoded IMA ADPCM values from `nibbles` and
+/// yields the internal values of the decoder for each sample.
+///
+/// `state` is the initial decoder state. See [`decode_adpcm_ima_traced()`] for details.
+pub fn trace_adpcm_ima(nibbles: impl IntoIterator<Item = u8>, state: AdpcmImaState)
+    -> impl Iterator<Item = AdpcmImaTraceStep> {
+    let mut state = state;
+    nibbles.into_iter().map(move |nibble| decode_adpcm_ima_traced(nibble, &mut state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_adpcm_ima;
+
+    #[test]
+    fn test_decode_adpcm_ima_traced() {
+        let mut state = AdpcmImaState { predictor: 20200, step_index: 84 };
+        let step = decode_adpcm_ima_traced(14, &mut state);
+        assert_eq!(step, AdpcmImaTraceStep {
+            nibble: 14,
+            step_index: 84,
+            step_size: 22385,
+            diff: -36375,
+            clamped: false,
+            predictor: -16175,
+            index_adjustment: 6,
+            new_step_index: 88,
+        });
+        assert_eq!(state, AdpcmImaState { predictor: -16175, step_index: 88 });
+
+        let mut state = AdpcmImaState { predictor: -30123, step_index: 80 };
+        let step = decode_adpcm_ima_traced(14, &mut state);
+        assert!(step.clamped);
+        assert_eq!(step.predictor, -32768);
+
+        // traced decoding matches the normal decoding
+        for step_index in [ 0, 10, 50, 88, 100 ] {
+            for predictor in [ -32768, -1000, 0, 1000, 32767 ] {
+                let mut state = AdpcmImaState { predictor, step_index };
+                for nibble in 0..=16 {
+                    let mut expected_state = state.clone();
+                    let expected = decode_adpcm_ima(nibble, &mut expected_state);
+                    let step = decode_adpcm_ima_traced(nibble, &mut state);
+                    assert_eq!(step.predictor, expected);
+                    assert_eq!(state, expected_state);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_trace_adpcm_ima() {
+        let mut state = AdpcmImaState::new();
+        let nibbles = [ 7, 7, 2, 2, 2, 7, 5 ];
+        let expected: [i16; 7] = core::array::from_fn(|i| decode_adpcm_ima(nibbles[i], &mut state));
+        assert!(trace_adpcm_ima(nibbles, AdpcmImaState::new()).map(|s| s.predictor).eq(expected));
+        assert_eq!(trace_adpcm_ima(nibbles, AdpcmImaState::new()).last().map(|s| s.new_step_index),
+            Some(state.step_index));
+    }
+}
@@ -101,11 +101,9 @@ mod tests {
         // test against reference values generated for all input values -32768..=32767
         // the reference values were generated with the G.191 software tools
         let buffer = include_bytes!("../test-files/ulaw-reference.bin");
-        let mut bi = 0;
-        for i in -32768..=32767 {
+        for (bi, i) in (-32768..=32767).enumerate() {
             let encoded = encode_ulaw(i);
             assert_eq!(buffer[bi], encoded);
-            bi += 1;
         }
     }
 }
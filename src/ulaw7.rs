@@ -0,0 +1,107 @@
+#[cfg(feature = "internal-no-panic")]
+use no_panic::no_panic;
+
+// Legacy 7-bit μ-law used by Bell System D1 channel banks. It uses the continuous
+// μ-law curve with μ = 100 (instead of the segmented μ = 255 curve of G.711) and 64 levels
+// for each polarity. The bits are inverted like in G.711 μ-law.
+
+// decoding table generated from 32767 * ((1 + 100)^(n / 63) - 1) / 100 for n = 0..=63
+const ULAW7_VALUES: &[i16; 128] = &[
+    -32767, -30429, -28257, -26238, -24361, -22617, -20996, -19490,
+    -18090, -16789, -15580, -14457, -13412, -12442, -11540, -10701,
+    -9922, -9198, -8525, -7900, -7319, -6779, -6277, -5810,
+    -5377, -4974, -4599, -4251, -3928, -3627, -3348, -3088,
+    -2847, -2623, -2414, -2221, -2041, -1873, -1718, -1573,
+    -1439, -1314, -1198, -1091, -990, -897, -811, -730,
+    -656, -586, -522, -462, -406, -354, -306, -261,
+    -220, -181, -145, -112, -81, -52, -25, 0,
+    32767, 30429, 28257, 26238, 24361, 22617, 20996, 19490,
+    18090, 16789, 15580, 14457, 13412, 12442, 11540, 10701,
+    9922, 9198, 8525, 7900, 7319, 6779, 6277, 5810,
+    5377, 4974, 4599, 4251, 3928, 3627, 3348, 3088,
+    2847, 2623, 2414, 2221, 2041, 1873, 1718, 1573,
+    1439, 1314, 1198, 1091, 990, 897, 811, 730,
+    656, 586, 522, 462, 406, 354, 306, 261,
+    220, 181, 145, 112, 81, 52, 25, 0,
+];
+
+// decision thresholds between the levels, generated from the same curve at n - 0.5
+const ULAW7_THRESHOLDS: &[i16; 63] = &[
+    12, 38, 66, 96, 128, 163, 200, 240,
+    283, 329, 379, 433, 491, 553, 620, 692,
+    770, 853, 943, 1039, 1143, 1255, 1376, 1505,
+    1644, 1794, 1955, 2129, 2316, 2517, 2733, 2965,
+    3216, 3485, 3775, 4087, 4422, 4783, 5172, 5590,
+    6039, 6523, 7044, 7604, 8207, 8856, 9554, 10305,
+    11113, 11982, 12918, 13925, 15008, 16174, 17428, 18777,
+    20229, 21792, 23473, 25282, 27229, 29323, 31577,
+];
+
+/// Decodes a 7-bit encoded D1 channel bank μ-law value to a linear 16-bit signed integer
+/// sample value.
+///
+/// Only the lowest 7 bits of `encoded` are used and the top-most bit (the signaling bit in
+/// D1 frames) is ignored.
+#[cfg_attr(feature = "internal-no-panic", no_panic)]
+#[inline(always)]
+pub fn decode_ulaw7(encoded: u8) -> i16 {
+    ULAW7_VALUES[usize::from(encoded & 0x7f)]
+}
+
+/// Encodes a linear 16-bit signed integer sample value to a 7-bit encoded D1 channel bank
+/// μ-law value.
+///
+/// The top-most bit of the returned value is always zero.
+#[cfg_attr(feature = "internal-no-panic", no_panic)]
+#[inline(always)]
+pub fn encode_ulaw7(linear: i16) -> u8 {
+    let sign = if linear >= 0 {
+        0x00
+    } else {
+        0x40
+    };
+    let magnitude = linear.unsigned_abs().min(32767);
+    let level = ULAW7_THRESHOLDS.partition_point(|t| magnitude >= t.unsigned_abs());
+    #[allow(clippy::cast_possible_truncation)] // level is 0..=63 so truncation never happens
+    {
+    (sign | level as u8) ^ 0x7f
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_ulaw7() {
+        assert_eq!(decode_ulaw7(0x7f), 0);
+        assert_eq!(decode_ulaw7(0x3f), 0);
+        assert_eq!(decode_ulaw7(0x40), 32767);
+        assert_eq!(decode_ulaw7(0x00), -32767);
+        assert_eq!(decode_ulaw7(0x7e), 25);
+        // the top-most bit is ignored
+        assert_eq!(decode_ulaw7(0xc0), 32767);
+    }
+
+    #[test]
+    fn test_encode_ulaw7() {
+        assert_eq!(encode_ulaw7(0), 0x7f);
+        assert_eq!(encode_ulaw7(11), 0x7f);
+        assert_eq!(encode_ulaw7(12), 0x7e);
+        assert_eq!(encode_ulaw7(-12), 0x3e);
+        assert_eq!(encode_ulaw7(32767), 0x40);
+        assert_eq!(encode_ulaw7(-32768), 0x00);
+        // encoding decoded values gives the same code, except for negative zero
+        for code in 0..128u8 {
+            let expected = if code == 0x3f { 0x7f } else { code };
+            assert_eq!(encode_ulaw7(decode_ulaw7(code)), expected);
+        }
+        // encoding is monotonic
+        let mut previous = -32768;
+        for linear in -32767..=32767i16 {
+            let decoded = decode_ulaw7(encode_ulaw7(linear));
+            assert!(decoded >= previous);
+            previous = decoded;
+        }
+    }
+}
@@ -0,0 +1,233 @@
+
+use crate::Error;
+use crate::adpcm_ima::IMA_STEP_TABLE;
+use crate::bitreader::BitReader;
+
+// LucasArts VIMA is an IMA ADPCM derivative with variable sized codes.
+// see https://wiki.multimedia.cx/index.php/VIMA
+
+// code size in bits for each step index
+const VIMA_SIZE_TABLE: &[u8; 89] = &[
+    2, 2, 2, 2, 2, 2, 2, 3, 3, 3,
+    3, 3, 3, 3, 4, 4, 4, 4, 4, 4,
+    4, 4, 5, 5, 5, 5, 5, 5, 5, 6,
+    6, 6, 6, 6, 6, 6, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 7
+];
+
+// step index adjustments for each code size (2..=7), indexed by the code without the sign bit
+const VIMA_INDEX_TABLES: [&[i8]; 6] = [
+    &[ -1, 4 ],
+    &[ -1, -1, 2, 6 ],
+    &[ -1, -1, -1, -1, 1, 2, 4, 6 ],
+    &[ -1, -1, -1, -1, -1, -1, -1, -1, 1, 1, 1, 2, 2, 4, 5, 6 ],
+    &[
+        -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+        1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 5, 5, 6, 6
+    ],
+    &[
+        -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+        -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2,
+        2, 2, 2, 2, 4, 4, 4, 4, 4, 4, 5, 5, 5, 5, 6, 6
+    ],
+];
+
+// predicted differences for each step index and 6-bit code
+static VIMA_PREDICT_TABLE: [i32; 89 * 64] = build_predict_table();
+
+const fn build_predict_table() -> [i32; 89 * 64] {
+    let mut table = [0; 89 * 64];
+    let mut step_index = 0;
+    while step_index < 89 {
+        let mut code = 0;
+        while code < 64 {
+            let mut bit = 32;
+            let mut value = IMA_STEP_TABLE[step_index] as i32;
+            let mut diff = 0;
+            while bit != 0 {
+                if code & bit != 0 {
+                    diff += value;
+                }
+                bit >>= 1;
+                value >>= 1;
+            }
+            table[step_index * 64 + code] = diff;
+            code += 1;
+        }
+        step_index += 1;
+    }
+    table
+}
+
+struct VimaHeader {
+    sample_count: usize,
+    channels: usize,
+    step_indexes: [i8; 2],
+    predictors: [i16; 2],
+    data_offset: usize,
+}
+
+fn parse_vima_header(buf: &[u8]) -> Result<VimaHeader, Error> {
+    let read_u32 = |pos: usize| -> Result<u32, Error> {
+        match buf.get(pos..pos+4) {
+            Some(b) => Ok(u32::from_be_bytes([ b[0], b[1], b[2], b[3] ])),
+            None => Err(Error::InvalidBufferSize),
+        }
+    };
+    let mut pos = 0;
+    let mut sample_count = read_u32(pos)?;
+    pos += 4;
+    if sample_count == 0xffff_ffff {
+        pos += 4;
+        sample_count = read_u32(pos)?;
+        pos += 4;
+    }
+    let Ok(sample_count) = usize::try_from(sample_count) else {
+        return Err(Error::InvalidBufferSize);
+    };
+    // every sample needs at least two bits
+    if sample_count > buf.len().saturating_mul(4) {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut header = VimaHeader {
+        sample_count,
+        channels: 1,
+        step_indexes: [0; 2],
+        predictors: [0; 2],
+        data_offset: 0,
+    };
+    let Some(b) = buf.get(pos..pos+3) else {
+        return Err(Error::InvalidBufferSize);
+    };
+    // a negative step index means stereo
+    header.step_indexes[0] = i8::from_be_bytes([ b[0] ]);
+    if header.step_indexes[0] < 0 {
+        header.step_indexes[0] = !header.step_indexes[0];
+        header.channels = 2;
+    }
+    header.predictors[0] = i16::from_be_bytes([ b[1], b[2] ]);
+    pos += 3;
+    if header.channels == 2 {
+        let Some(b) = buf.get(pos..pos+3) else {
+            return Err(Error::InvalidBufferSize);
+        };
+        header.step_indexes[1] = i8::from_be_bytes([ b[0] ]);
+        header.predictors[1] = i16::from_be_bytes([ b[1], b[2] ]);
+        pos += 3;
+    }
+    header.data_offset = pos;
+    Ok(header)
+}
+
+/// Returns the number of samples (counting all channels) and the number of channels
+/// in a LucasArts VIMA compressed frame.
+///
+/// An error is returned if the frame header is invalid.
+pub fn vima_frame_info(buf: &[u8]) -> Result<(usize, usize), Error> {
+    let header = parse_vima_header(buf)?;
+    Ok((header.sample_count * header.channels, header.channels))
+}
+
+/// Decodes a LucasArts VIMA compressed frame to 16-bit signed integer samples.
+///
+/// `buf` should contain a complete frame: the sample count, the initial states for 1 or 2
+/// channels and the variable sized codes.
+///
+/// This function outputs decoded samples to `out_samples`, which must be large enough to hold
+/// the number of samples returned by [`vima_frame_info()`]. Samples are interleaved for
+/// 2 channel audio. The number of written samples is returned.
+///
+/// An error is returned if the frame header is invalid, if `out_samples` is too small or
+/// if `buf` ends before all samples are decoded. In the last case, `out_samples` may have been
+/// partially written.
+pub fn decode_vima(buf: &[u8], out_samples: &mut [i16]) -> Result<usize, Error> {
+    let header = parse_vima_header(buf)?;
+    let channels = header.channels;
+    let total_len = header.sample_count * channels;
+    if out_samples.len() < total_len {
+        return Err(Error::InvalidBufferSize);
+    }
+    let mut reader = BitReader::new(&buf[header.data_offset..]);
+    // the channels are stored one after another
+    for ch in 0..channels {
+        let mut step_index = i32::from(header.step_indexes[ch]);
+        let mut predictor = i32::from(header.predictors[ch]);
+        for out in out_samples[..total_len].iter_mut().skip(ch).step_by(channels) {
+            let clamped_index = usize::try_from(step_index.clamp(0, 88)).unwrap_or(0);
+            let size = VIMA_SIZE_TABLE[clamped_index];
+            let code = reader.read(u32::from(size)).ok_or(Error::InvalidBufferSize)?;
+            let sign_bit = 1 << (size - 1);
+            let magnitude = code & (sign_bit - 1);
+            if magnitude == sign_bit - 1 {
+                // escape code: a raw 16-bit sample value follows
+                predictor = reader.read_signed(16).ok_or(Error::InvalidBufferSize)?;
+            } else {
+                let step = i32::from(IMA_STEP_TABLE[clamped_index]);
+                let table_index = (magnitude << (7 - size)) as usize | (clamped_index << 6);
+                let mut diff = VIMA_PREDICT_TABLE[table_index] + (step >> (size - 1));
+                if code & sign_bit != 0 {
+                    diff = -diff;
+                }
+                predictor = (predictor + diff).clamp(-32768, 32767);
+            }
+            // value is clamped so truncation never happens
+            #[allow(clippy::cast_possible_truncation)]
+            {
+            *out = predictor as i16;
+            }
+            let index_table = VIMA_INDEX_TABLES[usize::from(size - 2)];
+            step_index = (step_index.clamp(0, 88) + i32::from(index_table[magnitude as usize]))
+                .clamp(0, 88);
+        }
+    }
+    Ok(total_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_vima() {
+        // one channel: two normal codes and one escape code
+        let buf = [ 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x64,
+            0b0010_0110, 0b0000_0000, 0b0000_0000 ];
+        assert!(matches!(vima_frame_info(&buf), Ok((3, 1))));
+        let mut samples = [0i16; 3];
+        assert!(matches!(decode_vima(&buf, &mut samples), Ok(3)));
+        assert_eq!(samples, [ 103, 100, -32768 ]);
+
+        // two channels with different code sizes
+        let buf = [ 0x00, 0x00, 0x00, 0x02, 0xfa, 0x00, 0x00, 0x07, 0xff, 0xf6,
+            0b0000_0011, 0b0000_0000 ];
+        assert!(matches!(vima_frame_info(&buf), Ok((4, 2))));
+        let mut samples = [0i16; 4];
+        assert!(matches!(decode_vima(&buf, &mut samples), Ok(4)));
+        assert_eq!(samples, [ 6, 0, 11, -6 ]);
+
+        // extended sample count
+        let buf = [ 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x00, 0x64, 0x00 ];
+        let mut samples = [0i16; 1];
+        assert!(matches!(decode_vima(&buf, &mut samples), Ok(1)));
+        assert_eq!(samples, [ 103 ]);
+
+        // out_samples too small
+        let mut samples = [0i16; 0];
+        assert!(matches!(decode_vima(&buf, &mut samples), Err(Error::InvalidBufferSize)));
+
+        // data ends too early
+        let buf = [ 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x64, 0b0010_0110 ];
+        let mut samples = [0i16; 3];
+        assert!(matches!(decode_vima(&buf, &mut samples), Err(Error::InvalidBufferSize)));
+
+        // too short header
+        assert!(matches!(vima_frame_info(&[ 0x00, 0x00, 0x00, 0x03, 0x00 ]),
+            Err(Error::InvalidBufferSize)));
+    }
+}
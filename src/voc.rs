@@ -0,0 +1,589 @@
+
+use crate::{CreativeAdpcmState, Error};
+use crate::{decode_alaw, decode_ulaw, encode_alaw, encode_ulaw};
+use crate::{decode_creative_adpcm2_slice, decode_creative_adpcm3_slice};
+use crate::{decode_creative_adpcm4_slice, encode_creative_adpcm4_slice};
+use crate::{encode_creative_adpcm2_slice, encode_creative_adpcm3_slice};
+
+// Creative Voice File (.VOC) container
+// see http://www.shikadi.net/moddingwiki/VOC_Format
+
+const VOC_SIGNATURE: &[u8; 20] = b"Creative Voice File\x1a";
+// version 1.20 supports sound data blocks of type 9
+const VOC_VERSION: u16 = 0x0114;
+
+/// Size of the header written by [`write_voc_header()`].
+pub const VOC_HEADER_SIZE: usize = 26;
+
+/// Codec of VOC sound data blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VocCodec {
+    /// 8-bit unsigned linear PCM (codec id 0).
+    Pcm8,
+    /// Creative 8-bit to 4-bit ADPCM (codec id 1).
+    CreativeAdpcm4,
+    /// Creative 8-bit to 3-bit (2.6 bits per sample) ADPCM (codec id 2).
+    CreativeAdpcm3,
+    /// Creative 8-bit to 2-bit ADPCM (codec id 3).
+    CreativeAdpcm2,
+    /// 16-bit signed little-endian linear PCM (codec id 4).
+    Pcm16,
+    /// G.711 A-law (codec id 6).
+    Alaw,
+    /// G.711 μ-law (codec id 7).
+    Ulaw,
+    /// Creative 16-bit to 4-bit ADPCM (codec id 0x0200).
+    CreativeAdpcm16,
+    /// Other codec id.
+    Unknown(u16),
+}
+
+impl VocCodec {
+    /// Returns the codec for a codec id.
+    pub fn from_id(id: u16) -> VocCodec {
+        match id {
+            0 => VocCodec::Pcm8,
+            1 => VocCodec::CreativeAdpcm4,
+            2 => VocCodec::CreativeAdpcm3,
+            3 => VocCodec::CreativeAdpcm2,
+            4 => VocCodec::Pcm16,
+            6 => VocCodec::Alaw,
+            7 => VocCodec::Ulaw,
+            0x0200 => VocCodec::CreativeAdpcm16,
+            _ => VocCodec::Unknown(id),
+        }
+    }
+
+    /// Returns the codec id.
+    pub fn id(self) -> u16 {
+        match self {
+            VocCodec::Pcm8 => 0,
+            VocCodec::CreativeAdpcm4 => 1,
+            VocCodec::CreativeAdpcm3 => 2,
+            VocCodec::CreativeAdpcm2 => 3,
+            VocCodec::Pcm16 => 4,
+            VocCodec::Alaw => 6,
+            VocCodec::Ulaw => 7,
+            VocCodec::CreativeAdpcm16 => 0x0200,
+            VocCodec::Unknown(id) => id,
+        }
+    }
+
+    /// Returns the number of samples in `data_len` bytes of sound data, or `None` if
+    /// the codec isn't supported by [`decode_voc_sound_data()`] or
+    /// if `data_len` isn't valid for the codec.
+    ///
+    /// Creative ADPCM data is expected to start with the reference byte, which is
+    /// the first sample.
+    pub fn sample_count(self, data_len: usize) -> Option<usize> {
+        match self {
+            VocCodec::Pcm8 | VocCodec::Alaw | VocCodec::Ulaw => Some(data_len),
+            VocCodec::Pcm16 if data_len % 2 == 0 => Some(data_len / 2),
+            VocCodec::CreativeAdpcm4 if data_len > 0 => Some(1 + 2 * (data_len - 1)),
+            VocCodec::CreativeAdpcm3 if data_len > 0 => Some(1 + 3 * (data_len - 1)),
+            VocCodec::CreativeAdpcm2 if data_len > 0 => Some(1 + 4 * (data_len - 1)),
+            _ => None,
+        }
+    }
+}
+
+/// Block of a VOC file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VocBlock<'a> {
+    /// Terminator (block type 0), which ends the file.
+    Terminator,
+    /// Sound data (block type 1). The sample rate is calculated from the frequency divisor,
+    /// so it may differ slightly from the original sample rate.
+    SoundData {
+        /// Sample rate.
+        sample_rate: u32,
+        /// Codec of the data.
+        codec: VocCodec,
+        /// Encoded data.
+        data: &'a [u8],
+    },
+    /// Sound data continuation (block type 2), which uses the codec of the previous block.
+    SoundContinue {
+        /// Encoded data.
+        data: &'a [u8],
+    },
+    /// Silence (block type 3).
+    Silence {
+        /// Number of silent samples (1..=65536).
+        sample_count: u32,
+        /// Sample rate.
+        sample_rate: u32,
+    },
+    /// Sound data with a sample rate, bits per sample and channels (block type 9).
+    SoundDataNew {
+        /// Sample rate.
+        sample_rate: u32,
+        /// Number of bits per sample.
+        bits_per_sample: u8,
+        /// Number of channels.
+        channels: u8,
+        /// Codec of the data.
+        codec: VocCodec,
+        /// Encoded data, interleaved for multiple channels.
+        data: &'a [u8],
+    },
+    /// Other block types (markers, text, repeats and extended info blocks).
+    Other {
+        /// Block type.
+        block_type: u8,
+        /// Block data.
+        data: &'a [u8],
+    },
+}
+
+impl VocBlock<'_> {
+    /// Returns the block type.
+    pub fn block_type(&self) -> u8 {
+        match self {
+            VocBlock::Terminator => 0,
+            VocBlock::SoundData { .. } => 1,
+            VocBlock::SoundContinue { .. } => 2,
+            VocBlock::Silence { .. } => 3,
+            VocBlock::SoundDataNew { .. } => 9,
+            VocBlock::Other { block_type, .. } => *block_type,
+        }
+    }
+
+    /// Returns the number of bytes written by [`write_voc_block()`] for this block.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            VocBlock::Terminator => 1,
+            VocBlock::SoundData { data, .. } => 4 + 2 + data.len(),
+            VocBlock::SoundContinue { data } => 4 + data.len(),
+            VocBlock::Silence { .. } => 4 + 3,
+            VocBlock::SoundDataNew { data, .. } => 4 + 12 + data.len(),
+            VocBlock::Other { data, .. } => 4 + data.len(),
+        }
+    }
+}
+
+/// Iterator over the blocks of a VOC file, created by [`voc_blocks()`].
+///
+/// The iterator returns an error and stops if a block is truncated. It stops after
+/// the terminator block or at the end of the data.
+#[derive(Debug, Clone)]
+pub struct VocBlocks<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    is_done: bool,
+}
+
+impl<'a> Iterator for VocBlocks<'a> {
+    type Item = Result<VocBlock<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_done || self.pos >= self.buf.len() {
+            return None;
+        }
+        let block = parse_voc_block(&self.buf[self.pos..]);
+        match block {
+            Ok((VocBlock::Terminator, _)) | Err(_) => { self.is_done = true; },
+            Ok((_, len)) => { self.pos += len; },
+        }
+        Some(block.map(|(b, _)| b))
+    }
+}
+
+// sample rate of sound data and silence blocks
+fn sample_rate_from_divisor(divisor: u8) -> u32 {
+    1_000_000 / (256 - u32::from(divisor))
+}
+
+fn divisor_from_sample_rate(sample_rate: u32) -> u8 {
+    let rate = sample_rate.max(1);
+    let divisor = 256 - ((1_000_000 + rate / 2) / rate).clamp(1, 256);
+    u8::try_from(divisor).unwrap_or(0)
+}
+
+// parses a block and returns it and its length in bytes
+fn parse_voc_block(buf: &[u8]) -> Result<(VocBlock<'_>, usize), Error> {
+    let Some(&block_type) = buf.first() else {
+        return Err(Error::InvalidBufferSize);
+    };
+    if block_type == 0 {
+        return Ok((VocBlock::Terminator, 1));
+    }
+    if buf.len() < 4 {
+        return Err(Error::InvalidBufferSize);
+    }
+    let size = usize::from(buf[1]) | usize::from(buf[2]) << 8 | usize::from(buf[3]) << 16;
+    let Some(data) = buf.get(4..4+size) else {
+        return Err(Error::InvalidBufferSize);
+    };
+    let block = match block_type {
+        1 => {
+            if data.len() < 2 {
+                return Err(Error::InvalidData);
+            }
+            VocBlock::SoundData {
+                sample_rate: sample_rate_from_divisor(data[0]),
+                codec: VocCodec::from_id(u16::from(data[1])),
+                data: &data[2..],
+            }
+        },
+        2 => VocBlock::SoundContinue { data },
+        3 => {
+            if data.len() < 3 {
+                return Err(Error::InvalidData);
+            }
+            VocBlock::Silence {
+                sample_count: u32::from(u16::from_le_bytes([ data[0], data[1] ])) + 1,
+                sample_rate: sample_rate_from_divisor(data[2]),
+            }
+        },
+        9 => {
+            if data.len() < 12 {
+                return Err(Error::InvalidData);
+            }
+            VocBlock::SoundDataNew {
+                sample_rate: u32::from_le_bytes([ data[0], data[1], data[2], data[3] ]),
+                bits_per_sample: data[4],
+                channels: data[5],
+                codec: VocCodec::from_id(u16::from_le_bytes([ data[6], data[7] ])),
+                data: &data[12..],
+            }
+        },
+        _ => VocBlock::Other { block_type, data },
+    };
+    Ok((block, 4 + size))
+}
+
+/// Parses the header of a VOC file and returns an iterator over its blocks.
+///
+/// The header checksum isn't checked, because some programs write invalid checksums.
+///
+/// An error is returned if `buf` doesn't start with the "Creative Voice File" signature or
+/// if it is too short.
+pub fn voc_blocks(buf: &[u8]) -> Result<VocBlocks<'_>, Error> {
+    if buf.len() < VOC_HEADER_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    if &buf[0..20] != VOC_SIGNATURE {
+        return Err(Error::InvalidData);
+    }
+    let header_size = usize::from(u16::from_le_bytes([ buf[20], buf[21] ]));
+    if header_size > buf.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    Ok(VocBlocks { buf, pos: header_size, is_done: false })
+}
+
+/// Writes a VOC file header (version 1.20) to `out_buf`.
+pub fn write_voc_header(out_buf: &mut [u8; VOC_HEADER_SIZE]) {
+    let checksum = (!VOC_VERSION).wrapping_add(0x1234);
+    out_buf[0..20].copy_from_slice(VOC_SIGNATURE);
+    out_buf[20..22].copy_from_slice(&26u16.to_le_bytes());
+    out_buf[22..24].copy_from_slice(&VOC_VERSION.to_le_bytes());
+    out_buf[24..26].copy_from_slice(&checksum.to_le_bytes());
+}
+
+/// Writes a VOC block to `out_buf` and returns the number of bytes written.
+///
+/// The `out_buf` length must be at least [`VocBlock::encoded_len()`]. Sound data and silence
+/// blocks of type 1 and 3 store the sample rate as a frequency divisor, so their
+/// sample rate is rounded.
+///
+/// An error is returned if `out_buf` is too short, if block data is longer than 16 MB,
+/// if a silence block has an invalid sample count or if a block of type 1 has
+/// a codec id larger than 255.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn write_voc_block(block: &VocBlock<'_>, out_buf: &mut [u8]) -> Result<usize, Error> {
+    let len = block.encoded_len();
+    if out_buf.len() < len {
+        return Err(Error::InvalidBufferSize);
+    }
+    if let VocBlock::Terminator = block {
+        out_buf[0] = 0;
+        return Ok(1);
+    }
+    let size = u32::try_from(len - 4).map_err(|_| Error::InvalidBufferSize)?;
+    if size > 0x00ff_ffff {
+        return Err(Error::InvalidBufferSize);
+    }
+    let (header, body) = out_buf.split_at_mut(4);
+    match block {
+        VocBlock::SoundData { sample_rate, codec, data } => {
+            body[0] = divisor_from_sample_rate(*sample_rate);
+            body[1] = u8::try_from(codec.id()).map_err(|_| Error::InvalidData)?;
+            body[2..2+data.len()].copy_from_slice(data);
+        },
+        VocBlock::Silence { sample_count, sample_rate } => {
+            let count = sample_count.checked_sub(1)
+                .and_then(|c| u16::try_from(c).ok())
+                .ok_or(Error::InvalidData)?;
+            body[0..2].copy_from_slice(&count.to_le_bytes());
+            body[2] = divisor_from_sample_rate(*sample_rate);
+        },
+        VocBlock::SoundDataNew { sample_rate, bits_per_sample, channels, codec, data } => {
+            body[0..4].copy_from_slice(&sample_rate.to_le_bytes());
+            body[4] = *bits_per_sample;
+            body[5] = *channels;
+            body[6..8].copy_from_slice(&codec.id().to_le_bytes());
+            body[8..12].fill(0);
+            body[12..12+data.len()].copy_from_slice(data);
+        },
+        VocBlock::SoundContinue { data } | VocBlock::Other { data, .. } => {
+            body[..data.len()].copy_from_slice(data);
+        },
+        VocBlock::Terminator => {},
+    }
+    header[0] = block.block_type();
+    header[1..4].copy_from_slice(&size.to_le_bytes()[0..3]);
+    Ok(len)
+}
+
+/// Decodes VOC sound data to 16-bit signed integer samples.
+///
+/// The `out_samples` length must be [`VocCodec::sample_count()`] for the `data` length.
+/// Samples are interleaved for multiple channels.
+///
+/// Creative ADPCM data must start with the reference byte, as in the first sound data block.
+/// Continuation blocks can be decoded with the Creative ADPCM functions, such as
+/// [`decode_creative_adpcm4_slice()`](crate::decode_creative_adpcm4_slice), using the state
+/// from the previous block.
+///
+/// An error is returned if the codec isn't supported or if the `out_samples` length
+/// isn't correct.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_voc_sound_data(codec: VocCodec, data: &[u8], out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let Some(sample_count) = codec.sample_count(data.len()) else {
+        return Err(Error::InvalidData);
+    };
+    if sample_count != out_samples.len() {
+        return Err(Error::InvalidBufferSize);
+    }
+    match codec {
+        VocCodec::Pcm8 => {
+            for (b, out) in data.iter().zip(out_samples.iter_mut()) {
+                *out = i16::from_le_bytes([ 0, *b ^ 0x80 ]);
+            }
+        },
+        VocCodec::Pcm16 => {
+            for (b, out) in data.chunks_exact(2).zip(out_samples.iter_mut()) {
+                *out = i16::from_le_bytes([ b[0], b[1] ]);
+            }
+        },
+        VocCodec::Alaw => {
+            for (b, out) in data.iter().zip(out_samples.iter_mut()) {
+                *out = decode_alaw(*b);
+            }
+        },
+        VocCodec::Ulaw => {
+            for (b, out) in data.iter().zip(out_samples.iter_mut()) {
+                *out = decode_ulaw(*b);
+            }
+        },
+        VocCodec::CreativeAdpcm4 | VocCodec::CreativeAdpcm3 | VocCodec::CreativeAdpcm2 => {
+            let mut state = CreativeAdpcmState::with_reference(data[0]);
+            out_samples[0] = i16::from_le_bytes([ 0, data[0] ^ 0x80 ]);
+            let decode = match codec {
+                VocCodec::CreativeAdpcm4 => decode_creative_adpcm4_slice,
+                VocCodec::CreativeAdpcm3 => decode_creative_adpcm3_slice,
+                _ => decode_creative_adpcm2_slice,
+            };
+            decode(&data[1..], &mut state, &mut out_samples[1..])?;
+        },
+        _ => return Err(Error::InvalidData),
+    }
+    Ok(())
+}
+
+/// Encodes 16-bit signed integer samples to VOC sound data.
+///
+/// The `out_buf` length must be the number of bytes, which decode to `samples.len()`
+/// samples (`2 * samples.len()` for 16-bit PCM and `samples.len()` for the 8-bit codecs).
+/// 8-bit PCM samples are truncated to 8 bits.
+///
+/// Creative ADPCM data starts with the first sample truncated to an 8-bit reference byte.
+///
+/// An error is returned if the codec isn't supported or if the `out_buf` length
+/// isn't correct.
+/// If an error is returned, `out_buf` is left unmodified.
+pub fn encode_voc_sound_data(codec: VocCodec, samples: &[i16], out_buf: &mut [u8])
+    -> Result<(), Error> {
+
+    if codec.sample_count(out_buf.len()).is_none() {
+        return Err(Error::InvalidData);
+    }
+    if codec.sample_count(out_buf.len()) != Some(samples.len()) {
+        return Err(Error::InvalidBufferSize);
+    }
+    match codec {
+        VocCodec::Pcm8 => {
+            for (s, out) in samples.iter().zip(out_buf.iter_mut()) {
+                *out = s.to_le_bytes()[1] ^ 0x80;
+            }
+        },
+        VocCodec::Pcm16 => {
+            for (s, out) in samples.iter().zip(out_buf.chunks_exact_mut(2)) {
+                out.copy_from_slice(&s.to_le_bytes());
+            }
+        },
+        VocCodec::Alaw => {
+            for (s, out) in samples.iter().zip(out_buf.iter_mut()) {
+                *out = encode_alaw(*s);
+            }
+        },
+        VocCodec::Ulaw => {
+            for (s, out) in samples.iter().zip(out_buf.iter_mut()) {
+                *out = encode_ulaw(*s);
+            }
+        },
+        VocCodec::CreativeAdpcm4 | VocCodec::CreativeAdpcm3 | VocCodec::CreativeAdpcm2 => {
+            let (reference, data) = out_buf.split_at_mut(1);
+            reference[0] = samples[0].to_le_bytes()[1] ^ 0x80;
+            let mut state = CreativeAdpcmState::with_reference(reference[0]);
+            let encode = match codec {
+                VocCodec::CreativeAdpcm4 => encode_creative_adpcm4_slice,
+                VocCodec::CreativeAdpcm3 => encode_creative_adpcm3_slice,
+                _ => encode_creative_adpcm2_slice,
+            };
+            encode(&samples[1..], &mut state, data)?;
+        },
+        _ => return Err(Error::InvalidData),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_voc_codec() {
+        for id in [ 0, 1, 2, 3, 4, 5, 6, 7, 8, 0x0200, 0x1234 ] {
+            assert_eq!(VocCodec::from_id(id).id(), id);
+        }
+        assert_eq!(VocCodec::from_id(5), VocCodec::Unknown(5));
+        assert_eq!(VocCodec::Pcm16.sample_count(10), Some(5));
+        assert_eq!(VocCodec::Pcm16.sample_count(11), None);
+        assert_eq!(VocCodec::Ulaw.sample_count(11), Some(11));
+        assert_eq!(VocCodec::CreativeAdpcm4.sample_count(11), Some(21));
+        assert_eq!(VocCodec::CreativeAdpcm4.sample_count(0), None);
+        assert_eq!(VocCodec::CreativeAdpcm3.sample_count(11), Some(31));
+        assert_eq!(VocCodec::CreativeAdpcm2.sample_count(11), Some(41));
+        assert_eq!(VocCodec::CreativeAdpcm16.sample_count(11), None);
+    }
+
+    #[test]
+    fn test_voc_write_and_parse() {
+        let sound = [ 0x80u8, 0x90, 0x70 ];
+        let sound16 = [ 0x01u8, 0x02, 0xff, 0xff ];
+        let blocks = [
+            VocBlock::SoundData { sample_rate: 8000, codec: VocCodec::Pcm8, data: &sound },
+            VocBlock::SoundContinue { data: &sound[..2] },
+            VocBlock::Silence { sample_count: 100, sample_rate: 8000 },
+            VocBlock::SoundDataNew { sample_rate: 44100, bits_per_sample: 16, channels: 2,
+                codec: VocCodec::Pcm16, data: &sound16 },
+            VocBlock::Other { block_type: 4, data: &[ 1, 0 ] },
+            VocBlock::Terminator,
+        ];
+        let mut file = [0u8; 128];
+        let mut header = [0u8; VOC_HEADER_SIZE];
+        write_voc_header(&mut header);
+        assert_eq!(header[20..26], [ 0x1a, 0x00, 0x14, 0x01, 0x1f, 0x11 ]);
+        file[..VOC_HEADER_SIZE].copy_from_slice(&header);
+        let mut len = VOC_HEADER_SIZE;
+        for block in &blocks {
+            let result = write_voc_block(block, &mut file[len..]);
+            assert!(matches!(result, Ok(l) if l == block.encoded_len()));
+            len += block.encoded_len();
+        }
+        // sound data block: type 1, size 5, divisor 131, codec 0
+        assert_eq!(file[26..32], [ 1, 5, 0, 0, 131, 0 ]);
+
+        let result = voc_blocks(&file[..len + 10]);
+        assert!(result.is_ok());
+        let Ok(mut iter) = result else { return };
+        assert!(matches!(iter.next(), Some(Ok(b)) if b == blocks[0]));
+        assert!(matches!(iter.next(), Some(Ok(b)) if b == blocks[1]));
+        assert!(matches!(iter.next(), Some(Ok(b)) if b == blocks[2]));
+        assert!(matches!(iter.next(), Some(Ok(b)) if b == blocks[3]));
+        assert!(matches!(iter.next(), Some(Ok(b)) if b == blocks[4]));
+        assert!(matches!(iter.next(), Some(Ok(b)) if b == VocBlock::Terminator));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_voc_errors() {
+        let mut header = [0u8; VOC_HEADER_SIZE];
+        write_voc_header(&mut header);
+        assert!(matches!(voc_blocks(&header[..25]), Err(Error::InvalidBufferSize)));
+        let mut file = [0u8; 40];
+        file[..VOC_HEADER_SIZE].copy_from_slice(&header);
+        file[0] = b'c';
+        assert!(matches!(voc_blocks(&file), Err(Error::InvalidData)));
+        file[0] = b'C';
+        // truncated block stops the iteration
+        file[26..30].copy_from_slice(&[ 1, 100, 0, 0 ]);
+        let result = voc_blocks(&file);
+        assert!(result.is_ok());
+        let Ok(mut iter) = result else { return };
+        assert!(matches!(iter.next(), Some(Err(Error::InvalidBufferSize))));
+        assert!(iter.next().is_none());
+
+        let mut buf = [0u8; 8];
+        let block = VocBlock::SoundData { sample_rate: 8000, codec: VocCodec::Ulaw,
+            data: &[ 1, 2, 3 ] };
+        assert!(matches!(write_voc_block(&block, &mut buf[..8]), Err(Error::InvalidBufferSize)));
+        let block = VocBlock::Silence { sample_count: 0, sample_rate: 8000 };
+        assert!(matches!(write_voc_block(&block, &mut buf), Err(Error::InvalidData)));
+        let block = VocBlock::SoundData { sample_rate: 8000,
+            codec: VocCodec::CreativeAdpcm16, data: &[] };
+        assert!(matches!(write_voc_block(&block, &mut buf), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_voc_sound_data() {
+        let samples = [ 0, 256, -256, 32767, -32768 ];
+        for codec in [ VocCodec::Pcm8, VocCodec::Pcm16, VocCodec::Alaw, VocCodec::Ulaw ] {
+            let mut buf = [0u8; 10];
+            let len = if codec == VocCodec::Pcm16 { 10 } else { 5 };
+            assert!(encode_voc_sound_data(codec, &samples, &mut buf[..len]).is_ok());
+            let mut decoded = [0i16; 5];
+            assert!(decode_voc_sound_data(codec, &buf[..len], &mut decoded).is_ok());
+            for (s, d) in samples.iter().zip(decoded) {
+                assert!((i32::from(*s) - i32::from(d)).abs() <= 1024);
+            }
+            if codec == VocCodec::Pcm16 {
+                assert_eq!(decoded, samples);
+            }
+        }
+        // Creative ADPCM starts with the reference byte
+        let mut buf = [0u8; 3];
+        assert!(encode_voc_sound_data(VocCodec::CreativeAdpcm4, &[ 0x1234, 0x1534, 0x1834, 0x1b34,
+            0x1e34 ], &mut buf).is_ok());
+        assert_eq!(buf, [ 0x92, 0x33, 0x33 ]);
+        let mut decoded = [0i16; 5];
+        assert!(decode_voc_sound_data(VocCodec::CreativeAdpcm4, &buf, &mut decoded).is_ok());
+        assert_eq!(decoded, [ 0x1200, 0x1500, 0x1800, 0x1b00, 0x1e00 ]);
+        let mut buf = [0u8; 2];
+        assert!(encode_voc_sound_data(VocCodec::CreativeAdpcm2, &[ 0x1234, 0x1334, 0x1634, 0x1834,
+            0x1934 ], &mut buf).is_ok());
+        assert_eq!(buf, [ 0x92, 0b01_01_00_00 ]);
+        assert!(decode_voc_sound_data(VocCodec::CreativeAdpcm2, &buf, &mut decoded).is_ok());
+        assert_eq!(decoded, [ 0x1200, 0x1300, 0x1600, 0x1800, 0x1900 ]);
+        let mut buf = [0u8; 2];
+        assert!(encode_voc_sound_data(VocCodec::CreativeAdpcm3, &[ 0; 4 ], &mut buf).is_ok());
+        assert!(matches!(encode_voc_sound_data(VocCodec::CreativeAdpcm3, &[ 0; 5 ], &mut buf),
+            Err(Error::InvalidBufferSize)));
+
+        let mut decoded = [0i16; 3];
+        assert!(decode_voc_sound_data(VocCodec::Pcm8, &[ 0x80, 0x00, 0xff ], &mut decoded)
+            .is_ok());
+        assert_eq!(decoded, [ 0, -32768, 32512 ]);
+        assert!(matches!(decode_voc_sound_data(VocCodec::Ulaw, &[ 0, 0 ], &mut decoded),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_voc_sound_data(VocCodec::CreativeAdpcm16, &[ 0, 0 ],
+            &mut decoded), Err(Error::InvalidData)));
+        assert!(matches!(encode_voc_sound_data(VocCodec::Unknown(5), &samples, &mut [0u8; 5]),
+            Err(Error::InvalidData)));
+    }
+}
@@ -0,0 +1,317 @@
+
+use crate::Error;
+use crate::{AdpcmImaState, decode_adpcm_ima};
+
+// Westwood Studios AUD files (Command & Conquer, Red Alert) contain chunks of IMA ADPCM or
+// Westwood's own 8-bit SND1 compression.
+// see https://wiki.multimedia.cx/index.php/Westwood_AUD
+
+/// Size of the AUD file header in bytes.
+pub const WESTWOOD_AUD_HEADER_SIZE: usize = 12;
+
+/// Size of the AUD chunk header in bytes.
+pub const WESTWOOD_AUD_CHUNK_HEADER_SIZE: usize = 8;
+
+const WESTWOOD_AUD_CHUNK_ID: u32 = 0x0000_deaf;
+
+// SND1 4-bit deltas
+const SND1_DELTA_4BIT: &[i32; 16] = &[ -9, -8, -6, -5, -4, -3, -2, -1, 0, 1, 2, 3, 4, 5, 6, 8 ];
+
+/// Compression type of an AUD file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WestwoodAudCompression {
+    /// Westwood SND1 compression of 8-bit unsigned audio (type 1).
+    Snd1,
+    /// IMA ADPCM (type 99).
+    Ima,
+}
+
+/// AUD file header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WestwoodAudHeader {
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Size of the chunks after the header in bytes.
+    pub compressed_size: u32,
+    /// Size of the decoded audio in bytes.
+    pub output_size: u32,
+    /// `true` for 2 channel (stereo) audio.
+    pub is_stereo: bool,
+    /// `true` for 16-bit audio, `false` for 8-bit audio.
+    pub is_16bit: bool,
+    /// Compression type.
+    pub compression: WestwoodAudCompression,
+}
+
+/// AUD chunk header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WestwoodAudChunkHeader {
+    /// Size of the compressed chunk data after the chunk header in bytes.
+    pub compressed_size: usize,
+    /// Size of the decoded chunk data in bytes.
+    pub output_size: usize,
+}
+
+/// Parses the 12-byte header of an AUD file.
+///
+/// An error is returned if the `buf` length is less than [`WESTWOOD_AUD_HEADER_SIZE`] or
+/// if the compression type isn't supported.
+pub fn parse_westwood_aud_header(buf: &[u8]) -> Result<WestwoodAudHeader, Error> {
+    if buf.len() < WESTWOOD_AUD_HEADER_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    let compression = match buf[11] {
+        1 => WestwoodAudCompression::Snd1,
+        99 => WestwoodAudCompression::Ima,
+        _ => return Err(Error::InvalidData),
+    };
+    Ok(WestwoodAudHeader {
+        sample_rate: u32::from(u16::from_le_bytes([ buf[0], buf[1] ])),
+        compressed_size: u32::from_le_bytes([ buf[2], buf[3], buf[4], buf[5] ]),
+        output_size: u32::from_le_bytes([ buf[6], buf[7], buf[8], buf[9] ]),
+        is_stereo: (buf[10] & 0x01) != 0,
+        is_16bit: (buf[10] & 0x02) != 0,
+        compression,
+    })
+}
+
+/// Parses an 8-byte AUD chunk header.
+///
+/// An error is returned if the `buf` length is less than [`WESTWOOD_AUD_CHUNK_HEADER_SIZE`] or
+/// if the chunk id isn't 0x0000DEAF.
+pub fn parse_westwood_aud_chunk_header(buf: &[u8]) -> Result<WestwoodAudChunkHeader, Error> {
+    if buf.len() < WESTWOOD_AUD_CHUNK_HEADER_SIZE {
+        return Err(Error::InvalidBufferSize);
+    }
+    if u32::from_le_bytes([ buf[4], buf[5], buf[6], buf[7] ]) != WESTWOOD_AUD_CHUNK_ID {
+        return Err(Error::InvalidData);
+    }
+    Ok(WestwoodAudChunkHeader {
+        compressed_size: usize::from(u16::from_le_bytes([ buf[0], buf[1] ])),
+        output_size: usize::from(u16::from_le_bytes([ buf[2], buf[3] ])),
+    })
+}
+
+/// Decodes Westwood IMA ADPCM chunk data to 16-bit signed integer samples.
+///
+/// Each byte contains two samples of the same channel, the low nibble first. For 2 channel
+/// audio, the bytes of the channels are interleaved.
+///
+/// `states` must contain channel number of `AdpcmImaState` items (1 or 2). The state objects
+/// should be initialized to zero at the start of the file and subsequent calls should pass in
+/// the state values from the previous chunk.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// `2 * buf.len()`. Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if `states` has an invalid number of state objects or
+/// if the `buf` length isn't divisible by the number of channels or
+/// if the `out_samples` length isn't correct.
+/// If an error is returned, `states` and `out_samples` are left unmodified.
+pub fn decode_westwood_ima(buf: &[u8], states: &mut [AdpcmImaState], out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let channels = states.len();
+    if channels != 1 && channels != 2 {
+        return Err(Error::InvalidChannels);
+    }
+    if buf.len() % channels != 0 || out_samples.len() != buf.len() * 2 {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (bytes, out) in buf.chunks_exact(channels)
+        .zip(out_samples.chunks_exact_mut(2 * channels)) {
+        for (ch, (b, state)) in bytes.iter().zip(states.iter_mut()).enumerate() {
+            out[ch] = decode_adpcm_ima(*b & 0x0f, state);
+            out[ch + channels] = decode_adpcm_ima(*b >> 4, state);
+        }
+    }
+    Ok(())
+}
+
+// returns the number of samples of SND1 compressed data or None if the data is truncated
+fn snd1_output_len(buf: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    let mut len = 0;
+    while pos < buf.len() {
+        let code = buf[pos];
+        let count = usize::from(code & 0x3f) + 1;
+        pos += 1;
+        match code >> 6 {
+            0 => {
+                pos += count;
+                len += count * 4;
+            },
+            1 => {
+                pos += count;
+                len += count * 2;
+            },
+            2 if (code & 0x20) != 0 => {
+                len += 1;
+            },
+            2 => {
+                pos += count;
+                len += count;
+            },
+            _ => {
+                len += count;
+            },
+        }
+    }
+    if pos == buf.len() {
+        Some(len)
+    } else {
+        None
+    }
+}
+
+/// Decodes Westwood SND1 compressed chunk data to 8-bit unsigned samples.
+///
+/// SND1 compresses 8-bit unsigned mono audio with 2-bit and 4-bit deltas, raw samples,
+/// 5-bit deltas and runs of the previous sample. Each chunk starts from sample value 128.
+/// If the chunk compressed size equals its output size, the chunk is uncompressed.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// the output size of the chunk.
+///
+/// An error is returned if the compressed data is truncated or if it doesn't decode to
+/// exactly `out_samples.len()` samples.
+/// If an error is returned, `out_samples` is left unmodified.
+pub fn decode_westwood_snd1(buf: &[u8], out_samples: &mut [u8]) -> Result<(), Error> {
+    if buf.len() == out_samples.len() {
+        out_samples.copy_from_slice(buf);
+        return Ok(());
+    }
+    if snd1_output_len(buf) != Some(out_samples.len()) {
+        return Err(Error::InvalidData);
+    }
+    let mut sample: i32 = 128;
+    let mut out_iter = out_samples.iter_mut();
+    let mut write = |value: i32| {
+        if let Some(out) = out_iter.next() {
+            *out = u8::try_from(value.clamp(0, 255)).unwrap_or(0);
+        }
+    };
+    let mut pos = 0;
+    while pos < buf.len() {
+        let code = buf[pos];
+        let count = usize::from(code & 0x3f) + 1;
+        pos += 1;
+        match code >> 6 {
+            0 => {
+                for b in &buf[pos..pos + count] {
+                    for shift in [ 0, 2, 4, 6 ] {
+                        sample = (sample + i32::from((*b >> shift) & 3) - 2).clamp(0, 255);
+                        write(sample);
+                    }
+                }
+                pos += count;
+            },
+            1 => {
+                for b in &buf[pos..pos + count] {
+                    for nibble in [ *b & 0x0f, *b >> 4 ] {
+                        sample = (sample + SND1_DELTA_4BIT[usize::from(nibble)]).clamp(0, 255);
+                        write(sample);
+                    }
+                }
+                pos += count;
+            },
+            2 if (code & 0x20) != 0 => {
+                // 5-bit signed delta
+                let delta = i32::from(i8::from_le_bytes([ code << 3 ])) >> 3;
+                sample = (sample + delta).clamp(0, 255);
+                write(sample);
+            },
+            2 => {
+                for b in &buf[pos..pos + count] {
+                    sample = i32::from(*b);
+                    write(sample);
+                }
+                pos += count;
+            },
+            _ => {
+                for _ in 0..count {
+                    write(sample);
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_westwood_aud_header() {
+        let buf = [ 0x22, 0x56, 0x00, 0x01, 0, 0, 0x00, 0x04, 0, 0, 0x02, 99 ];
+        assert!(matches!(parse_westwood_aud_header(&buf), Ok(WestwoodAudHeader {
+            sample_rate: 22050, compressed_size: 256, output_size: 1024, is_stereo: false,
+            is_16bit: true, compression: WestwoodAudCompression::Ima })));
+        assert!(matches!(parse_westwood_aud_header(&buf[..11]), Err(Error::InvalidBufferSize)));
+        let mut buf = buf;
+        buf[11] = 1;
+        assert!(matches!(parse_westwood_aud_header(&buf),
+            Ok(WestwoodAudHeader { compression: WestwoodAudCompression::Snd1, .. })));
+        buf[11] = 2;
+        assert!(matches!(parse_westwood_aud_header(&buf), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_parse_westwood_aud_chunk_header() {
+        let buf = [ 0x00, 0x02, 0x00, 0x08, 0xaf, 0xde, 0, 0 ];
+        assert!(matches!(parse_westwood_aud_chunk_header(&buf),
+            Ok(WestwoodAudChunkHeader { compressed_size: 512, output_size: 2048 })));
+        assert!(matches!(parse_westwood_aud_chunk_header(&buf[..7]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(parse_westwood_aud_chunk_header(&[ 0, 2, 0, 8, 0xaf, 0xde, 0, 1 ]),
+            Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_decode_westwood_ima() {
+        let mut states = [ AdpcmImaState::new() ];
+        let mut out = [0i16; 4];
+        assert!(decode_westwood_ima(&[ 0x06, 0x00 ], &mut states, &mut out).is_ok());
+        assert_eq!(out, [ 10, 11, 12, 13 ]);
+
+        // stereo: bytes of the channels are interleaved
+        let mut states = [ AdpcmImaState::new(), AdpcmImaState::new() ];
+        assert!(decode_westwood_ima(&[ 0x06, 0x0e ], &mut states, &mut out).is_ok());
+        assert_eq!(out, [ 10, -10, 11, -9 ]);
+
+        // errors
+        assert!(matches!(decode_westwood_ima(&[ 0x06 ], &mut states, &mut out[..2]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_westwood_ima(&[ 0x06, 0x00 ], &mut states, &mut out[..2]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_westwood_ima(&[ 0x06, 0x00 ], &mut [], &mut out),
+            Err(Error::InvalidChannels)));
+    }
+
+    #[test]
+    fn test_decode_westwood_snd1() {
+        // 2-bit deltas, 4-bit deltas, 5-bit delta, raw samples and a run
+        let buf = [ 0x01, 0xff, 0x00, 0x40, 0xf0, 0xbf, 0x81, 10, 20, 0xc2 ];
+        let mut out = [0u8; 16];
+        assert!(decode_westwood_snd1(&buf, &mut out).is_ok());
+        assert_eq!(out, [ 129, 130, 131, 132, 130, 128, 126, 124, 115, 123, 122, 10, 20,
+            20, 20, 20 ]);
+
+        // deltas are clamped
+        let mut out = [0u8; 3];
+        assert!(decode_westwood_snd1(&[ 0x80, 250, 0xa8, 0xb8 ], &mut out).is_ok());
+        assert_eq!(out, [ 250, 255, 247 ]);
+
+        // uncompressed chunk
+        let mut out = [0u8; 3];
+        assert!(decode_westwood_snd1(&[ 1, 2, 3 ], &mut out).is_ok());
+        assert_eq!(out, [ 1, 2, 3 ]);
+
+        // errors
+        let mut out = [0u8; 16];
+        assert!(matches!(decode_westwood_snd1(&buf[..9], &mut out), Err(Error::InvalidData)));
+        assert!(matches!(decode_westwood_snd1(&buf, &mut out[..15]), Err(Error::InvalidData)));
+        assert_eq!(out, [0; 16]);
+    }
+}
@@ -0,0 +1,395 @@
+
+use crate::Error;
+use crate::sample_history::{impl_sample_history, SampleHistory};
+
+// CD-XA ADPCM is used in CD-ROM XA, CD-i and PlayStation CD audio streams.
+// see https://problemkaputt.de/psx-spx.htm#cdromxaaudioadpcmcompression
+
+// prediction filter coefficients in 1/64 units, the last filter is only used by the SPU
+pub(crate) const XA_FILTER_K0: &[i32; 5] = &[ 0, 60, 115, 98, 122 ];
+pub(crate) const XA_FILTER_K1: &[i32; 5] = &[ 0, 0, -52, -55, -60 ];
+
+/// Size of a CD-XA ADPCM sound group in bytes.
+pub const XA_SOUND_GROUP_SIZE: usize = 128;
+
+/// Number of sound groups in a Mode 2 Form 2 sector.
+pub const XA_SOUND_GROUPS_PER_SECTOR: usize = 18;
+
+/// State values for the CD-XA ADPCM decoder.
+///
+/// The values should be initialized to zeros at the start of the stream.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct XaAdpcmState {
+    /// The previous decoded sample.
+    pub previous: i16,
+    /// The sample before the previous decoded sample.
+    pub previous2: i16,
+}
+
+impl XaAdpcmState {
+    /// Creates a new XaAdpcmState with zero values.
+    pub fn new() -> XaAdpcmState {
+        XaAdpcmState { previous: 0, previous2: 0 }
+    }
+
+    // decodes a sample, which has been shifted to the top bits of `value`
+    fn decode(&mut self, value: i32, header: u8) -> i16 {
+        let filter = usize::from((header >> 4) & 3);
+        // shift values 13..=15 are reserved and work like 9
+        let shift = match header & 0x0f {
+            s @ 0..=12 => s,
+            _ => 9,
+        };
+        let prediction = (i32::from(self.previous) * XA_FILTER_K0[filter] +
+            i32::from(self.previous2) * XA_FILTER_K1[filter] + 32) >> 6;
+        #[allow(clippy::cast_possible_truncation)] // value is clamped so truncation never happens
+        let sample = ((value >> shift) + prediction).clamp(-32768, 32767) as i16;
+        self.push(sample);
+        sample
+    }
+}
+
+impl_sample_history!(XaAdpcmState);
+
+/// Coding information of CD-XA audio sectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XaCodingInfo {
+    /// `true` for 2 channel (stereo) audio, `false` for 1 channel (mono) audio.
+    pub is_stereo: bool,
+    /// Sample rate: 37800 or 18900 Hz.
+    pub sample_rate: u32,
+    /// Number of bits per encoded sample: 4 or 8.
+    pub bits_per_sample: u8,
+    /// `true` if emphasis is enabled.
+    pub emphasis: bool,
+}
+
+impl XaCodingInfo {
+    /// Parses the coding information byte of a sector subheader.
+    ///
+    /// An error is returned if the byte contains reserved values.
+    pub fn from_byte(coding_info: u8) -> Result<XaCodingInfo, Error> {
+        let is_stereo = match coding_info & 0x03 {
+            0 => false,
+            1 => true,
+            _ => return Err(Error::InvalidData),
+        };
+        let sample_rate = match (coding_info >> 2) & 0x03 {
+            0 => 37800,
+            1 => 18900,
+            _ => return Err(Error::InvalidData),
+        };
+        let bits_per_sample = match (coding_info >> 4) & 0x03 {
+            0 => 4,
+            1 => 8,
+            _ => return Err(Error::InvalidData),
+        };
+        Ok(XaCodingInfo {
+            is_stereo,
+            sample_rate,
+            bits_per_sample,
+            emphasis: (coding_info & 0x40) != 0,
+        })
+    }
+
+    /// Returns the number of channels (1 or 2).
+    pub fn channels(&self) -> usize {
+        if self.is_stereo {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Returns the number of samples in a sound group, counting all channels.
+    pub fn samples_per_sound_group(&self) -> usize {
+        if self.bits_per_sample == 8 {
+            112
+        } else {
+            224
+        }
+    }
+
+    /// Returns the number of samples in a sector, counting all channels.
+    pub fn samples_per_sector(&self) -> usize {
+        self.samples_per_sound_group() * XA_SOUND_GROUPS_PER_SECTOR
+    }
+}
+
+/// Subheader of a CD-ROM XA Mode 2 sector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XaSubheader {
+    /// File number. Interleaved streams have different file numbers.
+    pub file: u8,
+    /// Channel number. Interleaved streams of the same file have different channel numbers.
+    pub channel: u8,
+    /// Submode flags.
+    pub submode: u8,
+    /// Coding information byte, see [`XaCodingInfo::from_byte()`].
+    pub coding_info: u8,
+}
+
+impl XaSubheader {
+    /// Returns `true` if the sector contains audio.
+    pub fn is_audio(&self) -> bool {
+        (self.submode & 0x04) != 0
+    }
+
+    /// Returns `true` if the sector is a Form 2 sector.
+    pub fn is_form2(&self) -> bool {
+        (self.submode & 0x20) != 0
+    }
+
+    /// Returns `true` if the sector is the last sector of the file.
+    pub fn is_end_of_file(&self) -> bool {
+        (self.submode & 0x80) != 0
+    }
+
+    /// Returns the parsed coding information.
+    ///
+    /// An error is returned if the coding information byte contains reserved values.
+    pub fn coding(&self) -> Result<XaCodingInfo, Error> {
+        XaCodingInfo::from_byte(self.coding_info)
+    }
+}
+
+/// Parses a CD-ROM XA Mode 2 sector and returns its subheader and the 2304 bytes of
+/// sound groups.
+///
+/// `sector` can be a raw 2352 byte sector, which starts with the sync pattern and header, or
+/// a 2336 byte sector, which starts with the subheader (as in .xa files and "RIFF CDXA" files).
+/// The first copy of the subheader is used.
+///
+/// The sound groups are returned even if the sector isn't an audio sector. Use
+/// [`XaSubheader::is_audio()`], [`XaSubheader::file`] and [`XaSubheader::channel`] to select
+/// the sectors of an interleaved stream.
+///
+/// An error is returned if the `sector` length isn't 2352 or 2336 or if a raw sector doesn't
+/// have the sync pattern or isn't a Mode 2 sector.
+pub fn parse_xa_sector(sector: &[u8]) -> Result<(XaSubheader, &[u8]), Error> {
+    let subheader_pos = match sector.len() {
+        2352 => {
+            if sector[0] != 0 || sector[1..11].iter().any(|b| *b != 0xff) || sector[11] != 0 {
+                return Err(Error::InvalidData);
+            }
+            if sector[15] != 2 {
+                return Err(Error::InvalidData);
+            }
+            16
+        },
+        2336 => 0,
+        _ => return Err(Error::InvalidBufferSize),
+    };
+    let subheader = XaSubheader {
+        file: sector[subheader_pos],
+        channel: sector[subheader_pos+1],
+        submode: sector[subheader_pos+2],
+        coding_info: sector[subheader_pos+3],
+    };
+    let data_pos = subheader_pos + 8;
+    let data_len = XA_SOUND_GROUP_SIZE * XA_SOUND_GROUPS_PER_SECTOR;
+    Ok((subheader, &sector[data_pos..data_pos + data_len]))
+}
+
+/// Decodes a CD-XA ADPCM sound group to 16-bit signed integer samples.
+///
+/// `buf` should contain 16 bytes of sound parameters and 112 bytes of encoded samples.
+/// `coding` specifies the number of channels and bits per sample.
+///
+/// `states` must contain channel number of `XaAdpcmState` items (1 or 2). The state objects
+/// should be initialized to zero for the first call and subsequent calls should pass in
+/// the state values from the previous call.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// [`coding.samples_per_sound_group()`](XaCodingInfo::samples_per_sound_group): 224 for
+/// 4-bit audio and 112 for 8-bit audio. Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if `states` has an invalid number of state objects or
+/// if the `out_samples` length isn't correct.
+/// If an error is returned, `states` and `out_samples` are left unmodified.
+pub fn decode_xa_adpcm_sound_group(buf: &[u8; 128], coding: &XaCodingInfo,
+    states: &mut [XaAdpcmState], out_samples: &mut [i16]) -> Result<(), Error> {
+
+    let channels = coding.channels();
+    if states.len() != channels {
+        return Err(Error::InvalidChannels);
+    }
+    if out_samples.len() != coding.samples_per_sound_group() {
+        return Err(Error::InvalidBufferSize);
+    }
+    // 8 sound units of 4-bit samples or 4 sound units of 8-bit samples, each has 28 samples
+    let unit_count = if coding.bits_per_sample == 8 { 4 } else { 8 };
+    for unit in 0..unit_count {
+        let header = buf[4 + unit];
+        let ch = unit % channels;
+        let out_start = (unit / channels) * 28 * channels + ch;
+        for (i, out) in out_samples[out_start..].iter_mut().step_by(channels).take(28)
+            .enumerate() {
+            let value = if coding.bits_per_sample == 8 {
+                i32::from(i8::from_le_bytes([ buf[16 + i*4 + unit] ])) << 8
+            } else {
+                let b = buf[16 + i*4 + unit/2];
+                let nibble = if unit % 2 == 0 { b << 4 } else { b & 0xf0 };
+                i32::from(i8::from_le_bytes([ nibble ])) << 8
+            };
+            *out = states[ch].decode(value, header);
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the sound groups of a CD-ROM XA Mode 2 Form 2 audio sector to 16-bit signed integer
+/// samples.
+///
+/// `sector` should be a raw 2352 byte sector or a 2336 byte sector, see
+/// [`parse_xa_sector()`]. The coding information is read from the sector subheader.
+///
+/// `states` must contain channel number of `XaAdpcmState` items (1 or 2). The state objects
+/// should be initialized to zero for the first call and subsequent calls should pass in
+/// the state values from the previous call of the same stream.
+///
+/// This function outputs decoded samples to `out_samples`. The `out_samples` length must be
+/// [`samples_per_sector()`](XaCodingInfo::samples_per_sector) of the sector's coding
+/// information: 4032 for 4-bit audio and 2016 for 8-bit audio.
+/// Samples are interleaved for 2 channel audio.
+///
+/// An error is returned if the sector is invalid or isn't an audio sector,
+/// if `states` has an invalid number of state objects or
+/// if the `out_samples` length isn't correct.
+/// If an error is returned, `states` and `out_samples` are left unmodified.
+pub fn decode_xa_sector(sector: &[u8], states: &mut [XaAdpcmState], out_samples: &mut [i16])
+    -> Result<(), Error> {
+
+    let (subheader, data) = parse_xa_sector(sector)?;
+    if !subheader.is_audio() {
+        return Err(Error::InvalidData);
+    }
+    let coding = subheader.coding()?;
+    if states.len() != coding.channels() {
+        return Err(Error::InvalidChannels);
+    }
+    if out_samples.len() != coding.samples_per_sector() {
+        return Err(Error::InvalidBufferSize);
+    }
+    for (group, out) in data.chunks_exact(XA_SOUND_GROUP_SIZE)
+        .zip(out_samples.chunks_exact_mut(coding.samples_per_sound_group())) {
+        let Ok(group) = group.try_into() else {
+            return Err(Error::InvalidBufferSize);
+        };
+        decode_xa_adpcm_sound_group(group, &coding, states, out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CodecState;
+
+    #[test]
+    fn test_xa_coding_info() {
+        assert!(matches!(XaCodingInfo::from_byte(0x00), Ok(XaCodingInfo {
+            is_stereo: false, sample_rate: 37800, bits_per_sample: 4, emphasis: false })));
+        assert!(matches!(XaCodingInfo::from_byte(0x55), Ok(XaCodingInfo {
+            is_stereo: true, sample_rate: 18900, bits_per_sample: 8, emphasis: true })));
+        assert!(matches!(XaCodingInfo::from_byte(0x02), Err(Error::InvalidData)));
+        assert!(matches!(XaCodingInfo::from_byte(0x08), Err(Error::InvalidData)));
+        assert!(matches!(XaCodingInfo::from_byte(0x20), Err(Error::InvalidData)));
+        assert!(matches!(XaCodingInfo::from_byte(0x01).map(|c| c.samples_per_sector()),
+            Ok(4032)));
+    }
+
+    #[test]
+    fn test_decode_xa_adpcm_sound_group() {
+        // mono 4-bit: unit 0 uses filter 0 and unit 1 uses filter 1
+        let mut buf = [0u8; 128];
+        buf[4] = 0x00;
+        buf[5] = 0x1c;
+        buf[16 + 27*4] = 0x01;
+        buf[16 + 26*4] = 0x0f;
+        let coding = XaCodingInfo { is_stereo: false, sample_rate: 37800, bits_per_sample: 4,
+            emphasis: false };
+        let mut states = [ XaAdpcmState::new() ];
+        let mut out = [0i16; 224];
+        assert!(decode_xa_adpcm_sound_group(&buf, &coding, &mut states, &mut out).is_ok());
+        assert_eq!(out[25..31], [ 0, -4096, 4096, 3840, 3600, 3375 ]);
+
+        // stereo 4-bit: the odd units are the right channel
+        let mut buf = [0u8; 128];
+        buf[16] = 0x71;
+        buf[4] = 0x02;
+        buf[5] = 0x01;
+        let coding = XaCodingInfo { is_stereo: true, ..coding };
+        let mut states = [ XaAdpcmState::new(), XaAdpcmState::new() ];
+        assert!(decode_xa_adpcm_sound_group(&buf, &coding, &mut states, &mut out).is_ok());
+        assert_eq!(out[..4], [ 1024, 14336, 0, 0 ]);
+
+        // mono 8-bit
+        let mut buf = [0u8; 128];
+        buf[16] = 0x80;
+        buf[17] = 0x7f;
+        buf[5] = 0x04;
+        let coding = XaCodingInfo { is_stereo: false, sample_rate: 18900, bits_per_sample: 8,
+            emphasis: false };
+        let mut states = [ XaAdpcmState::new() ];
+        assert!(decode_xa_adpcm_sound_group(&buf, &coding, &mut states, &mut out[..112]).is_ok());
+        assert_eq!(out[0], -32768);
+        assert_eq!(out[28], 2032);
+
+        // invalid arguments
+        assert!(matches!(decode_xa_adpcm_sound_group(&buf, &coding, &mut states, &mut out),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_xa_adpcm_sound_group(&buf, &coding, &mut [], &mut out[..112]),
+            Err(Error::InvalidChannels)));
+    }
+
+    #[test]
+    fn test_decode_xa_sector() {
+        let mut sector = [0u8; 2352];
+        sector[1..11].fill(0xff);
+        sector[15] = 2;
+        // file 1, channel 3, audio + form 2 + realtime, stereo 37800 Hz 4-bit
+        sector[16..24].copy_from_slice(&[ 1, 3, 0x64, 0x01, 1, 3, 0x64, 0x01 ]);
+        sector[24 + 16] = 0x01;
+        assert!(parse_xa_sector(&sector).is_ok());
+        let Ok((subheader, data)) = parse_xa_sector(&sector) else { return };
+        assert_eq!(subheader.file, 1);
+        assert_eq!(subheader.channel, 3);
+        assert!(subheader.is_audio() && subheader.is_form2() && !subheader.is_end_of_file());
+        assert_eq!(data.len(), 2304);
+
+        let mut states = [ XaAdpcmState::new(), XaAdpcmState::new() ];
+        let mut out = [0i16; 4032];
+        assert!(decode_xa_sector(&sector, &mut states, &mut out).is_ok());
+        assert_eq!(out[..2], [ 4096, 0 ]);
+        assert!(decode_xa_sector(&sector[16..], &mut states, &mut out).is_ok());
+
+        // errors
+        assert!(matches!(decode_xa_sector(&sector, &mut states[..1], &mut out),
+            Err(Error::InvalidChannels)));
+        assert!(matches!(decode_xa_sector(&sector, &mut states, &mut out[..2016]),
+            Err(Error::InvalidBufferSize)));
+        assert!(matches!(decode_xa_sector(&sector[..2000], &mut states, &mut out),
+            Err(Error::InvalidBufferSize)));
+        sector[18] = 0x08;
+        assert!(matches!(decode_xa_sector(&sector, &mut states, &mut out),
+            Err(Error::InvalidData)));
+        sector[0] = 1;
+        assert!(matches!(parse_xa_sector(&sector), Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_codec_state() {
+        let mut state = XaAdpcmState { previous: -1000, previous2: 2000 };
+        assert!(!state.is_default());
+        let mut buf = [0u8; XaAdpcmState::SERIALIZED_LEN];
+        assert!(state.to_bytes(&mut buf).is_ok());
+        assert_eq!(buf, [ 0x18, 0xfc, 0xd0, 0x07 ]);
+        assert!(matches!(XaAdpcmState::from_bytes(&buf), Ok(s) if s == state));
+        state.reset();
+        assert!(state.is_default());
+        assert_eq!(state, XaAdpcmState::new());
+        assert!(matches!(XaAdpcmState::from_bytes(&buf[1..]), Err(Error::InvalidBufferSize)));
+        assert!(matches!(state.to_bytes(&mut [0u8; 5]), Err(Error::InvalidBufferSize)));
+    }
+}
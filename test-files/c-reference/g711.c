@@ -0,0 +1,88 @@
+/*
+ * G.711 A-law and u-law reference implementation following the algorithm of
+ * g711.c in the ITU-T G.191 Software Tools Library. Used only for differential testing.
+ */
+
+unsigned char ref_linear2alaw(short linval)
+{
+    short ix;
+    short iexp;
+
+    /* 1's complement for negative values, 12 bits */
+    ix = linval < 0 ? (~linval) >> 4 : linval >> 4;
+    if (ix > 15) {
+        iexp = 1;
+        while (ix > 16 + 15) {
+            ix >>= 1;
+            iexp++;
+        }
+        ix -= 16;
+        ix += iexp << 4;
+    }
+    if (linval >= 0)
+        ix |= 0x0080;
+    return (unsigned char)(ix ^ 0x0055);
+}
+
+short ref_alaw2linear(unsigned char logval)
+{
+    short ix;
+    short iexp;
+    short mant;
+
+    ix = logval ^ 0x0055;
+    ix &= 0x007F;
+    iexp = ix >> 4;
+    mant = ix & 0x000F;
+    if (iexp > 0)
+        mant = mant + 16;
+    mant = (mant << 4) + 0x0008;
+    if (iexp > 1)
+        mant = mant << (iexp - 1);
+    return logval > 127 ? mant : -mant;
+}
+
+unsigned char ref_linear2ulaw(short linval)
+{
+    short absno;
+    short segno;
+    short low_nibble;
+    short high_nibble;
+    short i;
+    short logval;
+
+    /* 1's complement for negative values, 14 bits with the bias */
+    absno = linval < 0 ? ((~linval) >> 2) + 33 : (linval >> 2) + 33;
+    if (absno > 0x1FFF)
+        absno = 0x1FFF;
+    i = absno >> 6;
+    segno = 1;
+    while (i != 0) {
+        segno++;
+        i >>= 1;
+    }
+    high_nibble = 0x0008 - segno;
+    low_nibble = (absno >> segno) & 0x000F;
+    low_nibble = 0x000F - low_nibble;
+    logval = (high_nibble << 4) | low_nibble;
+    if (linval >= 0)
+        logval = logval | 0x0080;
+    return (unsigned char)logval;
+}
+
+short ref_ulaw2linear(unsigned char logval)
+{
+    short sign;
+    short mantissa;
+    short exponent;
+    short segment;
+    short step;
+
+    sign = logval < 0x0080 ? -1 : 1;
+    mantissa = ~logval;
+    exponent = (mantissa >> 4) & 0x0007;
+    segment = exponent + 1;
+    mantissa = mantissa & 0x000F;
+    step = 4 << segment;
+    return sign * ((0x0080 << exponent) + step * mantissa + step / 2 - 4 * 33);
+}
@@ -0,0 +1,519 @@
+/*
+ * G.726 ADPCM reference implementation following the algorithm of the public domain
+ * Sun Microsystems g72x.c, g721.c, g723_24.c and g723_40.c, combined to one coder which
+ * is selected by the codeword size. Sun's code doesn't have 16 kbit/s, which uses the same
+ * algorithm with the 2-bit tables of ITU-T G.726. Used only for differential testing.
+ */
+
+#include <stdlib.h>
+
+unsigned char ref_linear2alaw(short linval);
+short ref_alaw2linear(unsigned char logval);
+unsigned char ref_linear2ulaw(short linval);
+short ref_ulaw2linear(unsigned char logval);
+
+struct ref_g72x_state {
+    long yl;
+    short yu;
+    short dms;
+    short dml;
+    short ap;
+    short a[2];
+    short b[6];
+    short pk[2];
+    short dq[6];
+    short sr[2];
+    char td;
+};
+
+static short power2[15] = {
+    1, 2, 4, 8, 0x10, 0x20, 0x40, 0x80, 0x100, 0x200, 0x400, 0x800, 0x1000, 0x2000, 0x4000
+};
+
+static short qtab_16[1] = { 261 };
+static short dqlntab_16[4] = { 116, 365, 365, 116 };
+static short witab_16[4] = { -704, 14048, 14048, -704 };
+static short fitab_16[4] = { 0, 0xE00, 0xE00, 0 };
+
+static short qtab_723_24[3] = { 8, 218, 331 };
+static short dqlntab_24[8] = { -2048, 135, 273, 373, 373, 273, 135, -2048 };
+static short witab_24[8] = { -128, 960, 4384, 18624, 18624, 4384, 960, -128 };
+static short fitab_24[8] = { 0, 0x200, 0x400, 0xE00, 0xE00, 0x400, 0x200, 0 };
+
+static short qtab_721[7] = { -124, 80, 178, 246, 300, 349, 400 };
+static short dqlntab_32[16] = {
+    -2048, 4, 135, 213, 273, 323, 373, 425, 425, 373, 323, 273, 213, 135, 4, -2048
+};
+/* g721.c stores these divided by 32 and shifts them when calling update() */
+static short witab_32[16] = {
+    -12, 18, 41, 64, 112, 198, 355, 1122, 1122, 355, 198, 112, 64, 41, 18, -12
+};
+static short fitab_32[16] = {
+    0, 0, 0, 0x200, 0x200, 0x200, 0x600, 0xE00, 0xE00, 0x600, 0x200, 0x200, 0x200, 0, 0, 0
+};
+
+static short qtab_723_40[15] = {
+    -122, -16, 68, 139, 198, 250, 298, 339, 378, 413, 445, 475, 502, 528, 553
+};
+static short dqlntab_40[32] = {
+    -2048, -66, 28, 104, 169, 224, 274, 318, 358, 395, 429, 459, 488, 514, 539, 566,
+    566, 539, 514, 488, 459, 429, 395, 358, 318, 274, 224, 169, 104, 28, -66, -2048
+};
+static short witab_40[32] = {
+    448, 448, 768, 1248, 1280, 1312, 1856, 3200, 4512, 5728, 7008, 8960, 11456, 14080,
+    16928, 22272, 22272, 16928, 14080, 11456, 8960, 7008, 5728, 4512, 3200, 1856, 1312,
+    1280, 1248, 768, 448, 448
+};
+static short fitab_40[32] = {
+    0, 0, 0, 0, 0, 0x200, 0x200, 0x200, 0x200, 0x200, 0x400, 0x600, 0x800, 0xA00, 0xC00,
+    0xC00, 0xC00, 0xC00, 0xA00, 0x800, 0x600, 0x400, 0x200, 0x200, 0x200, 0x200, 0x200,
+    0, 0, 0, 0, 0
+};
+
+static int quan(int val, short *table, int size)
+{
+    int i;
+
+    for (i = 0; i < size; i++)
+        if (val < *table++)
+            break;
+    return i;
+}
+
+static int fmult(int an, int srn)
+{
+    short anmag, anexp, anmant;
+    short wanexp, wanmant;
+    short retval;
+
+    anmag = (an > 0) ? an : ((-an) & 0x1FFF);
+    anexp = quan(anmag, power2, 15) - 6;
+    anmant = (anmag == 0) ? 32 : (anexp >= 0) ? anmag >> anexp : anmag << -anexp;
+    wanexp = anexp + ((srn >> 6) & 0xF) - 13;
+
+    wanmant = (anmant * (srn & 077) + 0x30) >> 4;
+    retval = (wanexp >= 0) ? ((wanmant << wanexp) & 0x7FFF) : (wanmant >> -wanexp);
+
+    return ((an ^ srn) < 0) ? -retval : retval;
+}
+
+void ref_g72x_init_state(struct ref_g72x_state *state_ptr)
+{
+    int cnta;
+
+    state_ptr->yl = 34816;
+    state_ptr->yu = 544;
+    state_ptr->dms = 0;
+    state_ptr->dml = 0;
+    state_ptr->ap = 0;
+    for (cnta = 0; cnta < 2; cnta++) {
+        state_ptr->a[cnta] = 0;
+        state_ptr->pk[cnta] = 0;
+        state_ptr->sr[cnta] = 32;
+    }
+    for (cnta = 0; cnta < 6; cnta++) {
+        state_ptr->b[cnta] = 0;
+        state_ptr->dq[cnta] = 32;
+    }
+    state_ptr->td = 0;
+}
+
+static int predictor_zero(struct ref_g72x_state *state_ptr)
+{
+    int i;
+    int sezi;
+
+    sezi = fmult(state_ptr->b[0] >> 2, state_ptr->dq[0]);
+    for (i = 1; i < 6; i++)
+        sezi += fmult(state_ptr->b[i] >> 2, state_ptr->dq[i]);
+    return sezi;
+}
+
+static int predictor_pole(struct ref_g72x_state *state_ptr)
+{
+    return fmult(state_ptr->a[1] >> 2, state_ptr->sr[1]) +
+        fmult(state_ptr->a[0] >> 2, state_ptr->sr[0]);
+}
+
+static int step_size(struct ref_g72x_state *state_ptr)
+{
+    int y;
+    int dif;
+    int al;
+
+    if (state_ptr->ap >= 256)
+        return state_ptr->yu;
+    y = state_ptr->yl >> 6;
+    dif = state_ptr->yu - y;
+    al = state_ptr->ap >> 2;
+    if (dif > 0)
+        y += (dif * al) >> 6;
+    else if (dif < 0)
+        y += (dif * al + 0x3F) >> 6;
+    return y;
+}
+
+static int quantize(int d, int y, short *table, int size)
+{
+    short dqm;
+    short exp;
+    short mant;
+    short dl;
+    short dln;
+    int i;
+
+    dqm = abs(d);
+    exp = quan(dqm >> 1, power2, 15);
+    mant = ((dqm << 7) >> exp) & 0x7F;
+    dl = (exp << 7) + mant;
+    dln = dl - (y >> 2);
+    i = quan(dln, table, size);
+    if (d < 0)
+        return (size << 1) + 1 - i;
+    else if (i == 0 && size > 1)
+        /* 16 kbit/s has a zero codeword, the other rates don't */
+        return (size << 1) + 1;
+    else
+        return i;
+}
+
+static int reconstruct(int sign, int dqln, int y)
+{
+    short dql;
+    short dex;
+    short dqt;
+    short dq;
+
+    dql = dqln + (y >> 2);
+    if (dql < 0)
+        return sign ? -0x8000 : 0;
+    dex = (dql >> 7) & 15;
+    dqt = 128 + (dql & 127);
+    dq = (dqt << 7) >> (14 - dex);
+    return sign ? (dq - 0x8000) : dq;
+}
+
+static void update(int code_size, int y, int wi, int fi, int dq, int sr, int dqsez,
+    struct ref_g72x_state *state_ptr)
+{
+    int cnt;
+    short mag, exp;
+    short a2p = 0;
+    short a1ul;
+    short pks1;
+    short fa1;
+    char tr;
+    short ylint, thr2, dqthr;
+    short ylfrac, thr1;
+    short pk0;
+
+    pk0 = (dqsez < 0) ? 1 : 0;
+
+    mag = dq & 0x7FFF;
+    ylint = state_ptr->yl >> 15;
+    ylfrac = (state_ptr->yl >> 10) & 0x1F;
+    thr1 = (32 + ylfrac) << ylint;
+    thr2 = (ylint > 9) ? 31 << 10 : thr1;
+    dqthr = (thr2 + (thr2 >> 1)) >> 1;
+    if (state_ptr->td == 0)
+        tr = 0;
+    else if (mag <= dqthr)
+        tr = 0;
+    else
+        tr = 1;
+
+    state_ptr->yu = y + ((wi - y) >> 5);
+    if (state_ptr->yu < 544)
+        state_ptr->yu = 544;
+    else if (state_ptr->yu > 5120)
+        state_ptr->yu = 5120;
+
+    state_ptr->yl += state_ptr->yu + ((-state_ptr->yl) >> 6);
+
+    if (tr == 1) {
+        state_ptr->a[0] = 0;
+        state_ptr->a[1] = 0;
+        for (cnt = 0; cnt < 6; cnt++)
+            state_ptr->b[cnt] = 0;
+    } else {
+        pks1 = pk0 ^ state_ptr->pk[0];
+
+        a2p = state_ptr->a[1] - (state_ptr->a[1] >> 7);
+        if (dqsez != 0) {
+            fa1 = (pks1) ? state_ptr->a[0] : -state_ptr->a[0];
+            if (fa1 < -8191)
+                a2p -= 0x100;
+            else if (fa1 > 8191)
+                a2p += 0xFF;
+            else
+                a2p += fa1 >> 5;
+
+            if (pk0 ^ state_ptr->pk[1]) {
+                if (a2p <= -12160)
+                    a2p = -12288;
+                else if (a2p >= 12416)
+                    a2p = 12288;
+                else
+                    a2p -= 0x80;
+            } else if (a2p <= -12416) {
+                a2p = -12288;
+            } else if (a2p >= 12160) {
+                a2p = 12288;
+            } else {
+                a2p += 0x80;
+            }
+        }
+
+        state_ptr->a[1] = a2p;
+
+        state_ptr->a[0] -= state_ptr->a[0] >> 8;
+        if (dqsez != 0) {
+            if (pks1 == 0)
+                state_ptr->a[0] += 192;
+            else
+                state_ptr->a[0] -= 192;
+        }
+
+        a1ul = 15360 - a2p;
+        if (state_ptr->a[0] < -a1ul)
+            state_ptr->a[0] = -a1ul;
+        else if (state_ptr->a[0] > a1ul)
+            state_ptr->a[0] = a1ul;
+
+        for (cnt = 0; cnt < 6; cnt++) {
+            if (code_size == 5)
+                state_ptr->b[cnt] -= state_ptr->b[cnt] >> 9;
+            else
+                state_ptr->b[cnt] -= state_ptr->b[cnt] >> 8;
+            if (dq & 0x7FFF) {
+                if ((dq ^ state_ptr->dq[cnt]) >= 0)
+                    state_ptr->b[cnt] += 128;
+                else
+                    state_ptr->b[cnt] -= 128;
+            }
+        }
+    }
+
+    for (cnt = 5; cnt > 0; cnt--)
+        state_ptr->dq[cnt] = state_ptr->dq[cnt - 1];
+    if (mag == 0) {
+        state_ptr->dq[0] = (dq >= 0) ? 0x20 : 0xFC20;
+    } else {
+        exp = quan(mag, power2, 15);
+        state_ptr->dq[0] = (dq >= 0) ?
+            (exp << 6) + ((mag << 6) >> exp) :
+            (exp << 6) + ((mag << 6) >> exp) - 0x400;
+    }
+
+    state_ptr->sr[1] = state_ptr->sr[0];
+    if (sr == 0) {
+        state_ptr->sr[0] = 0x20;
+    } else if (sr > 0) {
+        exp = quan(sr, power2, 15);
+        state_ptr->sr[0] = (exp << 6) + ((sr << 6) >> exp);
+    } else if (sr > -32768) {
+        mag = -sr;
+        exp = quan(mag, power2, 15);
+        state_ptr->sr[0] = (exp << 6) + ((mag << 6) >> exp) - 0x400;
+    } else {
+        state_ptr->sr[0] = 0xFC20;
+    }
+
+    state_ptr->pk[1] = state_ptr->pk[0];
+    state_ptr->pk[0] = pk0;
+
+    if (tr == 1)
+        state_ptr->td = 0;
+    else if (a2p < -11776)
+        state_ptr->td = 1;
+    else
+        state_ptr->td = 0;
+
+    state_ptr->dms += (fi - state_ptr->dms) >> 5;
+    state_ptr->dml += (((fi << 2) - state_ptr->dml) >> 7);
+
+    if (tr == 1)
+        state_ptr->ap = 256;
+    else if (y < 1536)
+        state_ptr->ap += (0x200 - state_ptr->ap) >> 4;
+    else if (state_ptr->td == 1)
+        state_ptr->ap += (0x200 - state_ptr->ap) >> 4;
+    else if (abs((state_ptr->dms << 2) - state_ptr->dml) >= (state_ptr->dml >> 3))
+        state_ptr->ap += (0x200 - state_ptr->ap) >> 4;
+    else
+        state_ptr->ap += (-state_ptr->ap) >> 4;
+}
+
+struct tables {
+    short *qtab;
+    int qsize;
+    short *dqlntab;
+    short *witab;
+    int wishift;
+    short *fitab;
+};
+
+static struct tables get_tables(int code_size)
+{
+    struct tables t;
+
+    switch (code_size) {
+    case 2:
+        t.qtab = qtab_16; t.qsize = 1; t.dqlntab = dqlntab_16; t.witab = witab_16;
+        t.wishift = 0; t.fitab = fitab_16;
+        break;
+    case 3:
+        t.qtab = qtab_723_24; t.qsize = 3; t.dqlntab = dqlntab_24; t.witab = witab_24;
+        t.wishift = 0; t.fitab = fitab_24;
+        break;
+    case 5:
+        t.qtab = qtab_723_40; t.qsize = 15; t.dqlntab = dqlntab_40; t.witab = witab_40;
+        t.wishift = 0; t.fitab = fitab_40;
+        break;
+    default:
+        t.qtab = qtab_721; t.qsize = 7; t.dqlntab = dqlntab_32; t.witab = witab_32;
+        t.wishift = 5; t.fitab = fitab_32;
+        break;
+    }
+    return t;
+}
+
+int ref_g72x_encode(short sample, int code_size, struct ref_g72x_state *state_ptr)
+{
+    struct tables t = get_tables(code_size);
+    short sezi, se, sez;
+    short d;
+    short sr;
+    short y;
+    short dqsez;
+    short dq, i;
+    int sl;
+
+    sl = sample >> 2;
+
+    sezi = predictor_zero(state_ptr);
+    sez = sezi >> 1;
+    se = (sezi + predictor_pole(state_ptr)) >> 1;
+
+    d = sl - se;
+
+    y = step_size(state_ptr);
+    i = quantize(d, y, t.qtab, t.qsize);
+
+    dq = reconstruct(i & (1 << (code_size - 1)), t.dqlntab[i], y);
+
+    sr = (dq < 0) ? se - (dq & 0x3FFF) : se + dq;
+
+    dqsez = sr + sez - se;
+
+    update(code_size, y, t.witab[i] << t.wishift, t.fitab[i], dq, sr, dqsez, state_ptr);
+
+    return i;
+}
+
+/* Sun's linear2alaw() and linear2ulaw() take an int and saturate large values */
+static short saturate(int val)
+{
+    return val > 32767 ? 32767 : val < -32768 ? -32768 : val;
+}
+
+static int tandem_adjust_alaw(int sr, int se, int y, int i, int sign, short *qtab, int size)
+{
+    unsigned char sp;
+    short dx;
+    char id;
+    int sd;
+    int im;
+    int imx;
+
+    if (sr <= -32768)
+        sr = -1;
+    sp = ref_linear2alaw(saturate((sr >> 1) << 3));
+    dx = (ref_alaw2linear(sp) >> 2) - se;
+    id = quantize(dx, y, qtab, size);
+
+    if (id == i)
+        return sp;
+    im = i ^ sign;
+    imx = id ^ sign;
+    if (imx > im) {
+        if (sp & 0x80)
+            sd = (sp == 0xD5) ? 0x55 : ((sp ^ 0x55) - 1) ^ 0x55;
+        else
+            sd = (sp == 0x2A) ? 0x2A : ((sp ^ 0x55) + 1) ^ 0x55;
+    } else {
+        if (sp & 0x80)
+            sd = (sp == 0xAA) ? 0xAA : ((sp ^ 0x55) + 1) ^ 0x55;
+        else
+            sd = (sp == 0x55) ? 0xD5 : ((sp ^ 0x55) - 1) ^ 0x55;
+    }
+    return sd;
+}
+
+static int tandem_adjust_ulaw(int sr, int se, int y, int i, int sign, short *qtab, int size)
+{
+    unsigned char sp;
+    short dx;
+    char id;
+    int sd;
+    int im;
+    int imx;
+
+    if (sr <= -32768)
+        sr = 0;
+    sp = ref_linear2ulaw(saturate(sr << 2));
+    dx = (ref_ulaw2linear(sp) >> 2) - se;
+    id = quantize(dx, y, qtab, size);
+    if (id == i)
+        return sp;
+    im = i ^ sign;
+    imx = id ^ sign;
+    if (imx > im) {
+        if (sp & 0x80)
+            sd = (sp == 0xFF) ? 0x7E : sp + 1;
+        else
+            sd = (sp == 0) ? 0 : sp - 1;
+    } else {
+        if (sp & 0x80)
+            sd = (sp == 0x80) ? 0x80 : sp - 1;
+        else
+            sd = (sp == 0x7F) ? 0xFE : sp + 1;
+    }
+    return sd;
+}
+
+/* out_coding is 0 for linear, 1 for A-law and 2 for u-law */
+int ref_g72x_decode(int i, int code_size, int out_coding, struct ref_g72x_state *state_ptr)
+{
+    struct tables t = get_tables(code_size);
+    short sezi, sei, sez, se;
+    short y;
+    short sr;
+    short dq;
+    short dqsez;
+
+    i &= (1 << code_size) - 1;
+    sezi = predictor_zero(state_ptr);
+    sez = sezi >> 1;
+    sei = sezi + predictor_pole(state_ptr);
+    se = sei >> 1;
+
+    y = step_size(state_ptr);
+
+    dq = reconstruct(i & (1 << (code_size - 1)), t.dqlntab[i], y);
+
+    sr = (dq < 0) ? (se - (dq & 0x3FFF)) : se + dq;
+
+    dqsez = sr - se + sez;
+
+    update(code_size, y, t.witab[i] << t.wishift, t.fitab[i], dq, sr, dqsez, state_ptr);
+
+    switch (out_coding) {
+    case 1:
+        return tandem_adjust_alaw(sr, se, y, i, 1 << (code_size - 1), t.qtab, t.qsize);
+    case 2:
+        return tandem_adjust_ulaw(sr, se, y, i, 1 << (code_size - 1), t.qtab, t.qsize);
+    default:
+        return sr << 2;
+    }
+}